@@ -0,0 +1,483 @@
+//! makebeliv のCLAPエフェクトプラグイン
+//!
+//! CLIと同じ `ChunkConverter`（チャンク変換 + ローカルDSP）を使い、
+//! DAW上でトラックに変換と揺らぎを適用します。
+//! 変換はワーカースレッドで行い、2チャンク分の遅延をホストに報告します。
+//!
+//! 設定は環境変数で行います：
+//! - `MAKEBELIV_API_URL`（既定: http://localhost:8000）
+//! - `MAKEBELIV_MODEL`（既定: default）
+//! - `MAKEBELIV_PITCH`（半音、既定: 0）
+//! - `MAKEBELIV_CHUNK_MS`（既定: 200）
+//! - `MAKEBELIV_CONFIG`（DSPプラグインを宣言した makebeliv.toml のパス）
+
+use clap_sys::entry::clap_plugin_entry;
+use clap_sys::ext::audio_ports::{
+    clap_audio_port_info, clap_plugin_audio_ports, CLAP_AUDIO_PORT_IS_MAIN, CLAP_EXT_AUDIO_PORTS,
+    CLAP_PORT_STEREO,
+};
+use clap_sys::ext::latency::{clap_plugin_latency, CLAP_EXT_LATENCY};
+use clap_sys::factory::plugin_factory::{clap_plugin_factory, CLAP_PLUGIN_FACTORY_ID};
+use clap_sys::host::clap_host;
+use clap_sys::plugin::{clap_plugin, clap_plugin_descriptor};
+use clap_sys::plugin_features::{
+    CLAP_PLUGIN_FEATURE_AUDIO_EFFECT, CLAP_PLUGIN_FEATURE_PITCH_SHIFTER,
+};
+use clap_sys::process::{clap_process, clap_process_status, CLAP_PROCESS_CONTINUE};
+use clap_sys::version::CLAP_VERSION;
+use std::ffi::{c_char, c_void, CStr};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+use tracing::warn;
+
+use makebeliv::audio::{remap_channels, AudioBuffer};
+use makebeliv::client::VoiceConversionClient;
+use makebeliv::config::Config;
+use makebeliv::converter::{ChunkConverter, PipelineConfig, DEFAULT_CHUNK_MS};
+use makebeliv::dsp::DspChain;
+
+const PLUGIN_ID: &CStr = c"io.github.kako-jun.makebeliv";
+
+/// プラグインが扱うチャンネル数（ステレオ入出力）
+const CHANNELS: usize = 2;
+
+/// 入出力バッファに保持する最大時間（秒）
+const BUFFER_SECONDS: usize = 4;
+
+struct Features([*const c_char; 3]);
+
+// 静的な文字列へのポインタのみを保持する
+unsafe impl Sync for Features {}
+
+static FEATURES: Features = Features([
+    CLAP_PLUGIN_FEATURE_AUDIO_EFFECT.as_ptr(),
+    CLAP_PLUGIN_FEATURE_PITCH_SHIFTER.as_ptr(),
+    std::ptr::null(),
+]);
+
+static DESCRIPTOR: clap_plugin_descriptor = clap_plugin_descriptor {
+    clap_version: CLAP_VERSION,
+    id: PLUGIN_ID.as_ptr(),
+    name: c"makebeliv".as_ptr(),
+    vendor: c"makebeliv contributors".as_ptr(),
+    url: c"https://github.com/kako-jun/makebeliv".as_ptr(),
+    manual_url: c"".as_ptr(),
+    support_url: c"".as_ptr(),
+    version: c"0.1.0".as_ptr(),
+    description: c"Voice conversion with natural fluctuation".as_ptr(),
+    features: FEATURES.0.as_ptr(),
+};
+
+/// 環境変数から読み込むプラグイン設定
+struct Settings {
+    api_url: String,
+    model: String,
+    pitch_shift: i32,
+    chunk_ms: u32,
+    config_path: Option<PathBuf>,
+}
+
+impl Settings {
+    fn from_env() -> Self {
+        let var = |name: &str| std::env::var(name).ok();
+        Self {
+            api_url: var("MAKEBELIV_API_URL").unwrap_or_else(|| "http://localhost:8000".into()),
+            model: var("MAKEBELIV_MODEL").unwrap_or_else(|| "default".into()),
+            pitch_shift: var("MAKEBELIV_PITCH")
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(0),
+            chunk_ms: var("MAKEBELIV_CHUNK_MS")
+                .and_then(|v| v.parse().ok())
+                .filter(|&ms| ms > 0)
+                .unwrap_or(DEFAULT_CHUNK_MS),
+            config_path: var("MAKEBELIV_CONFIG").map(PathBuf::from),
+        }
+    }
+
+    fn dsp_chain(&self) -> DspChain {
+        let config = match &self.config_path {
+            Some(path) => Config::load_from(path),
+            None => Config::load(),
+        };
+
+        match config.and_then(|c| DspChain::from_config(&c.dsp)) {
+            Ok(chain) => chain,
+            Err(e) => {
+                warn!("DSPチェーンを構築できません（無効化）: {}", e);
+                DspChain::new()
+            }
+        }
+    }
+}
+
+/// activate〜deactivate間の処理状態
+struct Engine {
+    input: AudioBuffer,
+    output: AudioBuffer,
+    latency_frames: u32,
+    running: Arc<AtomicBool>,
+    worker: Option<JoinHandle<()>>,
+    scratch: Vec<f32>,
+}
+
+impl Engine {
+    fn start(
+        settings: &Settings,
+        client: VoiceConversionClient,
+        sample_rate: u32,
+        max_frames: usize,
+    ) -> Self {
+        let capacity = sample_rate as usize * CHANNELS * BUFFER_SECONDS;
+        let input = AudioBuffer::new(capacity);
+        let output = AudioBuffer::new(capacity);
+
+        let chunk_frames = (sample_rate as u64 * settings.chunk_ms as u64 / 1000) as usize;
+        // チャンクの蓄積 + 変換時間の分だけ遅らせる
+        let latency_frames = chunk_frames * 2;
+        output.push(&vec![0.0; latency_frames * CHANNELS]);
+
+        let converter = ChunkConverter::new(
+            client,
+            PipelineConfig {
+                model: settings.model.clone(),
+                pitch_shift: settings.pitch_shift,
+                chunk_ms: settings.chunk_ms,
+                session_id: format!("clap-{}", std::process::id()),
+                transport: Default::default(),
+                codec: Default::default(),
+                engine: Default::default(),
+            },
+            settings.dsp_chain(),
+        );
+
+        let running = Arc::new(AtomicBool::new(true));
+        let worker = {
+            let (input, output, running) = (input.clone(), output.clone(), running.clone());
+            let poll = Duration::from_millis((settings.chunk_ms as u64 / 4).max(1));
+            std::thread::spawn(move || {
+                run_worker(
+                    converter,
+                    input,
+                    output,
+                    running,
+                    sample_rate,
+                    chunk_frames * CHANNELS,
+                    poll,
+                )
+            })
+        };
+
+        Self {
+            input,
+            output,
+            latency_frames: latency_frames as u32,
+            running,
+            worker: Some(worker),
+            scratch: Vec::with_capacity(max_frames * CHANNELS),
+        }
+    }
+}
+
+impl Drop for Engine {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::Release);
+        if let Some(worker) = self.worker.take() {
+            worker.join().ok();
+        }
+    }
+}
+
+/// ワーカースレッド：入力バッファからチャンクを取り出して変換する
+fn run_worker(
+    mut converter: ChunkConverter,
+    input: AudioBuffer,
+    output: AudioBuffer,
+    running: Arc<AtomicBool>,
+    sample_rate: u32,
+    chunk_len: usize,
+    poll: Duration,
+) {
+    let runtime = match tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+    {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            warn!("ランタイムを作成できません: {}", e);
+            return;
+        }
+    };
+
+    runtime.block_on(async {
+        while running.load(Ordering::Acquire) {
+            if input.len() < chunk_len {
+                tokio::time::sleep(poll).await;
+                continue;
+            }
+
+            let chunk = input.take(chunk_len);
+            match converter
+                .convert(&chunk, sample_rate, CHANNELS as u16)
+                .await
+            {
+                Ok(converted) => {
+                    output.push(&remap_channels(
+                        &converted.samples,
+                        converted.channels,
+                        CHANNELS as u16,
+                    ));
+                }
+                Err(e) => {
+                    // タイミングを保つため無音で埋める
+                    warn!("チャンク変換エラー: {}", e);
+                    output.push(&vec![0.0; chunk_len]);
+                }
+            }
+        }
+
+        converter.reset_session().await.ok();
+    });
+}
+
+/// clap_pluginの実体（plugin_dataが指す）
+struct Plugin {
+    clap: clap_plugin,
+    settings: Settings,
+    engine: Option<Engine>,
+}
+
+impl Plugin {
+    /// # Safety
+    /// `plugin` は `create_plugin` が返したポインタであること
+    unsafe fn from_ptr<'a>(plugin: *const clap_plugin) -> &'a mut Plugin {
+        &mut *((*plugin).plugin_data as *mut Plugin)
+    }
+}
+
+unsafe extern "C" fn plugin_init(_plugin: *const clap_plugin) -> bool {
+    true
+}
+
+unsafe extern "C" fn plugin_destroy(plugin: *const clap_plugin) {
+    drop(Box::from_raw((*plugin).plugin_data as *mut Plugin));
+}
+
+unsafe extern "C" fn plugin_activate(
+    plugin: *const clap_plugin,
+    sample_rate: f64,
+    _min_frames_count: u32,
+    max_frames_count: u32,
+) -> bool {
+    let plugin = Plugin::from_ptr(plugin);
+    let client = match VoiceConversionClient::new(plugin.settings.api_url.clone()) {
+        Ok(client) => client,
+        Err(e) => {
+            warn!("変換を開始できません: {:#}", e);
+            return false;
+        }
+    };
+    plugin.engine = Some(Engine::start(
+        &plugin.settings,
+        client,
+        sample_rate as u32,
+        max_frames_count as usize,
+    ));
+    true
+}
+
+unsafe extern "C" fn plugin_deactivate(plugin: *const clap_plugin) {
+    Plugin::from_ptr(plugin).engine = None;
+}
+
+unsafe extern "C" fn plugin_start_processing(_plugin: *const clap_plugin) -> bool {
+    true
+}
+
+unsafe extern "C" fn plugin_stop_processing(_plugin: *const clap_plugin) {}
+
+unsafe extern "C" fn plugin_reset(_plugin: *const clap_plugin) {}
+
+unsafe extern "C" fn plugin_process(
+    plugin: *const clap_plugin,
+    process: *const clap_process,
+) -> clap_process_status {
+    let plugin = Plugin::from_ptr(plugin);
+    let process = &*process;
+    let Some(engine) = plugin.engine.as_mut() else {
+        return CLAP_PROCESS_CONTINUE;
+    };
+    if process.audio_inputs_count == 0 || process.audio_outputs_count == 0 {
+        return CLAP_PROCESS_CONTINUE;
+    }
+
+    let frames = process.frames_count as usize;
+    let input = &*process.audio_inputs;
+    let output = &*process.audio_outputs;
+    let in_channels = input.channel_count as usize;
+    let out_channels = (output.channel_count as usize).min(CHANNELS);
+
+    // 入力をインターリーブしてバッファへ
+    engine.scratch.clear();
+    for frame in 0..frames {
+        for channel in 0..CHANNELS {
+            let sample = if in_channels == 0 {
+                0.0
+            } else {
+                *(*input.data32.add(channel.min(in_channels - 1))).add(frame)
+            };
+            engine.scratch.push(sample);
+        }
+    }
+    engine.input.push(&engine.scratch);
+
+    // 変換済みの音声を出力へ
+    engine.scratch.resize(frames * CHANNELS, 0.0);
+    engine.output.fill(&mut engine.scratch);
+    for channel in 0..out_channels {
+        let dst = *output.data32.add(channel);
+        for frame in 0..frames {
+            *dst.add(frame) = engine.scratch[frame * CHANNELS + channel];
+        }
+    }
+
+    CLAP_PROCESS_CONTINUE
+}
+
+unsafe extern "C" fn plugin_get_extension(
+    _plugin: *const clap_plugin,
+    id: *const c_char,
+) -> *const c_void {
+    let id = CStr::from_ptr(id);
+    if id == CLAP_EXT_AUDIO_PORTS {
+        &AUDIO_PORTS as *const _ as *const c_void
+    } else if id == CLAP_EXT_LATENCY {
+        &LATENCY as *const _ as *const c_void
+    } else {
+        std::ptr::null()
+    }
+}
+
+unsafe extern "C" fn plugin_on_main_thread(_plugin: *const clap_plugin) {}
+
+static AUDIO_PORTS: clap_plugin_audio_ports = clap_plugin_audio_ports {
+    count: Some(audio_ports_count),
+    get: Some(audio_ports_get),
+};
+
+unsafe extern "C" fn audio_ports_count(_plugin: *const clap_plugin, _is_input: bool) -> u32 {
+    1
+}
+
+unsafe extern "C" fn audio_ports_get(
+    _plugin: *const clap_plugin,
+    index: u32,
+    _is_input: bool,
+    info: *mut clap_audio_port_info,
+) -> bool {
+    if index != 0 {
+        return false;
+    }
+
+    let info = &mut *info;
+    info.id = 0;
+    info.name = [0; clap_sys::string_sizes::CLAP_NAME_SIZE];
+    for (dst, src) in info.name.iter_mut().zip(b"main") {
+        *dst = *src as c_char;
+    }
+    info.flags = CLAP_AUDIO_PORT_IS_MAIN;
+    info.channel_count = CHANNELS as u32;
+    info.port_type = CLAP_PORT_STEREO.as_ptr();
+    info.in_place_pair = 0;
+    true
+}
+
+static LATENCY: clap_plugin_latency = clap_plugin_latency {
+    get: Some(latency_get),
+};
+
+unsafe extern "C" fn latency_get(plugin: *const clap_plugin) -> u32 {
+    Plugin::from_ptr(plugin)
+        .engine
+        .as_ref()
+        .map_or(0, |engine| engine.latency_frames)
+}
+
+static FACTORY: clap_plugin_factory = clap_plugin_factory {
+    get_plugin_count: Some(factory_get_plugin_count),
+    get_plugin_descriptor: Some(factory_get_plugin_descriptor),
+    create_plugin: Some(factory_create_plugin),
+};
+
+unsafe extern "C" fn factory_get_plugin_count(_factory: *const clap_plugin_factory) -> u32 {
+    1
+}
+
+unsafe extern "C" fn factory_get_plugin_descriptor(
+    _factory: *const clap_plugin_factory,
+    index: u32,
+) -> *const clap_plugin_descriptor {
+    if index == 0 {
+        &DESCRIPTOR
+    } else {
+        std::ptr::null()
+    }
+}
+
+unsafe extern "C" fn factory_create_plugin(
+    _factory: *const clap_plugin_factory,
+    _host: *const clap_host,
+    plugin_id: *const c_char,
+) -> *const clap_plugin {
+    if CStr::from_ptr(plugin_id) != PLUGIN_ID {
+        return std::ptr::null();
+    }
+
+    let plugin = Box::into_raw(Box::new(Plugin {
+        clap: clap_plugin {
+            desc: &DESCRIPTOR,
+            plugin_data: std::ptr::null_mut(),
+            init: Some(plugin_init),
+            destroy: Some(plugin_destroy),
+            activate: Some(plugin_activate),
+            deactivate: Some(plugin_deactivate),
+            start_processing: Some(plugin_start_processing),
+            stop_processing: Some(plugin_stop_processing),
+            reset: Some(plugin_reset),
+            process: Some(plugin_process),
+            get_extension: Some(plugin_get_extension),
+            on_main_thread: Some(plugin_on_main_thread),
+        },
+        settings: Settings::from_env(),
+        engine: None,
+    }));
+    (*plugin).clap.plugin_data = plugin as *mut c_void;
+
+    &(*plugin).clap
+}
+
+unsafe extern "C" fn entry_init(_plugin_path: *const c_char) -> bool {
+    true
+}
+
+unsafe extern "C" fn entry_deinit() {}
+
+unsafe extern "C" fn entry_get_factory(factory_id: *const c_char) -> *const c_void {
+    if CStr::from_ptr(factory_id) == CLAP_PLUGIN_FACTORY_ID {
+        &FACTORY as *const _ as *const c_void
+    } else {
+        std::ptr::null()
+    }
+}
+
+/// CLAPエントリポイント
+#[allow(non_upper_case_globals)]
+#[no_mangle]
+pub static clap_entry: clap_plugin_entry = clap_plugin_entry {
+    clap_version: CLAP_VERSION,
+    init: Some(entry_init),
+    deinit: Some(entry_deinit),
+    get_factory: Some(entry_get_factory),
+};
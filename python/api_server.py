@@ -10,17 +10,44 @@ RVC変換 + 揺らぎエンジンを適用して返すHTTPサーバー。
 - GPU推論の並列化
 """
 
+import asyncio
+import hashlib
+import heapq
+import hmac
 import io
+import itertools
+import json
+import struct
 import numpy as np
 import soundfile as sf
-from fastapi import FastAPI, UploadFile, File, HTTPException
-from fastapi.responses import StreamingResponse
+from fastapi import FastAPI, UploadFile, File, Form, HTTPException
+from fastapi import Request, WebSocket, WebSocketDisconnect
+from fastapi.responses import JSONResponse, StreamingResponse
 from pydantic import BaseModel
-from typing import Optional
+from typing import List, Optional
 import logging
+import os
 import time
+from contextlib import asynccontextmanager
+from pathlib import Path
 
 from rvc_engine import RVCEngine, RVCConfig, RVCRealtimeEngine
+
+# 文字起こし（/transcribe）は faster-whisper があるときだけ使える
+try:
+    from faster_whisper import WhisperModel
+except ImportError:
+    WhisperModel = None
+# 話者の区別（/diarize）は pyannote.audio があるときだけ使える
+try:
+    from pyannote.audio import Pipeline as DiarizationPipeline
+except ImportError:
+    DiarizationPipeline = None
+# Opusでのチャンクの送受信（--codec opus）は opuslib があるときだけ使える
+try:
+    import opuslib
+except ImportError:
+    opuslib = None
 from fluctuation import FluctuationEngine, FluctuationConfig, add_background_noise
 
 # ロギング設定
@@ -31,10 +58,104 @@ logging.basicConfig(
 logger = logging.getLogger(__name__)
 
 # FastAPIアプリケーション
+SERVER_VERSION = "0.1.0"
+
+# クライアントとのプロトコルのバージョン（src/client.rs の PROTOCOL_VERSION と対応）
+PROTOCOL_VERSION = 2
+# 受け付けるクライアントの最低プロトコルバージョン
+MIN_CLIENT_PROTOCOL_VERSION = 1
+# クライアントがプロトコルのバージョンを伝えるヘッダー（ないクライアントはv1）
+PROTOCOL_HEADER = "X-Makebeliv-Protocol"
+
+# 変換リクエストの優先度を伝えるヘッダー（src/client.rs の PRIORITY_HEADER と対応）
+PRIORITY_HEADER = "X-Makebeliv-Priority"
+# 優先度（小さいほど先に処理する）
+PRIORITIES = {"realtime": 0, "batch": 1}
+
+# 対応しているオプション機能（src/client.rs の Capability と対応）
+CAPABILITIES = ["models", "priority", "sessions", "ws-chunks"]
+if WhisperModel is not None:
+    CAPABILITIES.append("transcribe")
+if opuslib is not None:
+    CAPABILITIES.append("opus")
+if DiarizationPipeline is not None:
+    CAPABILITIES.append("diarize")
+
+# チャンクの音声の形式を伝えるヘッダー（src/client.rs の CODEC_HEADER と対応）
+CODEC_HEADER = "X-Makebeliv-Codec"
+
+# 設定すると、このキーを Authorization: Bearer で送らないクライアントを断る
+# （クライアントは makebeliv auth login で保存したキーか、同じ名前の環境変数のキーを送る）
+API_KEY = os.environ.get("MAKEBELIV_API_KEY") or None
+
+# 文字起こしに使うWhisperのモデル（tiny, base, small, medium, large-v3 など）
+WHISPER_MODEL = os.environ.get("MAKEBELIV_WHISPER_MODEL", "small")
+
+# 話者の区別に使うpyannoteのパイプライン（Hugging Faceのトークンは HF_TOKEN で渡す）
+DIARIZATION_MODEL = os.environ.get("MAKEBELIV_DIARIZATION_MODEL", "pyannote/speaker-diarization-3.1")
+
+# WebSocketの変換結果のヘッダー（src/client.rs の STREAM_HEADER_BYTES と対応）
+# sample_rate: u32, channels: u16, codec: u16（0: PCM, 1: Opus）, processing_time_ms: f32（リトルエンディアン）
+STREAM_HEADER = struct.Struct("<IHHf")
+STREAM_CODEC_PCM = 0
+STREAM_CODEC_OPUS = 1
+
+# Opusのチャンクのヘッダー（src/opus_chunk.rs と対応）
+# sample_rate: u32, channels: u16, pre_skip: u16, frames: u32 の後に [len: u16][パケット] が続く
+OPUS_CHUNK_HEADER = struct.Struct("<IHHI")
+OPUS_RATES = (8000, 12000, 16000, 24000, 48000)
+OPUS_BITRATE = 64000
+
+
+def decode_opus_chunk(data: bytes):
+    """Opusのチャンクを復号する（モノラルのfloat32, サンプルレート）"""
+    sr, channels, pre_skip, frames = OPUS_CHUNK_HEADER.unpack_from(data)
+    decoder = opuslib.Decoder(sr, channels)
+    max_frames = sr * 120 // 1000
+    pcm = bytearray()
+    offset = OPUS_CHUNK_HEADER.size
+    while offset < len(data):
+        (length,) = struct.unpack_from("<H", data, offset)
+        offset += 2
+        pcm += decoder.decode_float(bytes(data[offset:offset + length]), max_frames)
+        offset += length
+    audio = np.frombuffer(bytes(pcm), dtype="<f4").reshape(-1, channels)
+    audio = audio[pre_skip:pre_skip + frames]
+    if channels > 1:
+        audio = audio.mean(axis=1)
+    return audio.reshape(-1).astype(np.float32), sr
+
+
+def encode_opus_chunk(audio: np.ndarray, sr: int) -> bytes:
+    """モノラルの音声を1つのOpusのチャンクにする"""
+    audio = np.asarray(audio, dtype=np.float32).reshape(-1)
+    if sr not in OPUS_RATES:
+        import librosa
+        audio = librosa.resample(audio, orig_sr=sr, target_sr=48000)
+        sr = 48000
+    encoder = opuslib.Encoder(sr, 1, opuslib.APPLICATION_VOIP)
+    encoder.bitrate = OPUS_BITRATE
+    # libopusの先読み（2.5ms + 遅延補償4ms）
+    pre_skip = sr * 13 // 2000
+    frame = sr // 50
+    frames = len(audio)
+    padded = -(-(frames + pre_skip) // frame) * frame
+    audio = np.concatenate([audio, np.zeros(padded - frames, dtype=np.float32)])
+    out = bytearray(OPUS_CHUNK_HEADER.pack(sr, 1, pre_skip, frames))
+    for start in range(0, padded, frame):
+        packet = encoder.encode_float(audio[start:start + frame].astype("<f4").tobytes(), frame)
+        out += struct.pack("<H", len(packet)) + packet
+    return bytes(out)
+
+
+def wants_opus(value: Optional[str]) -> bool:
+    """クライアントがOpusで送ってきたか（opuslib がなければ受け付けない）"""
+    return opuslib is not None and (value or "").lower() == "opus"
+
 app = FastAPI(
     title="Makebeliv Voice Conversion API",
     description="リアルタイムボイスチェンジャー API",
-    version="0.1.0"
+    version=SERVER_VERSION
 )
 
 
@@ -48,12 +169,54 @@ class ConvertRequest(BaseModel):
     enable_fluctuation: bool = True
 
 
+class ModelInfo(BaseModel):
+    """モデル情報"""
+    name: str
+    has_weights: bool
+    loaded: bool
+    # 重みファイルのSHA-256の先頭12桁（重みがなければNone）
+    version: Optional[str] = None
+
+
+class SessionInfo(BaseModel):
+    """変換セッションの情報"""
+    session_id: str
+    chunks: int
+    age_seconds: float
+    idle_seconds: float
+
+
+class TranscribedWord(BaseModel):
+    """文字起こしの1語（時刻は音声の先頭からの秒）"""
+    word: str
+    start: float
+    end: float
+
+
+class SpeakerTurn(BaseModel):
+    """1人の話者が話している区間（時刻は音声の先頭からの秒）"""
+    speaker: str
+    start: float
+    end: float
+
+
 class ServerStatus(BaseModel):
     """サーバーステータス"""
     status: str
     device: str
     models_loaded: int
     uptime_seconds: float
+    server_version: str = SERVER_VERSION
+    protocol_version: int = PROTOCOL_VERSION
+    min_client_protocol_version: int = MIN_CLIENT_PROTOCOL_VERSION
+    capabilities: List[str] = CAPABILITIES
+    # モデルが想定する入力のサンプルレート（Noneならどのレートでも受け付ける）
+    sample_rate: Optional[int] = None
+    queue_depth: int = 0
+    gpu_memory_used_mb: Optional[float] = None
+    gpu_memory_total_mb: Optional[float] = None
+    gpu_utilization_percent: Optional[float] = None
+    cpu_load_percent: Optional[float] = None
 
 
 # グローバル状態
@@ -64,7 +227,11 @@ class ServerState:
         self.start_time = time.time()
         self.rvc_engines = {}  # モデル名 -> RVCEngine
         self.fluctuation_engines = {}  # セッションID -> FluctuationEngine
+        self.sessions = {}  # セッションID -> {"chunks", "created", "last_used"}
         self.device = "cuda" if __import__("torch").cuda.is_available() else "cpu"
+        self.active_requests = 0  # 処理中の変換リクエスト数
+        self.whisper = None  # 最初の文字起こしでロードする
+        self.diarization = None  # 最初の話者の区別でロードする
 
         logger.info(f"サーバー初期化: device={self.device}")
 
@@ -90,11 +257,158 @@ class ServerState:
             self.fluctuation_engines[session_id] = FluctuationEngine(config)
             logger.info(f"揺らぎエンジン作成: {session_id}")
 
+        now = time.time()
+        session = self.sessions.setdefault(
+            session_id, {"chunks": 0, "created": now, "last_used": now}
+        )
+        session["chunks"] += 1
+        session["last_used"] = now
         return self.fluctuation_engines[session_id]
 
+    def get_whisper(self):
+        """文字起こしのモデルを取得（初回にロード）"""
+        if self.whisper is None:
+            compute_type = "float16" if self.device == "cuda" else "int8"
+            self.whisper = WhisperModel(WHISPER_MODEL, device=self.device, compute_type=compute_type)
+            logger.info(f"Whisperモデルをロード: {WHISPER_MODEL}")
+        return self.whisper
+
+    def get_diarization(self):
+        """話者の区別のパイプラインを取得（初回にロード）"""
+        if self.diarization is None:
+            pipeline = DiarizationPipeline.from_pretrained(
+                DIARIZATION_MODEL, use_auth_token=os.environ.get("HF_TOKEN")
+            )
+            if self.device == "cuda":
+                pipeline.to(__import__("torch").device("cuda"))
+            self.diarization = pipeline
+            logger.info(f"話者の区別のモデルをロード: {DIARIZATION_MODEL}")
+        return self.diarization
+
+    def drop_session(self, session_id: str) -> bool:
+        """セッションの状態を捨てる（次のチャンクで作り直される）"""
+        self.sessions.pop(session_id, None)
+        return self.fluctuation_engines.pop(session_id, None) is not None
+
+
+class GpuScheduler:
+    """変換処理を1つずつ実行するスケジューラ
+
+    GPUを奪い合うと全てのリクエストが遅くなるため、変換は同時に1つだけ実行します。
+    待っているリクエストは優先度順（同じ優先度なら到着順）に処理するので、
+    バッチのファイル処理が走っていてもリアルタイムのチャンクが先に割り込めます。
+    """
+
+    def __init__(self):
+        self.busy = False
+        self.waiters = []  # (優先度, 到着順, Future) のヒープ
+        self.counter = itertools.count()
+
+    @staticmethod
+    def parse_priority(value: Optional[str], default: str) -> str:
+        """ヘッダーの値を優先度に（不明な値は既定の優先度）"""
+        value = (value or "").strip().lower()
+        return value if value in PRIORITIES else default
+
+    @asynccontextmanager
+    async def slot(self, priority: str):
+        await self.acquire(priority)
+        try:
+            yield
+        finally:
+            self.release()
+
+    async def acquire(self, priority: str):
+        if not self.busy and not self.waiters:
+            self.busy = True
+            return
+        future = asyncio.get_running_loop().create_future()
+        heapq.heappush(self.waiters, (PRIORITIES[priority], next(self.counter), future))
+        try:
+            await future
+        except asyncio.CancelledError:
+            # 枠を渡された直後に切断された場合は次に回す
+            if future.done() and not future.cancelled():
+                self.release()
+            raise
+
+    def release(self):
+        while self.waiters:
+            _, _, future = heapq.heappop(self.waiters)
+            if not future.done():
+                # 枠はbusyのまま次の待ち手に引き継ぐ
+                future.set_result(None)
+                return
+        self.busy = False
+
 
 # グローバルインスタンス
 state = ServerState()
+scheduler = GpuScheduler()
+
+
+def get_resource_stats() -> dict:
+    """GPU/CPUリソースの使用状況を取得
+
+    取得できない項目はNoneを返します。
+    """
+    import torch
+
+    stats = {}
+
+    if state.device == "cuda":
+        props = torch.cuda.get_device_properties(0)
+        stats["gpu_memory_used_mb"] = torch.cuda.memory_reserved(0) / 1024**2
+        stats["gpu_memory_total_mb"] = props.total_memory / 1024**2
+        try:
+            # pynvmlが必要
+            stats["gpu_utilization_percent"] = float(torch.cuda.utilization(0))
+        except Exception:
+            pass
+
+    if hasattr(os, "getloadavg"):
+        # 1分平均のロードアベレージをコア数で正規化
+        stats["cpu_load_percent"] = os.getloadavg()[0] / (os.cpu_count() or 1) * 100
+
+    return stats
+
+
+def is_authorized(headers) -> bool:
+    """APIキーが設定されていれば、リクエストのキーと一致するか"""
+    if API_KEY is None:
+        return True
+    return hmac.compare_digest(headers.get("authorization", ""), f"Bearer {API_KEY}")
+
+
+@app.middleware("http")
+async def check_api_key(request: Request, call_next):
+    """APIキーのないリクエストを断る（/ はキーなしでも応答する）"""
+    if request.url.path != "/" and not is_authorized(request.headers):
+        return JSONResponse(status_code=401, content={"detail": "invalid or missing API key"})
+    return await call_next(request)
+
+
+@app.middleware("http")
+async def check_protocol_version(request: Request, call_next):
+    """古すぎるクライアントを分かりやすいエラーで断る
+
+    /status はバージョン確認に使うため常に応答します。
+    """
+    try:
+        client_version = int(request.headers.get(PROTOCOL_HEADER, "1"))
+    except ValueError:
+        client_version = 1
+
+    if client_version < MIN_CLIENT_PROTOCOL_VERSION and request.url.path != "/status":
+        return JSONResponse(
+            status_code=426,
+            content={
+                "detail": f"server needs protocol v{MIN_CLIENT_PROTOCOL_VERSION}, "
+                          f"client speaks v{client_version}"
+            },
+        )
+
+    return await call_next(request)
 
 
 # エンドポイント
@@ -103,7 +417,7 @@ async def root():
     """ルート - サーバー情報"""
     return {
         "name": "Makebeliv Voice Conversion API",
-        "version": "0.1.0",
+        "version": SERVER_VERSION,
         "status": "running"
     }
 
@@ -115,19 +429,73 @@ async def get_status():
         status="running",
         device=state.device,
         models_loaded=len(state.rvc_engines),
-        uptime_seconds=time.time() - state.start_time
+        uptime_seconds=time.time() - state.start_time,
+        queue_depth=state.active_requests,
+        **get_resource_stats()
     )
 
 
+# 重みファイルのバージョン（パス → (更新時刻, サイズ, バージョン)）。毎回全体を読まないよう覚えておく
+_model_versions: dict = {}
+
+
+def model_version(path: Path) -> Optional[str]:
+    """重みファイルのSHA-256の先頭12桁（クライアントのマニフェストと同じ求め方）"""
+    try:
+        stat = path.stat()
+    except OSError:
+        return None
+    cached = _model_versions.get(path)
+    if cached and cached[:2] == (stat.st_mtime_ns, stat.st_size):
+        return cached[2]
+    digest = hashlib.sha256()
+    with open(path, "rb") as f:
+        for block in iter(lambda: f.read(1 << 20), b""):
+            digest.update(block)
+    version = digest.hexdigest()[:12]
+    _model_versions[path] = (stat.st_mtime_ns, stat.st_size, version)
+    return version
+
+
+@app.get("/models")
+async def list_models():
+    """利用可能なモデル一覧を取得
+
+    models/<名前>/model.pth を持つディレクトリをモデルとして扱います。
+    defaultはモデルがなくてもデモモードで動作するため常に含めます。
+    """
+    models_dir = Path("models")
+    names = []
+    if models_dir.is_dir():
+        names = sorted(
+            p.name for p in models_dir.iterdir() if (p / "model.pth").exists()
+        )
+    if "default" not in names:
+        names.insert(0, "default")
+
+    loaded = {key.rsplit("_", 1)[0] for key in state.rvc_engines}
+    models = [
+        ModelInfo(
+            name=name,
+            has_weights=(models_dir / name / "model.pth").exists(),
+            loaded=name in loaded,
+            version=model_version(models_dir / name / "model.pth"),
+        )
+        for name in names
+    ]
+    return {"models": models}
+
+
 @app.post("/convert")
 async def convert_audio(
+    request: Request,
     audio: UploadFile = File(...),
-    model: str = "default",
-    pitch_shift: int = 0,
-    noise_type: str = "cafe",
-    noise_level: float = 0.02,
-    enable_fluctuation: bool = True,
-    session_id: str = "default"
+    model: str = Form("default"),
+    pitch_shift: int = Form(0),
+    noise_type: str = Form("cafe"),
+    noise_level: float = Form(0.02),
+    enable_fluctuation: bool = Form(True),
+    session_id: str = Form("default")
 ):
     """音声変換API
 
@@ -144,6 +512,7 @@ async def convert_audio(
         変換後の音声（WAV形式）
     """
     start_time = time.time()
+    state.active_requests += 1
 
     try:
         # 音声データを読み込み
@@ -156,27 +525,34 @@ async def convert_audio(
 
         logger.info(f"入力音声: sr={sr}Hz, len={len(audio_data)/sr:.2f}秒")
 
-        # 1. RVC変換
-        rvc_engine = state.get_or_create_rvc_engine(model, pitch_shift)
-        converted = rvc_engine.convert(audio_data, sr)
-
-        # 2. 揺らぎエンジン適用
-        if enable_fluctuation:
-            fluct_engine = state.get_or_create_fluctuation_engine(session_id)
-            converted = fluct_engine.apply_volume_fluctuation(converted)
-
-        # 3. ノイズ追加
-        if noise_level > 0:
-            converted = add_background_noise(
-                converted,
-                noise_type=noise_type,
-                noise_level=noise_level,
-                sr=sr
-            )
-
-        # 4. 出力
+        def run():
+            # 1. RVC変換
+            rvc_engine = state.get_or_create_rvc_engine(model, pitch_shift)
+            converted = rvc_engine.convert(audio_data, sr)
+
+            # 2. 揺らぎエンジン適用
+            if enable_fluctuation:
+                fluct_engine = state.get_or_create_fluctuation_engine(session_id)
+                converted = fluct_engine.apply_volume_fluctuation(converted)
+
+            # 3. ノイズ追加
+            if noise_level > 0:
+                converted = add_background_noise(
+                    converted,
+                    noise_type=noise_type,
+                    noise_level=noise_level,
+                    sr=sr
+                )
+            return converted
+
+        # ファイル処理はリアルタイムのチャンクより後回しにする
+        priority = scheduler.parse_priority(request.headers.get(PRIORITY_HEADER), "batch")
+        async with scheduler.slot(priority):
+            converted = await asyncio.to_thread(run)
+
+        # 4. 出力（ビット深度はクライアント側で変換するため32bit floatで返す）
         output_buffer = io.BytesIO()
-        sf.write(output_buffer, converted, sr, format='WAV')
+        sf.write(output_buffer, converted, sr, format='WAV', subtype='FLOAT')
         output_buffer.seek(0)
 
         elapsed = time.time() - start_time
@@ -194,15 +570,18 @@ async def convert_audio(
     except Exception as e:
         logger.error(f"変換エラー: {e}", exc_info=True)
         raise HTTPException(status_code=500, detail=str(e))
+    finally:
+        state.active_requests -= 1
 
 
 @app.post("/convert-chunk")
 async def convert_audio_chunk(
+    request: Request,
     audio: UploadFile = File(...),
-    model: str = "default",
-    pitch_shift: int = 0,
-    enable_fluctuation: bool = True,
-    session_id: str = "default"
+    model: str = Form("default"),
+    pitch_shift: int = Form(0),
+    enable_fluctuation: bool = Form(True),
+    session_id: str = Form("default")
 ):
     """音声チャンク変換API（リアルタイム用）
 
@@ -220,55 +599,274 @@ async def convert_audio_chunk(
         変換後の音声チャンク
     """
     start_time = time.time()
+    state.active_requests += 1
 
     try:
-        # 音声データを読み込み
+        # 音声データを読み込み（Opusで送られてきたらOpusで返す）
         audio_bytes = await audio.read()
-        audio_data, sr = sf.read(io.BytesIO(audio_bytes))
+        opus = wants_opus(request.headers.get(CODEC_HEADER))
+        if opus:
+            audio_data, sr = decode_opus_chunk(audio_bytes)
+        else:
+            audio_data, sr = sf.read(io.BytesIO(audio_bytes))
 
         # モノラル化
         if len(audio_data.shape) > 1:
             audio_data = np.mean(audio_data, axis=1)
 
-        # RVC変換（チャンクモード）
-        # TODO: RVCRealtimeEngineを使用
-        rvc_engine = state.get_or_create_rvc_engine(model, pitch_shift)
-        converted = rvc_engine.convert(audio_data, sr)
+        def run():
+            # RVC変換（チャンクモード）
+            # TODO: RVCRealtimeEngineを使用
+            rvc_engine = state.get_or_create_rvc_engine(model, pitch_shift)
+            converted = rvc_engine.convert(audio_data, sr)
 
-        # 揺らぎ（軽量版）
-        if enable_fluctuation:
-            fluct_engine = state.get_or_create_fluctuation_engine(session_id)
-            converted = fluct_engine.apply_volume_fluctuation(converted)
+            # 揺らぎ（軽量版）
+            if enable_fluctuation:
+                fluct_engine = state.get_or_create_fluctuation_engine(session_id)
+                converted = fluct_engine.apply_volume_fluctuation(converted)
+            return converted
+
+        priority = scheduler.parse_priority(request.headers.get(PRIORITY_HEADER), "realtime")
+        async with scheduler.slot(priority):
+            converted = await asyncio.to_thread(run)
 
         # 出力
         output_buffer = io.BytesIO()
-        sf.write(output_buffer, converted, sr, format='WAV')
+        if opus:
+            output_buffer.write(encode_opus_chunk(converted, sr))
+        else:
+            sf.write(output_buffer, converted, sr, format='WAV')
         output_buffer.seek(0)
 
         elapsed = time.time() - start_time
         logger.debug(f"チャンク変換: {elapsed*1000:.1f}ms")
 
+        headers = {"X-Processing-Time-Ms": str(int(elapsed * 1000))}
+        if opus:
+            headers[CODEC_HEADER] = "opus"
         return StreamingResponse(
             output_buffer,
-            media_type="audio/wav",
-            headers={
-                "X-Processing-Time-Ms": str(int(elapsed * 1000))
-            }
+            media_type="audio/opus" if opus else "audio/wav",
+            headers=headers
         )
 
     except Exception as e:
         logger.error(f"チャンク変換エラー: {e}")
         raise HTTPException(status_code=500, detail=str(e))
+    finally:
+        state.active_requests -= 1
+
+
+@app.websocket("/ws/convert-chunk")
+async def convert_audio_stream(websocket: WebSocket):
+    """音声チャンク変換（WebSocket、リアルタイム用）
+
+    接続を保ったまま生のPCMをやり取りし、チャンクごとのHTTPのオーバーヘッドを省きます。
+
+    - テキスト: {"type": "config", "model", "pitch_shift", "session_id",
+      "sample_rate", "channels", "codec"}（変わったときだけ送られる）
+    - バイナリ（受信）: 32bit floatのインターリーブPCM（codec が "opus" ならOpusのチャンク）
+    - バイナリ（送信）: STREAM_HEADER + 32bit floatのPCM（モノラル）かOpusのチャンク
+    - エラーは {"type": "error", "detail": ...} を送って接続は保つ
+    """
+    if not is_authorized(websocket.headers):
+        await websocket.close(code=1008)
+        return
+
+    await websocket.accept()
+    config = None
+    priority = scheduler.parse_priority(websocket.headers.get(PRIORITY_HEADER), "realtime")
+
+    try:
+        while True:
+            message = await websocket.receive()
+            if message["type"] == "websocket.disconnect":
+                break
+
+            if message.get("text") is not None:
+                try:
+                    data = json.loads(message["text"])
+                except ValueError:
+                    data = {}
+                if data.get("type") == "config":
+                    config = data
+                    logger.info(f"ストリーム設定: {config}")
+                else:
+                    await websocket.send_text(json.dumps({"type": "error", "detail": "invalid message"}))
+                continue
+
+            if config is None:
+                await websocket.send_text(
+                    json.dumps({"type": "error", "detail": "config must be sent before audio"})
+                )
+                continue
+
+            start_time = time.time()
+            state.active_requests += 1
+            try:
+                opus = wants_opus(config.get("codec"))
+                if opus:
+                    audio_data, sr = decode_opus_chunk(message["bytes"])
+                    channels = 1
+                else:
+                    sr = int(config["sample_rate"])
+                    channels = int(config["channels"])
+                    audio_data = np.frombuffer(message["bytes"], dtype="<f4")
+
+                # モノラル化
+                if channels > 1:
+                    audio_data = audio_data[: len(audio_data) // channels * channels]
+                    audio_data = audio_data.reshape(-1, channels).mean(axis=1)
+
+                def run():
+                    rvc_engine = state.get_or_create_rvc_engine(
+                        config.get("model", "default"), int(config.get("pitch_shift", 0))
+                    )
+                    converted = rvc_engine.convert(audio_data, sr)
+
+                    fluct_engine = state.get_or_create_fluctuation_engine(
+                        config.get("session_id", "default")
+                    )
+                    return fluct_engine.apply_volume_fluctuation(converted)
+
+                async with scheduler.slot(priority):
+                    converted = await asyncio.to_thread(run)
+
+                elapsed_ms = (time.time() - start_time) * 1000
+                if opus:
+                    header = STREAM_HEADER.pack(sr, 1, STREAM_CODEC_OPUS, elapsed_ms)
+                    await websocket.send_bytes(header + encode_opus_chunk(converted, sr))
+                else:
+                    header = STREAM_HEADER.pack(sr, 1, STREAM_CODEC_PCM, elapsed_ms)
+                    await websocket.send_bytes(header + np.asarray(converted, dtype="<f4").tobytes())
+            except Exception as e:
+                logger.error(f"ストリーム変換エラー: {e}")
+                await websocket.send_text(json.dumps({"type": "error", "detail": str(e)}))
+            finally:
+                state.active_requests -= 1
+
+    except WebSocketDisconnect:
+        pass
+
+
+@app.post("/transcribe")
+async def transcribe_audio(
+    request: Request,
+    audio: UploadFile = File(...),
+    language: Optional[str] = Form(None)
+):
+    """音声を文字起こしし、語ごとの時刻を返す
+
+    クライアントは指定した語を伏せるために使います（`monitor --bleep-word`）。
+    faster-whisper がインストールされていなければ501を返します。
+    """
+    if WhisperModel is None:
+        raise HTTPException(
+            status_code=501,
+            detail="文字起こしには faster-whisper が必要です（uv sync --extra transcribe）"
+        )
+
+    try:
+        audio_bytes = await audio.read()
+        audio_data, sr = sf.read(io.BytesIO(audio_bytes), dtype="float32")
+        if len(audio_data.shape) > 1:
+            audio_data = np.mean(audio_data, axis=1)
+        # Whisperは16kHzを前提にする
+        if sr != 16000:
+            import librosa
+            audio_data = librosa.resample(audio_data, orig_sr=sr, target_sr=16000)
+
+        def run():
+            segments, _ = state.get_whisper().transcribe(
+                audio_data, language=language, word_timestamps=True
+            )
+            return [
+                TranscribedWord(word=word.word, start=word.start, end=word.end)
+                for segment in segments
+                for word in (segment.words or [])
+            ]
+
+        # 出力を止めて待っているため、リアルタイムのチャンクと同じ扱いにする
+        priority = scheduler.parse_priority(request.headers.get(PRIORITY_HEADER), "realtime")
+        async with scheduler.slot(priority):
+            words = await asyncio.to_thread(run)
+
+        return {"words": words}
+
+    except Exception as e:
+        logger.error(f"文字起こしエラー: {e}", exc_info=True)
+        raise HTTPException(status_code=500, detail=str(e))
+
+
+@app.post("/diarize")
+async def diarize_audio(
+    request: Request,
+    audio: UploadFile = File(...),
+    num_speakers: Optional[int] = Form(None)
+):
+    """音声の話者を区別し、話者ごとの区間を返す
+
+    クライアントは選んだ話者の区間だけを変換するために使います（`process --only-speaker`）。
+    pyannote.audio がインストールされていなければ501を返します。
+    """
+    if DiarizationPipeline is None:
+        raise HTTPException(
+            status_code=501,
+            detail="話者の区別には pyannote.audio が必要です（uv sync --extra diarize）"
+        )
+
+    try:
+        audio_bytes = await audio.read()
+        audio_data, sr = sf.read(io.BytesIO(audio_bytes), dtype="float32")
+        if len(audio_data.shape) > 1:
+            audio_data = np.mean(audio_data, axis=1)
+
+        def run():
+            import torch
+            waveform = torch.from_numpy(np.ascontiguousarray(audio_data)).unsqueeze(0)
+            diarization = state.get_diarization()(
+                {"waveform": waveform, "sample_rate": sr}, num_speakers=num_speakers
+            )
+            return [
+                SpeakerTurn(speaker=speaker, start=turn.start, end=turn.end)
+                for turn, _, speaker in diarization.itertracks(yield_label=True)
+            ]
+
+        priority = scheduler.parse_priority(request.headers.get(PRIORITY_HEADER), "batch")
+        async with scheduler.slot(priority):
+            segments = await asyncio.to_thread(run)
+
+        return {"segments": segments}
+
+    except Exception as e:
+        logger.error(f"話者の区別エラー: {e}", exc_info=True)
+        raise HTTPException(status_code=500, detail=str(e))
+
+
+@app.get("/sessions")
+async def list_sessions():
+    """揺らぎエンジンの状態を持っているセッション一覧を取得"""
+    now = time.time()
+    sessions = [
+        SessionInfo(
+            session_id=session_id,
+            chunks=session["chunks"],
+            age_seconds=now - session["created"],
+            idle_seconds=now - session["last_used"],
+        )
+        for session_id, session in state.sessions.items()
+    ]
+    return {"sessions": sessions}
 
 
 @app.post("/reset-session")
 async def reset_session(session_id: str):
     """セッションをリセット
 
-    揺らぎエンジンの状態をクリアします。
+    揺らぎエンジンの状態を捨てます。クライアントはセッションの開始時と終了時に呼ぶため、
+    終わったセッションの状態はサーバーに残りません。
     """
-    if session_id in state.fluctuation_engines:
-        state.fluctuation_engines[session_id].reset()
+    if state.drop_session(session_id):
         logger.info(f"セッションリセット: {session_id}")
         return {"status": "reset", "session_id": session_id}
     else:
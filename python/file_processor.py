@@ -30,6 +30,9 @@ class ProcessConfig:
     # 音声パラメータ
     target_sr: int = 16000  # RVCの推奨サンプリングレート
 
+    # 出力のsoundfileサブタイプ（None なら入力と同じ）
+    output_subtype: Optional[str] = None
+
     # 揺らぎ設定
     enable_fluctuation: bool = True
     fluctuation_config: Optional[FluctuationConfig] = None
@@ -46,6 +49,24 @@ class ProcessConfig:
     rvc_model_path: str = "models/default/model.pth"
 
 
+# CLIのビット深度指定 → soundfileのサブタイプ
+BIT_DEPTH_SUBTYPES = {
+    "16": "PCM_16",
+    "24": "PCM_24",
+    "32f": "FLOAT",
+}
+
+
+def output_subtype_for(input_path: str) -> str:
+    """入力ファイルのビット深度を保つサブタイプを返す"""
+    subtype = sf.info(input_path).subtype
+    if subtype in ("PCM_24", "FLOAT"):
+        return subtype
+    if subtype in ("PCM_32", "DOUBLE"):
+        return "FLOAT"
+    return "PCM_16"
+
+
 class AudioFileProcessor:
     """音声ファイル処理クラス"""
 
@@ -139,29 +160,36 @@ class AudioFileProcessor:
         output_path = Path(self.config.output_path)
         output_path.parent.mkdir(parents=True, exist_ok=True)
 
-        sf.write(output_path, audio, sr)
-        print(f"出力: {output_path}")
+        subtype = self.config.output_subtype or output_subtype_for(self.config.input_path)
+        sf.write(output_path, audio, sr, subtype=subtype)
+        print(f"出力: {output_path} ({subtype})")
 
         return output_path
 
 
 if __name__ == "__main__":
-    # テスト実行
-    import sys
-
-    if len(sys.argv) < 2:
-        print("使用法: python file_processor.py <input_audio_file>")
-        sys.exit(1)
-
-    input_file = sys.argv[1]
-    output_file = "audio/output/processed.wav"
+    import argparse
+
+    parser = argparse.ArgumentParser(description="音声ファイルを処理する")
+    parser.add_argument("input", help="入力音声ファイル")
+    parser.add_argument("--output", default="audio/output/processed.wav", help="出力ファイル")
+    parser.add_argument("--model", default="default", help="モデル名（models/<名前>/model.pth）")
+    parser.add_argument("--noise", default="cafe", help="背景ノイズの種類")
+    parser.add_argument("--noise-level", type=float, default=0.02, help="背景ノイズのレベル（0で無効）")
+    parser.add_argument("--pitch", type=int, default=0, help="ピッチシフト（半音単位）")
+    parser.add_argument("--bit-depth", choices=sorted(BIT_DEPTH_SUBTYPES), help="出力のビット深度")
+    args = parser.parse_args()
 
     config = ProcessConfig(
-        input_path=input_file,
-        output_path=output_file,
+        input_path=args.input,
+        output_path=args.output,
+        output_subtype=BIT_DEPTH_SUBTYPES[args.bit_depth] if args.bit_depth else None,
         enable_fluctuation=True,
-        enable_noise=True,
-        noise_level=0.01
+        enable_noise=args.noise_level > 0,
+        noise_type=args.noise,
+        noise_level=args.noise_level,
+        pitch_shift=args.pitch,
+        rvc_model_path=f"models/{args.model}/model.pth",
     )
 
     processor = AudioFileProcessor(config)
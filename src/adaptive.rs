@@ -0,0 +1,165 @@
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+/// 処理時間がチャンク長のこの割合を超え続けたら、チャンクを延ばす
+const GROW_RATIO: f64 = 0.9;
+
+/// 処理時間がチャンク長のこの割合を下回り続けたら、チャンクを縮める
+///
+/// 縮めた後の割合が [`GROW_RATIO`] を超えないよう、`GROW_RATIO / RESIZE_FACTOR` より小さくします。
+const SHRINK_RATIO: f64 = 0.5;
+
+/// 延ばす・縮めるときの倍率
+const RESIZE_FACTOR: f64 = 1.5;
+
+/// 延ばすまでに超え続けるチャンク数（一時的な遅れでは延ばさない）
+const GROW_AFTER_CHUNKS: u32 = 3;
+
+/// 縮めるまでに下回り続けるチャンク数（延ばすよりゆっくり戻す）
+const SHRINK_AFTER_CHUNKS: u32 = 20;
+
+/// 処理時間の平滑化の係数（新しい値の重み）
+const SMOOTHING: f64 = 0.3;
+
+/// 延ばせる上限の既定値（元のチャンク長の倍数、`--max-chunk-ms`）
+pub const DEFAULT_MAX_CHUNK_FACTOR: u32 = 4;
+
+/// 変換せずに入力をそのまま流す時間（`--overload-passthrough`）
+const PASSTHROUGH_SECONDS: u64 = 10;
+
+/// サーバーの処理が追いつかないときにチャンク長を変える（`--adaptive-chunk`）
+///
+/// 1チャンクの処理時間がチャンク長を超えると、入力が溜まって遅延が増え続けます。
+/// 処理時間とチャンク長の比を平滑化して見張り、超え続けたらチャンクを延ばして
+/// リクエストごとのオーバーヘッドを減らし、余裕が続いたら元の長さへ戻します。
+/// 延ばす条件と縮める条件の間を空け（ヒステリシス）、行ったり来たりしないようにします。
+/// 上限まで延ばしても追いつかないときは、指定があれば一定時間変換をやめて入力をそのまま流します。
+#[derive(Debug, Clone)]
+pub struct AdaptiveChunk {
+    base_ms: u32,
+    max_ms: u32,
+    current_ms: u32,
+    /// 上限でも追いつかないとき、変換をやめて入力をそのまま流す
+    passthrough: bool,
+    passthrough_until: Option<Instant>,
+    /// 平滑化した、処理時間とチャンク長の比
+    ratio: Option<f64>,
+    over: u32,
+    under: u32,
+    /// 上限に達したことを知らせたか
+    warned_at_max: bool,
+}
+
+impl AdaptiveChunk {
+    pub fn new(base_ms: u32, max_ms: u32) -> Self {
+        Self {
+            base_ms,
+            max_ms: max_ms.max(base_ms),
+            current_ms: base_ms,
+            passthrough: false,
+            passthrough_until: None,
+            ratio: None,
+            over: 0,
+            under: 0,
+            warned_at_max: false,
+        }
+    }
+
+    pub fn with_passthrough(mut self, enabled: bool) -> Self {
+        self.passthrough = enabled;
+        self
+    }
+
+    /// 今のチャンク長（ミリ秒）
+    pub fn chunk_ms(&self) -> u32 {
+        self.current_ms
+    }
+
+    pub fn max_ms(&self) -> u32 {
+        self.max_ms
+    }
+
+    /// 変換をやめて入力をそのまま流している途中か（時間が過ぎたら変換に戻る）
+    pub fn is_passing_through(&mut self, now: Instant) -> bool {
+        match self.passthrough_until {
+            Some(until) if now < until => true,
+            Some(_) => {
+                self.passthrough_until = None;
+                self.reset();
+                info!("⏱ 変換を再開します（チャンク長 {}ms）", self.current_ms);
+                false
+            }
+            None => false,
+        }
+    }
+
+    /// 変換した1チャンクの処理時間を記録し、必要ならチャンク長を変える
+    pub fn observe(&mut self, processing_ms: f64, now: Instant) {
+        let sample = processing_ms / self.current_ms.max(1) as f64;
+        let ratio = match self.ratio {
+            Some(ratio) => ratio + (sample - ratio) * SMOOTHING,
+            None => sample,
+        };
+        self.ratio = Some(ratio);
+
+        if ratio > GROW_RATIO {
+            self.over += 1;
+            self.under = 0;
+        } else if ratio < SHRINK_RATIO {
+            self.under += 1;
+            self.over = 0;
+        } else {
+            self.over = 0;
+            self.under = 0;
+        }
+
+        if self.over >= GROW_AFTER_CHUNKS {
+            self.grow(ratio, now);
+        } else if self.under >= SHRINK_AFTER_CHUNKS && self.current_ms > self.base_ms {
+            let next = ((self.current_ms as f64 / RESIZE_FACTOR) as u32).max(self.base_ms);
+            info!(
+                "⏱ 処理に余裕があるため、チャンク長を {}ms → {}ms に縮めます（処理時間はチャンク長の{:.0}%）",
+                self.current_ms,
+                next,
+                ratio * 100.0
+            );
+            self.current_ms = next;
+            self.warned_at_max = false;
+            self.reset();
+        }
+    }
+
+    fn grow(&mut self, ratio: f64, now: Instant) {
+        if self.current_ms < self.max_ms {
+            let next = ((self.current_ms as f64 * RESIZE_FACTOR) as u32).min(self.max_ms);
+            warn!(
+                "⏱ サーバーの処理が追いつかないため、チャンク長を {}ms → {}ms に延ばします（処理時間はチャンク長の{:.0}%）",
+                self.current_ms,
+                next,
+                ratio * 100.0
+            );
+            self.current_ms = next;
+        } else if self.passthrough {
+            warn!(
+                "⏱ チャンク長を上限（{}ms）まで延ばしても追いつかないため、{}秒間は変換せずに入力をそのまま流します",
+                self.max_ms, PASSTHROUGH_SECONDS
+            );
+            self.passthrough_until = Some(now + Duration::from_secs(PASSTHROUGH_SECONDS));
+        } else if !self.warned_at_max {
+            warn!(
+                "⚠ チャンク長を上限（{}ms）まで延ばしても追いつきません。遅延が増え続けます",
+                self.max_ms
+            );
+            warn!("💡 --max-chunk-ms を大きくするか、--overload-passthrough で一時的に変換をやめられます");
+            self.warned_at_max = true;
+        }
+        self.reset();
+    }
+
+    /// チャンク長を変えたら、新しい長さで測り直す
+    fn reset(&mut self) {
+        self.ratio = None;
+        self.over = 0;
+        self.under = 0;
+    }
+}
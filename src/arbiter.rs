@@ -0,0 +1,136 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::MutexGuard;
+use tracing::{debug, info};
+
+/// 判定に使う直近のリアルタイム変換の数
+const LIVE_WINDOW: usize = 10;
+
+/// リアルタイム変換の遅延がチャンク長のこの割合を超えたら、バッチ変換を待たせる
+const LIVE_HEADROOM: f64 = 0.8;
+
+/// 余裕のうち、バッチの1区間に使ってよい割合
+const SEGMENT_SHARE: f64 = 0.5;
+
+/// バッチの1区間の長さの範囲（秒）
+const MIN_SEGMENT_SECONDS: f64 = 0.5;
+const MAX_SEGMENT_SECONDS: f64 = 10.0;
+
+/// 変換の速さが分かるまでの区間の長さ（秒）
+const INITIAL_SEGMENT_SECONDS: f64 = 1.0;
+
+struct State {
+    /// 直近のリアルタイム変換にかかった時間
+    live: VecDeque<Duration>,
+    /// 音声1秒の変換にかかるサーバーの時間（秒、バッチの結果から求める）
+    seconds_per_second: Option<f64>,
+    /// ライブのために待った回数
+    waits: u64,
+    segments: u64,
+}
+
+/// 同じプロセスでリアルタイム変換とバッチ変換がサーバーを共有するときの調停
+///
+/// サーバーは優先度（`X-Makebeliv-Priority`）でリアルタイムのチャンクを先に処理しますが、
+/// 実行中の変換は中断しません。そのためバッチの音声を、1区間の変換がリアルタイムの
+/// 余裕（チャンク長 − 直近の遅延）に収まる長さに分けて1つずつ送り、リアルタイムの
+/// 遅延がチャンク長に近づいている間はバッチを止めます。バッチは遅くなりますが、
+/// リアルタイムのチャンクは最悪でも1区間分しか待たされません。
+pub struct Arbiter {
+    chunk: Duration,
+    state: Mutex<State>,
+    /// バッチの変換は1つずつ
+    batch: tokio::sync::Mutex<()>,
+}
+
+impl Arbiter {
+    pub fn new(chunk_ms: u32) -> Self {
+        Self {
+            chunk: Duration::from_millis(chunk_ms.max(1) as u64),
+            state: Mutex::new(State {
+                live: VecDeque::with_capacity(LIVE_WINDOW),
+                seconds_per_second: None,
+                waits: 0,
+                segments: 0,
+            }),
+            batch: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// リアルタイムのチャンクの変換にかかった時間を記録する
+    pub fn record_live(&self, elapsed: Duration) {
+        let mut state = self.state();
+        if state.live.len() == LIVE_WINDOW {
+            state.live.pop_front();
+        }
+        state.live.push_back(elapsed);
+    }
+
+    /// バッチの1区間の変換にかかった時間を記録する（区間の長さの見積もりに使う）
+    pub fn record_batch(&self, audio_seconds: f64, elapsed: Duration) {
+        if audio_seconds <= 0.0 {
+            return;
+        }
+        let rate = elapsed.as_secs_f64() / audio_seconds;
+        let mut state = self.state();
+        // 1回の揺れで区間が大きく変わらないよう、なだらかに追う
+        state.seconds_per_second = Some(match state.seconds_per_second {
+            Some(previous) => previous * 0.7 + rate * 0.3,
+            None => rate,
+        });
+        state.segments += 1;
+    }
+
+    /// 直近のリアルタイム変換で最も遅かったもの
+    fn worst_live(&self) -> Duration {
+        self.state().live.iter().max().copied().unwrap_or_default()
+    }
+
+    /// リアルタイム変換に余裕があるか（記録がなければある）
+    pub fn live_has_headroom(&self) -> bool {
+        self.worst_live().as_secs_f64() <= self.chunk.as_secs_f64() * LIVE_HEADROOM
+    }
+
+    /// 次に送るバッチの区間の長さ（秒）
+    pub fn segment_seconds(&self) -> f64 {
+        let Some(rate) = self.state().seconds_per_second else {
+            return INITIAL_SEGMENT_SECONDS;
+        };
+        let slack = (self.chunk.saturating_sub(self.worst_live())).as_secs_f64();
+        (slack * SEGMENT_SHARE / rate.max(f64::EPSILON))
+            .clamp(MIN_SEGMENT_SECONDS, MAX_SEGMENT_SECONDS)
+    }
+
+    /// バッチの区間を送ってよくなるまで待つ（返した値を持っている間は次の区間を送らない）
+    pub async fn batch_permit(&self) -> MutexGuard<'_, ()> {
+        let permit = self.batch.lock().await;
+        let mut waiting = false;
+        while !self.live_has_headroom() {
+            if !waiting {
+                debug!(
+                    "リアルタイム変換の遅延が大きいため、バッチ変換を待ちます（{}ms / チャンク {}ms）",
+                    self.worst_live().as_millis(),
+                    self.chunk.as_millis()
+                );
+                self.state().waits += 1;
+                waiting = true;
+            }
+            tokio::time::sleep(self.chunk).await;
+        }
+        permit
+    }
+
+    /// 調停の結果を表示する
+    pub fn log_summary(&self) {
+        let state = self.state();
+        info!(
+            "バッチ変換の調停: {}区間を送信、リアルタイム変換のために{}回待機",
+            state.segments, state.waits
+        );
+    }
+}
@@ -1,190 +1,480 @@
-use anyhow::{Context, Result};
-use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
-use cpal::{Device, Host, Stream, StreamConfig};
-use std::sync::{Arc, Mutex};
-use tracing::{info, warn};
-
-/// 音声入力マネージャー
-pub struct AudioInput {
-    host: Host,
-    device: Device,
-    config: StreamConfig,
+use std::str::FromStr;
+use std::sync::atomic::{fence, AtomicBool, AtomicU32, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use crate::dsp::analysis;
+
+#[cfg(feature = "devices")]
+pub use crate::device::{available_hosts, list_devices, select_host, AudioInput, AudioOutput};
+
+/// クリップとみなすレベル（フルスケール比）
+pub const CLIP_THRESHOLD: f32 = 0.999;
+
+/// クリップの発生回数を数えるカウンター
+///
+/// クローンは同じカウンターを共有します（オーディオコールバックからの計測用）。
+#[derive(Clone, Default)]
+pub struct ClipCounter {
+    /// クリップしたサンプル数
+    samples: Arc<AtomicU64>,
+    /// クリップの発生回数（連続したクリップは1回と数える）
+    events: Arc<AtomicU64>,
+    /// 直前のブロックがクリップで終わったか
+    clipping: Arc<AtomicBool>,
 }
 
-impl AudioInput {
-    /// デフォルトの入力デバイスで初期化
-    pub fn new() -> Result<Self> {
-        let host = cpal::default_host();
-        let device = host
-            .default_input_device()
-            .context("入力デバイスが見つかりません")?;
-
-        let config = device
-            .default_input_config()
-            .context("入力デバイスの設定取得エラー")?
-            .into();
-
-        info!("入力デバイス: {}", device.name()?);
-
-        Ok(Self {
-            host,
-            device,
-            config,
-        })
-    }
-
-    /// 音声ストリームを開始
-    pub fn start_stream<F>(&self, mut callback: F) -> Result<Stream>
-    where
-        F: FnMut(&[f32]) + Send + 'static,
-    {
-        let stream = self.device.build_input_stream(
-            &self.config,
-            move |data: &[f32], _: &cpal::InputCallbackInfo| {
-                callback(data);
-            },
-            |err| {
-                warn!("音声入力エラー: {}", err);
-            },
-            None,
-        )?;
-
-        stream.play()?;
-        info!("音声入力ストリーム開始");
-
-        Ok(stream)
+impl ClipCounter {
+    pub fn new() -> Self {
+        Self::default()
     }
 
-    pub fn sample_rate(&self) -> u32 {
-        self.config.sample_rate.0
-    }
+    /// ブロックを計測する
+    pub fn observe(&self, samples: &[f32]) {
+        let mut clipping = self.clipping.load(Ordering::Relaxed);
+        let mut clipped = 0;
+        let mut events = 0;
+
+        for &sample in samples {
+            let is_clip = sample.abs() >= CLIP_THRESHOLD;
+            if is_clip {
+                clipped += 1;
+                if !clipping {
+                    events += 1;
+                }
+            }
+            clipping = is_clip;
+        }
 
-    pub fn channels(&self) -> u16 {
-        self.config.channels
+        self.clipping.store(clipping, Ordering::Relaxed);
+        if clipped > 0 {
+            self.samples.fetch_add(clipped, Ordering::Relaxed);
+            self.events.fetch_add(events, Ordering::Relaxed);
+        }
     }
-}
-
-/// 音声出力マネージャー
-pub struct AudioOutput {
-    host: Host,
-    device: Device,
-    config: StreamConfig,
-}
 
-impl AudioOutput {
-    /// デフォルトの出力デバイスで初期化
-    pub fn new() -> Result<Self> {
-        let host = cpal::default_host();
-        let device = host
-            .default_output_device()
-            .context("出力デバイスが見つかりません")?;
-
-        let config = device
-            .default_output_config()
-            .context("出力デバイスの設定取得エラー")?
-            .into();
-
-        info!("出力デバイス: {}", device.name()?);
-
-        Ok(Self {
-            host,
-            device,
-            config,
-        })
-    }
-
-    /// 音声ストリームを開始
-    pub fn start_stream<F>(&self, mut callback: F) -> Result<Stream>
-    where
-        F: FnMut(&mut [f32]) + Send + 'static,
-    {
-        let stream = self.device.build_output_stream(
-            &self.config,
-            move |data: &mut [f32], _: &cpal::OutputCallbackInfo| {
-                callback(data);
-            },
-            |err| {
-                warn!("音声出力エラー: {}", err);
-            },
-            None,
-        )?;
-
-        stream.play()?;
-        info!("音声出力ストリーム開始");
-
-        Ok(stream)
+    /// クリップの発生回数
+    pub fn events(&self) -> u64 {
+        self.events.load(Ordering::Relaxed)
     }
 
-    pub fn sample_rate(&self) -> u32 {
-        self.config.sample_rate.0
+    /// クリップしたサンプル数
+    pub fn samples(&self) -> u64 {
+        self.samples.load(Ordering::Relaxed)
     }
 }
 
-/// 音声バッファ（リングバッファ）
+/// 音声バッファ（ロックフリーのリングバッファ）
+///
+/// 音声コールバックがロック待ちで止まらないよう、位置の受け渡しはアトミック変数だけで行います。
+/// 書き込み（`push`）は1つのスレッドからだけ行ってください（入力コールバックか変換ループ）。
+/// 読み出し（`fill`・`take`・`peek`）と `clear` は、書き込みと並行してどのスレッドからでも行えます。
+/// いっぱいのときは古いデータから捨て、捨てた回数と量を数えます。
+///
+/// クローンは同じバッファを共有します（入出力コールバックとの受け渡し用）。
+#[derive(Clone)]
 pub struct AudioBuffer {
-    buffer: Arc<Mutex<Vec<f32>>>,
-    capacity: usize,
+    ring: Arc<Ring>,
+}
+
+struct Ring {
+    /// サンプル（f32のビット列。書き込みと読み出しが重なっても未定義動作にならないようアトミックにする）
+    slots: Box<[AtomicU32]>,
+    /// 読み出し位置（増える一方で、`slots` の長さで割った余りが添字）
+    read: AtomicUsize,
+    /// 書き込み位置（書き込み側だけが進める）
+    write: AtomicUsize,
+    /// あふれて捨てたサンプル数
+    dropped: AtomicU64,
+    /// あふれた回数
+    overruns: AtomicU64,
+    /// 出力先を埋めきれなかった回数
+    underruns: AtomicU64,
+    /// 直前の `fill` で出力先を埋めきれなかったか（続けて足りないときは1回と数える）
+    starved: AtomicBool,
 }
 
 impl AudioBuffer {
     pub fn new(capacity: usize) -> Self {
         Self {
-            buffer: Arc::new(Mutex::new(Vec::with_capacity(capacity))),
-            capacity,
+            ring: Arc::new(Ring {
+                slots: (0..capacity.max(1)).map(|_| AtomicU32::new(0)).collect(),
+                read: AtomicUsize::new(0),
+                write: AtomicUsize::new(0),
+                dropped: AtomicU64::new(0),
+                overruns: AtomicU64::new(0),
+                underruns: AtomicU64::new(0),
+                // 再生を始める前の空は数えない
+                starved: AtomicBool::new(true),
+            }),
         }
     }
 
-    /// データを追加
+    /// データを追加（入りきらなければ古いデータを捨てる）
     pub fn push(&self, data: &[f32]) {
-        let mut buffer = self.buffer.lock().unwrap();
+        let ring = &self.ring;
+        let capacity = ring.slots.len();
+
+        // 容量より長ければ末尾だけ残す
+        let data = if data.len() > capacity {
+            ring.dropped
+                .fetch_add((data.len() - capacity) as u64, Ordering::Relaxed);
+            ring.overruns.fetch_add(1, Ordering::Relaxed);
+            &data[data.len() - capacity..]
+        } else {
+            data
+        };
+
+        // 容量チェック（読み出し位置を進めて古いデータを削除）
+        let write = ring.write.load(Ordering::Relaxed);
+        loop {
+            let read = ring.read.load(Ordering::Acquire);
+            let overflow = (write - read + data.len()).saturating_sub(capacity);
+            if overflow == 0 {
+                break;
+            }
+            if ring
+                .read
+                .compare_exchange(read, read + overflow, Ordering::AcqRel, Ordering::Acquire)
+                .is_ok()
+            {
+                ring.dropped.fetch_add(overflow as u64, Ordering::Relaxed);
+                ring.overruns.fetch_add(1, Ordering::Relaxed);
+                break;
+            }
+        }
+        // 捨てた場所を読んでいる途中の読み手が、書き換えに気づけるようにする
+        fence(Ordering::Release);
 
-        // 容量チェック
-        if buffer.len() + data.len() > self.capacity {
-            // 古いデータを削除
-            let overflow = buffer.len() + data.len() - self.capacity;
-            buffer.drain(0..overflow);
+        for (i, sample) in data.iter().enumerate() {
+            ring.slots[(write + i) % capacity].store(sample.to_bits(), Ordering::Relaxed);
         }
+        ring.write.store(write + data.len(), Ordering::Release);
+    }
 
-        buffer.extend_from_slice(data);
+    /// 先頭から `out` に写す（`advance` なら読み出し位置も進める）。`min` に満たなければ何もしない
+    ///
+    /// 写している間にほかの読み手か書き込みのあふれで位置が動いたら、写し直します。
+    fn copy_out(&self, out: &mut [f32], min: usize, advance: bool) -> usize {
+        let ring = &self.ring;
+        let capacity = ring.slots.len();
+        loop {
+            let read = ring.read.load(Ordering::Acquire);
+            let write = ring.write.load(Ordering::Acquire);
+            let len = write.saturating_sub(read).min(out.len());
+            if len < min {
+                return 0;
+            }
+            for (i, dst) in out[..len].iter_mut().enumerate() {
+                *dst = f32::from_bits(ring.slots[(read + i) % capacity].load(Ordering::Relaxed));
+            }
+            fence(Ordering::Acquire);
+
+            let unchanged = if advance {
+                ring.read
+                    .compare_exchange(read, read + len, Ordering::AcqRel, Ordering::Acquire)
+                    .is_ok()
+            } else {
+                ring.read.load(Ordering::Relaxed) == read
+            };
+            if unchanged {
+                return len;
+            }
+        }
     }
 
     /// データを取得してクリア
     pub fn take(&self, len: usize) -> Vec<f32> {
-        let mut buffer = self.buffer.lock().unwrap();
-
-        if buffer.len() >= len {
-            let data = buffer.drain(0..len).collect();
-            data
-        } else {
+        let mut data = vec![0.0; len];
+        if self.copy_out(&mut data, len, true) < len {
             // データ不足の場合は空
-            Vec::new()
+            return Vec::new();
+        }
+        data
+    }
+
+    /// 先頭から最大 `len` サンプルを取り出さずにコピー
+    pub fn peek(&self, len: usize) -> Vec<f32> {
+        let mut data = vec![0.0; len.min(self.len())];
+        let copied = self.copy_out(&mut data, 0, false);
+        data.truncate(copied);
+        data
+    }
+
+    /// 出力先を埋める（不足分は無音で埋める）。実際に書き込んだサンプル数を返す
+    pub fn fill(&self, out: &mut [f32]) -> usize {
+        let available = self.copy_out(out, 0, true);
+        out[available..].fill(0.0);
+
+        let starved = available < out.len();
+        if starved && !self.ring.starved.swap(true, Ordering::Relaxed) {
+            self.ring.underruns.fetch_add(1, Ordering::Relaxed);
+        } else if !starved {
+            self.ring.starved.store(false, Ordering::Relaxed);
         }
+
+        available
+    }
+
+    /// あふれて捨てたサンプル数の累計
+    pub fn dropped(&self) -> u64 {
+        self.ring.dropped.load(Ordering::Relaxed)
+    }
+
+    /// あふれた回数の累計
+    pub fn overruns(&self) -> u64 {
+        self.ring.overruns.load(Ordering::Relaxed)
+    }
+
+    /// 出力先を埋めている途中でデータが尽きた回数の累計（続けて足りない間は1回と数える）
+    pub fn underruns(&self) -> u64 {
+        self.ring.underruns.load(Ordering::Relaxed)
     }
 
     /// バッファ内のデータ量
     pub fn len(&self) -> usize {
-        self.buffer.lock().unwrap().len()
+        let read = self.ring.read.load(Ordering::Acquire);
+        let write = self.ring.write.load(Ordering::Acquire);
+        write.saturating_sub(read)
+    }
+
+    /// バッファが空かどうか
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
     }
 
     /// バッファをクリア
     pub fn clear(&self) {
-        self.buffer.lock().unwrap().clear();
+        let write = self.ring.write.load(Ordering::Acquire);
+        self.ring.read.fetch_max(write, Ordering::AcqRel);
     }
 }
 
-/// 利用可能なデバイス一覧を表示
-pub fn list_devices() -> Result<()> {
-    let host = cpal::default_host();
+/// インターリーブ音声のチャンネル数を変換
+///
+/// モノラルにするときは全チャンネルを平均し、それ以外は不足チャンネルに最終チャンネルを複製します。
+pub fn remap_channels(samples: &[f32], from: u16, to: u16) -> Vec<f32> {
+    if from == to || from == 0 {
+        return samples.to_vec();
+    }
+    if to == 1 {
+        return analysis::downmix(samples, from);
+    }
+
+    let (from, to) = (from as usize, to as usize);
+    let mut out = Vec::with_capacity(samples.len() / from * to);
+    for frame in samples.chunks_exact(from) {
+        for channel in 0..to {
+            out.push(frame[channel.min(from - 1)]);
+        }
+    }
+    out
+}
+
+/// 変換に送る入力チャンネルの選び方（`--channel`）
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ChannelSelect {
+    /// 全チャンネルを平均してモノラルにする
+    #[default]
+    Mix,
+    /// 1つのチャンネルだけを使う（0始まり）
+    Index(u16),
+    /// デバイスのチャンネル数のまま送る
+    All,
+}
+
+impl ChannelSelect {
+    /// 選んだ後のチャンネル数
+    pub fn channels(self, from: u16) -> u16 {
+        match self {
+            Self::All => from,
+            Self::Mix | Self::Index(_) => 1,
+        }
+    }
 
-    println!("入力デバイス:");
-    for device in host.input_devices()? {
-        println!("  - {}", device.name()?);
+    /// インターリーブ音声から選ぶ（そのまま使えるならNone）
+    ///
+    /// チャンネル番号がデバイスのチャンネル数を超えるときは最終チャンネルを使います。
+    pub fn apply(self, samples: &[f32], from: u16) -> Option<Vec<f32>> {
+        if self.channels(from) == from {
+            return None;
+        }
+        match self {
+            Self::All => None,
+            Self::Mix => Some(analysis::downmix(samples, from)),
+            Self::Index(index) => {
+                let index = index.min(from - 1) as usize;
+                Some(
+                    samples
+                        .chunks_exact(from as usize)
+                        .map(|frame| frame[index])
+                        .collect(),
+                )
+            }
+        }
+    }
+}
+
+impl FromStr for ChannelSelect {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s.to_lowercase().as_str() {
+            "mix" => Ok(Self::Mix),
+            "all" => Ok(Self::All),
+            "left" => Ok(Self::Index(0)),
+            "right" => Ok(Self::Index(1)),
+            n => match n.parse::<u16>() {
+                Ok(n) if n >= 1 => Ok(Self::Index(n - 1)),
+                _ => anyhow::bail!(
+                    "不明なチャンネル指定: {}（mix, left, right, all または1始まりの番号）",
+                    s
+                ),
+            },
+        }
     }
+}
 
-    println!("\n出力デバイス:");
-    for device in host.output_devices()? {
-        println!("  - {}", device.name()?);
+impl std::fmt::Display for ChannelSelect {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Mix => f.write_str("mix"),
+            Self::All => f.write_str("all"),
+            Self::Index(0) => f.write_str("left"),
+            Self::Index(1) => f.write_str("right"),
+            Self::Index(index) => write!(f, "{}", index + 1),
+        }
     }
+}
 
-    Ok(())
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn counting(range: std::ops::Range<u32>) -> Vec<f32> {
+        range.map(|i| i as f32).collect()
+    }
+
+    #[test]
+    fn wraps_around_the_end_of_the_ring() {
+        let buffer = AudioBuffer::new(8);
+        for round in 0..5u32 {
+            let data = counting(round * 6..round * 6 + 6);
+            buffer.push(&data);
+            assert_eq!(buffer.take(6), data);
+        }
+        assert!(buffer.is_empty());
+        assert_eq!((buffer.dropped(), buffer.overruns()), (0, 0));
+    }
+
+    #[test]
+    fn overflow_drops_the_oldest_samples() {
+        let buffer = AudioBuffer::new(8);
+        buffer.push(&counting(0..6));
+        buffer.push(&counting(6..11));
+        assert_eq!((buffer.dropped(), buffer.overruns()), (3, 1));
+        assert_eq!(buffer.take(8), counting(3..11));
+
+        // 容量より長いときは末尾だけを残す
+        buffer.push(&counting(0..20));
+        assert_eq!((buffer.dropped(), buffer.overruns()), (15, 2));
+        assert_eq!(buffer.take(8), counting(12..20));
+    }
+
+    #[test]
+    fn take_returns_nothing_until_enough_is_buffered() {
+        let buffer = AudioBuffer::new(8);
+        buffer.push(&counting(0..3));
+        assert!(buffer.take(4).is_empty());
+        assert_eq!(buffer.len(), 3);
+    }
+
+    #[test]
+    fn peek_does_not_advance() {
+        let buffer = AudioBuffer::new(8);
+        buffer.push(&counting(0..5));
+        assert_eq!(buffer.peek(3), counting(0..3));
+        assert_eq!(buffer.peek(10), counting(0..5));
+        assert_eq!(buffer.len(), 5);
+        assert_eq!(buffer.take(5), counting(0..5));
+    }
+
+    #[test]
+    fn underruns_count_once_per_starvation() {
+        let buffer = AudioBuffer::new(16);
+        let mut out = [1.0f32; 4];
+
+        // 再生を始める前の空は数えない
+        assert_eq!(buffer.fill(&mut out), 0);
+        assert_eq!(buffer.underruns(), 0);
+
+        buffer.push(&counting(1..7));
+        assert_eq!(buffer.fill(&mut out), 4);
+        assert_eq!(buffer.underruns(), 0);
+
+        // 足りない分は無音で埋め、続けて足りない間は1回と数える
+        assert_eq!(buffer.fill(&mut out), 2);
+        assert_eq!(out, [5.0, 6.0, 0.0, 0.0]);
+        buffer.fill(&mut out);
+        buffer.fill(&mut out);
+        assert_eq!(buffer.underruns(), 1);
+
+        buffer.push(&counting(0..4));
+        buffer.fill(&mut out);
+        buffer.fill(&mut out);
+        assert_eq!(buffer.underruns(), 2);
+    }
+
+    #[test]
+    fn clear_discards_buffered_samples() {
+        let buffer = AudioBuffer::new(8);
+        buffer.push(&counting(0..5));
+        buffer.clear();
+        assert!(buffer.is_empty());
+        buffer.push(&counting(5..7));
+        assert_eq!(buffer.take(2), counting(5..7));
+    }
+
+    /// 書き込みと読み出しを別のスレッドで並行させても、順序が入れ替わったり値が壊れたりせず、
+    /// 読めたサンプルと捨てたサンプルを合わせると書いたサンプルと一致する
+    #[test]
+    fn concurrent_push_and_fill_keep_order() {
+        const TOTAL: u32 = 1 << 20;
+        let buffer = AudioBuffer::new(1024);
+        let writer = {
+            let buffer = buffer.clone();
+            std::thread::spawn(move || {
+                let mut next = 0;
+                while next < TOTAL {
+                    let end = (next + 97).min(TOTAL);
+                    buffer.push(&counting(next..end));
+                    next = end;
+                    if next % 4096 < 97 {
+                        std::thread::yield_now();
+                    }
+                }
+            })
+        };
+
+        let mut received = 0u64;
+        let mut last = -1.0f32;
+        let mut out = [0.0f32; 61];
+        let mut drain = |buffer: &AudioBuffer| {
+            let n = buffer.fill(&mut out);
+            for &sample in &out[..n] {
+                assert!(sample > last, "{} の後に {}", last, sample);
+                assert_eq!(sample.fract(), 0.0);
+                last = sample;
+            }
+            received += n as u64;
+            n
+        };
+        while !writer.is_finished() {
+            drain(&buffer);
+        }
+        writer.join().unwrap();
+        while drain(&buffer) > 0 {}
+
+        assert_eq!(last, (TOTAL - 1) as f32);
+        assert_eq!(received + buffer.dropped(), TOTAL as u64);
+    }
 }
@@ -0,0 +1,130 @@
+#[cfg(feature = "keychain")]
+use anyhow::Context;
+use anyhow::Result;
+#[cfg(feature = "keychain")]
+use std::collections::BTreeMap;
+#[cfg(feature = "keychain")]
+use std::sync::{Mutex, OnceLock};
+#[cfg(feature = "keychain")]
+use tracing::debug;
+
+/// キーチェーンに保存するときのサービス名
+#[cfg(feature = "keychain")]
+const SERVICE: &str = "makebeliv";
+
+/// APIキーを直接渡す環境変数（キーチェーンより優先する。CIやコンテナ向け）
+pub const API_KEY_ENV: &str = "MAKEBELIV_API_KEY";
+
+#[cfg(not(feature = "keychain"))]
+const UNAVAILABLE: &str =
+    "APIキーをキーチェーンに保存するには keychain フィーチャーを有効にしてビルドしてください（環境変数 MAKEBELIV_API_KEY で渡せます）";
+
+/// 一度調べたAPIキー（macOSではキーチェーンを読むたびに確認が出ることがあるため）
+#[cfg(feature = "keychain")]
+static CACHE: OnceLock<Mutex<BTreeMap<String, Option<String>>>> = OnceLock::new();
+
+/// このビルドでキーチェーンを使えるか確かめる（`auth login` でキーを入力してもらう前に）
+pub fn ensure_available() -> Result<()> {
+    #[cfg(not(feature = "keychain"))]
+    anyhow::bail!(UNAVAILABLE);
+    #[cfg(feature = "keychain")]
+    Ok(())
+}
+
+/// 同じサーバーを同じ項目にする（末尾の / を除く）
+#[cfg(feature = "keychain")]
+fn account(api_url: &str) -> &str {
+    api_url.trim_end_matches('/')
+}
+
+#[cfg(feature = "keychain")]
+fn entry(api_url: &str) -> Result<keyring::Entry> {
+    keyring::Entry::new(SERVICE, account(api_url)).context("キーチェーンを開けません")
+}
+
+/// APIキーをOSのキーチェーンに保存する（`auth login`）
+#[cfg(feature = "keychain")]
+pub fn store(api_url: &str, key: &str) -> Result<()> {
+    entry(api_url)?
+        .set_password(key)
+        .with_context(|| {
+            format!(
+                "APIキーをキーチェーンに保存できません（キーチェーンが使えない環境では環境変数 {} で渡せます）",
+                API_KEY_ENV
+            )
+        })?;
+    forget(api_url);
+    Ok(())
+}
+
+#[cfg(not(feature = "keychain"))]
+pub fn store(_api_url: &str, _key: &str) -> Result<()> {
+    ensure_available()
+}
+
+/// 保存したAPIキーを削除する（保存されていなければfalse）
+#[cfg(feature = "keychain")]
+pub fn delete(api_url: &str) -> Result<bool> {
+    let deleted = match entry(api_url)?.delete_credential() {
+        Ok(()) => true,
+        Err(keyring::Error::NoEntry) => false,
+        Err(e) => return Err(e).context("APIキーをキーチェーンから削除できません"),
+    };
+    forget(api_url);
+    Ok(deleted)
+}
+
+#[cfg(not(feature = "keychain"))]
+pub fn delete(_api_url: &str) -> Result<bool> {
+    ensure_available().map(|()| false)
+}
+
+#[cfg(feature = "keychain")]
+fn forget(api_url: &str) {
+    if let Some(cache) = CACHE.get() {
+        cache
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(account(api_url));
+    }
+}
+
+/// サーバーに送るAPIキー（環境変数、キーチェーンの順に探し、なければNone）
+///
+/// キーチェーンが使えない環境（Secret Serviceのないサーバーなど）では、キーなしで続けます。
+pub fn api_key(api_url: &str) -> Option<String> {
+    if let Some(key) = std::env::var(API_KEY_ENV)
+        .ok()
+        .filter(|key| !key.is_empty())
+    {
+        return Some(key);
+    }
+    keychain_key(api_url)
+}
+
+#[cfg(not(feature = "keychain"))]
+fn keychain_key(_api_url: &str) -> Option<String> {
+    None
+}
+
+#[cfg(feature = "keychain")]
+fn keychain_key(api_url: &str) -> Option<String> {
+    let mut cache = CACHE
+        .get_or_init(Mutex::default)
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    cache
+        .entry(account(api_url).to_string())
+        .or_insert_with(
+            || match entry(api_url).and_then(|entry| Ok(entry.get_password()?)) {
+                Ok(key) => Some(key),
+                Err(e) => {
+                    if !matches!(e.downcast_ref(), Some(keyring::Error::NoEntry)) {
+                        debug!("キーチェーンからAPIキーを読めません: {:#}", e);
+                    }
+                    None
+                }
+            },
+        )
+        .clone()
+}
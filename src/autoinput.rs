@@ -0,0 +1,156 @@
+use anyhow::{Context, Result};
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use std::time::Duration;
+use tracing::{debug, info};
+
+use crate::audio::AudioBuffer;
+use crate::bluetooth;
+use crate::device;
+use crate::dsp::analysis;
+
+/// デバイスごとに入力レベルを測る時間
+const PROBE_DURATION: Duration = Duration::from_millis(500);
+
+/// 実際に音が入っていると判定するレベル
+const SIGNAL_THRESHOLD_DB: f32 = -60.0;
+
+/// 名前に含まれていれば加点するキーワード（小文字）
+const PREFERRED: &[(&str, i32)] = &[("headset", 30), ("usb", 20), ("mic", 10)];
+
+/// 名前に含まれていれば減点するキーワード（小文字）
+const AVOIDED: &[(&str, i32)] = &[
+    ("makebeliv", -100), // 自分の仮想マイク
+    ("monitor", -50),    // PulseAudio/PipeWireのモニターソース
+    ("loopback", -50),
+    ("stereo mix", -50),
+    ("what u hear", -50),
+    ("cable output", -50),
+    ("blackhole", -50),
+    ("null", -50),
+    ("cam", -30), // webcam, camera
+    ("hdmi", -40),
+    ("displayport", -40),
+];
+
+/// デフォルトデバイスへの加点（同点のときに優先する）
+const DEFAULT_BONUS: i32 = 5;
+const SIGNAL_BONUS: i32 = 25;
+const SILENCE_PENALTY: i32 = -20;
+
+/// 入力デバイスの候補
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    pub name: String,
+    /// 測定した入力レベル（開けなかった場合はNone）
+    pub level_db: Option<f32>,
+    pub score: i32,
+}
+
+/// デバイス名から点数を付ける
+fn name_score(name: &str) -> i32 {
+    let name = name.to_lowercase();
+    PREFERRED
+        .iter()
+        .chain(AVOIDED)
+        .filter(|(keyword, _)| name.contains(keyword))
+        .map(|(_, score)| score)
+        .sum()
+}
+
+/// 短時間録音して入力レベルを測る
+fn probe_level(device: &cpal::Device) -> Result<f32> {
+    let config: cpal::StreamConfig = device.default_input_config()?.into();
+    let captured = AudioBuffer::new(config.sample_rate.0 as usize * config.channels as usize * 2);
+    let buffer = captured.clone();
+
+    let stream = device.build_input_stream(
+        &config,
+        move |data: &[f32], _: &cpal::InputCallbackInfo| buffer.push(data),
+        |err| debug!("入力レベル測定エラー: {}", err),
+        None,
+    )?;
+    stream.play()?;
+    std::thread::sleep(PROBE_DURATION);
+    drop(stream);
+
+    Ok(analysis::rms_db(&captured.take(captured.len())))
+}
+
+/// すべての入力デバイスを採点する（点数の高い順）
+pub fn score_inputs() -> Result<Vec<Candidate>> {
+    let host = device::host()?;
+    let default_name = host.default_input_device().and_then(|d| d.name().ok());
+
+    let mut candidates = Vec::new();
+    for device in host.input_devices()? {
+        let Ok(name) = device.name() else {
+            continue;
+        };
+
+        let mut score = name_score(&name);
+        if default_name.as_deref() == Some(name.as_str()) {
+            score += DEFAULT_BONUS;
+        }
+
+        let level_db = match probe_level(&device) {
+            Ok(level_db) => {
+                // analysis::SILENCE_DB はデジタル無音（ミュート・未接続）
+                if level_db > SIGNAL_THRESHOLD_DB {
+                    score += SIGNAL_BONUS;
+                } else if level_db <= analysis::SILENCE_DB {
+                    score += SILENCE_PENALTY;
+                }
+                Some(level_db)
+            }
+            Err(e) => {
+                debug!("入力デバイスを開けません: {} ({})", name, e);
+                None
+            }
+        };
+
+        candidates.push(Candidate {
+            name,
+            level_db,
+            score,
+        });
+    }
+
+    // 開けなかったデバイスは最後に回す
+    candidates.sort_by_key(|c| (c.level_db.is_none(), -c.score));
+    Ok(candidates)
+}
+
+/// 最も良さそうな入力デバイスを選ぶ（`--input auto`）
+pub fn select_input() -> Result<String> {
+    select_input_where(|_| true)
+}
+
+/// Bluetooth以外で最も良さそうな入力デバイスを選ぶ（`--bt-a2dp`）
+///
+/// Bluetoothのマイクを使うとヘッドセットが通話用プロファイルに切り替わるため、別のマイクを使います。
+pub fn select_non_bluetooth_input() -> Result<String> {
+    select_input_where(|name| !bluetooth::is_bluetooth_name(name))
+}
+
+fn select_input_where(filter: impl Fn(&str) -> bool) -> Result<String> {
+    info!("入力デバイスを自動選択中...");
+    let candidates: Vec<_> = score_inputs()?
+        .into_iter()
+        .filter(|c| filter(&c.name))
+        .collect();
+
+    for c in &candidates {
+        let level = c
+            .level_db
+            .map_or_else(|| "開けません".to_string(), |db| format!("{:.1} dBFS", db));
+        info!("  {:>5}点  {:<14} {}", c.score, level, c.name);
+    }
+
+    let best = candidates
+        .into_iter()
+        .find(|c| c.level_db.is_some())
+        .context("使用できる入力デバイスがありません")?;
+    info!("  → {}", best.name);
+
+    Ok(best.name)
+}
@@ -0,0 +1,196 @@
+use anyhow::Result;
+use std::any::Any;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::virtual_audio::{VirtualInput, VirtualOutput};
+
+/// 入力コールバック（インターリーブのf32サンプル）
+pub type InputCallback = Box<dyn FnMut(&[f32]) + Send>;
+
+/// 出力コールバック（渡されたバッファを埋める）
+pub type OutputCallback = Box<dyn FnMut(&mut [f32]) + Send>;
+
+/// 動作中のストリーム（ドロップで停止）
+pub struct AudioStream(#[allow(dead_code)] Box<dyn Any>);
+
+impl AudioStream {
+    /// バックエンド固有のストリームを包む
+    pub fn new<T: 'static>(stream: T) -> Self {
+        Self(Box::new(stream))
+    }
+}
+
+/// 入力デバイス
+pub trait InputDevice {
+    /// 音声ストリームを開始
+    fn start_stream(&self, callback: InputCallback) -> Result<AudioStream>;
+
+    /// ストリームの作り直しが必要か
+    fn needs_rebuild(&self) -> bool;
+
+    /// デバイスの現在の設定を読み直す（ストリームは呼び出し側で作り直す）
+    fn refresh(&mut self) -> Result<()>;
+
+    /// コールバックあたりのフレーム数を要求する（次に開始するストリームから有効）
+    fn request_buffer_frames(&mut self, frames: u32);
+
+    fn name(&self) -> String;
+
+    fn sample_rate(&self) -> u32;
+
+    fn channels(&self) -> u16;
+
+    /// 入力が終わったか（ファイル入力の終端など。マイクは終わらない）
+    fn is_finished(&self) -> bool {
+        false
+    }
+
+    /// デバイスが報告した、音を取り込んでからコールバックに届くまでの遅延（分からなければNone）
+    fn reported_latency(&self) -> Option<Duration> {
+        None
+    }
+}
+
+/// 出力デバイス
+pub trait OutputDevice {
+    /// 音声ストリームを開始
+    fn start_stream(&self, callback: OutputCallback) -> Result<AudioStream>;
+
+    /// ストリームの作り直しが必要か
+    fn needs_rebuild(&self) -> bool;
+
+    /// デバイスの現在の設定を読み直す（ストリームは呼び出し側で作り直す）
+    fn refresh(&mut self) -> Result<()>;
+
+    /// コールバックあたりのフレーム数を要求する（次に開始するストリームから有効）
+    fn request_buffer_frames(&mut self, frames: u32);
+
+    fn name(&self) -> String;
+
+    fn sample_rate(&self) -> u32;
+
+    fn channels(&self) -> u16;
+
+    /// デバイスが報告した、コールバックで書き込んでから鳴るまでの遅延（分からなければNone）
+    fn reported_latency(&self) -> Option<Duration> {
+        None
+    }
+
+    /// 停止前に出力バッファを再生し切る必要があるか（書き出す音声を欠かさないため）
+    fn drain_on_stop(&self) -> bool {
+        false
+    }
+
+    /// ストリーム停止後の後処理（ファイルへの書き出しなど）
+    fn finish(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// 入出力デバイスを開くバックエンド
+pub trait AudioBackend: Sync {
+    /// バックエンド名（ログ表示用）
+    fn name(&self) -> &'static str;
+
+    /// 入力デバイスを開く（Noneならデフォルト）
+    fn open_input(&self, device: Option<&str>) -> Result<Box<dyn InputDevice>>;
+
+    /// 出力デバイスを開く（Noneならデフォルト）
+    fn open_output(&self, device: Option<&str>) -> Result<Box<dyn OutputDevice>>;
+}
+
+/// cpalのデバイス（ALSA, JACK, WASAPI, CoreAudio など。ホストAPIは `--audio-host` で選択）
+#[cfg(feature = "devices")]
+pub struct CpalBackend;
+
+#[cfg(feature = "devices")]
+impl AudioBackend for CpalBackend {
+    fn name(&self) -> &'static str {
+        "cpal"
+    }
+
+    fn open_input(&self, device: Option<&str>) -> Result<Box<dyn InputDevice>> {
+        Ok(Box::new(crate::device::AudioInput::open(device)?))
+    }
+
+    fn open_output(&self, device: Option<&str>) -> Result<Box<dyn OutputDevice>> {
+        Ok(Box::new(crate::device::AudioOutput::open(device)?))
+    }
+}
+
+/// WAVファイルを実時間で入出力する仮想デバイス（サウンドハードウェアなしでの実行・CI用）
+///
+/// デバイス名はファイルパスです。入力には既存のWAV、出力には書き出し先を指定します。
+pub struct WavBackend;
+
+impl AudioBackend for WavBackend {
+    fn name(&self) -> &'static str {
+        "wav"
+    }
+
+    fn open_input(&self, device: Option<&str>) -> Result<Box<dyn InputDevice>> {
+        let path = device.ok_or_else(|| anyhow::anyhow!("WAV入力にはファイルパスが必要です"))?;
+        Ok(Box::new(VirtualInput::open(Path::new(path))?))
+    }
+
+    fn open_output(&self, device: Option<&str>) -> Result<Box<dyn OutputDevice>> {
+        let path = device.ok_or_else(|| anyhow::anyhow!("WAV出力にはファイルパスが必要です"))?;
+        Ok(Box::new(VirtualOutput::new(Path::new(path))))
+    }
+}
+
+/// WAVファイルを実時間で流して入力にし、再生した音声を共有のバッファに集めるバックエンド（テスト用）
+///
+/// [`crate::RealtimePipeline::with_backend`] に渡すと、入力の指定と出力デバイスの代わりに使われます。
+/// 入力のデバイス名を指定しなければ、作るときに渡したファイルを流します。
+pub struct CaptureBackend {
+    input: PathBuf,
+    played: Arc<Mutex<Vec<f32>>>,
+}
+
+impl CaptureBackend {
+    pub fn new(input: &Path) -> Self {
+        Self {
+            input: input.to_path_buf(),
+            played: Arc::default(),
+        }
+    }
+
+    /// 再生した音声（インターリーブ、[`crate::virtual_audio::VIRTUAL_OUTPUT_SAMPLE_RATE`] のステレオ）
+    pub fn played(&self) -> Arc<Mutex<Vec<f32>>> {
+        self.played.clone()
+    }
+}
+
+impl AudioBackend for CaptureBackend {
+    fn name(&self) -> &'static str {
+        "capture"
+    }
+
+    fn open_input(&self, device: Option<&str>) -> Result<Box<dyn InputDevice>> {
+        let path = device.map(Path::new).unwrap_or(&self.input);
+        Ok(Box::new(VirtualInput::open(path)?))
+    }
+
+    fn open_output(&self, _device: Option<&str>) -> Result<Box<dyn OutputDevice>> {
+        Ok(Box::new(VirtualOutput::shared(self.played.clone())))
+    }
+}
+
+/// デバイス指定に対応するバックエンドと、バックエンド内でのデバイス名
+///
+/// `wav:<パス>` はWAVバックエンド、`termux` はTermuxのバックエンド（`termux` 機能）、
+/// それ以外はcpalのデバイス名として扱います。
+#[cfg(feature = "devices")]
+pub fn resolve(device: Option<&str>) -> (&'static dyn AudioBackend, Option<&str>) {
+    #[cfg(feature = "termux")]
+    if device == Some(crate::termux::TERMUX_DEVICE) {
+        return (&crate::termux::TermuxBackend, None);
+    }
+    match device.and_then(|name| name.strip_prefix(crate::virtual_audio::VIRTUAL_PREFIX)) {
+        Some(path) => (&WavBackend, Some(path)),
+        None => (&CpalBackend, device),
+    }
+}
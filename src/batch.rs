@@ -0,0 +1,176 @@
+use anyhow::{Context, Result};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// バッチ処理の出力ファイル名の既定のテンプレート
+pub const DEFAULT_NAME_TEMPLATE: &str = "{stem}_{model}_{pitch}.wav";
+
+/// バッチ処理の既定の出力ディレクトリ
+pub const DEFAULT_OUTPUT_DIR: &str = "audio/output";
+
+/// 入力の指定を展開する
+///
+/// ディレクトリならその中のWAVファイル（サブディレクトリは見ない）、`*` などを含めば
+/// グロブに一致するファイルを名前順に返します。1つのファイルの指定ならNoneを返します。
+pub fn expand_inputs(input: &Path) -> Result<Option<Vec<PathBuf>>> {
+    let pattern = input.to_string_lossy();
+    let files: Vec<PathBuf> = if input.is_dir() {
+        std::fs::read_dir(input)
+            .with_context(|| format!("入力ディレクトリを読み込めません: {}", input.display()))?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.is_file() && is_wav(path))
+            .collect()
+    } else if is_glob(&pattern) {
+        glob::glob(&pattern)
+            .with_context(|| format!("入力のパターンが不正です: {}", pattern))?
+            .filter_map(|entry| entry.ok())
+            .filter(|path| path.is_file())
+            .collect()
+    } else {
+        return Ok(None);
+    };
+
+    anyhow::ensure!(
+        !files.is_empty(),
+        "入力に一致するWAVファイルがありません: {}",
+        input.display()
+    );
+    let mut files = files;
+    files.sort();
+    Ok(Some(files))
+}
+
+fn is_glob(pattern: &str) -> bool {
+    pattern.contains(['*', '?', '['])
+}
+
+pub(crate) fn is_wav(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("wav"))
+}
+
+/// テンプレートから出力ファイル名を作る
+///
+/// `{stem}`（入力の拡張子を除いた名前）、`{model}`、`{pitch}`（例: `+3`）が使えます。
+pub fn output_name(template: &str, input: &Path, model: &str, pitch: i32) -> Result<String> {
+    let stem = input
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    let mut name = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        name.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find('}')
+            .with_context(|| format!("テンプレートの {{ が閉じていません: {}", template))?;
+        match &rest[start + 1..start + end] {
+            "stem" => name.push_str(&stem),
+            "model" => name.push_str(model),
+            "pitch" => name.push_str(&format!("{:+}", pitch)),
+            other => anyhow::bail!(
+                "不明なテンプレートの項目: {{{}}}（{{stem}}, {{model}}, {{pitch}}）",
+                other
+            ),
+        }
+        rest = &rest[start + end + 1..];
+    }
+    name.push_str(rest);
+
+    anyhow::ensure!(
+        !name.is_empty() && !name.contains(['/', '\\']),
+        "出力ファイル名が不正です: {:?}（テンプレート: {}）",
+        name,
+        template
+    );
+    Ok(name)
+}
+
+/// 入力ごとの出力先を決める（同じ出力先になる入力があればエラー）
+pub fn plan_outputs(
+    inputs: &[PathBuf],
+    output_dir: &Path,
+    template: &str,
+    model: &str,
+    pitch: i32,
+) -> Result<Vec<(PathBuf, PathBuf)>> {
+    let mut seen = BTreeSet::new();
+    inputs
+        .iter()
+        .map(|input| {
+            let output = output_dir.join(output_name(template, input, model, pitch)?);
+            anyhow::ensure!(
+                seen.insert(output.clone()),
+                "出力先が重なります: {}（--name-template に {{stem}} を含めてください）",
+                output.display()
+            );
+            Ok((input.clone(), output))
+        })
+        .collect()
+}
+
+/// 1ファイルの結果
+struct BatchEntry {
+    input: PathBuf,
+    output: PathBuf,
+    elapsed: Duration,
+    error: Option<String>,
+}
+
+/// バッチ処理の結果（最後に一覧を表示する）
+#[derive(Default)]
+pub struct BatchReport {
+    entries: Vec<BatchEntry>,
+}
+
+impl BatchReport {
+    pub fn record(&mut self, input: &Path, output: &Path, elapsed: Duration, result: &Result<()>) {
+        self.entries.push(BatchEntry {
+            input: input.to_path_buf(),
+            output: output.to_path_buf(),
+            elapsed,
+            error: result.as_ref().err().map(|e| format!("{:#}", e)),
+        });
+    }
+
+    /// 変換が終わった（結果を記録した）ファイルの数
+    pub fn finished(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn failures(&self) -> usize {
+        self.entries.iter().filter(|e| e.error.is_some()).count()
+    }
+
+    /// 結果を入力の名前順に表示する（並列に変換すると終わった順に記録されるため）
+    pub fn print(&mut self) {
+        self.entries.sort_by(|a, b| a.input.cmp(&b.input));
+        let failures = self.failures();
+        println!(
+            "\n📋 バッチ処理の結果: 成功 {} / 失敗 {}",
+            self.entries.len() - failures,
+            failures
+        );
+
+        let width = self
+            .entries
+            .iter()
+            .map(|e| e.input.display().to_string().chars().count())
+            .max()
+            .unwrap_or(0);
+        for entry in &self.entries {
+            let input = entry.input.display().to_string();
+            match &entry.error {
+                None => println!(
+                    "  ✓ {:<width$}  → {}（{:.1}秒）",
+                    input,
+                    entry.output.display(),
+                    entry.elapsed.as_secs_f64()
+                ),
+                Some(error) => println!("  ✗ {:<width$}  {}", input, error),
+            }
+        }
+    }
+}
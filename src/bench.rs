@@ -0,0 +1,418 @@
+use anyhow::Result;
+use std::f32::consts::PI;
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::task::JoinSet;
+use tracing::{info, warn};
+
+use crate::client::{Codec, VoiceConversionClient};
+use crate::wav;
+
+/// ライブ使用の安全マージン（p95遅延がチャンク長のこの割合以下ならOK）
+pub(crate) const LIVE_SAFETY_MARGIN: f64 = 0.8;
+
+/// `bench --input` で試すチャンク長の既定値（ミリ秒）
+pub const DEFAULT_CHUNK_SIZES: &[u32] = &[100, 200, 300, 500];
+
+/// `bench --input` で試す同時ストリーム数の既定値
+pub const DEFAULT_CONCURRENCY: &[usize] = &[1, 2, 4];
+
+/// ベンチマーク用のサンプリングレート
+const BENCH_SAMPLE_RATE: u32 = 16000;
+
+/// ベンチマーク設定
+pub struct BenchConfig {
+    /// チャンク長（ミリ秒）
+    pub chunk_ms: u32,
+    /// 計測回数（ウォームアップを除く）
+    pub iterations: usize,
+}
+
+/// モデルごとの計測結果
+pub struct ModelBenchResult {
+    pub model: String,
+    /// 往復遅延（ミリ秒）
+    pub latencies_ms: Vec<f64>,
+    /// サーバー処理時間（ミリ秒）
+    pub server_ms: Vec<f64>,
+    pub errors: usize,
+}
+
+impl ModelBenchResult {
+    /// 往復遅延のパーセンタイル
+    pub fn latency_percentile(&self, p: f64) -> Option<f64> {
+        percentile(&self.latencies_ms, p)
+    }
+
+    /// リアルタイム係数（処理時間 / チャンク長）。1.0未満なら実時間より速い
+    pub fn real_time_factor(&self, chunk_ms: u32) -> Option<f64> {
+        let times = if self.server_ms.is_empty() {
+            &self.latencies_ms
+        } else {
+            &self.server_ms
+        };
+        mean(times).map(|t| t / chunk_ms as f64)
+    }
+
+    /// 指定チャンク長でライブ使用できるか
+    pub fn is_live_safe(&self, chunk_ms: u32) -> bool {
+        self.errors == 0
+            && self
+                .latency_percentile(95.0)
+                .is_some_and(|p95| p95 <= chunk_ms as f64 * LIVE_SAFETY_MARGIN)
+    }
+}
+
+/// 疑似音声のテスト信号を生成（ビブラート付きの倍音）
+fn test_signal(chunk_ms: u32) -> Vec<f32> {
+    let len = (BENCH_SAMPLE_RATE as u64 * chunk_ms as u64 / 1000) as usize;
+    let sr = BENCH_SAMPLE_RATE as f32;
+
+    (0..len)
+        .map(|i| {
+            let t = i as f32 / sr;
+            let f0 = 180.0 + 8.0 * (2.0 * PI * 5.0 * t).sin();
+            (1..=4)
+                .map(|h| 0.3 / h as f32 * (2.0 * PI * f0 * h as f32 * t).sin())
+                .sum()
+        })
+        .collect()
+}
+
+/// 1モデルを計測
+pub async fn bench_model(
+    client: &VoiceConversionClient,
+    model: &str,
+    config: &BenchConfig,
+) -> Result<ModelBenchResult> {
+    let chunk = wav::encode(&test_signal(config.chunk_ms), BENCH_SAMPLE_RATE, 1)?;
+    let session_id = format!("bench-{}", model);
+
+    // ウォームアップ（モデルロード時間を除外）
+    if let Err(e) = client
+        .convert_chunk(&chunk, Codec::Pcm, model, 0, &session_id)
+        .await
+    {
+        warn!("ウォームアップ失敗 ({}): {}", model, e);
+    }
+
+    let mut result = ModelBenchResult {
+        model: model.to_string(),
+        latencies_ms: Vec::with_capacity(config.iterations),
+        server_ms: Vec::with_capacity(config.iterations),
+        errors: 0,
+    };
+
+    for _ in 0..config.iterations {
+        let start = Instant::now();
+        match client
+            .convert_chunk(&chunk, Codec::Pcm, model, 0, &session_id)
+            .await
+        {
+            Ok(response) => {
+                result
+                    .latencies_ms
+                    .push(start.elapsed().as_secs_f64() * 1000.0);
+                if let Some(server_ms) = response.meta.processing_time_ms {
+                    result.server_ms.push(server_ms);
+                }
+            }
+            Err(e) => {
+                warn!("チャンク変換失敗 ({}): {}", model, e);
+                result.errors += 1;
+            }
+        }
+    }
+
+    client.reset_session(&session_id).await.ok();
+
+    Ok(result)
+}
+
+/// 全モデルを計測
+pub async fn run(
+    client: &VoiceConversionClient,
+    models: &[String],
+    config: &BenchConfig,
+) -> Result<Vec<ModelBenchResult>> {
+    let mut results = Vec::with_capacity(models.len());
+
+    for model in models {
+        info!("計測中: {}", model);
+        results.push(bench_model(client, model, config).await?);
+    }
+
+    Ok(results)
+}
+
+/// 音声ファイルを使ったスループット計測の設定（`bench --input`）
+pub struct SweepConfig {
+    /// 試すチャンク長（ミリ秒）
+    pub chunk_sizes: Vec<u32>,
+    /// 試す同時ストリーム数（`monitor` を同時に動かす数）
+    pub concurrency: Vec<usize>,
+}
+
+/// チャンク長・同時ストリーム数の組み合わせ1つの計測結果
+pub struct SweepResult {
+    pub chunk_ms: u32,
+    pub streams: usize,
+    /// 往復遅延（ミリ秒）
+    pub latencies_ms: Vec<f64>,
+    /// サーバー処理時間（ミリ秒）
+    pub server_ms: Vec<f64>,
+    pub errors: usize,
+    /// 全ストリームが送り終えるまでの時間（秒）
+    pub wall_secs: f64,
+    /// 1ストリームあたりの音声の長さ（秒）
+    pub audio_secs: f64,
+}
+
+impl SweepResult {
+    /// リアルタイム係数（かかった時間 / 全ストリームの音声の長さ）。1.0未満なら実時間より速い
+    pub fn real_time_factor(&self) -> Option<f64> {
+        let audio = self.audio_secs * self.streams as f64;
+        (audio > 0.0).then(|| self.wall_secs / audio)
+    }
+
+    /// 往復遅延のパーセンタイル
+    pub fn latency_percentile(&self, p: f64) -> Option<f64> {
+        percentile(&self.latencies_ms, p)
+    }
+
+    /// ライブ使用できるか（p99の往復がチャンク長の [`LIVE_SAFETY_MARGIN`] 以下）
+    pub fn is_live_safe(&self) -> bool {
+        self.errors == 0
+            && self
+                .latency_percentile(99.0)
+                .is_some_and(|p99| p99 <= self.chunk_ms as f64 * LIVE_SAFETY_MARGIN)
+    }
+}
+
+/// 音声ファイルをチャンク長・同時ストリーム数を変えながら変換して計測する
+///
+/// 各ストリームはファイル全体をチャンクに分け、前のチャンクが返ってきたら次を送ります
+/// （`monitor` を同時に `streams` 個動かしたときと同じ負荷）。端の半端なチャンクは送りません。
+pub async fn sweep(
+    client: &VoiceConversionClient,
+    model: &str,
+    samples: &[f32],
+    sample_rate: u32,
+    channels: u16,
+    config: &SweepConfig,
+) -> Result<Vec<SweepResult>> {
+    let ch = channels.max(1) as usize;
+    let mut results = Vec::with_capacity(config.chunk_sizes.len() * config.concurrency.len());
+
+    for &chunk_ms in &config.chunk_sizes {
+        let frames = (sample_rate as u64 * chunk_ms as u64 / 1000) as usize;
+        let chunks = samples
+            .chunks_exact(frames.max(1) * ch)
+            .map(|chunk| wav::encode(chunk, sample_rate, channels))
+            .collect::<Result<Vec<_>>>()?;
+        if chunks.is_empty() {
+            warn!(
+                "入力が{}msより短いため、このチャンク長は計測しません",
+                chunk_ms
+            );
+            continue;
+        }
+        let chunks = Arc::new(chunks);
+
+        // ウォームアップ（モデルロード時間を除外）
+        if let Err(e) = client
+            .convert_chunk(&chunks[0], Codec::Pcm, model, 0, "bench-warmup")
+            .await
+        {
+            warn!("ウォームアップ失敗 ({}): {}", model, e);
+        }
+
+        for &streams in &config.concurrency {
+            info!(
+                "計測中: {}（{}msチャンク × {}ストリーム）",
+                model, chunk_ms, streams
+            );
+            let mut result = SweepResult {
+                chunk_ms,
+                streams,
+                latencies_ms: Vec::with_capacity(chunks.len() * streams),
+                server_ms: Vec::with_capacity(chunks.len() * streams),
+                errors: 0,
+                wall_secs: 0.0,
+                audio_secs: (chunks.len() * frames) as f64 / sample_rate as f64,
+            };
+
+            let start = Instant::now();
+            let mut tasks = JoinSet::new();
+            for stream in 0..streams {
+                let (client, chunks, model) = (client.clone(), chunks.clone(), model.to_string());
+                tasks.spawn(async move {
+                    let session_id = format!("bench-{}-{}-{}", model, chunk_ms, stream);
+                    let mut timings = Vec::with_capacity(chunks.len());
+                    for chunk in chunks.iter() {
+                        let sent = Instant::now();
+                        let timing = client
+                            .convert_chunk(chunk, Codec::Pcm, &model, 0, &session_id)
+                            .await
+                            .map(|response| {
+                                (
+                                    sent.elapsed().as_secs_f64() * 1000.0,
+                                    response.meta.processing_time_ms,
+                                )
+                            });
+                        timings.push(timing);
+                    }
+                    client.reset_session(&session_id).await.ok();
+                    timings
+                });
+            }
+            while let Some(timings) = tasks.join_next().await {
+                for timing in timings? {
+                    match timing {
+                        Ok((latency, server)) => {
+                            result.latencies_ms.push(latency);
+                            result.server_ms.extend(server);
+                        }
+                        Err(e) => {
+                            warn!("チャンク変換失敗 ({}): {}", model, e);
+                            result.errors += 1;
+                        }
+                    }
+                }
+            }
+            result.wall_secs = start.elapsed().as_secs_f64();
+            results.push(result);
+        }
+    }
+
+    Ok(results)
+}
+
+/// スループット計測の結果と、`monitor` に勧めるチャンク長を表示
+pub fn print_sweep(model: &str, results: &[SweepResult]) {
+    println!(
+        "
+モデル: {}",
+        model
+    );
+    println!(
+        "{:>10} {:>10} {:>8} {:>10} {:>10} {:>10} {:>12} {:>7}  ライブ",
+        "チャンク", "ストリーム", "RTF", "p50(ms)", "p95(ms)", "p99(ms)", "サーバー(ms)", "エラー"
+    );
+
+    let fmt = |v: Option<f64>, precision: usize| {
+        v.map_or_else(|| "-".to_string(), |v| format!("{:.*}", precision, v))
+    };
+
+    for r in results {
+        println!(
+            "{:>10} {:>10} {:>8} {:>10} {:>10} {:>10} {:>12} {:>7}  {}",
+            format!("{}ms", r.chunk_ms),
+            r.streams,
+            fmt(r.real_time_factor(), 2),
+            fmt(r.latency_percentile(50.0), 1),
+            fmt(r.latency_percentile(95.0), 1),
+            fmt(r.latency_percentile(99.0), 1),
+            fmt(percentile(&r.server_ms, 50.0), 1),
+            r.errors,
+            if r.is_live_safe() { "✓" } else { "✗" }
+        );
+    }
+
+    // 最も少ないストリーム数（ふつうは1つの monitor）でライブ使用できる最短のチャンク長
+    let fewest = results.iter().map(|r| r.streams).min();
+    let best = results
+        .iter()
+        .filter(|r| Some(r.streams) == fewest && r.is_live_safe())
+        .min_by_key(|r| r.chunk_ms);
+    match best {
+        Some(best) => {
+            let streams = results
+                .iter()
+                .filter(|r| r.chunk_ms == best.chunk_ms && r.is_live_safe())
+                .map(|r| r.streams)
+                .max()
+                .unwrap_or(best.streams);
+            println!(
+                "\n✅ monitor の推奨チャンク長: {}ms（makebeliv monitor --model {} --chunk-ms {}）",
+                best.chunk_ms, model, best.chunk_ms
+            );
+            println!(
+                "   このチャンク長で同時に{}ストリームまでライブ使用できます",
+                streams
+            );
+        }
+        None => println!(
+            "\n⚠ 試したチャンク長ではライブ使用できません。--chunk-sizes に長いチャンクを加えてください"
+        ),
+    }
+}
+
+/// 計測結果を表示
+pub fn print_report(results: &[ModelBenchResult], chunk_ms: u32) {
+    println!("\nチャンク長: {}ms", chunk_ms);
+    println!(
+        "{:<20} {:>8} {:>10} {:>10} {:>10} {:>7}  ライブ",
+        "モデル", "RTF", "p50(ms)", "p95(ms)", "max(ms)", "エラー"
+    );
+
+    let fmt = |v: Option<f64>, precision: usize| {
+        v.map_or_else(|| "-".to_string(), |v| format!("{:.*}", precision, v))
+    };
+
+    for r in results {
+        println!(
+            "{:<20} {:>8} {:>10} {:>10} {:>10} {:>7}  {}",
+            r.model,
+            fmt(r.real_time_factor(chunk_ms), 2),
+            fmt(r.latency_percentile(50.0), 1),
+            fmt(r.latency_percentile(95.0), 1),
+            fmt(r.latency_percentile(100.0), 1),
+            r.errors,
+            if r.is_live_safe(chunk_ms) {
+                "✓"
+            } else {
+                "✗"
+            }
+        );
+    }
+
+    let safe: Vec<&str> = results
+        .iter()
+        .filter(|r| r.is_live_safe(chunk_ms))
+        .map(|r| r.model.as_str())
+        .collect();
+
+    if safe.is_empty() {
+        println!(
+            "\n⚠ {}msチャンクでライブ使用できるモデルはありません。--chunk-ms を大きくしてください。",
+            chunk_ms
+        );
+    } else {
+        println!(
+            "\n✅ {}msチャンクでライブ使用可能: {}",
+            chunk_ms,
+            safe.join(", ")
+        );
+    }
+}
+
+pub(crate) fn mean(values: &[f64]) -> Option<f64> {
+    if values.is_empty() {
+        None
+    } else {
+        Some(values.iter().sum::<f64>() / values.len() as f64)
+    }
+}
+
+/// 最近傍法によるパーセンタイル
+pub(crate) fn percentile(values: &[f64], p: f64) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let rank = ((p / 100.0) * sorted.len() as f64).ceil() as usize;
+    Some(sorted[rank.clamp(1, sorted.len()) - 1])
+}
@@ -0,0 +1,391 @@
+use anyhow::Result;
+use std::collections::VecDeque;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+use crate::client::{TranscribedWord, VoiceConversionClient};
+use crate::wav;
+
+/// 文字起こしに送る区間の既定の長さ（ミリ秒、この分だけ出力が遅れる）
+pub const DEFAULT_WINDOW_MS: u32 = 2000;
+
+/// 伏せ音の周波数
+const TONE_HZ: f32 = 1000.0;
+
+/// 伏せ音のピークレベル
+const TONE_LEVEL: f32 = 0.2;
+
+/// 語の前後に広げて伏せる長さ（ミリ秒、文字起こしの時刻のずれを吸収する）
+const SPAN_MARGIN_MS: f64 = 60.0;
+
+/// 伏せる区間の端のフェード（ミリ秒）
+const FADE_MS: u32 = 5;
+
+/// 伏せ方
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BleepMode {
+    /// 1kHzのピー音に置き換える
+    #[default]
+    Tone,
+    /// 無音にする
+    Silence,
+}
+
+impl FromStr for BleepMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "tone" | "beep" => Ok(Self::Tone),
+            "silence" | "mute" => Ok(Self::Silence),
+            _ => anyhow::bail!("不明な伏せ方: {}（tone, silence）", s),
+        }
+    }
+}
+
+impl std::fmt::Display for BleepMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Tone => "tone",
+            Self::Silence => "silence",
+        })
+    }
+}
+
+/// 比べるために語を正規化する（小文字にし、記号を除いて空白を1つにまとめる）
+fn normalize(text: &str) -> String {
+    text.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// 文字起こしの中から指定した語を探し、伏せる区間（秒）を返す
+///
+/// 英数字だけの語は単語全体が一致したときだけ、それ以外（日本語など）は語をまたいだ
+/// 部分一致でも伏せます。文字起こしは日本語を細かく区切って返すことがあるためです。
+pub fn flagged_spans(words: &[TranscribedWord], keywords: &[String]) -> Vec<(f64, f64)> {
+    // 語をつないだ文字列と、各文字がどの語から来たか
+    let mut text = String::new();
+    let mut owners = Vec::new();
+    for (i, word) in words.iter().enumerate() {
+        let normalized = normalize(&word.word);
+        if normalized.is_empty() {
+            continue;
+        }
+        let ascii_join = text.ends_with(|c: char| c.is_ascii_alphanumeric())
+            && normalized.starts_with(|c: char| c.is_ascii_alphanumeric());
+        if ascii_join || (!text.is_empty() && word.word.starts_with(char::is_whitespace)) {
+            text.push(' ');
+            owners.push(i);
+        }
+        for c in normalized.chars() {
+            text.push(c);
+            owners.push(i);
+        }
+    }
+    let chars: Vec<char> = text.chars().collect();
+    let is_boundary = |index: Option<&char>| index.is_none_or(|c| !c.is_ascii_alphanumeric());
+
+    let mut spans = Vec::new();
+    for keyword in keywords.iter().map(|keyword| normalize(keyword)) {
+        let pattern: Vec<char> = keyword.chars().collect();
+        if pattern.is_empty() || pattern.len() > chars.len() {
+            continue;
+        }
+        let whole_word = keyword.is_ascii();
+        for start in 0..=chars.len() - pattern.len() {
+            let end = start + pattern.len();
+            if chars[start..end] != pattern[..] {
+                continue;
+            }
+            if whole_word
+                && !(is_boundary(start.checked_sub(1).and_then(|i| chars.get(i)))
+                    && is_boundary(chars.get(end)))
+            {
+                continue;
+            }
+            let (first, last) = (&words[owners[start]], &words[owners[end - 1]]);
+            spans.push((first.start, last.end));
+        }
+    }
+    spans.sort_by(|a, b| a.0.total_cmp(&b.0));
+    spans
+}
+
+/// 区間を伏せ音か無音に置き換える（時刻は `samples` の先頭からの秒）
+pub fn censor(
+    samples: &mut [f32],
+    sample_rate: u32,
+    channels: u16,
+    spans: &[(f64, f64)],
+    mode: BleepMode,
+) {
+    let channels = channels.max(1) as usize;
+    let frames = samples.len() / channels;
+    let fade = (sample_rate * FADE_MS / 1000).max(1) as f32;
+    let to_frame = |seconds: f64| ((seconds.max(0.0) * sample_rate as f64) as usize).min(frames);
+
+    for &(start, end) in spans {
+        let first = to_frame(start - SPAN_MARGIN_MS / 1000.0);
+        let last = to_frame(end + SPAN_MARGIN_MS / 1000.0);
+        for frame in first..last {
+            // 端はフェードでつなぐ（クリックを出さない）
+            let gain = ((frame - first) as f32 / fade)
+                .min((last - frame) as f32 / fade)
+                .min(1.0);
+            let tone = match mode {
+                BleepMode::Tone => {
+                    TONE_LEVEL
+                        * (2.0 * std::f32::consts::PI * TONE_HZ * frame as f32 / sample_rate as f32)
+                            .sin()
+                }
+                BleepMode::Silence => 0.0,
+            };
+            for sample in &mut samples[frame * channels..(frame + 1) * channels] {
+                *sample = *sample * (1.0 - gain) + tone * gain;
+            }
+        }
+    }
+}
+
+/// 文字起こしを待っている区間
+struct PendingWindow {
+    samples: Vec<f32>,
+    sample_rate: u32,
+    channels: u16,
+    words: JoinHandle<Result<Vec<TranscribedWord>>>,
+    /// これを過ぎたら文字起こしを諦めてそのまま出す
+    deadline: Instant,
+}
+
+/// リアルタイム変換の出力から指定した語を伏せる
+///
+/// 出力を一定の長さの区間に溜めてサーバーで文字起こしし、指定した語の部分を
+/// 伏せ音か無音に置き換えてから出します。そのため出力は区間の長さと文字起こしの時間だけ
+/// 遅れます。区間の境目をまたいだ語は見逃すことがあり、文字起こしが区間の長さより
+/// 遅れたときはその区間をそのまま出します。
+pub struct Bleeper {
+    client: Arc<VoiceConversionClient>,
+    keywords: Vec<String>,
+    mode: BleepMode,
+    window: Duration,
+    format: Option<(u32, u16)>,
+    /// 溜めている途中の区間
+    filling: Vec<f32>,
+    pending: VecDeque<PendingWindow>,
+    bleeped: u64,
+}
+
+impl Bleeper {
+    pub fn new(client: VoiceConversionClient, keywords: Vec<String>, mode: BleepMode) -> Self {
+        Self {
+            client: Arc::new(client),
+            keywords,
+            mode,
+            window: Duration::from_millis(DEFAULT_WINDOW_MS as u64),
+            format: None,
+            filling: Vec::new(),
+            pending: VecDeque::new(),
+            bleeped: 0,
+        }
+    }
+
+    pub fn with_window_ms(mut self, window_ms: u32) -> Self {
+        self.window = Duration::from_millis(window_ms.max(1) as u64);
+        self
+    }
+
+    pub fn mode(&self) -> BleepMode {
+        self.mode
+    }
+
+    pub fn keywords(&self) -> &[String] {
+        &self.keywords
+    }
+
+    pub fn window(&self) -> Duration {
+        self.window
+    }
+
+    /// 伏せた回数
+    pub fn bleeped(&self) -> u64 {
+        self.bleeped
+    }
+
+    /// 出力を溜める（区間が溜まったら文字起こしを始める）
+    ///
+    /// 出力の形式が変わったら、溜めていた古い形式の音声は捨てます。
+    pub fn push(&mut self, samples: &[f32], sample_rate: u32, channels: u16) {
+        if self.format != Some((sample_rate, channels)) {
+            if self.format.is_some() {
+                debug!("出力の形式が変わったため、伏せ待ちの音声を捨てます");
+            }
+            self.format = Some((sample_rate, channels));
+            self.filling.clear();
+            for window in self.pending.drain(..) {
+                window.words.abort();
+            }
+        }
+
+        self.filling.extend_from_slice(samples);
+        let window_len =
+            (self.window.as_secs_f64() * sample_rate as f64) as usize * channels.max(1) as usize;
+        if self.filling.len() >= window_len {
+            self.send_window();
+        }
+    }
+
+    /// 溜めている途中の区間を文字起こしに送る
+    fn send_window(&mut self) {
+        let Some((sample_rate, channels)) = self.format else {
+            return;
+        };
+        if self.filling.is_empty() {
+            return;
+        }
+        let samples = std::mem::take(&mut self.filling);
+        let audio = wav::encode(&samples, sample_rate, channels);
+        let client = self.client.clone();
+        self.pending.push_back(PendingWindow {
+            samples,
+            sample_rate,
+            channels,
+            words: tokio::spawn(async move { client.transcribe(audio?).await }),
+            deadline: Instant::now() + self.window,
+        });
+    }
+
+    /// 文字起こしが終わった区間を、古い順に伏せて返す
+    pub async fn poll(&mut self) -> Vec<f32> {
+        let mut released = Vec::new();
+        while let Some(window) = self.pending.front() {
+            if !window.words.is_finished() && Instant::now() < window.deadline {
+                break;
+            }
+            let window = self.pending.pop_front().expect("先頭の区間がある");
+            released.extend(self.finish_window(window).await);
+        }
+        released
+    }
+
+    /// 残りをすべて文字起こしして返す（停止時）
+    pub async fn finish(&mut self) -> Vec<f32> {
+        self.send_window();
+        let mut released = Vec::new();
+        while let Some(window) = self.pending.pop_front() {
+            released.extend(self.finish_window(window).await);
+        }
+        released
+    }
+
+    /// 文字起こしを期限まで待ち、指定した語を伏せる
+    async fn finish_window(&mut self, window: PendingWindow) -> Vec<f32> {
+        let PendingWindow {
+            mut samples,
+            sample_rate,
+            channels,
+            mut words,
+            deadline,
+        } = window;
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        let Ok(result) = tokio::time::timeout(remaining, &mut words).await else {
+            words.abort();
+            warn!("⚠ 文字起こしが間に合わないため、伏せずに出力します");
+            return samples;
+        };
+
+        match result {
+            Ok(Ok(words)) => {
+                let spans = flagged_spans(&words, &self.keywords);
+                if !spans.is_empty() {
+                    censor(&mut samples, sample_rate, channels, &spans, self.mode);
+                    self.bleeped += spans.len() as u64;
+                    info!(
+                        "🔇 {}箇所を伏せました（累計{}箇所）",
+                        spans.len(),
+                        self.bleeped
+                    );
+                }
+            }
+            Ok(Err(e)) => warn!("⚠ 文字起こしエラー（伏せずに出力します）: {:#}", e),
+            Err(e) => warn!("⚠ 文字起こしタスクエラー: {}", e),
+        }
+        samples
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(list: &[(&str, f64, f64)]) -> Vec<TranscribedWord> {
+        list.iter()
+            .map(|&(word, start, end)| TranscribedWord {
+                word: word.to_string(),
+                start,
+                end,
+            })
+            .collect()
+    }
+
+    fn keywords(list: &[&str]) -> Vec<String> {
+        list.iter().map(|keyword| keyword.to_string()).collect()
+    }
+
+    #[test]
+    fn flags_whole_ascii_words() {
+        let words = words(&[(" what", 0.0, 0.3), (" the", 0.3, 0.5), (" heck", 0.5, 0.9)]);
+        assert_eq!(
+            flagged_spans(&words, &keywords(&["heck"])),
+            vec![(0.5, 0.9)]
+        );
+        // 複数の語からなるキーワードは、最初の語の始まりから最後の語の終わりまで
+        assert_eq!(
+            flagged_spans(&words, &keywords(&["the heck"])),
+            vec![(0.3, 0.9)]
+        );
+    }
+
+    #[test]
+    fn ignores_ascii_keywords_inside_other_words() {
+        let words = words(&[
+            (" first", 0.0, 0.4),
+            (" class", 0.4, 0.8),
+            (" passage", 0.8, 1.3),
+        ]);
+        assert!(flagged_spans(&words, &keywords(&["ass"])).is_empty());
+    }
+
+    #[test]
+    fn joins_japanese_words_split_by_the_transcription() {
+        let words = words(&[
+            ("今日", 0.0, 0.3),
+            ("は", 0.3, 0.4),
+            ("馬", 0.4, 0.6),
+            ("鹿", 0.6, 0.8),
+            ("だ", 0.8, 0.9),
+        ]);
+        assert_eq!(
+            flagged_spans(&words, &keywords(&["馬鹿"])),
+            vec![(0.4, 0.8)]
+        );
+    }
+
+    #[test]
+    fn ignores_case_and_punctuation() {
+        let words = words(&[(" Oh,", 0.0, 0.2), (" HECK!", 0.2, 0.6), (" no.", 0.6, 0.8)]);
+        assert_eq!(
+            flagged_spans(&words, &keywords(&["Heck"])),
+            vec![(0.2, 0.6)]
+        );
+        assert_eq!(
+            flagged_spans(&words, &keywords(&["oh heck"])),
+            vec![(0.0, 0.6)]
+        );
+    }
+}
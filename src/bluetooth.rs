@@ -0,0 +1,134 @@
+use anyhow::{Context, Result};
+use std::process::Command;
+use tracing::{debug, info};
+
+use crate::hotplug::DeviceDirection;
+
+/// 通話用プロファイル（HFP/HSP）とみなすサンプリングレートの上限
+///
+/// HFPはmSBCで16kHz、CVSDでは8kHzしか通りません。
+pub const HANDS_FREE_MAX_RATE: u32 = 16000;
+
+/// デバイス名に含まれていればBluetoothとみなすキーワード（小文字）
+const BLUETOOTH_KEYWORDS: &[&str] = &[
+    "bluetooth",
+    "bluez",
+    "hands-free",
+    "handsfree",
+    "airpods",
+    "buds",
+    "wh-1000",
+    "wf-1000",
+];
+
+/// デバイス名に含まれていれば通話用プロファイルとみなすキーワード（小文字）
+const HANDS_FREE_KEYWORDS: &[&str] = &[
+    "hands-free",
+    "handsfree",
+    "headset-head-unit",
+    "headset_head_unit",
+    "hfp",
+    "hsp",
+];
+
+/// PulseAudio/PipeWireの既定デバイスを指す、ALSA上のデバイス名
+const SOUND_SERVER_DEVICES: &[&str] = &["default", "pulse", "pipewire"];
+
+/// 選んだデバイスがBluetoothの場合の状態
+#[derive(Debug, Clone)]
+pub struct BluetoothDevice {
+    /// デバイス名（サウンドサーバー経由ならそのシンク・ソース名）
+    pub name: String,
+    /// 通話用プロファイル（HFP/HSP）で動いているか
+    pub hands_free: bool,
+}
+
+/// デバイスがBluetoothなら、どのプロファイルで動いているかを調べる（Bluetoothでなければ None）
+///
+/// 名前とサンプリングレートから判定します。Linuxでサウンドサーバーの既定デバイスを使っているときは、
+/// `pactl` で実際の既定のシンク・ソースを確認します。
+pub fn inspect(
+    name: &str,
+    sample_rate: u32,
+    direction: DeviceDirection,
+) -> Option<BluetoothDevice> {
+    let lower = name.to_lowercase();
+    if BLUETOOTH_KEYWORDS.iter().any(|k| lower.contains(k)) {
+        return Some(BluetoothDevice {
+            name: name.to_string(),
+            hands_free: sample_rate <= HANDS_FREE_MAX_RATE
+                || HANDS_FREE_KEYWORDS.iter().any(|k| lower.contains(k)),
+        });
+    }
+
+    if !cfg!(target_os = "linux") || !SOUND_SERVER_DEVICES.contains(&lower.as_str()) {
+        return None;
+    }
+    let default = default_device(direction)?;
+    if !default.starts_with("bluez") {
+        return None;
+    }
+    let lower = default.to_lowercase();
+    Some(BluetoothDevice {
+        // Bluetoothのマイクが使えるのは通話用プロファイルのときだけ
+        hands_free: direction == DeviceDirection::Input
+            || HANDS_FREE_KEYWORDS.iter().any(|k| lower.contains(k)),
+        name: default,
+    })
+}
+
+/// デバイス名だけでBluetoothか判定する（入力デバイスの選び直し用）
+pub fn is_bluetooth_name(name: &str) -> bool {
+    let lower = name.to_lowercase();
+    BLUETOOTH_KEYWORDS.iter().any(|k| lower.contains(k))
+}
+
+/// サウンドサーバーの既定のシンク・ソース名
+fn default_device(direction: DeviceDirection) -> Option<String> {
+    let command = match direction {
+        DeviceDirection::Input => "get-default-source",
+        DeviceDirection::Output => "get-default-sink",
+    };
+    let output = Command::new("pactl").arg(command).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let name = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (!name.is_empty()).then_some(name)
+}
+
+/// Bluetoothのカードをすべて高音質の再生用プロファイル（A2DP）に切り替える（Linuxのみ）
+///
+/// 切り替えたカード名を返します。A2DPに対応していないカードはそのままです。
+pub fn force_a2dp() -> Result<Vec<String>> {
+    anyhow::ensure!(
+        cfg!(target_os = "linux"),
+        "A2DPへの切り替えはLinux（PulseAudio/PipeWire）でのみ行えます"
+    );
+    let cards = Command::new("pactl")
+        .args(["list", "short", "cards"])
+        .output()
+        .context("pactl が見つかりません（PulseAudioかPipeWireのpipewire-pulseが必要です）")?;
+    let mut switched = Vec::new();
+    for card in String::from_utf8_lossy(&cards.stdout)
+        .lines()
+        .filter_map(|line| line.split_whitespace().nth(1))
+        .filter(|card| card.starts_with("bluez_card"))
+    {
+        // PipeWireとPulseAudioでプロファイル名が違う
+        let profile = ["a2dp-sink", "a2dp_sink"].into_iter().find(|profile| {
+            Command::new("pactl")
+                .args(["set-card-profile", card, profile])
+                .status()
+                .is_ok_and(|status| status.success())
+        });
+        match profile {
+            Some(profile) => {
+                info!("Bluetoothを{}に切り替えました: {}", profile, card);
+                switched.push(card.to_string());
+            }
+            None => debug!("A2DPに切り替えられません: {}", card),
+        }
+    }
+    Ok(switched)
+}
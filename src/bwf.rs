@@ -0,0 +1,188 @@
+use anyhow::{Context, Result};
+use std::path::Path;
+
+/// bext チャンクの固定部分のバイト数（EBU Tech 3285 v1。後ろにコーディングヒストリーが続く）
+const BEXT_FIXED_BYTES: usize = 602;
+
+/// 固定長の文字列フィールド（位置, バイト数）
+const DESCRIPTION: (usize, usize) = (0, 256);
+const ORIGINATOR: (usize, usize) = (256, 32);
+const ORIGINATOR_REFERENCE: (usize, usize) = (288, 32);
+const ORIGINATION_DATE: (usize, usize) = (320, 10);
+const ORIGINATION_TIME: (usize, usize) = (330, 8);
+
+/// TimeReference の位置
+const TIME_REFERENCE_OFFSET: usize = 338;
+
+/// Version の位置
+const VERSION_OFFSET: usize = TIME_REFERENCE_OFFSET + 8;
+
+const BEXT_VERSION: u16 = 1;
+
+/// Broadcast WAV の bext チャンク
+///
+/// 文字列はフィールドの長さで切り詰めます（文字の途中では切りません）。
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Bext {
+    /// 内容の説明（256バイトまで）
+    pub description: String,
+    /// 作ったソフトウェア・機材（32バイトまで）
+    pub originator: String,
+    /// 作った側での識別子（32バイトまで）
+    pub originator_reference: String,
+    /// 作った日（`yyyy-mm-dd`）
+    pub origination_date: String,
+    /// 作った時刻（`hh:mm:ss`）
+    pub origination_time: String,
+    /// 先頭のサンプルの時刻（その日の0時からのサンプル数）。DAWはこれで素材を時間軸に並べる
+    pub time_reference: u64,
+    /// コーディングヒストリー（加工の履歴。1行に1つ、CRLFで終える）
+    pub coding_history: String,
+}
+
+impl Bext {
+    fn encode(&self) -> Vec<u8> {
+        let mut data = vec![0u8; BEXT_FIXED_BYTES];
+        put_str(&mut data, DESCRIPTION, &self.description);
+        put_str(&mut data, ORIGINATOR, &self.originator);
+        put_str(&mut data, ORIGINATOR_REFERENCE, &self.originator_reference);
+        put_str(&mut data, ORIGINATION_DATE, &self.origination_date);
+        put_str(&mut data, ORIGINATION_TIME, &self.origination_time);
+        data[TIME_REFERENCE_OFFSET..TIME_REFERENCE_OFFSET + 8]
+            .copy_from_slice(&self.time_reference.to_le_bytes());
+        data[VERSION_OFFSET..VERSION_OFFSET + 2].copy_from_slice(&BEXT_VERSION.to_le_bytes());
+        data.extend_from_slice(self.coding_history.as_bytes());
+        // 奇数バイトのチャンクの詰め物を読み飛ばさないリーダーがあるため、中身を偶数バイトに揃える
+        if data.len() % 2 == 1 {
+            data.push(0);
+        }
+        data
+    }
+
+    fn decode(data: &[u8]) -> Result<Self> {
+        anyhow::ensure!(
+            data.len() >= TIME_REFERENCE_OFFSET + 8,
+            "bext チャンクが短すぎます（{} bytes）",
+            data.len()
+        );
+        Ok(Self {
+            description: get_str(data, DESCRIPTION),
+            originator: get_str(data, ORIGINATOR),
+            originator_reference: get_str(data, ORIGINATOR_REFERENCE),
+            origination_date: get_str(data, ORIGINATION_DATE),
+            origination_time: get_str(data, ORIGINATION_TIME),
+            time_reference: u64::from_le_bytes(
+                data[TIME_REFERENCE_OFFSET..TIME_REFERENCE_OFFSET + 8].try_into()?,
+            ),
+            coding_history: data
+                .get(BEXT_FIXED_BYTES..)
+                .map(|rest| {
+                    String::from_utf8_lossy(rest)
+                        .trim_end_matches('\0')
+                        .to_string()
+                })
+                .unwrap_or_default(),
+        })
+    }
+}
+
+/// 固定長のフィールドに文字列を書く（残りは0で埋まったまま）
+fn put_str(data: &mut [u8], (offset, len): (usize, usize), value: &str) {
+    let mut end = value.len().min(len);
+    while !value.is_char_boundary(end) {
+        end -= 1;
+    }
+    data[offset..offset + end].copy_from_slice(&value.as_bytes()[..end]);
+}
+
+fn get_str(data: &[u8], (offset, len): (usize, usize)) -> String {
+    let field = &data[offset..offset + len];
+    let end = field.iter().position(|&b| b == 0).unwrap_or(len);
+    String::from_utf8_lossy(&field[..end]).into_owned()
+}
+
+/// コーディングヒストリーの1行（EBU R98 の書式。`T=` に加工の内容を書く）
+pub fn coding_history_line(spec: &hound::WavSpec, text: &str) -> String {
+    let mode = match spec.channels {
+        1 => "mono".to_string(),
+        2 => "stereo".to_string(),
+        n => format!("{}ch", n),
+    };
+    format!(
+        "A=PCM,F={},W={},M={},T={}\r\n",
+        spec.sample_rate, spec.bits_per_sample, mode, text
+    )
+}
+
+/// WAVファイルの bext チャンクを読む（なければNone）
+pub fn read(path: &Path) -> Result<Option<Bext>> {
+    let data = std::fs::read(path)
+        .with_context(|| format!("WAVファイルの読み込みエラー: {}", path.display()))?;
+    chunks(&data)
+        .with_context(|| format!("WAVファイルを読み込めません: {}", path.display()))?
+        .into_iter()
+        .find(|(id, _)| id == b"bext")
+        .map(|(_, body)| Bext::decode(body))
+        .transpose()
+}
+
+/// WAVファイルに bext チャンクを書き込む（既にあれば置き換える）
+///
+/// bext は `fmt ` の前に置きます。ファイル全体を書き直すため、一時ファイルに書いてから置き換えます。
+pub fn write(path: &Path, bext: &Bext) -> Result<()> {
+    let data = std::fs::read(path)
+        .with_context(|| format!("WAVファイルの読み込みエラー: {}", path.display()))?;
+    let chunks = chunks(&data)
+        .with_context(|| format!("WAVファイルを読み込めません: {}", path.display()))?;
+
+    let mut output = Vec::with_capacity(data.len() + BEXT_FIXED_BYTES + 8);
+    output.extend_from_slice(b"RIFF\0\0\0\0WAVE");
+    push_chunk(&mut output, b"bext", &bext.encode());
+    for (id, body) in chunks.iter().filter(|(id, _)| id != b"bext") {
+        push_chunk(&mut output, id, body);
+    }
+    let riff_size = u32::try_from(output.len() - 8).context("WAVファイルが大きすぎます")?;
+    output[4..8].copy_from_slice(&riff_size.to_le_bytes());
+
+    let mut partial = path.as_os_str().to_owned();
+    partial.push(".part");
+    let partial = std::path::PathBuf::from(partial);
+    std::fs::write(&partial, output)
+        .and_then(|()| std::fs::rename(&partial, path))
+        .inspect_err(|_| {
+            std::fs::remove_file(&partial).ok();
+        })
+        .with_context(|| format!("WAVファイルの書き込みエラー: {}", path.display()))
+}
+
+/// RIFFのチャンク（ID, 中身）の一覧
+fn chunks(data: &[u8]) -> Result<Vec<([u8; 4], &[u8])>> {
+    anyhow::ensure!(
+        data.len() >= 12 && &data[..4] == b"RIFF" && &data[8..12] == b"WAVE",
+        "WAVデータではありません"
+    );
+    let mut chunks = Vec::new();
+    let mut rest = &data[12..];
+    while rest.len() >= 8 {
+        let id: [u8; 4] = rest[..4].try_into()?;
+        let size = u32::from_le_bytes(rest[4..8].try_into()?) as usize;
+        anyhow::ensure!(
+            rest.len() >= 8 + size,
+            "{} チャンクが途中で切れています",
+            String::from_utf8_lossy(&id)
+        );
+        chunks.push((id, &rest[8..8 + size]));
+        // チャンクは偶数バイトに揃える
+        rest = &rest[(8 + size + size % 2).min(rest.len())..];
+    }
+    Ok(chunks)
+}
+
+fn push_chunk(output: &mut Vec<u8>, id: &[u8; 4], body: &[u8]) {
+    output.extend_from_slice(id);
+    output.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    output.extend_from_slice(body);
+    if body.len() % 2 == 1 {
+        output.push(0);
+    }
+}
@@ -0,0 +1,118 @@
+use anyhow::Result;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::debug;
+
+/// 再現性のある軽量な乱数（xorshift64）
+#[derive(Clone)]
+pub(crate) struct XorShift(u64);
+
+impl XorShift {
+    pub(crate) fn new(seed: u64) -> Self {
+        // 0だと常に0になるため
+        Self(seed.max(1))
+    }
+
+    /// 時刻から種を作る
+    pub(crate) fn from_time() -> Self {
+        Self::new(time_seed())
+    }
+
+    /// 0.0以上1.0未満
+    pub(crate) fn next_f64(&mut self) -> f64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// 時刻から作った乱数の種
+pub fn time_seed() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos() as u64)
+}
+
+/// 注入する遅延（`<ms>` または `<ms>:<揺れ幅ms>`）
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct LatencySpec {
+    pub base_ms: u64,
+    /// 揺れ幅（±）
+    pub jitter_ms: u64,
+}
+
+impl FromStr for LatencySpec {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let parse = |v: &str| {
+            v.trim()
+                .trim_end_matches("ms")
+                .parse::<u64>()
+                .map_err(|_| anyhow::anyhow!("遅延の形式が不正です: {}（例: 80, 80:30）", s))
+        };
+        match s.split_once(':') {
+            Some((base, jitter)) => Ok(Self {
+                base_ms: parse(base)?,
+                jitter_ms: parse(jitter)?,
+            }),
+            None => Ok(Self {
+                base_ms: parse(s)?,
+                jitter_ms: 0,
+            }),
+        }
+    }
+}
+
+impl std::fmt::Display for LatencySpec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}±{}ms", self.base_ms, self.jitter_ms)
+    }
+}
+
+/// チャンク変換に注入する障害（耐障害性の確認用）
+#[derive(Debug, Clone, Default)]
+pub struct ChaosOptions {
+    pub latency: LatencySpec,
+    /// 変換を失敗させる割合（0.0-1.0）
+    pub error_rate: f64,
+    /// 乱数の種（同じ種なら同じ順で遅延・失敗する。Noneなら時刻）
+    pub seed: Option<u64>,
+}
+
+impl ChaosOptions {
+    /// 何も注入しない設定か
+    pub fn is_noop(&self) -> bool {
+        self.latency == LatencySpec::default() && self.error_rate <= 0.0
+    }
+}
+
+/// チャンク変換の前に遅延・失敗を注入する
+pub struct ChaosInjector {
+    options: ChaosOptions,
+    rng: XorShift,
+}
+
+impl ChaosInjector {
+    pub fn new(options: ChaosOptions) -> Self {
+        let rng = options.seed.map_or_else(XorShift::from_time, XorShift::new);
+        Self { options, rng }
+    }
+
+    /// 遅延を入れ、失敗させる場合はエラーを返す
+    pub async fn perturb(&mut self) -> Result<()> {
+        let LatencySpec { base_ms, jitter_ms } = self.options.latency;
+        let jitter = jitter_ms as f64 * (self.rng.next_f64() * 2.0 - 1.0);
+        let delay = Duration::from_secs_f64((base_ms as f64 + jitter).max(0.0) / 1000.0);
+        if !delay.is_zero() {
+            tokio::time::sleep(delay).await;
+        }
+
+        if self.rng.next_f64() < self.options.error_rate {
+            debug!("チャンク変換の失敗を注入");
+            anyhow::bail!("注入されたエラー（--inject-error-rate）");
+        }
+        Ok(())
+    }
+}
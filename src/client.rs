@@ -1,139 +1,1450 @@
 use anyhow::{Context, Result};
 use bytes::Bytes;
+use futures_util::{SinkExt, StreamExt};
+use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION};
 use reqwest::multipart;
-use std::path::Path;
-use tracing::{debug, info};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+use tokio_util::io::ReaderStream;
+use tracing::{debug, info, warn};
+
+use crate::arbiter::Arbiter;
+use crate::auth;
+use crate::debug_http;
+use crate::opus_chunk;
+use crate::progress::TransferProgress;
+use crate::retry::RetryPolicy;
+
+/// 変換レスポンスのメタ情報（レスポンスヘッダー）
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ConvertResponseMeta {
+    /// サーバー側の処理時間（X-Processing-Time-Ms）
+    pub processing_time_ms: Option<f64>,
+    /// 変換後の音声の長さ（X-Audio-Length-Seconds）
+    pub audio_length_seconds: Option<f64>,
+    /// 変換後の音声の形式（X-Makebeliv-Codec、なければWAV）
+    pub codec: Codec,
+}
+
+impl ConvertResponseMeta {
+    fn from_headers(headers: &HeaderMap) -> Self {
+        let number = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.parse().ok())
+        };
+        Self {
+            processing_time_ms: number("X-Processing-Time-Ms"),
+            audio_length_seconds: number("X-Audio-Length-Seconds"),
+            codec: headers
+                .get(CODEC_HEADER)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.parse().ok())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// 変換の結果
+pub struct ConvertResponse {
+    /// 変換後の音声（WAV。`meta.codec` がOpusならOpusのチャンク）
+    pub audio: Bytes,
+    pub meta: ConvertResponseMeta,
+}
+
+/// クライアントが話すプロトコルのバージョン
+///
+/// - v1: 初期版（/status, /models はモデル名のみ, /convert, /convert-chunk）
+/// - v2: /models がモデル情報を返す, /status にバージョン情報
+pub const PROTOCOL_VERSION: u32 = 2;
+
+/// 接続できるサーバーの最低プロトコルバージョン
+pub const MIN_SERVER_PROTOCOL_VERSION: u32 = 1;
+
+/// リクエストごとにクライアントのプロトコルバージョンを伝えるヘッダー
+const PROTOCOL_HEADER: &str = "X-Makebeliv-Protocol";
+
+/// 変換リクエストの優先度を伝えるヘッダー（対応しないサーバーは無視する）
+pub const PRIORITY_HEADER: &str = "X-Makebeliv-Priority";
+
+/// チャンクの音声の形式を伝えるヘッダー
+///
+/// 送るチャンクの形式を付け、サーバーはOpusで返したときだけ同じヘッダーを付けます。
+pub const CODEC_HEADER: &str = "X-Makebeliv-Codec";
+
+/// GPUを共有するときのスケジューリングの優先度
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Priority {
+    /// リアルタイム変換のチャンク（途切れないよう先に処理してほしい）
+    Realtime,
+    /// ファイル処理などのバッチ
+    Batch,
+}
+
+impl Priority {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Realtime => "realtime",
+            Self::Batch => "batch",
+        }
+    }
+}
+
+impl std::fmt::Display for Priority {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// プロトコルのバージョンによって使えるかが変わる機能
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProtocolFeature {
+    /// /models のモデル情報（重みの有無・ロード状況）
+    ModelDetails,
+}
+
+impl ProtocolFeature {
+    const ALL: [Self; 1] = [Self::ModelDetails];
+
+    /// この機能が使えるようになったバージョン
+    pub fn since(self) -> u32 {
+        match self {
+            Self::ModelDetails => 2,
+        }
+    }
+}
+
+impl std::fmt::Display for ProtocolFeature {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::ModelDetails => "モデル情報",
+        })
+    }
+}
+
+/// サーバーが対応しているかで有効・無効が変わるオプション機能
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Capability {
+    /// WebSocketでのチャンク変換
+    WsChunks,
+    /// Opusでの音声送受信
+    Opus,
+    /// モデルの事前ロード（/warmup）
+    Warmup,
+    /// 文字起こし（/transcribe）
+    Transcribe,
+    /// モデル一覧（/models）
+    Models,
+    /// 優先度ヘッダーに従ったスケジューリング
+    Priority,
+    /// 変換セッション一覧（/sessions）
+    Sessions,
+    /// 話者の区別（/diarize）
+    Diarize,
+}
+
+impl Capability {
+    pub const ALL: [Self; 8] = [
+        Self::WsChunks,
+        Self::Opus,
+        Self::Warmup,
+        Self::Transcribe,
+        Self::Models,
+        Self::Priority,
+        Self::Sessions,
+        Self::Diarize,
+    ];
+
+    /// 対応を判定できるHTTPエンドポイント（OpenAPIのパス）
+    fn endpoint(self) -> Option<&'static str> {
+        match self {
+            Self::Warmup => Some("/warmup"),
+            Self::Transcribe => Some("/transcribe"),
+            Self::Models => Some("/models"),
+            Self::Sessions => Some("/sessions"),
+            Self::Diarize => Some("/diarize"),
+            // WebSocketやコーデック、ヘッダーはOpenAPIに現れないため申告でのみ判定
+            Self::WsChunks | Self::Opus | Self::Priority => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Capability {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::WsChunks => "ws-chunks",
+            Self::Opus => "opus",
+            Self::Warmup => "warmup",
+            Self::Transcribe => "transcribe",
+            Self::Models => "models",
+            Self::Priority => "priority",
+            Self::Sessions => "sessions",
+            Self::Diarize => "diarize",
+        })
+    }
+}
+
+/// サーバーが対応しているオプション機能
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ServerCapabilities(BTreeSet<Capability>);
+
+impl<'de> Deserialize<'de> for ServerCapabilities {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        // 新しいサーバーが申告する、このクライアントの知らない機能は無視する
+        let names = Vec::<String>::deserialize(deserializer)?;
+        Ok(names
+            .iter()
+            .filter_map(|name| Capability::ALL.into_iter().find(|c| c.to_string() == *name))
+            .collect())
+    }
+}
+
+impl ServerCapabilities {
+    pub fn supports(&self, capability: Capability) -> bool {
+        self.0.contains(&capability)
+    }
+
+    /// 1行サマリー（例: "models, warmup"）
+    pub fn summary(&self) -> String {
+        if self.0.is_empty() {
+            return "なし".to_string();
+        }
+        self.0
+            .iter()
+            .map(Capability::to_string)
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// OpenAPIのパス一覧から判定する（申告しない古いサーバー向け）
+    fn from_openapi(openapi: &serde_json::Value) -> Self {
+        let paths = openapi.get("paths").and_then(|p| p.as_object());
+        Self(
+            Capability::ALL
+                .into_iter()
+                .filter(|c| {
+                    c.endpoint()
+                        .zip(paths)
+                        .is_some_and(|(endpoint, paths)| paths.contains_key(endpoint))
+                })
+                .collect(),
+        )
+    }
+}
+
+impl FromIterator<Capability> for ServerCapabilities {
+    fn from_iter<I: IntoIterator<Item = Capability>>(iter: I) -> Self {
+        Self(iter.into_iter().collect())
+    }
+}
+
+/// GPU使用率がこの割合を超えたら逼迫とみなす
+const GPU_PRESSURE_RATIO: f64 = 0.9;
+
+/// サーバーのリソース使用状況（/statusの一部）
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ResourceStats {
+    /// 処理中の変換リクエスト数
+    #[serde(default)]
+    pub queue_depth: u32,
+    pub gpu_memory_used_mb: Option<f64>,
+    pub gpu_memory_total_mb: Option<f64>,
+    pub gpu_utilization_percent: Option<f64>,
+    pub cpu_load_percent: Option<f64>,
+}
+
+impl ResourceStats {
+    /// GPUメモリ使用率（0.0-1.0）
+    pub fn gpu_memory_ratio(&self) -> Option<f64> {
+        match (self.gpu_memory_used_mb, self.gpu_memory_total_mb) {
+            (Some(used), Some(total)) if total > 0.0 => Some(used / total),
+            _ => None,
+        }
+    }
+
+    /// GPUが処理に追いつかなくなりそうか
+    pub fn is_under_pressure(&self) -> bool {
+        self.queue_depth > 1
+            || self
+                .gpu_memory_ratio()
+                .is_some_and(|r| r > GPU_PRESSURE_RATIO)
+            || self
+                .gpu_utilization_percent
+                .is_some_and(|u| u / 100.0 > GPU_PRESSURE_RATIO)
+    }
+
+    /// 1行サマリー
+    pub fn summary(&self) -> String {
+        let mut parts = Vec::new();
+
+        if let (Some(used), Some(total)) = (self.gpu_memory_used_mb, self.gpu_memory_total_mb) {
+            parts.push(format!("GPUメモリ {:.0}/{:.0}MB", used, total));
+        }
+        if let Some(util) = self.gpu_utilization_percent {
+            parts.push(format!("GPU使用率 {:.0}%", util));
+        }
+        if let Some(cpu) = self.cpu_load_percent {
+            parts.push(format!("CPU負荷 {:.0}%", cpu));
+        }
+        parts.push(format!("キュー {}", self.queue_depth));
+
+        parts.join(" | ")
+    }
+}
+
+/// サーバーのステータス（/status）
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ServerStatus {
+    pub status: String,
+    /// 推論デバイス（cuda, cpu）
+    pub device: String,
+    /// ロード済みのモデル数
+    pub models_loaded: u32,
+    pub uptime_seconds: f64,
+    /// サーバーのバージョン（古いサーバーは返さない）
+    #[serde(default)]
+    pub server_version: Option<String>,
+    /// サーバーが話すプロトコルのバージョン（返さないサーバーはv1）
+    #[serde(default = "legacy_protocol_version")]
+    pub protocol_version: u32,
+    /// サーバーが受け付けるクライアントの最低プロトコルバージョン
+    #[serde(default = "legacy_protocol_version")]
+    pub min_client_protocol_version: u32,
+    /// サーバーが申告するオプション機能（古いサーバーは返さない）
+    #[serde(default)]
+    pub capabilities: Option<ServerCapabilities>,
+    /// モデルが想定する入力のサンプルレート（申告しないサーバーはどのレートでも受け付ける）
+    #[serde(default)]
+    pub sample_rate: Option<u32>,
+    #[serde(flatten)]
+    pub resources: ResourceStats,
+}
+
+fn legacy_protocol_version() -> u32 {
+    1
+}
+
+impl ServerStatus {
+    /// 双方で通じるプロトコルのバージョン
+    pub fn negotiated_protocol_version(&self) -> u32 {
+        self.protocol_version.min(PROTOCOL_VERSION)
+    }
+
+    /// このサーバーとの間で機能が使えるか
+    pub fn supports(&self, feature: ProtocolFeature) -> bool {
+        self.negotiated_protocol_version() >= feature.since()
+    }
+
+    /// プロトコルが通じるか確認する
+    ///
+    /// 通じない組み合わせならエラーを返します。サーバーが古い場合は
+    /// 使えない機能を警告して、使える範囲で続行します。
+    pub fn negotiate(&self) -> Result<()> {
+        if self.protocol_version < MIN_SERVER_PROTOCOL_VERSION {
+            anyhow::bail!(
+                "サーバーのプロトコルが古すぎます（サーバー: v{}, クライアントが必要: v{}以上）。サーバーを更新してください",
+                self.protocol_version,
+                MIN_SERVER_PROTOCOL_VERSION
+            );
+        }
+        if PROTOCOL_VERSION < self.min_client_protocol_version {
+            anyhow::bail!(
+                "クライアントのプロトコルが古すぎます（クライアント: v{}, サーバーが必要: v{}以上）。makebeliv self-update で更新してください",
+                PROTOCOL_VERSION,
+                self.min_client_protocol_version
+            );
+        }
+
+        let unsupported: Vec<String> = ProtocolFeature::ALL
+            .into_iter()
+            .filter(|&feature| !self.supports(feature))
+            .map(|feature| feature.to_string())
+            .collect();
+        if unsupported.is_empty() {
+            debug!(
+                "プロトコル: v{}（サーバー: v{}）",
+                self.negotiated_protocol_version(),
+                self.protocol_version
+            );
+        } else {
+            warn!(
+                "サーバーのプロトコルが古いため無効な機能があります（サーバー: v{}, クライアント: v{}）: {}",
+                self.protocol_version,
+                PROTOCOL_VERSION,
+                unsupported.join(", ")
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// サーバーで利用可能なモデル（/models）
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "ModelEntry")]
+pub struct ModelInfo {
+    pub name: String,
+    /// 重みファイル（models/<名前>/model.pth）があるか（なければデモモード）
+    pub has_weights: bool,
+    /// サーバーのメモリにロード済みか
+    pub loaded: bool,
+    /// 重みファイルのバージョン（SHA-256の先頭12桁。古いサーバー・重みのないモデルはNone）
+    pub version: Option<String>,
+}
+
+/// /models の要素（古いサーバーはモデル名の文字列だけを返す）
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ModelEntry {
+    Name(String),
+    Info {
+        name: String,
+        #[serde(default)]
+        has_weights: bool,
+        #[serde(default)]
+        loaded: bool,
+        #[serde(default)]
+        version: Option<String>,
+    },
+}
+
+impl From<ModelEntry> for ModelInfo {
+    fn from(entry: ModelEntry) -> Self {
+        match entry {
+            ModelEntry::Name(name) => Self {
+                name,
+                ..Default::default()
+            },
+            ModelEntry::Info {
+                name,
+                has_weights,
+                loaded,
+                version,
+            } => Self {
+                name,
+                has_weights,
+                loaded,
+                version,
+            },
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ModelList {
+    models: Vec<ModelInfo>,
+}
+
+/// サーバーが状態を持っている変換セッション（/sessions）
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SessionInfo {
+    pub session_id: String,
+    /// 変換したチャンク数
+    #[serde(default)]
+    pub chunks: u64,
+    /// 作成からの経過秒数
+    #[serde(default)]
+    pub age_seconds: f64,
+    /// 最後のチャンクからの経過秒数
+    #[serde(default)]
+    pub idle_seconds: f64,
+}
+
+#[derive(Deserialize)]
+struct SessionList {
+    sessions: Vec<SessionInfo>,
+}
+
+/// 文字起こしの1語と、音声の先頭からの時刻（/transcribe）
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TranscribedWord {
+    pub word: String,
+    /// 開始・終了（秒）
+    pub start: f64,
+    pub end: f64,
+}
+
+#[derive(Deserialize)]
+struct Transcription {
+    words: Vec<TranscribedWord>,
+}
+
+/// 1人の話者が話している区間（/diarize）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpeakerTurn {
+    pub speaker: String,
+    /// 開始・終了（秒）
+    pub start: f64,
+    pub end: f64,
+}
+
+#[derive(Deserialize)]
+struct Diarization {
+    segments: Vec<SpeakerTurn>,
+}
+
+/// チャンク変換の通信方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Transport {
+    /// チャンクごとにHTTPのマルチパートで送る
+    #[default]
+    Http,
+    /// WebSocketの接続を保ったまま生のPCMを送受信する
+    Ws,
+}
+
+impl FromStr for Transport {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "http" => Ok(Self::Http),
+            "ws" | "websocket" => Ok(Self::Ws),
+            _ => anyhow::bail!("不明な通信方式: {}（http, ws）", s),
+        }
+    }
+}
+
+impl std::fmt::Display for Transport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Http => "http",
+            Self::Ws => "ws",
+        })
+    }
+}
+
+/// チャンクの音声の符号化方式（`--codec`）
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Codec {
+    /// 圧縮しない（HTTPではWAV、WebSocketでは32bit floatのPCM）
+    #[default]
+    Pcm,
+    /// Opusで圧縮する（サーバーが `opus` に対応しているときだけ）
+    Opus,
+}
+
+impl Codec {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Pcm => "pcm",
+            Self::Opus => "opus",
+        }
+    }
+}
+
+impl FromStr for Codec {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "pcm" | "wav" => Ok(Self::Pcm),
+            "opus" => Ok(Self::Opus),
+            _ => anyhow::bail!("不明なコーデック: {}（pcm, opus）", s),
+        }
+    }
+}
+
+impl std::fmt::Display for Codec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// 接続の既定のタイムアウト（秒）
+pub const DEFAULT_CONNECT_TIMEOUT_SECS: f64 = 10.0;
+
+/// リクエストの既定のタイムアウト（秒）。長いファイルの変換も収まるよう長めにする
+pub const DEFAULT_TIMEOUT_SECS: f64 = 600.0;
+
+/// チャンク変換の既定のタイムアウト（秒）。これより遅れたチャンクは再生に間に合わない
+pub const DEFAULT_CHUNK_TIMEOUT_SECS: f64 = 3.0;
+
+/// HTTPのタイムアウト
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Timeouts {
+    /// サーバーへの接続（TCP・TLS・WebSocketのハンドシェイク）
+    pub connect: Duration,
+    /// ファイル変換などのリクエスト全体（応答の受信まで）
+    pub request: Duration,
+    /// チャンク変換1回（HTTP・WebSocket）
+    pub chunk: Duration,
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Self {
+            connect: Duration::from_secs_f64(DEFAULT_CONNECT_TIMEOUT_SECS),
+            request: Duration::from_secs_f64(DEFAULT_TIMEOUT_SECS),
+            chunk: Duration::from_secs_f64(DEFAULT_CHUNK_TIMEOUT_SECS),
+        }
+    }
+}
+
+impl Timeouts {
+    /// 接続とリクエストのタイムアウトを指定する（チャンクはリクエストより長くしない）
+    pub fn new(connect: Duration, request: Duration) -> Self {
+        Self {
+            connect,
+            request,
+            chunk: request.min(Duration::from_secs_f64(DEFAULT_CHUNK_TIMEOUT_SECS)),
+        }
+    }
+}
+
+/// このプロセスで作るクライアントのタイムアウト（`--timeout` / `--connect-timeout`）
+static TIMEOUTS: OnceLock<Timeouts> = OnceLock::new();
+
+/// 以後に作るクライアントのタイムアウトを設定する（最初の1回だけ有効）
+pub fn set_timeouts(timeouts: Timeouts) {
+    if TIMEOUTS.set(timeouts).is_err() {
+        warn!("HTTPのタイムアウトは設定済みです");
+    }
+}
+
+pub(crate) fn timeouts() -> Timeouts {
+    TIMEOUTS.get().copied().unwrap_or_default()
+}
+
+/// WebSocketでのチャンク変換のエンドポイント
+const STREAM_PATH: &str = "/ws/convert-chunk";
+
+/// 変換結果のバイナリメッセージの先頭に付くヘッダーのバイト数
+///
+/// `[sample_rate: u32][channels: u16][codec: u16][processing_time_ms: f32]`（リトルエンディアン）
+/// の後に、32bit floatのインターリーブPCM（`codec` が0）か、Opusのチャンク（`codec` が1、
+/// [`crate::opus_chunk`] の形式）が続きます。
+pub const STREAM_HEADER_BYTES: usize = 12;
+
+/// 変換結果のヘッダーの `codec`
+pub const STREAM_CODEC_PCM: u16 = 0;
+pub const STREAM_CODEC_OPUS: u16 = 1;
+
+/// WebSocketで送る音声の形式と変換パラメータ
+///
+/// 変わったときだけ `{"type": "config", ...}` として送り直します。
+/// 送る音声は32bit floatのインターリーブPCM（ヘッダーなし）か、`codec` がOpusなら
+/// Opusのチャンクです。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StreamSettings {
+    pub model: String,
+    pub pitch_shift: i32,
+    pub session_id: String,
+    pub sample_rate: u32,
+    pub channels: u16,
+    /// 送受信する音声の形式（Opusに対応しないサーバーは無視してPCMとして読むため、
+    /// サーバーが `opus` を申告しているときだけOpusにする）
+    #[serde(default)]
+    pub codec: Codec,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ClientMessage<'a> {
+    Config(&'a StreamSettings),
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ServerMessage {
+    Error { detail: String },
+}
+
+/// WebSocketで変換したチャンク
+pub struct StreamChunk {
+    pub samples: Vec<f32>,
+    pub sample_rate: u32,
+    pub channels: u16,
+    /// サーバー側の処理時間
+    pub processing_time_ms: f64,
+    /// 送信したバイト数
+    pub bytes_sent: usize,
+    /// 受信したバイト数
+    pub bytes_received: usize,
+}
+
+/// 変換サーバーとの持続的なWebSocket接続
+pub struct ConversionStream {
+    socket: WebSocketStream<MaybeTlsStream<TcpStream>>,
+    /// 最後に送った設定
+    settings: Option<StreamSettings>,
+    /// 1チャンクの変換結果を待つ上限
+    timeout: Duration,
+}
+
+impl ConversionStream {
+    /// 1チャンクを送り、変換結果を待つ
+    pub async fn convert(
+        &mut self,
+        settings: &StreamSettings,
+        samples: &[f32],
+    ) -> Result<StreamChunk> {
+        if self.settings.as_ref() != Some(settings) {
+            let config = serde_json::to_string(&ClientMessage::Config(settings))?;
+            self.socket
+                .send(Message::Text(config))
+                .await
+                .context("ストリーム設定の送信エラー")?;
+            self.settings = Some(settings.clone());
+        }
+
+        let payload = match settings.codec {
+            Codec::Pcm => samples.iter().flat_map(|s| s.to_le_bytes()).collect(),
+            Codec::Opus => opus_chunk::encode(samples, settings.sample_rate, settings.channels)?,
+        };
+        let bytes_sent = payload.len();
+        let timeout = self.timeout;
+        tokio::time::timeout(timeout, self.exchange(payload))
+            .await
+            .map_err(|_| {
+                anyhow::anyhow!(
+                    "ストリームの変換結果が{:.1}秒以内に届きません",
+                    timeout.as_secs_f64()
+                )
+            })?
+            .map(|data| decode_stream_chunk(&data, bytes_sent))?
+    }
+
+    /// 音声を送り、変換結果のメッセージを受け取る
+    async fn exchange(&mut self, payload: Vec<u8>) -> Result<Vec<u8>> {
+        self.socket
+            .send(Message::Binary(payload))
+            .await
+            .context("ストリーム送信エラー")?;
+
+        loop {
+            let message = self
+                .socket
+                .next()
+                .await
+                .context("ストリームが閉じられました")?
+                .context("ストリーム受信エラー")?;
+
+            match message {
+                Message::Binary(data) => return Ok(data),
+                Message::Text(text) => match serde_json::from_str(&text) {
+                    Ok(ServerMessage::Error { detail }) => {
+                        anyhow::bail!("サーバーの変換エラー: {}", detail)
+                    }
+                    Err(_) => debug!("不明なストリームメッセージ: {}", text),
+                },
+                Message::Close(frame) => {
+                    anyhow::bail!("ストリームが閉じられました: {:?}", frame)
+                }
+                // Pingへの応答はtungsteniteが行う
+                _ => {}
+            }
+        }
+    }
+
+    /// 接続を閉じる
+    pub async fn close(mut self) -> Result<()> {
+        self.socket
+            .close(None)
+            .await
+            .context("ストリームの切断エラー")
+    }
+}
+
+fn decode_stream_chunk(data: &[u8], bytes_sent: usize) -> Result<StreamChunk> {
+    anyhow::ensure!(
+        data.len() >= STREAM_HEADER_BYTES,
+        "変換結果の形式が不正です（{} bytes）",
+        data.len()
+    );
+
+    let mut sample_rate = u32::from_le_bytes(data[0..4].try_into()?);
+    let mut channels = u16::from_le_bytes(data[4..6].try_into()?);
+    let codec = u16::from_le_bytes(data[6..8].try_into()?);
+    let processing_time_ms = f32::from_le_bytes(data[8..12].try_into()?) as f64;
+    let samples = match codec {
+        STREAM_CODEC_PCM => {
+            anyhow::ensure!(
+                (data.len() - STREAM_HEADER_BYTES).is_multiple_of(4),
+                "変換結果の形式が不正です（{} bytes）",
+                data.len()
+            );
+            data[STREAM_HEADER_BYTES..]
+                .chunks_exact(4)
+                .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                .collect()
+        }
+        STREAM_CODEC_OPUS => {
+            let (samples, rate, decoded_channels) =
+                opus_chunk::decode(&data[STREAM_HEADER_BYTES..])?;
+            sample_rate = rate;
+            channels = decoded_channels;
+            samples
+        }
+        _ => anyhow::bail!("変換結果のコーデックが不明です: {}", codec),
+    };
+
+    Ok(StreamChunk {
+        samples,
+        sample_rate,
+        channels,
+        processing_time_ms,
+        bytes_sent,
+        bytes_received: data.len(),
+    })
+}
 
 /// 音声変換APIクライアント
+///
+/// 複製しても接続プール・調べたオプション機能・調停は共有されます。
+#[derive(Clone)]
 pub struct VoiceConversionClient {
     client: reqwest::Client,
     base_url: String,
+    /// 接続時に調べたオプション機能（未確認ならNone）
+    capabilities: Arc<OnceLock<ServerCapabilities>>,
+    /// リアルタイム変換とバッチ変換の調停（同じプロセスで両方を行うとき）
+    arbiter: Option<Arc<Arbiter>>,
+    /// `Authorization` ヘッダー（APIキーがなければNone）
+    authorization: Option<HeaderValue>,
+    /// ファイル変換の再試行
+    retry: RetryPolicy,
+    /// チャンク変換の再試行
+    chunk_retry: RetryPolicy,
+    timeouts: Timeouts,
+    /// ファイル変換（`convert_file`）の進み具合の表示
+    progress: Option<TransferProgress>,
 }
 
 impl VoiceConversionClient {
     /// 新しいクライアントを作成
-    pub fn new(base_url: String) -> Self {
-        Self {
-            client: reqwest::Client::new(),
+    ///
+    /// `auth login` で保存したAPIキー（または `MAKEBELIV_API_KEY`）があれば、すべての
+    /// リクエストに `Authorization: Bearer` で付けます。
+    pub fn new(base_url: String) -> Result<Self> {
+        let authorization = auth::api_key(&base_url)
+            .map(|key| {
+                let mut value = HeaderValue::from_str(&format!("Bearer {}", key))
+                    .context("APIキーにヘッダーに使えない文字が含まれています")?;
+                value.set_sensitive(true);
+                anyhow::Ok(value)
+            })
+            .transpose()?;
+
+        let mut headers = HeaderMap::new();
+        headers.insert(PROTOCOL_HEADER, PROTOCOL_VERSION.into());
+        if let Some(value) = &authorization {
+            headers.insert(AUTHORIZATION, value.clone());
+        }
+        let timeouts = timeouts();
+        let client = reqwest::Client::builder()
+            .default_headers(headers)
+            .connect_timeout(timeouts.connect)
+            .timeout(timeouts.request)
+            .build()
+            .context("HTTPクライアントを作成できません")?;
+
+        Ok(Self {
+            client,
             base_url,
+            capabilities: Arc::default(),
+            arbiter: None,
+            authorization,
+            retry: RetryPolicy::default(),
+            chunk_retry: RetryPolicy::chunk(),
+            timeouts,
+            progress: None,
+        })
+    }
+
+    /// ファイル変換（`convert_wav`）の再試行を設定する（既定: 3回まで）
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// ファイル変換（`convert_file`）の送受信の進み具合を表示する
+    pub fn with_progress(mut self, progress: Option<TransferProgress>) -> Self {
+        self.progress = progress;
+        self
+    }
+
+    /// チャンク変換（`convert_chunk`）の再試行を設定する（既定: 2回まで）
+    pub fn with_chunk_retry(mut self, retry: RetryPolicy) -> Self {
+        self.chunk_retry = retry;
+        self
+    }
+
+    /// リアルタイム変換とバッチ変換を調停する（`monitor --batch`）
+    pub fn with_arbiter(mut self, arbiter: Arc<Arbiter>) -> Self {
+        self.arbiter = Some(arbiter);
+        self
+    }
+
+    pub fn arbiter(&self) -> Option<&Arc<Arbiter>> {
+        self.arbiter.as_ref()
+    }
+
+    /// APIキーを付けて送るか
+    pub fn has_api_key(&self) -> bool {
+        self.authorization.is_some()
+    }
+
+    /// 接続時のハンドシェイク（プロトコルのバージョンを確認）
+    pub async fn handshake(&self) -> Result<ServerStatus> {
+        let status = self.check_status().await?;
+        status.negotiate()?;
+        self.probe_capabilities(&status).await;
+        Ok(status)
+    }
+
+    /// サーバーが対応しているオプション機能を調べ、以降の呼び出しで使う
+    ///
+    /// 申告がなければOpenAPIのパス一覧から判定します。
+    /// どちらも取れなければ何も対応していないものとして扱います。
+    pub async fn probe_capabilities(&self, status: &ServerStatus) -> &ServerCapabilities {
+        let capabilities = match &status.capabilities {
+            Some(capabilities) => capabilities.clone(),
+            None => match self.fetch_openapi().await {
+                Ok(openapi) => ServerCapabilities::from_openapi(&openapi),
+                Err(e) => {
+                    debug!("OpenAPIの取得エラー: {:#}", e);
+                    ServerCapabilities::default()
+                }
+            },
+        };
+        info!("サーバーの対応機能: {}", capabilities.summary());
+
+        self.capabilities.get_or_init(|| capabilities)
+    }
+
+    /// 調べたオプション機能（未確認ならNone）
+    pub fn capabilities(&self) -> Option<&ServerCapabilities> {
+        self.capabilities.get()
+    }
+
+    /// 機能が使えるか（未確認なら使えるものとして試す）
+    pub fn supports(&self, capability: Capability) -> bool {
+        self.capabilities
+            .get()
+            .is_none_or(|capabilities| capabilities.supports(capability))
+    }
+
+    /// リクエストを送る（`--debug-http` が有効なら送受信を記録する）
+    async fn send(
+        &self,
+        call: &str,
+        request: reqwest::RequestBuilder,
+        fields: &[(&str, String)],
+    ) -> reqwest::Result<reqwest::Response> {
+        match debug_http::recorder() {
+            Some(recorder) => {
+                recorder
+                    .execute(&self.client, call, request.build()?, fields)
+                    .await
+            }
+            None => request.send().await,
         }
     }
 
+    async fn fetch_openapi(&self) -> Result<serde_json::Value> {
+        let url = format!("{}/openapi.json", self.base_url);
+        self.send("openapi", self.client.get(&url), &[])
+            .await?
+            .error_for_status()?
+            .json()
+            .await
+            .context("OpenAPIの解析エラー")
+    }
+
+    /// JSONの応答を型に読み込む
+    ///
+    /// サーバーと形式が食い違ったときに、どのエンドポイントのどの項目かを示すエラーにします。
+    async fn decode<T: DeserializeOwned>(response: reqwest::Response, endpoint: &str) -> Result<T> {
+        let body = response
+            .bytes()
+            .await
+            .with_context(|| format!("{} の応答の受信エラー", endpoint))?;
+        let mut deserializer = serde_json::Deserializer::from_slice(&body);
+        serde_path_to_error::deserialize(&mut deserializer).map_err(|e| {
+            anyhow::anyhow!(
+                "{} の応答の形式が想定と異なります（項目 {}）: {}",
+                endpoint,
+                e.path(),
+                e.inner()
+            )
+        })
+    }
+
     /// サーバーのステータスを確認
-    pub async fn check_status(&self) -> Result<serde_json::Value> {
+    pub async fn check_status(&self) -> Result<ServerStatus> {
         let url = format!("{}/status", self.base_url);
         let response = self
-            .client
-            .get(&url)
-            .send()
+            .send("status", self.client.get(&url), &[])
             .await
             .context("ステータス取得エラー")?;
+        if response.status() == reqwest::StatusCode::UNAUTHORIZED {
+            anyhow::bail!(
+                "APIキーが{}（makebeliv auth login --api-url {} で保存できます）",
+                if self.has_api_key() {
+                    "正しくありません"
+                } else {
+                    "必要です"
+                },
+                self.base_url
+            );
+        }
+        let response = response
+            .error_for_status()
+            .context("ステータス取得エラー")?;
 
-        let status = response.json().await.context("JSON解析エラー")?;
-        Ok(status)
+        Self::decode(response, "/status").await
     }
 
-    /// 音声ファイルを変換
-    pub async fn convert_file(
+    /// サーバーのリソース使用状況を取得
+    pub async fn resource_stats(&self) -> Result<ResourceStats> {
+        Ok(self.check_status().await?.resources)
+    }
+
+    /// サーバーで利用可能なモデル一覧を取得
+    ///
+    /// モデル一覧に対応していないサーバーでは `default` だけを返します。
+    pub async fn list_models(&self) -> Result<Vec<ModelInfo>> {
+        if !self.supports(Capability::Models) {
+            debug!("モデル一覧に未対応のサーバーのため default のみ使用");
+            return Ok(vec![ModelInfo {
+                name: "default".to_string(),
+                ..Default::default()
+            }]);
+        }
+
+        let url = format!("{}/models", self.base_url);
+        let response = self
+            .send("models", self.client.get(&url), &[])
+            .await
+            .context("モデル一覧取得エラー")?
+            .error_for_status()
+            .context("モデル一覧取得エラー")?;
+
+        let list: ModelList = Self::decode(response, "/models").await?;
+
+        Ok(list.models)
+    }
+
+    /// WAV音声を変換（変換後の音声はWAVで返る）
+    pub async fn convert_wav(
         &self,
-        input_path: &Path,
-        output_path: &Path,
+        audio: Vec<u8>,
         model: &str,
         pitch_shift: i32,
         noise_type: &str,
         noise_level: f32,
-    ) -> Result<()> {
+    ) -> Result<ConvertResponse> {
         info!("音声変換リクエスト送信...");
 
-        // ファイルを読み込み
-        let audio_bytes = std::fs::read(input_path).context("入力ファイル読み込みエラー")?;
-
-        // マルチパートフォームを構築
-        let form = multipart::Form::new()
-            .part(
-                "audio",
-                multipart::Part::bytes(audio_bytes)
-                    .file_name(input_path.file_name().unwrap().to_string_lossy().to_string())
-                    .mime_str("audio/wav")?,
-            )
-            .text("model", model.to_string())
-            .text("pitch_shift", pitch_shift.to_string())
-            .text("noise_type", noise_type.to_string())
-            .text("noise_level", noise_level.to_string());
+        let audio = Bytes::from(audio);
+        let fields = [
+            ("audio", format!("<{} bytes>", audio.len())),
+            ("model", model.to_string()),
+            ("pitch_shift", pitch_shift.to_string()),
+            ("noise_type", noise_type.to_string()),
+            ("noise_level", noise_level.to_string()),
+        ];
 
-        // リクエスト送信
-        let url = format!("{}/convert", self.base_url);
         let response = self
-            .client
-            .post(&url)
-            .multipart(form)
-            .send()
-            .await
-            .context("変換リクエストエラー")?;
+            .retry
+            .run("変換リクエスト", || async {
+                // 送り直すたびにフォームを作る（音声は複製せず共有する）
+                let form = multipart::Form::new()
+                    .part(
+                        "audio",
+                        multipart::Part::stream_with_length(audio.clone(), audio.len() as u64)
+                            .file_name("input.wav")
+                            .mime_str("audio/wav")?,
+                    )
+                    .text("model", model.to_string())
+                    .text("pitch_shift", pitch_shift.to_string())
+                    .text("noise_type", noise_type.to_string())
+                    .text("noise_level", noise_level.to_string());
 
-        // 処理時間を取得
-        if let Some(processing_time) = response.headers().get("X-Processing-Time-Ms") {
-            info!(
-                "サーバー処理時間: {}ms",
-                processing_time.to_str().unwrap_or("?")
-            );
+                let url = format!("{}/convert", self.base_url);
+                let request = self
+                    .client
+                    .post(&url)
+                    .header(PRIORITY_HEADER, Priority::Batch.as_str())
+                    .multipart(form);
+                let response = self
+                    .send("convert", request, &fields)
+                    .await
+                    .context("変換リクエストエラー")?
+                    .error_for_status()
+                    .context("変換リクエストエラー")?;
+
+                let meta = ConvertResponseMeta::from_headers(response.headers());
+                let audio = response.bytes().await.context("レスポンス読み込みエラー")?;
+                Ok(ConvertResponse { audio, meta })
+            })
+            .await?;
+
+        if let Some(processing_time) = response.meta.processing_time_ms {
+            info!("サーバー処理時間: {}ms", processing_time);
         }
+        Ok(response)
+    }
+
+    /// WAVファイルを変換し、変換後のWAVを `output` に書き出す
+    ///
+    /// 入力はファイルから読みながら送り、応答も受け取りながら書き出すので、
+    /// 長い録音でも全体をメモリに載せません。再試行のたびにファイルを開き直します。
+    /// 書き込みは `<output>.part` に行い、受信し終えてから置き換えます。
+    pub async fn convert_file(
+        &self,
+        input: &Path,
+        output: &Path,
+        model: &str,
+        pitch_shift: i32,
+        noise_type: &str,
+        noise_level: f32,
+    ) -> Result<ConvertResponseMeta> {
+        info!("音声変換リクエスト送信（ストリーミング）...");
 
-        // レスポンスを保存
-        let audio_data = response.bytes().await.context("レスポンス読み込みエラー")?;
+        let length = tokio::fs::metadata(input)
+            .await
+            .with_context(|| format!("入力ファイルの読み込みエラー: {}", input.display()))?
+            .len();
+        let fields = [
+            ("audio", format!("<{} bytes, {}>", length, input.display())),
+            ("model", model.to_string()),
+            ("pitch_shift", pitch_shift.to_string()),
+            ("noise_type", noise_type.to_string()),
+            ("noise_level", noise_level.to_string()),
+        ];
 
-        std::fs::write(output_path, audio_data).context("出力ファイル書き込みエラー")?;
+        let mut partial = output.as_os_str().to_owned();
+        partial.push(".part");
+        let partial = PathBuf::from(partial);
+        let progress = self.progress.as_ref().map(|progress| {
+            progress.start(&input.file_name().unwrap_or_default().to_string_lossy())
+        });
 
-        info!("✓ 変換完了: {}", output_path.display());
+        let result = self
+            .retry
+            .run("変換リクエスト", || async {
+                // 送り直すたびにファイルを先頭から読み直す
+                let file = tokio::fs::File::open(input).await.with_context(|| {
+                    format!("入力ファイルの読み込みエラー: {}", input.display())
+                })?;
+                if let Some(progress) = &progress {
+                    progress.uploading(length);
+                }
+                let count = progress.as_ref().map(|progress| progress.upload_counter());
+                let body =
+                    reqwest::Body::wrap_stream(ReaderStream::new(file).inspect(move |chunk| {
+                        if let (Some(count), Ok(chunk)) = (&count, chunk) {
+                            count(chunk.len());
+                        }
+                    }));
+                let form = multipart::Form::new()
+                    .part(
+                        "audio",
+                        multipart::Part::stream_with_length(body, length)
+                            .file_name("input.wav")
+                            .mime_str("audio/wav")?,
+                    )
+                    .text("model", model.to_string())
+                    .text("pitch_shift", pitch_shift.to_string())
+                    .text("noise_type", noise_type.to_string())
+                    .text("noise_level", noise_level.to_string());
 
-        Ok(())
+                let url = format!("{}/convert", self.base_url);
+                let request = self
+                    .client
+                    .post(&url)
+                    .header(PRIORITY_HEADER, Priority::Batch.as_str())
+                    .multipart(form);
+                let response = self
+                    .send("convert", request, &fields)
+                    .await
+                    .context("変換リクエストエラー")?
+                    .error_for_status()
+                    .context("変換リクエストエラー")?;
+
+                let meta = ConvertResponseMeta::from_headers(response.headers());
+                if let Some(progress) = &progress {
+                    progress.downloading(response.content_length(), meta.processing_time_ms);
+                }
+                let mut file = tokio::io::BufWriter::new(
+                    tokio::fs::File::create(&partial).await.with_context(|| {
+                        format!("出力ファイルの作成エラー: {}", partial.display())
+                    })?,
+                );
+                let mut body = response.bytes_stream();
+                while let Some(chunk) = body.next().await {
+                    let chunk = chunk.context("レスポンス読み込みエラー")?;
+                    if let Some(progress) = &progress {
+                        progress.downloaded(chunk.len());
+                    }
+                    file.write_all(&chunk)
+                        .await
+                        .context("出力ファイルの書き込みエラー")?;
+                }
+                file.flush().await.context("出力ファイルの書き込みエラー")?;
+                Ok(meta)
+            })
+            .await
+            .and_then(|meta| {
+                std::fs::rename(&partial, output).with_context(|| {
+                    format!("出力ファイルの書き込みエラー: {}", output.display())
+                })?;
+                Ok(meta)
+            });
+
+        drop(progress);
+        let meta = result.inspect_err(|_| {
+            std::fs::remove_file(&partial).ok();
+        })?;
+        if let Some(processing_time) = meta.processing_time_ms {
+            info!("サーバー処理時間: {}ms", processing_time);
+        }
+        Ok(meta)
     }
 
     /// 音声チャンクを変換（リアルタイム用）
+    ///
+    /// `audio_data` は `codec` の形式（PCMならWAV）です。サーバーがOpusで返したかは
+    /// 返り値の `meta.codec` で分かります。
     pub async fn convert_chunk(
         &self,
         audio_data: &[u8],
+        codec: Codec,
         model: &str,
         pitch_shift: i32,
         session_id: &str,
-    ) -> Result<Bytes> {
+    ) -> Result<ConvertResponse> {
         debug!("チャンク変換リクエスト: {} bytes", audio_data.len());
 
+        let fields = [
+            ("audio", format!("<{} bytes, {}>", audio_data.len(), codec)),
+            ("model", model.to_string()),
+            ("pitch_shift", pitch_shift.to_string()),
+            ("session_id", session_id.to_string()),
+        ];
+        let (file_name, mime) = match codec {
+            Codec::Pcm => ("chunk.wav", "audio/wav"),
+            Codec::Opus => ("chunk.opus", "audio/opus"),
+        };
+
+        let audio = Bytes::copy_from_slice(audio_data);
+        self.chunk_retry
+            .run("チャンク変換リクエスト", || async {
+                let form = multipart::Form::new()
+                    .part(
+                        "audio",
+                        multipart::Part::stream_with_length(audio.clone(), audio.len() as u64)
+                            .file_name(file_name)
+                            .mime_str(mime)?,
+                    )
+                    .text("model", model.to_string())
+                    .text("pitch_shift", pitch_shift.to_string())
+                    .text("session_id", session_id.to_string());
+
+                let url = format!("{}/convert-chunk", self.base_url);
+                let request = self
+                    .client
+                    .post(&url)
+                    .header(PRIORITY_HEADER, Priority::Realtime.as_str())
+                    .header(CODEC_HEADER, codec.as_str())
+                    .timeout(self.timeouts.chunk)
+                    .multipart(form);
+                let response = self
+                    .send("convert-chunk", request, &fields)
+                    .await
+                    .context("チャンク変換リクエストエラー")?
+                    .error_for_status()
+                    .context("チャンク変換リクエストエラー")?;
+
+                let meta = ConvertResponseMeta::from_headers(response.headers());
+                let audio = response.bytes().await.context("チャンク読み込みエラー")?;
+
+                Ok(ConvertResponse { audio, meta })
+            })
+            .await
+    }
+
+    /// セッションをリセット（サーバーの揺らぎの状態を捨てる）
+    pub async fn reset_session(&self, session_id: &str) -> Result<()> {
+        let url = format!("{}/reset-session", self.base_url);
+        let request = self.client.post(&url).query(&[("session_id", session_id)]);
+        self.send("reset-session", request, &[])
+            .await
+            .context("セッションリセットエラー")?
+            .error_for_status()
+            .context("セッションリセットエラー")?;
+
+        info!("セッションリセット完了: {}", session_id);
+        Ok(())
+    }
+
+    /// サーバーが状態を持っているセッションの一覧を取得
+    pub async fn list_sessions(&self) -> Result<Vec<SessionInfo>> {
+        anyhow::ensure!(
+            self.supports(Capability::Sessions),
+            "サーバーがセッション一覧に対応していません"
+        );
+
+        let url = format!("{}/sessions", self.base_url);
+        let response = self
+            .send("sessions", self.client.get(&url), &[])
+            .await
+            .context("セッション一覧取得エラー")?
+            .error_for_status()
+            .context("セッション一覧取得エラー")?;
+
+        let list: SessionList = Self::decode(response, "/sessions").await?;
+
+        Ok(list.sessions)
+    }
+
+    /// WAVを文字起こしして、語ごとの時刻を取得
+    pub async fn transcribe(&self, audio: Vec<u8>) -> Result<Vec<TranscribedWord>> {
+        anyhow::ensure!(
+            self.supports(Capability::Transcribe),
+            "サーバーが文字起こしに対応していません"
+        );
+
+        let fields = [("audio", format!("<{} bytes>", audio.len()))];
+        let form = multipart::Form::new().part(
+            "audio",
+            multipart::Part::bytes(audio)
+                .file_name("input.wav")
+                .mime_str("audio/wav")?,
+        );
+
+        // 出力を止めて待っているため、リアルタイムのチャンクと同じ優先度で送る
+        let url = format!("{}/transcribe", self.base_url);
+        let request = self
+            .client
+            .post(&url)
+            .header(PRIORITY_HEADER, Priority::Realtime.as_str())
+            .multipart(form);
+        let response = self
+            .send("transcribe", request, &fields)
+            .await
+            .context("文字起こしリクエストエラー")?
+            .error_for_status()
+            .context("文字起こしリクエストエラー")?;
+
+        let transcription: Transcription = Self::decode(response, "/transcribe").await?;
+
+        Ok(transcription.words)
+    }
+
+    /// WAVの話者を区別して、話者ごとの区間を取得
+    ///
+    /// `speakers` は話者の数の見込みです（サーバーは目安として使います）。
+    pub async fn diarize(&self, audio: Vec<u8>, speakers: usize) -> Result<Vec<SpeakerTurn>> {
+        anyhow::ensure!(
+            self.supports(Capability::Diarize),
+            "サーバーが話者の区別に対応していません"
+        );
+
+        let fields = [
+            ("audio", format!("<{} bytes>", audio.len())),
+            ("num_speakers", speakers.to_string()),
+        ];
         let form = multipart::Form::new()
             .part(
                 "audio",
-                multipart::Part::bytes(audio_data.to_vec())
-                    .file_name("chunk.wav")
+                multipart::Part::bytes(audio)
+                    .file_name("input.wav")
                     .mime_str("audio/wav")?,
             )
-            .text("model", model.to_string())
-            .text("pitch_shift", pitch_shift.to_string())
-            .text("session_id", session_id.to_string());
+            .text("num_speakers", speakers.to_string());
 
-        let url = format!("{}/convert-chunk", self.base_url);
-        let response = self
+        let url = format!("{}/diarize", self.base_url);
+        let request = self
             .client
             .post(&url)
-            .multipart(form)
-            .send()
+            .header(PRIORITY_HEADER, Priority::Batch.as_str())
+            .multipart(form);
+        let response = self
+            .send("diarize", request, &fields)
             .await
-            .context("チャンク変換リクエストエラー")?;
+            .context("話者の区別のリクエストエラー")?
+            .error_for_status()
+            .context("話者の区別のリクエストエラー")?;
 
-        let converted_data = response.bytes().await.context("チャンク読み込みエラー")?;
+        let diarization: Diarization = Self::decode(response, "/diarize").await?;
 
-        Ok(converted_data)
+        Ok(diarization.segments)
     }
 
-    /// セッションをリセット
-    pub async fn reset_session(&self, session_id: &str) -> Result<()> {
-        let url = format!("{}/reset-session?session_id={}", self.base_url, session_id);
-        self.client
-            .post(&url)
-            .send()
-            .await
-            .context("セッションリセットエラー")?;
+    /// WebSocketでの変換用に持続的な接続を開く（`--transport ws`）
+    pub async fn open_stream(&self) -> Result<ConversionStream> {
+        let url = stream_url(&self.base_url)?;
+        let mut request = url
+            .as_str()
+            .into_client_request()
+            .context("WebSocketのURLが不正です")?;
+        request
+            .headers_mut()
+            .insert(PROTOCOL_HEADER, PROTOCOL_VERSION.into());
+        request.headers_mut().insert(
+            PRIORITY_HEADER,
+            Priority::Realtime
+                .as_str()
+                .parse()
+                .expect("固定のヘッダー値"),
+        );
+        if let Some(value) = &self.authorization {
+            request.headers_mut().insert(AUTHORIZATION, value.clone());
+        }
 
-        info!("セッションリセット完了: {}", session_id);
-        Ok(())
+        let (socket, _) = tokio::time::timeout(
+            self.timeouts.connect,
+            tokio_tungstenite::connect_async(request),
+        )
+        .await
+        .map_err(|_| {
+            anyhow::anyhow!(
+                "{:.1}秒以内に接続できません",
+                self.timeouts.connect.as_secs_f64()
+            )
+        })
+        .and_then(|result| Ok(result?))
+        .with_context(|| format!("WebSocket接続エラー: {}", url))?;
+        info!("WebSocketで接続: {}", url);
+
+        Ok(ConversionStream {
+            socket,
+            settings: None,
+            timeout: self.timeouts.chunk,
+        })
     }
 }
+
+/// APIのURL（http/https）からWebSocketのURL（ws/wss）を作る
+fn stream_url(base_url: &str) -> Result<reqwest::Url> {
+    let mut url = reqwest::Url::parse(base_url)
+        .with_context(|| format!("APIサーバーのURLが不正です: {}", base_url))?;
+    let scheme = match url.scheme() {
+        "https" => "wss",
+        _ => "ws",
+    };
+    url.set_scheme(scheme)
+        .map_err(|_| anyhow::anyhow!("WebSocketのURLを作れません: {}", base_url))?;
+    url.set_path(&format!(
+        "{}{}",
+        url.path().trim_end_matches('/'),
+        STREAM_PATH
+    ));
+    Ok(url)
+}
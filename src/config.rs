@@ -0,0 +1,889 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+use crate::bleep::DEFAULT_WINDOW_MS as DEFAULT_BLEEP_WINDOW_MS;
+use crate::converter::DEFAULT_CHUNK_MS;
+use crate::dsp::analysis;
+use crate::retry::{self, RetryPolicy};
+
+/// プロジェクト設定ファイル名
+pub const CONFIG_FILE_NAME: &str = "makebeliv.toml";
+
+/// ユーザー設定ファイル名（`~/.config/makebeliv/` に置く）
+pub const USER_CONFIG_FILE_NAME: &str = "config.toml";
+
+/// 設定ファイルの形式のバージョン
+///
+/// 項目の名前や置き場所を変えたら上げ、`MIGRATIONS` に古い形式からの移行を足します。
+pub const CONFIG_VERSION: u32 = 1;
+
+/// 背景ノイズの既定の音量
+pub const DEFAULT_NOISE_LEVEL: f32 = 0.02;
+
+/// 設定の内容
+///
+/// デフォルト < ユーザー設定（`~/.config/makebeliv/config.toml`）
+/// < プロジェクト設定（`makebeliv.toml`）< プリセット（`--preset`）< CLIフラグ
+/// の順に上書きされます。
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// 設定ファイルの形式のバージョン（書かれていなければ0）
+    pub version: u32,
+    pub server: ServerConfig,
+    pub conversion: ConversionConfig,
+    pub audio: AudioConfig,
+    pub dsp: DspConfig,
+    pub hooks: HooksConfig,
+    pub bleep: BleepConfig,
+    pub recording: RecordingConfig,
+    pub hotkeys: HotkeysConfig,
+    /// 名前付きプリセット（`[presets.<名前>]`）
+    pub presets: BTreeMap<String, Preset>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            version: CONFIG_VERSION,
+            server: ServerConfig::default(),
+            conversion: ConversionConfig::default(),
+            audio: AudioConfig::default(),
+            dsp: DspConfig::default(),
+            hooks: HooksConfig::default(),
+            bleep: BleepConfig::default(),
+            recording: RecordingConfig::default(),
+            hotkeys: HotkeysConfig::default(),
+            presets: BTreeMap::new(),
+        }
+    }
+}
+
+/// APIサーバーの設定
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ServerConfig {
+    pub api_url: String,
+    /// 失敗したリクエストの再試行（`[server.retry]`）
+    pub retry: RetryConfig,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            api_url: "http://localhost:8000".to_string(),
+            retry: RetryConfig::default(),
+        }
+    }
+}
+
+/// 失敗した変換リクエストの再試行の設定
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetryConfig {
+    /// ファイル変換の試行回数（最初の1回を含む。1で再試行しない）
+    pub attempts: u32,
+    /// 最初の再試行までの待ち時間（ミリ秒、再試行ごとに倍にする）
+    pub backoff_ms: u64,
+    /// 待ち時間の上限（ミリ秒）
+    pub max_backoff_ms: u64,
+    /// 待ち時間の揺れ（0〜1、±割合）
+    pub jitter: f64,
+    /// リアルタイム変換のチャンクの試行回数
+    pub chunk_attempts: u32,
+    /// チャンクの再試行までの待ち時間（ミリ秒）
+    pub chunk_backoff_ms: u64,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            attempts: retry::DEFAULT_ATTEMPTS,
+            backoff_ms: retry::DEFAULT_BACKOFF_MS,
+            max_backoff_ms: retry::DEFAULT_MAX_BACKOFF_MS,
+            jitter: retry::DEFAULT_JITTER,
+            chunk_attempts: retry::DEFAULT_CHUNK_ATTEMPTS,
+            chunk_backoff_ms: retry::DEFAULT_CHUNK_BACKOFF_MS,
+        }
+    }
+}
+
+impl RetryConfig {
+    /// ファイル変換（`/convert`）の再試行
+    pub fn policy(&self) -> RetryPolicy {
+        RetryPolicy {
+            attempts: self.attempts,
+            backoff: Duration::from_millis(self.backoff_ms),
+            max_backoff: Duration::from_millis(self.max_backoff_ms),
+            jitter: self.jitter,
+        }
+    }
+
+    /// チャンク変換（`/convert-chunk`）の再試行
+    pub fn chunk_policy(&self) -> RetryPolicy {
+        RetryPolicy {
+            attempts: self.chunk_attempts,
+            backoff: Duration::from_millis(self.chunk_backoff_ms),
+            ..self.policy()
+        }
+    }
+}
+
+/// 変換パラメータの設定
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConversionConfig {
+    pub model: String,
+    /// `model` に期待するバージョン（サーバーが申告する重みのSHA-256の先頭12桁。Noneなら確かめない）
+    pub model_version: Option<String>,
+    /// 背景ノイズの種類
+    pub noise: String,
+    /// 背景ノイズの音量（0.0-1.0、0で無効）
+    pub noise_level: f32,
+    /// 句の切れ目に入れる息の音量（0.0-1.0、0で無効）
+    pub breath_level: f32,
+    /// 息として使うWAVを置いたディレクトリ（省略時は生成する）
+    pub breath_dir: Option<PathBuf>,
+    /// ピッチシフト（半音）
+    pub pitch: i32,
+    /// 句ごとにランダムに変えるピッチの幅（±セント、0で無効）
+    pub pitch_contour: f32,
+    /// ファイル処理で時間とともに揺らす話速の幅（±パーセント、0で無効）
+    pub rate_fluctuation: f32,
+    /// リアルタイム変換のチャンク長（ミリ秒）
+    pub chunk_ms: u32,
+    /// 次のチャンクと重ねて送り、クロスフェードでつなぐ長さ（ミリ秒、0で無効）
+    ///
+    /// 重ねた部分は普通の音声として送るだけで、サーバーには重なりを伝えません（つなぐのはクライアント）。
+    pub overlap_ms: u32,
+    /// ファイル変換の出力に電子透かしを埋め込む（`--watermark`）
+    pub watermark: bool,
+    /// 電子透かしの鍵（省略時は "makebeliv"。同じ鍵でないと検出できない）
+    pub watermark_key: Option<String>,
+}
+
+impl Default for ConversionConfig {
+    fn default() -> Self {
+        Self {
+            model: "default".to_string(),
+            model_version: None,
+            noise: "cafe".to_string(),
+            noise_level: DEFAULT_NOISE_LEVEL,
+            breath_level: 0.0,
+            breath_dir: None,
+            pitch: 0,
+            pitch_contour: 0.0,
+            rate_fluctuation: 0.0,
+            chunk_ms: DEFAULT_CHUNK_MS,
+            overlap_ms: 0,
+            watermark: false,
+            watermark_key: None,
+        }
+    }
+}
+
+/// 音声デバイスの設定
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AudioConfig {
+    /// 入力（`--input` と同じ書式。省略時はデフォルトデバイス）
+    pub input: Option<String>,
+    /// 変換に送る入力チャンネル（`--channel` と同じ書式。省略時は全チャンネルの平均）
+    pub channel: Option<String>,
+    /// 出力デバイス名（省略時はデフォルトデバイス）
+    pub output_device: Option<String>,
+    /// ステレオ出力での声の定位（-1.0 左〜1.0 右）
+    pub pan: f32,
+    /// 声の左右への広がり（0.0 モノラル〜2.0、1.0でそのまま）
+    pub stereo_width: f32,
+    /// 背景ノイズの左右への広がり（0.0〜2.0、1.0でそのまま）
+    pub noise_width: f32,
+    /// 低電力モード（`--low-power`）
+    pub low_power: bool,
+    /// 無音のチャンクをサーバーへ送らない（`--vad`）
+    pub vad: bool,
+    /// 発話とみなすレベル（dBFS、`--vad-threshold`）
+    pub vad_threshold: f32,
+    /// 機材に合わせた既定値（`--profile` と同じ値）
+    pub profile: Option<String>,
+}
+
+impl Default for AudioConfig {
+    fn default() -> Self {
+        Self {
+            input: None,
+            channel: None,
+            output_device: None,
+            pan: 0.0,
+            stereo_width: 1.0,
+            noise_width: 1.0,
+            low_power: false,
+            vad: true,
+            vad_threshold: analysis::DEFAULT_VAD_THRESHOLD_DB,
+            profile: None,
+        }
+    }
+}
+
+/// モデル・ピッチ・ノイズ・息・デバイスの組み合わせ（`makebeliv preset`）
+///
+/// 指定した項目だけ `[conversion]` と `[audio]` の値を上書きします。
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Preset {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// モデルのバージョンを固定する（長く続く企画で声を揃えるため）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model_version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pitch: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub noise: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub noise_level: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub breath_level: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub breath_dir: Option<PathBuf>,
+    /// 入力（`--input` と同じ書式）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub input: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_device: Option<String>,
+}
+
+impl Preset {
+    /// 何も指定していないか
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// 1行の説明（`preset list`）
+    pub fn summary(&self) -> String {
+        let mut parts = Vec::new();
+        match (&self.model, &self.model_version) {
+            (Some(model), Some(version)) => parts.push(format!("モデル {}@{}", model, version)),
+            (Some(model), None) => parts.push(format!("モデル {}", model)),
+            (None, Some(version)) => parts.push(format!("モデルのバージョン {}", version)),
+            (None, None) => {}
+        }
+        if let Some(pitch) = self.pitch {
+            parts.push(format!("ピッチ {:+}", pitch));
+        }
+        match (&self.noise, self.noise_level) {
+            (Some(noise), Some(level)) => parts.push(format!("ノイズ {} ({})", noise, level)),
+            (Some(noise), None) => parts.push(format!("ノイズ {}", noise)),
+            (None, Some(level)) => parts.push(format!("ノイズ音量 {}", level)),
+            (None, None) => {}
+        }
+        match (self.breath_level, &self.breath_dir) {
+            (Some(level), Some(dir)) => parts.push(format!("息 {} ({})", level, dir.display())),
+            (Some(level), None) => parts.push(format!("息 {}", level)),
+            (None, Some(dir)) => parts.push(format!("息 {}", dir.display())),
+            (None, None) => {}
+        }
+        if let Some(input) = &self.input {
+            parts.push(format!("入力 {}", input));
+        }
+        if let Some(output) = &self.output_device {
+            parts.push(format!("出力 {}", output));
+        }
+        parts.join(" / ")
+    }
+
+    /// TOMLのテーブルにする（コメントを保ったまま書き込むため）
+    fn to_table(&self) -> toml_edit::Table {
+        let mut table = toml_edit::Table::new();
+        let mut audio = toml_edit::Table::new();
+        self.write_to(&mut table, &mut audio);
+        table.extend(audio);
+        table
+    }
+
+    /// 変換パラメータを `conversion` に、デバイスを `audio` に書き込む
+    fn write_to(&self, conversion: &mut toml_edit::Table, audio: &mut toml_edit::Table) {
+        if let Some(model) = &self.model {
+            // 固定したバージョンは前のモデルのものなので、新しいモデルには引き継がない
+            if conversion.get("model").and_then(|item| item.as_str()) != Some(model.as_str()) {
+                conversion.remove("model_version");
+            }
+            conversion["model"] = toml_edit::value(model.as_str());
+        }
+        if let Some(version) = &self.model_version {
+            conversion["model_version"] = toml_edit::value(version.as_str());
+        }
+        if let Some(pitch) = self.pitch {
+            conversion["pitch"] = toml_edit::value(pitch as i64);
+        }
+        if let Some(noise) = &self.noise {
+            conversion["noise"] = toml_edit::value(noise.as_str());
+        }
+        if let Some(level) = self.noise_level {
+            conversion["noise_level"] = toml_edit::value(float_value(level));
+        }
+        if let Some(level) = self.breath_level {
+            conversion["breath_level"] = toml_edit::value(float_value(level));
+        }
+        if let Some(dir) = &self.breath_dir {
+            conversion["breath_dir"] = toml_edit::value(dir.to_string_lossy().as_ref());
+        }
+        if let Some(input) = &self.input {
+            audio["input"] = toml_edit::value(input.as_str());
+        }
+        if let Some(output) = &self.output_device {
+            audio["output_device"] = toml_edit::value(output.as_str());
+        }
+    }
+}
+
+/// ローカルエフェクトチェーンの設定
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DspConfig {
+    /// 追加で読み込むDSPプラグイン（宣言順に適用）
+    pub plugins: Vec<PluginConfig>,
+    /// ホストするLV2プラグイン（`lv2` 機能が必要。ネイティブプラグインの後に適用）
+    pub lv2: Vec<Lv2PluginConfig>,
+}
+
+/// DSPプラグインの宣言
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PluginConfig {
+    /// 共有ライブラリのパス
+    pub path: PathBuf,
+    /// プラグインに渡すパラメータ（JSONに変換して渡す）
+    #[serde(default)]
+    pub params: toml::Table,
+}
+
+/// LV2プラグインの宣言
+///
+/// TTLを解析しないため、ポート番号は `lv2info` などで確認して指定します。
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Lv2PluginConfig {
+    /// プラグインURI
+    pub uri: String,
+    /// プラグインの共有ライブラリ（例: /usr/lib/lv2/foo.lv2/foo.so）
+    pub binary: PathBuf,
+    /// バンドルディレクトリ（省略時はbinaryの親ディレクトリ）
+    pub bundle: Option<PathBuf>,
+    /// オーディオ入力ポート番号
+    pub audio_in: Vec<u32>,
+    /// オーディオ出力ポート番号
+    pub audio_out: Vec<u32>,
+    /// コントロール入力ポート番号 → 値
+    #[serde(default)]
+    pub controls: BTreeMap<String, f32>,
+    /// 接続が必要なコントロール出力ポート番号（値は捨てる）
+    #[serde(default)]
+    pub control_outputs: Vec<u32>,
+}
+
+/// ライフサイクルフック（シェルコマンド）
+///
+/// コマンドには `MAKEBELIV_EVENT`, `MAKEBELIV_SESSION_ID`, `MAKEBELIV_MODEL`,
+/// `MAKEBELIV_PITCH`, `MAKEBELIV_API_URL` などの環境変数が渡されます。
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HooksConfig {
+    pub on_session_start: Option<String>,
+    pub on_session_stop: Option<String>,
+    /// `MAKEBELIV_ERROR` に最後のエラーが入る
+    pub on_server_lost: Option<String>,
+}
+
+/// 指定した語を伏せる設定（`monitor --bleep-word`）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BleepConfig {
+    /// 伏せる語（空なら伏せない）
+    pub words: Vec<String>,
+    /// 伏せ方（tone, silence）
+    pub mode: String,
+    /// 文字起こしに送る区間の長さ（ミリ秒、この分だけ出力が遅れる）
+    pub window_ms: u32,
+}
+
+impl Default for BleepConfig {
+    fn default() -> Self {
+        Self {
+            words: Vec::new(),
+            mode: "tone".to_string(),
+            window_ms: DEFAULT_BLEEP_WINDOW_MS,
+        }
+    }
+}
+
+/// セッション録音の設定（`monitor --record`）
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RecordingConfig {
+    /// 録音を終えた後のアップロード（`[recording.upload]`）
+    pub upload: UploadConfig,
+}
+
+/// 録音ファイルのアップロード先
+///
+/// S3互換ストレージの認証情報は設定ファイルには書かず、環境変数
+/// `AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY`（, `AWS_SESSION_TOKEN`）から読みます。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UploadConfig {
+    /// アップロード先（`s3://<バケット>/<プレフィックス>` か `https://...`、なければアップロードしない）
+    pub destination: Option<String>,
+    /// S3互換ストレージのエンドポイント（省略時はAWSの `https://s3.<region>.amazonaws.com`）
+    pub endpoint: Option<String>,
+    /// S3の署名に使うリージョン
+    pub region: String,
+    /// HTTP PUTに付けるヘッダー（`$NAME` と書くと環境変数 NAME の値）
+    pub headers: BTreeMap<String, String>,
+    /// 1ファイルの試行回数（最初の1回を含む）
+    pub attempts: u32,
+    /// アップロードできたファイルを手元から消す
+    pub delete_after_upload: bool,
+}
+
+impl Default for UploadConfig {
+    fn default() -> Self {
+        Self {
+            destination: None,
+            endpoint: None,
+            region: "us-east-1".to_string(),
+            headers: BTreeMap::new(),
+            attempts: 5,
+            delete_after_upload: false,
+        }
+    }
+}
+
+/// `monitor` の実行中のキー操作
+///
+/// キーは1文字か space, tab, enter, up, down, left, right, f1〜f12 で指定します。
+/// 空文字列にするとその操作にはキーを割り当てません。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HotkeysConfig {
+    /// キー操作を受け付ける（`--no-hotkeys` で無効）
+    pub enabled: bool,
+    /// 変換せずに元の声を流す／戻す
+    pub bypass: String,
+    /// 出力を無音にする／戻す
+    pub mute: String,
+    /// ピッチを半音上げる
+    pub pitch_up: String,
+    /// ピッチを半音下げる
+    pub pitch_down: String,
+    /// 次のプリセットに切り替える（`[presets.<名前>]` を名前順に巡回）
+    pub next_preset: String,
+}
+
+impl Default for HotkeysConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            bypass: "b".to_string(),
+            mute: "m".to_string(),
+            pitch_up: "up".to_string(),
+            pitch_down: "down".to_string(),
+            next_preset: "p".to_string(),
+        }
+    }
+}
+
+impl Config {
+    /// ユーザー設定とカレントディレクトリの makebeliv.toml を重ねて読み込む
+    ///
+    /// どちらも存在しなければデフォルトです。
+    pub fn load() -> Result<Self> {
+        let mut merged = toml::Table::new();
+        for path in Self::sources() {
+            merge_tables(&mut merged, read_table(&path)?);
+        }
+        merged
+            .try_into()
+            .context("設定ファイルの解析エラー（ユーザー設定とプロジェクト設定の組み合わせ）")
+    }
+
+    /// 読み込む設定ファイル（存在するもの、優先度の低い順）
+    pub fn sources() -> Vec<PathBuf> {
+        user_config_path()
+            .into_iter()
+            .chain([PathBuf::from(CONFIG_FILE_NAME)])
+            .filter(|path| path.is_file())
+            .collect()
+    }
+
+    /// 名前でプリセットを探す（なければ登録済みの名前を示すエラー）
+    pub fn preset(&self, name: &str) -> Result<&Preset> {
+        self.presets.get(name).with_context(|| {
+            let names: Vec<&str> = self.presets.keys().map(String::as_str).collect();
+            if names.is_empty() {
+                format!(
+                    "プリセットが見つかりません: {}（makebeliv preset save で作成できます）",
+                    name
+                )
+            } else {
+                format!(
+                    "プリセットが見つかりません: {}（登録済み: {}）",
+                    name,
+                    names.join(", ")
+                )
+            }
+        })
+    }
+
+    /// プリセットの値で `[conversion]` と `[audio]` を上書きする
+    pub fn apply_preset(&mut self, name: &str) -> Result<()> {
+        let preset = self.preset(name)?.clone();
+
+        let conversion = &mut self.conversion;
+        if let Some(model) = preset.model {
+            if model != conversion.model {
+                conversion.model_version = None;
+            }
+            conversion.model = model;
+        }
+        if let Some(version) = preset.model_version {
+            conversion.model_version = Some(version);
+        }
+        if let Some(pitch) = preset.pitch {
+            conversion.pitch = pitch;
+        }
+        if let Some(noise) = preset.noise {
+            conversion.noise = noise;
+        }
+        if let Some(level) = preset.noise_level {
+            conversion.noise_level = level;
+        }
+        if let Some(level) = preset.breath_level {
+            conversion.breath_level = level;
+        }
+        if let Some(dir) = preset.breath_dir {
+            conversion.breath_dir = Some(dir);
+        }
+        if let Some(input) = preset.input {
+            self.audio.input = Some(input);
+        }
+        if let Some(output) = preset.output_device {
+            self.audio.output_device = Some(output);
+        }
+        Ok(())
+    }
+
+    /// 設定をTOMLとして書き出す（`config show`）
+    pub fn to_toml(&self) -> Result<String> {
+        toml::to_string_pretty(self).context("設定の書き出しエラー")
+    }
+
+    /// 指定パスの設定ファイルを読み込む
+    pub fn load_from(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("設定ファイルの読み込みエラー: {}", path.display()))?;
+        toml::from_str(&text)
+            .with_context(|| format!("設定ファイルの解析エラー: {}", path.display()))
+    }
+}
+
+/// ユーザー設定ファイルのパス（`~/.config/makebeliv/config.toml`）
+pub fn user_config_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("makebeliv").join(USER_CONFIG_FILE_NAME))
+}
+
+/// 古い形式からの移行（`from` から `from + 1` へ）
+struct Migration {
+    from: u32,
+    /// 変更の内容（ログに出す）
+    summary: &'static str,
+    apply: fn(&mut toml_edit::DocumentMut) -> Result<()>,
+}
+
+/// 形式の移行（古い順）
+const MIGRATIONS: &[Migration] = &[Migration {
+    from: 0,
+    summary: "形式のバージョンを記録する（項目の変更なし）",
+    apply: |_| Ok(()),
+}];
+
+/// 設定ファイルを読み込む（古い形式ならメモリ上で今の形式に移行し、元のバージョンも返す）
+fn read_document(path: &Path) -> Result<(toml_edit::DocumentMut, u32)> {
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("設定ファイルの読み込みエラー: {}", path.display()))?;
+    let mut doc: toml_edit::DocumentMut = text
+        .parse()
+        .with_context(|| format!("設定ファイルの解析エラー: {}", path.display()))?;
+    let version = migrate_document(&mut doc, path)?;
+    Ok((doc, version))
+}
+
+/// 古い形式を今の形式にする（元のバージョンを返す）
+fn migrate_document(doc: &mut toml_edit::DocumentMut, path: &Path) -> Result<u32> {
+    let version = match doc.get("version") {
+        None => 0,
+        Some(item) => item
+            .as_integer()
+            .and_then(|version| u32::try_from(version).ok())
+            .with_context(|| {
+                format!(
+                    "設定ファイルの version が不正です: {}（0以上の整数）",
+                    path.display()
+                )
+            })?,
+    };
+    if version > CONFIG_VERSION {
+        warn!(
+            "⚠ 設定ファイルがこのバージョンより新しい形式です（version {}、対応は{}まで）: {}",
+            version,
+            CONFIG_VERSION,
+            path.display()
+        );
+        return Ok(version);
+    }
+    if version == CONFIG_VERSION {
+        return Ok(version);
+    }
+
+    for migration in MIGRATIONS.iter().filter(|m| m.from >= version) {
+        debug!(
+            "設定の移行 {} → {}: {}",
+            migration.from,
+            migration.from + 1,
+            migration.summary
+        );
+        (migration.apply)(doc).with_context(|| {
+            format!(
+                "設定ファイルの移行エラー（version {} → {}）: {}",
+                migration.from,
+                migration.from + 1,
+                path.display()
+            )
+        })?;
+    }
+    doc["version"] = toml_edit::value(CONFIG_VERSION as i64);
+    Ok(version)
+}
+
+/// 移行前のファイルを `<名前>.v<バージョン>.bak` に残す
+fn backup_file(path: &Path, version: u32) -> Result<PathBuf> {
+    let mut backup = path.as_os_str().to_owned();
+    backup.push(format!(".v{}.bak", version));
+    let backup = PathBuf::from(backup);
+    std::fs::copy(path, &backup)
+        .with_context(|| format!("設定ファイルの控えの作成エラー: {}", backup.display()))?;
+    Ok(backup)
+}
+
+/// 設定ファイルを今の形式に移行して書き戻す（`config upgrade`）
+///
+/// 元のバージョンと、移行したときは元のファイルの控えのパスを返します。
+pub fn upgrade_file(path: &Path) -> Result<(u32, Option<PathBuf>)> {
+    let (doc, version) = read_document(path)?;
+    if version >= CONFIG_VERSION {
+        return Ok((version, None));
+    }
+    let backup = backup_file(path, version)?;
+    std::fs::write(path, doc.to_string())
+        .with_context(|| format!("設定ファイルの書き込みエラー: {}", path.display()))?;
+    Ok((version, Some(backup)))
+}
+
+/// 設定ファイルのうち、どの設定にも当たらない項目（`presets.fast.pich` など）
+pub fn unknown_keys(path: &Path) -> Result<Vec<String>> {
+    let (doc, _) = read_document(path)?;
+    let table = doc
+        .to_string()
+        .parse()
+        .with_context(|| format!("設定ファイルの解析エラー: {}", path.display()))?;
+    Ok(find_unknown_keys(&table))
+}
+
+fn read_table(path: &Path) -> Result<toml::Table> {
+    let (doc, version) = read_document(path)?;
+    if version < CONFIG_VERSION {
+        // 移行できなくても読み込みは続ける（次に読むときも同じ移行をする）
+        let written = backup_file(path, version).and_then(|backup| {
+            std::fs::write(path, doc.to_string())
+                .with_context(|| format!("設定ファイルの書き込みエラー: {}", path.display()))?;
+            Ok(backup)
+        });
+        match written {
+            Ok(backup) => info!(
+                "設定ファイルを新しい形式（version {}）に移行しました: {}（元のファイル: {}）",
+                CONFIG_VERSION,
+                path.display(),
+                backup.display()
+            ),
+            Err(e) => warn!("⚠ 移行した設定ファイルを書き戻せません: {:#}", e),
+        }
+    }
+
+    let table = doc
+        .to_string()
+        .parse()
+        .with_context(|| format!("設定ファイルの解析エラー: {}", path.display()))?;
+    for key in find_unknown_keys(&table) {
+        warn!(
+            "⚠ 設定ファイルの不明な項目を無視します: {}（{}）",
+            key,
+            path.display()
+        );
+    }
+    Ok(table)
+}
+
+/// 読み込んで書き出し直したときに消える項目を探す
+///
+/// 綴りの誤りや、名前の変わった古い項目を黙って無視しないためです。
+/// 解析できない設定は、読み込みのエラーとして別に報告されるため何も返しません。
+fn find_unknown_keys(table: &toml::Table) -> Vec<String> {
+    let Ok(config) = table.clone().try_into::<Config>() else {
+        return Vec::new();
+    };
+    let Ok(toml::Value::Table(known)) = toml::Value::try_from(&config) else {
+        return Vec::new();
+    };
+    let mut unknown = Vec::new();
+    collect_unknown_keys(table, &known, "", &mut unknown);
+    unknown
+}
+
+fn collect_unknown_keys(
+    table: &toml::Table,
+    known: &toml::Table,
+    prefix: &str,
+    unknown: &mut Vec<String>,
+) {
+    for (key, value) in table {
+        let name = if prefix.is_empty() {
+            key.clone()
+        } else {
+            format!("{}.{}", prefix, key)
+        };
+        match (value, known.get(key)) {
+            (_, None) => unknown.push(name),
+            (toml::Value::Table(table), Some(toml::Value::Table(known))) => {
+                collect_unknown_keys(table, known, &name, unknown)
+            }
+            (toml::Value::Array(items), Some(toml::Value::Array(known))) => {
+                for (i, (item, known)) in items.iter().zip(known).enumerate() {
+                    if let (toml::Value::Table(item), toml::Value::Table(known)) = (item, known) {
+                        collect_unknown_keys(item, known, &format!("{}[{}]", name, i), unknown);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// `upper` の値で `base` を上書きする（テーブルは再帰的に、配列は丸ごと置き換える）
+fn merge_tables(base: &mut toml::Table, upper: toml::Table) {
+    for (key, value) in upper {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(upper)) => {
+                merge_tables(base, upper)
+            }
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// 設定ファイルの値を書き換える（コメントや書式は保持する）
+pub fn set_value(
+    path: &Path,
+    table: &str,
+    key: &str,
+    value: impl Into<toml_edit::Value>,
+) -> Result<()> {
+    edit_document(path, |doc| {
+        section(doc, table)?[key] = toml_edit::value(value);
+        Ok(())
+    })
+}
+
+/// プリセットを `[presets.<名前>]` に保存する（同名は置き換える）
+pub fn save_preset(path: &Path, name: &str, preset: &Preset) -> Result<()> {
+    edit_document(path, |doc| {
+        let presets = presets_table(doc)?;
+        presets[name] = toml_edit::Item::Table(preset.to_table());
+        Ok(())
+    })
+}
+
+/// プリセットを削除する（見つからなければfalse）
+pub fn delete_preset(path: &Path, name: &str) -> Result<bool> {
+    if !path.exists() {
+        return Ok(false);
+    }
+    let mut removed = false;
+    edit_document(path, |doc| {
+        removed = presets_table(doc)?.remove(name).is_some();
+        Ok(())
+    })?;
+    Ok(removed)
+}
+
+/// プリセットの値を `[conversion]` と `[audio]` に書き込み、既定の設定にする
+pub fn apply_preset_defaults(path: &Path, preset: &Preset) -> Result<()> {
+    edit_document(path, |doc| {
+        let mut conversion = std::mem::take(section(doc, "conversion")?);
+        let mut audio = std::mem::take(section(doc, "audio")?);
+        preset.write_to(&mut conversion, &mut audio);
+        *section(doc, "conversion")? = conversion;
+        *section(doc, "audio")? = audio;
+        Ok(())
+    })
+}
+
+fn section<'a>(
+    doc: &'a mut toml_edit::DocumentMut,
+    name: &str,
+) -> Result<&'a mut toml_edit::Table> {
+    doc.entry(name)
+        .or_insert_with(toml_edit::table)
+        .as_table_mut()
+        .with_context(|| format!("[{}] がテーブルではありません", name))
+}
+
+/// f32をTOMLの浮動小数にする（そのままf64にすると 0.03 が 0.029999… と書かれるため、表示形式を経由する）
+fn float_value(value: f32) -> f64 {
+    value.to_string().parse().unwrap_or(value as f64)
+}
+
+fn presets_table(doc: &mut toml_edit::DocumentMut) -> Result<&mut toml_edit::Table> {
+    let presets = section(doc, "presets")?;
+    // 空の [presets] 見出しを書かない
+    presets.set_implicit(true);
+    Ok(presets)
+}
+
+/// 設定ファイルを読み込んで編集し、書き戻す（なければ作成する）
+fn edit_document(
+    path: &Path,
+    edit: impl FnOnce(&mut toml_edit::DocumentMut) -> Result<()>,
+) -> Result<()> {
+    let (mut doc, version) = if path.exists() {
+        read_document(path)?
+    } else {
+        let mut doc = toml_edit::DocumentMut::new();
+        doc["version"] = toml_edit::value(CONFIG_VERSION as i64);
+        (doc, CONFIG_VERSION)
+    };
+    edit(&mut doc)?;
+    if version < CONFIG_VERSION {
+        backup_file(path, version)?;
+    }
+
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("設定ディレクトリの作成エラー: {}", dir.display()))?;
+    }
+    std::fs::write(path, doc.to_string())
+        .with_context(|| format!("設定ファイルの書き込みエラー: {}", path.display()))
+}
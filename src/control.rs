@@ -0,0 +1,343 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::sync::{mpsc, oneshot};
+use tracing::{debug, info, warn};
+
+/// 実行中に変えられるパラメータ（`ctl set <名前> <値>`）
+pub const PARAMS: &[&str] = &["model", "pitch", "noise_level"];
+
+/// 1回の要求の最大の長さ（バイト）
+const MAX_REQUEST_BYTES: usize = 4096;
+
+/// 制御ソケットへの要求（1行のJSON）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "cmd", rename_all = "lowercase")]
+pub enum ControlRequest {
+    /// 今のパラメータを返す
+    Get,
+    /// パラメータを変える（`param` は [`PARAMS`] のどれか）
+    Set { param: String, value: String },
+}
+
+/// 実行中のパラメータ
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LiveParams {
+    pub model: String,
+    pub pitch: i32,
+    /// 背景ノイズの音量（ノイズなしで起動していればNone）
+    pub noise_level: Option<f32>,
+}
+
+impl std::fmt::Display for LiveParams {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "モデル {} / ピッチ {:+}", self.model, self.pitch)?;
+        match self.noise_level {
+            Some(level) => write!(f, " / ノイズ {}", level),
+            None => write!(f, " / ノイズなし"),
+        }
+    }
+}
+
+/// 制御ソケットからの応答（1行のJSON）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ControlResponse {
+    pub ok: bool,
+    /// エラーの内容（失敗したとき）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// 要求を処理した後のパラメータ
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub params: Option<LiveParams>,
+}
+
+impl ControlResponse {
+    pub fn ok(params: LiveParams) -> Self {
+        Self {
+            ok: true,
+            error: None,
+            params: Some(params),
+        }
+    }
+
+    pub fn error(error: impl std::fmt::Display) -> Self {
+        Self {
+            ok: false,
+            error: Some(error.to_string()),
+            params: None,
+        }
+    }
+}
+
+/// 受け取った要求と、応答の返し先
+pub type ControlMessage = (ControlRequest, oneshot::Sender<ControlResponse>);
+
+/// 既定の制御ソケット
+///
+/// Unixでは `$XDG_RUNTIME_DIR/makebeliv.sock`（なければ一時ディレクトリ。ほかのユーザーから
+/// つなげないよう、作ったソケットは0600にします）、Windowsでは名前付きパイプ `\\.\pipe\makebeliv` です。
+pub fn default_path() -> PathBuf {
+    if cfg!(windows) {
+        return PathBuf::from(r"\\.\pipe\makebeliv");
+    }
+    dirs::runtime_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("makebeliv.sock")
+}
+
+/// 制御ソケットの受け手（ドロップでソケットを片付ける）
+pub struct ControlServer {
+    rx: mpsc::UnboundedReceiver<ControlMessage>,
+    path: PathBuf,
+}
+
+impl ControlServer {
+    pub async fn recv(&mut self) -> Option<ControlMessage> {
+        self.rx.recv().await
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for ControlServer {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        std::fs::remove_file(&self.path).ok();
+    }
+}
+
+/// 制御ソケットで要求を受け付け始める
+///
+/// 接続ごとに1行ずつ要求を読み、処理を待って応答を1行返します。
+pub fn serve(path: &Path) -> Result<ControlServer> {
+    let (tx, rx) = mpsc::unbounded_channel();
+    listen(path, tx)?;
+    info!("制御ソケット: {}", path.display());
+    Ok(ControlServer {
+        rx,
+        path: path.to_path_buf(),
+    })
+}
+
+#[cfg(unix)]
+fn listen(path: &Path, tx: mpsc::UnboundedSender<ControlMessage>) -> Result<()> {
+    if path.exists() {
+        // 前回のソケットが残っているだけなら消す（使われていればエラー）
+        anyhow::ensure!(
+            std::os::unix::net::UnixStream::connect(path).is_err(),
+            "制御ソケットはほかの monitor が使っています: {}",
+            path.display()
+        );
+        std::fs::remove_file(path)
+            .with_context(|| format!("古い制御ソケットを消せません: {}", path.display()))?;
+    }
+    let listener = tokio::net::UnixListener::bind(path)
+        .with_context(|| format!("制御ソケットを作れません: {}", path.display()))?;
+    // 一時ディレクトリはほかのユーザーと共有なので、権限をumaskに任せない
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))
+            .with_context(|| format!("制御ソケットの権限を設定できません: {}", path.display()))?;
+    }
+    tokio::spawn(async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    tokio::spawn(handle_connection(stream, tx.clone()));
+                }
+                Err(e) => {
+                    warn!("⚠ 制御ソケットの受け付けエラー: {}", e);
+                    return;
+                }
+            }
+            if tx.is_closed() {
+                return;
+            }
+        }
+    });
+    Ok(())
+}
+
+#[cfg(windows)]
+fn listen(path: &Path, tx: mpsc::UnboundedSender<ControlMessage>) -> Result<()> {
+    use tokio::net::windows::named_pipe::ServerOptions;
+
+    let name = path.as_os_str().to_owned();
+    let mut server = ServerOptions::new()
+        .first_pipe_instance(true)
+        .create(&name)
+        .with_context(|| format!("制御用の名前付きパイプを作れません: {}", path.display()))?;
+    tokio::spawn(async move {
+        loop {
+            if let Err(e) = server.connect().await {
+                warn!("⚠ 制御用の名前付きパイプの受け付けエラー: {}", e);
+                return;
+            }
+            // 次の接続を待つインスタンスを先に作ってから、今の接続を処理する
+            let connected = match ServerOptions::new().create(&name) {
+                Ok(next) => std::mem::replace(&mut server, next),
+                Err(e) => {
+                    warn!("⚠ 制御用の名前付きパイプを作れません: {}", e);
+                    return;
+                }
+            };
+            tokio::spawn(handle_connection(connected, tx.clone()));
+            if tx.is_closed() {
+                return;
+            }
+        }
+    });
+    Ok(())
+}
+
+/// 1つの接続の要求を読み、応答を返す
+async fn handle_connection<S>(stream: S, tx: mpsc::UnboundedSender<ControlMessage>)
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let (reader, mut writer) = tokio::io::split(stream);
+    let mut reader = BufReader::new(reader);
+    let mut line = Vec::new();
+    loop {
+        // 改行が来なくても上限を超えて溜めない
+        line.clear();
+        let mut limited = (&mut reader).take(MAX_REQUEST_BYTES as u64 + 1);
+        match limited.read_until(b'\n', &mut line).await {
+            Ok(0) => return,
+            Ok(_) => {}
+            Err(e) => {
+                debug!("制御ソケットの読み込みエラー: {}", e);
+                return;
+            }
+        }
+        if line.len() > MAX_REQUEST_BYTES {
+            // 行の残りを読み飛ばせないので、応答して切る
+            let mut json = serde_json::to_string(&ControlResponse::error("要求が長すぎます"))
+                .unwrap_or_default();
+            json.push('\n');
+            writer.write_all(json.as_bytes()).await.ok();
+            return;
+        }
+        let text = String::from_utf8_lossy(&line);
+        if text.trim().is_empty() {
+            continue;
+        }
+        let response = match serde_json::from_str::<ControlRequest>(&text) {
+            Ok(request) => {
+                let (reply, answer) = oneshot::channel();
+                if tx.send((request, reply)).is_err() {
+                    return;
+                }
+                answer
+                    .await
+                    .unwrap_or_else(|_| ControlResponse::error("monitor が停止しました"))
+            }
+            Err(e) => ControlResponse::error(format!("要求を解釈できません: {}", e)),
+        };
+        let mut json = serde_json::to_string(&response).unwrap_or_default();
+        json.push('\n');
+        if writer.write_all(json.as_bytes()).await.is_err() {
+            return;
+        }
+    }
+}
+
+/// 実行中の monitor に要求を1つ送り、応答を待つ（`makebeliv ctl`）
+pub async fn send(path: &Path, request: &ControlRequest) -> Result<ControlResponse> {
+    let stream = connect(path).await.with_context(|| {
+        format!(
+            "制御ソケットにつなげません: {}（monitor が動いていますか？）",
+            path.display()
+        )
+    })?;
+    let (reader, mut writer) = tokio::io::split(stream);
+    let mut json = serde_json::to_string(request)?;
+    json.push('\n');
+    writer.write_all(json.as_bytes()).await?;
+
+    let line = BufReader::new(reader)
+        .lines()
+        .next_line()
+        .await?
+        .context("monitor から応答がありません")?;
+    serde_json::from_str(&line).context("monitor の応答を解釈できません")
+}
+
+#[cfg(unix)]
+async fn connect(path: &Path) -> std::io::Result<tokio::net::UnixStream> {
+    tokio::net::UnixStream::connect(path).await
+}
+
+#[cfg(windows)]
+async fn connect(path: &Path) -> std::io::Result<tokio::net::windows::named_pipe::NamedPipeClient> {
+    tokio::net::windows::named_pipe::ClientOptions::new().open(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params() -> LiveParams {
+        LiveParams {
+            model: "default".to_string(),
+            pitch: 0,
+            noise_level: None,
+        }
+    }
+
+    /// 接続を1つ処理させ、送った要求への応答を読む（要求はすべて `get` として答える）
+    async fn exchange(request: &[u8]) -> Vec<ControlResponse> {
+        let (client, server) = tokio::io::duplex(MAX_REQUEST_BYTES * 4);
+        let (tx, mut rx) = mpsc::unbounded_channel::<ControlMessage>();
+        tokio::spawn(handle_connection(server, tx));
+        tokio::spawn(async move {
+            while let Some((_, reply)) = rx.recv().await {
+                reply.send(ControlResponse::ok(params())).ok();
+            }
+        });
+        let (reader, mut writer) = tokio::io::split(client);
+        writer.write_all(request).await.unwrap();
+        let mut lines = BufReader::new(reader).lines();
+        let mut responses = Vec::new();
+        while let Ok(Ok(Some(line))) =
+            tokio::time::timeout(std::time::Duration::from_secs(1), lines.next_line()).await
+        {
+            responses.push(serde_json::from_str(&line).unwrap());
+        }
+        responses
+    }
+
+    #[tokio::test]
+    async fn answers_each_line() {
+        let responses = exchange(b"{\"cmd\":\"get\"}\n\n{\"cmd\":\"bogus\"}\n").await;
+        assert_eq!(responses.len(), 2);
+        assert_eq!(responses[0], ControlResponse::ok(params()));
+        assert!(!responses[1].ok);
+    }
+
+    #[tokio::test]
+    async fn rejects_an_overlong_request_without_a_newline() {
+        let request = vec![b'x'; MAX_REQUEST_BYTES * 3];
+        let responses = exchange(&request).await;
+        assert_eq!(responses.len(), 1);
+        assert_eq!(responses[0].error.as_deref(), Some("要求が長すぎます"));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn socket_is_private_to_the_user() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("makebeliv.sock");
+        let server = serve(&path).unwrap();
+        let mode = std::fs::metadata(server.path())
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+}
@@ -0,0 +1,306 @@
+use anyhow::Result;
+use std::str::FromStr;
+use std::time::Instant;
+use tracing::warn;
+
+use crate::chaos::{ChaosInjector, ChaosOptions};
+use crate::client::{
+    Capability, Codec, ConversionStream, StreamSettings, Transport, VoiceConversionClient,
+};
+use crate::dsp::pitch::{self, PitchShifter};
+use crate::dsp::{DspChain, DspStage};
+use crate::opus_chunk;
+use crate::wav;
+
+/// デフォルトのチャンク長（ミリ秒）
+pub const DEFAULT_CHUNK_MS: u32 = 200;
+
+/// 変換済みチャンク
+pub struct ConvertedChunk {
+    /// 変換後のサンプル（インターリーブ）
+    pub samples: Vec<f32>,
+    pub sample_rate: u32,
+    pub channels: u16,
+    /// 送信したバイト数（HTTPではWAV、WebSocketでは生のPCM、Opusなら圧縮後）
+    pub bytes_sent: usize,
+    /// 受信したバイト数
+    pub bytes_received: usize,
+}
+
+/// 変換エンジン
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Engine {
+    /// Python側のサーバー（RVC）で変換する
+    #[default]
+    Server,
+    /// ローカルのDSPでピッチだけ変える（サーバーが使えないときの縮退運転）
+    Local,
+}
+
+impl FromStr for Engine {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "server" => Ok(Self::Server),
+            "local" => {
+                pitch::ensure_available()?;
+                Ok(Self::Local)
+            }
+            _ => anyhow::bail!("不明な変換エンジン: {}（server, local）", s),
+        }
+    }
+}
+
+impl std::fmt::Display for Engine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Server => "server",
+            Self::Local => "local",
+        })
+    }
+}
+
+/// リアルタイム変換の設定
+pub struct PipelineConfig {
+    pub model: String,
+    pub pitch_shift: i32,
+    pub chunk_ms: u32,
+    pub session_id: String,
+    pub transport: Transport,
+    /// チャンクの符号化方式（使えなければPCMに戻す）
+    pub codec: Codec,
+    pub engine: Engine,
+}
+
+/// チャンク変換 → ローカルDSP を行う変換器（音声I/Oに依存しない）
+///
+/// CLIのリアルタイムパイプラインとプラグインの両方で使用します。
+pub struct ChunkConverter {
+    client: VoiceConversionClient,
+    config: PipelineConfig,
+    chain: DspChain,
+    /// WebSocketの接続（`Transport::Ws` で未接続・切断後はNone）
+    stream: Option<ConversionStream>,
+    /// 注入する障害（`--inject-latency` など）
+    chaos: Option<ChaosInjector>,
+    /// `Engine::Local` のピッチシフター（最初のチャンクで作成）
+    shifter: Option<PitchShifter>,
+}
+
+impl ChunkConverter {
+    pub fn new(client: VoiceConversionClient, config: PipelineConfig, chain: DspChain) -> Self {
+        Self {
+            client,
+            config,
+            chain,
+            stream: None,
+            chaos: None,
+            shifter: None,
+        }
+    }
+
+    /// 変換の前に遅延・失敗を注入する（耐障害性の確認用）
+    pub fn with_chaos(mut self, options: ChaosOptions) -> Self {
+        if !options.is_noop() {
+            warn!(
+                "障害を注入します: 遅延 {}, 失敗率 {:.0}%",
+                options.latency,
+                options.error_rate * 100.0
+            );
+            self.chaos = Some(ChaosInjector::new(options));
+        }
+        self
+    }
+
+    pub fn config(&self) -> &PipelineConfig {
+        &self.config
+    }
+
+    pub fn config_mut(&mut self) -> &mut PipelineConfig {
+        &mut self.config
+    }
+
+    pub fn chain(&self) -> &DspChain {
+        &self.chain
+    }
+
+    pub fn client(&self) -> &VoiceConversionClient {
+        &self.client
+    }
+
+    /// 1チャンクを変換してローカルDSPを適用
+    pub async fn convert(
+        &mut self,
+        chunk: &[f32],
+        sample_rate: u32,
+        channels: u16,
+    ) -> Result<ConvertedChunk> {
+        if let Some(chaos) = &mut self.chaos {
+            chaos.perturb().await?;
+        }
+
+        if self.config.engine == Engine::Local {
+            return Ok(self.convert_local(chunk, sample_rate, channels));
+        }
+
+        let started = Instant::now();
+        let result = self.convert_remote(chunk, sample_rate, channels).await;
+        // 失敗も遅れとして数え、同じプロセスのバッチ変換を控えさせる
+        if let Some(arbiter) = self.client.arbiter() {
+            arbiter.record_live(started.elapsed());
+        }
+        result
+    }
+
+    /// サーバーで1チャンクを変換（HTTPかWebSocket）
+    async fn convert_remote(
+        &mut self,
+        chunk: &[f32],
+        sample_rate: u32,
+        channels: u16,
+    ) -> Result<ConvertedChunk> {
+        if self.config.transport == Transport::Ws {
+            if self.client.supports(Capability::WsChunks) {
+                return self.convert_ws(chunk, sample_rate, channels).await;
+            }
+            warn!("サーバーがWebSocketに対応していないため、HTTPで変換します");
+            self.config.transport = Transport::Http;
+        }
+
+        let codec = self.negotiate_codec();
+        let request = match codec {
+            Codec::Pcm => wav::encode(chunk, sample_rate, channels)?,
+            Codec::Opus => opus_chunk::encode(chunk, sample_rate, channels)?,
+        };
+        let response = self
+            .client
+            .convert_chunk(
+                &request,
+                codec,
+                &self.config.model,
+                self.config.pitch_shift,
+                &self.config.session_id,
+            )
+            .await?;
+
+        // Opusを受け付けてもPCMで返すサーバーがあるため、返ってきた形式で読む
+        let (mut samples, sample_rate, channels) = match response.meta.codec {
+            Codec::Pcm => {
+                let (samples, spec) = wav::decode(&response.audio)?;
+                (samples, spec.sample_rate, spec.channels)
+            }
+            Codec::Opus => opus_chunk::decode(&response.audio)?,
+        };
+        self.chain.prepare(sample_rate, channels);
+        self.chain.process(&mut samples);
+
+        Ok(ConvertedChunk {
+            samples,
+            sample_rate,
+            channels,
+            bytes_sent: request.len(),
+            bytes_received: response.audio.len(),
+        })
+    }
+
+    /// WebSocketで1チャンクを変換（切れていれば接続し直す）
+    async fn convert_ws(
+        &mut self,
+        chunk: &[f32],
+        sample_rate: u32,
+        channels: u16,
+    ) -> Result<ConvertedChunk> {
+        let codec = self.negotiate_codec();
+        let stream = match &mut self.stream {
+            Some(stream) => stream,
+            None => self.stream.insert(self.client.open_stream().await?),
+        };
+
+        let settings = StreamSettings {
+            model: self.config.model.clone(),
+            pitch_shift: self.config.pitch_shift,
+            session_id: self.config.session_id.clone(),
+            sample_rate,
+            channels,
+            codec,
+        };
+        let converted = match stream.convert(&settings, chunk).await {
+            Ok(converted) => converted,
+            Err(e) => {
+                // 次のチャンクで接続し直す
+                self.stream = None;
+                return Err(e);
+            }
+        };
+
+        let mut samples = converted.samples;
+        self.chain
+            .prepare(converted.sample_rate, converted.channels);
+        self.chain.process(&mut samples);
+
+        Ok(ConvertedChunk {
+            samples,
+            sample_rate: converted.sample_rate,
+            channels: converted.channels,
+            bytes_sent: converted.bytes_sent,
+            bytes_received: converted.bytes_received,
+        })
+    }
+
+    /// チャンクの符号化方式を決める（Opusが使えなければ警告してPCMに戻す）
+    fn negotiate_codec(&mut self) -> Codec {
+        if self.config.codec == Codec::Opus {
+            if !opus_chunk::is_available() {
+                warn!("このビルドはOpusに対応していないため、PCMで送ります（cargo build --features opus）");
+                self.config.codec = Codec::Pcm;
+            } else if !self.client.supports(Capability::Opus) {
+                warn!("サーバーがOpusに対応していないため、PCMで送ります");
+                self.config.codec = Codec::Pcm;
+            }
+        }
+        self.config.codec
+    }
+
+    /// ローカルのピッチシフトで1チャンクを変換（サーバーとは通信しない）
+    ///
+    /// サーバーに届かない間の代わりの出力（`--fallback pitch`）にも使います。
+    pub fn convert_local(
+        &mut self,
+        chunk: &[f32],
+        sample_rate: u32,
+        channels: u16,
+    ) -> ConvertedChunk {
+        let pitch_shift = self.config.pitch_shift as f32;
+        let shifter = self
+            .shifter
+            .get_or_insert_with(|| PitchShifter::new(pitch_shift));
+        shifter.set_semitones(pitch_shift);
+        shifter.prepare(sample_rate, channels);
+
+        let mut samples = chunk.to_vec();
+        shifter.process(&mut samples);
+        self.chain.prepare(sample_rate, channels);
+        self.chain.process(&mut samples);
+
+        ConvertedChunk {
+            samples,
+            sample_rate,
+            channels,
+            bytes_sent: 0,
+            bytes_received: 0,
+        }
+    }
+
+    /// サーバー側のセッション状態をリセット
+    pub async fn reset_session(&mut self) -> Result<()> {
+        self.chain.reset();
+        if let Some(shifter) = &mut self.shifter {
+            shifter.reset();
+        }
+        if self.config.engine == Engine::Local {
+            return Ok(());
+        }
+        self.client.reset_session(&self.config.session_id).await
+    }
+}
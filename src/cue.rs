@@ -0,0 +1,852 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+use std::str::FromStr;
+use tracing::{info, warn};
+
+use crate::config::Config;
+
+/// 字幕の先頭を話者名とみなす最大の文字数
+const MAX_SPEAKER_CHARS: usize = 24;
+
+/// 区間ごとの設定の上書き（キューファイルの1行）
+///
+/// 指定しなかった項目は、コマンドラインや設定ファイルの値のままです。
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Cue {
+    /// 区間の始まり（秒）
+    #[serde(deserialize_with = "deserialize_time")]
+    pub start: f64,
+    /// 区間の終わり（秒）
+    #[serde(deserialize_with = "deserialize_time")]
+    pub end: f64,
+    /// ログに出す名前（役名や場面など）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    /// 使うプリセット（`makebeliv preset save` で登録したもの。下の項目で上書きできる）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preset: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// ピッチシフト（半音）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pitch: Option<i32>,
+    /// 背景ノイズの種類（cafe, street, room かWAVファイル）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub noise: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub noise_level: Option<f32>,
+}
+
+impl Cue {
+    /// ログに出す名前（なければ時刻）
+    pub fn name(&self) -> String {
+        self.label
+            .clone()
+            .unwrap_or_else(|| format!("{}〜{}", format_time(self.start), format_time(self.end)))
+    }
+}
+
+/// 1つの入力ファイルを区間ごとに違う設定で変換するためのキュー（`process --cues`）
+///
+/// JSON（キューの配列か `{"cues": [...]}`）かCSV（1行目が項目名）で書きます。
+/// 時刻は秒（`12.5`）か `分:秒`（`1:02.5`）、`時:分:秒` です。
+/// キューは重なってはいけません。キューのない部分は元の設定で変換します。
+///
+/// 台本として、話者名つきの字幕（SRT・WebVTT）やチャプターの一覧も読めます。
+/// 台本のキューは話者名・章の名前をラベルにし、[`CueSheet::cast`] でプリセットを割り当てます。
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CueSheet {
+    /// 始まりの順
+    cues: Vec<Cue>,
+    /// 台本（字幕・チャプター）から読んだ（ラベルと同じ名前のプリセットを使う）
+    script: bool,
+}
+
+/// キューで分けた区間（フレーム単位）
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CueSpan<'a> {
+    pub start: usize,
+    pub end: usize,
+    /// この区間のキュー（Noneなら元の設定のまま）
+    pub cue: Option<&'a Cue>,
+}
+
+impl CueSheet {
+    /// キューファイルを読み込む
+    ///
+    /// 拡張子が .csv ならCSV、.srt・.vtt なら字幕、.json ならJSONです。
+    /// それ以外は、`[` か `{` で始まればJSON、そうでなければチャプターの一覧として読みます。
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("キューファイルの読み込みエラー: {}", path.display()))?;
+        let text = text.trim_start_matches('\u{feff}');
+        let extension = path
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        let sheet = match extension.as_str() {
+            "csv" => Self::parse_csv(text),
+            "srt" | "vtt" => Self::parse_subtitles(text),
+            "json" => Self::parse_json(text),
+            _ if text.trim_start().starts_with(['[', '{']) => Self::parse_json(text),
+            _ => Self::parse_chapters(text),
+        };
+        sheet.with_context(|| format!("キューファイルの形式が不正です: {}", path.display()))
+    }
+
+    pub fn parse_json(text: &str) -> Result<Self> {
+        let mut value: serde_json::Value =
+            serde_json::from_str(text).context("JSONの解析エラー")?;
+        if let Some(cues) = value.get_mut("cues") {
+            value = cues.take();
+        }
+        // どのキューのどの項目が不正かを示す
+        let cues: Vec<Cue> = serde_path_to_error::deserialize(value)
+            .map_err(|e| anyhow::anyhow!("{}: {}", e.path(), e.inner()))?;
+        Self::new(cues)
+    }
+
+    /// CSVを読む（項目名: start, end, label, preset, model, pitch, noise, noise_level。空欄は上書きしない）
+    pub fn parse_csv(text: &str) -> Result<Self> {
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with('#'));
+        let (_, header) = lines.next().context("CSVの項目名の行がありません")?;
+        let columns: Vec<String> = split_csv(header)?
+            .into_iter()
+            .map(|c| c.to_lowercase())
+            .collect();
+        for required in ["start", "end"] {
+            anyhow::ensure!(
+                columns.iter().any(|c| c == required),
+                "CSVに {} の列がありません",
+                required
+            );
+        }
+
+        let mut cues = Vec::new();
+        for (index, line) in lines {
+            let row = index + 1;
+            let fields = split_csv(line).with_context(|| format!("{}行目", row))?;
+            let mut cue = Cue::default();
+            for required in ["start", "end"] {
+                let filled = columns
+                    .iter()
+                    .zip(&fields)
+                    .any(|(column, value)| column == required && !value.trim().is_empty());
+                anyhow::ensure!(filled, "{}行目に {} がありません", row, required);
+            }
+            for (column, value) in columns.iter().zip(&fields) {
+                let value = value.trim();
+                if value.is_empty() {
+                    continue;
+                }
+                let parsed: Result<()> = (|| {
+                    match column.as_str() {
+                        "start" => cue.start = parse_time(value)?,
+                        "end" => cue.end = parse_time(value)?,
+                        "label" => cue.label = Some(value.to_string()),
+                        "preset" => cue.preset = Some(value.to_string()),
+                        "model" => cue.model = Some(value.to_string()),
+                        "pitch" => cue.pitch = Some(value.trim_start_matches('+').parse()?),
+                        "noise" => cue.noise = Some(value.to_string()),
+                        "noise_level" => cue.noise_level = Some(value.parse()?),
+                        _ => anyhow::bail!("不明な列です"),
+                    }
+                    Ok(())
+                })();
+                parsed
+                    .with_context(|| format!("{}行目の {} が不正です: {}", row, column, value))?;
+            }
+            cues.push(cue);
+        }
+        Self::new(cues)
+    }
+
+    /// 話者名つきの字幕（SRT・WebVTT）を読む
+    ///
+    /// 話者は字幕の先頭の `名前: `（全角の `：` も可）、`[名前]`、`名前「`、`<v 名前>` です。
+    /// 話者のない字幕は直前の話者の続きとみなします。1つの字幕は次の字幕が始まるまで続け、
+    /// 同じ話者の続く字幕は1つのキューにまとめます。
+    pub fn parse_subtitles(text: &str) -> Result<Self> {
+        let mut lines: Vec<(f64, f64, Option<String>)> = Vec::new();
+        let text = text.replace("\r\n", "\n");
+        for block in text.split("\n\n") {
+            // 番号や WEBVTT の見出し、NOTE は読み飛ばす
+            let mut rows = block.lines().skip_while(|line| !line.contains("-->"));
+            let Some(timing) = rows.next() else { continue };
+            let (start, end) = timing
+                .split_once("-->")
+                .context("字幕の時刻の行が不正です")?;
+            // WebVTT は時刻の後に位置の指定が続くことがある
+            let end = end.split_whitespace().next().unwrap_or_default();
+            let time = |s: &str| {
+                parse_time(&s.trim().replace(',', "."))
+                    .with_context(|| format!("字幕の時刻が不正です: {}", timing.trim()))
+            };
+            let (start, end) = (time(start)?, time(end)?);
+            let speaker = rows.find(|row| !row.trim().is_empty()).and_then(speaker);
+            lines.push((start, end, speaker));
+        }
+        anyhow::ensure!(!lines.is_empty(), "字幕が1つもありません");
+        lines.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        let mut cues: Vec<Cue> = Vec::new();
+        let mut current = None;
+        for (i, (start, end, speaker)) in lines.iter().enumerate() {
+            if speaker.is_some() {
+                current = speaker.clone();
+            }
+            let end = lines.get(i + 1).map_or(*end, |next| next.0);
+            let Some(name) = current.clone().filter(|_| end > *start) else {
+                continue;
+            };
+            match cues.last_mut() {
+                Some(cue) if cue.label.as_ref() == Some(&name) && cue.end >= *start => {
+                    cue.end = end
+                }
+                _ => cues.push(Cue {
+                    start: *start,
+                    end,
+                    label: Some(name),
+                    ..Default::default()
+                }),
+            }
+        }
+        anyhow::ensure!(!cues.is_empty(), "話者の分かる字幕がありません");
+        Ok(Self {
+            script: true,
+            ..Self::new(cues)?
+        })
+    }
+
+    /// チャプターの一覧を読む
+    ///
+    /// 1行に `時刻 章の名前`（`0:00 序章` のような動画の説明欄の形式）か、
+    /// ffmpeg のメタデータファイル（`;FFMETADATA1` の `[CHAPTER]`）です。
+    /// 一覧の形式では、各章は次の章の始まりまで（最後の章は終わりまで）続きます。
+    pub fn parse_chapters(text: &str) -> Result<Self> {
+        let mut cues = if text.trim_start().starts_with(";FFMETADATA") {
+            parse_ffmetadata(text)?
+        } else {
+            let mut cues: Vec<Cue> = Vec::new();
+            for (index, line) in text.lines().enumerate() {
+                let line = line.trim();
+                if line.is_empty() || line.starts_with('#') {
+                    continue;
+                }
+                let (time, title) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+                let title = title
+                    .trim_start()
+                    .trim_start_matches(['-', '–', '|'])
+                    .trim();
+                anyhow::ensure!(!title.is_empty(), "{}行目に章の名前がありません", index + 1);
+                let start = parse_time(time).with_context(|| format!("{}行目", index + 1))?;
+                if let Some(previous) = cues.last_mut() {
+                    previous.end = start;
+                }
+                cues.push(Cue {
+                    start,
+                    end: f64::INFINITY,
+                    label: Some(title.to_string()),
+                    ..Default::default()
+                });
+            }
+            cues
+        };
+        anyhow::ensure!(!cues.is_empty(), "章が1つもありません");
+        cues.retain(|cue| cue.end > cue.start);
+        Ok(Self {
+            script: true,
+            ..Self::new(cues)?
+        })
+    }
+
+    /// 話者や章の名前にプリセットを割り当て、プリセットの設定をキューに入れる
+    ///
+    /// `cast` は名前とプリセットの対応（`--cast 名前=プリセット`）です。台本のキューは、
+    /// 対応がなければ名前と同じプリセットを使い、それもなければ元の設定のまま変換します。
+    /// キューに直接書いた項目はプリセットより優先します。
+    pub fn cast(&mut self, cast: &BTreeMap<String, String>, config: &Config) -> Result<()> {
+        let names: BTreeSet<&str> = self
+            .cues
+            .iter()
+            .filter_map(|c| c.label.as_deref())
+            .collect();
+        for name in cast.keys().filter(|name| !names.contains(name.as_str())) {
+            warn!("--cast の {} はキューにありません", name);
+        }
+
+        let script = self.script;
+        let mut unassigned = BTreeSet::new();
+        for cue in &mut self.cues {
+            let Some(label) = &cue.label else { continue };
+            if cue.preset.is_none() {
+                cue.preset = cast.get(label).cloned().or_else(|| {
+                    (script && config.presets.contains_key(label)).then(|| label.clone())
+                });
+            }
+            if script && cue.preset.is_none() {
+                unassigned.insert(label.clone());
+            }
+        }
+        if !unassigned.is_empty() {
+            let names: Vec<String> = unassigned.into_iter().collect();
+            warn!(
+                "プリセットを割り当てていない名前は元の設定で変換します: {}",
+                names.join(", ")
+            );
+        }
+        if script {
+            self.cues.retain(|cue| cue.preset.is_some());
+        }
+
+        let mut logged = BTreeSet::new();
+        for cue in &mut self.cues {
+            let Some(name) = &cue.preset else { continue };
+            let preset = config
+                .preset(name)
+                .with_context(|| format!("キュー {} のプリセット", cue.name()))?;
+            if logged.insert((cue.label.clone(), name.clone())) {
+                match &cue.label {
+                    Some(label) if label != name => {
+                        info!("キャスト: {} → プリセット {}", label, name)
+                    }
+                    _ => info!("キャスト: プリセット {}", name),
+                }
+            }
+            cue.model = cue.model.take().or_else(|| preset.model.clone());
+            cue.pitch = cue.pitch.or(preset.pitch);
+            cue.noise = cue.noise.take().or_else(|| preset.noise.clone());
+            cue.noise_level = cue.noise_level.or(preset.noise_level);
+        }
+        Ok(())
+    }
+
+    /// キューを始まりの順に並べ、時刻と重なりを確かめる
+    fn new(mut cues: Vec<Cue>) -> Result<Self> {
+        anyhow::ensure!(!cues.is_empty(), "キューが1つもありません");
+        for cue in &cues {
+            anyhow::ensure!(
+                cue.start >= 0.0 && cue.end > cue.start,
+                "キュー {} の時刻が不正です（終わりは始まりより後にしてください）",
+                cue.name()
+            );
+            if let Some(level) = cue.noise_level {
+                anyhow::ensure!(
+                    (0.0..=1.0).contains(&level),
+                    "キュー {} の noise_level は0.0〜1.0で指定してください",
+                    cue.name()
+                );
+            }
+        }
+        cues.sort_by(|a, b| a.start.total_cmp(&b.start));
+        for pair in cues.windows(2) {
+            anyhow::ensure!(
+                pair[1].start >= pair[0].end,
+                "キュー {} と {} が重なっています",
+                pair[0].name(),
+                pair[1].name()
+            );
+        }
+        Ok(Self {
+            cues,
+            script: false,
+        })
+    }
+
+    pub fn cues(&self) -> &[Cue] {
+        &self.cues
+    }
+
+    /// `frames` フレームの音声を、キューの区間とその間の区間に分ける（隙間なく並ぶ）
+    ///
+    /// 音声より後ろのキューは切り詰めるか除きます。
+    pub fn spans(&self, frames: usize, sample_rate: u32) -> Vec<CueSpan<'_>> {
+        let frame_at = |seconds: f64| ((seconds * sample_rate as f64).round() as usize).min(frames);
+        let mut spans = Vec::new();
+        let mut position = 0;
+        for cue in &self.cues {
+            let (start, end) = (frame_at(cue.start), frame_at(cue.end));
+            if start >= end {
+                continue;
+            }
+            if start > position {
+                spans.push(CueSpan {
+                    start: position,
+                    end: start,
+                    cue: None,
+                });
+            }
+            spans.push(CueSpan {
+                start,
+                end,
+                cue: Some(cue),
+            });
+            position = end;
+        }
+        if position < frames {
+            spans.push(CueSpan {
+                start: position,
+                end: frames,
+                cue: None,
+            });
+        }
+        spans
+    }
+}
+
+/// 字幕の1行目から話者名を読む
+fn speaker(line: &str) -> Option<String> {
+    let line = line.trim().trim_start_matches('-').trim_start();
+    let name = if let Some(rest) = line.strip_prefix("<v") {
+        // WebVTT の <v 名前> / <v.クラス 名前>
+        let (tag, _) = rest.split_once('>')?;
+        tag.split_once(char::is_whitespace)?.1
+    } else if let Some(rest) = line.strip_prefix('[') {
+        rest.split_once(']')?.0
+    } else {
+        let end = line.find([':', '：', '「'])?;
+        &line[..end]
+    };
+    let name = name.trim();
+    // 長すぎるものは話者名でなく本文とみなす
+    (!name.is_empty() && name.chars().count() <= MAX_SPEAKER_CHARS).then(|| name.to_string())
+}
+
+/// ffmpeg のメタデータファイルの章を読む
+fn parse_ffmetadata(text: &str) -> Result<Vec<Cue>> {
+    let mut chapters: Vec<BTreeMap<String, String>> = Vec::new();
+    let mut in_chapter = false;
+    for line in text.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_chapter = line.eq_ignore_ascii_case("[CHAPTER]");
+            if in_chapter {
+                chapters.push(BTreeMap::new());
+            }
+        } else if let (true, Some((key, value))) = (in_chapter, line.split_once('=')) {
+            if let Some(chapter) = chapters.last_mut() {
+                chapter.insert(key.trim().to_lowercase(), value.trim().to_string());
+            }
+        }
+    }
+    chapters
+        .iter()
+        .enumerate()
+        .map(|(i, chapter)| {
+            let number = i + 1;
+            let timebase = chapter.get("timebase").map_or("1/1000", String::as_str);
+            let (num, den) = timebase
+                .split_once('/')
+                .and_then(|(n, d)| Some((n.parse::<f64>().ok()?, d.parse::<f64>().ok()?)))
+                .filter(|(_, d)| *d > 0.0)
+                .with_context(|| {
+                    format!("{}番目の章の TIMEBASE が不正です: {}", number, timebase)
+                })?;
+            let time = |key: &str| -> Result<f64> {
+                let value = chapter.get(key).with_context(|| {
+                    format!("{}番目の章に {} がありません", number, key.to_uppercase())
+                })?;
+                let ticks: f64 = value.parse().with_context(|| {
+                    format!(
+                        "{}番目の章の {} が不正です: {}",
+                        number,
+                        key.to_uppercase(),
+                        value
+                    )
+                })?;
+                Ok(ticks * num / den)
+            };
+            Ok(Cue {
+                start: time("start")?,
+                end: time("end")?,
+                label: Some(
+                    chapter
+                        .get("title")
+                        .cloned()
+                        .unwrap_or_else(|| format!("第{}章", number)),
+                ),
+                ..Default::default()
+            })
+        })
+        .collect()
+}
+
+/// 時刻を秒にする（`12.5`, `1:02.5`, `1:02:03.25`）
+pub fn parse_time(s: &str) -> Result<f64> {
+    let mut seconds = 0.0;
+    let parts: Vec<&str> = s.trim().split(':').collect();
+    anyhow::ensure!(parts.len() <= 3, "時刻の形式が不正です: {}", s);
+    for (i, part) in parts.iter().enumerate() {
+        let value: f64 = part
+            .parse()
+            .ok()
+            .filter(|v: &f64| v.is_finite() && *v >= 0.0)
+            .with_context(|| format!("時刻の形式が不正です: {}", s))?;
+        // 分と秒は60未満（先頭の単位は制限しない）
+        anyhow::ensure!(i == 0 || value < 60.0, "時刻の形式が不正です: {}", s);
+        seconds = seconds * 60.0 + value;
+    }
+    Ok(seconds)
+}
+
+/// 入力の一部の時間範囲（`process --range 1:10-2:30`）
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TimeRange {
+    /// 始まり（秒）
+    pub start: f64,
+    /// 終わり（秒）
+    pub end: f64,
+}
+
+impl TimeRange {
+    /// サンプル単位の範囲（フレーム）。終わりが長さを超えていれば長さまでにする
+    pub fn frames(&self, frames: usize, sample_rate: u32) -> Result<(usize, usize)> {
+        let to_frame = |seconds: f64| (seconds * sample_rate as f64).round() as usize;
+        let (start, end) = (to_frame(self.start), to_frame(self.end).min(frames));
+        anyhow::ensure!(
+            start < end,
+            "範囲 {} は入力（{}）の外です",
+            self,
+            format_time(frames as f64 / sample_rate.max(1) as f64)
+        );
+        Ok((start, end))
+    }
+}
+
+impl FromStr for TimeRange {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (start, end) = s
+            .split_once('-')
+            .with_context(|| format!("範囲は 始まり-終わり で指定してください: {}", s))?;
+        let range = Self {
+            start: parse_time(start)?,
+            end: parse_time(end)?,
+        };
+        anyhow::ensure!(
+            range.start < range.end,
+            "範囲の終わりは始まりより後にしてください: {}",
+            s
+        );
+        Ok(range)
+    }
+}
+
+impl std::fmt::Display for TimeRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}〜{}", format_time(self.start), format_time(self.end))
+    }
+}
+
+/// 秒を `分:秒` で表す
+pub fn format_time(seconds: f64) -> String {
+    format!("{}:{:05.2}", (seconds / 60.0) as u64, seconds % 60.0)
+}
+
+fn deserialize_time<'de, D>(deserializer: D) -> std::result::Result<f64, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Time {
+        Seconds(f64),
+        Text(String),
+    }
+    match Time::deserialize(deserializer)? {
+        Time::Seconds(seconds) => Ok(seconds),
+        Time::Text(text) => parse_time(&text).map_err(serde::de::Error::custom),
+    }
+}
+
+/// CSVの1行を列に分ける（`"` で囲んだ列にはカンマと `""` を書ける）
+fn split_csv(line: &str) -> Result<Vec<String>> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, quoted) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            ('"', true) => quoted = false,
+            ('"', false) if field.trim().is_empty() => {
+                field.clear();
+                quoted = true;
+            }
+            (',', false) => fields.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+    anyhow::ensure!(!quoted, "\" が閉じていません");
+    fields.push(field);
+    Ok(fields)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_times() {
+        let cases = [
+            ("0", 0.0),
+            ("12.5", 12.5),
+            (" 7 ", 7.0),
+            ("1:02.5", 62.5),
+            ("90:00", 5400.0),
+            ("1:02:03.25", 3723.25),
+            ("0:00:59.99", 59.99),
+        ];
+        for (text, expected) in cases {
+            let seconds = parse_time(text).unwrap_or_else(|e| panic!("{}: {}", text, e));
+            assert!((seconds - expected).abs() < 1e-9, "{} -> {}", text, seconds);
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_times() {
+        for text in [
+            "", "abc", "-1", "1:-2", "1:60", "1:02:60", "1:2:3:4", "1::2", "inf", "NaN", "1,5",
+        ] {
+            assert!(parse_time(text).is_err(), "{:?} は不正なはず", text);
+        }
+    }
+
+    #[test]
+    fn parses_time_ranges() {
+        let cases = [
+            ("1:10-2:30", 70.0, 150.0),
+            ("0-0.5", 0.0, 0.5),
+            (" 1 - 2 ", 1.0, 2.0),
+        ];
+        for (text, start, end) in cases {
+            let range: TimeRange = text.parse().unwrap_or_else(|e| panic!("{}: {}", text, e));
+            assert_eq!(range, TimeRange { start, end }, "{}", text);
+        }
+        // 逆順・空・区切りなし・時刻の誤り
+        for text in ["2:30-1:10", "5-5", "1:10", "a-b", "1:70-2:00", "1-2-3"] {
+            assert!(
+                text.parse::<TimeRange>().is_err(),
+                "{:?} は不正なはず",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn time_range_frames_clamp_to_the_input() {
+        let range = TimeRange {
+            start: 1.0,
+            end: 2.0,
+        };
+        assert_eq!(range.frames(48_000, 16_000).unwrap(), (16_000, 32_000));
+        // 終わりが長さを超えていれば長さまで
+        assert_eq!(range.frames(20_000, 16_000).unwrap(), (16_000, 20_000));
+        // 始まりが長さ以降なら範囲の外
+        assert!(range.frames(16_000, 16_000).is_err());
+        assert!(range.frames(8_000, 16_000).is_err());
+    }
+
+    #[test]
+    fn parses_json_cues() {
+        let array = r#"[
+            {"start": "0:10", "end": 20, "label": "B", "pitch": -3},
+            {"start": 0, "end": "0:05.5", "label": "A", "noise_level": 0.2}
+        ]"#;
+        let wrapped = format!(r#"{{"cues": {}}}"#, array);
+        for text in [array, wrapped.as_str()] {
+            let sheet = CueSheet::parse_json(text).unwrap();
+            let cues = sheet.cues();
+            // 始まりの順に並ぶ
+            assert_eq!(cues.len(), 2);
+            assert_eq!((cues[0].start, cues[0].end), (0.0, 5.5));
+            assert_eq!(cues[0].noise_level, Some(0.2));
+            assert_eq!((cues[1].start, cues[1].end), (10.0, 20.0));
+            assert_eq!(cues[1].pitch, Some(-3));
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_json_cues() {
+        let cases = [
+            ("not json", "JSON"),
+            ("[]", "1つも"),
+            (r#"[{"start": 0, "end": 1, "speed": 2}]"#, "speed"),
+            (r#"[{"start": "1:xx", "end": 2}]"#, "時刻"),
+            (r#"[{"start": 3, "end": 1}]"#, "時刻が不正"),
+            (r#"[{"start": 1, "end": 1}]"#, "時刻が不正"),
+            (r#"[{"start": -1, "end": 1}]"#, "時刻が不正"),
+            (
+                r#"[{"start": 0, "end": 1, "noise_level": 1.5}]"#,
+                "noise_level",
+            ),
+            (
+                r#"[{"start": 0, "end": 5, "label": "A"}, {"start": 4, "end": 8, "label": "B"}]"#,
+                "重なって",
+            ),
+        ];
+        for (text, message) in cases {
+            let error = format!("{:#}", CueSheet::parse_json(text).unwrap_err());
+            assert!(error.contains(message), "{}: {}", text, error);
+        }
+    }
+
+    #[test]
+    fn parses_csv_cues() {
+        let text = "\
+# コメント
+Start,End,Label,Preset,Pitch,Noise_Level
+
+0:00,0:05,\"Alice, narrator\",,+2,
+5,10,\"He said \"\"hi\"\"\",robot,,0.5
+";
+        let sheet = CueSheet::parse_csv(text).unwrap();
+        let cues = sheet.cues();
+        assert_eq!(cues.len(), 2);
+        assert_eq!(cues[0].label.as_deref(), Some("Alice, narrator"));
+        assert_eq!(cues[0].pitch, Some(2));
+        assert_eq!(cues[0].preset, None);
+        assert_eq!(cues[1].label.as_deref(), Some("He said \"hi\""));
+        assert_eq!(cues[1].preset.as_deref(), Some("robot"));
+        assert_eq!(cues[1].noise_level, Some(0.5));
+    }
+
+    #[test]
+    fn rejects_invalid_csv_cues() {
+        let cases = [
+            ("", "項目名"),
+            ("start,label\n0,a\n", "end の列"),
+            ("start,end,speed\n0,1,2\n", "speed"),
+            ("start,end\n0,\n", "end がありません"),
+            ("start,end,pitch\n0,1,high\n", "pitch"),
+            ("start,end,label\n0,1,\"open\n", "閉じて"),
+            ("start,end\n2,1\n", "時刻が不正"),
+            ("start,end\n0,5\n3,8\n", "重なって"),
+        ];
+        for (text, message) in cases {
+            let error = format!("{:#}", CueSheet::parse_csv(text).unwrap_err());
+            assert!(error.contains(message), "{:?}: {}", text, error);
+        }
+    }
+
+    #[test]
+    fn parses_srt_speakers() {
+        let text = "1\r\n00:00:01,000 --> 00:00:02,000\r\nAlice: こんにちは\r\n\r\n\
+2\r\n00:00:02,500 --> 00:00:03,000\r\n続きの台詞\r\n\r\n\
+3\r\n00:00:04,000 --> 00:00:05,000\r\n[Bob] やあ\r\n\r\n\
+4\r\n00:00:06,000 --> 00:00:07,500\r\nアリス「またね」\r\n";
+        let sheet = CueSheet::parse_subtitles(text).unwrap();
+        let cues: Vec<(f64, f64, &str)> = sheet
+            .cues()
+            .iter()
+            .map(|c| (c.start, c.end, c.label.as_deref().unwrap()))
+            .collect();
+        // 話者のない字幕は直前の話者に続け、各字幕は次の字幕の始まりまで続く
+        assert_eq!(
+            cues,
+            [(1.0, 4.0, "Alice"), (4.0, 6.0, "Bob"), (6.0, 7.5, "アリス")]
+        );
+    }
+
+    #[test]
+    fn parses_vtt_voice_tags() {
+        let text = "WEBVTT\n\nNOTE 見出し\n\n\
+00:01.000 --> 00:02.000 align:start\n<v Alice>Hi\n\n\
+00:02.000 --> 00:03.000\n<v.loud Bob>Hey\n";
+        let sheet = CueSheet::parse_subtitles(text).unwrap();
+        let labels: Vec<_> = sheet.cues().iter().map(|c| c.label.clone()).collect();
+        assert_eq!(labels, [Some("Alice".into()), Some("Bob".into())]);
+    }
+
+    #[test]
+    fn rejects_invalid_subtitles() {
+        let cases = [
+            ("", "1つもありません"),
+            ("1\n00:00:01,000 --> xx\nAlice: hi\n", "時刻が不正"),
+            (
+                "1\n00:00:01,000 --> 00:00:02,000\n話者のない台詞\n",
+                "話者の分かる",
+            ),
+        ];
+        for (text, message) in cases {
+            let error = format!("{:#}", CueSheet::parse_subtitles(text).unwrap_err());
+            assert!(error.contains(message), "{:?}: {}", text, error);
+        }
+    }
+
+    #[test]
+    fn parses_chapter_lists() {
+        let sheet =
+            CueSheet::parse_chapters("0:00 序章\n# メモ\n1:30 - 本編\n10:00 | 終章\n").unwrap();
+        let cues = sheet.cues();
+        assert_eq!(cues.len(), 3);
+        assert_eq!((cues[0].start, cues[0].end), (0.0, 90.0));
+        assert_eq!(cues[1].label.as_deref(), Some("本編"));
+        assert_eq!(cues[2].start, 600.0);
+        assert!(cues[2].end.is_infinite());
+
+        let ffmetadata =
+            ";FFMETADATA1\ntitle=x\n\n[CHAPTER]\nTIMEBASE=1/1000\nSTART=0\nEND=1500\ntitle=A\n\n\
+[CHAPTER]\nTIMEBASE=1/10\nSTART=15\nEND=30\n";
+        let sheet = CueSheet::parse_chapters(ffmetadata).unwrap();
+        let cues: Vec<_> = sheet
+            .cues()
+            .iter()
+            .map(|c| (c.start, c.end, c.label.clone().unwrap()))
+            .collect();
+        assert_eq!(
+            cues,
+            [(0.0, 1.5, "A".to_string()), (1.5, 3.0, "第2章".to_string())]
+        );
+
+        for (text, message) in [
+            ("0:00\n", "章の名前"),
+            ("x 序章\n", "1行目"),
+            ("\n# だけ\n", "1つも"),
+            (";FFMETADATA1\n[CHAPTER]\nSTART=0\n", "END"),
+            (
+                ";FFMETADATA1\n[CHAPTER]\nTIMEBASE=1/0\nSTART=0\nEND=1\n",
+                "TIMEBASE",
+            ),
+        ] {
+            let error = format!("{:#}", CueSheet::parse_chapters(text).unwrap_err());
+            assert!(error.contains(message), "{:?}: {}", text, error);
+        }
+    }
+
+    #[test]
+    fn spans_cover_the_input_without_gaps() {
+        let sheet = CueSheet::parse_json(
+            r#"[{"start": 1, "end": 2, "label": "A"},
+                {"start": 2, "end": 3, "label": "B"},
+                {"start": 5, "end": 9, "label": "C"},
+                {"start": 20, "end": 30, "label": "D"}]"#,
+        )
+        .unwrap();
+        let spans: Vec<(usize, usize, Option<&str>)> = sheet
+            .spans(60, 10)
+            .iter()
+            .map(|s| (s.start, s.end, s.cue.and_then(|c| c.label.as_deref())))
+            .collect();
+        // C は音声の終わりで切り詰め、音声より後ろの D は除く
+        assert_eq!(
+            spans,
+            [
+                (0, 10, None),
+                (10, 20, Some("A")),
+                (20, 30, Some("B")),
+                (30, 50, None),
+                (50, 60, Some("C")),
+            ]
+        );
+    }
+}
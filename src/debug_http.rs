@@ -0,0 +1,415 @@
+use anyhow::{Context as _, Result};
+use bytes::Bytes;
+use futures_util::Stream;
+use reqwest::header::HeaderMap;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+use std::task::{Context, Poll};
+use std::time::Instant;
+use tracing::{info, warn};
+
+/// `--debug-http` で有効にした記録先
+static RECORDER: OnceLock<HttpRecorder> = OnceLock::new();
+
+/// 本文をそのまま残すContent-Type（それ以外は先頭バイトだけ残す）
+const TEXT_CONTENT_TYPES: &[&str] = &["application/json", "text/"];
+
+/// 残すテキスト本文の最大長
+const MAX_TEXT_BODY_BYTES: usize = 64 * 1024;
+
+/// バイナリ本文（音声など）から残す先頭バイト数
+const BINARY_HEAD_BYTES: usize = 64;
+
+/// 値を伏せるヘッダー（小文字）
+const REDACTED_HEADERS: &[&str] = &[
+    "authorization",
+    "proxy-authorization",
+    "cookie",
+    "set-cookie",
+    "x-api-key",
+];
+
+/// HTTPの送受信を1回ごとにJSONファイルへ記録する
+pub struct HttpRecorder {
+    dir: PathBuf,
+    seq: AtomicU64,
+}
+
+/// 記録するリクエストの情報
+#[derive(Serialize)]
+struct RequestRecord {
+    method: String,
+    url: String,
+    headers: Vec<(String, String)>,
+    /// マルチパートのフィールド（ファイルはサイズのみ）
+    fields: Vec<(String, String)>,
+}
+
+/// 記録するレスポンスの情報
+#[derive(Serialize)]
+struct ResponseRecord {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body_len: usize,
+    /// テキスト本文（JSONなど）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    body: Option<String>,
+    /// バイナリ本文の先頭（16進）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    body_head: Option<String>,
+}
+
+#[derive(Serialize)]
+struct CallRecord {
+    call: String,
+    elapsed_ms: f64,
+    request: RequestRecord,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response: Option<ResponseRecord>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// 以降のHTTP通信を `dir` に記録する（`--debug-http`）
+pub fn enable(dir: &Path) -> Result<()> {
+    std::fs::create_dir_all(dir)
+        .with_context(|| format!("記録先を作成できません: {}", dir.display()))?;
+
+    RECORDER
+        .set(HttpRecorder {
+            dir: dir.to_path_buf(),
+            seq: AtomicU64::new(0),
+        })
+        .map_err(|_| anyhow::anyhow!("HTTPの記録は既に有効です"))?;
+    info!("HTTPの送受信を記録: {}", dir.display());
+
+    Ok(())
+}
+
+/// 有効な記録先（無効ならNone）
+pub fn recorder() -> Option<&'static HttpRecorder> {
+    RECORDER.get()
+}
+
+impl HttpRecorder {
+    /// リクエストを送り、送受信を記録する
+    ///
+    /// テキスト本文は読み切って記録し、読み直したレスポンスを返します。バイナリ本文（音声など）は
+    /// 読み切らずに流し、先頭だけを写し取って、読み終えたとき（途中で捨てられたときも）に記録します。
+    pub async fn execute(
+        &self,
+        client: &reqwest::Client,
+        call: &str,
+        request: reqwest::Request,
+        fields: &[(&str, String)],
+    ) -> reqwest::Result<reqwest::Response> {
+        let mut record = CallRecord {
+            call: call.to_string(),
+            elapsed_ms: 0.0,
+            request: RequestRecord {
+                method: request.method().to_string(),
+                url: sanitize_url(request.url()),
+                headers: sanitize_headers(request.headers()),
+                fields: fields
+                    .iter()
+                    .map(|(name, value)| (name.to_string(), value.clone()))
+                    .collect(),
+            },
+            response: None,
+            error: None,
+        };
+        let path = self.next_path(call);
+
+        let start = Instant::now();
+        let response = match client.execute(request).await {
+            Ok(response) => response,
+            Err(e) => {
+                record.elapsed_ms = elapsed_ms(start);
+                record.error = Some(e.to_string());
+                write_record(&path, &record);
+                return Err(e);
+            }
+        };
+        let status = response.status();
+        let version = response.version();
+        let headers = response.headers().clone();
+
+        if !is_text(&headers) {
+            let tee = Tee {
+                inner: Box::pin(response.bytes_stream()),
+                record: Some((path, record)),
+                status: status.as_u16(),
+                headers: headers.clone(),
+                start,
+                head: Vec::with_capacity(BINARY_HEAD_BYTES),
+                len: 0,
+                finished: false,
+                error: None,
+            };
+            return Ok(rebuild(
+                status,
+                version,
+                headers,
+                reqwest::Body::wrap_stream(tee),
+            ));
+        }
+
+        let body = response.bytes().await;
+        record.elapsed_ms = elapsed_ms(start);
+        match &body {
+            Ok(body) => {
+                let end = body.len().min(MAX_TEXT_BODY_BYTES);
+                record.response = Some(ResponseRecord {
+                    status: status.as_u16(),
+                    headers: sanitize_headers(&headers),
+                    body_len: body.len(),
+                    body: Some(String::from_utf8_lossy(&body[..end]).into_owned()),
+                    body_head: None,
+                });
+            }
+            Err(e) => record.error = Some(e.to_string()),
+        }
+        write_record(&path, &record);
+        Ok(rebuild(status, version, headers, body?))
+    }
+
+    /// 次の記録のファイル名（呼び出した順に番号を振る）
+    fn next_path(&self, call: &str) -> PathBuf {
+        let seq = self.seq.fetch_add(1, Ordering::Relaxed);
+        self.dir.join(format!("{:05}-{}.json", seq, call))
+    }
+}
+
+/// バイナリ本文をそのまま流しながら、先頭と長さを写し取るストリーム
+///
+/// 読み終えたとき、エラーになったとき、途中で捨てられたときのいずれかで一度だけ記録を書きます。
+struct Tee {
+    inner: Pin<Box<dyn Stream<Item = reqwest::Result<Bytes>> + Send + Sync>>,
+    /// 記録先と、レスポンス以外を埋めた記録（書いたらNone）
+    record: Option<(PathBuf, CallRecord)>,
+    status: u16,
+    headers: HeaderMap,
+    start: Instant,
+    head: Vec<u8>,
+    len: usize,
+    finished: bool,
+    error: Option<String>,
+}
+
+impl Tee {
+    fn finish(&mut self) {
+        let Some((path, mut record)) = self.record.take() else {
+            return;
+        };
+        record.elapsed_ms = elapsed_ms(self.start);
+        record.response = Some(ResponseRecord {
+            status: self.status,
+            headers: sanitize_headers(&self.headers),
+            body_len: self.len,
+            body: None,
+            body_head: Some(hex(&self.head)),
+        });
+        record.error = self
+            .error
+            .take()
+            .or_else(|| (!self.finished).then(|| "本文を読み終える前に破棄されました".to_string()));
+        write_record(&path, &record);
+    }
+}
+
+impl Stream for Tee {
+    type Item = reqwest::Result<Bytes>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let polled = self.inner.as_mut().poll_next(cx);
+        match &polled {
+            Poll::Ready(Some(Ok(chunk))) => {
+                let take = chunk.len().min(BINARY_HEAD_BYTES - self.head.len());
+                self.head.extend_from_slice(&chunk[..take]);
+                self.len += chunk.len();
+            }
+            Poll::Ready(Some(Err(e))) => {
+                self.error = Some(e.to_string());
+                self.finish();
+            }
+            Poll::Ready(None) => {
+                self.finished = true;
+                self.finish();
+            }
+            Poll::Pending => {}
+        }
+        polled
+    }
+}
+
+impl Drop for Tee {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+/// 読んだ本文でレスポンスを作り直す
+fn rebuild(
+    status: reqwest::StatusCode,
+    version: reqwest::Version,
+    headers: HeaderMap,
+    body: impl Into<reqwest::Body>,
+) -> reqwest::Response {
+    let mut rebuilt = http::Response::new(body.into());
+    *rebuilt.status_mut() = status;
+    *rebuilt.version_mut() = version;
+    *rebuilt.headers_mut() = headers;
+    reqwest::Response::from(rebuilt)
+}
+
+fn write_record(path: &Path, record: &CallRecord) {
+    let result = std::fs::File::create(path)
+        .map_err(anyhow::Error::from)
+        .and_then(|file| Ok(serde_json::to_writer_pretty(file, record)?));
+    if let Err(e) = result {
+        warn!("HTTPの記録エラー ({}): {}", path.display(), e);
+    }
+}
+
+fn is_text(headers: &HeaderMap) -> bool {
+    headers
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|ct| TEXT_CONTENT_TYPES.iter().any(|t| ct.starts_with(t)))
+}
+
+fn elapsed_ms(start: Instant) -> f64 {
+    start.elapsed().as_secs_f64() * 1000.0
+}
+
+fn sanitize_headers(headers: &HeaderMap) -> Vec<(String, String)> {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            let value = if REDACTED_HEADERS.contains(&name.as_str()) {
+                "<redacted>".to_string()
+            } else {
+                String::from_utf8_lossy(value.as_bytes()).into_owned()
+            };
+            (name.to_string(), value)
+        })
+        .collect()
+}
+
+/// URLに含まれる認証情報を伏せる
+fn sanitize_url(url: &reqwest::Url) -> String {
+    let mut url = url.clone();
+    if url.password().is_some() {
+        let _ = url.set_password(Some("redacted"));
+    }
+    url.to_string()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    /// 1回だけ `content_type` の本文を返すHTTPサーバーを立て、そのURLを返す
+    async fn serve_once(content_type: &'static str, body: Vec<u8>) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = [0u8; 1024];
+            let _ = socket.read(&mut request).await;
+            let head = format!(
+                "HTTP/1.1 200 OK\r\ncontent-type: {}\r\ncontent-length: {}\r\nconnection: close\r\n\r\n",
+                content_type,
+                body.len()
+            );
+            socket.write_all(head.as_bytes()).await.unwrap();
+            socket.write_all(&body).await.unwrap();
+        });
+        url
+    }
+
+    fn recorder(name: &str) -> HttpRecorder {
+        let dir = std::env::temp_dir().join(format!(
+            "makebeliv-debug-http-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        HttpRecorder {
+            dir,
+            seq: AtomicU64::new(0),
+        }
+    }
+
+    fn read_record(recorder: &HttpRecorder, call: &str) -> serde_json::Value {
+        let path = recorder.dir.join(format!("00000-{}.json", call));
+        let record = serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        std::fs::remove_dir_all(&recorder.dir).ok();
+        record
+    }
+
+    async fn execute(recorder: &HttpRecorder, url: &str, call: &str) -> reqwest::Response {
+        let client = reqwest::Client::new();
+        let request = client.get(url).build().unwrap();
+        recorder
+            .execute(&client, call, request, &[("model", "default".into())])
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn streams_binary_bodies_and_records_only_the_head() {
+        let body: Vec<u8> = (0..200_000u32).map(|i| i as u8).collect();
+        let url = serve_once("audio/wav", body.clone()).await;
+        let recorder = recorder("binary");
+
+        let response = execute(&recorder, &url, "convert").await;
+        // 本文を読み終えるまで記録は書かない
+        assert!(!recorder.dir.join("00000-convert.json").exists());
+        assert_eq!(response.bytes().await.unwrap(), body);
+
+        let record = read_record(&recorder, "convert");
+        assert_eq!(record["response"]["body_len"], body.len());
+        assert_eq!(
+            record["response"]["body_head"],
+            hex(&body[..BINARY_HEAD_BYTES])
+        );
+        assert!(record["response"].get("body").is_none());
+        assert!(record.get("error").is_none());
+        assert_eq!(record["request"]["fields"][0][0], "model");
+    }
+
+    #[tokio::test]
+    async fn records_binary_bodies_dropped_before_the_end() {
+        let url = serve_once("application/octet-stream", vec![7; 100_000]).await;
+        let recorder = recorder("dropped");
+
+        drop(execute(&recorder, &url, "convert").await);
+
+        let record = read_record(&recorder, "convert");
+        assert!(record["response"]["body_len"].as_u64().unwrap() < 100_000);
+        assert!(record["error"].as_str().unwrap().contains("破棄"));
+    }
+
+    #[tokio::test]
+    async fn keeps_text_bodies() {
+        let url = serve_once("application/json", br#"{"status":"ok"}"#.to_vec()).await;
+        let recorder = recorder("text");
+
+        let response = execute(&recorder, &url, "status").await;
+        assert_eq!(response.text().await.unwrap(), r#"{"status":"ok"}"#);
+
+        let record = read_record(&recorder, "status");
+        assert_eq!(record["response"]["body"], r#"{"status":"ok"}"#);
+        assert_eq!(record["response"]["body_len"], 15);
+    }
+}
@@ -0,0 +1,485 @@
+use anyhow::{Context, Result};
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{
+    BufferSize, Device, Stream, StreamConfig, SupportedBufferSize, SupportedStreamConfigRange,
+};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use tracing::{info, warn};
+
+use crate::backend::{AudioStream, InputCallback, InputDevice, OutputCallback, OutputDevice};
+
+/// `--audio-host` で選択されたホストAPI
+static SELECTED_HOST: OnceLock<cpal::HostId> = OnceLock::new();
+
+/// このビルドで利用可能なホストAPI名（WASAPI, ALSA, JACK など）
+pub fn available_hosts() -> Vec<&'static str> {
+    cpal::available_hosts()
+        .into_iter()
+        .map(|id| id.name())
+        .collect()
+}
+
+/// 使用するホストAPIを名前で選択（大文字小文字は区別しない）
+///
+/// 以降に開くデバイスはすべてこのホストから探します。
+pub fn select_host(name: &str) -> Result<()> {
+    let id = cpal::available_hosts()
+        .into_iter()
+        .find(|id| id.name().eq_ignore_ascii_case(name))
+        .with_context(|| {
+            format!(
+                "ホストAPIが見つかりません: {}（利用可能: {}）",
+                name,
+                available_hosts().join(", ")
+            )
+        })?;
+
+    SELECTED_HOST
+        .set(id)
+        .map_err(|_| anyhow::anyhow!("ホストAPIは既に選択されています"))?;
+    info!("ホストAPI: {}", id.name());
+
+    Ok(())
+}
+
+/// 選択中のホスト（未選択ならcpalのデフォルト）
+pub(crate) fn host() -> Result<cpal::Host> {
+    match SELECTED_HOST.get() {
+        Some(&id) => {
+            cpal::host_from_id(id).with_context(|| format!("ホストAPIを開けません: {}", id.name()))
+        }
+        None => Ok(cpal::default_host()),
+    }
+}
+
+/// 音声入力マネージャー
+pub struct AudioInput {
+    device: Device,
+    config: StreamConfig,
+    /// 要求するコールバックあたりのフレーム数（Noneならデバイス既定）
+    buffer_frames: Option<u32>,
+    /// ストリームでエラーが発生したか（デバイスの再構成が必要）
+    stream_error: Arc<AtomicBool>,
+    /// デバイスが最後に報告した遅延（マイクロ秒、0なら不明）
+    latency_us: Arc<AtomicU64>,
+}
+
+impl AudioInput {
+    /// デフォルトの入力デバイスで初期化
+    pub fn new() -> Result<Self> {
+        Self::open(None)
+    }
+
+    /// 名前で入力デバイスを指定して初期化（Noneならデフォルト）
+    pub fn open(name: Option<&str>) -> Result<Self> {
+        let host = host()?;
+        let device = match name {
+            Some(name) => find_device(host.input_devices()?, name)
+                .with_context(|| format!("入力デバイスが見つかりません: {}", name))?,
+            None => host
+                .default_input_device()
+                .context("入力デバイスが見つかりません")?,
+        };
+
+        let config = device
+            .default_input_config()
+            .context("入力デバイスの設定取得エラー")?
+            .into();
+
+        info!("入力デバイス: {}", device.name()?);
+
+        Ok(Self {
+            device,
+            config,
+            buffer_frames: None,
+            stream_error: Arc::new(AtomicBool::new(false)),
+            latency_us: Arc::new(AtomicU64::new(0)),
+        })
+    }
+
+    /// 音声ストリームを開始
+    pub fn start_stream<F>(&self, mut callback: F) -> Result<Stream>
+    where
+        F: FnMut(&[f32]) + Send + 'static,
+    {
+        let channels = self.config.channels.max(1) as usize;
+        let mut reported = false;
+        let latency_us = self.latency_us.clone();
+        let stream = self.device.build_input_stream(
+            &self.config,
+            move |data: &[f32], info: &cpal::InputCallbackInfo| {
+                let timestamp = info.timestamp();
+                if let Some(latency) = timestamp.callback.duration_since(&timestamp.capture) {
+                    latency_us.store(latency.as_micros() as u64, Ordering::Relaxed);
+                }
+                if !reported {
+                    reported = true;
+                    info!(
+                        "入力バッファ: {} フレーム/コールバック",
+                        data.len() / channels
+                    );
+                }
+                callback(data);
+            },
+            {
+                let stream_error = self.stream_error.clone();
+                move |err| {
+                    warn!("音声入力エラー: {}", err);
+                    stream_error.store(true, Ordering::Relaxed);
+                }
+            },
+            None,
+        )?;
+
+        stream.play()?;
+        info!("音声入力ストリーム開始");
+
+        Ok(stream)
+    }
+
+    /// ストリームの作り直しが必要か（エラー発生、またはOSがレート・チャンネル数を変更した）
+    pub fn needs_rebuild(&self) -> bool {
+        let changed = self.device.default_input_config().is_ok_and(|c| {
+            c.sample_rate() != self.config.sample_rate || c.channels() != self.config.channels
+        });
+        self.stream_error.swap(false, Ordering::Relaxed) || changed
+    }
+
+    /// デバイスの現在の設定を読み直す（ストリームは呼び出し側で作り直す）
+    pub fn refresh(&mut self) -> Result<()> {
+        self.config = self
+            .device
+            .default_input_config()
+            .context("入力デバイスの設定取得エラー")?
+            .into();
+        if let Some(frames) = self.buffer_frames {
+            self.request_buffer_frames(frames);
+        }
+        self.stream_error.store(false, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// コールバックあたりのフレーム数を要求する（デバイスの対応範囲に丸める）
+    ///
+    /// 次に開始するストリームから有効になります。
+    pub fn request_buffer_frames(&mut self, frames: u32) {
+        self.buffer_frames = Some(frames);
+        self.config.buffer_size = negotiate_buffer_size(
+            self.device.supported_input_configs().ok(),
+            &self.config,
+            frames,
+            "入力",
+        );
+    }
+
+    /// デバイス名
+    pub fn name(&self) -> String {
+        self.device.name().unwrap_or_default()
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.config.sample_rate.0
+    }
+
+    pub fn channels(&self) -> u16 {
+        self.config.channels
+    }
+
+    /// デバイスが最後に報告した遅延（ストリームを開始するまでは分からない）
+    pub fn reported_latency(&self) -> Option<Duration> {
+        match self.latency_us.load(Ordering::Relaxed) {
+            0 => None,
+            us => Some(Duration::from_micros(us)),
+        }
+    }
+}
+
+impl InputDevice for AudioInput {
+    fn start_stream(&self, callback: InputCallback) -> Result<AudioStream> {
+        Ok(AudioStream::new(AudioInput::start_stream(self, callback)?))
+    }
+
+    fn needs_rebuild(&self) -> bool {
+        AudioInput::needs_rebuild(self)
+    }
+
+    fn refresh(&mut self) -> Result<()> {
+        AudioInput::refresh(self)
+    }
+
+    fn request_buffer_frames(&mut self, frames: u32) {
+        AudioInput::request_buffer_frames(self, frames)
+    }
+
+    fn name(&self) -> String {
+        AudioInput::name(self)
+    }
+
+    fn sample_rate(&self) -> u32 {
+        AudioInput::sample_rate(self)
+    }
+
+    fn channels(&self) -> u16 {
+        AudioInput::channels(self)
+    }
+
+    fn reported_latency(&self) -> Option<Duration> {
+        AudioInput::reported_latency(self)
+    }
+}
+
+/// 音声出力マネージャー
+pub struct AudioOutput {
+    device: Device,
+    config: StreamConfig,
+    /// 要求するコールバックあたりのフレーム数（Noneならデバイス既定）
+    buffer_frames: Option<u32>,
+    /// ストリームでエラーが発生したか（デバイスの再構成が必要）
+    stream_error: Arc<AtomicBool>,
+    /// デバイスが最後に報告した遅延（マイクロ秒、0なら不明）
+    latency_us: Arc<AtomicU64>,
+}
+
+impl AudioOutput {
+    /// デフォルトの出力デバイスで初期化
+    pub fn new() -> Result<Self> {
+        Self::open(None)
+    }
+
+    /// 名前で出力デバイスを指定して初期化（Noneならデフォルト）
+    pub fn open(name: Option<&str>) -> Result<Self> {
+        let host = host()?;
+        let device = match name {
+            Some(name) => find_device(host.output_devices()?, name)
+                .with_context(|| format!("出力デバイスが見つかりません: {}", name))?,
+            None => host
+                .default_output_device()
+                .context("出力デバイスが見つかりません")?,
+        };
+
+        let config = device
+            .default_output_config()
+            .context("出力デバイスの設定取得エラー")?
+            .into();
+
+        info!("出力デバイス: {}", device.name()?);
+
+        Ok(Self {
+            device,
+            config,
+            buffer_frames: None,
+            stream_error: Arc::new(AtomicBool::new(false)),
+            latency_us: Arc::new(AtomicU64::new(0)),
+        })
+    }
+
+    /// 音声ストリームを開始
+    pub fn start_stream<F>(&self, mut callback: F) -> Result<Stream>
+    where
+        F: FnMut(&mut [f32]) + Send + 'static,
+    {
+        let channels = self.config.channels.max(1) as usize;
+        let mut reported = false;
+        let latency_us = self.latency_us.clone();
+        let stream = self.device.build_output_stream(
+            &self.config,
+            move |data: &mut [f32], info: &cpal::OutputCallbackInfo| {
+                let timestamp = info.timestamp();
+                if let Some(latency) = timestamp.playback.duration_since(&timestamp.callback) {
+                    latency_us.store(latency.as_micros() as u64, Ordering::Relaxed);
+                }
+                if !reported {
+                    reported = true;
+                    info!(
+                        "出力バッファ: {} フレーム/コールバック",
+                        data.len() / channels
+                    );
+                }
+                callback(data);
+            },
+            {
+                let stream_error = self.stream_error.clone();
+                move |err| {
+                    warn!("音声出力エラー: {}", err);
+                    stream_error.store(true, Ordering::Relaxed);
+                }
+            },
+            None,
+        )?;
+
+        stream.play()?;
+        info!("音声出力ストリーム開始");
+
+        Ok(stream)
+    }
+
+    /// ストリームの作り直しが必要か（エラー発生、またはOSがレート・チャンネル数を変更した）
+    pub fn needs_rebuild(&self) -> bool {
+        let changed = self.device.default_output_config().is_ok_and(|c| {
+            c.sample_rate() != self.config.sample_rate || c.channels() != self.config.channels
+        });
+        self.stream_error.swap(false, Ordering::Relaxed) || changed
+    }
+
+    /// デバイスの現在の設定を読み直す（ストリームは呼び出し側で作り直す）
+    pub fn refresh(&mut self) -> Result<()> {
+        self.config = self
+            .device
+            .default_output_config()
+            .context("出力デバイスの設定取得エラー")?
+            .into();
+        if let Some(frames) = self.buffer_frames {
+            self.request_buffer_frames(frames);
+        }
+        self.stream_error.store(false, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// コールバックあたりのフレーム数を要求する（デバイスの対応範囲に丸める）
+    ///
+    /// 次に開始するストリームから有効になります。
+    pub fn request_buffer_frames(&mut self, frames: u32) {
+        self.buffer_frames = Some(frames);
+        self.config.buffer_size = negotiate_buffer_size(
+            self.device.supported_output_configs().ok(),
+            &self.config,
+            frames,
+            "出力",
+        );
+    }
+
+    /// デバイス名
+    pub fn name(&self) -> String {
+        self.device.name().unwrap_or_default()
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.config.sample_rate.0
+    }
+
+    pub fn channels(&self) -> u16 {
+        self.config.channels
+    }
+
+    /// デバイスが最後に報告した遅延（ストリームを開始するまでは分からない）
+    pub fn reported_latency(&self) -> Option<Duration> {
+        match self.latency_us.load(Ordering::Relaxed) {
+            0 => None,
+            us => Some(Duration::from_micros(us)),
+        }
+    }
+}
+
+impl OutputDevice for AudioOutput {
+    fn start_stream(&self, callback: OutputCallback) -> Result<AudioStream> {
+        Ok(AudioStream::new(AudioOutput::start_stream(self, callback)?))
+    }
+
+    fn needs_rebuild(&self) -> bool {
+        AudioOutput::needs_rebuild(self)
+    }
+
+    fn refresh(&mut self) -> Result<()> {
+        AudioOutput::refresh(self)
+    }
+
+    fn request_buffer_frames(&mut self, frames: u32) {
+        AudioOutput::request_buffer_frames(self, frames)
+    }
+
+    fn name(&self) -> String {
+        AudioOutput::name(self)
+    }
+
+    fn sample_rate(&self) -> u32 {
+        AudioOutput::sample_rate(self)
+    }
+
+    fn channels(&self) -> u16 {
+        AudioOutput::channels(self)
+    }
+
+    fn reported_latency(&self) -> Option<Duration> {
+        AudioOutput::reported_latency(self)
+    }
+}
+
+/// 要求フレーム数をデバイスの対応範囲に丸めて `BufferSize` を決める
+fn negotiate_buffer_size(
+    ranges: Option<impl Iterator<Item = SupportedStreamConfigRange>>,
+    config: &StreamConfig,
+    frames: u32,
+    direction: &str,
+) -> BufferSize {
+    let matching: Vec<SupportedStreamConfigRange> = ranges
+        .into_iter()
+        .flatten()
+        .filter(|r| {
+            r.channels() == config.channels
+                && r.min_sample_rate() <= config.sample_rate
+                && config.sample_rate <= r.max_sample_rate()
+        })
+        .collect();
+    let range = matching
+        .iter()
+        .find(|r| r.sample_format() == cpal::SampleFormat::F32)
+        .or(matching.first())
+        .map(|r| *r.buffer_size());
+
+    match range {
+        Some(SupportedBufferSize::Range { min, max }) => {
+            let granted = frames.clamp(min, max);
+            info!(
+                "{}バッファ要求: {} フレーム（対応範囲 {}〜{}）",
+                direction, granted, min, max
+            );
+            BufferSize::Fixed(granted)
+        }
+        _ => {
+            info!(
+                "{}バッファの対応範囲が不明なため、デバイス既定値を使用します",
+                direction
+            );
+            BufferSize::Default
+        }
+    }
+}
+
+/// 名前でデバイスを探す（完全一致を優先し、なければ部分一致）
+fn find_device(devices: impl Iterator<Item = Device>, name: &str) -> Option<Device> {
+    let devices: Vec<Device> = devices.collect();
+    let position = devices
+        .iter()
+        .position(|d| d.name().is_ok_and(|n| n == name))
+        .or_else(|| {
+            devices
+                .iter()
+                .position(|d| d.name().is_ok_and(|n| n.contains(name)))
+        })?;
+    devices.into_iter().nth(position)
+}
+
+/// 利用可能なデバイス一覧を表示
+pub fn list_devices() -> Result<()> {
+    let host = host()?;
+
+    println!("ホストAPI:");
+    for name in available_hosts() {
+        let mark = if name == host.id().name() { "*" } else { " " };
+        println!("  {} {}", mark, name);
+    }
+
+    println!("\n入力デバイス（{}）:", host.id().name());
+    for device in host.input_devices()? {
+        println!("  - {}", device.name()?);
+    }
+
+    println!("\n出力デバイス（{}）:", host.id().name());
+    for device in host.output_devices()? {
+        println!("  - {}", device.name()?);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,252 @@
+use anyhow::Result;
+use std::str::FromStr;
+
+use crate::client::SpeakerTurn;
+use crate::dsp::analysis::{self, DEFAULT_VAD_THRESHOLD_DB};
+use crate::resample::{resample, ResampleQuality};
+
+/// 話者の数の既定の見込み（`--speakers`）
+pub const DEFAULT_SPEAKERS: usize = 2;
+
+/// ローカルの推定で話者を判定する単位（ミリ秒、話者の切り替わりはこの精度で分かる）
+const WINDOW_MS: usize = 250;
+
+/// 発話を判定するフレーム（ミリ秒）
+const FRAME_MS: usize = 10;
+
+/// ピッチを推定するフレーム（ミリ秒、最低のピッチの2周期より長くする）
+const PITCH_FRAME_MS: usize = 40;
+
+/// ピッチを推定する前に下げるレート（声の基本周波数には十分で、計算が軽い）
+const PITCH_RATE: u32 = 8000;
+
+/// 単位の中でこの割合以上が発話なら、発話の単位とみなす
+const VOICED_RATIO: f32 = 0.3;
+
+/// 話者の区間と区間でない部分のつなぎ目のクロスフェード（ミリ秒）
+const FADE_MS: f64 = 20.0;
+
+/// 話者の区別の方法（`--diarization`）
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DiarizationSource {
+    /// サーバーが対応していればサーバー、そうでなければローカル
+    #[default]
+    Auto,
+    /// サーバーで区別する（`/diarize`）
+    Server,
+    /// 音量とピッチからローカルで推定する
+    Local,
+}
+
+impl FromStr for DiarizationSource {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "auto" => Ok(Self::Auto),
+            "server" => Ok(Self::Server),
+            "local" => Ok(Self::Local),
+            _ => anyhow::bail!("不明な話者の区別の方法: {}（auto, server, local）", s),
+        }
+    }
+}
+
+impl std::fmt::Display for DiarizationSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Auto => "auto",
+            Self::Server => "server",
+            Self::Local => "local",
+        })
+    }
+}
+
+/// 音量とピッチから話者を推定する（ローカル）
+///
+/// 音声を0.25秒の単位に分け、発話している単位のピッチの中央値を `speakers` 個の
+/// グループに分けます。話者はピッチの低い順に `speaker-1`, `speaker-2`, ... です。
+/// ピッチの分からない単位は前後の話者に含めます。声の高さが近い話者は区別できません。
+pub fn local_turns(
+    samples: &[f32],
+    sample_rate: u32,
+    channels: u16,
+    speakers: usize,
+) -> Result<Vec<SpeakerTurn>> {
+    let mono = analysis::downmix(samples, channels);
+    let low = resample(&mono, sample_rate, PITCH_RATE, 1, ResampleQuality::Fast)?;
+
+    // 単位ごとに、発話しているか・ピッチ（log2 Hz）を調べる
+    let window = PITCH_RATE as usize * WINDOW_MS / 1000;
+    let frame = PITCH_RATE as usize * FRAME_MS / 1000;
+    let pitch_frame = PITCH_RATE as usize * PITCH_FRAME_MS / 1000;
+    let units: Vec<Option<Option<f32>>> = low
+        .chunks(window)
+        .map(|unit| {
+            let voiced = unit
+                .chunks(frame)
+                .filter(|f| analysis::rms_db(f) >= DEFAULT_VAD_THRESHOLD_DB)
+                .count();
+            if (voiced as f32) < unit.len().div_ceil(frame) as f32 * VOICED_RATIO {
+                return None;
+            }
+            let mut pitches: Vec<f32> = unit
+                .chunks_exact(pitch_frame)
+                .filter(|f| analysis::rms_db(f) >= DEFAULT_VAD_THRESHOLD_DB)
+                .filter_map(|f| analysis::estimate_pitch(f, PITCH_RATE))
+                .collect();
+            pitches.sort_by(f32::total_cmp);
+            Some(pitches.get(pitches.len() / 2).map(|hz| hz.log2()))
+        })
+        .collect();
+
+    let pitched: Vec<f32> = units.iter().flatten().flatten().copied().collect();
+    let centers = cluster(&pitched, speakers.max(1));
+
+    // 単位に話者を割り当てる（ピッチの分からない発話は直前の話者、なければ直後の話者）
+    let mut labels: Vec<Option<usize>> = units
+        .iter()
+        .map(|unit| match unit {
+            Some(Some(pitch)) => Some(nearest(&centers, *pitch)),
+            _ => None,
+        })
+        .collect();
+    let mut last = None;
+    for (label, unit) in labels.iter_mut().zip(&units) {
+        if unit.is_some() && label.is_none() {
+            *label = last;
+        }
+        last = label.or(last);
+    }
+    let mut next = None;
+    for (label, unit) in labels.iter_mut().zip(&units).rev() {
+        if unit.is_some() && label.is_none() {
+            *label = next;
+        }
+        next = label.or(next);
+    }
+
+    // 同じ話者の続く単位をまとめる
+    let seconds = WINDOW_MS as f64 / 1000.0;
+    let total = low.len() as f64 / PITCH_RATE as f64;
+    let mut turns: Vec<SpeakerTurn> = Vec::new();
+    for (i, label) in labels.iter().enumerate() {
+        let Some(label) = label else { continue };
+        let speaker = format!("speaker-{}", label + 1);
+        let start = i as f64 * seconds;
+        let end = ((i + 1) as f64 * seconds).min(total);
+        match turns.last_mut() {
+            Some(turn) if turn.speaker == speaker && (turn.end - start).abs() < 1e-9 => {
+                turn.end = end;
+            }
+            _ => turns.push(SpeakerTurn {
+                speaker,
+                start,
+                end,
+            }),
+        }
+    }
+    Ok(turns)
+}
+
+/// 1次元のk-means（中心を小さい順に返す）
+fn cluster(values: &[f32], k: usize) -> Vec<f32> {
+    if values.is_empty() {
+        return vec![0.0];
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f32::total_cmp);
+    let k = k.min(sorted.len());
+    // 分位点から始める
+    let mut centers: Vec<f32> = (0..k)
+        .map(|i| sorted[(2 * i + 1) * sorted.len() / (2 * k)])
+        .collect();
+    for _ in 0..50 {
+        let mut sums = vec![(0.0f32, 0usize); k];
+        for &value in &sorted {
+            let sum = &mut sums[nearest(&centers, value)];
+            sum.0 += value;
+            sum.1 += 1;
+        }
+        let moved: Vec<f32> = sums
+            .iter()
+            .zip(&centers)
+            .map(|(&(sum, n), &center)| if n == 0 { center } else { sum / n as f32 })
+            .collect();
+        if moved == centers {
+            break;
+        }
+        centers = moved;
+    }
+    centers.sort_by(f32::total_cmp);
+    centers
+}
+
+fn nearest(centers: &[f32], value: f32) -> usize {
+    centers
+        .iter()
+        .enumerate()
+        .min_by(|a, b| (a.1 - value).abs().total_cmp(&(b.1 - value).abs()))
+        .map_or(0, |(i, _)| i)
+}
+
+/// 話者ごとの話した長さの合計（秒）と区間の数（話者名の順）
+pub fn speaker_totals(turns: &[SpeakerTurn]) -> Vec<(String, f64, usize)> {
+    let mut totals: std::collections::BTreeMap<&str, (f64, usize)> = Default::default();
+    for turn in turns {
+        let total = totals.entry(&turn.speaker).or_default();
+        total.0 += turn.end - turn.start;
+        total.1 += 1;
+    }
+    totals
+        .into_iter()
+        .map(|(speaker, (seconds, count))| (speaker.to_string(), seconds, count))
+        .collect()
+}
+
+/// `spans`（秒）の中だけ変換後の音声を使い、それ以外は元の音声のままにする
+///
+/// つなぎ目は区間の外側で短くクロスフェードします。変換後の音声は元の音声と
+/// 同じレート・チャンネル数に揃えてから渡してください。
+pub fn splice(
+    original: &[f32],
+    converted: &[f32],
+    sample_rate: u32,
+    channels: u16,
+    spans: &[(f64, f64)],
+) -> Vec<f32> {
+    let ch = channels.max(1) as usize;
+    let frames = original.len() / ch;
+    let fade = (FADE_MS / 1000.0 * sample_rate as f64) as usize;
+    let frame_at = |seconds: f64| ((seconds.max(0.0) * sample_rate as f64) as usize).min(frames);
+
+    // フレームごとの変換後の音声の割合
+    let mut weights = vec![0.0f32; frames];
+    for &(start, end) in spans {
+        let (start, end) = (frame_at(start), frame_at(end));
+        for weight in &mut weights[start..end] {
+            *weight = 1.0;
+        }
+        for i in 1..=fade {
+            let w = 1.0 - i as f32 / (fade + 1) as f32;
+            if let Some(before) = start.checked_sub(i) {
+                weights[before] = weights[before].max(w);
+            }
+            if end + i - 1 < frames {
+                weights[end + i - 1] = weights[end + i - 1].max(w);
+            }
+        }
+    }
+
+    let mut output = original.to_vec();
+    for (i, &w) in weights.iter().enumerate() {
+        if w == 0.0 {
+            continue;
+        }
+        for c in 0..ch {
+            let index = i * ch + c;
+            let wet = converted.get(index).copied().unwrap_or(0.0);
+            output[index] = output[index] * (1.0 - w) + wet * w;
+        }
+    }
+    output
+}
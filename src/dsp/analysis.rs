@@ -0,0 +1,165 @@
+/// 無音とみなす下限（dBFS）
+pub const SILENCE_DB: f32 = -100.0;
+
+/// 発話とみなすRMSレベルの既定しきい値（dBFS）
+pub const DEFAULT_VAD_THRESHOLD_DB: f32 = -45.0;
+
+/// ピッチ推定の探索範囲（Hz）
+const PITCH_MIN_HZ: f32 = 70.0;
+const PITCH_MAX_HZ: f32 = 500.0;
+
+/// 自己相関のピークがこの値未満なら無声とみなす
+const PITCH_CLARITY: f32 = 0.5;
+
+/// 振幅をdBFSに変換
+pub fn to_db(amplitude: f32) -> f32 {
+    if amplitude <= 0.0 {
+        SILENCE_DB
+    } else {
+        (20.0 * amplitude.log10()).max(SILENCE_DB)
+    }
+}
+
+/// dBを振幅（ゲイン）に変換
+pub fn from_db(db: f32) -> f32 {
+    10f32.powf(db / 20.0)
+}
+
+/// RMSレベル（dBFS）
+pub fn rms_db(samples: &[f32]) -> f32 {
+    if samples.is_empty() {
+        return SILENCE_DB;
+    }
+    let power = super::simd::sum_squares(samples) / samples.len() as f32;
+    to_db(power.sqrt())
+}
+
+/// ピークレベル（dBFS）
+pub fn peak_db(samples: &[f32]) -> f32 {
+    to_db(super::simd::peak(samples))
+}
+
+/// 音声の区間のレベル（メーター表示用）
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Level {
+    /// RMSレベル（dBFS）
+    pub rms_db: f32,
+    /// ピークレベル（dBFS）
+    pub peak_db: f32,
+}
+
+impl Level {
+    pub fn measure(samples: &[f32]) -> Self {
+        Self {
+            rms_db: rms_db(samples),
+            peak_db: peak_db(samples),
+        }
+    }
+}
+
+impl std::fmt::Display for Level {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "RMS {:.1} dB / ピーク {:.1} dB",
+            self.rms_db, self.peak_db
+        )
+    }
+}
+
+impl Default for Level {
+    fn default() -> Self {
+        Self {
+            rms_db: SILENCE_DB,
+            peak_db: SILENCE_DB,
+        }
+    }
+}
+
+/// 一定の間のレベルを集計する（定期的なレベル表示用）
+#[derive(Debug, Default)]
+pub struct LevelMeter {
+    sum_squares: f64,
+    samples: u64,
+    peak: f32,
+}
+
+impl LevelMeter {
+    pub fn observe(&mut self, samples: &[f32]) {
+        self.sum_squares += super::simd::sum_squares(samples) as f64;
+        self.samples += samples.len() as u64;
+        self.peak = self.peak.max(super::simd::peak(samples));
+    }
+
+    /// 前回から集計したレベルを返してリセットする（音声が来ていなければNone）
+    pub fn take(&mut self) -> Option<Level> {
+        let meter = std::mem::take(self);
+        (meter.samples > 0).then(|| Level {
+            rms_db: to_db((meter.sum_squares / meter.samples as f64).sqrt() as f32),
+            peak_db: to_db(meter.peak),
+        })
+    }
+}
+
+/// インターリーブ音声をモノラルに平均化
+pub fn downmix(samples: &[f32], channels: u16) -> Vec<f32> {
+    if channels <= 1 {
+        return samples.to_vec();
+    }
+    samples
+        .chunks_exact(channels as usize)
+        .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+        .collect()
+}
+
+/// `target` フレームに最も近いゼロクロス位置（フレーム）を `window` フレーム以内で探す
+///
+/// 返す位置 `i` は、フレーム `i - 1` と `i` の間で符号が変わる境界です。
+pub fn nearest_zero_crossing(
+    samples: &[f32],
+    channels: u16,
+    target: usize,
+    window: usize,
+) -> Option<usize> {
+    let mono = downmix(samples, channels);
+    let crosses = |i: usize| i > 0 && i < mono.len() && (mono[i - 1] >= 0.0) != (mono[i] >= 0.0);
+
+    (0..=window).find_map(|offset| {
+        [target.checked_sub(offset), Some(target + offset)]
+            .into_iter()
+            .flatten()
+            .find(|&i| crosses(i))
+    })
+}
+
+/// 正規化自己相関による基本周波数推定（モノラル入力）
+///
+/// 無声・無音と判断した場合は `None` を返します。
+pub fn estimate_pitch(mono: &[f32], sample_rate: u32) -> Option<f32> {
+    let min_lag = (sample_rate as f32 / PITCH_MAX_HZ) as usize;
+    let max_lag = (sample_rate as f32 / PITCH_MIN_HZ) as usize;
+    if mono.len() < max_lag * 2 {
+        return None;
+    }
+
+    let energy: f32 = mono.iter().map(|s| s * s).sum();
+    if energy <= f32::EPSILON {
+        return None;
+    }
+
+    let (best_lag, best_corr) = (min_lag..=max_lag)
+        .map(|lag| {
+            let corr: f32 = mono[..mono.len() - lag]
+                .iter()
+                .zip(&mono[lag..])
+                .map(|(a, b)| a * b)
+                .sum();
+            (lag, corr / energy)
+        })
+        .fold(
+            (0, 0.0f32),
+            |best, cur| if cur.1 > best.1 { cur } else { best },
+        );
+
+    (best_corr >= PITCH_CLARITY).then(|| sample_rate as f32 / best_lag as f32)
+}
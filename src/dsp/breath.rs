@@ -0,0 +1,250 @@
+use anyhow::{Context, Result};
+use std::path::Path;
+use std::sync::Arc;
+use tracing::{debug, info, warn};
+
+use super::analysis;
+use super::phrase::PhraseDetector;
+use super::DspStage;
+use crate::chaos::XorShift;
+use crate::resample::{self, ResampleQuality};
+use crate::wav;
+
+/// 句の切れ目ごとに息を入れる確率（毎回だと不自然なため）
+const BREATH_PROBABILITY: f64 = 0.7;
+
+/// 息の音量の揺らぎ（±dB）
+const LEVEL_VARIATION_DB: f32 = 4.0;
+
+/// 生成する息の種類の数
+const SYNTHESIZED_BREATHS: usize = 6;
+
+/// 生成する息の長さの範囲（ミリ秒）
+const SYNTHESIZED_MS: std::ops::Range<f64> = 250.0..450.0;
+
+/// 息の音源（モノラル、ピーク1.0に正規化）
+#[derive(Clone)]
+enum BreathSource {
+    /// 帯域を絞ったノイズから生成する
+    Synthesized,
+    /// 録音した息のWAV
+    Samples(Arc<Vec<(Vec<f32>, u32)>>),
+}
+
+/// 句の切れ目に小さな息の音を差し込むステージ
+///
+/// 変換後の音声の音量で発話を判定し、ある程度の長さの発話の後に無音が続いたところへ、
+/// ランダムに選んだ息を、ランダムな音量で混ぜます。
+#[derive(Clone)]
+pub struct BreathInserter {
+    /// 息のピークレベル（0.0-1.0）
+    level: f32,
+    source: BreathSource,
+    rng: XorShift,
+    format: Option<(u32, u16)>,
+    /// `prepare` で出力のレートに揃えた息
+    breaths: Vec<Vec<f32>>,
+    /// 再生中の息（番号・位置・音量）
+    playing: Option<(usize, usize, f32)>,
+    phrases: PhraseDetector,
+}
+
+impl BreathInserter {
+    /// `dir` を指定するとその中のWAVを息として使う（なければ生成する）
+    pub fn new(level: f32, dir: Option<&Path>) -> Result<Self> {
+        anyhow::ensure!(
+            (0.0..=1.0).contains(&level),
+            "息のレベルは0.0〜1.0で指定してください: {}",
+            level
+        );
+
+        let source = match dir {
+            Some(dir) => BreathSource::Samples(Arc::new(load_breaths(dir)?)),
+            None => BreathSource::Synthesized,
+        };
+
+        Ok(Self {
+            level,
+            source,
+            rng: XorShift::from_time(),
+            format: None,
+            breaths: Vec::new(),
+            playing: None,
+            phrases: PhraseDetector::default(),
+        })
+    }
+
+    /// 乱数の種を固定する（同じ種なら同じ位置に同じ息が入る。マニフェストからの再現用）
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = XorShift::new(seed);
+        self
+    }
+
+    pub fn level(&self) -> f32 {
+        self.level
+    }
+
+    /// ファイル全体に息を入れる
+    pub fn insert_all(&mut self, samples: &mut [f32], sample_rate: u32, channels: u16) {
+        self.prepare(sample_rate, channels);
+        self.process(samples);
+    }
+
+    /// ランダムに息を選んで再生を始める
+    fn start_breath(&mut self) {
+        if self.playing.is_some()
+            || self.breaths.is_empty()
+            || self.rng.next_f64() >= BREATH_PROBABILITY
+        {
+            return;
+        }
+        let index = (self.rng.next_f64() * self.breaths.len() as f64) as usize;
+        let db = (self.rng.next_f64() as f32 * 2.0 - 1.0) * LEVEL_VARIATION_DB;
+        let gain = self.level * analysis::from_db(db);
+        debug!("息を挿入: {}番（{:.1}dB）", index, db);
+        self.playing = Some((index.min(self.breaths.len() - 1), 0, gain));
+    }
+
+    /// 再生中の息の次のサンプル
+    fn next_breath_sample(&mut self) -> f32 {
+        let Some((index, position, gain)) = self.playing.as_mut() else {
+            return 0.0;
+        };
+        let breath = &self.breaths[*index];
+        let sample = breath[*position] * *gain;
+        *position += 1;
+        if *position >= breath.len() {
+            self.playing = None;
+        }
+        sample
+    }
+}
+
+/// ディレクトリ内のWAVを息として読み込む（ピーク1.0に正規化）
+fn load_breaths(dir: &Path) -> Result<Vec<(Vec<f32>, u32)>> {
+    let mut paths: Vec<_> = std::fs::read_dir(dir)
+        .with_context(|| format!("息のディレクトリを読み込めません: {}", dir.display()))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|ext| ext.eq_ignore_ascii_case("wav"))
+        })
+        .collect();
+    paths.sort();
+
+    let mut breaths = Vec::new();
+    for path in paths {
+        let (samples, spec) = wav::read_file(&path)?;
+        let mut mono = analysis::downmix(&samples, spec.channels);
+        let peak = mono.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+        if peak <= f32::EPSILON {
+            warn!("無音の息のファイルを飛ばします: {}", path.display());
+            continue;
+        }
+        mono.iter_mut().for_each(|s| *s /= peak);
+        breaths.push((mono, spec.sample_rate));
+    }
+    anyhow::ensure!(
+        !breaths.is_empty(),
+        "息のWAVファイルがありません: {}",
+        dir.display()
+    );
+    info!("息のサンプル: {}個（{}）", breaths.len(), dir.display());
+    Ok(breaths)
+}
+
+/// 帯域を絞ったノイズに膨らんでしぼむ包絡をかけて、息の音を作る
+fn synthesize_breath(rng: &mut XorShift, sample_rate: u32) -> Vec<f32> {
+    let ms = SYNTHESIZED_MS.start + rng.next_f64() * (SYNTHESIZED_MS.end - SYNTHESIZED_MS.start);
+    let len = (sample_rate as f64 * ms / 1000.0) as usize;
+    // 吸う音は立ち上がりが遅く、切れは早い
+    let attack = 0.55 + rng.next_f64() as f32 * 0.2;
+
+    let coefficient = |hz: f32| 1.0 - (-2.0 * std::f32::consts::PI * hz / sample_rate as f32).exp();
+    let (highpass, lowpass) = (
+        coefficient(600.0),
+        coefficient(2500.0 + rng.next_f64() as f32 * 1500.0),
+    );
+    let (mut low_state, mut band_state) = (0.0f32, 0.0f32);
+
+    let mut breath: Vec<f32> = (0..len)
+        .map(|i| {
+            let white = rng.next_f64() as f32 * 2.0 - 1.0;
+            low_state += (white - low_state) * highpass;
+            band_state += (white - low_state - band_state) * lowpass;
+
+            let t = i as f32 / len as f32;
+            let envelope = if t < attack {
+                (t / attack * std::f32::consts::FRAC_PI_2).sin()
+            } else {
+                ((1.0 - t) / (1.0 - attack) * std::f32::consts::FRAC_PI_2).sin()
+            };
+            band_state * envelope * envelope
+        })
+        .collect();
+
+    let peak = breath.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+    if peak > f32::EPSILON {
+        breath.iter_mut().for_each(|s| *s /= peak);
+    }
+    breath
+}
+
+impl DspStage for BreathInserter {
+    fn name(&self) -> &str {
+        "breath"
+    }
+
+    fn prepare(&mut self, sample_rate: u32, channels: u16) {
+        if self.format == Some((sample_rate, channels)) {
+            return;
+        }
+        self.format = Some((sample_rate, channels));
+        self.phrases.prepare(sample_rate);
+        self.playing = None;
+
+        self.breaths = match &self.source {
+            BreathSource::Synthesized => (0..SYNTHESIZED_BREATHS)
+                .map(|_| synthesize_breath(&mut self.rng, sample_rate))
+                .collect(),
+            BreathSource::Samples(samples) => samples
+                .iter()
+                .filter_map(|(breath, rate)| {
+                    resample::resample(breath, *rate, sample_rate, 1, ResampleQuality::Balanced)
+                        .map_err(|e| warn!("息のサンプルを変換できません: {}", e))
+                        .ok()
+                })
+                .filter(|breath| !breath.is_empty())
+                .collect(),
+        };
+    }
+
+    fn process(&mut self, samples: &mut [f32]) {
+        let Some((_, channels)) = self.format else {
+            return;
+        };
+        if self.level <= 0.0 {
+            return;
+        }
+
+        for frame in samples.chunks_mut(channels.max(1) as usize) {
+            // 判定は息を混ぜる前の音で行う
+            if self
+                .phrases
+                .push(frame.iter().sum::<f32>() / frame.len() as f32)
+            {
+                self.start_breath();
+            }
+
+            let breath = self.next_breath_sample();
+            for sample in frame {
+                *sample += breath;
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        self.playing = None;
+        self.phrases.reset();
+    }
+}
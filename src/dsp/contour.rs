@@ -0,0 +1,126 @@
+use tracing::debug;
+
+use super::phrase::{PhraseDetector, VAD_FRAME_MS};
+use super::pitch::PitchShifter;
+use super::DspStage;
+use crate::chaos::XorShift;
+
+/// 句の中でピッチが目標へ近づく時定数（秒）
+const DRIFT_SECONDS: f32 = 2.0;
+
+/// 句ごとにゆっくり変わるピッチのずれ
+///
+/// 句の切れ目ごとに、開始時のずれと句の中で向かう目標を `±cents` の範囲でランダムに選び直し、
+/// 句の中ではなめらかに動かします。長い独り言がずっと同じ移調に聞こえないようにするためのもので、
+/// 切り替えは無音の間に行います。出力は位相ボコーダーの分だけ遅れます。
+pub struct PitchContour {
+    /// ずれの幅（±セント）
+    cents: f32,
+    shifter: PitchShifter,
+    phrases: PhraseDetector,
+    rng: XorShift,
+    format: Option<(u32, u16)>,
+    /// 現在のずれと句の中で向かう目標（セント）
+    current: f32,
+    target: f32,
+    /// 10msごとに目標へ近づく割合
+    smoothing: f32,
+}
+
+impl PitchContour {
+    pub fn new(cents: f32) -> Self {
+        let mut contour = Self {
+            cents: cents.abs(),
+            shifter: PitchShifter::new(0.0),
+            phrases: PhraseDetector::default(),
+            rng: XorShift::from_time(),
+            format: None,
+            current: 0.0,
+            target: 0.0,
+            smoothing: VAD_FRAME_MS as f32 / 1000.0 / DRIFT_SECONDS,
+        };
+        contour.next_phrase();
+        contour
+    }
+
+    /// 乱数の種を固定する（同じ種なら同じ揺らぎになる。マニフェストからの再現用）
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = XorShift::new(seed);
+        self.next_phrase();
+        self
+    }
+
+    pub fn cents(&self) -> f32 {
+        self.cents
+    }
+
+    /// ファイル全体に適用する（遅れを取り除き、入力と同じ長さで返す）
+    pub fn apply_all(&mut self, samples: &[f32], sample_rate: u32, channels: u16) -> Vec<f32> {
+        self.prepare(sample_rate, channels);
+        self.reset();
+
+        let latency = self.shifter.latency_frames() * channels as usize;
+        let mut buffer = samples.to_vec();
+        buffer.resize(samples.len() + latency, 0.0);
+        self.process(&mut buffer);
+        buffer.split_off(latency)
+    }
+
+    /// 次の句のずれを選ぶ
+    fn next_phrase(&mut self) {
+        self.current = self.random_cents();
+        self.target = self.random_cents();
+        debug!(
+            "ピッチの揺らぎ: {:+.0} → {:+.0} cents",
+            self.current, self.target
+        );
+    }
+
+    fn random_cents(&mut self) -> f32 {
+        (self.rng.next_f64() as f32 * 2.0 - 1.0) * self.cents
+    }
+}
+
+impl DspStage for PitchContour {
+    fn name(&self) -> &str {
+        "pitch-contour"
+    }
+
+    fn prepare(&mut self, sample_rate: u32, channels: u16) {
+        if self.format == Some((sample_rate, channels)) {
+            return;
+        }
+        self.format = Some((sample_rate, channels));
+        self.shifter.prepare(sample_rate, channels);
+        self.phrases.prepare(sample_rate);
+    }
+
+    fn process(&mut self, samples: &mut [f32]) {
+        let Some((sample_rate, channels)) = self.format else {
+            return;
+        };
+        let channels = channels.max(1) as usize;
+        let block = (sample_rate * VAD_FRAME_MS / 1000).max(1) as usize * channels;
+
+        // 10msごとにずれを動かしてからずらす
+        for block in samples.chunks_mut(block) {
+            for frame in block.chunks(channels) {
+                if self
+                    .phrases
+                    .push(frame.iter().sum::<f32>() / frame.len() as f32)
+                {
+                    self.next_phrase();
+                }
+            }
+            self.current += (self.target - self.current) * self.smoothing;
+            self.shifter.set_semitones(self.current / 100.0);
+            self.shifter.process(block);
+        }
+    }
+
+    fn reset(&mut self) {
+        self.shifter.reset();
+        self.phrases.reset();
+        self.next_phrase();
+    }
+}
@@ -0,0 +1,91 @@
+/// 連続するチャンクの変換結果を重ねてつなぐ（オーバーラップ加算）
+///
+/// 各チャンクは前のチャンクの末尾と同じ区間の音声を先頭に含めて変換しておきます。
+/// 変換結果の末尾は次のチャンクが届くまで持っておき、次のチャンクの先頭と
+/// 重ねてクロスフェードするので、チャンクごとに変換した音声の継ぎ目が目立ちません。
+/// 持っておく分だけ出力が遅れます。
+#[derive(Debug, Clone, Default)]
+pub struct ChunkCrossfade {
+    /// 前のチャンクの末尾（次のチャンクの先頭と重ねる）
+    held: Vec<f32>,
+    channels: u16,
+}
+
+impl ChunkCrossfade {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 1チャンク分の変換結果をつなぎ、出力してよい音声を返す
+    ///
+    /// `overlap` は先頭のうち前のチャンクと同じ区間のフレーム数、`hold` は末尾のうち
+    /// 次のチャンクと重ねるために持っておくフレーム数です。前のチャンクの末尾が
+    /// なければ（最初のチャンクや変換の失敗の後）、重なる部分をフェードインして出します。
+    pub fn process(
+        &mut self,
+        samples: &[f32],
+        channels: u16,
+        overlap: usize,
+        hold: usize,
+    ) -> Vec<f32> {
+        if channels != self.channels {
+            self.held.clear();
+            self.channels = channels;
+        }
+        let ch = channels.max(1) as usize;
+        let frames = samples.len() / ch;
+        let overlap = overlap.min(frames);
+        let (head, body) = samples[..frames * ch].split_at(overlap * ch);
+
+        let mut output = Vec::with_capacity(samples.len() + self.held.len());
+        let held_frames = self.held.len() / ch;
+        if held_frames > 0 {
+            // 持っておいた末尾と先頭は、どちらも終わりが同じ時刻になる
+            let n = held_frames.min(overlap);
+            output.extend_from_slice(&self.held[..(held_frames - n) * ch]);
+            let held = &self.held[(held_frames - n) * ch..];
+            let head = &head[(overlap - n) * ch..];
+            for i in 0..n {
+                let w = fade_in_gain(i, n);
+                for c in 0..ch {
+                    let index = i * ch + c;
+                    output.push(held[index] * (1.0 - w) + head[index] * w);
+                }
+            }
+        } else {
+            for i in 0..overlap {
+                let w = fade_in_gain(i, overlap);
+                output.extend(head[i * ch..(i + 1) * ch].iter().map(|s| s * w));
+            }
+        }
+
+        let body_frames = body.len() / ch;
+        let hold = hold.min(body_frames);
+        let (ready, held) = body.split_at((body_frames - hold) * ch);
+        output.extend_from_slice(ready);
+        self.held = held.to_vec();
+        output
+    }
+
+    /// 持っている末尾をフェードアウトして出し切る（変換の失敗時や停止時）
+    pub fn flush(&mut self) -> Vec<f32> {
+        let ch = self.channels.max(1) as usize;
+        let mut held = std::mem::take(&mut self.held);
+        let frames = held.len() / ch;
+        for (i, frame) in held.chunks_exact_mut(ch).enumerate() {
+            let w = 1.0 - fade_in_gain(i, frames);
+            frame.iter_mut().for_each(|s| *s *= w);
+        }
+        held
+    }
+
+    /// 持っている末尾を捨てる（出力の形式が変わったとき）
+    pub fn reset(&mut self) {
+        self.held.clear();
+    }
+}
+
+/// `len` フレームのフェードインの `i` 番目の音量（二乗余弦。逆向きと足すと1になる）
+fn fade_in_gain(i: usize, len: usize) -> f32 {
+    0.5 - 0.5 * (std::f32::consts::PI * (i as f32 + 0.5) / len as f32).cos()
+}
@@ -0,0 +1,144 @@
+use tracing::{debug, info, warn};
+
+use super::analysis;
+use super::phrase::VAD_FRAME_MS;
+
+/// 間（句と句の間の無音）とみなす最短の長さ（ミリ秒）。これより短い無音は句の中の息継ぎとして扱う
+pub const MIN_GAP_MS: u32 = 150;
+
+/// 入力の間と変換結果の間を対応づけるときに許すずれ（ミリ秒）
+const MATCH_TOLERANCE_MS: u32 = 1000;
+
+/// 変換結果の間の長さを、入力の間と同じに揃える（`--preserve-gaps`）
+///
+/// サーバーが句の間の無音を縮めることがあり、映像に付けたナレーションが少しずつずれていきます。
+/// 入力と変換結果のそれぞれで [`MIN_GAP_MS`] 以上の無音を探して前から順に対応づけ、
+/// 変換結果の間を入力と同じ長さに切り詰めるか、無音を足して伸ばします。句そのものには触れません。
+pub struct GapKeeper {
+    /// 入力の間（フレーム、始まりと終わり）
+    gaps: Vec<(usize, usize)>,
+    threshold_db: f32,
+}
+
+impl GapKeeper {
+    /// 元の入力から間を測る
+    pub fn new(original: &[f32], sample_rate: u32, channels: u16, threshold_db: f32) -> Self {
+        let gaps = find_gaps(original, sample_rate, channels, threshold_db);
+        debug!("入力の間: {}か所", gaps.len());
+        Self { gaps, threshold_db }
+    }
+
+    /// 入力で見つかった間の数
+    pub fn len(&self) -> usize {
+        self.gaps.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.gaps.is_empty()
+    }
+
+    /// ファイル全体の間を入力に揃える
+    pub fn apply_all(&self, samples: &[f32], sample_rate: u32, channels: u16) -> Vec<f32> {
+        let ch = channels.max(1) as usize;
+        let converted = find_gaps(samples, sample_rate, channels, self.threshold_db);
+        let pairs = match_gaps(&self.gaps, &converted, sample_rate);
+
+        let mut output = Vec::with_capacity(samples.len());
+        let mut pos = 0;
+        let (mut shrunk, mut max_diff) = (0, 0usize);
+        for &((in_start, in_end), (out_start, out_end)) in &pairs {
+            output.extend_from_slice(&samples[pos * ch..out_start * ch]);
+            let (target, have) = (in_end - in_start, out_end - out_start);
+            // 間の前半と後半を残し、真ん中で切り詰めるか無音を足す
+            let head = target.min(have) / 2;
+            let tail = target.min(have) - head;
+            output.extend_from_slice(&samples[out_start * ch..(out_start + head) * ch]);
+            output.resize(output.len() + target.saturating_sub(have) * ch, 0.0);
+            output.extend_from_slice(&samples[(out_end - tail) * ch..out_end * ch]);
+            if have < target {
+                shrunk += 1;
+            }
+            max_diff = max_diff.max(target.abs_diff(have));
+            pos = out_end;
+        }
+        output.extend_from_slice(&samples[pos * ch..]);
+
+        info!(
+            "  間の長さを入力に揃えました: {}/{}か所（縮んでいた間 {}か所、最大の差 {}ms）",
+            pairs.len(),
+            self.gaps.len(),
+            shrunk,
+            max_diff as u64 * 1000 / sample_rate.max(1) as u64
+        );
+        if pairs.len() < self.gaps.len() {
+            warn!(
+                "⚠ 変換結果で見つからなかった間が{}か所あります（その間はそのままです）",
+                self.gaps.len() - pairs.len()
+            );
+        }
+        output
+    }
+}
+
+/// `threshold_db` 未満が [`MIN_GAP_MS`] 以上続く区間（フレーム、始まりと終わり）
+pub fn find_gaps(
+    samples: &[f32],
+    sample_rate: u32,
+    channels: u16,
+    threshold_db: f32,
+) -> Vec<(usize, usize)> {
+    let mono = analysis::downmix(samples, channels);
+    let frame_len = (sample_rate * VAD_FRAME_MS / 1000).max(1) as usize;
+    let min_frames = (MIN_GAP_MS / VAD_FRAME_MS) as usize;
+
+    let mut gaps = Vec::new();
+    let mut silent_from = None;
+    for (i, frame) in mono.chunks(frame_len).enumerate() {
+        match (analysis::rms_db(frame) < threshold_db, silent_from) {
+            (true, None) => silent_from = Some(i),
+            (false, Some(from)) => {
+                if i - from >= min_frames {
+                    gaps.push((from * frame_len, i * frame_len));
+                }
+                silent_from = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(from) = silent_from {
+        let frames = mono.len().div_ceil(frame_len);
+        if frames - from >= min_frames {
+            gaps.push((from * frame_len, mono.len()));
+        }
+    }
+    gaps
+}
+
+/// 入力の間ごとに、変換結果で同じ位置にある間を前から順に探す
+///
+/// 位置は直前に対応づけた間からの距離で比べるため、それまでのずれが溜まっていても対応づけられます。
+fn match_gaps(
+    input: &[(usize, usize)],
+    converted: &[(usize, usize)],
+    sample_rate: u32,
+) -> Vec<((usize, usize), (usize, usize))> {
+    let tolerance = (sample_rate * MATCH_TOLERANCE_MS / 1000) as usize;
+    let mut pairs = Vec::new();
+    let (mut last_in, mut last_out) = (0, 0);
+    let mut next = 0;
+    for &(start, end) in input {
+        let expected = last_out + (start - last_in);
+        let found = converted[next..]
+            .iter()
+            .enumerate()
+            .take_while(|(_, gap)| gap.0 <= expected + tolerance)
+            .filter(|(_, gap)| gap.0.abs_diff(expected) <= tolerance)
+            .min_by_key(|(_, gap)| gap.0.abs_diff(expected));
+        if let Some((k, &gap)) = found {
+            pairs.push(((start, end), gap));
+            (last_in, last_out) = (end, gap.1);
+            next += k + 1;
+        }
+    }
+    pairs
+}
@@ -0,0 +1,288 @@
+use anyhow::{Context, Result};
+use libloading::Library;
+use std::collections::BTreeMap;
+use std::ffi::{c_char, c_void, CStr, CString};
+use std::path::Path;
+use tracing::warn;
+
+use super::DspStage;
+use crate::config::Lv2PluginConfig;
+
+type Lv2Handle = *mut c_void;
+
+/// LV2_Feature（ホスト機能は提供しないため、終端NULLのみ渡す）
+#[repr(C)]
+struct Lv2Feature {
+    uri: *const c_char,
+    data: *mut c_void,
+}
+
+/// LV2_Descriptor（lv2/core/lv2.h）
+#[repr(C)]
+struct Lv2Descriptor {
+    uri: *const c_char,
+    instantiate: unsafe extern "C" fn(
+        descriptor: *const Lv2Descriptor,
+        sample_rate: f64,
+        bundle_path: *const c_char,
+        features: *const *const Lv2Feature,
+    ) -> Lv2Handle,
+    connect_port: unsafe extern "C" fn(instance: Lv2Handle, port: u32, data: *mut c_void),
+    activate: Option<unsafe extern "C" fn(instance: Lv2Handle)>,
+    run: unsafe extern "C" fn(instance: Lv2Handle, sample_count: u32),
+    deactivate: Option<unsafe extern "C" fn(instance: Lv2Handle)>,
+    cleanup: unsafe extern "C" fn(instance: Lv2Handle),
+    extension_data: Option<unsafe extern "C" fn(uri: *const c_char) -> *const c_void>,
+}
+
+type DescriptorFn = unsafe extern "C" fn(index: u32) -> *const Lv2Descriptor;
+
+/// 1つのLV2インスタンス
+struct Instance {
+    handle: Lv2Handle,
+    descriptor: *const Lv2Descriptor,
+    /// コントロールポートの値（ポインタを渡すためアドレス固定）
+    _controls: Box<[f32]>,
+}
+
+impl Drop for Instance {
+    fn drop(&mut self) {
+        // SAFETY: handleはinstantiateで取得し、以後使用されない
+        unsafe {
+            let descriptor = &*self.descriptor;
+            if let Some(deactivate) = descriptor.deactivate {
+                deactivate(self.handle);
+            }
+            (descriptor.cleanup)(self.handle);
+        }
+    }
+}
+
+/// LV2プラグインをホストするDSPステージ
+///
+/// TTLの解析は行わないため、ポート番号は設定で明示します。
+/// ホスト機能（URIDなど）を要求するプラグインはインスタンス化に失敗します。
+/// モノラルプラグイン（入出力1ポート）はチャンネルごとにインスタンスを作成します。
+pub struct Lv2Stage {
+    name: String,
+    descriptor: *const Lv2Descriptor,
+    bundle_path: CString,
+    audio_in: Vec<u32>,
+    audio_out: Vec<u32>,
+    controls: BTreeMap<u32, f32>,
+    control_outputs: Vec<u32>,
+    instances: Vec<Instance>,
+    channels: usize,
+    in_buffers: Vec<Vec<f32>>,
+    out_buffers: Vec<Vec<f32>>,
+    // descriptorより後に破棄する必要がある
+    _library: Library,
+}
+
+// LV2インスタンスは単一スレッドからのみ使用される
+unsafe impl Send for Lv2Stage {}
+
+impl Lv2Stage {
+    /// プラグインバイナリを読み込み、URIに一致するディスクリプタを探す
+    pub fn load(config: &Lv2PluginConfig) -> Result<Self> {
+        if config.audio_in.len() != config.audio_out.len() || config.audio_in.is_empty() {
+            anyhow::bail!(
+                "{}: audio_in と audio_out は同数（1以上）を指定してください",
+                config.uri
+            );
+        }
+
+        let bundle = config
+            .bundle
+            .clone()
+            .or_else(|| config.binary.parent().map(Path::to_path_buf))
+            .context("LV2バンドルパスを特定できません")?;
+        // LV2仕様ではバンドルパスは末尾にセパレータを含む
+        let mut bundle_path = bundle.to_string_lossy().into_owned();
+        if !bundle_path.ends_with(std::path::MAIN_SEPARATOR) {
+            bundle_path.push(std::path::MAIN_SEPARATOR);
+        }
+
+        // SAFETY: ユーザーが設定で明示したLV2バイナリを読み込む
+        unsafe {
+            let library = Library::new(&config.binary).with_context(|| {
+                format!("LV2プラグインを読み込めません: {}", config.binary.display())
+            })?;
+            let descriptor_fn: DescriptorFn = *library
+                .get(b"lv2_descriptor\0")
+                .context("lv2_descriptor がありません")?;
+
+            let mut index = 0;
+            let descriptor = loop {
+                let descriptor = descriptor_fn(index);
+                if descriptor.is_null() {
+                    anyhow::bail!("URI {} のプラグインが見つかりません", config.uri);
+                }
+                if CStr::from_ptr((*descriptor).uri).to_string_lossy() == config.uri {
+                    break descriptor;
+                }
+                index += 1;
+            };
+
+            Ok(Self {
+                name: format!("lv2:{}", config.uri),
+                descriptor,
+                bundle_path: CString::new(bundle_path)?,
+                audio_in: config.audio_in.clone(),
+                audio_out: config.audio_out.clone(),
+                controls: config
+                    .controls
+                    .iter()
+                    .filter_map(|(port, value)| Some((port.parse().ok()?, *value)))
+                    .collect(),
+                control_outputs: config.control_outputs.clone(),
+                instances: Vec::new(),
+                channels: 0,
+                in_buffers: Vec::new(),
+                out_buffers: Vec::new(),
+                _library: library,
+            })
+        }
+    }
+
+    fn instantiate(&self, sample_rate: u32) -> Option<Instance> {
+        let features: [*const Lv2Feature; 1] = [std::ptr::null()];
+
+        // コントロール入力 + コントロール出力（ダミー）の値領域
+        let mut controls: Box<[f32]> = self
+            .controls
+            .values()
+            .copied()
+            .chain(self.control_outputs.iter().map(|_| 0.0))
+            .collect();
+
+        // SAFETY: descriptorはライブラリが生存している間有効
+        unsafe {
+            let descriptor = &*self.descriptor;
+            let handle = (descriptor.instantiate)(
+                self.descriptor,
+                sample_rate as f64,
+                self.bundle_path.as_ptr(),
+                features.as_ptr(),
+            );
+            if handle.is_null() {
+                return None;
+            }
+
+            let ports = self.controls.keys().chain(self.control_outputs.iter());
+            for (port, value) in ports.zip(controls.iter_mut()) {
+                (descriptor.connect_port)(handle, *port, value as *mut f32 as *mut c_void);
+            }
+
+            if let Some(activate) = descriptor.activate {
+                activate(handle);
+            }
+
+            Some(Instance {
+                handle,
+                descriptor: self.descriptor,
+                _controls: controls,
+            })
+        }
+    }
+}
+
+impl DspStage for Lv2Stage {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn prepare(&mut self, sample_rate: u32, channels: u16) {
+        self.instances.clear();
+        self.channels = channels as usize;
+
+        let ports = self.audio_in.len();
+        let instance_count = if ports == 1 {
+            self.channels
+        } else if ports == self.channels {
+            1
+        } else {
+            warn!(
+                "{}: {}ch音声に{}ポートのプラグインは適用できません（バイパス）",
+                self.name, channels, ports
+            );
+            return;
+        };
+
+        for _ in 0..instance_count {
+            match self.instantiate(sample_rate) {
+                Some(instance) => self.instances.push(instance),
+                None => {
+                    warn!("{}: インスタンス化に失敗しました（バイパス）", self.name);
+                    self.instances.clear();
+                    return;
+                }
+            }
+        }
+
+        self.in_buffers = vec![Vec::new(); self.channels];
+        self.out_buffers = vec![Vec::new(); self.channels];
+    }
+
+    fn process(&mut self, samples: &mut [f32]) {
+        if self.instances.is_empty() || self.channels == 0 {
+            return;
+        }
+
+        let frames = samples.len() / self.channels;
+        for (channel, (input, output)) in self
+            .in_buffers
+            .iter_mut()
+            .zip(self.out_buffers.iter_mut())
+            .enumerate()
+        {
+            input.clear();
+            input.extend(samples.iter().skip(channel).step_by(self.channels));
+            output.clear();
+            output.resize(frames, 0.0);
+        }
+
+        let ports_per_instance = self.audio_in.len();
+        for (i, instance) in self.instances.iter().enumerate() {
+            // SAFETY: バッファはrun中に再確保されない
+            unsafe {
+                let descriptor = &*instance.descriptor;
+                for port in 0..ports_per_instance {
+                    let channel = i * ports_per_instance + port;
+                    (descriptor.connect_port)(
+                        instance.handle,
+                        self.audio_in[port],
+                        self.in_buffers[channel].as_mut_ptr() as *mut c_void,
+                    );
+                    (descriptor.connect_port)(
+                        instance.handle,
+                        self.audio_out[port],
+                        self.out_buffers[channel].as_mut_ptr() as *mut c_void,
+                    );
+                }
+                (descriptor.run)(instance.handle, frames as u32);
+            }
+        }
+
+        for (frame, chunk) in samples.chunks_exact_mut(self.channels).enumerate() {
+            for (channel, sample) in chunk.iter_mut().enumerate() {
+                *sample = self.out_buffers[channel][frame];
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        for instance in &self.instances {
+            // SAFETY: handleは有効
+            unsafe {
+                let descriptor = &*instance.descriptor;
+                if let (Some(deactivate), Some(activate)) =
+                    (descriptor.deactivate, descriptor.activate)
+                {
+                    deactivate(instance.handle);
+                    activate(instance.handle);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,136 @@
+use anyhow::Result;
+#[cfg(feature = "dsp-plugins")]
+use tracing::info;
+
+use crate::config::DspConfig;
+
+pub mod analysis;
+pub mod breath;
+pub mod contour;
+pub mod crossfade;
+pub mod gaps;
+#[cfg(feature = "lv2")]
+pub mod lv2;
+pub mod noise;
+pub mod phrase;
+pub mod pitch;
+#[cfg(feature = "dsp-plugins")]
+pub mod plugin;
+pub mod rate;
+pub mod simd;
+pub mod stereo;
+pub mod stretch;
+pub mod vad;
+
+/// ローカルエフェクトチェーンの1段
+///
+/// サンプルはインターリーブ形式で渡され、インプレースで処理します。
+/// オーディオスレッド近くで呼ばれるため、`process` 内でのブロッキングは避けてください。
+pub trait DspStage: Send {
+    /// ステージ名（ログ・診断用）
+    fn name(&self) -> &str;
+
+    /// サンプリングレート・チャンネル数の確定時に呼ばれる
+    fn prepare(&mut self, _sample_rate: u32, _channels: u16) {}
+
+    /// サンプルを処理
+    fn process(&mut self, samples: &mut [f32]);
+
+    /// 内部状態をリセット
+    fn reset(&mut self) {}
+}
+
+/// DSPステージを順に適用するチェーン
+#[derive(Default)]
+pub struct DspChain {
+    stages: Vec<Box<dyn DspStage>>,
+    format: Option<(u32, u16)>,
+}
+
+impl DspChain {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 設定ファイルで宣言されたプラグインからチェーンを構築
+    #[cfg(feature = "dsp-plugins")]
+    pub fn from_config(config: &DspConfig) -> Result<Self> {
+        let mut chain = Self::new();
+
+        for plugin in &config.plugins {
+            let stage = plugin::PluginStage::load(&plugin.path, &plugin.params)?;
+            info!("DSPプラグイン読み込み: {}", stage.name());
+            chain.push(Box::new(stage));
+        }
+
+        #[cfg(feature = "lv2")]
+        for plugin in &config.lv2 {
+            let stage = lv2::Lv2Stage::load(plugin)?;
+            info!("LV2プラグイン読み込み: {}", stage.name());
+            chain.push(Box::new(stage));
+        }
+
+        #[cfg(not(feature = "lv2"))]
+        if !config.lv2.is_empty() {
+            anyhow::bail!(
+                "LV2プラグインを使うには lv2 機能を有効にしてビルドしてください（cargo build --features lv2）"
+            );
+        }
+
+        Ok(chain)
+    }
+
+    /// dsp-plugins 機能なしのビルドではプラグインの宣言をエラーにする
+    #[cfg(not(feature = "dsp-plugins"))]
+    pub fn from_config(config: &DspConfig) -> Result<Self> {
+        if !config.plugins.is_empty() || !config.lv2.is_empty() {
+            anyhow::bail!(
+                "DSPプラグインを使うには dsp-plugins 機能を有効にしてビルドしてください（cargo build --features dsp-plugins）"
+            );
+        }
+        Ok(Self::new())
+    }
+
+    /// 末尾にステージを追加
+    pub fn push(&mut self, mut stage: Box<dyn DspStage>) {
+        if let Some((sample_rate, channels)) = self.format {
+            stage.prepare(sample_rate, channels);
+        }
+        self.stages.push(stage);
+    }
+
+    /// 全ステージを準備（フォーマットが変わった場合のみ）
+    pub fn prepare(&mut self, sample_rate: u32, channels: u16) {
+        if self.format == Some((sample_rate, channels)) {
+            return;
+        }
+
+        for stage in &mut self.stages {
+            stage.prepare(sample_rate, channels);
+        }
+        self.format = Some((sample_rate, channels));
+    }
+
+    /// 全ステージを順に適用
+    pub fn process(&mut self, samples: &mut [f32]) {
+        for stage in &mut self.stages {
+            stage.process(samples);
+        }
+    }
+
+    /// 全ステージの状態をリセット
+    pub fn reset(&mut self) {
+        for stage in &mut self.stages {
+            stage.reset();
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stages.is_empty()
+    }
+
+    /// ステージ名の一覧
+    pub fn stage_names(&self) -> Vec<&str> {
+        self.stages.iter().map(|s| s.name()).collect()
+    }
+}
@@ -0,0 +1,333 @@
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tracing::{info, warn};
+
+use super::stereo::StereoImage;
+use super::DspStage;
+use crate::chaos::XorShift;
+use crate::resample::{self, ResampleQuality};
+use crate::wav;
+
+/// 生成ノイズの音量を測る長さ（秒）
+const CALIBRATION_SECONDS: u32 = 1;
+
+/// 背景ノイズの音源
+#[derive(Clone)]
+enum NoiseSource {
+    /// カフェ（ピンクノイズ、細かく揺れる）
+    Cafe,
+    /// 街中（ブラウンノイズ、ゆっくり大きくうねる）
+    Street,
+    /// 室内（こもったホワイトノイズ、ほぼ一定）
+    Room,
+    /// WAVファイルのループ（モノラル）
+    Loop {
+        path: PathBuf,
+        samples: Arc<Vec<f32>>,
+        sample_rate: u32,
+    },
+}
+
+impl NoiseSource {
+    /// 音量の揺らぎの幅（±dB）と、目標を変える間隔（秒）
+    fn fluctuation(&self) -> (f32, f32) {
+        match self {
+            Self::Cafe => (3.0, 0.7),
+            Self::Street => (6.0, 2.5),
+            Self::Room => (1.0, 4.0),
+            Self::Loop { .. } => (2.0, 2.0),
+        }
+    }
+}
+
+impl std::fmt::Display for NoiseSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Cafe => f.write_str("cafe"),
+            Self::Street => f.write_str("street"),
+            Self::Room => f.write_str("room"),
+            Self::Loop { path, .. } => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+/// 色付きノイズの生成器の状態
+#[derive(Clone, Default)]
+struct Generator {
+    /// ピンクノイズのフィルタ（Paul Kelletの近似）
+    pink: [f32; 3],
+    brown: f32,
+    lowpass: f32,
+}
+
+/// 変換後の音声に背景ノイズを混ぜるステージ
+///
+/// `cafe` / `street` / `room` は色付きノイズを生成し、WAVファイルのパスを渡すと
+/// その音をループ再生します。音量はゆっくりランダムに揺らします。
+/// 変換結果とは独立に鳴らせるため、出力コールバックで使えばサーバーの応答が
+/// 途切れている間もノイズは鳴り続けます。
+#[derive(Clone)]
+pub struct NoiseMixer {
+    source: NoiseSource,
+    /// ノイズのRMSレベル（0.0-1.0）
+    level: f32,
+    rng: XorShift,
+    format: Option<(u32, u16)>,
+    generator: Generator,
+    /// 生成ノイズをRMS 1.0に揃える係数
+    calibration: f32,
+    /// `prepare` で出力のレートに揃えたループ
+    loop_samples: Vec<f32>,
+    loop_position: usize,
+    /// 室内ノイズのローパスの係数
+    lowpass_coefficient: f32,
+    /// 音量の揺らぎ（現在値・目標値・次に目標を変えるまでのフレーム数）
+    gain: f32,
+    target_gain: f32,
+    frames_until_target: usize,
+    smoothing: f32,
+    /// 左右への広がり（ステレオ出力のみ、Noneならモノラルのまま）
+    image: Option<StereoImage>,
+}
+
+impl NoiseMixer {
+    /// `noise` は `cafe` / `street` / `room` かWAVファイルのパス
+    pub fn new(noise: &str, level: f32) -> Result<Self> {
+        anyhow::ensure!(
+            (0.0..=1.0).contains(&level),
+            "ノイズレベルは0.0〜1.0で指定してください: {}",
+            level
+        );
+
+        let source = match noise.to_lowercase().as_str() {
+            "cafe" => NoiseSource::Cafe,
+            "street" => NoiseSource::Street,
+            "room" => NoiseSource::Room,
+            _ if is_loop_path(noise) => {
+                let path = PathBuf::from(noise);
+                let (samples, spec) = wav::read_file(&path)?;
+                anyhow::ensure!(!samples.is_empty(), "ノイズのファイルが空です: {}", noise);
+                let mut samples = super::analysis::downmix(&samples, spec.channels);
+                // レベルをそろえるためRMS 1.0に正規化する
+                let rms =
+                    (samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32).sqrt();
+                anyhow::ensure!(rms > f32::EPSILON, "ノイズのファイルが無音です: {}", noise);
+                samples.iter_mut().for_each(|s| *s /= rms);
+                info!(
+                    "ノイズのループ: {}（{:.1}秒）",
+                    path.display(),
+                    samples.len() as f64 / spec.sample_rate as f64
+                );
+                NoiseSource::Loop {
+                    path,
+                    samples: Arc::new(samples),
+                    sample_rate: spec.sample_rate,
+                }
+            }
+            _ => anyhow::bail!(
+                "不明なノイズの種類: {}（cafe, street, room またはWAVファイルのパス）",
+                noise
+            ),
+        };
+
+        Ok(Self {
+            source,
+            level,
+            rng: XorShift::from_time(),
+            format: None,
+            generator: Generator::default(),
+            calibration: 1.0,
+            loop_samples: Vec::new(),
+            loop_position: 0,
+            lowpass_coefficient: 0.0,
+            gain: 1.0,
+            target_gain: 1.0,
+            frames_until_target: 0,
+            smoothing: 0.0,
+            image: None,
+        })
+    }
+
+    /// 左右への広がり（1.0でモノラルのまま、大きいほど広い）
+    pub fn with_width(mut self, width: f32) -> Self {
+        self.image = (width != 1.0).then(|| StereoImage::new(0.0, width));
+        self
+    }
+
+    /// 乱数の種を固定する（同じ種なら同じノイズになる。マニフェストからの再現用）
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = XorShift::new(seed);
+        self
+    }
+
+    pub fn width(&self) -> f32 {
+        self.image.as_ref().map_or(1.0, StereoImage::width)
+    }
+
+    pub fn level(&self) -> f32 {
+        self.level
+    }
+
+    pub fn set_level(&mut self, level: f32) {
+        self.level = level.clamp(0.0, 1.0);
+    }
+
+    /// ノイズの種類（`cafe` などか、ループのパス）
+    pub fn source_name(&self) -> String {
+        self.source.to_string()
+    }
+
+    /// ファイル全体にノイズを混ぜる
+    pub fn mix_all(&mut self, samples: &mut [f32], sample_rate: u32, channels: u16) {
+        self.prepare(sample_rate, channels);
+        self.process(samples);
+    }
+
+    /// 次のノイズのサンプル（RMS 1.0程度）
+    fn next_sample(&mut self) -> f32 {
+        if let NoiseSource::Loop { .. } = self.source {
+            let Some(&sample) = self.loop_samples.get(self.loop_position) else {
+                return 0.0;
+            };
+            self.loop_position = (self.loop_position + 1) % self.loop_samples.len();
+            return sample;
+        }
+
+        // 分散1の一様乱数
+        let white = (self.rng.next_f64() as f32 * 2.0 - 1.0) * 3f32.sqrt();
+        let g = &mut self.generator;
+        let raw = match self.source {
+            NoiseSource::Cafe => {
+                g.pink[0] = 0.99765 * g.pink[0] + white * 0.0990460;
+                g.pink[1] = 0.96300 * g.pink[1] + white * 0.2965164;
+                g.pink[2] = 0.57000 * g.pink[2] + white * 1.0526913;
+                g.pink.iter().sum::<f32>() + white * 0.1848
+            }
+            NoiseSource::Street => {
+                g.brown = (g.brown + 0.02 * white) / 1.02;
+                g.brown
+            }
+            NoiseSource::Room => {
+                g.lowpass += (white - g.lowpass) * self.lowpass_coefficient;
+                g.lowpass
+            }
+            NoiseSource::Loop { .. } => unreachable!(),
+        };
+        raw * self.calibration
+    }
+
+    /// 揺らぎを1フレーム進めて現在の音量を返す
+    fn next_gain(&mut self) -> f32 {
+        if self.frames_until_target == 0 {
+            let (depth_db, interval) = self.source.fluctuation();
+            let db = (self.rng.next_f64() as f32 * 2.0 - 1.0) * depth_db;
+            self.target_gain = super::analysis::from_db(db);
+            let sample_rate = self.format.map_or(48000, |(rate, _)| rate);
+            self.frames_until_target = (interval * sample_rate as f32) as usize;
+        }
+        self.frames_until_target -= 1;
+        self.gain += (self.target_gain - self.gain) * self.smoothing;
+        self.gain
+    }
+}
+
+/// 種類名ではなくファイルとして扱う指定か
+fn is_loop_path(noise: &str) -> bool {
+    let path = Path::new(noise);
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("wav"))
+        || path.components().count() > 1
+}
+
+impl DspStage for NoiseMixer {
+    fn name(&self) -> &str {
+        "noise"
+    }
+
+    fn prepare(&mut self, sample_rate: u32, channels: u16) {
+        if self.format == Some((sample_rate, channels)) {
+            return;
+        }
+        self.format = Some((sample_rate, channels));
+        if let Some(image) = self.image.as_mut() {
+            image.prepare(sample_rate, channels);
+        }
+
+        let (_, interval) = self.source.fluctuation();
+        // 目標の間隔の半分ほどで追いつく
+        self.smoothing = 1.0 / (interval * 0.5 * sample_rate as f32).max(1.0);
+        self.lowpass_coefficient =
+            1.0 - (-2.0 * std::f32::consts::PI * 1000.0 / sample_rate as f32).exp();
+
+        match &self.source {
+            NoiseSource::Loop {
+                path,
+                samples,
+                sample_rate: loop_rate,
+            } => {
+                self.loop_samples = resample::resample(
+                    samples,
+                    *loop_rate,
+                    sample_rate,
+                    1,
+                    ResampleQuality::Balanced,
+                )
+                .unwrap_or_else(|e| {
+                    warn!(
+                        "ノイズのループを変換できません（{}）: {}",
+                        path.display(),
+                        e
+                    );
+                    Vec::new()
+                });
+                // 毎回同じところから始まらないようにする
+                self.loop_position =
+                    (self.rng.next_f64() * self.loop_samples.len() as f64) as usize;
+            }
+            _ => {
+                // 実際に生成してRMSを測り、種類によらず同じレベルにそろえる
+                self.calibration = 1.0;
+                self.generator = Generator::default();
+                let frames = sample_rate * CALIBRATION_SECONDS;
+                let power =
+                    (0..frames).map(|_| self.next_sample().powi(2)).sum::<f32>() / frames as f32;
+                self.calibration = 1.0 / power.sqrt().max(f32::EPSILON);
+            }
+        }
+    }
+
+    fn process(&mut self, samples: &mut [f32]) {
+        let Some((_, channels)) = self.format else {
+            return;
+        };
+        if self.level <= 0.0 {
+            return;
+        }
+
+        for frame in samples.chunks_mut(channels.max(1) as usize) {
+            let noise = self.next_sample() * self.next_gain() * self.level;
+            let (left, right) = match self.image.as_mut() {
+                Some(image) if frame.len() >= 2 => image.frame(noise, noise),
+                _ => (noise, noise),
+            };
+            for (channel, sample) in frame.iter_mut().enumerate() {
+                *sample += match channel {
+                    0 => left,
+                    1 => right,
+                    _ => noise,
+                };
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        self.generator = Generator::default();
+        self.gain = 1.0;
+        self.target_gain = 1.0;
+        self.frames_until_target = 0;
+        if let Some(image) = self.image.as_mut() {
+            image.reset();
+        }
+    }
+}
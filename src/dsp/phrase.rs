@@ -0,0 +1,57 @@
+use super::analysis::{self, DEFAULT_VAD_THRESHOLD_DB};
+
+/// 発話判定の単位（ミリ秒）
+pub const VAD_FRAME_MS: u32 = 10;
+
+/// これより短い発話は句とみなさない（ミリ秒）
+const MIN_PHRASE_MS: u32 = 400;
+
+/// 発話の後にこれだけ無音が続いたら句の切れ目とみなす（ミリ秒）
+const PAUSE_MS: u32 = 120;
+
+/// 音量で発話を判定し、句の切れ目を見つける
+///
+/// モノラルのサンプルを1つずつ渡すと、10msごとに判定して、ある程度の長さの発話の後に
+/// 無音が続いたところで `true` を返します。
+#[derive(Clone, Default)]
+pub struct PhraseDetector {
+    frame: Vec<f32>,
+    frame_len: usize,
+    speech_ms: u32,
+    silence_ms: u32,
+}
+
+impl PhraseDetector {
+    pub fn prepare(&mut self, sample_rate: u32) {
+        self.frame_len = (sample_rate * VAD_FRAME_MS / 1000).max(1) as usize;
+        self.reset();
+    }
+
+    /// 1サンプル進める。句の切れ目ならtrue
+    pub fn push(&mut self, sample: f32) -> bool {
+        self.frame.push(sample);
+        if self.frame.len() < self.frame_len.max(1) {
+            return false;
+        }
+        let speech = analysis::rms_db(&self.frame) >= DEFAULT_VAD_THRESHOLD_DB;
+        self.frame.clear();
+
+        if speech {
+            self.speech_ms += VAD_FRAME_MS;
+            self.silence_ms = 0;
+            return false;
+        }
+        self.silence_ms += VAD_FRAME_MS;
+        if self.silence_ms != PAUSE_MS || self.speech_ms < MIN_PHRASE_MS {
+            return false;
+        }
+        self.speech_ms = 0;
+        true
+    }
+
+    pub fn reset(&mut self) {
+        self.frame.clear();
+        self.speech_ms = 0;
+        self.silence_ms = 0;
+    }
+}
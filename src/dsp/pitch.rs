@@ -0,0 +1,333 @@
+#[cfg(feature = "local-dsp")]
+use realfft::num_complex::Complex;
+#[cfg(feature = "local-dsp")]
+use realfft::{ComplexToReal, RealFftPlanner, RealToComplex};
+#[cfg(feature = "local-dsp")]
+use std::f32::consts::PI;
+#[cfg(feature = "local-dsp")]
+use std::sync::Arc;
+
+use super::DspStage;
+
+#[cfg(not(feature = "local-dsp"))]
+const UNAVAILABLE: &str =
+    "ローカルのピッチシフト（--engine local, --fallback pitch, --pitch-contour）を使うには local-dsp フィーチャーを有効にしてビルドしてください（cargo build --features local-dsp）";
+
+/// このビルドでローカルのピッチシフトを使えるか確かめる（使えなければ理由を示すエラー）
+pub fn ensure_available() -> anyhow::Result<()> {
+    #[cfg(not(feature = "local-dsp"))]
+    anyhow::bail!(UNAVAILABLE);
+    #[cfg(feature = "local-dsp")]
+    Ok(())
+}
+
+/// 分析フレームの長さの目安（ミリ秒）。低い声の倍音を分けられる長さにする
+#[cfg(feature = "local-dsp")]
+const FRAME_MS: u32 = 40;
+
+/// フレームの重なり（ホップ = フレーム長 / OVERSAMPLING）
+#[cfg(feature = "local-dsp")]
+const OVERSAMPLING: usize = 4;
+
+/// 位相ボコーダーによるピッチシフター
+///
+/// 長さを変えずにピッチだけを変えます。サーバーが使えないときの縮退運転用で、
+/// 声質（フォルマント）も一緒に動くため、RVCのような声の変換にはなりません。
+/// 出力は `latency_frames` だけ遅れます。
+#[cfg(feature = "local-dsp")]
+pub struct PitchShifter {
+    semitones: f32,
+    ratio: f32,
+    format: Option<(u32, u16)>,
+    fft: Option<Fft>,
+    channels: Vec<ChannelState>,
+}
+
+/// サンプリングレートごとに作り直すFFTと作業領域
+#[cfg(feature = "local-dsp")]
+struct Fft {
+    size: usize,
+    forward: Arc<dyn RealToComplex<f32>>,
+    inverse: Arc<dyn ComplexToReal<f32>>,
+    window: Vec<f32>,
+    frame: Vec<f32>,
+    spectrum: Vec<Complex<f32>>,
+    scratch: Vec<Complex<f32>>,
+    analysis_magnitude: Vec<f32>,
+    analysis_frequency: Vec<f32>,
+    synthesis_magnitude: Vec<f32>,
+    synthesis_frequency: Vec<f32>,
+    /// ビンあたりの周波数（Hz）
+    bin_hz: f32,
+}
+
+/// チャンネルごとの状態
+#[cfg(feature = "local-dsp")]
+struct ChannelState {
+    input: Vec<f32>,
+    output: Vec<f32>,
+    accumulator: Vec<f32>,
+    last_phase: Vec<f32>,
+    phase_sum: Vec<f32>,
+    position: usize,
+}
+
+#[cfg(feature = "local-dsp")]
+impl PitchShifter {
+    /// `semitones` 半音ずらすピッチシフター
+    pub fn new(semitones: f32) -> Self {
+        let mut shifter = Self {
+            semitones: 0.0,
+            ratio: 1.0,
+            format: None,
+            fft: None,
+            channels: Vec::new(),
+        };
+        shifter.set_semitones(semitones);
+        shifter
+    }
+
+    /// ずらす量を変える（処理中でも次のフレームから反映）
+    pub fn set_semitones(&mut self, semitones: f32) {
+        self.semitones = semitones;
+        self.ratio = 2f32.powf(semitones / 12.0);
+    }
+
+    pub fn semitones(&self) -> f32 {
+        self.semitones
+    }
+
+    /// 処理による遅れ（フレーム）。`prepare` の前は0
+    pub fn latency_frames(&self) -> usize {
+        self.fft
+            .as_ref()
+            .map_or(0, |fft| fft.size - fft.size / OVERSAMPLING)
+    }
+
+    /// ファイル全体をずらす（遅れを取り除き、入力と同じ長さで返す）
+    pub fn shift_all(&mut self, samples: &[f32], sample_rate: u32, channels: u16) -> Vec<f32> {
+        self.prepare(sample_rate, channels);
+        self.reset();
+
+        // 遅れの分だけ無音を足して流し切り、先頭の遅れを捨てる
+        let latency = self.latency_frames() * channels as usize;
+        let mut buffer = samples.to_vec();
+        buffer.resize(samples.len() + latency, 0.0);
+        self.process(&mut buffer);
+        buffer.split_off(latency)
+    }
+}
+
+#[cfg(feature = "local-dsp")]
+impl Fft {
+    fn new(sample_rate: u32) -> Self {
+        let size = ((sample_rate * FRAME_MS / 1000) as usize)
+            .next_power_of_two()
+            .max(256);
+        let mut planner = RealFftPlanner::<f32>::new();
+        let forward = planner.plan_fft_forward(size);
+        let inverse = planner.plan_fft_inverse(size);
+        let scratch_len = forward.get_scratch_len().max(inverse.get_scratch_len());
+        let bins = size / 2 + 1;
+
+        // ハン窓
+        let window = (0..size)
+            .map(|i| 0.5 - 0.5 * (2.0 * PI * i as f32 / size as f32).cos())
+            .collect();
+
+        Self {
+            size,
+            frame: vec![0.0; size],
+            spectrum: vec![Complex::default(); bins],
+            scratch: vec![Complex::default(); scratch_len],
+            analysis_magnitude: vec![0.0; bins],
+            analysis_frequency: vec![0.0; bins],
+            synthesis_magnitude: vec![0.0; bins],
+            synthesis_frequency: vec![0.0; bins],
+            bin_hz: sample_rate as f32 / size as f32,
+            forward,
+            inverse,
+            window,
+        }
+    }
+
+    /// 1フレームを分析し、周波数を `ratio` 倍して合成する（結果は `accumulator` に足す）
+    fn shift_frame(&mut self, state: &mut ChannelState, ratio: f32) {
+        let size = self.size;
+        let hop = size / OVERSAMPLING;
+        let bins = size / 2 + 1;
+        // ホップあたりに各ビンの位相が進む量
+        let expected = 2.0 * PI * hop as f32 / size as f32;
+
+        for (frame, (&input, &window)) in self
+            .frame
+            .iter_mut()
+            .zip(state.input.iter().zip(&self.window))
+        {
+            *frame = input * window;
+        }
+        let _ = self.forward.process_with_scratch(
+            &mut self.frame,
+            &mut self.spectrum,
+            &mut self.scratch,
+        );
+
+        // 分析: 位相の進みから各ビンの真の周波数を求める
+        for k in 0..bins {
+            let (magnitude, phase) = self.spectrum[k].to_polar();
+            let mut delta = phase - state.last_phase[k] - k as f32 * expected;
+            state.last_phase[k] = phase;
+            delta -= 2.0 * PI * (delta / (2.0 * PI)).round();
+            let deviation = OVERSAMPLING as f32 * delta / (2.0 * PI);
+            self.analysis_magnitude[k] = magnitude;
+            self.analysis_frequency[k] = (k as f32 + deviation) * self.bin_hz;
+        }
+
+        // ピッチシフト: ビンを ratio 倍の位置へ移す
+        self.synthesis_magnitude.fill(0.0);
+        self.synthesis_frequency.fill(0.0);
+        for k in 0..bins {
+            let target = (k as f32 * ratio).round() as usize;
+            if target < bins {
+                self.synthesis_magnitude[target] += self.analysis_magnitude[k];
+                self.synthesis_frequency[target] = self.analysis_frequency[k] * ratio;
+            }
+        }
+
+        // 合成: 周波数から位相を積み上げる
+        for k in 0..bins {
+            let deviation = self.synthesis_frequency[k] / self.bin_hz - k as f32;
+            state.phase_sum[k] += 2.0 * PI * deviation / OVERSAMPLING as f32 + k as f32 * expected;
+            self.spectrum[k] = Complex::from_polar(self.synthesis_magnitude[k], state.phase_sum[k]);
+        }
+        // 実数信号の直流・ナイキストは虚部を持たない
+        self.spectrum[0].im = 0.0;
+        self.spectrum[bins - 1].im = 0.0;
+        let _ = self.inverse.process_with_scratch(
+            &mut self.spectrum,
+            &mut self.frame,
+            &mut self.scratch,
+        );
+
+        // 窓を掛けて重ね合わせ（ハン窓の2乗を4重に重ねると1.5になる）
+        let scale = 1.0 / (size as f32 * OVERSAMPLING as f32 * 0.375);
+        for ((acc, &frame), &window) in state
+            .accumulator
+            .iter_mut()
+            .zip(&self.frame)
+            .zip(&self.window)
+        {
+            *acc += frame * window * scale;
+        }
+    }
+}
+
+#[cfg(feature = "local-dsp")]
+impl ChannelState {
+    fn new(size: usize) -> Self {
+        let bins = size / 2 + 1;
+        Self {
+            input: vec![0.0; size],
+            output: vec![0.0; size],
+            accumulator: vec![0.0; size],
+            last_phase: vec![0.0; bins],
+            phase_sum: vec![0.0; bins],
+            position: size - size / OVERSAMPLING,
+        }
+    }
+}
+
+#[cfg(feature = "local-dsp")]
+impl DspStage for PitchShifter {
+    fn name(&self) -> &str {
+        "pitch-shift"
+    }
+
+    fn prepare(&mut self, sample_rate: u32, channels: u16) {
+        if self.format == Some((sample_rate, channels)) {
+            return;
+        }
+        let fft = Fft::new(sample_rate);
+        self.channels = (0..channels.max(1))
+            .map(|_| ChannelState::new(fft.size))
+            .collect();
+        self.fft = Some(fft);
+        self.format = Some((sample_rate, channels));
+    }
+
+    fn process(&mut self, samples: &mut [f32]) {
+        let Some(fft) = &mut self.fft else {
+            return;
+        };
+        let size = fft.size;
+        let hop = size / OVERSAMPLING;
+        let latency = size - hop;
+        let channel_count = self.channels.len();
+
+        for (channel, state) in self.channels.iter_mut().enumerate() {
+            for sample in samples.iter_mut().skip(channel).step_by(channel_count) {
+                state.input[state.position] = *sample;
+                *sample = state.output[state.position - latency];
+                state.position += 1;
+
+                if state.position >= size {
+                    state.position = latency;
+                    fft.shift_frame(state, self.ratio);
+
+                    state.output[..hop].copy_from_slice(&state.accumulator[..hop]);
+                    state.accumulator.copy_within(hop.., 0);
+                    state.accumulator[size - hop..].fill(0.0);
+                    state.input.copy_within(hop.., 0);
+                }
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        if let Some(fft) = &self.fft {
+            for state in &mut self.channels {
+                *state = ChannelState::new(fft.size);
+            }
+        }
+    }
+}
+
+/// `local-dsp` なしのビルドのピッチシフター（音声をそのまま通す）
+///
+/// 使う機能は、作る前に [`ensure_available`] で断ります。
+#[cfg(not(feature = "local-dsp"))]
+pub struct PitchShifter {
+    semitones: f32,
+}
+
+#[cfg(not(feature = "local-dsp"))]
+impl PitchShifter {
+    pub fn new(semitones: f32) -> Self {
+        Self { semitones }
+    }
+
+    pub fn set_semitones(&mut self, semitones: f32) {
+        self.semitones = semitones;
+    }
+
+    pub fn semitones(&self) -> f32 {
+        self.semitones
+    }
+
+    pub fn latency_frames(&self) -> usize {
+        0
+    }
+
+    pub fn shift_all(&mut self, samples: &[f32], _sample_rate: u32, _channels: u16) -> Vec<f32> {
+        samples.to_vec()
+    }
+}
+
+#[cfg(not(feature = "local-dsp"))]
+impl DspStage for PitchShifter {
+    fn name(&self) -> &str {
+        "pitch-shift"
+    }
+
+    fn process(&mut self, _samples: &mut [f32]) {}
+}
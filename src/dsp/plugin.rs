@@ -0,0 +1,134 @@
+use anyhow::{Context, Result};
+use libloading::Library;
+use std::ffi::{c_char, c_void, CString};
+use std::path::Path;
+
+use super::DspStage;
+
+/// プラグインABIバージョン（互換性のない変更時に上げる）
+pub const PLUGIN_ABI_VERSION: u32 = 1;
+
+type AbiVersionFn = unsafe extern "C" fn() -> u32;
+type CreateFn = unsafe extern "C" fn(params_json: *const c_char) -> *mut c_void;
+type PrepareFn = unsafe extern "C" fn(handle: *mut c_void, sample_rate: u32, channels: u16);
+type ProcessFn = unsafe extern "C" fn(handle: *mut c_void, samples: *mut f32, len: usize);
+type ResetFn = unsafe extern "C" fn(handle: *mut c_void);
+type DestroyFn = unsafe extern "C" fn(handle: *mut c_void);
+
+/// 共有ライブラリから読み込んだDSPステージ
+///
+/// プラグインは以下のC ABI関数をエクスポートする必要があります：
+/// - `makebeliv_abi_version() -> u32`
+/// - `makebeliv_stage_create(params_json: *const c_char) -> *mut c_void`
+/// - `makebeliv_stage_prepare(handle, sample_rate: u32, channels: u16)`
+/// - `makebeliv_stage_process(handle, samples: *mut f32, len: usize)`
+/// - `makebeliv_stage_reset(handle)`（省略可）
+/// - `makebeliv_stage_destroy(handle)`
+pub struct PluginStage {
+    name: String,
+    handle: *mut c_void,
+    prepare: PrepareFn,
+    process: ProcessFn,
+    reset: Option<ResetFn>,
+    destroy: DestroyFn,
+    // handleより後に破棄する必要がある
+    _library: Library,
+}
+
+// プラグインのハンドルは単一スレッドからのみ使用される
+unsafe impl Send for PluginStage {}
+
+impl PluginStage {
+    /// 共有ライブラリを読み込み、ステージを作成
+    pub fn load(path: &Path, params: &toml::Table) -> Result<Self> {
+        let name = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_else(|| path.display().to_string());
+
+        let params_json = CString::new(serde_json::to_string(params)?)
+            .context("プラグインパラメータにNUL文字が含まれています")?;
+
+        // SAFETY: ユーザーが設定で明示したライブラリを読み込む。
+        // シンボルの型は上記ABIとして規定されている。
+        unsafe {
+            let library = Library::new(path)
+                .with_context(|| format!("プラグインを読み込めません: {}", path.display()))?;
+
+            let abi_version: AbiVersionFn = *library
+                .get(b"makebeliv_abi_version\0")
+                .with_context(|| format!("{}: makebeliv_abi_version がありません", name))?;
+            let version = abi_version();
+            if version != PLUGIN_ABI_VERSION {
+                anyhow::bail!(
+                    "{}: ABIバージョン不一致（プラグイン: {}, 本体: {}）",
+                    name,
+                    version,
+                    PLUGIN_ABI_VERSION
+                );
+            }
+
+            let create: CreateFn = *library
+                .get(b"makebeliv_stage_create\0")
+                .with_context(|| format!("{}: makebeliv_stage_create がありません", name))?;
+            let prepare: PrepareFn = *library
+                .get(b"makebeliv_stage_prepare\0")
+                .with_context(|| format!("{}: makebeliv_stage_prepare がありません", name))?;
+            let process: ProcessFn = *library
+                .get(b"makebeliv_stage_process\0")
+                .with_context(|| format!("{}: makebeliv_stage_process がありません", name))?;
+            let destroy: DestroyFn = *library
+                .get(b"makebeliv_stage_destroy\0")
+                .with_context(|| format!("{}: makebeliv_stage_destroy がありません", name))?;
+            let reset: Option<ResetFn> = library
+                .get::<ResetFn>(b"makebeliv_stage_reset\0")
+                .ok()
+                .map(|f| *f);
+
+            let handle = create(params_json.as_ptr());
+            if handle.is_null() {
+                anyhow::bail!("{}: ステージの作成に失敗しました", name);
+            }
+
+            Ok(Self {
+                name,
+                handle,
+                prepare,
+                process,
+                reset,
+                destroy,
+                _library: library,
+            })
+        }
+    }
+}
+
+impl DspStage for PluginStage {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn prepare(&mut self, sample_rate: u32, channels: u16) {
+        // SAFETY: handleはcreateで取得した有効なポインタ
+        unsafe { (self.prepare)(self.handle, sample_rate, channels) }
+    }
+
+    fn process(&mut self, samples: &mut [f32]) {
+        // SAFETY: samplesは有効な可変スライス
+        unsafe { (self.process)(self.handle, samples.as_mut_ptr(), samples.len()) }
+    }
+
+    fn reset(&mut self) {
+        if let Some(reset) = self.reset {
+            // SAFETY: handleはcreateで取得した有効なポインタ
+            unsafe { reset(self.handle) }
+        }
+    }
+}
+
+impl Drop for PluginStage {
+    fn drop(&mut self) {
+        // SAFETY: handleはこの時点でまだ有効で、以後使用されない
+        unsafe { (self.destroy)(self.handle) }
+    }
+}
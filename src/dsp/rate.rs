@@ -0,0 +1,66 @@
+use tracing::debug;
+
+use super::stretch::time_stretch;
+use crate::chaos::XorShift;
+
+/// 速さを変える区間の長さ（ミリ秒）
+const SEGMENT_MS: u32 = 1000;
+
+/// 目標の速さを選び直す間隔（区間の数）
+const SEGMENTS_PER_TARGET: usize = 4;
+
+/// 区間ごとに目標へ近づく割合
+const SMOOTHING: f32 = 0.3;
+
+/// 時間とともにゆっくり変わる話速
+///
+/// 音声を1秒ずつの区間に分け、区間ごとに `±percent` の範囲で速さを変えてWSOLAで伸縮します。
+/// 速さは数秒ごとに選び直す目標へなめらかに近づくため、長いナレーションが
+/// 機械的に一定のテンポに聞こえにくくなります。ピッチは変わらず、全体の長さは少し変わります。
+pub struct RateFluctuation {
+    /// 速さの揺れ幅（±パーセント）
+    percent: f32,
+    rng: XorShift,
+}
+
+impl RateFluctuation {
+    pub fn new(percent: f32) -> Self {
+        Self {
+            percent: percent.abs(),
+            rng: XorShift::from_time(),
+        }
+    }
+
+    /// 乱数の種を固定する（同じ種なら同じ揺らぎになる。マニフェストからの再現用）
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = XorShift::new(seed);
+        self
+    }
+
+    pub fn percent(&self) -> f32 {
+        self.percent
+    }
+
+    /// ファイル全体に適用する
+    pub fn apply_all(&mut self, samples: &[f32], sample_rate: u32, channels: u16) -> Vec<f32> {
+        let segment = (sample_rate * SEGMENT_MS / 1000).max(1) as usize * channels.max(1) as usize;
+        let mut output = Vec::with_capacity(samples.len());
+        let mut speed = self.random_speed();
+        let mut target = speed;
+
+        // 区間の先頭と末尾は元の音声のままなので、つなげても途切れない
+        for (i, chunk) in samples.chunks(segment).enumerate() {
+            if i % SEGMENTS_PER_TARGET == 0 {
+                target = self.random_speed();
+                debug!("話速の揺らぎ: 目標 {:.3}倍", target);
+            }
+            speed += (target - speed) * SMOOTHING;
+            output.extend(time_stretch(chunk, channels, sample_rate, speed));
+        }
+        output
+    }
+
+    fn random_speed(&mut self) -> f32 {
+        1.0 + (self.rng.next_f64() as f32 * 2.0 - 1.0) * self.percent / 100.0
+    }
+}
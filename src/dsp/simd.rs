@@ -0,0 +1,123 @@
+//! サンプル列の基本演算
+//!
+//! aarch64（Raspberry Pi 4/5 など）ではNEON命令で4サンプルずつ処理します。
+//! NEONはaarch64では必ず使えるため、実行時の判定はしません。
+//! それ以外のアーキテクチャでは同じ計算をスカラーで行います（コンパイラの自動ベクトル化に任せる）。
+
+#[cfg(target_arch = "aarch64")]
+use neon as imp;
+#[cfg(not(target_arch = "aarch64"))]
+use scalar as imp;
+
+/// 全サンプルにゲインを掛ける
+pub fn scale(samples: &mut [f32], gain: f32) {
+    imp::scale(samples, gain)
+}
+
+/// 二乗和（RMSの計算用）
+pub fn sum_squares(samples: &[f32]) -> f32 {
+    imp::sum_squares(samples)
+}
+
+/// 絶対値の最大（ピークレベル）
+pub fn peak(samples: &[f32]) -> f32 {
+    imp::peak(samples)
+}
+
+/// f32サンプルを16bit整数に変換して `out` に追加する（±1.0でクリップ）
+pub fn to_i16(samples: &[f32], out: &mut Vec<i16>) {
+    out.reserve(samples.len());
+    imp::to_i16(samples, out)
+}
+
+/// スカラー版（NEONの端数の処理にも使う）
+mod scalar {
+    pub fn scale(samples: &mut [f32], gain: f32) {
+        for sample in samples {
+            *sample *= gain;
+        }
+    }
+
+    pub fn sum_squares(samples: &[f32]) -> f32 {
+        samples.iter().map(|s| s * s).sum()
+    }
+
+    pub fn peak(samples: &[f32]) -> f32 {
+        samples.iter().fold(0.0f32, |peak, s| peak.max(s.abs()))
+    }
+
+    pub fn to_i16(samples: &[f32], out: &mut Vec<i16>) {
+        let scale = i16::MAX as f32;
+        out.extend(
+            samples
+                .iter()
+                .map(|&sample| (sample.clamp(-1.0, 1.0) * scale).round() as i16),
+        );
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+mod neon {
+    use std::arch::aarch64::*;
+
+    /// 1回に処理するサンプル数（128bitレジスタのf32）
+    const LANES: usize = 4;
+
+    // SAFETY（以下すべて）: aarch64ではNEONが常に使え、読み書きは `chunks_exact` の
+    // 4サンプルの範囲に収まる
+
+    pub fn scale(samples: &mut [f32], gain: f32) {
+        let mut chunks = samples.chunks_exact_mut(LANES);
+        for chunk in &mut chunks {
+            unsafe {
+                let v = vld1q_f32(chunk.as_ptr());
+                vst1q_f32(chunk.as_mut_ptr(), vmulq_n_f32(v, gain));
+            }
+        }
+        super::scalar::scale(chunks.into_remainder(), gain);
+    }
+
+    pub fn sum_squares(samples: &[f32]) -> f32 {
+        let mut chunks = samples.chunks_exact(LANES);
+        let total = unsafe {
+            let mut acc = vdupq_n_f32(0.0);
+            for chunk in &mut chunks {
+                let v = vld1q_f32(chunk.as_ptr());
+                acc = vfmaq_f32(acc, v, v);
+            }
+            vaddvq_f32(acc)
+        };
+        total + super::scalar::sum_squares(chunks.remainder())
+    }
+
+    pub fn peak(samples: &[f32]) -> f32 {
+        let mut chunks = samples.chunks_exact(LANES);
+        let peak = unsafe {
+            let mut acc = vdupq_n_f32(0.0);
+            for chunk in &mut chunks {
+                acc = vmaxq_f32(acc, vabsq_f32(vld1q_f32(chunk.as_ptr())));
+            }
+            vmaxvq_f32(acc)
+        };
+        peak.max(super::scalar::peak(chunks.remainder()))
+    }
+
+    pub fn to_i16(samples: &[f32], out: &mut Vec<i16>) {
+        let mut converted = [0i16; LANES];
+        let mut chunks = samples.chunks_exact(LANES);
+        for chunk in &mut chunks {
+            unsafe {
+                let scale = vdupq_n_f32(i16::MAX as f32);
+                let v = vminq_f32(
+                    vmaxq_f32(vld1q_f32(chunk.as_ptr()), vdupq_n_f32(-1.0)),
+                    vdupq_n_f32(1.0),
+                );
+                // 最近接への丸め（偶数丸め）と、飽和つきの16bitへの縮小
+                let ints = vqmovn_s32(vcvtnq_s32_f32(vmulq_f32(v, scale)));
+                vst1_s16(converted.as_mut_ptr(), ints);
+            }
+            out.extend_from_slice(&converted);
+        }
+        super::scalar::to_i16(chunks.remainder(), out);
+    }
+}
@@ -0,0 +1,93 @@
+use super::DspStage;
+
+/// 広げるときに側方成分として足す遅延音の遅れ（ミリ秒）
+const DECORRELATION_MS: u32 = 15;
+
+/// 定位（パン）と広がり
+///
+/// 広がりはミッド・サイドで調整します。1.0でそのまま、0.0でモノラル、1.0を超えると
+/// 少し遅らせた音をサイドに足して、モノラルの声でも左右に広げます。遅延音は左右で
+/// 逆相に入るため、モノラルにまとめたときには打ち消し合います。
+/// パンは中央で音量が変わらない等パワーの法則で、最初の2チャンネルだけに効きます。
+#[derive(Clone)]
+pub struct StereoImage {
+    /// -1.0（左）〜 1.0（右）
+    pan: f32,
+    /// 0.0（モノラル）〜 2.0
+    width: f32,
+    gains: (f32, f32),
+    delay: Vec<f32>,
+    position: usize,
+    channels: usize,
+}
+
+impl StereoImage {
+    pub fn new(pan: f32, width: f32) -> Self {
+        let pan = pan.clamp(-1.0, 1.0);
+        let angle = (pan + 1.0) * std::f32::consts::FRAC_PI_4;
+        Self {
+            pan,
+            width: width.max(0.0),
+            gains: (
+                angle.cos() * std::f32::consts::SQRT_2,
+                angle.sin() * std::f32::consts::SQRT_2,
+            ),
+            delay: Vec::new(),
+            position: 0,
+            channels: 0,
+        }
+    }
+
+    pub fn pan(&self) -> f32 {
+        self.pan
+    }
+
+    pub fn width(&self) -> f32 {
+        self.width
+    }
+
+    /// 左右1組のサンプルを処理する
+    pub fn frame(&mut self, left: f32, right: f32) -> (f32, f32) {
+        let mid = (left + right) * 0.5;
+        let mut side = (left - right) * 0.5 * self.width.min(1.0);
+        if let Some(delayed) = self.delay.get_mut(self.position) {
+            side += (self.width - 1.0).max(0.0) * 0.5 * *delayed;
+            *delayed = mid;
+            self.position = (self.position + 1) % self.delay.len();
+        }
+        ((mid + side) * self.gains.0, (mid - side) * self.gains.1)
+    }
+}
+
+impl DspStage for StereoImage {
+    fn name(&self) -> &str {
+        "stereo"
+    }
+
+    fn prepare(&mut self, sample_rate: u32, channels: u16) {
+        self.channels = channels as usize;
+        let delay = if self.width > 1.0 {
+            (sample_rate * DECORRELATION_MS / 1000).max(1) as usize
+        } else {
+            0
+        };
+        if self.delay.len() != delay {
+            self.delay = vec![0.0; delay];
+            self.position = 0;
+        }
+    }
+
+    fn process(&mut self, samples: &mut [f32]) {
+        if self.channels < 2 {
+            return;
+        }
+        for frame in samples.chunks_exact_mut(self.channels) {
+            (frame[0], frame[1]) = self.frame(frame[0], frame[1]);
+        }
+    }
+
+    fn reset(&mut self) {
+        self.delay.fill(0.0);
+        self.position = 0;
+    }
+}
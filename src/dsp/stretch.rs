@@ -0,0 +1,87 @@
+use super::analysis::downmix;
+
+/// WSOLAの窓の長さ（ミリ秒）
+const WINDOW_MS: u32 = 20;
+
+/// つなぎ目を探す範囲（±ミリ秒）
+const SEARCH_MS: u32 = 5;
+
+/// WSOLAで音声を `speed` 倍速にする（ピッチは変えない）
+///
+/// 窓を半分ずつ重ねながら、直前の区間と波形が最もよく続く位置を探して切り貼りします。
+/// 先頭と末尾は元の音声のままにするため、連続するチャンクに使っても継ぎ目は途切れません。
+/// 短すぎる音声はそのまま返します。
+pub fn time_stretch(samples: &[f32], channels: u16, sample_rate: u32, speed: f32) -> Vec<f32> {
+    let channels = channels.max(1) as usize;
+    let frames = samples.len() / channels;
+    let window = (sample_rate * WINDOW_MS / 1000) as usize;
+    let hop = window / 2;
+    let search = (sample_rate * SEARCH_MS / 1000) as usize;
+    if (speed - 1.0).abs() < f32::EPSILON || hop == 0 || frames < window * 2 + search {
+        return samples.to_vec();
+    }
+
+    let mono = downmix(samples, channels as u16);
+    let fade: Vec<f32> = (0..window)
+        .map(|i| 0.5 - 0.5 * (2.0 * std::f32::consts::PI * i as f32 / window as f32).cos())
+        .collect();
+
+    let mut output = vec![0.0; (frames as f32 / speed) as usize * channels + window * channels];
+    let mut output_position = 0;
+    let mut input_position = 0;
+
+    // 最初の区間は前半に窓をかけずに置く（先頭がフェードインしないように）
+    for (i, &gain) in fade.iter().enumerate() {
+        let gain = if i < hop { 1.0 } else { gain };
+        for c in 0..channels {
+            output[i * channels + c] = samples[i * channels + c] * gain;
+        }
+    }
+
+    loop {
+        // 直前の区間の続き（これに最も似た位置を探す）
+        let natural = input_position + hop;
+        let nominal = ((output_position + hop) as f32 * speed) as usize;
+        let latest = frames - window;
+        if nominal + search > latest
+            || natural + hop > frames
+            || (output_position + hop + window) * channels > output.len()
+        {
+            break;
+        }
+
+        let reference = &mono[natural..natural + hop];
+        let best = (nominal.saturating_sub(search)..=nominal + search)
+            .max_by(|&a, &b| {
+                similarity(reference, &mono[a..a + hop])
+                    .total_cmp(&similarity(reference, &mono[b..b + hop]))
+            })
+            .unwrap_or(nominal);
+
+        output_position += hop;
+        input_position = best;
+        for (i, &gain) in fade.iter().enumerate() {
+            let src = (input_position + i) * channels;
+            let dst = (output_position + i) * channels;
+            for c in 0..channels {
+                output[dst + c] += samples[src + c] * gain;
+            }
+        }
+    }
+
+    // 最後の区間の後半以降は元の音声をそのまま続ける
+    output.truncate((output_position + hop) * channels);
+    output.extend_from_slice(&samples[(input_position + hop) * channels..]);
+    output
+}
+
+/// 正規化した相互相関
+fn similarity(reference: &[f32], candidate: &[f32]) -> f32 {
+    let (dot, energy) = reference
+        .iter()
+        .zip(candidate)
+        .fold((0.0, 0.0), |(dot, energy), (r, c)| {
+            (dot + r * c, energy + c * c)
+        });
+    dot / energy.sqrt().max(f32::EPSILON)
+}
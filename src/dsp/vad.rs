@@ -0,0 +1,96 @@
+use std::str::FromStr;
+
+use super::analysis;
+use super::phrase::VAD_FRAME_MS;
+
+/// 発話が終わった後も送り続ける長さ（ミリ秒）。語尾や息継ぎを切らないため
+pub const HANGOVER_MS: u32 = 300;
+
+/// 無音の間に流すコンフォートノイズのレベル（dBFS）。背景ノイズがないときの無音の不自然さを消す
+pub const COMFORT_NOISE_DB: f32 = -66.0;
+
+/// 無音のチャンクを見分けて、サーバーへ送らないためのゲート（`--vad-threshold`）
+///
+/// チャンクを10msごとに区切り、どこかがしきい値以上なら発話とみなします。
+/// 発話の後は [`HANGOVER_MS`] の間、無音でも送り続けます。
+pub struct VoiceGate {
+    threshold_db: f32,
+    /// 最後に発話があってからの長さ（ミリ秒）
+    silent_ms: u32,
+    /// コンフォートノイズの乱数の状態
+    seed: u32,
+}
+
+impl VoiceGate {
+    pub fn new(threshold_db: f32) -> Self {
+        Self {
+            threshold_db,
+            // 話し始めるまでは送らない
+            silent_ms: HANGOVER_MS + 1,
+            seed: 0x2545_f491,
+        }
+    }
+
+    pub fn threshold_db(&self) -> f32 {
+        self.threshold_db
+    }
+
+    /// チャンクを送るか（発話を含むか、発話の直後なら送る）
+    pub fn is_open(&mut self, chunk: &[f32], sample_rate: u32, channels: u16) -> bool {
+        let mono = analysis::downmix(chunk, channels);
+        let frame_len = (sample_rate * VAD_FRAME_MS / 1000).max(1) as usize;
+        let voiced = mono
+            .chunks(frame_len)
+            .any(|frame| analysis::rms_db(frame) >= self.threshold_db);
+
+        if voiced {
+            self.silent_ms = 0;
+            return true;
+        }
+        let chunk_ms = (mono.len() as u64 * 1000 / sample_rate.max(1) as u64) as u32;
+        self.silent_ms = self.silent_ms.saturating_add(chunk_ms);
+        self.silent_ms <= HANGOVER_MS
+    }
+
+    /// 送らなかったチャンクの代わりに流すコンフォートノイズ（`len` サンプル）
+    pub fn comfort_noise(&mut self, len: usize) -> Vec<f32> {
+        let level = analysis::from_db(COMFORT_NOISE_DB);
+        (0..len)
+            .map(|_| {
+                // xorshift32
+                self.seed ^= self.seed << 13;
+                self.seed ^= self.seed >> 17;
+                self.seed ^= self.seed << 5;
+                (self.seed as f32 / u32::MAX as f32 * 2.0 - 1.0) * level
+            })
+            .collect()
+    }
+}
+
+/// 無音のチャンクを送らないか（`--vad`）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VadMode {
+    On,
+    Off,
+}
+
+impl FromStr for VadMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s.to_lowercase().as_str() {
+            "on" => Ok(Self::On),
+            "off" => Ok(Self::Off),
+            _ => anyhow::bail!("不明なVADの指定: {}（on, off）", s),
+        }
+    }
+}
+
+impl std::fmt::Display for VadMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::On => "on",
+            Self::Off => "off",
+        })
+    }
+}
@@ -0,0 +1,162 @@
+use anyhow::{Context, Result};
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+use tracing::{info, warn};
+
+use crate::audio::AudioBuffer;
+
+/// 一度に読み込むフレーム数
+const READ_FRAMES: usize = 1024;
+
+/// 生PCMのサンプル形式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SampleFormat {
+    S16Le,
+    S24Le,
+    S32Le,
+    F32Le,
+}
+
+impl SampleFormat {
+    pub fn bytes_per_sample(&self) -> usize {
+        match self {
+            SampleFormat::S16Le => 2,
+            SampleFormat::S24Le => 3,
+            SampleFormat::S32Le | SampleFormat::F32Le => 4,
+        }
+    }
+
+    fn decode(&self, bytes: &[u8]) -> f32 {
+        match self {
+            SampleFormat::S16Le => i16::from_le_bytes([bytes[0], bytes[1]]) as f32 / 32768.0,
+            SampleFormat::S24Le => {
+                // 上位バイトに詰めて符号拡張
+                i32::from_le_bytes([0, bytes[0], bytes[1], bytes[2]]) as f32 / 2147483648.0
+            }
+            SampleFormat::S32Le => {
+                i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f32 / 2147483648.0
+            }
+            SampleFormat::F32Le => f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+        }
+    }
+}
+
+/// 生PCMのフォーマット（例: `s16le:48000:1`）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PcmFormat {
+    pub sample_format: SampleFormat,
+    pub sample_rate: u32,
+    pub channels: u16,
+}
+
+impl FromStr for PcmFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let parts: Vec<&str> = s.split(':').collect();
+        let [format, rate, channels] = parts[..] else {
+            anyhow::bail!(
+                "フォーマットは <形式>:<レート>:<チャンネル数> で指定してください: {}",
+                s
+            );
+        };
+
+        let sample_format = match format.to_ascii_lowercase().as_str() {
+            "s16le" => SampleFormat::S16Le,
+            "s24le" => SampleFormat::S24Le,
+            "s32le" => SampleFormat::S32Le,
+            "f32le" => SampleFormat::F32Le,
+            other => anyhow::bail!(
+                "未対応のサンプル形式: {}（s16le, s24le, s32le, f32le）",
+                other
+            ),
+        };
+        let sample_rate: u32 = rate
+            .parse()
+            .with_context(|| format!("不正なサンプリングレート: {}", rate))?;
+        let channels: u16 = channels
+            .parse()
+            .with_context(|| format!("不正なチャンネル数: {}", channels))?;
+        if sample_rate == 0 || channels == 0 {
+            anyhow::bail!("サンプリングレートとチャンネル数は1以上を指定してください");
+        }
+
+        Ok(Self {
+            sample_format,
+            sample_rate,
+            channels,
+        })
+    }
+}
+
+impl std::fmt::Display for PcmFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let format = match self.sample_format {
+            SampleFormat::S16Le => "s16le",
+            SampleFormat::S24Le => "s24le",
+            SampleFormat::S32Le => "s32le",
+            SampleFormat::F32Le => "f32le",
+        };
+        write!(f, "{}:{}:{}", format, self.sample_rate, self.channels)
+    }
+}
+
+/// FIFO（名前付きパイプ）から生PCMを読み込んでバッファへ送る
+///
+/// 書き込み側が閉じても再度オープンして待ち続けます。
+/// スレッドはプロセス終了まで動作します。
+pub fn spawn_reader(path: &Path, format: PcmFormat, buffer: AudioBuffer) -> Result<()> {
+    let path: PathBuf = path.to_path_buf();
+    if !path.exists() {
+        anyhow::bail!("FIFOが見つかりません: {}", path.display());
+    }
+
+    std::thread::Builder::new()
+        .name("fifo-input".into())
+        .spawn(move || loop {
+            if let Err(e) = read_until_eof(&path, format, &buffer) {
+                warn!("FIFO読み込みエラー: {:#}", e);
+                std::thread::sleep(Duration::from_secs(1));
+            }
+        })
+        .context("FIFO読み込みスレッドの起動に失敗")?;
+
+    Ok(())
+}
+
+fn read_until_eof(path: &Path, format: PcmFormat, buffer: &AudioBuffer) -> Result<()> {
+    // 書き込み側が接続するまでブロックする
+    let mut file =
+        File::open(path).with_context(|| format!("FIFOを開けません: {}", path.display()))?;
+    info!("FIFO入力接続: {}", path.display());
+
+    let sample_bytes = format.sample_format.bytes_per_sample();
+    let mut raw = vec![0u8; READ_FRAMES * format.channels as usize * sample_bytes];
+    let mut pending = 0;
+    let mut samples = Vec::with_capacity(READ_FRAMES * format.channels as usize);
+
+    loop {
+        let read = file.read(&mut raw[pending..])?;
+        if read == 0 {
+            info!("FIFO入力切断: {}", path.display());
+            return Ok(());
+        }
+        pending += read;
+
+        // サンプル境界までをデコードし、端数は次回に回す
+        let usable = pending - pending % sample_bytes;
+        samples.clear();
+        samples.extend(
+            raw[..usable]
+                .chunks_exact(sample_bytes)
+                .map(|b| format.sample_format.decode(b)),
+        );
+        buffer.push(&samples);
+
+        raw.copy_within(usable..pending, 0);
+        pending -= usable;
+    }
+}
@@ -0,0 +1,266 @@
+use anyhow::{Context, Result};
+use std::fmt::Write as _;
+use std::path::Path;
+use std::str::FromStr;
+
+/// 処理グラフの出力形式（`--dump-pipeline`）
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum GraphFormat {
+    /// 人が読むための一覧
+    #[default]
+    Text,
+    /// Graphviz の DOT（`dot -Tsvg` などで図にする）
+    Dot,
+}
+
+impl FromStr for GraphFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "text" => Ok(Self::Text),
+            "dot" => Ok(Self::Dot),
+            _ => anyhow::bail!("不明なグラフの形式: {}（text, dot）", s),
+        }
+    }
+}
+
+impl std::fmt::Display for GraphFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Text => "text",
+            Self::Dot => "dot",
+        })
+    }
+}
+
+/// 処理グラフの1段
+#[derive(Debug, Clone)]
+pub struct Stage {
+    name: String,
+    details: Vec<String>,
+    /// この段が出す音声の形式（サンプルレート, チャンネル数）
+    format: Option<(u32, u16)>,
+    /// バッファの大きさ（表示用）
+    buffer: Option<String>,
+    /// この段で増える遅延（ミリ秒、分からなければNone）
+    latency_ms: Option<f64>,
+}
+
+impl Stage {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            details: Vec::new(),
+            format: None,
+            buffer: None,
+            latency_ms: None,
+        }
+    }
+
+    pub fn detail(mut self, detail: impl Into<String>) -> Self {
+        self.details.push(detail.into());
+        self
+    }
+
+    pub fn format(mut self, sample_rate: u32, channels: u16) -> Self {
+        self.format = Some((sample_rate, channels));
+        self
+    }
+
+    pub fn buffer(mut self, buffer: impl Into<String>) -> Self {
+        self.buffer = Some(buffer.into());
+        self
+    }
+
+    pub fn latency_ms(mut self, latency_ms: f64) -> Self {
+        self.latency_ms = Some(latency_ms);
+        self
+    }
+
+    fn format_label(&self) -> Option<String> {
+        self.format
+            .map(|(rate, channels)| format!("{}Hz/{}ch", rate, channels))
+    }
+
+    /// 形式・バッファ・遅延をまとめた1行
+    fn summary(&self) -> Vec<String> {
+        let mut parts: Vec<String> = self.format_label().into_iter().collect();
+        if let Some(buffer) = &self.buffer {
+            parts.push(format!("バッファ {}", buffer));
+        }
+        if let Some(latency) = self.latency_ms {
+            parts.push(format!("遅延 {:.1}ms", latency));
+        }
+        parts
+    }
+}
+
+/// 構築した処理グラフ（段と、段から段への音声の流れ）
+///
+/// 主経路（入力から出力まで）は `then` で、録音やサイドトーンのような分岐は
+/// `branch` でつなぎます。主経路の遅延の合計と、構成の問題（二重の
+/// リサンプリングなど）の注意書きもあわせて出力します。
+#[derive(Debug, Default)]
+pub struct PipelineGraph {
+    stages: Vec<Stage>,
+    edges: Vec<(usize, usize)>,
+    /// 主経路の段
+    main: Vec<usize>,
+    notes: Vec<String>,
+}
+
+impl PipelineGraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 主経路の最後に段をつなぐ（段の番号を返す）
+    pub fn then(&mut self, stage: Stage) -> usize {
+        let id = self.push(stage);
+        if let Some(&last) = self.main.last() {
+            self.edges.push((last, id));
+        }
+        self.main.push(id);
+        id
+    }
+
+    /// `from` から分かれる段を足す（段の番号を返す）
+    pub fn branch(&mut self, from: usize, stage: Stage) -> usize {
+        let id = self.push(stage);
+        self.edges.push((from, id));
+        id
+    }
+
+    fn push(&mut self, stage: Stage) -> usize {
+        self.stages.push(stage);
+        self.stages.len() - 1
+    }
+
+    /// 構成についての注意書きを足す
+    pub fn note(&mut self, note: impl Into<String>) {
+        self.notes.push(note.into());
+    }
+
+    /// 主経路の遅延の合計（分からない段は0として数える）
+    pub fn main_latency_ms(&self) -> f64 {
+        self.main
+            .iter()
+            .filter_map(|&id| self.stages[id].latency_ms)
+            .sum()
+    }
+
+    pub fn render(&self, format: GraphFormat) -> String {
+        match format {
+            GraphFormat::Text => self.render_text(),
+            GraphFormat::Dot => self.render_dot(),
+        }
+    }
+
+    /// 標準出力か、指定したファイルへ書き出す
+    pub fn write(&self, format: GraphFormat, path: Option<&Path>) -> Result<()> {
+        let rendered = self.render(format);
+        match path {
+            Some(path) => std::fs::write(path, rendered).with_context(|| {
+                format!("パイプラインのグラフを書き出せません: {}", path.display())
+            }),
+            None => {
+                print!("{}", rendered);
+                Ok(())
+            }
+        }
+    }
+
+    fn render_text(&self) -> String {
+        let mut out = format!(
+            "\n🔧 パイプライン（主経路の遅延の見積もり: 約{:.0}ms）\n",
+            self.main_latency_ms()
+        );
+        for (id, stage) in self.stages.iter().enumerate() {
+            let marker = if self.main.contains(&id) { ' ' } else { '┊' };
+            let _ = write!(out, "  {}{:>2}. {}", marker, id + 1, stage.name);
+            let summary = stage.summary();
+            if !summary.is_empty() {
+                let _ = write!(out, "  [{}]", summary.join(" / "));
+            }
+            let next: Vec<String> = self
+                .edges
+                .iter()
+                .filter(|(from, _)| *from == id)
+                .map(|(_, to)| (to + 1).to_string())
+                .collect();
+            if !next.is_empty() {
+                let _ = write!(out, "  → {}", next.join(", "));
+            }
+            out.push('\n');
+            for detail in &stage.details {
+                let _ = writeln!(out, "        {}", detail);
+            }
+        }
+        for note in &self.notes {
+            let _ = writeln!(out, "  ⚠ {}", note);
+        }
+        out
+    }
+
+    fn render_dot(&self) -> String {
+        let mut out = String::from("digraph makebeliv {\n    rankdir=LR;\n    node [shape=box, fontname=\"sans-serif\"];\n");
+        let _ = writeln!(
+            out,
+            "    label=\"主経路の遅延の見積もり: 約{:.0}ms\";",
+            self.main_latency_ms()
+        );
+        for (id, stage) in self.stages.iter().enumerate() {
+            let mut lines = vec![stage.name.clone()];
+            lines.extend(stage.details.iter().cloned());
+            lines.extend(stage.summary());
+            let style = if self.main.contains(&id) {
+                ""
+            } else {
+                ", style=dashed"
+            };
+            let _ = writeln!(
+                out,
+                "    s{} [label=\"{}\"{}];",
+                id + 1,
+                lines
+                    .iter()
+                    .map(|line| escape(line))
+                    .collect::<Vec<_>>()
+                    .join("\\n"),
+                style
+            );
+        }
+        for &(from, to) in &self.edges {
+            match self.stages[from].format_label() {
+                Some(label) => {
+                    let _ = writeln!(
+                        out,
+                        "    s{} -> s{} [label=\"{}\"];",
+                        from + 1,
+                        to + 1,
+                        label
+                    );
+                }
+                None => {
+                    let _ = writeln!(out, "    s{} -> s{};", from + 1, to + 1);
+                }
+            }
+        }
+        for (i, note) in self.notes.iter().enumerate() {
+            let _ = writeln!(
+                out,
+                "    note{} [shape=note, color=orange, label=\"⚠ {}\"];",
+                i + 1,
+                escape(note)
+            );
+        }
+        out.push_str("}\n");
+        out
+    }
+}
+
+/// DOTの文字列に入れられるようにする
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
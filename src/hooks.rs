@@ -0,0 +1,102 @@
+use std::process::Stdio;
+use tokio::process::Command;
+use tracing::{debug, info, warn};
+
+use crate::config::HooksConfig;
+
+/// フックを発火するイベント
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookEvent {
+    /// リアルタイム変換セッション開始
+    SessionStart,
+    /// リアルタイム変換セッション終了
+    SessionStop,
+    /// セッション中にサーバーへ接続できなくなった
+    ServerLost,
+}
+
+impl HookEvent {
+    pub fn name(&self) -> &'static str {
+        match self {
+            HookEvent::SessionStart => "session_start",
+            HookEvent::SessionStop => "session_stop",
+            HookEvent::ServerLost => "server_lost",
+        }
+    }
+}
+
+/// ライフサイクルフック
+///
+/// 設定されたシェルコマンドを非同期に起動します。
+/// セッション情報は `MAKEBELIV_*` 環境変数で渡します。
+#[derive(Debug, Clone, Default)]
+pub struct Hooks {
+    config: HooksConfig,
+    env: Vec<(String, String)>,
+}
+
+impl Hooks {
+    pub fn new(config: HooksConfig) -> Self {
+        Self {
+            config,
+            env: Vec::new(),
+        }
+    }
+
+    /// 全フックに渡す環境変数を追加
+    pub fn env(mut self, key: &str, value: impl ToString) -> Self {
+        self.env.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    fn command_for(&self, event: HookEvent) -> Option<&str> {
+        match event {
+            HookEvent::SessionStart => self.config.on_session_start.as_deref(),
+            HookEvent::SessionStop => self.config.on_session_stop.as_deref(),
+            HookEvent::ServerLost => self.config.on_server_lost.as_deref(),
+        }
+    }
+
+    /// イベントに対応するコマンドを起動（終了は待たない）
+    pub fn fire(&self, event: HookEvent, extra_env: &[(&str, String)]) {
+        let Some(command) = self.command_for(event) else {
+            return;
+        };
+
+        info!("フック実行: {} → {}", event.name(), command);
+
+        let mut cmd = shell_command(command);
+        cmd.env("MAKEBELIV_EVENT", event.name())
+            .envs(self.env.iter().map(|(k, v)| (k.as_str(), v.as_str())))
+            .envs(extra_env.iter().map(|(k, v)| (*k, v.as_str())))
+            .stdin(Stdio::null());
+
+        match cmd.spawn() {
+            Ok(mut child) => {
+                let name = event.name();
+                tokio::spawn(async move {
+                    match child.wait().await {
+                        Ok(status) if status.success() => debug!("フック完了: {}", name),
+                        Ok(status) => warn!("フックが失敗しました: {} ({})", name, status),
+                        Err(e) => warn!("フックの待機エラー: {} ({})", name, e),
+                    }
+                });
+            }
+            Err(e) => warn!("フックを起動できません: {} ({})", event.name(), e),
+        }
+    }
+}
+
+#[cfg(windows)]
+fn shell_command(command: &str) -> Command {
+    let mut cmd = Command::new("cmd");
+    cmd.args(["/C", command]);
+    cmd
+}
+
+#[cfg(not(windows))]
+fn shell_command(command: &str) -> Command {
+    let mut cmd = Command::new("sh");
+    cmd.args(["-c", command]);
+    cmd
+}
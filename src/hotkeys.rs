@@ -0,0 +1,253 @@
+use anyhow::Result;
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use std::io::IsTerminal;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tracing::debug;
+
+use crate::config::{HotkeysConfig, Preset};
+
+/// キー入力を待つ間隔（この間隔で受け手が閉じたか確認する）
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// `monitor` の実行中にキーで切り替える操作
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HotkeyAction {
+    /// 変換せずに元の声をそのまま流す／戻す
+    Bypass,
+    /// 出力を無音にする／戻す
+    Mute,
+    /// ピッチを半音上げる
+    PitchUp,
+    /// ピッチを半音下げる
+    PitchDown,
+    /// 次のプリセットに切り替える
+    NextPreset,
+}
+
+impl std::fmt::Display for HotkeyAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Bypass => "バイパス",
+            Self::Mute => "ミュート",
+            Self::PitchUp => "ピッチ+1",
+            Self::PitchDown => "ピッチ-1",
+            Self::NextPreset => "次のプリセット",
+        })
+    }
+}
+
+/// キーと操作の対応（`[hotkeys]`）
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    /// キー、操作、設定に書かれたキーの名前
+    bindings: Vec<(KeyCode, HotkeyAction, String)>,
+}
+
+impl Keymap {
+    /// 設定からキーの対応を作る（空文字列のキーは割り当てない）
+    pub fn from_config(config: &HotkeysConfig) -> Result<Self> {
+        let mut bindings: Vec<(KeyCode, HotkeyAction, String)> = Vec::new();
+        for (name, spec, action) in [
+            ("bypass", &config.bypass, HotkeyAction::Bypass),
+            ("mute", &config.mute, HotkeyAction::Mute),
+            ("pitch_up", &config.pitch_up, HotkeyAction::PitchUp),
+            ("pitch_down", &config.pitch_down, HotkeyAction::PitchDown),
+            ("next_preset", &config.next_preset, HotkeyAction::NextPreset),
+        ] {
+            let spec = spec.trim();
+            if spec.is_empty() {
+                continue;
+            }
+            let key = parse_key(spec)
+                .map_err(|e| anyhow::anyhow!("[hotkeys] {} の値が不正です: {}", name, e))?;
+            if let Some((_, other, _)) = bindings.iter().find(|(k, _, _)| *k == key) {
+                anyhow::bail!(
+                    "[hotkeys] {} のキー {} は「{}」と重なっています",
+                    name,
+                    spec,
+                    other
+                );
+            }
+            bindings.push((key, action, spec.to_string()));
+        }
+        Ok(Self { bindings })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bindings.is_empty()
+    }
+
+    /// 起動時に表示する割り当ての一覧（例: `b バイパス / m ミュート`）
+    pub fn describe(&self) -> String {
+        self.bindings
+            .iter()
+            .map(|(_, action, spec)| format!("{} {}", spec, action))
+            .collect::<Vec<_>>()
+            .join(" / ")
+    }
+
+    fn action(&self, key: KeyCode) -> Option<HotkeyAction> {
+        let key = match key {
+            KeyCode::Char(c) => KeyCode::Char(c.to_ascii_lowercase()),
+            key => key,
+        };
+        self.bindings
+            .iter()
+            .find(|(k, _, _)| *k == key)
+            .map(|(_, action, _)| *action)
+    }
+}
+
+/// キーの名前を解釈する（1文字、space, tab, enter, up, down, left, right, f1〜f12）
+fn parse_key(spec: &str) -> Result<KeyCode> {
+    let lower = spec.to_lowercase();
+    let mut chars = lower.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return Ok(KeyCode::Char(c));
+    }
+    Ok(match lower.as_str() {
+        "space" => KeyCode::Char(' '),
+        "tab" => KeyCode::Tab,
+        "enter" => KeyCode::Enter,
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        _ => match lower.strip_prefix('f').and_then(|n| n.parse::<u8>().ok()) {
+            Some(n @ 1..=12) => KeyCode::F(n),
+            _ => anyhow::bail!(
+                "不明なキー: {}（1文字, space, tab, enter, up, down, left, right, f1〜f12）",
+                spec
+            ),
+        },
+    })
+}
+
+/// ホットキーの設定と、切り替え先のプリセット
+pub struct Hotkeys {
+    pub keymap: Keymap,
+    /// プリセットの名前と内容（名前順）
+    pub presets: Vec<(String, Preset)>,
+    /// 今使っているプリセット
+    pub current_preset: Option<String>,
+    /// プリセットにない項目に使う、起動時のモデルとピッチ
+    pub(crate) base: (String, i32),
+}
+
+impl Hotkeys {
+    /// `current_preset` は起動時に使っているプリセット（`--preset`）
+    pub fn new(
+        keymap: Keymap,
+        presets: Vec<(String, Preset)>,
+        current_preset: Option<String>,
+    ) -> Self {
+        Self {
+            keymap,
+            presets,
+            current_preset,
+            base: (String::new(), 0),
+        }
+    }
+}
+
+/// キー入力の受け手（ドロップで端末の設定を戻す）
+pub struct HotkeyListener {
+    rx: mpsc::UnboundedReceiver<HotkeyAction>,
+    #[cfg(unix)]
+    _terminal: unix::CbreakGuard,
+}
+
+impl HotkeyListener {
+    pub async fn recv(&mut self) -> Option<HotkeyAction> {
+        self.rx.recv().await
+    }
+}
+
+/// 端末のキー入力を別スレッドで読み始める（標準入力が端末でなければ None）
+///
+/// Unixでは端末の行単位の入力とエコーだけを止めます。Ctrl+Cはそのまま効き、
+/// ログの表示も崩れません。
+pub fn listen(keymap: Keymap) -> Result<Option<HotkeyListener>> {
+    if !std::io::stdin().is_terminal() {
+        return Ok(None);
+    }
+    #[cfg(unix)]
+    let terminal = unix::CbreakGuard::enable()?;
+
+    let (tx, rx) = mpsc::unbounded_channel();
+    std::thread::Builder::new()
+        .name("hotkeys".into())
+        .spawn(move || {
+            while !tx.is_closed() {
+                match event::poll(POLL_INTERVAL) {
+                    Ok(false) => continue,
+                    Ok(true) => {}
+                    Err(e) => {
+                        debug!("キー入力を読めません: {}", e);
+                        return;
+                    }
+                }
+                let Ok(Event::Key(key)) = event::read() else {
+                    continue;
+                };
+                if key.kind == KeyEventKind::Release
+                    || key
+                        .modifiers
+                        .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT)
+                {
+                    continue;
+                }
+                if let Some(action) = keymap.action(key.code) {
+                    if tx.send(action).is_err() {
+                        return;
+                    }
+                }
+            }
+        })?;
+
+    Ok(Some(HotkeyListener {
+        rx,
+        #[cfg(unix)]
+        _terminal: terminal,
+    }))
+}
+
+#[cfg(unix)]
+mod unix {
+    use std::io;
+
+    /// 端末を1キーずつ読める状態（非カノニカル・エコーなし）にし、ドロップで戻す
+    pub struct CbreakGuard {
+        saved: libc::termios,
+    }
+
+    impl CbreakGuard {
+        pub fn enable() -> io::Result<Self> {
+            // SAFETY: termios は tcgetattr が埋める。標準入力が端末であることは呼び出し側で確認済み
+            unsafe {
+                let mut termios: libc::termios = std::mem::zeroed();
+                if libc::tcgetattr(libc::STDIN_FILENO, &mut termios) != 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                let saved = termios;
+                termios.c_lflag &= !(libc::ICANON | libc::ECHO);
+                termios.c_cc[libc::VMIN] = 1;
+                termios.c_cc[libc::VTIME] = 0;
+                if libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &termios) != 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                Ok(Self { saved })
+            }
+        }
+    }
+
+    impl Drop for CbreakGuard {
+        fn drop(&mut self) {
+            // SAFETY: enable で読み取った元の設定を書き戻すだけ
+            unsafe {
+                libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &self.saved);
+            }
+        }
+    }
+}
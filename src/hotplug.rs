@@ -0,0 +1,125 @@
+use anyhow::Result;
+use cpal::traits::{DeviceTrait, HostTrait};
+use std::collections::BTreeSet;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tracing::debug;
+
+use crate::device;
+
+/// デバイス一覧を確認する間隔（cpalには接続イベントがないためポーリングする）
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// デバイスの入出力方向
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeviceDirection {
+    Input,
+    Output,
+}
+
+impl std::fmt::Display for DeviceDirection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Input => "入力",
+            Self::Output => "出力",
+        })
+    }
+}
+
+/// デバイスの接続・切断
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeviceEvent {
+    Added {
+        direction: DeviceDirection,
+        name: String,
+    },
+    Removed {
+        direction: DeviceDirection,
+        name: String,
+    },
+}
+
+impl std::fmt::Display for DeviceEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Added { direction, name } => write!(f, "+ {}: {}", direction, name),
+            Self::Removed { direction, name } => write!(f, "- {}: {}", direction, name),
+        }
+    }
+}
+
+/// ある時点のデバイス名一覧
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DeviceSnapshot {
+    inputs: BTreeSet<String>,
+    outputs: BTreeSet<String>,
+}
+
+impl DeviceSnapshot {
+    /// 選択中のホストAPIのデバイス一覧を取得
+    pub fn capture() -> Result<Self> {
+        let host = device::host()?;
+        Ok(Self {
+            inputs: device_names(host.input_devices()?),
+            outputs: device_names(host.output_devices()?),
+        })
+    }
+
+    /// `newer` との差分をイベントとして返す
+    pub fn diff(&self, newer: &Self) -> Vec<DeviceEvent> {
+        let mut events = Vec::new();
+        for (direction, old, new) in [
+            (DeviceDirection::Input, &self.inputs, &newer.inputs),
+            (DeviceDirection::Output, &self.outputs, &newer.outputs),
+        ] {
+            events.extend(new.difference(old).map(|name| DeviceEvent::Added {
+                direction,
+                name: name.clone(),
+            }));
+            events.extend(old.difference(new).map(|name| DeviceEvent::Removed {
+                direction,
+                name: name.clone(),
+            }));
+        }
+        events
+    }
+}
+
+fn device_names(devices: impl Iterator<Item = cpal::Device>) -> BTreeSet<String> {
+    devices.filter_map(|d| d.name().ok()).collect()
+}
+
+/// デバイスの接続・切断を監視する
+///
+/// 別スレッドで一覧をポーリングし、変化をチャンネルに流します。
+/// 受信側を破棄すると監視も終了します。
+pub fn watch(interval: Duration) -> mpsc::UnboundedReceiver<DeviceEvent> {
+    let (tx, rx) = mpsc::unbounded_channel();
+
+    std::thread::spawn(move || {
+        let mut current = DeviceSnapshot::capture().unwrap_or_default();
+        loop {
+            std::thread::sleep(interval);
+            if tx.is_closed() {
+                break;
+            }
+
+            let latest = match DeviceSnapshot::capture() {
+                Ok(latest) => latest,
+                Err(e) => {
+                    debug!("デバイス一覧の取得エラー: {}", e);
+                    continue;
+                }
+            };
+
+            for event in current.diff(&latest) {
+                if tx.send(event).is_err() {
+                    return;
+                }
+            }
+            current = latest;
+        }
+    });
+
+    rx
+}
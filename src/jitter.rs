@@ -0,0 +1,109 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use crate::audio::AudioBuffer;
+
+/// 出力が途切れるときと再開するときのフェード（ミリ秒）
+const FADE_MS: u32 = 5;
+
+/// 出力のジッターバッファ（`--jitter-buffer-ms`）
+///
+/// 変換結果が一定量溜まってから再生を始め、途切れたら再び溜まるまで待ちます。
+/// 応答の到着の揺らぎを吸収する代わりに、その分だけ出力が遅れます。
+/// 途切れるときは残りをフェードアウトし、再開するときはフェードインするので、
+/// 深さが0（溜めずにすぐ再生）でもプツッという音は出ません。
+///
+/// クローンは同じ停止の合図を共有します。
+#[derive(Debug, Clone, Default)]
+pub struct JitterBuffer {
+    depth_ms: u32,
+    /// 停止時に、溜まりきるのを待たずに残りを再生する
+    draining: Arc<AtomicBool>,
+}
+
+impl JitterBuffer {
+    pub fn new(depth_ms: u32) -> Self {
+        Self {
+            depth_ms,
+            draining: Arc::default(),
+        }
+    }
+
+    pub fn depth_ms(&self) -> u32 {
+        self.depth_ms
+    }
+
+    /// 溜まりきるのを待たずに残りを再生させる（停止時）
+    pub fn drain(&self) {
+        self.draining.store(true, Ordering::Relaxed);
+    }
+
+    /// 指定した形式の出力コールバックで使う読み手を作る
+    ///
+    /// 読み手は最初は再生中として始まるので、起動時の予備再生の無音はそのまま流れます。
+    pub fn reader(&self, sample_rate: u32, channels: u16) -> JitterReader {
+        let channels = channels.max(1) as usize;
+        JitterReader {
+            depth: (sample_rate as u64 * self.depth_ms as u64 / 1000) as usize * channels,
+            fade: (sample_rate * FADE_MS / 1000) as usize,
+            channels,
+            holding: false,
+            faded_in: usize::MAX,
+            draining: self.draining.clone(),
+        }
+    }
+}
+
+/// 出力コールバックでジッターバッファから読む
+pub struct JitterReader {
+    /// 再生を始めるのに必要なサンプル数
+    depth: usize,
+    fade: usize,
+    channels: usize,
+    /// 溜まるのを待っている
+    holding: bool,
+    /// 再開してからフェードインしたフレーム数
+    faded_in: usize,
+    draining: Arc<AtomicBool>,
+}
+
+impl JitterReader {
+    /// 出力先を埋める（不足分は無音で埋める）。実際に書き込んだサンプル数を返す
+    pub fn fill(&mut self, buffer: &AudioBuffer, out: &mut [f32]) -> usize {
+        if self.holding {
+            let ready = buffer.len() >= self.depth.max(1) || self.draining.load(Ordering::Relaxed);
+            if !ready || buffer.is_empty() {
+                out.fill(0.0);
+                return 0;
+            }
+            self.holding = false;
+            self.faded_in = 0;
+        }
+
+        let written = buffer.fill(out);
+        let frames = written / self.channels;
+        for frame in out[..frames * self.channels].chunks_exact_mut(self.channels) {
+            if self.faded_in >= self.fade {
+                break;
+            }
+            let gain = self.faded_in as f32 / self.fade as f32;
+            frame.iter_mut().for_each(|s| *s *= gain);
+            self.faded_in += 1;
+        }
+
+        if written < out.len() {
+            // 途切れる直前をフェードアウトし、また溜まるまで待つ
+            let fade = self.fade.min(frames);
+            let start = frames - fade;
+            for (i, frame) in out[start * self.channels..frames * self.channels]
+                .chunks_exact_mut(self.channels)
+                .enumerate()
+            {
+                let gain = 1.0 - (i + 1) as f32 / fade as f32;
+                frame.iter_mut().for_each(|s| *s *= gain);
+            }
+            self.holding = true;
+        }
+        written
+    }
+}
@@ -0,0 +1,526 @@
+use anyhow::Result;
+use std::f32::consts::PI;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+use crate::backend;
+use crate::bench::{self, percentile};
+use crate::client::{Codec, VoiceConversionClient};
+use crate::dsp::analysis;
+use crate::pipeline::InputSpec;
+use crate::wav;
+
+/// クリック（窓をかけた1kHzのトーンバースト）の長さ（ミリ秒）
+const CLICK_MS: u32 = 5;
+
+const CLICK_FREQ: f32 = 1000.0;
+
+const CLICK_AMPLITUDE: f32 = 0.5;
+
+/// チャンクの中でクリックを置く位置（チャンク長に対する割合）
+const CLICK_POSITION: f64 = 0.25;
+
+/// クリックの始まりとみなす、ピークに対する大きさ
+const ONSET_RATIO: f32 = 0.3;
+
+/// ピークがこれより小さければクリックは届いていないとみなす（dBFS）
+const MIN_CLICK_DB: f32 = -50.0;
+
+/// ループバックなしでデバイスを動かし、バッファと報告値を読む時間
+const DEVICE_PROBE: Duration = Duration::from_millis(500);
+
+/// ループバックでクリックを鳴らす間隔（ミリ秒）。この間に戻ってこなければ見失ったとみなす
+const LOOPBACK_INTERVAL_MS: u32 = 600;
+
+/// ループバックで最初のクリックを鳴らすまでの待ち（ストリームの立ち上がりを避ける）
+const LOOPBACK_LEAD_IN_MS: u32 = 300;
+
+/// 変換の往復がチャンク長のこの割合より短ければ、チャンクを短くできると案内する
+const SHORTEN_CHUNK_RATIO: f64 = 0.3;
+
+/// p50とp95の差がこれを超えたら、ジッターバッファを案内する（ミリ秒）
+const JITTER_HINT_MS: f64 = 30.0;
+
+/// 遅延計測の設定（`makebeliv latency`）
+pub struct LatencyConfig {
+    pub input: InputSpec,
+    /// 出力デバイス名（`wav:<パス>` も可。Noneならデフォルト）
+    pub output_device: Option<String>,
+    pub model: String,
+    /// チャンク長（ミリ秒）
+    pub chunk_ms: u32,
+    /// コールバックあたりのフレーム数（Noneならチャンク長に近づける。`monitor` と同じ）
+    pub device_buffer: Option<u32>,
+    /// ジッターバッファの深さ（ミリ秒、計測せず合計に足す）
+    pub jitter_buffer_ms: u32,
+    /// 送るクリックの数
+    pub clicks: usize,
+    /// 出力からクリックを鳴らし、入力で拾って実測する
+    pub loopback: bool,
+}
+
+/// デバイス1つの計測結果
+pub struct DeviceLatency {
+    pub name: String,
+    /// コールバックあたりのバッファの長さ（ミリ秒）
+    pub buffer_ms: Option<f64>,
+    /// デバイスが報告した遅延（ミリ秒）
+    pub reported_ms: Option<f64>,
+}
+
+impl DeviceLatency {
+    /// 見積もり（報告値がなければバッファ1つ分）
+    pub fn estimate_ms(&self) -> Option<f64> {
+        self.reported_ms.or(self.buffer_ms)
+    }
+}
+
+/// 遅延の内訳
+pub struct LatencyReport {
+    pub chunk_ms: u32,
+    pub jitter_buffer_ms: u32,
+    pub capture: DeviceLatency,
+    pub playback: DeviceLatency,
+    /// 変換リクエストの往復（ミリ秒）
+    pub round_trip_ms: Vec<f64>,
+    /// サーバーの処理時間（X-Processing-Time-Ms）
+    pub server_ms: Vec<f64>,
+    /// 往復からサーバーの処理時間を引いた、通信にかかった時間（ミリ秒）
+    pub network_ms: Vec<f64>,
+    /// 変換結果の中でクリックが後ろにずれた量（ミリ秒、モデル自体の遅れ）
+    pub shift_ms: Vec<f64>,
+    pub errors: usize,
+    /// ループバックで実測した、出力から入力までの往復（ミリ秒）
+    pub loopback_ms: Vec<f64>,
+    /// ループバックで戻ってこなかったクリックの数
+    pub loopback_lost: usize,
+}
+
+impl LatencyReport {
+    /// 入力から再生までの合計の見積もり（ミリ秒、p50）
+    ///
+    /// ループバックで実測できていれば、デバイスの分は報告値の代わりに実測値を使います。
+    pub fn total_ms(&self) -> Option<f64> {
+        let devices = match percentile(&self.loopback_ms, 50.0) {
+            Some(measured) => measured,
+            None => self.capture.estimate_ms()? + self.playback.estimate_ms()?,
+        };
+        Some(
+            devices
+                + self.chunk_ms as f64
+                + percentile(&self.round_trip_ms, 50.0)?
+                + percentile(&self.shift_ms, 50.0).unwrap_or(0.0).max(0.0)
+                + self.jitter_buffer_ms as f64,
+        )
+    }
+}
+
+/// デバイスを計測してから、クリック入りのチャンクをサーバーで変換して計測する
+pub async fn run(client: &VoiceConversionClient, config: &LatencyConfig) -> Result<LatencyReport> {
+    let devices = {
+        let input = config.input.clone();
+        let output_device = config.output_device.clone();
+        let (device_buffer, chunk_ms) = (config.device_buffer, config.chunk_ms);
+        let clicks = if config.loopback { config.clicks } else { 0 };
+        tokio::task::spawn_blocking(move || {
+            measure_devices(
+                &input,
+                output_device.as_deref(),
+                device_buffer,
+                chunk_ms,
+                clicks,
+            )
+        })
+        .await??
+    };
+
+    let mut report = LatencyReport {
+        chunk_ms: config.chunk_ms,
+        jitter_buffer_ms: config.jitter_buffer_ms,
+        capture: devices.capture,
+        playback: devices.playback,
+        round_trip_ms: Vec::with_capacity(config.clicks),
+        server_ms: Vec::with_capacity(config.clicks),
+        network_ms: Vec::with_capacity(config.clicks),
+        shift_ms: Vec::with_capacity(config.clicks),
+        errors: 0,
+        loopback_ms: devices.loopback_ms,
+        loopback_lost: devices.loopback_lost,
+    };
+    measure_conversion(client, config, devices.sample_rate, &mut report).await?;
+    Ok(report)
+}
+
+/// デバイスの計測結果
+struct Devices {
+    capture: DeviceLatency,
+    playback: DeviceLatency,
+    /// 入力のサンプリングレート（変換に送るチャンクもこのレートで作る）
+    sample_rate: u32,
+    loopback_ms: Vec<f64>,
+    loopback_lost: usize,
+}
+
+/// 入出力を開いて動かし、バッファの長さと報告値を読む（`clicks` が1以上ならループバックも測る）
+fn measure_devices(
+    input: &InputSpec,
+    output_device: Option<&str>,
+    device_buffer: Option<u32>,
+    chunk_ms: u32,
+    clicks: usize,
+) -> Result<Devices> {
+    let Some(mut input) = input.open()? else {
+        anyhow::bail!(
+            "FIFO入力の遅延は測れません（--input にデバイスか wav:<パス> を指定してください）"
+        );
+    };
+    let (output_backend, output_name) = backend::resolve(output_device);
+    let mut output = output_backend.open_output(output_name)?;
+
+    let (in_rate, in_channels) = (input.sample_rate(), input.channels());
+    let (out_rate, out_channels) = (output.sample_rate(), output.channels());
+    let chunk_frames = |rate: u32| (rate as u64 * chunk_ms as u64 / 1000) as u32;
+    input.request_buffer_frames(device_buffer.unwrap_or(chunk_frames(in_rate)));
+    output.request_buffer_frames(device_buffer.unwrap_or(chunk_frames(out_rate)));
+
+    let capture = Arc::new(Mutex::new(Capture::default()));
+    let emitted = Arc::new(Mutex::new(Vec::with_capacity(clicks)));
+    let out_frames = Arc::new(AtomicUsize::new(0));
+
+    let mut train = ClickTrain::new(out_rate, out_channels, clicks, emitted.clone());
+    let output_stream = output.start_stream(Box::new({
+        let out_frames = out_frames.clone();
+        move |data: &mut [f32]| {
+            train.fill(data);
+            out_frames.store(data.len() / out_channels.max(1) as usize, Ordering::Relaxed);
+        }
+    }))?;
+    let input_stream = input.start_stream(Box::new({
+        let capture = capture.clone();
+        move |data: &[f32]| {
+            let arrived = Instant::now();
+            let mono = analysis::downmix(data, in_channels);
+            let mut capture = capture.lock().unwrap_or_else(|e| e.into_inner());
+            capture.callback_frames = mono.len();
+            capture.samples.extend_from_slice(&mono);
+            let end = capture.samples.len();
+            capture.arrivals.push((end, arrived));
+        }
+    }))?;
+
+    if clicks > 0 {
+        info!(
+            "🔊 クリックを{}回鳴らします（マイクに届く音量にしてください）",
+            clicks
+        );
+        std::thread::sleep(Duration::from_millis(
+            (LOOPBACK_LEAD_IN_MS + LOOPBACK_INTERVAL_MS * (clicks as u32 + 1)) as u64,
+        ));
+    } else {
+        std::thread::sleep(DEVICE_PROBE);
+    }
+    drop(output_stream);
+    drop(input_stream);
+
+    let capture = capture.lock().unwrap_or_else(|e| e.into_inner());
+    let emitted = emitted.lock().unwrap_or_else(|e| e.into_inner());
+    let frames_ms = |frames: usize, rate: u32| {
+        (frames > 0).then(|| frames as f64 * 1000.0 / rate.max(1) as f64)
+    };
+    let as_ms = |latency: Option<Duration>| latency.map(|d| d.as_secs_f64() * 1000.0);
+
+    let mut loopback_ms = Vec::with_capacity(emitted.len());
+    let offset = find_click(&click(in_rate)).unwrap_or(0);
+    let window = (in_rate * LOOPBACK_INTERVAL_MS / 1000) as usize;
+    for &at in emitted.iter() {
+        let measured = capture.frame_at(at, in_rate).and_then(|from| {
+            let to = (from + window).min(capture.samples.len());
+            let onset = from + find_click(capture.samples.get(from..to)?)?;
+            let heard = capture.time_of(onset.saturating_sub(offset), in_rate)?;
+            heard.checked_duration_since(at)
+        });
+        if let Some(latency) = measured {
+            loopback_ms.push(latency.as_secs_f64() * 1000.0);
+        }
+    }
+
+    Ok(Devices {
+        capture: DeviceLatency {
+            name: input.name(),
+            buffer_ms: frames_ms(capture.callback_frames, in_rate),
+            reported_ms: as_ms(input.reported_latency()),
+        },
+        playback: DeviceLatency {
+            name: output.name(),
+            buffer_ms: frames_ms(out_frames.load(Ordering::Relaxed), out_rate),
+            reported_ms: as_ms(output.reported_latency()),
+        },
+        sample_rate: in_rate,
+        loopback_lost: emitted.len() - loopback_ms.len(),
+        loopback_ms,
+    })
+}
+
+/// 入力で取り込んだ音声と、コールバックが届いた時刻
+#[derive(Default)]
+struct Capture {
+    /// 取り込んだ音声（モノラル）
+    samples: Vec<f32>,
+    /// コールバックごとの（そこまでのフレーム数, 届いた時刻）
+    arrivals: Vec<(usize, Instant)>,
+    /// 最後のコールバックのフレーム数
+    callback_frames: usize,
+}
+
+impl Capture {
+    /// 時刻 `at` に取り込まれていたフレーム
+    fn frame_at(&self, at: Instant, rate: u32) -> Option<usize> {
+        let &(end, arrived) = self.arrivals.iter().find(|(_, arrived)| *arrived >= at)?;
+        let back = arrived.duration_since(at).as_secs_f64() * rate as f64;
+        Some(end.saturating_sub(back as usize))
+    }
+
+    /// フレーム `frame` を取り込んだ時刻（届いた時刻から、コールバックで後ろにあった分を引く）
+    fn time_of(&self, frame: usize, rate: u32) -> Option<Instant> {
+        let &(end, arrived) = self.arrivals.iter().find(|(end, _)| *end > frame)?;
+        arrived.checked_sub(Duration::from_secs_f64(
+            (end - frame) as f64 / rate.max(1) as f64,
+        ))
+    }
+}
+
+/// 一定の間隔でクリックを鳴らす出力（鳴らすたびに書き込んだ時刻を記録する）
+struct ClickTrain {
+    click: Vec<f32>,
+    channels: usize,
+    sample_rate: u32,
+    frame: usize,
+    next: usize,
+    interval: usize,
+    remaining: usize,
+    playing: Option<usize>,
+    emitted: Arc<Mutex<Vec<Instant>>>,
+}
+
+impl ClickTrain {
+    fn new(
+        sample_rate: u32,
+        channels: u16,
+        clicks: usize,
+        emitted: Arc<Mutex<Vec<Instant>>>,
+    ) -> Self {
+        Self {
+            click: click(sample_rate),
+            channels: channels.max(1) as usize,
+            sample_rate,
+            frame: 0,
+            next: (sample_rate * LOOPBACK_LEAD_IN_MS / 1000) as usize,
+            interval: (sample_rate * LOOPBACK_INTERVAL_MS / 1000) as usize,
+            remaining: clicks,
+            playing: None,
+            emitted,
+        }
+    }
+
+    fn fill(&mut self, out: &mut [f32]) {
+        let now = Instant::now();
+        for (i, frame) in out.chunks_mut(self.channels).enumerate() {
+            if self.remaining > 0 && self.frame == self.next {
+                let offset = Duration::from_secs_f64(i as f64 / self.sample_rate as f64);
+                self.emitted
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .push(now + offset);
+                self.playing = Some(0);
+                self.remaining -= 1;
+                self.next += self.interval;
+            }
+            let value = match self.playing {
+                Some(pos) if pos < self.click.len() => {
+                    self.playing = Some(pos + 1);
+                    self.click[pos]
+                }
+                _ => 0.0,
+            };
+            frame.fill(value);
+            self.frame += 1;
+        }
+    }
+}
+
+/// クリック入りのチャンクを変換し、往復・サーバー処理時間・クリックのずれを測る
+async fn measure_conversion(
+    client: &VoiceConversionClient,
+    config: &LatencyConfig,
+    sample_rate: u32,
+    report: &mut LatencyReport,
+) -> Result<()> {
+    let len = (sample_rate as u64 * config.chunk_ms as u64 / 1000) as usize;
+    let position = (len as f64 * CLICK_POSITION) as usize;
+    let mut chunk = vec![0.0f32; len];
+    let click = click(sample_rate);
+    let end = (position + click.len()).min(len);
+    chunk[position..end].copy_from_slice(&click[..end - position]);
+    let sent_onset = find_click(&chunk).unwrap_or(position) as f64 / sample_rate as f64;
+    let data = wav::encode(&chunk, sample_rate, 1)?;
+    let session_id = "latency";
+
+    info!("📡 クリック入りのチャンクを{}回変換します", config.clicks);
+    // ウォームアップ（モデルロード時間を除外）
+    if let Err(e) = client
+        .convert_chunk(&data, Codec::Pcm, &config.model, 0, session_id)
+        .await
+    {
+        warn!("ウォームアップ失敗: {}", e);
+    }
+
+    for _ in 0..config.clicks {
+        let start = Instant::now();
+        let response = match client
+            .convert_chunk(&data, Codec::Pcm, &config.model, 0, session_id)
+            .await
+        {
+            Ok(response) => response,
+            Err(e) => {
+                warn!("チャンク変換失敗: {}", e);
+                report.errors += 1;
+                continue;
+            }
+        };
+        let round_trip = start.elapsed().as_secs_f64() * 1000.0;
+        report.round_trip_ms.push(round_trip);
+        if let Some(server) = response.meta.processing_time_ms {
+            report.server_ms.push(server);
+            report.network_ms.push((round_trip - server).max(0.0));
+        }
+        if let Ok((samples, spec)) = wav::decode(&response.audio) {
+            let mono = analysis::downmix(&samples, spec.channels);
+            if let Some(onset) = find_click(&mono) {
+                let onset = onset as f64 / spec.sample_rate.max(1) as f64;
+                report.shift_ms.push((onset - sent_onset) * 1000.0);
+            }
+        }
+    }
+
+    client.reset_session(session_id).await.ok();
+    Ok(())
+}
+
+/// 窓をかけたトーンバースト
+fn click(sample_rate: u32) -> Vec<f32> {
+    let len = (sample_rate * CLICK_MS / 1000).max(1) as usize;
+    (0..len)
+        .map(|i| {
+            let window = 0.5 - 0.5 * (2.0 * PI * i as f32 / len as f32).cos();
+            let t = i as f32 / sample_rate as f32;
+            CLICK_AMPLITUDE * window * (2.0 * PI * CLICK_FREQ * t).sin()
+        })
+        .collect()
+}
+
+/// クリックの始まり（ピークの [`ONSET_RATIO`] を最初に超えたサンプル）
+fn find_click(samples: &[f32]) -> Option<usize> {
+    let peak = samples.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+    if analysis::to_db(peak) < MIN_CLICK_DB {
+        return None;
+    }
+    samples.iter().position(|s| s.abs() >= peak * ONSET_RATIO)
+}
+
+/// 内訳と、チャンク長・バッファの調整の目安を表示
+pub fn print_report(report: &LatencyReport) {
+    let fmt = |v: Option<f64>| v.map_or_else(|| "-".to_string(), |v| format!("{:.1}ms", v));
+    let stats = |values: &[f64]| {
+        format!(
+            "p50 {} / p95 {}",
+            fmt(percentile(values, 50.0)),
+            fmt(percentile(values, 95.0))
+        )
+    };
+    let device = |device: &DeviceLatency| {
+        let source = match device.reported_ms {
+            Some(_) => "デバイスの報告値",
+            None => "バッファ1つ分で見積もり",
+        };
+        format!(
+            "{}（{}、バッファ {}）  {}",
+            fmt(device.estimate_ms()),
+            source,
+            fmt(device.buffer_ms),
+            device.name
+        )
+    };
+
+    println!("\n遅延の内訳（チャンク長 {}ms）", report.chunk_ms);
+    println!("  取り込み  デバイス          {}", device(&report.capture));
+    println!(
+        "            チャンクを溜める  {}",
+        fmt(Some(report.chunk_ms as f64))
+    );
+    println!(
+        "  変換      往復              {}（失敗 {}回）",
+        stats(&report.round_trip_ms),
+        report.errors
+    );
+    if report.server_ms.is_empty() {
+        println!("            サーバー処理      -（X-Processing-Time-Ms がありません）");
+    } else {
+        println!("            サーバー処理      {}", stats(&report.server_ms));
+        println!(
+            "            通信              {}",
+            stats(&report.network_ms)
+        );
+    }
+    println!(
+        "            モデルの遅れ      {}",
+        match percentile(&report.shift_ms, 50.0) {
+            Some(shift) => format!("{:.1}ms（変換結果の中でクリックがずれた量）", shift),
+            None => "-（変換結果からクリックを見つけられませんでした）".to_string(),
+        }
+    );
+    println!(
+        "  再生      ジッターバッファ  {}",
+        fmt(Some(report.jitter_buffer_ms as f64))
+    );
+    println!("            デバイス          {}", device(&report.playback));
+    if !report.loopback_ms.is_empty() || report.loopback_lost > 0 {
+        println!(
+            "  ループバック（出力→入力の実測）  {}（{}/{}回）",
+            stats(&report.loopback_ms),
+            report.loopback_ms.len(),
+            report.loopback_ms.len() + report.loopback_lost
+        );
+    }
+    println!("  合計（p50の見積もり）  {}", fmt(report.total_ms()));
+
+    if report.loopback_lost > 0 && report.loopback_ms.is_empty() {
+        println!("\n⚠ ループバックのクリックが入力に届きませんでした。スピーカーの音がマイクに入るようにしてください");
+    }
+    let chunk_ms = report.chunk_ms as f64;
+    if let Some(p95) = percentile(&report.round_trip_ms, 95.0) {
+        if p95 > chunk_ms * bench::LIVE_SAFETY_MARGIN {
+            println!(
+                "\n⚠ 変換の往復（p95 {:.0}ms）がチャンク長に近く、音が途切れるおそれがあります。--chunk-ms を大きくしてください",
+                p95
+            );
+        } else if p95 < chunk_ms * SHORTEN_CHUNK_RATIO {
+            let suggested = ((p95 / bench::LIVE_SAFETY_MARGIN / 10.0).ceil() * 10.0).max(10.0);
+            println!(
+                "\n💡 変換は十分に速いため、--chunk-ms {:.0} まで短くすると遅延を減らせます",
+                suggested
+            );
+        }
+        if let Some(p50) = percentile(&report.round_trip_ms, 50.0) {
+            let spread = p95 - p50;
+            if spread > JITTER_HINT_MS && (report.jitter_buffer_ms as f64) < spread {
+                println!(
+                    "💡 往復のばらつきが大きいため（p95 - p50 = {:.0}ms）、--jitter-buffer-ms {:.0} で途切れを防げます",
+                    spread,
+                    (spread / 10.0).ceil() * 10.0
+                );
+            }
+        }
+    }
+}
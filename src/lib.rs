@@ -1,2 +1,101 @@
+//! makebeliv のライブラリ
+//!
+//! CLI（`makebeliv`）と同じ変換を、ほかのアプリ（Tauriなど）に組み込んで使えます。
+//! CLIはこのライブラリの上の薄い層です。
+//!
+//! - ファイル変換: [`process::process_file`]（設定は [`ProcessOptions`]）
+//! - リアルタイム変換: [`monitor::Monitor`]（設定は [`pipeline::PipelineOptions`]、`devices` 機能）、
+//!   組み立てを細かく決めるなら [`RealtimePipeline`]
+//! - APIサーバーとの通信: [`VoiceConversionClient`]
+//! - 音声処理: [`dsp`]、WAVの読み書き: [`wav`]、設定ファイル: [`config`]
+//!
+//! ```no_run
+//! use makebeliv::config::Config;
+//! use makebeliv::process::{self, ProcessOptions};
+//!
+//! # async fn convert() -> anyhow::Result<()> {
+//! let mut options = ProcessOptions::from_config(&Config::load()?);
+//! options.use_api = true;
+//! options.pitch = 3;
+//! process::process_file("in.wav".as_ref(), "out.wav".as_ref(), &options).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod adaptive;
+pub mod arbiter;
 pub mod audio;
+pub mod auth;
+#[cfg(feature = "devices")]
+pub mod autoinput;
+pub mod backend;
+pub mod batch;
+pub mod bench;
+pub mod bleep;
+#[cfg(feature = "devices")]
+pub mod bluetooth;
+pub mod bwf;
+pub mod chaos;
 pub mod client;
+pub mod config;
+pub mod control;
+pub mod converter;
+pub mod cue;
+pub mod debug_http;
+#[cfg(feature = "devices")]
+pub mod device;
+pub mod diarize;
+pub mod dsp;
+pub mod fifo;
+pub mod graph;
+pub mod hooks;
+#[cfg(feature = "devices")]
+pub mod hotkeys;
+#[cfg(feature = "devices")]
+pub mod hotplug;
+pub mod jitter;
+#[cfg(feature = "devices")]
+pub mod latency;
+pub mod manifest;
+#[cfg(feature = "mock-server")]
+pub mod mock_server;
+#[cfg(feature = "devices")]
+pub mod monitor;
+pub mod notify;
+pub mod opus_chunk;
+#[cfg(feature = "opus")]
+pub mod opus_writer;
+#[cfg(feature = "devices")]
+pub mod pipeline;
+pub mod process;
+pub mod progress;
+pub mod python;
+pub mod recorder;
+pub mod resample;
+pub mod retry;
+pub mod script;
+#[cfg(feature = "devices")]
+pub mod secondary;
+pub mod session;
+pub mod shutdown;
+pub mod stats;
+pub mod summary;
+#[cfg(feature = "termux")]
+pub mod termux;
+pub mod timecode;
+#[cfg(feature = "tui")]
+pub mod tui;
+pub mod update;
+pub mod upload;
+pub mod video;
+pub mod virtual_audio;
+#[cfg(feature = "devices")]
+pub mod vmic;
+pub mod watch;
+pub mod watermark;
+pub mod wav;
+
+pub use client::VoiceConversionClient;
+#[cfg(feature = "devices")]
+pub use pipeline::RealtimePipeline;
+pub use process::ProcessOptions;
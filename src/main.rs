@@ -1,18 +1,86 @@
 use anyhow::{Context, Result};
-use clap::{Parser, Subcommand};
-use std::path::PathBuf;
-use std::process::{Child, Command};
+use clap::{Args, Parser, Subcommand};
+use std::collections::BTreeMap;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitCode};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
 use tracing::{info, warn};
 
-mod audio;
-mod client;
-
-use client::VoiceConversionClient;
+#[cfg(feature = "devices")]
+use makebeliv::audio::{self, ChannelSelect};
+use makebeliv::auth;
+use makebeliv::batch;
+use makebeliv::bench::{self, BenchConfig, SweepConfig};
+#[cfg(feature = "devices")]
+use makebeliv::bleep::BleepMode;
+use makebeliv::client::{self, Timeouts, VoiceConversionClient};
+use makebeliv::config::{self, Config};
+use makebeliv::control::{self, ControlRequest};
+use makebeliv::converter::Engine;
+use makebeliv::cue::{CueSheet, TimeRange};
+use makebeliv::debug_http;
+use makebeliv::diarize::{self, DiarizationSource};
+#[cfg(feature = "devices")]
+use makebeliv::dsp::vad::VadMode;
+use makebeliv::manifest::{self, Manifest};
+#[cfg(feature = "mock-server")]
+use makebeliv::mock_server::{self, MockOptions};
+use makebeliv::process::{self, ProcessOptions};
+use makebeliv::progress::TransferProgress;
+use makebeliv::python;
+use makebeliv::resample::ResampleQuality;
+use makebeliv::retry::RetryPolicy;
+use makebeliv::session::SessionManager;
+use makebeliv::shutdown::{self, Interrupted};
+use makebeliv::stats;
+use makebeliv::timecode::{self, SyncMarkers, SyncOptions};
+use makebeliv::update;
+use makebeliv::watch::{self, FolderWatcher};
+use makebeliv::watermark;
+use makebeliv::wav::{self, BitDepth};
+
+#[cfg(feature = "devices")]
+use makebeliv::{
+    chaos::{ChaosOptions, LatencySpec},
+    client::{Codec, Transport},
+    fifo::PcmFormat,
+    graph::GraphFormat,
+    hotplug,
+    latency::{self, LatencyConfig},
+    monitor::Monitor,
+    pipeline::{
+        CatchUp, Fallback, InputSpec, PipelineOptions, Profile, DEFAULT_LEVEL_INTERVAL_SECS,
+    },
+    recorder::{self, RecordFormat, RecordingOptions},
+    secondary::SecondaryOptions,
+    vmic::{self, VmicBackend},
+};
 
 #[derive(Parser)]
 #[command(name = "makebeliv")]
 #[command(about = "Real-time voice conversion with natural fluctuation", long_about = None)]
 struct Cli {
+    /// Audio host API to use (e.g. WASAPI, DirectSound, ALSA, JACK; see list-devices)
+    #[cfg(feature = "devices")]
+    #[arg(long, global = true)]
+    audio_host: Option<String>,
+
+    /// Record every HTTP request/response (sanitized) as JSON files in this directory
+    #[arg(long, global = true, value_name = "DIR")]
+    debug_http: Option<PathBuf>,
+
+    /// Give up on an API request after this many seconds (chunk conversions use at most 3s)
+    #[arg(long, global = true, value_name = "SECONDS", default_value_t = client::DEFAULT_TIMEOUT_SECS)]
+    timeout: f64,
+
+    /// Give up connecting to the API server after this many seconds
+    #[arg(long, global = true, value_name = "SECONDS", default_value_t = client::DEFAULT_CONNECT_TIMEOUT_SECS)]
+    connect_timeout: f64,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -37,94 +105,1031 @@ enum Commands {
         port: u16,
     },
 
+    /// Start a built-in mock API server that returns audio unchanged (no Python needed)
+    #[cfg(feature = "mock-server")]
+    MockServer {
+        /// Host address
+        #[arg(long, default_value = "127.0.0.1")]
+        host: String,
+
+        /// Port number
+        #[arg(long, default_value = "8000")]
+        port: u16,
+
+        /// Added latency per conversion request in milliseconds
+        #[arg(long, default_value = "0")]
+        latency_ms: u64,
+
+        /// Random latency variation (+/-) in milliseconds
+        #[arg(long, default_value = "0")]
+        jitter_ms: u64,
+
+        /// Fraction of conversion requests that fail with HTTP 500 (0.0-1.0)
+        #[arg(long, default_value = "0")]
+        error_rate: f64,
+
+        /// Reject requests without `Authorization: Bearer <key>` (HTTP 401)
+        #[arg(long)]
+        api_key: Option<String>,
+
+        /// Sample rate (Hz) to report in /status as the models' input rate (clients resample to it)
+        #[arg(long)]
+        sample_rate: Option<u32>,
+    },
+
     /// Process audio file (development mode)
-    Process {
-        /// Input audio file
+    Process(Box<ProcessArgs>),
+
+    /// Watch a folder and convert WAV files as they are dropped into it
+    Watch(WatchArgs),
+
+    /// Re-run a conversion exactly as recorded in a --manifest sidecar, after checking the input hash and model versions
+    Reproduce {
+        /// Manifest written by process --manifest (<output>.makebeliv.json)
+        manifest: PathBuf,
+
+        /// Input file (default: the input path recorded in the manifest)
         #[arg(short, long)]
-        input: PathBuf,
+        input: Option<PathBuf>,
 
-        /// Output audio file (default: audio/output/processed.wav)
+        /// Output file (default: <recorded output>.reproduced.<ext>)
         #[arg(short, long)]
         output: Option<PathBuf>,
 
-        /// Voice model to use
-        #[arg(short, long, default_value = "default")]
-        model: String,
+        /// API server URL (default: the URL recorded in the manifest)
+        #[arg(long)]
+        api_url: Option<String>,
 
-        /// Background noise type (cafe, street, room)
-        #[arg(short, long, default_value = "cafe")]
-        noise: String,
+        /// Convert even if the input hash or a model version differs from the manifest
+        #[arg(long)]
+        force: bool,
+    },
 
-        /// Pitch shift in semitones (e.g., +3)
-        #[arg(short, long, default_value = "0")]
-        pitch: i32,
+    /// Check a WAV file for the inaudible watermark that process --watermark embeds (exits with an error when none is found)
+    VerifyWatermark {
+        /// WAV file to check
+        file: PathBuf,
 
-        /// Use API server (default: direct Python execution)
+        /// Watermark key (default: [conversion] watermark_key in config, then "makebeliv")
         #[arg(long)]
-        use_api: bool,
-
-        /// API server URL
-        #[arg(long, default_value = "http://localhost:8000")]
-        api_url: String,
+        key: Option<String>,
     },
 
     /// Real-time voice conversion
-    Monitor {
-        /// Voice model to use
-        #[arg(short, long, default_value = "default")]
-        model: String,
-
-        /// Background noise type
-        #[arg(short, long, default_value = "cafe")]
-        noise: String,
-
-        /// Pitch shift in semitones
-        #[arg(short, long, default_value = "0")]
-        pitch: i32,
-
-        /// API server URL
-        #[arg(long, default_value = "http://localhost:8000")]
-        api_url: String,
+    #[cfg(feature = "devices")]
+    Monitor(Box<MonitorArgs>),
+
+    /// Show server status and GPU/CPU resource usage
+    Status {
+        /// API server URL (default: [server] api_url in config, then http://localhost:8000)
+        #[arg(long)]
+        api_url: Option<String>,
+    },
+
+    /// Benchmark real-time factor and latency per model
+    Bench {
+        /// Models to benchmark (comma separated, or "all")
+        #[arg(long, default_value = "all")]
+        models: String,
+
+        /// Chunk size in milliseconds used for live-safety judgement (default: [conversion] chunk_ms in config, then 200)
+        #[arg(long)]
+        chunk_ms: Option<u32>,
+
+        /// Measured requests per model (after one warmup request)
+        #[arg(long, default_value = "20")]
+        iterations: usize,
+
+        /// Run this WAV file through the API with each of --chunk-sizes and --concurrency instead of a synthetic chunk, and recommend a chunk size for monitor
+        #[arg(long)]
+        input: Option<PathBuf>,
+
+        /// Chunk sizes in milliseconds to try with --input
+        #[arg(long, value_delimiter = ',', default_values_t = bench::DEFAULT_CHUNK_SIZES.to_vec(), requires = "input")]
+        chunk_sizes: Vec<u32>,
+
+        /// Numbers of concurrent streams (monitors sharing the server) to try with --input
+        #[arg(long, value_delimiter = ',', default_values_t = bench::DEFAULT_CONCURRENCY.to_vec(), requires = "input")]
+        concurrency: Vec<usize>,
+
+        /// API server URL (default: [server] api_url in config, then http://localhost:8000)
+        #[arg(long)]
+        api_url: Option<String>,
+    },
+
+    /// Send a click through the devices and the server and show capture, network/server and playback latency
+    #[cfg(feature = "devices")]
+    Latency {
+        /// Input source: "default", "auto" or "wav:<path>", as in monitor (default: [audio] input in config)
+        #[arg(long)]
+        input: Option<InputSpec>,
+
+        /// Output device name or "wav:<path>", as in monitor (default: [audio] output_device in config, then system default)
+        #[arg(long)]
+        output_device: Option<String>,
+
+        /// Voice model to convert the click with (default: [conversion] model in config, then "default")
+        #[arg(short, long)]
+        model: Option<String>,
+
+        /// Chunk size in milliseconds (default: [conversion] chunk_ms in config, then 200)
+        #[arg(long)]
+        chunk_ms: Option<u32>,
+
+        /// Frames per device callback (default: close to the chunk size, as in monitor)
+        #[arg(long)]
+        device_buffer: Option<u32>,
+
+        /// Jitter buffer depth in milliseconds added to the total (default: 0, 150 with [audio] profile = "pi")
+        #[arg(long)]
+        jitter_buffer_ms: Option<u32>,
+
+        /// Number of clicks to convert (and to play with --loopback)
+        #[arg(long, default_value = "10")]
+        clicks: usize,
+
+        /// Also play the clicks on the output device and time them coming back through the input (put the mic near the speaker)
+        #[arg(long)]
+        loopback: bool,
+
+        /// API server URL (default: [server] api_url in config, then http://localhost:8000)
+        #[arg(long)]
+        api_url: Option<String>,
+    },
+
+    /// Show cumulative usage and latency/failure trends from past sessions
+    Stats {
+        /// Number of recent days to show in the daily table
+        #[arg(long, default_value = "14")]
+        days: u32,
+    },
+
+    /// Inspect configuration (~/.config/makebeliv/config.toml < ./makebeliv.toml < CLI flags)
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+
+    /// Manage named presets of model, pitch, noise and device routing
+    Preset {
+        #[command(subcommand)]
+        action: PresetAction,
+    },
+
+    /// Inspect or reset realtime conversion sessions held by the server
+    Session {
+        #[command(subcommand)]
+        action: SessionAction,
+    },
+
+    /// Change the model, pitch or noise level of a running monitor through its control socket
+    Ctl {
+        #[command(subcommand)]
+        action: CtlAction,
+
+        /// Control socket of the monitor (default: $XDG_RUNTIME_DIR/makebeliv.sock, or \\.\pipe\makebeliv on Windows)
+        #[arg(long, global = true)]
+        socket: Option<PathBuf>,
+    },
+
+    /// Store or remove the API key for a server in the OS keychain
+    Auth {
+        #[command(subcommand)]
+        action: AuthAction,
     },
 
     /// List audio devices
-    ListDevices,
+    #[cfg(feature = "devices")]
+    ListDevices {
+        /// Keep running and print devices as they are plugged in or removed
+        #[arg(long)]
+        watch: bool,
+    },
+
+    /// Manage the virtual microphone
+    #[cfg(feature = "devices")]
+    Vmic {
+        #[command(subcommand)]
+        action: VmicAction,
+    },
+
+    /// Update makebeliv to the latest GitHub release
+    SelfUpdate {
+        /// Only check for a new version without installing
+        #[arg(long)]
+        check: bool,
+
+        /// Skip confirmation prompts
+        #[arg(short, long)]
+        yes: bool,
+    },
 }
 
-#[tokio::main]
-async fn main() -> Result<()> {
+#[derive(Args, Clone)]
+struct ProcessArgs {
+    /// Input audio or video file (MP4/MKV/MOV/WebM, needs ffmpeg), a directory of WAV files, or a glob such as "audio/input/*.wav"
+    #[arg(short, long)]
+    input: PathBuf,
+
+    /// Output file for a single input (default: audio/output/processed.wav, or processed.<ext> for video input)
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Output directory when converting a directory or glob (default: audio/output)
+    #[arg(long)]
+    output_dir: Option<PathBuf>,
+
+    /// Output file name for a directory or glob; {stem}, {model} and {pitch} are replaced
+    #[arg(long, default_value = batch::DEFAULT_NAME_TEMPLATE)]
+    name_template: String,
+
+    /// Number of files to convert at the same time for a directory or glob
+    #[arg(short, long, default_value_t = 1)]
+    jobs: usize,
+
+    /// Preset to start from (see `makebeliv preset list`); explicit flags still take precedence
+    #[arg(long)]
+    preset: Option<String>,
+
+    /// Voice model to use (default: [conversion] model in config, then "default")
+    #[arg(short, long)]
+    model: Option<String>,
+
+    /// Background noise: cafe, street, room or a WAV file to loop, mixed locally (default: [conversion] noise in config, then "cafe")
+    #[arg(short, long)]
+    noise: Option<String>,
+
+    /// Background noise level, 0.0-1.0, 0 disables noise (default: [conversion] noise_level in config, then 0.02)
+    #[arg(long)]
+    noise_level: Option<f32>,
+
+    /// Peak level of breaths inserted at phrase boundaries, 0.0-1.0, 0 disables them (default: [conversion] breath_level in config, then 0)
+    #[arg(long)]
+    breath_level: Option<f32>,
+
+    /// Directory of breath WAV files to pick from at random (default: [conversion] breath_dir in config, then synthesized breaths)
+    #[arg(long)]
+    breath_dir: Option<PathBuf>,
+
+    /// Pitch shift in semitones, e.g. +3 (default: [conversion] pitch in config, then 0)
+    #[arg(short, long, allow_hyphen_values = true)]
+    pitch: Option<i32>,
+
+    /// Vary pitch slowly by up to +/- this many cents, re-rolled at each phrase boundary, 0 disables (default: [conversion] pitch_contour in config, then 0)
+    #[arg(long)]
+    pitch_contour: Option<f32>,
+
+    /// Vary speaking rate slowly over time by up to +/- this many percent, pitch unchanged, 0 disables (default: [conversion] rate_fluctuation in config, then 0)
+    #[arg(long)]
+    rate_fluctuation: Option<f32>,
+
+    /// Output WAV bit depth: 16, 24 or 32f (default: same as input)
+    #[arg(long)]
+    bit_depth: Option<BitDepth>,
+
+    /// Resampler quality when the converted audio comes back at another rate
+    #[arg(long, default_value = "best")]
+    resample_quality: ResampleQuality,
+
+    /// Convert only this speaker's segments and leave the other speakers untouched (detected speakers are logged; the local heuristic names them speaker-1, speaker-2, ... from the lowest voice)
+    #[arg(long)]
+    only_speaker: Option<String>,
+
+    /// How to tell speakers apart for --only-speaker: "auto" (the server if it supports diarization, else local), "server" or "local" (energy/pitch heuristic)
+    #[arg(long, default_value_t = DiarizationSource::Auto, requires = "only_speaker")]
+    diarization: DiarizationSource,
+
+    /// Expected number of speakers for --only-speaker
+    #[arg(long, default_value_t = diarize::DEFAULT_SPEAKERS, requires = "only_speaker")]
+    speakers: usize,
+
+    /// Cue file of time ranges with preset/model/pitch/noise/noise_level overrides, to convert sections of one file with different voices: JSON, CSV, a subtitle script (.srt/.vtt with "NAME:" speaker prefixes) or a chapter list
+    #[arg(long)]
+    cues: Option<PathBuf>,
+
+    /// Assign a preset to a speaker or chapter name in --cues, as NAME=PRESET; repeat for several (names without one use the preset of the same name, if any)
+    #[arg(long, value_name = "NAME=PRESET", requires = "cues")]
+    cast: Vec<String>,
+
+    /// Convert only this time range of the input, cut sample-accurately: START-END as seconds, M:SS or H:MM:SS (e.g. 1:10-2:30); the output holds just the range unless --splice is given
+    #[arg(long, conflicts_with = "cues")]
+    range: Option<TimeRange>,
+
+    /// With --range, write the whole original file with the converted range spliced back in, crossfaded over 10 ms at both boundaries
+    #[arg(long, requires = "range")]
+    splice: bool,
+
+    /// Keep the pauses between phrases exactly as long as in the input (the server sometimes shortens them), so converted narration stays in sync with video
+    #[arg(long)]
+    preserve_gaps: bool,
+
+    /// Add sync markers to the output WAV: "bwf" (start time in the bext chunk, carried over from a BWF input), "sidecar" (a <file>.timecode.csv with a timecode every --sync-interval seconds) or "both"
+    #[arg(long)]
+    sync_markers: Option<SyncMarkers>,
+
+    /// Seconds between sync markers in the timecode sidecar
+    #[arg(long, value_name = "SECONDS", default_value_t = timecode::DEFAULT_SYNC_INTERVAL_SECS, requires = "sync_markers")]
+    sync_interval: u32,
+
+    /// Timecode frame rate (non-drop) used in the sidecar and the log
+    #[arg(long, value_name = "FPS", default_value_t = timecode::DEFAULT_TIMECODE_FPS, requires = "sync_markers")]
+    timecode_fps: u32,
+
+    /// Write a BWF bext chunk into the output WAV (origination time, description and a coding history line with the model, pitch and noise used) so the settings travel with the file into DAWs
+    #[arg(long)]
+    bwf: bool,
+
+    /// Write a <output>.makebeliv.json manifest next to each output (input hash, all parameters, server/model versions, timings) so the conversion can be reproduced later
+    #[arg(long)]
+    manifest: bool,
+
+    /// Seed for the random pitch/rate fluctuation, breaths and noise (default: from the clock; recorded in --manifest)
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// Embed an inaudible spread-spectrum watermark in the output WAV so the clip can later be shown to be converted (see verify-watermark; default: [conversion] watermark in config)
+    #[arg(long)]
+    watermark: bool,
+
+    /// Key for --watermark; only the same key finds it again (implies --watermark; default: [conversion] watermark_key in config, then "makebeliv")
+    #[arg(long)]
+    watermark_key: Option<String>,
+
+    /// Refuse to convert when the server's version of the model differs from the one pinned by model_version in the preset or [conversion] (default: warn and continue)
+    #[arg(long)]
+    strict_versions: bool,
+
+    /// Conversion engine: "server" (RVC via Python) or "local" (pure-Rust pitch shift only, no Python needed)
+    #[arg(long, default_value = "server")]
+    engine: Engine,
+
+    /// Use API server (default: direct Python execution)
+    #[arg(long)]
+    use_api: bool,
+
+    /// API server URL (default: [server] api_url in config, then http://localhost:8000)
+    #[arg(long)]
+    api_url: Option<String>,
+
+    /// Hide the upload / server processing / download progress bars (they are only drawn on a terminal)
+    #[arg(long)]
+    no_progress: bool,
+}
+
+#[derive(Args)]
+struct WatchArgs {
+    /// Folder to watch for new WAV files (subfolders are not watched)
+    #[arg(long, default_value = "audio/input")]
+    dir: PathBuf,
+
+    /// Output directory for converted files (default: audio/output)
+    #[arg(long)]
+    output_dir: Option<PathBuf>,
+
+    /// Output file name; {stem}, {model} and {pitch} are replaced
+    #[arg(long, default_value = batch::DEFAULT_NAME_TEMPLATE)]
+    name_template: String,
+
+    /// Milliseconds a new file's size must stay unchanged before converting it, so partially written files are skipped
+    #[arg(long, default_value_t = watch::DEFAULT_SETTLE_MS)]
+    settle_ms: u64,
+
+    /// Preset to convert with (see `makebeliv preset list`); the config is re-read for every file
+    #[arg(long)]
+    preset: Option<String>,
+
+    /// Refuse to convert when the server's version of the model differs from the one pinned by model_version in the preset or [conversion] (default: warn and continue)
+    #[arg(long)]
+    strict_versions: bool,
+
+    /// Conversion engine: "server" (RVC via Python) or "local" (pure-Rust pitch shift only, no Python needed)
+    #[arg(long, default_value = "server")]
+    engine: Engine,
+
+    /// Use API server (default: direct Python execution)
+    #[arg(long)]
+    use_api: bool,
+
+    /// API server URL (default: [server] api_url in config, then http://localhost:8000)
+    #[arg(long)]
+    api_url: Option<String>,
+}
+
+#[cfg(feature = "devices")]
+#[derive(Args)]
+struct MonitorArgs {
+    /// Preset to start from (see `makebeliv preset list`); explicit flags still take precedence
+    #[arg(long)]
+    preset: Option<String>,
+
+    /// Voice model to use (default: [conversion] model in config, then "default")
+    #[arg(short, long)]
+    model: Option<String>,
+
+    /// Background noise: cafe, street, room or a WAV file to loop, mixed locally into the output (default: [conversion] noise in config, then "cafe")
+    #[arg(short, long)]
+    noise: Option<String>,
+
+    /// Background noise level, 0.0-1.0, 0 disables noise (default: [conversion] noise_level in config, then 0.02)
+    #[arg(long)]
+    noise_level: Option<f32>,
+
+    /// Peak level of breaths inserted at phrase boundaries, 0.0-1.0, 0 disables them (default: [conversion] breath_level in config, then 0)
+    #[arg(long)]
+    breath_level: Option<f32>,
+
+    /// Directory of breath WAV files to pick from at random (default: [conversion] breath_dir in config, then synthesized breaths)
+    #[arg(long)]
+    breath_dir: Option<PathBuf>,
+
+    /// Pitch shift in semitones (default: [conversion] pitch in config, then 0)
+    #[arg(short, long, allow_hyphen_values = true)]
+    pitch: Option<i32>,
+
+    /// Vary pitch slowly by up to +/- this many cents, re-rolled at each phrase boundary, 0 disables (default: [conversion] pitch_contour in config, then 0)
+    #[arg(long)]
+    pitch_contour: Option<f32>,
+
+    /// Word to bleep when the server's transcription hears it in the output; repeat or comma-separate for several (default: [bleep] words in config)
+    #[arg(long = "bleep-word", value_delimiter = ',')]
+    bleep_words: Vec<String>,
+
+    /// How to hide bleeped words: "tone" or "silence" (default: [bleep] mode in config, then tone)
+    #[arg(long)]
+    bleep_mode: Option<BleepMode>,
+
+    /// Milliseconds of output held back for each transcription when bleeping; this adds latency (default: [bleep] window_ms in config, then 2000)
+    #[arg(long)]
+    bleep_window: Option<u32>,
+
+    /// Place the voice from -1.0 (left) to 1.0 (right) on a stereo output (default: [audio] pan in config, then 0)
+    #[arg(long, allow_hyphen_values = true)]
+    pan: Option<f32>,
+
+    /// Stereo width of the voice: 0 is mono, 1 leaves it as is, up to 2 for wider (default: [audio] stereo_width in config, then 1)
+    #[arg(long)]
+    stereo_width: Option<f32>,
+
+    /// Stereo width of the background noise, 0-2 (default: [audio] noise_width in config, then 1)
+    #[arg(long)]
+    noise_width: Option<f32>,
+
+    /// API server URL (default: [server] api_url in config, then http://localhost:8000)
+    #[arg(long)]
+    api_url: Option<String>,
+
+    /// Chunk size in milliseconds (default: [conversion] chunk_ms in config, then 200)
+    #[arg(long)]
+    chunk_ms: Option<u32>,
+
+    /// Chunk transport: "http" (multipart per chunk) or "ws" (persistent WebSocket with raw PCM)
+    #[arg(long, default_value = "http")]
+    transport: Transport,
+
+    /// Chunk codec: "pcm" (uncompressed) or "opus" (compressed, needs the opus feature and a server that supports it; falls back to pcm otherwise) (default: pcm, opus with --profile pi)
+    #[arg(long)]
+    codec: Option<Codec>,
+
+    /// Conversion engine: "server" (RVC via the API server) or "local" (pure-Rust pitch shift only, works without the server)
+    #[arg(long, default_value = "server")]
+    engine: Engine,
+
+    /// Add latency before each chunk conversion: <ms> or <ms>:<jitter ms> (resilience testing)
+    #[arg(long, hide = true)]
+    inject_latency: Option<LatencySpec>,
+
+    /// Fraction of chunk conversions that fail (0.0-1.0, resilience testing)
+    #[arg(long, hide = true, default_value = "0")]
+    inject_error_rate: f64,
+
+    /// Random seed for injected latency/errors (same seed, same sequence)
+    #[arg(long, hide = true)]
+    inject_seed: Option<u64>,
+
+    /// Rhai script adjusting parameters per chunk (defines `on_chunk(event)`)
+    #[arg(long)]
+    script: Option<PathBuf>,
+
+    /// Input source: "default", "auto" (score devices and pick the best mic), "fifo:<path>" for raw PCM from a named pipe, or "wav:<path>" to play a WAV file in real time and stop at its end, or "termux" for the phone's microphone under Termux (needs the termux feature) (default: [audio] input in config)
+    #[arg(long)]
+    input: Option<InputSpec>,
+
+    /// Raw PCM format for FIFO input (<s16le|s24le|s32le|f32le>:<rate>:<channels>)
+    #[arg(long, default_value = "s16le:48000:1")]
+    input_format: PcmFormat,
+
+    /// Input channel to convert: "mix" (average all channels to mono), "left", "right", a 1-based channel number, or "all" (send every channel as is); the converted voice is duplicated to every output channel (default: [audio] channel in config, then mix)
+    #[arg(long)]
+    channel: Option<ChannelSelect>,
+
+    /// Output device name, "wav:<path>" to capture playback into a WAV file instead of a device, or "termux" for the phone's speaker under Termux (needs the termux feature) (default: [audio] output_device in config, then system default)
+    #[arg(long)]
+    output_device: Option<String>,
+
+    /// Resampler quality when the device and server rates differ (fast, balanced, best; default: fast)
+    #[arg(long)]
+    resample_quality: Option<ResampleQuality>,
+
+    /// Sample rate (Hz) of the audio sent to the server; the input is resampled to it (default: the rate the server reports for its models, else the input device rate)
+    #[arg(long)]
+    sample_rate: Option<u32>,
+
+    /// Pad the final partial chunk with silence and convert it on stop
+    #[arg(long)]
+    pad_final: bool,
+
+    /// Move chunk boundaries to the nearest zero crossing (within 10ms)
+    #[arg(long)]
+    align_zero_crossings: bool,
+
+    /// Resend the last N ms of each chunk with the next one and crossfade the converted chunks on the client; the server only sees a longer chunk (adds N ms of latency, 0 = off) (default: [conversion] overlap_ms in config, then 0)
+    #[arg(long, visible_alias = "overlap-ms")]
+    crossfade_ms: Option<u32>,
+
+    /// Wait until this much converted audio (ms) is queued before playing, and again after running dry (absorbs late responses, adds latency; 0 = play immediately) (default: 0, 150 with --profile pi)
+    #[arg(long)]
+    jitter_buffer_ms: Option<u32>,
+
+    /// Frames per device callback (default: close to the chunk size, clamped to the device range)
+    #[arg(long)]
+    device_buffer: Option<u32>,
+
+    /// Defaults for the hardware: "default" or "pi" (Raspberry Pi front-end for a remote GPU server: chunks of at least 300 ms, a 150 ms jitter buffer, the fast resampler and Opus chunks); explicit flags still take precedence (default: [audio] profile in config)
+    #[arg(long)]
+    profile: Option<Profile>,
+
+    /// Skip sending silent chunks to the server and play silence (or faint comfort noise without background noise) instead: "on" or "off" (default: [audio] vad in config, then on)
+    #[arg(long)]
+    vad: Option<VadMode>,
+
+    /// Level in dBFS at which a 10 ms frame counts as speech for --vad (default: [audio] vad_threshold in config, then -45)
+    #[arg(long, allow_hyphen_values = true)]
+    vad_threshold: Option<f32>,
+
+    /// Keep a Bluetooth headset in its high-quality playback profile (A2DP): use another microphone instead of the headset's and, on Linux, switch Bluetooth cards from hands-free (HFP) to A2DP
+    #[arg(long)]
+    bt_a2dp: bool,
+
+    /// Save battery: chunks of at least 400 ms (unless --chunk-ms is given), no optional DSP stages (pitch contour, breath, stereo image, [dsp] plugins), the fast resampler and less frequent polling (default: [audio] low_power in config)
+    #[arg(long)]
+    low_power: bool,
+
+    /// Catch up when more than this much converted audio (ms) is queued for playback
+    #[arg(long)]
+    max_latency_ms: Option<u32>,
+
+    /// How to catch up with --max-latency-ms: "drop" (discard queued audio) or "stretch" (play slightly faster until back at half the limit)
+    #[arg(long, default_value = "drop")]
+    catchup: CatchUp,
+
+    /// Grow the chunk size when the server takes longer than a chunk to convert it, instead of falling further behind, and shrink it back once there is headroom
+    #[arg(long)]
+    adaptive_chunk: bool,
+
+    /// Largest chunk size (ms) --adaptive-chunk may grow to (default: 4x the chunk size, capped at --max-latency-ms)
+    #[arg(long, requires = "adaptive_chunk")]
+    max_chunk_ms: Option<u32>,
+
+    /// With --adaptive-chunk, play the input unconverted for 10 s when even the largest chunk size cannot keep up
+    #[arg(long, requires = "adaptive_chunk")]
+    overload_passthrough: bool,
+
+    /// What to play while the server is unreachable: "silence", "passthrough" (your raw microphone) or "pitch" (the microphone with a local pitch shift). Conversion resumes automatically once the server answers again
+    #[arg(long, default_value = "silence")]
+    fallback: Fallback,
+
+    /// Refuse to convert when the server's version of the model differs from the one pinned by model_version in the preset or [conversion] (default: warn and continue)
+    #[arg(long)]
+    strict_versions: bool,
+
+    /// Also write the end-of-session summary as JSON to this path
+    #[arg(long)]
+    summary_json: Option<PathBuf>,
+
+    /// Play your raw microphone to headphones at this level in dB (e.g. -12), independent of the converted output
+    #[arg(long, allow_hyphen_values = true)]
+    sidetone: Option<f32>,
+
+    /// Output device for the sidetone (default: system default output)
+    #[arg(long)]
+    sidetone_device: Option<String>,
+
+    /// Also play the converted output on this device (e.g. for a producer/engineer), separate from the virtual mic
+    #[arg(long)]
+    echo_device: Option<String>,
+
+    /// Level of the echo output in dB
+    #[arg(long, allow_hyphen_values = true, default_value_t = -12.0)]
+    echo_level: f32,
+
+    /// Record the microphone input and converted output in this directory (see --record-format)
+    #[arg(long)]
+    record: Option<PathBuf>,
+
+    /// Recording file format: wav (16-bit) or opus (Ogg Opus, encoded on a separate thread; needs the opus feature)
+    #[arg(long, default_value_t = RecordFormat::Wav, requires = "record")]
+    record_format: RecordFormat,
+
+    /// Opus recording bitrate in kbps
+    #[arg(long, default_value_t = recorder::DEFAULT_OPUS_BITRATE_KBPS, requires = "record")]
+    record_bitrate: u32,
+
+    /// Warn when free space on the recording volume drops below this many MB
+    #[arg(long, default_value_t = recorder::DEFAULT_WARN_FREE_MB)]
+    record_min_free: u64,
+
+    /// Add sync markers to recordings for lining them up with camera files: "bwf" (start time of day in the WAV's bext chunk), "sidecar" (a <file>.timecode.csv with the wall-clock timecode every --sync-interval seconds) or "both"
+    #[arg(long, requires = "record")]
+    sync_markers: Option<SyncMarkers>,
+
+    /// Seconds between sync markers in the timecode sidecar
+    #[arg(long, value_name = "SECONDS", default_value_t = timecode::DEFAULT_SYNC_INTERVAL_SECS, requires = "sync_markers")]
+    sync_interval: u32,
+
+    /// Timecode frame rate (non-drop) used in the sidecar and the log
+    #[arg(long, value_name = "FPS", default_value_t = timecode::DEFAULT_TIMECODE_FPS, requires = "sync_markers")]
+    timecode_fps: u32,
+
+    /// Keep recordings local even if [recording.upload] is configured
+    #[arg(long, requires = "record")]
+    no_upload: bool,
+
+    /// Disable desktop notifications (server disconnects, low recording disk space)
+    #[arg(long)]
+    no_notify: bool,
+
+    /// Ignore keyboard shortcuts while monitoring (bypass, mute, pitch nudge, next preset; keys are set in [hotkeys] in config)
+    #[arg(long)]
+    no_hotkeys: bool,
+
+    /// Control socket for `makebeliv ctl` (default: $XDG_RUNTIME_DIR/makebeliv.sock, or \\.\pipe\makebeliv on Windows)
+    #[arg(long)]
+    control_socket: Option<PathBuf>,
+
+    /// Do not open the control socket
+    #[arg(long, conflicts_with = "control_socket")]
+    no_control_socket: bool,
+
+    /// Print input/output RMS and peak levels every this many seconds (0: only warn about clipping and a near-silent input)
+    #[arg(long, value_name = "SECONDS", default_value_t = DEFAULT_LEVEL_INTERVAL_SECS)]
+    level_interval: u64,
+
+    /// Show a live terminal dashboard: input/output level meters, round-trip latency per chunk, failed and dropped chunks, model/pitch/noise and buffer fill (logs go to a pane)
+    #[arg(long, conflicts_with = "dump_pipeline")]
+    tui: bool,
+
+    /// Convert files in the background through the same server while monitoring: a WAV file, a directory or a glob (live chunks keep priority)
+    #[arg(long)]
+    batch: Option<PathBuf>,
+
+    /// Output directory for --batch (default: audio/output)
+    #[arg(long, requires = "batch")]
+    batch_output_dir: Option<PathBuf>,
+
+    /// Convert one chunk, print the processing graph (stages, sample rates, buffer sizes, latencies) and stop (text, dot)
+    #[arg(long, conflicts_with = "batch")]
+    dump_pipeline: Option<GraphFormat>,
+
+    /// Write the --dump-pipeline graph to this file instead of stdout
+    #[arg(long, requires = "dump_pipeline")]
+    dump_pipeline_output: Option<PathBuf>,
+}
+
+#[derive(Subcommand)]
+enum ConfigAction {
+    /// Print the effective configuration after merging defaults and config files
+    Show,
+
+    /// Migrate the user and project config files to the current format (originals are kept as <file>.v<N>.bak)
+    Upgrade,
+}
+
+#[derive(Subcommand)]
+enum PresetAction {
+    /// Save the given settings as a preset in the user config (replaces one with the same name)
+    Save {
+        /// Preset name
+        name: String,
+
+        #[command(flatten)]
+        values: PresetValues,
+    },
+
+    /// List presets from the user and project config
+    List,
+
+    /// Make a preset the default by writing its values to [conversion]/[audio] in the user config
+    Apply {
+        /// Preset name
+        name: String,
+    },
+
+    /// Delete a preset from the user config
+    Delete {
+        /// Preset name
+        name: String,
+    },
+}
+
+#[derive(Args)]
+struct PresetValues {
+    /// Voice model
+    #[arg(long)]
+    model: Option<String>,
+
+    /// Pin the model version the server must report (the first 12 hex digits of the weights' SHA-256); conversions warn, or refuse with --strict-versions, when it differs
+    #[arg(long)]
+    model_version: Option<String>,
+
+    /// Pitch shift in semitones
+    #[arg(long, allow_hyphen_values = true)]
+    pitch: Option<i32>,
+
+    /// Background noise type
+    #[arg(long)]
+    noise: Option<String>,
+
+    /// Background noise level (0.0-1.0)
+    #[arg(long)]
+    noise_level: Option<f32>,
+
+    /// Breath level (0.0-1.0)
+    #[arg(long)]
+    breath_level: Option<f32>,
+
+    /// Directory of breath WAV files
+    #[arg(long)]
+    breath_dir: Option<PathBuf>,
+
+    /// Input source, same syntax as `monitor --input`
+    #[arg(long)]
+    input: Option<String>,
+
+    /// Output device name
+    #[arg(long)]
+    output_device: Option<String>,
+}
+
+#[derive(Subcommand)]
+enum SessionAction {
+    /// List sessions the server keeps fluctuation state for
+    List {
+        /// API server URL (default: [server] api_url in config, then http://localhost:8000)
+        #[arg(long)]
+        api_url: Option<String>,
+    },
+
+    /// Reset a session's fluctuation state on the server
+    Reset {
+        /// Session ID (see `makebeliv session list`)
+        #[arg(required_unless_present = "all")]
+        session_id: Option<String>,
+
+        /// Reset every session on the server
+        #[arg(long, conflicts_with = "session_id")]
+        all: bool,
+
+        /// API server URL (default: [server] api_url in config, then http://localhost:8000)
+        #[arg(long)]
+        api_url: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum CtlAction {
+    /// Show the current model, pitch and noise level
+    Get,
+
+    /// Set a parameter: model <name>, pitch <semitones> (e.g. +2) or noise_level <0.0-1.0>
+    Set {
+        /// Parameter name: model, pitch or noise_level
+        param: String,
+
+        /// New value
+        #[arg(allow_hyphen_values = true)]
+        value: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum AuthAction {
+    /// Save an API key in the OS keychain (prompted, or read from stdin when piped; never written to config)
+    Login {
+        /// API server URL (default: [server] api_url in config, then http://localhost:8000)
+        #[arg(long)]
+        api_url: Option<String>,
+    },
+
+    /// Remove the saved API key from the OS keychain
+    Logout {
+        /// API server URL (default: [server] api_url in config, then http://localhost:8000)
+        #[arg(long)]
+        api_url: Option<String>,
+    },
+}
+
+#[cfg(feature = "devices")]
+#[derive(Subcommand)]
+enum VmicAction {
+    /// Create a virtual microphone and route monitor output to it
+    Create {
+        /// Audio backend providing the virtual device
+        #[arg(long, value_enum, default_value = "alsa")]
+        backend: VmicBackend,
+
+        /// Skip confirmation prompts
+        #[arg(short, long)]
+        yes: bool,
+    },
+
+    /// Check the virtual audio driver (VB-Cable, BlackHole, ALSA loopback) and routing
+    Doctor {
+        /// Apply suggested config fixes without asking
+        #[arg(short, long)]
+        yes: bool,
+    },
+}
+
+/// ログの出力先を決める（`monitor --tui` ではダッシュボードのログ欄へ）
+fn init_logging(cli: &Cli) {
+    #[cfg(feature = "tui")]
+    if let Commands::Monitor(args) = &cli.command {
+        if args.tui {
+            tracing_subscriber::fmt()
+                .with_ansi(false)
+                .with_writer(makebeliv::tui::capture_logs())
+                .init();
+            return;
+        }
+    }
+    let _ = cli;
     tracing_subscriber::fmt::init();
+}
 
+#[tokio::main]
+async fn main() -> ExitCode {
     let cli = Cli::parse();
+    init_logging(&cli);
+
+    match run(cli).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) if e.is::<Interrupted>() => {
+            warn!("{}", Interrupted);
+            ExitCode::from(shutdown::INTERRUPTED_EXIT_CODE)
+        }
+        Err(e) => {
+            eprintln!("Error: {:?}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(cli: Cli) -> Result<()> {
+    #[cfg(feature = "devices")]
+    if let Some(name) = &cli.audio_host {
+        audio::select_host(name)?;
+    }
+
+    if let Some(dir) = &cli.debug_http {
+        debug_http::enable(dir)?;
+    }
+    anyhow::ensure!(
+        [cli.timeout, cli.connect_timeout]
+            .iter()
+            .all(|seconds| seconds.is_finite() && *seconds > 0.0),
+        "--timeout と --connect-timeout は0より大きい秒数を指定してください"
+    );
+    client::set_timeouts(Timeouts::new(
+        Duration::from_secs_f64(cli.connect_timeout),
+        Duration::from_secs_f64(cli.timeout),
+    ));
 
     match cli.command {
         Commands::Setup { yes } => setup_environment(yes),
-        Commands::Server { host, port } => start_server(host, port),
-        Commands::Process {
+        Commands::Server { host, port } => start_server(host, port).await,
+        #[cfg(feature = "mock-server")]
+        Commands::MockServer {
+            host,
+            port,
+            latency_ms,
+            jitter_ms,
+            error_rate,
+            api_key,
+            sample_rate,
+        } => {
+            let addr = format!("{}:{}", host, port)
+                .parse()
+                .with_context(|| format!("アドレスが不正です: {}:{}", host, port))?;
+            mock_server::serve(
+                addr,
+                MockOptions {
+                    latency_ms,
+                    jitter_ms,
+                    error_rate,
+                    api_key,
+                    sample_rate,
+                },
+            )
+            .await
+        }
+        Commands::Process(args) => process_audio(*args).await,
+        Commands::Watch(args) => watch_folder(args).await,
+        Commands::Reproduce {
+            manifest,
             input,
             output,
-            model,
-            noise,
-            pitch,
-            use_api,
             api_url,
-        } => {
-            if use_api {
-                process_audio_via_api(input, output, model, noise, pitch, api_url).await
-            } else {
-                process_audio_direct(input, output, model, noise, pitch)
+            force,
+        } => reproduce(&manifest, input, output, api_url, force).await,
+        Commands::VerifyWatermark { file, key } => verify_watermark(&file, key),
+        #[cfg(feature = "devices")]
+        Commands::Monitor(args) => monitor_realtime(*args).await,
+        Commands::Status { api_url } => show_status(api_url).await,
+        Commands::Bench {
+            models,
+            chunk_ms,
+            iterations,
+            input,
+            chunk_sizes,
+            concurrency,
+            api_url,
+        } => match input {
+            Some(input) => {
+                let config = SweepConfig {
+                    chunk_sizes,
+                    concurrency,
+                };
+                run_sweep(models, &input, config, api_url).await
             }
-        }
-        Commands::Monitor {
+            None => run_bench(models, chunk_ms, iterations, api_url).await,
+        },
+        #[cfg(feature = "devices")]
+        Commands::Latency {
+            input,
+            output_device,
             model,
-            noise,
-            pitch,
+            chunk_ms,
+            device_buffer,
+            jitter_buffer_ms,
+            clicks,
+            loopback,
             api_url,
-        } => monitor_realtime(model, noise, pitch, api_url).await,
-        Commands::ListDevices => {
+        } => {
+            measure_latency(
+                input,
+                output_device,
+                model,
+                chunk_ms,
+                device_buffer,
+                jitter_buffer_ms,
+                clicks,
+                loopback,
+                api_url,
+            )
+            .await
+        }
+        Commands::Config { action } => match action {
+            ConfigAction::Show => show_config(),
+            ConfigAction::Upgrade => upgrade_config(),
+        },
+        Commands::Preset { action } => match action {
+            PresetAction::Save { name, values } => save_preset(&name, values),
+            PresetAction::List => list_presets(),
+            PresetAction::Apply { name } => apply_preset(&name),
+            PresetAction::Delete { name } => delete_preset(&name),
+        },
+        Commands::Session { action } => match action {
+            SessionAction::List { api_url } => list_sessions(api_url).await,
+            SessionAction::Reset {
+                session_id,
+                all,
+                api_url,
+            } => reset_sessions(session_id, all, api_url).await,
+        },
+        Commands::Ctl { action, socket } => control_monitor(action, socket).await,
+        Commands::Auth { action } => match action {
+            AuthAction::Login { api_url } => auth_login(api_url).await,
+            AuthAction::Logout { api_url } => auth_logout(api_url),
+        },
+        Commands::Stats { days } => {
+            let sessions = stats::load(&stats::store_path()?)?;
+            stats::print_report(&sessions, days);
+            Ok(())
+        }
+        #[cfg(feature = "devices")]
+        Commands::ListDevices { watch } => {
             audio::list_devices()?;
+            if watch {
+                watch_devices().await;
+            }
             Ok(())
         }
+        #[cfg(feature = "devices")]
+        Commands::Vmic { action } => match action {
+            VmicAction::Create { backend, yes } => vmic::create(backend, yes),
+            VmicAction::Doctor { yes } => vmic::doctor(yes),
+        },
+        Commands::SelfUpdate { check, yes } => self_update(check, yes).await,
     }
 }
 
@@ -167,8 +1172,11 @@ fn setup_environment(skip_confirm: bool) -> Result<()> {
         }
     }
 
+    let root = python::project_root()?;
+    info!("Python側: {}", root.display());
+
     info!("仮想環境を作成中...");
-    let venv_status = Command::new("uv")
+    let venv_status = python::uv(&root)
         .args(["venv", ".venv"])
         .status()
         .context("仮想環境の作成に失敗")?;
@@ -192,7 +1200,7 @@ fn setup_environment(skip_confirm: bool) -> Result<()> {
 
     if use_gpu {
         info!("CUDA対応PyTorchをインストール中...");
-        let torch_install = Command::new("uv")
+        let torch_install = python::uv(&root)
             .args([
                 "pip",
                 "install",
@@ -212,7 +1220,7 @@ fn setup_environment(skip_confirm: bool) -> Result<()> {
     }
 
     // その他の依存関係をインストール
-    let deps_status = Command::new("uv")
+    let deps_status = python::uv(&root)
         .args(["pip", "install", "-r", "requirements.txt"])
         .status()
         .context("依存関係のインストールに失敗")?;
@@ -237,24 +1245,33 @@ fn setup_environment(skip_confirm: bool) -> Result<()> {
     Ok(())
 }
 
-fn start_server(host: String, port: u16) -> Result<()> {
+async fn start_server(host: String, port: u16) -> Result<()> {
     info!("🚀 APIサーバーを起動中...");
     info!("   アドレス: {}:{}", host, port);
 
-    // uvxを使ってAPIサーバーを起動
-    let status = Command::new("uv")
-        .args([
-            "run",
-            "uvicorn",
-            "python.api_server:app",
-            "--host",
-            &host,
-            "--port",
-            &port.to_string(),
-            "--reload",
-        ])
-        .status()
-        .context("APIサーバー起動エラー")?;
+    let root = python::project_root()?;
+    info!("   Python側: {}", root.display());
+
+    // uvを使ってAPIサーバーを起動（Ctrl+Cではuvicornが終了するのを待つ）
+    let mut command = python::uv(&root);
+    command.args([
+        "run",
+        "uvicorn",
+        "python.api_server:app",
+        "--host",
+        &host,
+        "--port",
+        &port.to_string(),
+        "--reload",
+    ]);
+    let status = match shutdown::run_child(command).await {
+        Ok(status) => status,
+        Err(e) if e.is::<Interrupted>() => {
+            info!("APIサーバーを停止しました");
+            return Ok(());
+        }
+        Err(e) => return Err(e.context("APIサーバー起動エラー")),
+    };
 
     if !status.success() {
         anyhow::bail!("APIサーバーの起動に失敗しました");
@@ -263,133 +1280,1122 @@ fn start_server(host: String, port: u16) -> Result<()> {
     Ok(())
 }
 
-fn process_audio_direct(
-    input: PathBuf,
-    output: Option<PathBuf>,
-    model: String,
-    noise: String,
-    pitch: i32,
-) -> Result<()> {
-    info!("🎙️ 音声ファイル処理モード（直接実行）");
+/// 1ファイルか、ディレクトリ・グロブに一致するファイルをすべて変換する
+async fn process_audio(args: ProcessArgs) -> Result<()> {
+    let Some(inputs) = batch::expand_inputs(&args.input)? else {
+        return process_file(args).await;
+    };
+    anyhow::ensure!(
+        args.output.is_none(),
+        "複数のファイルを変換するときは -o ではなく --output-dir を指定してください"
+    );
+    anyhow::ensure!(
+        args.cues.is_none(),
+        "--cues は1つのファイルを変換するときだけ指定できます"
+    );
+    anyhow::ensure!(args.jobs >= 1, "--jobs は1以上で指定してください");
+
+    let options = Arc::new(process_options(&args)?);
+    manifest::check_model_version(&options, args.strict_versions).await?;
+    let output_dir = args
+        .output_dir
+        .clone()
+        .unwrap_or_else(|| PathBuf::from(batch::DEFAULT_OUTPUT_DIR));
+    let plan = batch::plan_outputs(
+        &inputs,
+        &output_dir,
+        &args.name_template,
+        &options.model,
+        options.pitch,
+    )?;
+    info!(
+        "📂 {}個のファイルを変換します → {}（同時に{}個）",
+        plan.len(),
+        output_dir.display(),
+        args.jobs
+    );
+
+    // 同時に変換するファイル数をセマフォで抑える
+    let semaphore = Arc::new(Semaphore::new(args.jobs));
+    let total = plan.len();
+    let mut tasks = JoinSet::new();
+    for (i, (input, output)) in plan.into_iter().enumerate() {
+        let semaphore = semaphore.clone();
+        let options = options.clone();
+        tasks.spawn(async move {
+            let _permit = semaphore.acquire_owned().await.expect("セマフォは閉じない");
+            info!("[{}/{}] 開始: {}", i + 1, total, input.display());
+            let started = Instant::now();
+            let result = process::process_file(&input, &output, &options).await;
+            (input, output, started.elapsed(), result)
+        });
+    }
 
-    if !input.exists() {
-        anyhow::bail!("入力ファイルが見つかりません: {}", input.display());
+    let mut report = batch::BatchReport::default();
+    while let Some(joined) = tasks.join_next().await {
+        let (input, output, elapsed, result) = joined.context("変換タスクの実行エラー")?;
+        report.record(&input, &output, elapsed, &result);
+        match result {
+            Ok(()) => info!(
+                "[完了 {}/{}] {}（{:.1}秒）",
+                report.finished(),
+                total,
+                input.display(),
+                elapsed.as_secs_f64()
+            ),
+            // 残りの変換は取り消す（子プロセスも止まる）
+            Err(e) if e.is::<Interrupted>() => {
+                tasks.abort_all();
+                report.print();
+                return Err(e);
+            }
+            Err(e) => warn!(
+                "[失敗 {}/{}] {}: {:#}",
+                report.finished(),
+                total,
+                input.display(),
+                e
+            ),
+        }
     }
 
-    let output_path = output.unwrap_or_else(|| PathBuf::from("audio/output/processed.wav"));
+    report.print();
+    let failures = report.failures();
+    anyhow::ensure!(
+        failures == 0,
+        "{}個のファイルの変換に失敗しました",
+        failures
+    );
+    Ok(())
+}
 
-    info!("設定:");
-    info!("  入力: {}", input.display());
-    info!("  出力: {}", output_path.display());
-    info!("  モデル: {}", model);
-    info!("  ノイズ: {}", noise);
-    info!("  ピッチ: {:+} semitones", pitch);
+/// フォルダに置かれたWAVファイルを、その時点の設定とプリセットで変換し続ける
+async fn watch_folder(args: WatchArgs) -> Result<()> {
+    let WatchArgs {
+        dir,
+        output_dir,
+        name_template,
+        settle_ms,
+        preset,
+        strict_versions,
+        engine,
+        use_api,
+        api_url,
+    } = args;
+    let output_dir = output_dir.unwrap_or_else(|| PathBuf::from(batch::DEFAULT_OUTPUT_DIR));
+    std::fs::create_dir_all(&output_dir)
+        .with_context(|| format!("出力ディレクトリを作成できません: {}", output_dir.display()))?;
+    // テンプレートの誤りは最初のファイルを待つ前に知らせる
+    batch::output_name(&name_template, Path::new("input.wav"), "model", 0)?;
+
+    let mut watcher = FolderWatcher::new(&dir, Duration::from_millis(settle_ms))?;
+    info!(
+        "👀 フォルダを監視しています: {} → {}",
+        watcher.dir().display(),
+        output_dir.display()
+    );
+    println!("\n新しいWAVファイルを置くと変換します... Ctrl+C で停止");
+
+    let mut converted = 0;
+    let mut failed = 0;
+    loop {
+        let input = tokio::select! {
+            input = watcher.next() => match input {
+                Some(input) => input,
+                None => break,
+            },
+            _ = shutdown::ctrl_c() => break,
+        };
+
+        // 設定ファイルやプリセットの変更を次のファイルから反映する
+        let result = async {
+            let conversion = load_config(preset.as_deref())?.conversion;
+            let name =
+                batch::output_name(&name_template, &input, &conversion.model, conversion.pitch)?;
+            let output = output_dir.join(name);
+            watcher.ignore(&output);
+            info!("📥 {} → {}", input.display(), output.display());
+            process_file(ProcessArgs {
+                input: input.clone(),
+                output: Some(output),
+                output_dir: None,
+                name_template: name_template.clone(),
+                jobs: 1,
+                preset: preset.clone(),
+                model: None,
+                noise: None,
+                noise_level: None,
+                breath_level: None,
+                breath_dir: None,
+                pitch: None,
+                pitch_contour: None,
+                rate_fluctuation: None,
+                bit_depth: None,
+                resample_quality: ResampleQuality::Best,
+                only_speaker: None,
+                diarization: DiarizationSource::Auto,
+                speakers: diarize::DEFAULT_SPEAKERS,
+                cues: None,
+                cast: Vec::new(),
+                range: None,
+                splice: false,
+                preserve_gaps: false,
+                sync_markers: None,
+                sync_interval: timecode::DEFAULT_SYNC_INTERVAL_SECS,
+                timecode_fps: timecode::DEFAULT_TIMECODE_FPS,
+                bwf: false,
+                manifest: false,
+                seed: None,
+                watermark: false,
+                watermark_key: None,
+                strict_versions,
+                engine,
+                use_api,
+                api_url: api_url.clone(),
+                no_progress: false,
+            })
+            .await
+        }
+        .await;
+
+        match result {
+            Ok(()) => converted += 1,
+            Err(e) if e.is::<Interrupted>() => break,
+            Err(e) => {
+                failed += 1;
+                warn!("⚠ 変換に失敗しました（{}）: {:#}", input.display(), e);
+            }
+        }
+    }
 
-    // Pythonスクリプトを実行
-    let status = Command::new("uv")
-        .args(["run", "python", "python/file_processor.py"])
-        .arg(input.to_str().unwrap())
-        .status()
-        .context("Pythonスクリプトの実行に失敗")?;
+    info!(
+        "フォルダの監視を終了しました（変換 {} / 失敗 {}）",
+        converted, failed
+    );
+    Ok(())
+}
 
-    if !status.success() {
-        anyhow::bail!("音声処理に失敗しました");
+/// 引数と設定ファイル（プリセット）から変換の設定を決める
+fn process_options(args: &ProcessArgs) -> Result<ProcessOptions> {
+    let config = load_config(args.preset.as_deref())?;
+    let mut options = ProcessOptions::from_config(&config);
+    options.engine = args.engine;
+    options.use_api = args.use_api;
+    if let Some(api_url) = &args.api_url {
+        options.api_url = api_url.clone();
+    }
+    if let Some(model) = &args.model {
+        // 固定したバージョンは設定ファイルのモデルのもの
+        if *model != options.model {
+            options.model_version = None;
+        }
+        options.model = model.clone();
+    }
+    if let Some(noise) = &args.noise {
+        options.noise = noise.clone();
+    }
+    if let Some(level) = args.noise_level {
+        options.noise_level = level;
+    }
+    if let Some(level) = args.breath_level {
+        options.breath_level = level;
     }
+    if let Some(dir) = &args.breath_dir {
+        options.breath_dir = Some(dir.clone());
+    }
+    if let Some(pitch) = args.pitch {
+        options.pitch = pitch;
+    }
+    if let Some(cents) = args.pitch_contour {
+        options.pitch_contour = cents;
+    }
+    if let Some(percent) = args.rate_fluctuation {
+        options.rate_fluctuation = percent;
+    }
+    options.bit_depth = args.bit_depth;
+    options.resample_quality = args.resample_quality;
+    anyhow::ensure!(args.speakers >= 1, "--speakers は1以上で指定してください");
+    options.only_speaker = args.only_speaker.clone();
+    options.diarization = args.diarization;
+    options.speakers = args.speakers;
+    if let Some(path) = &args.cues {
+        let mut cast = BTreeMap::new();
+        for entry in &args.cast {
+            let (name, preset) = entry
+                .split_once('=')
+                .filter(|(name, preset)| !name.trim().is_empty() && !preset.trim().is_empty())
+                .with_context(|| {
+                    format!("--cast は 名前=プリセット で指定してください: {}", entry)
+                })?;
+            cast.insert(name.trim().to_string(), preset.trim().to_string());
+        }
+        let mut cues = CueSheet::load(path)?;
+        cues.cast(&cast, &config)?;
+        options.cues = Some(cues);
+    }
+    options.range = args.range;
+    options.splice = args.splice;
+    options.preserve_gaps = args.preserve_gaps;
+    options.sync = args
+        .sync_markers
+        .map(|markers| SyncOptions::new(markers, args.sync_interval, args.timecode_fps))
+        .transpose()?;
+    options.bwf = args.bwf;
+    options.manifest = args.manifest;
+    options.seed = args.seed;
+    options.watermark |= args.watermark;
+    if let Some(key) = &args.watermark_key {
+        options.watermark = true;
+        options.watermark_key = key.clone();
+    }
+    if args.use_api && !args.no_progress {
+        options.progress = Some(TransferProgress::new());
+    }
+    Ok(options)
+}
 
-    info!("✅ 処理完了: {}", output_path.display());
+async fn process_file(args: ProcessArgs) -> Result<()> {
+    let options = process_options(&args)?;
+    manifest::check_model_version(&options, args.strict_versions).await?;
+    let output = args
+        .output
+        .unwrap_or_else(|| process::default_output(&args.input));
+    process::process_file(&args.input, &output, &options).await
+}
 
+/// 電子透かしを探して結果を表示する（見つからなければエラー）
+fn verify_watermark(file: &Path, key: Option<String>) -> Result<()> {
+    let key = match key {
+        Some(key) => key,
+        None => Config::load()?
+            .conversion
+            .watermark_key
+            .unwrap_or_else(|| watermark::DEFAULT_KEY.to_string()),
+    };
+    let spec = wav::read_spec(file)?;
+    let Some(detection) = watermark::detect_file(file, &key)? else {
+        anyhow::bail!(
+            "短すぎて判定できません（{:.1}秒以上必要です）: {}",
+            watermark::min_seconds(spec.sample_rate),
+            file.display()
+        );
+    };
+    if !detection.is_present() {
+        anyhow::bail!(
+            "電子透かしは見つかりませんでした（相関 {:.1}、しきい値 {:.1}）: {}",
+            detection.score,
+            watermark::DETECTION_THRESHOLD,
+            file.display()
+        );
+    }
+    println!(
+        "✅ 電子透かしを検出しました: {}（相関 {:.1}、しきい値 {:.1}）",
+        file.display(),
+        detection.score,
+        watermark::DETECTION_THRESHOLD
+    );
+    println!("このファイルは makebeliv で変換された音声です");
     Ok(())
 }
 
-async fn process_audio_via_api(
-    input: PathBuf,
+/// マニフェストの記録どおりに変換をやり直す
+async fn reproduce(
+    path: &Path,
+    input: Option<PathBuf>,
     output: Option<PathBuf>,
-    model: String,
-    noise: String,
-    pitch: i32,
-    api_url: String,
+    api_url: Option<String>,
+    force: bool,
 ) -> Result<()> {
-    info!("🎙️ 音声ファイル処理モード（API経由）");
+    let manifest = Manifest::load(path)?;
+    info!(
+        "🔁 マニフェストの記録どおりに変換します: {}",
+        path.display()
+    );
+    info!(
+        "  記録: makebeliv {}、{} に変換",
+        manifest.makebeliv_version, manifest.timings.started_at
+    );
+
+    let input = input.unwrap_or_else(|| manifest.input.path.clone());
+    if manifest.verify_input(&input)? {
+        info!("  ✓ 入力が記録と一致しました: {}", input.display());
+    } else {
+        anyhow::ensure!(
+            force,
+            "入力が記録と違います（SHA-256が一致しません）: {}。--force で続行できます",
+            input.display()
+        );
+        warn!("⚠ 入力が記録と違いますが、--force のため続行します");
+    }
 
-    if !input.exists() {
-        anyhow::bail!("入力ファイルが見つかりません: {}", input.display());
+    let mut options = manifest.parameters.to_options(&load_config(None)?)?;
+    if let Some(api_url) = api_url {
+        options.api_url = api_url;
+    }
+    let (server, models) = manifest::versions(&options).await;
+    if let (Some(recorded), Some(current)) = (&manifest.server, &server) {
+        if recorded.version != current.version {
+            warn!(
+                "⚠ サーバーのバージョンが記録と違います（記録: {}、現在: {}）",
+                recorded.version.as_deref().unwrap_or("不明"),
+                current.version.as_deref().unwrap_or("不明")
+            );
+        }
+    }
+    let mismatches = manifest.model_mismatches(&models);
+    for mismatch in &mismatches {
+        warn!("⚠ モデルのバージョンが記録と違います: {}", mismatch);
     }
+    anyhow::ensure!(
+        mismatches.is_empty() || force,
+        "モデルが記録と違うため、同じ声になりません。--force で続行できます"
+    );
+
+    let output = output.unwrap_or_else(|| manifest::reproduced_path(&manifest.output.path));
+    process::process_file(&input, &output, &options).await?;
+
+    let (sha256, _) = manifest::file_sha256(&output)?;
+    if sha256 == manifest.output.sha256 {
+        info!("✅ 出力が記録と一致しました（SHA-256）");
+    } else {
+        info!("  出力のSHA-256は記録と一致しません（bext の作成日時などのメタデータや、サーバーでの計算の揺れでも変わります）");
+    }
+    Ok(())
+}
 
-    let output_path = output.unwrap_or_else(|| PathBuf::from("audio/output/processed.wav"));
+/// 引数と設定ファイル（プリセット）からリアルタイム変換の設定を決める
+#[cfg(feature = "devices")]
+fn monitor_options(config: &Config, args: &MonitorArgs) -> Result<PipelineOptions> {
+    let mut options = PipelineOptions::from_config(config)?;
+    if let Some(api_url) = &args.api_url {
+        options.api_url = api_url.clone();
+    }
+    if let Some(model) = &args.model {
+        // 固定したバージョンは設定ファイルのモデルのもの
+        if *model != options.model {
+            options.model_version = None;
+        }
+        options.model = model.clone();
+    }
+    if let Some(noise) = &args.noise {
+        options.noise = noise.clone();
+    }
+    if let Some(level) = args.noise_level {
+        options.noise_level = level;
+    }
+    if let Some(width) = args.noise_width {
+        options.noise_width = width;
+    }
+    if let Some(level) = args.breath_level {
+        options.breath_level = level;
+    }
+    if let Some(dir) = &args.breath_dir {
+        options.breath_dir = Some(dir.clone());
+    }
+    if let Some(pitch) = args.pitch {
+        options.pitch = pitch;
+    }
+    if let Some(cents) = args.pitch_contour {
+        options.pitch_contour = cents;
+    }
+    if let Some(pan) = args.pan {
+        options.pan = pan;
+    }
+    if let Some(width) = args.stereo_width {
+        options.stereo_width = width;
+    }
+    if let Some(profile) = args.profile {
+        options.profile = profile;
+    }
+    options.low_power |= args.low_power;
+    options.chunk_ms = args.chunk_ms;
+    options.jitter_buffer_ms = args.jitter_buffer_ms;
+    options.codec = args.codec;
+    options.crossfade_ms = args.crossfade_ms;
+    options.pad_final = args.pad_final;
+    options.align_zero_crossings = args.align_zero_crossings;
+    options.max_latency_ms = args.max_latency_ms;
+    options.catchup = args.catchup;
+    options.adaptive_chunk = args.adaptive_chunk;
+    options.max_chunk_ms = args.max_chunk_ms;
+    options.overload_passthrough = args.overload_passthrough;
+    options.fallback = args.fallback;
+    if let Some(mode) = args.vad {
+        options.vad = mode == VadMode::On;
+    }
+    if let Some(threshold) = args.vad_threshold {
+        options.vad_threshold = threshold;
+    }
+    if let Some(input) = &args.input {
+        options.input = input.clone();
+    }
+    if let Some(channel) = args.channel {
+        options.channel = channel;
+    }
+    if let Some(name) = &args.output_device {
+        options.output_device = Some(name.clone());
+    }
+    options.sample_rate = args.sample_rate;
+    if let Some(quality) = args.resample_quality {
+        options.resample_quality = quality;
+    }
+    options.input_format = args.input_format;
+    options.device_buffer = args.device_buffer;
+    options.bt_a2dp = args.bt_a2dp;
+    options.engine = args.engine;
+    options.transport = args.transport;
+    options.strict_versions = args.strict_versions;
+    if !args.bleep_words.is_empty() {
+        options.bleep_words = args.bleep_words.clone();
+    }
+    if let Some(mode) = args.bleep_mode {
+        options.bleep_mode = mode;
+    }
+    if let Some(window) = args.bleep_window {
+        options.bleep_window_ms = window;
+    }
+    options.chaos = ChaosOptions {
+        latency: args.inject_latency.unwrap_or_default(),
+        error_rate: args.inject_error_rate,
+        seed: args.inject_seed,
+    };
+    options.script = args.script.clone();
+    options.sidetone = args.sidetone.map(|level_db| SecondaryOptions {
+        device: args.sidetone_device.clone(),
+        level_db,
+    });
+    options.echo = args.echo_device.clone().map(|device| SecondaryOptions {
+        device: Some(device),
+        level_db: args.echo_level,
+    });
+    let sync = args
+        .sync_markers
+        .map(|markers| SyncOptions::new(markers, args.sync_interval, args.timecode_fps))
+        .transpose()?;
+    options.recording = args.record.clone().map(|dir| RecordingOptions {
+        dir,
+        warn_free_mb: args.record_min_free,
+        format: args.record_format,
+        bitrate_kbps: args.record_bitrate,
+        sync,
+    });
+    options.upload = !args.no_upload;
+    options.notify = !args.no_notify;
+    options.hotkeys &= !args.no_hotkeys;
+    options.preset = args.preset.clone();
+    if args.no_control_socket {
+        options.control_socket = None;
+    } else if let Some(path) = &args.control_socket {
+        options.control_socket = Some(path.clone());
+    }
+    options.level_interval = args.level_interval;
+    options.dashboard = args.tui;
+    options.batch = args.batch.clone();
+    options.batch_output_dir = args.batch_output_dir.clone();
+    options.dump_pipeline = args
+        .dump_pipeline
+        .map(|format| (format, args.dump_pipeline_output.clone()));
+    options.validate()?;
+    Ok(options)
+}
 
-    info!("設定:");
-    info!("  入力: {}", input.display());
-    info!("  出力: {}", output_path.display());
-    info!("  モデル: {}", model);
-    info!("  ノイズ: {}", noise);
-    info!("  ピッチ: {:+} semitones", pitch);
-    info!("  APIサーバー: {}", api_url);
+#[cfg(feature = "devices")]
+async fn monitor_realtime(args: MonitorArgs) -> Result<()> {
+    let config = load_config(args.preset.as_deref())?;
+    let options = monitor_options(&config, &args)?;
+    let dump = options.dump_pipeline.is_some();
+    let monitor = Monitor::connect(&config, options).await?;
+    if !dump {
+        println!("\n🎙️ 変換中... Ctrl+C で停止");
+    }
+    let report = monitor.run(shutdown::ctrl_c()).await?;
+    let Some(summary) = &report.summary else {
+        // 1チャンクだけの確認なので、概要も履歴も残さない
+        return Ok(());
+    };
 
-    // APIクライアント作成
-    let client = VoiceConversionClient::new(api_url);
+    summary.print();
+    if let Err(e) = stats::record(summary) {
+        warn!("セッション履歴の記録に失敗: {}", e);
+    }
+    if let Some(path) = args.summary_json {
+        summary.write_json(&path)?;
+        info!("セッション概要を書き出しました: {}", path.display());
+    }
+    report.upload_recordings(shutdown::ctrl_c()).await
+}
 
-    // サーバー状態確認
-    match client.check_status().await {
-        Ok(status) => {
-            info!("✓ サーバー接続成功: {:?}", status);
+/// デバイスの接続・切断を Ctrl+C まで表示し続ける
+#[cfg(feature = "devices")]
+async fn watch_devices() {
+    println!("\nデバイスの変化を監視中（Ctrl+C で終了）...");
+    let mut events = hotplug::watch(hotplug::DEFAULT_POLL_INTERVAL);
+
+    loop {
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => break,
+            event = events.recv() => match event {
+                Some(event) => {
+                    println!("[{}] {}", chrono::Local::now().format("%H:%M:%S"), event)
+                }
+                None => break,
+            },
         }
-        Err(e) => {
-            warn!("⚠ サーバー接続エラー: {}", e);
-            println!("\nAPIサーバーが起動していない可能性があります。");
-            println!("以下のコマンドでサーバーを起動してください:");
-            println!("  makebeliv server");
-            return Err(e);
+    }
+}
+
+/// 設定を読み込み、プリセットが指定されていれば重ねる
+fn load_config(preset: Option<&str>) -> Result<Config> {
+    let mut config = Config::load()?;
+    if let Some(name) = preset {
+        config.apply_preset(name)?;
+        info!("プリセット: {}", name);
+    }
+    Ok(config)
+}
+
+/// プリセットを保存するユーザー設定ファイル
+fn preset_store() -> Result<PathBuf> {
+    config::user_config_path().context("ユーザー設定の場所が見つかりません")
+}
+
+fn save_preset(name: &str, values: PresetValues) -> Result<()> {
+    let PresetValues {
+        model,
+        model_version,
+        pitch,
+        noise,
+        noise_level,
+        breath_level,
+        breath_dir,
+        input,
+        output_device,
+    } = values;
+    let preset = config::Preset {
+        model,
+        model_version,
+        pitch,
+        noise,
+        noise_level,
+        breath_level,
+        breath_dir,
+        input,
+        output_device,
+    };
+    anyhow::ensure!(
+        !preset.is_empty(),
+        "保存する値を指定してください（--model, --model-version, --pitch, --noise, --noise-level, --breath-level, --breath-dir, --input, --output-device）"
+    );
+    if let Some(level) = preset.noise_level {
+        anyhow::ensure!(
+            (0.0..=1.0).contains(&level),
+            "--noise-level は0.0〜1.0で指定してください"
+        );
+    }
+    if let Some(level) = preset.breath_level {
+        anyhow::ensure!(
+            (0.0..=1.0).contains(&level),
+            "--breath-level は0.0〜1.0で指定してください"
+        );
+    }
+    #[cfg(feature = "devices")]
+    if let Some(input) = &preset.input {
+        input.parse::<InputSpec>()?;
+    }
+
+    let path = preset_store()?;
+    let replaced = Config::load()?.presets.contains_key(name);
+    config::save_preset(&path, name, &preset)?;
+    info!(
+        "✓ プリセット「{}」を{}しました: {}",
+        name,
+        if replaced { "更新" } else { "保存" },
+        preset.summary()
+    );
+    info!("  使い方: makebeliv monitor --preset {}", name);
+
+    Ok(())
+}
+
+fn list_presets() -> Result<()> {
+    let presets = Config::load()?.presets;
+    if presets.is_empty() {
+        println!("プリセットはありません（makebeliv preset save <名前> --model ... で作成）");
+        return Ok(());
+    }
+
+    let width = presets
+        .keys()
+        .map(|name| name.chars().count())
+        .max()
+        .unwrap_or(0);
+    for (name, preset) in &presets {
+        println!("{:<width$}  {}", name, preset.summary(), width = width);
+    }
+
+    Ok(())
+}
+
+fn apply_preset(name: &str) -> Result<()> {
+    let mut presets = Config::load()?.presets;
+    let preset = presets
+        .remove(name)
+        .with_context(|| format!("プリセットが見つかりません: {}", name))?;
+
+    let path = preset_store()?;
+    config::apply_preset_defaults(&path, &preset)?;
+    info!(
+        "✓ プリセット「{}」を既定の設定にしました: {}",
+        name,
+        path.display()
+    );
+    if Config::sources().contains(&PathBuf::from(config::CONFIG_FILE_NAME)) {
+        warn!(
+            "⚠ {} の値はユーザー設定より優先されます",
+            config::CONFIG_FILE_NAME
+        );
+    }
+
+    Ok(())
+}
+
+fn delete_preset(name: &str) -> Result<()> {
+    let path = preset_store()?;
+    if config::delete_preset(&path, name)? {
+        info!("✓ プリセット「{}」を削除しました", name);
+        Ok(())
+    } else if Config::load()?.presets.contains_key(name) {
+        anyhow::bail!(
+            "プリセット「{}」は {} で定義されています（直接編集してください）",
+            name,
+            config::CONFIG_FILE_NAME
+        )
+    } else {
+        anyhow::bail!("プリセットが見つかりません: {}", name)
+    }
+}
+
+fn show_config() -> Result<()> {
+    let config = Config::load()?;
+
+    let sources = Config::sources();
+    if sources.is_empty() {
+        println!("# 設定ファイルなし（デフォルト値）");
+    } else {
+        println!("# 読み込んだ設定ファイル（後のものが優先）:");
+        for path in &sources {
+            println!("#   {}", path.display());
+        }
+    }
+    if let Some(path) = config::user_config_path() {
+        if !sources.contains(&path) {
+            println!("# ユーザー設定の場所: {}", path.display());
         }
     }
+    println!();
+    print!("{}", config.to_toml()?);
 
-    // 音声変換
-    client
-        .convert_file(&input, &output_path, &model, pitch, &noise, 0.02)
-        .await?;
+    Ok(())
+}
+
+fn upgrade_config() -> Result<()> {
+    let sources = Config::sources();
+    if sources.is_empty() {
+        println!("移行する設定ファイルがありません");
+        return Ok(());
+    }
+
+    for path in &sources {
+        let (version, backup) = config::upgrade_file(path)?;
+        match backup {
+            Some(backup) => println!(
+                "✓ {}: version {} → {}（元のファイル: {}）",
+                path.display(),
+                version,
+                config::CONFIG_VERSION,
+                backup.display()
+            ),
+            None if version > config::CONFIG_VERSION => println!(
+                "⚠ {}: このバージョンより新しい形式です（version {}）",
+                path.display(),
+                version
+            ),
+            None => println!(
+                "✓ {}: 最新の形式です（version {}）",
+                path.display(),
+                version
+            ),
+        }
+        for key in config::unknown_keys(path)? {
+            println!("  ⚠ 不明な項目（無視されます）: {}", key);
+        }
+    }
+    Ok(())
+}
+
+async fn control_monitor(action: CtlAction, socket: Option<PathBuf>) -> Result<()> {
+    let socket = socket.unwrap_or_else(control::default_path);
+    let request = match action {
+        CtlAction::Get => ControlRequest::Get,
+        CtlAction::Set { param, value } => ControlRequest::Set { param, value },
+    };
+    let response = control::send(&socket, &request).await?;
+    if let Some(error) = response.error.filter(|_| !response.ok) {
+        anyhow::bail!("{}", error);
+    }
+    if let Some(params) = response.params {
+        println!("{}", params);
+    }
+    Ok(())
+}
+
+async fn list_sessions(api_url: Option<String>) -> Result<()> {
+    let api_url = api_url.unwrap_or(Config::load()?.server.api_url);
+    let sessions = SessionManager::new(api_url.clone())?
+        .list()
+        .await
+        .with_context(|| format!("セッション一覧を取得できません: {}", api_url))?;
+
+    if sessions.is_empty() {
+        println!("セッションはありません");
+        return Ok(());
+    }
+    for session in &sessions {
+        println!(
+            "{}  {}チャンク（{:.0}秒前に開始、最後の変換は{:.0}秒前）",
+            session.session_id, session.chunks, session.age_seconds, session.idle_seconds
+        );
+    }
+    Ok(())
+}
+
+async fn reset_sessions(
+    session_id: Option<String>,
+    all: bool,
+    api_url: Option<String>,
+) -> Result<()> {
+    let api_url = api_url.unwrap_or(Config::load()?.server.api_url);
+    let sessions = SessionManager::new(api_url)?;
+
+    if all {
+        let count = sessions.reset_all().await?;
+        info!("✓ {}件のセッションをリセットしました", count);
+    } else if let Some(session_id) = session_id {
+        sessions.reset(&session_id).await?;
+    }
+    Ok(())
+}
+
+async fn auth_login(api_url: Option<String>) -> Result<()> {
+    let api_url = api_url.unwrap_or(Config::load()?.server.api_url);
+    auth::ensure_available()?;
+
+    // 履歴に残らないよう、引数ではなく入力で受け取る
+    let key = if std::io::stdin().is_terminal() {
+        rpassword::prompt_password(format!("{} のAPIキー: ", api_url))
+            .context("APIキーの入力エラー")?
+    } else {
+        let mut input = String::new();
+        std::io::stdin()
+            .read_line(&mut input)
+            .context("APIキーの入力エラー")?;
+        input
+    };
+    let key = key.trim();
+    anyhow::ensure!(!key.is_empty(), "APIキーが空です");
+
+    auth::store(&api_url, key)?;
+    info!("✓ APIキーをキーチェーンに保存しました: {}", api_url);
+
+    // 保存したキーで通じるか確かめる（サーバーが止まっていても保存は済んでいる）
+    match VoiceConversionClient::new(api_url.clone())?
+        .handshake()
+        .await
+    {
+        Ok(_) => info!("✓ サーバーに接続できました"),
+        Err(e) => warn!("⚠ サーバーに接続できません: {:#}", e),
+    }
+    Ok(())
+}
+
+fn auth_logout(api_url: Option<String>) -> Result<()> {
+    let api_url = api_url.unwrap_or(Config::load()?.server.api_url);
+    if auth::delete(&api_url)? {
+        info!("✓ APIキーを削除しました: {}", api_url);
+    } else {
+        info!("保存されたAPIキーはありません: {}", api_url);
+    }
+    if std::env::var_os(auth::API_KEY_ENV).is_some() {
+        warn!(
+            "⚠ 環境変数 {} が設定されているため、そのキーは引き続き送られます",
+            auth::API_KEY_ENV
+        );
+    }
+    Ok(())
+}
+
+async fn show_status(api_url: Option<String>) -> Result<()> {
+    let api_url = api_url.unwrap_or(Config::load()?.server.api_url);
+    let client = VoiceConversionClient::new(api_url.clone())?;
+
+    let status = client
+        .handshake()
+        .await
+        .with_context(|| format!("サーバーの状態を取得できません: {}", api_url))?;
+    let stats = &status.resources;
 
-    info!("✅ 処理完了: {}", output_path.display());
+    println!("サーバー: {}", api_url);
+    if client.has_api_key() {
+        println!("  認証: APIキーを送信");
+    }
+    if let Some(version) = &status.server_version {
+        println!("  バージョン: {}", version);
+    }
+    println!(
+        "  プロトコル: v{}（クライアント: v{}）",
+        status.protocol_version,
+        client::PROTOCOL_VERSION
+    );
+    println!("  状態: {}", status.status);
+    if let Some(capabilities) = client.capabilities() {
+        println!("  対応機能: {}", capabilities.summary());
+    }
+    println!("  デバイス: {}", status.device);
+    println!("  ロード済みモデル: {}", status.models_loaded);
+    println!("  稼働時間: {:.0}秒", status.uptime_seconds);
+
+    println!("\nリソース:");
+    match (stats.gpu_memory_used_mb, stats.gpu_memory_total_mb) {
+        (Some(used), Some(total)) => println!(
+            "  GPUメモリ: {:.0} / {:.0} MB ({:.0}%)",
+            used,
+            total,
+            stats.gpu_memory_ratio().unwrap_or(0.0) * 100.0
+        ),
+        _ => println!("  GPUメモリ: -"),
+    }
+    match stats.gpu_utilization_percent {
+        Some(util) => println!("  GPU使用率: {:.0}%", util),
+        None => println!("  GPU使用率: -"),
+    }
+    match stats.cpu_load_percent {
+        Some(cpu) => println!("  CPU負荷: {:.0}%", cpu),
+        None => println!("  CPU負荷: -"),
+    }
+    println!("  処理待ちキュー: {}", stats.queue_depth);
+
+    if stats.is_under_pressure() {
+        println!("\n⚠ GPUが逼迫しています。リアルタイム変換が遅れる可能性があります。");
+    }
 
     Ok(())
 }
 
-async fn monitor_realtime(
-    model: String,
-    noise: String,
-    pitch: i32,
-    api_url: String,
+async fn run_bench(
+    models: String,
+    chunk_ms: Option<u32>,
+    iterations: usize,
+    api_url: Option<String>,
 ) -> Result<()> {
-    info!("🎧 リアルタイム音声変換モード");
-    info!("設定:");
-    info!("  モデル: {}", model);
-    info!("  ノイズ: {}", noise);
-    info!("  ピッチ: {:+} semitones", pitch);
+    let config = Config::load()?;
+    let chunk_ms = chunk_ms.unwrap_or(config.conversion.chunk_ms);
+    let api_url = api_url.unwrap_or(config.server.api_url);
+    info!("⏱️ モデル別ベンチマーク");
+    info!("  チャンク長: {}ms", chunk_ms);
+    info!("  計測回数: {}", iterations);
     info!("  APIサーバー: {}", api_url);
 
-    // APIクライアント作成
-    let client = VoiceConversionClient::new(api_url);
+    if chunk_ms == 0 || iterations == 0 {
+        anyhow::bail!("--chunk-ms と --iterations は1以上を指定してください");
+    }
 
-    // サーバー状態確認
-    match client.check_status().await {
-        Ok(status) => {
-            info!("✓ サーバー接続成功: {:?}", status);
-        }
-        Err(e) => {
-            warn!("⚠ サーバー接続エラー: {}", e);
-            println!("\nAPIサーバーが起動していない可能性があります。");
-            println!("以下のコマンドでサーバーを起動してください:");
-            println!("  makebeliv server");
-            return Err(e);
-        }
+    // 再試行すると遅延の計測が歪むため、失敗はそのまま数える
+    let client = VoiceConversionClient::new(api_url)?.with_chunk_retry(RetryPolicy::none());
+    client.handshake().await?;
+
+    let models = bench_models(&client, &models).await?;
+
+    let config = BenchConfig {
+        chunk_ms,
+        iterations,
+    };
+    let results = bench::run(&client, &models, &config).await?;
+    bench::print_report(&results, chunk_ms);
+
+    Ok(())
+}
+
+#[cfg(feature = "devices")]
+#[allow(clippy::too_many_arguments)]
+async fn measure_latency(
+    input: Option<InputSpec>,
+    output_device: Option<String>,
+    model: Option<String>,
+    chunk_ms: Option<u32>,
+    device_buffer: Option<u32>,
+    jitter_buffer_ms: Option<u32>,
+    clicks: usize,
+    loopback: bool,
+    api_url: Option<String>,
+) -> Result<()> {
+    let config = Config::load()?;
+    let profile: Profile = config
+        .audio
+        .profile
+        .as_deref()
+        .map(str::parse)
+        .transpose()
+        .context("[audio] profile の値が不正です")?
+        .unwrap_or_default();
+    let input = match input {
+        Some(input) => input,
+        None => config
+            .audio
+            .input
+            .as_deref()
+            .map(str::parse)
+            .transpose()
+            .context("[audio] input の値が不正です")?
+            .unwrap_or(InputSpec::Default),
+    };
+    let chunk_ms = chunk_ms.unwrap_or(profile.chunk_ms(config.conversion.chunk_ms));
+    let api_url = api_url.unwrap_or(config.server.api_url);
+    anyhow::ensure!(
+        chunk_ms > 0 && clicks > 0,
+        "--chunk-ms と --clicks は1以上を指定してください"
+    );
+    info!("⏱️ 遅延の計測");
+    info!("  チャンク長: {}ms", chunk_ms);
+    info!("  APIサーバー: {}", api_url);
+
+    // 再試行すると遅延の計測が歪むため、失敗はそのまま数える
+    let client = VoiceConversionClient::new(api_url)?.with_chunk_retry(RetryPolicy::none());
+    client.handshake().await?;
+
+    let latency_config = LatencyConfig {
+        input,
+        output_device: output_device.or(config.audio.output_device),
+        model: model.unwrap_or(config.conversion.model),
+        chunk_ms,
+        device_buffer,
+        jitter_buffer_ms: jitter_buffer_ms.unwrap_or(profile.jitter_buffer_ms()),
+        clicks,
+        loopback,
+    };
+    let report = latency::run(&client, &latency_config).await?;
+    latency::print_report(&report);
+
+    Ok(())
+}
+
+async fn run_sweep(
+    models: String,
+    input: &Path,
+    config: SweepConfig,
+    api_url: Option<String>,
+) -> Result<()> {
+    let api_url = api_url.unwrap_or(Config::load()?.server.api_url);
+    anyhow::ensure!(
+        !config.chunk_sizes.contains(&0) && !config.concurrency.contains(&0),
+        "--chunk-sizes と --concurrency は1以上を指定してください"
+    );
+    let (samples, spec) = wav::read_file(input)?;
+    info!("⏱️ スループット計測: {}", input.display());
+    info!(
+        "  チャンク長: {}ms",
+        config
+            .chunk_sizes
+            .iter()
+            .map(u32::to_string)
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    info!(
+        "  同時ストリーム数: {}",
+        config
+            .concurrency
+            .iter()
+            .map(usize::to_string)
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    info!("  APIサーバー: {}", api_url);
+
+    // 再試行すると遅延の計測が歪むため、失敗はそのまま数える
+    let client = VoiceConversionClient::new(api_url)?.with_chunk_retry(RetryPolicy::none());
+    client.handshake().await?;
+
+    for model in bench_models(&client, &models).await? {
+        let results = bench::sweep(
+            &client,
+            &model,
+            &samples,
+            spec.sample_rate,
+            spec.channels,
+            &config,
+        )
+        .await?;
+        bench::print_sweep(&model, &results);
     }
 
-    println!("\n⚠️  リアルタイムモードは現在開発中です。");
-    println!("代わりに以下のコマンドでファイル処理をお試しください:");
-    println!("  makebeliv process -i audio/input/test.wav --use-api");
+    Ok(())
+}
+
+/// `--models` の計測対象（"all" ならサーバーのモデルすべて）
+async fn bench_models(client: &VoiceConversionClient, models: &str) -> Result<Vec<String>> {
+    let models: Vec<String> = if models == "all" {
+        client
+            .list_models()
+            .await?
+            .into_iter()
+            .map(|m| m.name)
+            .collect()
+    } else {
+        models
+            .split(',')
+            .map(|m| m.trim().to_string())
+            .filter(|m| !m.is_empty())
+            .collect()
+    };
+
+    if models.is_empty() {
+        anyhow::bail!("計測対象のモデルがありません");
+    }
+    Ok(models)
+}
+
+async fn self_update(check_only: bool, skip_confirm: bool) -> Result<()> {
+    info!("🔄 アップデートを確認中...");
+    info!("  現在のバージョン: {}", update::CURRENT_VERSION);
+
+    let Some(available) = update::check().await? else {
+        println!("✅ 最新バージョンです ({})", update::CURRENT_VERSION);
+        return Ok(());
+    };
+
+    println!(
+        "\n新しいバージョンがあります: {} → {}",
+        update::CURRENT_VERSION,
+        available.version
+    );
+
+    if check_only {
+        println!("更新するには: makebeliv self-update");
+        return Ok(());
+    }
+
+    if !skip_confirm {
+        println!("更新しますか？ (y/N)");
+        let mut input = String::new();
+        std::io::stdin().read_line(&mut input)?;
+        if !input.trim().eq_ignore_ascii_case("y") {
+            println!("アップデートをキャンセルしました。");
+            return Ok(());
+        }
+    }
 
-    // TODO: リアルタイム処理実装
-    // 1. マイク入力開始
-    // 2. チャンク単位で変換
-    // 3. スピーカー/仮想マイクに出力
+    update::install(&available).await?;
+    println!("\n✅ アップデートが完了しました！");
 
     Ok(())
 }
@@ -0,0 +1,464 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeSet;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+use crate::client::VoiceConversionClient;
+use crate::config::Config;
+use crate::converter::Engine;
+use crate::cue::{Cue, CueSheet, TimeRange};
+use crate::process::ProcessOptions;
+use crate::timecode::SyncOptions;
+
+/// マニフェストのファイル名に付ける拡張子
+const MANIFEST_SUFFIX: &str = ".makebeliv.json";
+
+/// マニフェストの形式のバージョン（項目の意味を変えたら上げる）
+pub const MANIFEST_VERSION: u32 = 1;
+
+/// ローカルのモデルの置き場所（Pythonを直接実行するとき）
+const MODELS_DIR: &str = "models";
+
+/// モデルのバージョンとして使うSHA-256の桁数（サーバーの /models と同じ）
+const MODEL_VERSION_DIGITS: usize = 12;
+
+/// 1つの変換の記録（`<出力>.makebeliv.json`）
+///
+/// 入力のハッシュ・すべての設定・サーバーとモデルのバージョン・かかった時間を残し、
+/// 後で同じ変換をやり直せるようにします。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Manifest {
+    pub manifest_version: u32,
+    /// 変換した makebeliv のバージョン
+    pub makebeliv_version: String,
+    pub input: FileRecord,
+    pub output: FileRecord,
+    pub parameters: Parameters,
+    /// 変換したサーバー（API経由のときだけ）
+    pub server: Option<ServerRecord>,
+    /// 使ったモデル（ローカルエンジンでは空）
+    pub models: Vec<ModelRecord>,
+    pub timings: Timings,
+}
+
+/// ファイルとそのハッシュ
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileRecord {
+    pub path: PathBuf,
+    /// SHA-256（16進）
+    pub sha256: String,
+    pub bytes: u64,
+}
+
+/// 変換の設定（`process` のオプションに対応）
+///
+/// 列挙型は `FromStr` で読み戻せる文字列で書きます。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Parameters {
+    pub engine: String,
+    pub use_api: bool,
+    pub api_url: String,
+    pub model: String,
+    pub noise: String,
+    pub noise_level: f32,
+    pub breath_level: f32,
+    pub breath_dir: Option<PathBuf>,
+    pub pitch: i32,
+    pub pitch_contour: f32,
+    pub rate_fluctuation: f32,
+    pub bit_depth: Option<String>,
+    pub resample_quality: String,
+    pub only_speaker: Option<String>,
+    pub diarization: String,
+    pub speakers: usize,
+    /// キュー（プリセットを割り当てた後の値）
+    pub cues: Option<Vec<Cue>>,
+    pub range: Option<TimeRange>,
+    pub splice: bool,
+    pub preserve_gaps: bool,
+    pub sync_markers: Option<String>,
+    pub sync_interval: Option<u32>,
+    pub timecode_fps: Option<u32>,
+    pub bwf: bool,
+    /// 揺らぎ・息・ノイズの乱数の種
+    pub seed: Option<u64>,
+    /// 電子透かしを埋め込んだか（鍵は残さない。やり直すときは設定ファイルの鍵を使う）
+    #[serde(default)]
+    pub watermark: bool,
+}
+
+impl Parameters {
+    pub fn from_options(options: &ProcessOptions) -> Self {
+        Self {
+            engine: options.engine.to_string(),
+            use_api: options.use_api,
+            api_url: options.api_url.clone(),
+            model: options.model.clone(),
+            noise: options.noise.clone(),
+            noise_level: options.noise_level,
+            breath_level: options.breath_level,
+            breath_dir: options.breath_dir.clone(),
+            pitch: options.pitch,
+            pitch_contour: options.pitch_contour,
+            rate_fluctuation: options.rate_fluctuation,
+            bit_depth: options.bit_depth.map(|depth| depth.to_string()),
+            resample_quality: options.resample_quality.to_string(),
+            only_speaker: options.only_speaker.clone(),
+            diarization: options.diarization.to_string(),
+            speakers: options.speakers,
+            cues: options.cues.as_ref().map(|cues| cues.cues().to_vec()),
+            range: options.range,
+            splice: options.splice,
+            preserve_gaps: options.preserve_gaps,
+            sync_markers: options.sync.map(|sync| sync.markers.to_string()),
+            sync_interval: options.sync.map(|sync| sync.interval_secs),
+            timecode_fps: options.sync.map(|sync| sync.fps),
+            bwf: options.bwf,
+            seed: options.seed,
+            watermark: options.watermark,
+        }
+    }
+
+    /// 記録した設定から変換の設定を作り直す（`reproduce`）
+    ///
+    /// 記録にない項目（再試行の回数など、結果を変えないもの）は設定ファイルの値を使います。
+    pub fn to_options(&self, config: &Config) -> Result<ProcessOptions> {
+        let mut options = ProcessOptions::from_config(config);
+        options.engine = self.engine.parse()?;
+        options.use_api = self.use_api;
+        options.api_url = self.api_url.clone();
+        options.model = self.model.clone();
+        options.noise = self.noise.clone();
+        options.noise_level = self.noise_level;
+        options.breath_level = self.breath_level;
+        options.breath_dir = self.breath_dir.clone();
+        options.pitch = self.pitch;
+        options.pitch_contour = self.pitch_contour;
+        options.rate_fluctuation = self.rate_fluctuation;
+        options.bit_depth = self.bit_depth.as_deref().map(str::parse).transpose()?;
+        options.resample_quality = self.resample_quality.parse()?;
+        options.only_speaker = self.only_speaker.clone();
+        options.diarization = self.diarization.parse()?;
+        options.speakers = self.speakers;
+        options.cues = match &self.cues {
+            Some(cues) => Some(CueSheet::parse_json(&serde_json::to_string(cues)?)?),
+            None => None,
+        };
+        options.range = self.range;
+        options.splice = self.splice;
+        options.preserve_gaps = self.preserve_gaps;
+        options.sync = match (&self.sync_markers, self.sync_interval, self.timecode_fps) {
+            (Some(markers), Some(interval), Some(fps)) => {
+                Some(SyncOptions::new(markers.parse()?, interval, fps)?)
+            }
+            _ => None,
+        };
+        options.bwf = self.bwf;
+        options.seed = self.seed;
+        options.watermark = self.watermark;
+        Ok(options)
+    }
+}
+
+/// 変換したサーバー
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServerRecord {
+    pub url: String,
+    /// サーバーのバージョン（申告しないサーバーはNone）
+    pub version: Option<String>,
+    pub protocol_version: u32,
+    /// 推論デバイス（cuda, cpu）
+    pub device: String,
+}
+
+/// 使ったモデルとそのバージョン
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelRecord {
+    pub name: String,
+    /// 重みファイルのSHA-256の先頭12桁（わからなければNone）
+    pub version: Option<String>,
+}
+
+/// かかった時間
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Timings {
+    /// 変換を始めた時刻（RFC 3339）
+    pub started_at: String,
+    pub finished_at: String,
+    pub elapsed_seconds: f64,
+}
+
+impl Timings {
+    pub fn new(started: DateTime<Local>, elapsed_seconds: f64) -> Self {
+        Self {
+            started_at: started.to_rfc3339(),
+            finished_at: Local::now().to_rfc3339(),
+            elapsed_seconds,
+        }
+    }
+}
+
+impl Manifest {
+    /// 変換を終えた `input` → `output` のマニフェストを作る
+    ///
+    /// サーバー・モデルのバージョンは取れる範囲で記録します（取れなくても失敗しません）。
+    pub async fn collect(
+        input: &Path,
+        output: &Path,
+        options: &ProcessOptions,
+        timings: Timings,
+    ) -> Result<Self> {
+        let (server, models) = versions(options).await;
+        Ok(Self {
+            manifest_version: MANIFEST_VERSION,
+            makebeliv_version: env!("CARGO_PKG_VERSION").to_string(),
+            input: FileRecord::of(input)?,
+            output: FileRecord::of(output)?,
+            parameters: Parameters::from_options(options),
+            server,
+            models,
+            timings,
+        })
+    }
+
+    /// マニフェストを読み込む
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("マニフェストの読み込みエラー: {}", path.display()))?;
+        let manifest: Self = serde_json::from_str(&text)
+            .with_context(|| format!("マニフェストの形式が不正です: {}", path.display()))?;
+        anyhow::ensure!(
+            manifest.manifest_version <= MANIFEST_VERSION,
+            "マニフェストの形式が新しすぎます（v{}、このバージョンはv{}まで）。makebeliv を更新してください",
+            manifest.manifest_version,
+            MANIFEST_VERSION
+        );
+        Ok(manifest)
+    }
+
+    /// `input` が記録した入力と同じか（SHA-256で比べる）
+    pub fn verify_input(&self, input: &Path) -> Result<bool> {
+        let (sha256, _) = file_sha256(input)?;
+        Ok(sha256 == self.input.sha256)
+    }
+
+    /// 記録したモデルのバージョンと `current` が違うもの（記録にないバージョンは比べない）
+    pub fn model_mismatches(&self, current: &[ModelRecord]) -> Vec<String> {
+        self.models
+            .iter()
+            .filter_map(|recorded| {
+                let version = recorded.version.as_deref()?;
+                let now = current
+                    .iter()
+                    .find(|model| model.name == recorded.name)
+                    .and_then(|model| model.version.as_deref());
+                (now != Some(version)).then(|| {
+                    format!(
+                        "{}（記録: {}、現在: {}）",
+                        recorded.name,
+                        version,
+                        now.unwrap_or("不明")
+                    )
+                })
+            })
+            .collect()
+    }
+
+    /// 出力の横に書き出す
+    pub fn write(&self, output: &Path) -> Result<PathBuf> {
+        let path = sidecar_path(output);
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(&path, json + "\n")
+            .with_context(|| format!("マニフェストの書き込みエラー: {}", path.display()))?;
+        info!("  📋 マニフェスト: {}", path.display());
+        Ok(path)
+    }
+}
+
+impl FileRecord {
+    pub fn of(path: &Path) -> Result<Self> {
+        let (sha256, bytes) = file_sha256(path)?;
+        Ok(Self {
+            // 別のディレクトリから再現できるよう、絶対パスで残す
+            path: std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf()),
+            sha256,
+            bytes,
+        })
+    }
+}
+
+/// マニフェストのパス（`out.wav` → `out.wav.makebeliv.json`）
+pub fn sidecar_path(output: &Path) -> PathBuf {
+    let mut path = output.as_os_str().to_owned();
+    path.push(MANIFEST_SUFFIX);
+    PathBuf::from(path)
+}
+
+/// やり直した変換の出力先の既定（`out.wav` → `out.reproduced.wav`）
+pub fn reproduced_path(output: &Path) -> PathBuf {
+    let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match output.extension() {
+        Some(ext) => format!("{}.reproduced.{}", stem, ext.to_string_lossy()),
+        None => format!("{}.reproduced", stem),
+    };
+    output.with_file_name(name)
+}
+
+/// ファイルのSHA-256（16進）とバイト数。大きなファイルでもメモリに載せないよう少しずつ読む
+pub fn file_sha256(path: &Path) -> Result<(String, u64)> {
+    let mut file = std::fs::File::open(path)
+        .with_context(|| format!("ファイルの読み込みエラー: {}", path.display()))?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; 1 << 16];
+    let mut bytes = 0;
+    loop {
+        let read = file
+            .read(&mut buffer)
+            .with_context(|| format!("ファイルの読み込みエラー: {}", path.display()))?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+        bytes += read as u64;
+    }
+    Ok((format!("{:x}", hasher.finalize()), bytes))
+}
+
+/// 固定したモデルのバージョン（`model_version`）と今のバージョンを比べる
+pub async fn check_model_version(options: &ProcessOptions, strict: bool) -> Result<()> {
+    let Some(pinned) = &options.model_version else {
+        return Ok(());
+    };
+    if options.engine == Engine::Local {
+        return Ok(());
+    }
+    let (_, models) = versions(options).await;
+    let current = models
+        .iter()
+        .find(|model| model.name == options.model)
+        .and_then(|model| model.version.as_deref());
+    compare_model_version(&options.model, pinned, current, strict)
+}
+
+/// 固定したバージョンと今のバージョン（Noneなら不明）を比べる
+///
+/// 食い違うときや確かめられないときは警告し、`strict` なら中止します（`--strict-versions`）。
+pub fn compare_model_version(
+    model: &str,
+    pinned: &str,
+    current: Option<&str>,
+    strict: bool,
+) -> Result<()> {
+    let problem = match current {
+        Some(current) if current == pinned => {
+            info!(
+                "✓ モデル {} のバージョン: {}（固定した値と一致）",
+                model, current
+            );
+            return Ok(());
+        }
+        Some(current) => format!(
+            "モデル {} のバージョンが固定した値と異なります: {}（固定: {}）",
+            model, current, pinned
+        ),
+        None => format!(
+            "モデル {} のバージョンを確かめられません（固定: {}）",
+            model, pinned
+        ),
+    };
+    anyhow::ensure!(!strict, "{}（--strict-versions）", problem);
+    warn!("⚠ {}。声が以前と変わる可能性があります", problem);
+    warn!("💡 固定した重みに戻すか、プリセットの model_version を更新してください（--strict-versions で中止できます）");
+    Ok(())
+}
+
+/// 変換に使うサーバーとモデルの今のバージョン（取れない項目はNone）
+pub async fn versions(options: &ProcessOptions) -> (Option<ServerRecord>, Vec<ModelRecord>) {
+    let names = model_names(options);
+    match (options.engine, options.use_api) {
+        (Engine::Local, _) => (None, Vec::new()),
+        (Engine::Server, true) => query_server(&options.api_url, names).await,
+        (Engine::Server, false) => (None, names.into_iter().map(local_model).collect()),
+    }
+}
+
+/// 変換に使ったモデルの名前（キューで切り替えたものも含む）
+fn model_names(options: &ProcessOptions) -> BTreeSet<String> {
+    let mut names = BTreeSet::from([options.model.clone()]);
+    if let Some(cues) = &options.cues {
+        names.extend(cues.cues().iter().filter_map(|cue| cue.model.clone()));
+    }
+    names
+}
+
+/// サーバーのバージョンと、サーバーが申告するモデルのバージョン
+async fn query_server(
+    api_url: &str,
+    names: BTreeSet<String>,
+) -> (Option<ServerRecord>, Vec<ModelRecord>) {
+    let unknown = |names: BTreeSet<String>| {
+        names
+            .into_iter()
+            .map(|name| ModelRecord {
+                name,
+                version: None,
+            })
+            .collect()
+    };
+    let client = match VoiceConversionClient::new(api_url.to_string()) {
+        Ok(client) => client,
+        Err(e) => {
+            warn!("⚠ サーバーのバージョンを確かめられません: {:#}", e);
+            return (None, unknown(names));
+        }
+    };
+    let status = match client.check_status().await {
+        Ok(status) => status,
+        Err(e) => {
+            warn!("⚠ サーバーのバージョンを確かめられません: {}", e);
+            return (None, unknown(names));
+        }
+    };
+    client.probe_capabilities(&status).await;
+    let server = ServerRecord {
+        url: api_url.to_string(),
+        version: status.server_version.clone(),
+        protocol_version: status.negotiated_protocol_version(),
+        device: status.device.clone(),
+    };
+    let models = match client.list_models().await {
+        Ok(available) => names
+            .into_iter()
+            .map(|name| ModelRecord {
+                version: available
+                    .iter()
+                    .find(|model| model.name == name)
+                    .and_then(|model| model.version.clone()),
+                name,
+            })
+            .collect(),
+        Err(e) => {
+            warn!("⚠ モデルのバージョンを確かめられません: {}", e);
+            unknown(names)
+        }
+    };
+    (Some(server), models)
+}
+
+/// ローカルの重みファイル（`models/<名前>/model.pth`）のバージョン
+fn local_model(name: String) -> ModelRecord {
+    let weights = Path::new(MODELS_DIR).join(&name).join("model.pth");
+    let version = weights
+        .exists()
+        .then(|| file_sha256(&weights))
+        .and_then(|hash| {
+            hash.inspect_err(|e| warn!("⚠ モデルのバージョンを求められません: {}", e))
+                .ok()
+                .map(|(sha256, _)| sha256[..MODEL_VERSION_DIGITS].to_string())
+        });
+    ModelRecord { name, version }
+}
@@ -0,0 +1,475 @@
+use anyhow::{Context, Result};
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{DefaultBodyLimit, Multipart, Query, State};
+use axum::http::{header, HeaderMap, Request, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use bytes::Bytes;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{debug, info};
+
+use crate::chaos::XorShift;
+use crate::client::{
+    self, Capability, Codec, ModelInfo, ServerStatus, SessionInfo, StreamSettings, TranscribedWord,
+};
+use crate::diarize;
+use crate::dsp::analysis::{self, DEFAULT_VAD_THRESHOLD_DB};
+
+/// モックサーバーの挙動
+#[derive(Debug, Clone, Default)]
+pub struct MockOptions {
+    /// 変換リクエストに加える遅延
+    pub latency_ms: u64,
+    /// 遅延の揺れ幅（±）
+    pub jitter_ms: u64,
+    /// 変換リクエストを500で失敗させる割合（0.0-1.0）
+    pub error_rate: f64,
+    /// 指定すると `Authorization: Bearer <キー>` のないリクエストを401で断る
+    pub api_key: Option<String>,
+    /// `/status` で申告するモデルの入力のサンプルレート（クライアントの自動変換の確認用）
+    pub sample_rate: Option<u32>,
+}
+
+/// 注入したエラーの説明
+const INJECTED_ERROR: &str = "injected error (mock-server)";
+
+struct MockState {
+    options: MockOptions,
+    start: Instant,
+    /// 遅延の揺れ・エラー注入用の乱数
+    rng: Mutex<XorShift>,
+    active_requests: AtomicU64,
+    /// チャンクを受け取ったセッション（作成時刻・最後の受信時刻・チャンク数）
+    sessions: Mutex<BTreeMap<String, (Instant, Instant, u64)>>,
+}
+
+impl MockState {
+    fn touch_session(&self, session_id: &str) {
+        let now = Instant::now();
+        let mut sessions = self.sessions.lock().unwrap_or_else(|e| e.into_inner());
+        let session = sessions
+            .entry(session_id.to_string())
+            .or_insert((now, now, 0));
+        session.1 = now;
+        session.2 += 1;
+    }
+
+    fn next_random(&self) -> f64 {
+        self.rng
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .next_f64()
+    }
+
+    /// 設定した遅延を入れ、エラーにするかを決める（trueならエラー）
+    async fn inject_fault(&self) -> bool {
+        let jitter = self.options.jitter_ms as f64 * (self.next_random() * 2.0 - 1.0);
+        let delay =
+            Duration::from_secs_f64((self.options.latency_ms as f64 + jitter).max(0.0) / 1000.0);
+        if !delay.is_zero() {
+            tokio::time::sleep(delay).await;
+        }
+
+        let fault = self.next_random() < self.options.error_rate;
+        if fault {
+            debug!("エラーを注入");
+        }
+        fault
+    }
+}
+
+/// APIを真似て、受け取った音声をそのまま返すサーバーを起動する
+///
+/// Python側なしでリアルタイム変換・リトライ・フェイルオーバーを確認するためのもので、
+/// Ctrl+Cで終了します。
+pub async fn serve(addr: SocketAddr, options: MockOptions) -> Result<()> {
+    anyhow::ensure!(
+        (0.0..=1.0).contains(&options.error_rate),
+        "--error-rate は0.0〜1.0で指定してください"
+    );
+
+    let state = Arc::new(MockState {
+        options,
+        start: Instant::now(),
+        rng: Mutex::new(XorShift::from_time()),
+        active_requests: AtomicU64::new(0),
+        sessions: Mutex::default(),
+    });
+
+    let app = Router::new()
+        .route("/", get(root))
+        .route("/status", get(status))
+        .route("/models", get(models))
+        .route("/convert", post(convert))
+        .route("/convert-chunk", post(convert))
+        .route("/transcribe", post(transcribe))
+        .route("/diarize", post(diarize))
+        .route("/sessions", get(sessions))
+        .route("/reset-session", post(reset_session))
+        .route("/ws/convert-chunk", get(convert_stream))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            require_api_key,
+        ))
+        .layer(DefaultBodyLimit::disable())
+        .with_state(state);
+
+    let server = axum::Server::try_bind(&addr)
+        .with_context(|| format!("ポートを開けません: {}", addr))?
+        .serve(app.into_make_service());
+    info!("モックサーバーを起動: http://{}", server.local_addr());
+
+    server
+        .with_graceful_shutdown(crate::shutdown::ctrl_c())
+        .await
+        .context("モックサーバーエラー")
+}
+
+/// APIキーを確かめる（`/` はキーなしでも応答する）
+async fn require_api_key<B>(
+    State(state): State<Arc<MockState>>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    if let Some(key) = &state.options.api_key {
+        let authorized = request
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value == format!("Bearer {}", key));
+        if !authorized && request.uri().path() != "/" {
+            debug!("APIキーのないリクエストを断りました: {}", request.uri());
+            return (
+                StatusCode::UNAUTHORIZED,
+                Json(serde_json::json!({ "detail": "invalid or missing API key" })),
+            )
+                .into_response();
+        }
+    }
+    next.run(request).await
+}
+
+async fn root() -> impl IntoResponse {
+    Json(serde_json::json!({
+        "name": "Makebeliv Voice Conversion API (mock)",
+        "version": env!("CARGO_PKG_VERSION"),
+        "status": "running",
+    }))
+}
+
+async fn status(State(state): State<Arc<MockState>>) -> impl IntoResponse {
+    Json(ServerStatus {
+        status: "running".to_string(),
+        device: "mock".to_string(),
+        models_loaded: 1,
+        uptime_seconds: state.start.elapsed().as_secs_f64(),
+        server_version: Some(format!("{}-mock", env!("CARGO_PKG_VERSION"))),
+        protocol_version: client::PROTOCOL_VERSION,
+        min_client_protocol_version: 1,
+        capabilities: Some(
+            [
+                Capability::Models,
+                Capability::Opus,
+                Capability::Priority,
+                Capability::Sessions,
+                Capability::Diarize,
+                Capability::Transcribe,
+                Capability::WsChunks,
+            ]
+            .into_iter()
+            .collect(),
+        ),
+        sample_rate: state.options.sample_rate,
+        resources: client::ResourceStats {
+            queue_depth: state.active_requests.load(Ordering::Relaxed) as u32,
+            ..Default::default()
+        },
+    })
+}
+
+async fn models() -> impl IntoResponse {
+    Json(serde_json::json!({
+        "models": [ModelInfo {
+            name: "default".to_string(),
+            has_weights: false,
+            loaded: true,
+            version: None,
+        }],
+    }))
+}
+
+/// /convert・/convert-chunk（音声をそのまま返す）
+async fn convert(
+    State(state): State<Arc<MockState>>,
+    headers: HeaderMap,
+    mut multipart: Multipart,
+) -> Response {
+    // 変換は並行に処理するため、優先度は記録するだけ
+    debug!(
+        "優先度: {}",
+        headers
+            .get(client::PRIORITY_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("なし")
+    );
+    // 音声は変えずに返すため、Opusのチャンクもそのまま返せる
+    let codec = headers
+        .get(client::CODEC_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse().ok())
+        .unwrap_or_default();
+    state.active_requests.fetch_add(1, Ordering::Relaxed);
+    let response = convert_inner(&state, &mut multipart, codec).await;
+    state.active_requests.fetch_sub(1, Ordering::Relaxed);
+    response.unwrap_or_else(|response| response)
+}
+
+async fn convert_inner(
+    state: &MockState,
+    multipart: &mut Multipart,
+    codec: Codec,
+) -> Result<Response, Response> {
+    let start = Instant::now();
+
+    let mut audio: Option<Bytes> = None;
+    let mut session_id: Option<String> = None;
+    while let Some(field) = multipart.next_field().await.map_err(bad_request)? {
+        match field.name() {
+            Some("audio") => audio = Some(field.bytes().await.map_err(bad_request)?),
+            Some("session_id") => session_id = Some(field.text().await.map_err(bad_request)?),
+            _ => {}
+        }
+    }
+    let audio = audio.ok_or_else(|| bad_request("audio がありません"))?;
+    if let Some(session_id) = session_id {
+        state.touch_session(&session_id);
+    }
+
+    if state.inject_fault().await {
+        return Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "detail": INJECTED_ERROR })),
+        )
+            .into_response());
+    }
+
+    let mut response = (
+        [
+            (header::CONTENT_TYPE, "audio/wav".to_string()),
+            (
+                header::HeaderName::from_static("x-processing-time-ms"),
+                start.elapsed().as_millis().to_string(),
+            ),
+        ],
+        audio,
+    )
+        .into_response();
+    if codec == Codec::Opus {
+        let headers = response.headers_mut();
+        headers.insert(
+            header::CONTENT_TYPE,
+            "audio/opus".parse().expect("固定のヘッダー値"),
+        );
+        headers.insert(
+            client::CODEC_HEADER,
+            "opus".parse().expect("固定のヘッダー値"),
+        );
+    }
+    Ok(response)
+}
+
+/// 文字起こしで語とみなす最短の発話（ミリ秒）
+const MOCK_WORD_MIN_MS: usize = 100;
+
+/// /transcribe（発話が続いている区間をそれぞれ "mock" という語として返す）
+async fn transcribe(mut multipart: Multipart) -> Response {
+    let mut audio: Option<Bytes> = None;
+    while let Ok(Some(field)) = multipart.next_field().await {
+        if field.name() == Some("audio") {
+            audio = field.bytes().await.ok();
+        }
+    }
+    let Some(audio) = audio else {
+        return bad_request("audio がありません");
+    };
+    let (samples, spec) = match crate::wav::decode(&audio) {
+        Ok(decoded) => decoded,
+        Err(e) => return bad_request(format!("{:#}", e)),
+    };
+
+    // 10msごとに音量で発話を判定する
+    let mono = analysis::downmix(&samples, spec.channels);
+    let frame = (spec.sample_rate / 100).max(1) as usize;
+    let mut words = Vec::new();
+    let mut start = None;
+    let voiced = mono
+        .chunks(frame)
+        .map(|frame| analysis::rms_db(frame) >= DEFAULT_VAD_THRESHOLD_DB)
+        .chain(std::iter::once(false));
+    for (i, voiced) in voiced.enumerate() {
+        match (voiced, start) {
+            (true, None) => start = Some(i),
+            (false, Some(first)) => {
+                if (i - first) * 10 >= MOCK_WORD_MIN_MS {
+                    words.push(TranscribedWord {
+                        word: "mock".to_string(),
+                        start: first as f64 / 100.0,
+                        end: i as f64 / 100.0,
+                    });
+                }
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    debug!("文字起こし: {}語", words.len());
+    Json(serde_json::json!({ "words": words })).into_response()
+}
+
+/// /diarize（ローカルと同じ音量とピッチによる推定で話者を分ける）
+async fn diarize(mut multipart: Multipart) -> Response {
+    let mut audio: Option<Bytes> = None;
+    let mut speakers = diarize::DEFAULT_SPEAKERS;
+    while let Ok(Some(field)) = multipart.next_field().await {
+        match field.name() {
+            Some("audio") => audio = field.bytes().await.ok(),
+            Some("num_speakers") => {
+                if let Some(n) = field.text().await.ok().and_then(|n| n.parse().ok()) {
+                    speakers = n;
+                }
+            }
+            _ => {}
+        }
+    }
+    let Some(audio) = audio else {
+        return bad_request("audio がありません");
+    };
+    let turns = crate::wav::decode(&audio).and_then(|(samples, spec)| {
+        diarize::local_turns(&samples, spec.sample_rate, spec.channels, speakers)
+    });
+    match turns {
+        Ok(turns) => {
+            debug!("話者の区別: {}区間", turns.len());
+            Json(serde_json::json!({ "segments": turns })).into_response()
+        }
+        Err(e) => bad_request(format!("{:#}", e)),
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum StreamMessage {
+    Config(StreamSettings),
+}
+
+/// /ws/convert-chunk（受け取った音声をヘッダーを付けてそのまま返す）
+async fn convert_stream(
+    State(state): State<Arc<MockState>>,
+    upgrade: WebSocketUpgrade,
+) -> Response {
+    upgrade.on_upgrade(move |socket| stream_session(state, socket))
+}
+
+async fn stream_session(state: Arc<MockState>, mut socket: WebSocket) {
+    let mut settings: Option<StreamSettings> = None;
+
+    while let Some(Ok(message)) = socket.recv().await {
+        let reply = match message {
+            Message::Text(text) => match serde_json::from_str(&text) {
+                Ok(StreamMessage::Config(config)) => {
+                    debug!("ストリーム設定: {:?}", config);
+                    settings = Some(config);
+                    continue;
+                }
+                Err(e) => stream_error(&format!("invalid message: {}", e)),
+            },
+            Message::Binary(pcm) => match &settings {
+                None => stream_error("config must be sent before audio"),
+                Some(settings) => {
+                    let start = Instant::now();
+                    state.touch_session(&settings.session_id);
+                    state.active_requests.fetch_add(1, Ordering::Relaxed);
+                    let fault = state.inject_fault().await;
+                    state.active_requests.fetch_sub(1, Ordering::Relaxed);
+
+                    if fault {
+                        stream_error(INJECTED_ERROR)
+                    } else {
+                        let mut data = Vec::with_capacity(client::STREAM_HEADER_BYTES + pcm.len());
+                        data.extend_from_slice(&settings.sample_rate.to_le_bytes());
+                        data.extend_from_slice(&settings.channels.to_le_bytes());
+                        let codec = match settings.codec {
+                            Codec::Pcm => client::STREAM_CODEC_PCM,
+                            Codec::Opus => client::STREAM_CODEC_OPUS,
+                        };
+                        data.extend_from_slice(&codec.to_le_bytes());
+                        data.extend_from_slice(
+                            &(start.elapsed().as_secs_f32() * 1000.0).to_le_bytes(),
+                        );
+                        data.extend_from_slice(&pcm);
+                        Message::Binary(data)
+                    }
+                }
+            },
+            Message::Close(_) => break,
+            _ => continue,
+        };
+
+        if socket.send(reply).await.is_err() {
+            break;
+        }
+    }
+}
+
+fn stream_error(detail: &str) -> Message {
+    Message::Text(serde_json::json!({ "type": "error", "detail": detail }).to_string())
+}
+
+#[derive(Deserialize)]
+struct ResetSession {
+    session_id: String,
+}
+
+async fn sessions(State(state): State<Arc<MockState>>) -> impl IntoResponse {
+    let sessions = state.sessions.lock().unwrap_or_else(|e| e.into_inner());
+    let sessions: Vec<_> = sessions
+        .iter()
+        .map(|(session_id, (created, last_used, chunks))| SessionInfo {
+            session_id: session_id.clone(),
+            chunks: *chunks,
+            age_seconds: created.elapsed().as_secs_f64(),
+            idle_seconds: last_used.elapsed().as_secs_f64(),
+        })
+        .collect();
+    Json(serde_json::json!({ "sessions": sessions }))
+}
+
+async fn reset_session(
+    State(state): State<Arc<MockState>>,
+    Query(query): Query<ResetSession>,
+) -> impl IntoResponse {
+    let removed = state
+        .sessions
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .remove(&query.session_id)
+        .is_some();
+    debug!("セッションリセット: {}", query.session_id);
+    let status = if removed { "reset" } else { "not_found" };
+    Json(serde_json::json!({ "status": status, "session_id": query.session_id }))
+}
+
+fn bad_request(e: impl std::fmt::Display) -> Response {
+    (
+        StatusCode::BAD_REQUEST,
+        Json(serde_json::json!({ "detail": e.to_string() })),
+    )
+        .into_response()
+}
@@ -0,0 +1,520 @@
+//! リアルタイム変換（`monitor`）の準備と後始末
+//!
+//! [`PipelineOptions`] からサーバーとの接続の確認、セッション、フック、
+//! 並行するバッチ変換までを準備し、止まったら後始末をします。
+
+use anyhow::{Context, Result};
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use crate::arbiter::Arbiter;
+use crate::batch::{self, BatchReport};
+use crate::bleep::{BleepMode, Bleeper};
+use crate::client::{self, VoiceConversionClient};
+use crate::config::Config;
+use crate::converter::{Engine, PipelineConfig};
+use crate::dsp::stereo::StereoImage;
+use crate::dsp::DspChain;
+use crate::hooks::Hooks;
+use crate::hotkeys::{Hotkeys, Keymap};
+use crate::manifest;
+use crate::notify::Notifier;
+use crate::pipeline::{PipelineOptions, Profile, RealtimePipeline};
+use crate::process::{self, ProcessOptions};
+use crate::script::ParamScript;
+use crate::session::SessionManager;
+use crate::shutdown::Interrupted;
+use crate::summary::SessionSummary;
+use crate::upload::Uploader;
+
+/// 並行するバッチ変換の計画（入力と出力の組、変換の設定、共有するクライアント）
+struct BatchPlan {
+    plan: Vec<(PathBuf, PathBuf)>,
+    options: ProcessOptions,
+    client: VoiceConversionClient,
+}
+
+/// 準備のできたリアルタイム変換
+pub struct Monitor {
+    pipeline: RealtimePipeline,
+    sessions: SessionManager,
+    uploader: Option<Uploader>,
+    batch: Option<BatchPlan>,
+    dump: bool,
+}
+
+impl Monitor {
+    /// サーバーとの接続を確かめ、パイプラインを組み立てる
+    ///
+    /// サーバーの状態と対応する機能を確かめ、固定したモデルのバージョンを比べてから
+    /// セッションを始めます。ローカルエンジンではサーバーに問い合わせません。
+    pub async fn connect(config: &Config, options: PipelineOptions) -> Result<Self> {
+        options.validate()?;
+        let dump = options.dump_pipeline.is_some();
+        // 認証情報の不足などは、録音を始める前に知らせる
+        let uploader = match (&options.recording, options.upload && !dump) {
+            (Some(_), true) => Uploader::from_config(&config.recording.upload)?,
+            _ => None,
+        };
+        if let Some(uploader) = &uploader {
+            info!(
+                "録音はセッションの終了後に {} へアップロードします",
+                uploader.describe()
+            );
+        }
+        let chunk_ms = options.chunk_ms();
+        let codec = options.codec();
+        let adaptive = options.adaptive_chunk();
+
+        info!("🎧 リアルタイム音声変換モード");
+        info!("設定:");
+        info!("  モデル: {}", options.model);
+        info!("  ノイズ: {} ({})", options.noise, options.noise_level);
+        if options.breath_level > 0.0 {
+            info!("  息: {}", options.breath_level);
+        }
+        info!("  ピッチ: {:+} semitones", options.pitch);
+        if options.pitch_contour > 0.0 {
+            info!("  ピッチの揺らぎ: ±{} cents", options.pitch_contour);
+        }
+        info!("  チャンク長: {}ms", chunk_ms);
+        if let Some(adaptive) = &adaptive {
+            info!(
+                "  チャンク長の自動調整: 追いつかなければ{}msまで延ばす",
+                adaptive.max_ms()
+            );
+        }
+        let noise_mixer = process::noise_mixer(&options.noise, options.noise_level)?
+            .map(|mixer| mixer.with_width(options.noise_width));
+        let mut breath =
+            process::breath_inserter(options.breath_level, options.breath_dir.as_deref())?;
+        let mut contour = process::pitch_contour_stage(options.pitch_contour)?;
+        let mut stereo = stereo_image(options.pan, options.stereo_width);
+        if options.profile == Profile::Pi {
+            info!(
+                "  プロファイル: {}（ジッターバッファ {}ms / リサンプル {} / {}）",
+                options.profile,
+                options.jitter_buffer_ms(),
+                options.resample_quality(),
+                codec
+            );
+        }
+        if options.low_power {
+            // 声の変換と背景ノイズ以外の処理を止める
+            let mut disabled = Vec::new();
+            if contour.take().is_some() {
+                disabled.push("ピッチの揺らぎ");
+            }
+            if breath.take().is_some() {
+                disabled.push("息");
+            }
+            if stereo.take().is_some() {
+                disabled.push("定位と広がり");
+            }
+            if !config.dsp.plugins.is_empty() || !config.dsp.lv2.is_empty() {
+                disabled.push("DSPプラグイン");
+            }
+            info!(
+                "  低電力モード: チャンク {}ms / リサンプル {} / 止めた処理: {}",
+                chunk_ms,
+                options.resample_quality(),
+                if disabled.is_empty() {
+                    "なし".to_string()
+                } else {
+                    disabled.join(", ")
+                }
+            );
+        }
+        let vad = options.voice_gate();
+        if let Some(gate) = &vad {
+            info!("  無音の判定: {}dBFS未満は送らない", gate.threshold_db());
+        }
+        let bleeper = bleeper(
+            &options.api_url,
+            options.bleep_words.clone(),
+            options.bleep_mode,
+            options.bleep_window_ms,
+        )
+        .await?;
+
+        // バッチ変換はリアルタイム変換と同じモデル・ピッチで、そのほかは process と同じ設定で行う
+        let batch = match &options.batch {
+            Some(input) => {
+                let inputs = batch::expand_inputs(input)?.unwrap_or_else(|| vec![input.clone()]);
+                let output_dir = options
+                    .batch_output_dir
+                    .clone()
+                    .unwrap_or_else(|| PathBuf::from(batch::DEFAULT_OUTPUT_DIR));
+                let plan = batch::plan_outputs(
+                    &inputs,
+                    &output_dir,
+                    batch::DEFAULT_NAME_TEMPLATE,
+                    &options.model,
+                    options.pitch,
+                )?;
+                let mut batch_options = ProcessOptions::from_config(config);
+                batch_options.use_api = true;
+                batch_options.api_url = options.api_url.clone();
+                batch_options.model = options.model.clone();
+                batch_options.pitch = options.pitch;
+                info!(
+                    "  バッチ変換: {}個のファイル → {}（リアルタイム変換を優先）",
+                    plan.len(),
+                    output_dir.display()
+                );
+                Some((plan, batch_options))
+            }
+            None => None,
+        };
+
+        // 送信するレート（指定がなければサーバーの申告、それもなければ入力のレート）
+        let mut wire_rate = options.sample_rate;
+        if let Some(rate) = wire_rate {
+            info!("  送信するサンプルレート: {}Hz", rate);
+        }
+
+        // APIクライアント作成（バッチ変換があれば複製して共有する）
+        let mut client = VoiceConversionClient::new(options.api_url.clone())?
+            .with_retry(config.server.retry.policy())
+            .with_chunk_retry(config.server.retry.chunk_policy());
+
+        if options.engine == Engine::Local {
+            info!("  エンジン: ローカルDSP（ピッチシフトのみ、サーバー不要）");
+            warn!("ローカルエンジンはピッチシフトのみです（モデルによる声の変換は行いません）");
+        } else {
+            info!(
+                "  APIサーバー: {} ({}, {})",
+                options.api_url, options.transport, codec
+            );
+            if let Some(rate) = connect_server(&mut client, &options).await? {
+                if wire_rate.is_none() {
+                    info!("  送信するサンプルレート: {}Hz（サーバーの申告）", rate);
+                    wire_rate = Some(rate);
+                }
+            }
+            if batch.is_some() {
+                if !client.supports(client::Capability::Priority) {
+                    warn!("⚠ サーバーが優先度に対応していないため、バッチ変換中はリアルタイム変換が遅れることがあります");
+                }
+                client = client.with_arbiter(Arc::new(Arbiter::new(chunk_ms)));
+            }
+        }
+
+        let mut chain = if options.low_power {
+            DspChain::new()
+        } else {
+            DspChain::from_config(&config.dsp)?
+        };
+        if let Some(contour) = contour {
+            chain.push(Box::new(contour));
+        }
+        if let Some(breath) = breath {
+            chain.push(Box::new(breath));
+        }
+        if let Some(name) = &options.output_device {
+            info!("  出力デバイス: {}", name);
+        }
+
+        let mut sessions = SessionManager::new(options.api_url.clone())?
+            .with_remote(options.engine == Engine::Server);
+        let session_id = sessions.start().await;
+        let hooks = Hooks::new(config.hooks.clone())
+            .env("MAKEBELIV_SESSION_ID", &session_id)
+            .env("MAKEBELIV_MODEL", &options.model)
+            .env("MAKEBELIV_PITCH", options.pitch)
+            .env("MAKEBELIV_API_URL", &options.api_url);
+
+        let pipeline_config = PipelineConfig {
+            model: options.model.clone(),
+            pitch_shift: options.pitch,
+            chunk_ms,
+            session_id,
+            transport: options.transport,
+            codec,
+            engine: options.engine,
+        };
+
+        let batch = batch.map(|(plan, options)| BatchPlan {
+            plan,
+            options,
+            client: client.clone(),
+        });
+        let mut pipeline = RealtimePipeline::new(client, pipeline_config, chain)
+            .with_hooks(hooks)
+            .with_notifier(Notifier::new(options.notify))
+            .with_noise(noise_mixer)
+            .with_level_interval(options.level_interval)
+            .with_bleeper(bleeper)
+            .with_stereo_image(stereo)
+            .with_vad(vad)
+            .with_chaos(options.chaos.clone())
+            .with_sidetone(options.sidetone.clone())
+            .with_echo(options.echo.clone())
+            .with_recording(options.recording.clone())
+            .with_input(options.input.clone(), options.input_format)
+            .with_channel_select(options.channel)
+            .with_output_device(options.output_device.clone())
+            .with_resample_quality(options.resample_quality())
+            .with_wire_rate(wire_rate)
+            .with_chunk_options(options.chunk_options())
+            .with_jitter_buffer(options.jitter_buffer_ms())
+            .with_low_power(options.low_power)
+            .with_bluetooth_a2dp(options.bt_a2dp)
+            .with_device_buffer(options.device_buffer)
+            .with_latency_guard(options.latency_guard())
+            .with_adaptive_chunk(adaptive)
+            .with_fallback(options.fallback);
+        if let Some(path) = &options.script {
+            info!("パラメータスクリプト: {}", path.display());
+            pipeline = pipeline.with_script(ParamScript::load(path)?);
+        }
+        if !dump {
+            pipeline = pipeline.with_control_socket(options.control_socket.clone());
+        }
+        if options.dashboard {
+            #[cfg(feature = "tui")]
+            {
+                pipeline = pipeline.with_dashboard(Some(crate::tui::Dashboard::start()?));
+            }
+            #[cfg(not(feature = "tui"))]
+            anyhow::bail!(
+                "--tui を使うには tui フィーチャーを有効にしてビルドしてください（cargo build --features tui）"
+            );
+        }
+        if let Some((format, path)) = options.dump_pipeline {
+            pipeline = pipeline.with_dump_pipeline(format, path);
+        } else if options.hotkeys {
+            let keymap = Keymap::from_config(&config.hotkeys)?;
+            if !keymap.is_empty() {
+                let presets = config
+                    .presets
+                    .iter()
+                    .map(|(name, preset)| (name.clone(), preset.clone()))
+                    .collect();
+                pipeline =
+                    pipeline.with_hotkeys(Some(Hotkeys::new(keymap, presets, options.preset)));
+            }
+        }
+
+        Ok(Self {
+            pipeline,
+            sessions,
+            uploader,
+            batch,
+            dump,
+        })
+    }
+
+    /// `shutdown` が完了するか入力が終わるまで変換し、セッションとバッチ変換を片付ける
+    pub async fn run<F>(mut self, shutdown: F) -> Result<MonitorReport>
+    where
+        F: Future<Output = ()>,
+    {
+        let batch = self.batch.map(|batch| {
+            let report = Arc::new(Mutex::new(BatchReport::default()));
+            let task = tokio::spawn(background_batch(
+                batch.client.clone(),
+                batch.plan,
+                batch.options,
+                report.clone(),
+            ));
+            (task, report, batch.client)
+        });
+        let result = self.pipeline.run(shutdown).await;
+        // エラーで止まった場合もサーバーに状態を残さない
+        self.sessions.stop_all().await;
+        if let Some((task, report, client)) = batch {
+            finish_batch(task, &report, &client);
+        }
+        let summary = result?;
+        Ok(MonitorReport {
+            // 1チャンクだけの確認なので、概要は残さない
+            summary: (!self.dump).then_some(summary),
+            uploader: self.uploader,
+        })
+    }
+}
+
+/// リアルタイム変換の結果
+pub struct MonitorReport {
+    /// セッションの概要（`--dump-pipeline` で1チャンクだけ確かめたときはNone）
+    pub summary: Option<SessionSummary>,
+    uploader: Option<Uploader>,
+}
+
+impl MonitorReport {
+    /// 録音をアップロードする（`shutdown` が先に完了すれば中断して手元に残す）
+    pub async fn upload_recordings<F>(&self, shutdown: F) -> Result<()>
+    where
+        F: Future<Output = ()>,
+    {
+        let (Some(uploader), Some(summary)) = (&self.uploader, &self.summary) else {
+            return Ok(());
+        };
+        if summary.recordings.is_empty() {
+            return Ok(());
+        }
+        tokio::select! {
+            report = uploader.upload_all(&summary.recordings) => {
+                anyhow::ensure!(
+                    report.failed.is_empty(),
+                    "{}個の録音をアップロードできませんでした（手元に残しています）",
+                    report.failed.len()
+                );
+            }
+            _ = shutdown => {
+                warn!("⚠ アップロードを中断しました（録音は手元に残しています）");
+            }
+        }
+        Ok(())
+    }
+}
+
+/// サーバーの状態と機能を確かめ、サーバーが申告したサンプルレートを返す
+async fn connect_server(
+    client: &mut VoiceConversionClient,
+    options: &PipelineOptions,
+) -> Result<Option<u32>> {
+    let status = match client.check_status().await {
+        Ok(status) => status,
+        Err(e) => {
+            warn!("⚠ サーバー接続エラー: {}", e);
+            return Err(e.context(
+                "APIサーバーが起動していない可能性があります。\
+                 makebeliv server でサーバーを起動するか、\
+                 サーバーなしでピッチだけ変えるには --engine local を指定してください",
+            ));
+        }
+    };
+    info!("✓ サーバー接続成功: {} ({})", status.status, status.device);
+    status.negotiate()?;
+    client.probe_capabilities(&status).await;
+    if let Some(pinned) = &options.model_version {
+        let current = match client.list_models().await {
+            Ok(models) => models
+                .into_iter()
+                .find(|m| m.name == options.model)
+                .and_then(|m| m.version),
+            Err(e) => {
+                warn!("⚠ モデルのバージョンを確かめられません: {}", e);
+                None
+            }
+        };
+        manifest::compare_model_version(
+            &options.model,
+            pinned,
+            current.as_deref(),
+            options.strict_versions,
+        )?;
+    }
+    if let Ok(stats) = client.resource_stats().await {
+        info!("  リソース: {}", stats.summary());
+        if stats.is_under_pressure() {
+            warn!("⚠ サーバーのGPUが逼迫しています。変換が遅れる可能性があります");
+        }
+    }
+    Ok(status.sample_rate)
+}
+
+/// リアルタイム変換と並行して、ファイルを1つずつ変換する（`monitor --batch`）
+async fn background_batch(
+    client: VoiceConversionClient,
+    plan: Vec<(PathBuf, PathBuf)>,
+    options: ProcessOptions,
+    report: Arc<Mutex<BatchReport>>,
+) {
+    let total = plan.len();
+    for (i, (input, output)) in plan.into_iter().enumerate() {
+        info!("[バッチ {}/{}] 開始: {}", i + 1, total, input.display());
+        let started = Instant::now();
+        let result = process::process_with_client(&client, &input, &output, &options).await;
+        if let Err(e) = &result {
+            warn!(
+                "[バッチ {}/{}] 失敗: {}: {:#}",
+                i + 1,
+                total,
+                input.display(),
+                e
+            );
+        }
+        report.lock().unwrap_or_else(|e| e.into_inner()).record(
+            &input,
+            &output,
+            started.elapsed(),
+            &result,
+        );
+        if result.as_ref().is_err_and(|e| e.is::<Interrupted>()) {
+            return;
+        }
+    }
+    info!("✅ バッチ変換が終わりました（リアルタイム変換は続いています）");
+}
+
+/// リアルタイム変換の停止に合わせてバッチ変換を止め、結果を表示する
+fn finish_batch(task: JoinHandle<()>, report: &Mutex<BatchReport>, client: &VoiceConversionClient) {
+    if !task.is_finished() {
+        task.abort();
+        info!("リアルタイム変換の停止に合わせて、バッチ変換を中断しました");
+    }
+    report.lock().unwrap_or_else(|e| e.into_inner()).print();
+    if let Some(arbiter) = client.arbiter() {
+        arbiter.log_summary();
+    }
+}
+
+/// 声の定位と広がり（中央・そのままなら何もしない）
+fn stereo_image(pan: f32, width: f32) -> Option<StereoImage> {
+    (pan != 0.0 || width != 1.0).then(|| StereoImage::new(pan, width))
+}
+
+/// 指定した語を伏せる準備（語がなければ伏せない）
+///
+/// 文字起こしはサーバーで行うため、ローカルエンジンでもAPIサーバーが必要です。
+async fn bleeper(
+    api_url: &str,
+    words: Vec<String>,
+    mode: BleepMode,
+    window_ms: u32,
+) -> Result<Option<Bleeper>> {
+    if words.is_empty() {
+        return Ok(None);
+    }
+    let client = VoiceConversionClient::new(api_url.to_string())?;
+    client
+        .handshake()
+        .await
+        .context("語を伏せるにはAPIサーバーでの文字起こしが必要です")?;
+    anyhow::ensure!(
+        client.supports(client::Capability::Transcribe),
+        "サーバーが文字起こしに対応していないため、語を伏せられません（{}）",
+        api_url
+    );
+    Ok(Some(
+        Bleeper::new(client, words, mode).with_window_ms(window_ms),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn centered_unchanged_image_is_skipped() {
+        assert!(stereo_image(0.0, 1.0).is_none());
+        assert!(stereo_image(-0.5, 1.0).is_some());
+        assert!(stereo_image(0.0, 1.5).is_some());
+    }
+
+    #[tokio::test]
+    async fn no_bleeper_without_words() {
+        // 語がなければサーバーに問い合わせない
+        let bleeper = bleeper("http://127.0.0.1:9", Vec::new(), BleepMode::Tone, 1000)
+            .await
+            .unwrap();
+        assert!(bleeper.is_none());
+    }
+}
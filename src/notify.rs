@@ -0,0 +1,85 @@
+use tracing::debug;
+#[cfg(feature = "notifications")]
+use tracing::warn;
+
+/// アプリ名（通知の送信元として表示される）
+#[cfg(feature = "notifications")]
+const APP_NAME: &str = "makebeliv";
+
+/// デスクトップ通知する重大イベント
+///
+/// ターミナルがOBSなどの裏に隠れているとログに気づけないため、
+/// 配信に影響する出来事だけを通知します。
+#[derive(Debug, Clone)]
+pub enum Alert {
+    /// 変換サーバーに接続できなくなった
+    ServerLost { error: String },
+    /// 変換サーバーへの接続が回復した
+    ServerRecovered,
+    /// 録音先の空き容量が少ない
+    DiskLow { free_mb: u64 },
+    /// 録音を止めた（変換は継続）
+    RecordingStopped { reason: String },
+}
+
+impl Alert {
+    fn summary(&self) -> &'static str {
+        match self {
+            Alert::ServerLost { .. } => "makebeliv: サーバー接続が切れました",
+            Alert::ServerRecovered => "makebeliv: サーバー接続が回復しました",
+            Alert::DiskLow { .. } => "makebeliv: 録音先の空き容量が少なくなっています",
+            Alert::RecordingStopped { .. } => "makebeliv: 録音を停止しました",
+        }
+    }
+
+    #[cfg(feature = "notifications")]
+    fn body(&self) -> String {
+        match self {
+            Alert::ServerLost { error } => format!("変換を一時停止しています\n{}", error),
+            Alert::ServerRecovered => "変換を再開しました".to_string(),
+            Alert::DiskLow { free_mb } => format!("残り {}MB", free_mb),
+            Alert::RecordingStopped { reason } => format!("変換は継続しています\n{}", reason),
+        }
+    }
+}
+
+/// デスクトップ通知の送信
+#[derive(Debug, Clone, Default)]
+pub struct Notifier {
+    enabled: bool,
+}
+
+impl Notifier {
+    pub fn new(enabled: bool) -> Self {
+        Self { enabled }
+    }
+
+    /// 通知を送る（送信はバックグラウンドで行い、失敗してもログに残すだけ）
+    #[cfg(feature = "notifications")]
+    pub fn send(&self, alert: Alert) {
+        if !self.enabled {
+            return;
+        }
+
+        std::thread::spawn(move || {
+            let result = notify_rust::Notification::new()
+                .appname(APP_NAME)
+                .summary(alert.summary())
+                .body(&alert.body())
+                .show();
+
+            match result {
+                Ok(_) => debug!("通知を送信: {}", alert.summary()),
+                Err(e) => warn!("デスクトップ通知の送信に失敗: {}", e),
+            }
+        });
+    }
+
+    /// notifications 機能なしのビルドでは何もしない
+    #[cfg(not(feature = "notifications"))]
+    pub fn send(&self, alert: Alert) {
+        if self.enabled {
+            debug!("通知（無効なビルド）: {}", alert.summary());
+        }
+    }
+}
@@ -0,0 +1,163 @@
+use anyhow::Result;
+
+/// Opusで圧縮したチャンクの先頭に付くヘッダーのバイト数
+///
+/// `[sample_rate: u32][channels: u16][pre_skip: u16][frames: u32]`（リトルエンディアン）の後に、
+/// `[len: u16][パケット]` が20msごとに続きます。`pre_skip` は復号した音声の先頭から
+/// 捨てるフレーム数（符号化器の先読み）、`frames` は捨てた後に残すフレーム数です。
+/// チャンクごとに符号化器を作り直すため、1つのチャンクだけで復号できます。
+pub const HEADER_BYTES: usize = 12;
+
+/// Opusが扱えないレートの音声は、このレートにリサンプリングしてから符号化する
+#[cfg(feature = "opus")]
+const FALLBACK_RATE: u32 = 48000;
+
+/// チャンクのビットレート（kbps、声の変換に足りる音質）
+#[cfg(feature = "opus")]
+const BITRATE_KBPS: i32 = 64;
+
+/// 1パケットの最大バイト数（libopusの推奨値）
+#[cfg(feature = "opus")]
+const MAX_PACKET_BYTES: usize = 4000;
+
+#[cfg(not(feature = "opus"))]
+const UNAVAILABLE: &str =
+    "Opusでチャンクを送るには opus フィーチャーを有効にしてビルドしてください（cargo build --features opus）";
+
+/// このビルドでOpusのチャンクを扱えるか
+pub fn is_available() -> bool {
+    cfg!(feature = "opus")
+}
+
+/// インターリーブ音声を1つのOpusチャンクにする（3チャンネル以上はステレオにまとめる）
+#[cfg(feature = "opus")]
+pub fn encode(samples: &[f32], sample_rate: u32, channels: u16) -> Result<Vec<u8>> {
+    use anyhow::Context;
+    use audiopus::coder::Encoder;
+    use audiopus::{Application, Bitrate};
+
+    use crate::audio::remap_channels;
+    use crate::resample::{resample, ResampleQuality};
+
+    let out_channels = channels.clamp(1, 2);
+    let samples = remap_channels(samples, channels, out_channels);
+    let (mut samples, rate) = if opus_rate(sample_rate).is_some() {
+        (samples, sample_rate)
+    } else {
+        let resampled = resample(
+            &samples,
+            sample_rate,
+            FALLBACK_RATE,
+            out_channels,
+            ResampleQuality::Fast,
+        )?;
+        (resampled, FALLBACK_RATE)
+    };
+
+    let mut encoder = Encoder::new(
+        opus_rate(rate).context("Opusのサンプルレートが不正です")?,
+        opus_channels(out_channels)?,
+        Application::Voip,
+    )
+    .context("Opus符号化器の作成エラー")?;
+    encoder
+        .set_bitrate(Bitrate::BitsPerSecond(BITRATE_KBPS * 1000))
+        .context("Opusのビットレートの設定エラー")?;
+    let pre_skip = encoder
+        .lookahead()
+        .context("Opus符号化器の先読みの取得エラー")? as usize;
+
+    // 先読みの分を無音で押し出し、20msの倍数にそろえる
+    let ch = out_channels as usize;
+    let frames = samples.len() / ch;
+    let frame_len = (rate / 50) as usize;
+    let padded = (frames + pre_skip).div_ceil(frame_len) * frame_len;
+    samples.resize(padded * ch, 0.0);
+
+    let mut data = Vec::with_capacity(HEADER_BYTES + padded / frame_len * 200);
+    data.extend_from_slice(&rate.to_le_bytes());
+    data.extend_from_slice(&out_channels.to_le_bytes());
+    data.extend_from_slice(&(pre_skip as u16).to_le_bytes());
+    data.extend_from_slice(&(frames as u32).to_le_bytes());
+
+    let mut packet = vec![0u8; MAX_PACKET_BYTES];
+    for frame in samples.chunks_exact(frame_len * ch) {
+        let len = encoder
+            .encode_float(frame, &mut packet)
+            .context("Opusの符号化エラー")?;
+        data.extend_from_slice(&(len as u16).to_le_bytes());
+        data.extend_from_slice(&packet[..len]);
+    }
+    Ok(data)
+}
+
+#[cfg(not(feature = "opus"))]
+pub fn encode(_samples: &[f32], _sample_rate: u32, _channels: u16) -> Result<Vec<u8>> {
+    anyhow::bail!(UNAVAILABLE)
+}
+
+/// Opusチャンクを復号する（サンプル, サンプルレート, チャンネル数）
+#[cfg(feature = "opus")]
+pub fn decode(data: &[u8]) -> Result<(Vec<f32>, u32, u16)> {
+    use anyhow::Context;
+    use audiopus::coder::Decoder;
+    use audiopus::packet::Packet;
+
+    anyhow::ensure!(
+        data.len() >= HEADER_BYTES,
+        "Opusチャンクの形式が不正です（{} bytes）",
+        data.len()
+    );
+    let rate = u32::from_le_bytes(data[0..4].try_into()?);
+    let channels = u16::from_le_bytes(data[4..6].try_into()?);
+    let pre_skip = u16::from_le_bytes(data[6..8].try_into()?) as usize;
+    let frames = u32::from_le_bytes(data[8..12].try_into()?) as usize;
+
+    let mut decoder = Decoder::new(
+        opus_rate(rate)
+            .with_context(|| format!("Opusチャンクのサンプルレートが不正です: {}Hz", rate))?,
+        opus_channels(channels)?,
+    )
+    .context("Opus復号器の作成エラー")?;
+
+    let ch = channels as usize;
+    // 1パケットは最長120ms
+    let mut buffer = vec![0.0f32; rate as usize * 120 / 1000 * ch];
+    let mut samples = Vec::with_capacity((pre_skip + frames) * ch);
+    let mut rest = &data[HEADER_BYTES..];
+    while !rest.is_empty() {
+        anyhow::ensure!(rest.len() >= 2, "Opusチャンクが途中で切れています");
+        let len = u16::from_le_bytes([rest[0], rest[1]]) as usize;
+        anyhow::ensure!(rest.len() >= 2 + len, "Opusチャンクが途中で切れています");
+        let packet = Packet::try_from(&rest[2..2 + len]).context("Opusのパケットが不正です")?;
+        let decoded = decoder
+            .decode_float(Some(packet), (&mut buffer[..]).try_into()?, false)
+            .context("Opusの復号エラー")?;
+        samples.extend_from_slice(&buffer[..decoded * ch]);
+        rest = &rest[2 + len..];
+    }
+
+    // 先読みの分と、そろえるために足した無音を捨てる
+    let end = ((pre_skip + frames) * ch).min(samples.len());
+    let start = (pre_skip * ch).min(end);
+    Ok((samples[start..end].to_vec(), rate, channels))
+}
+
+#[cfg(not(feature = "opus"))]
+pub fn decode(_data: &[u8]) -> Result<(Vec<f32>, u32, u16)> {
+    anyhow::bail!(UNAVAILABLE)
+}
+
+#[cfg(feature = "opus")]
+fn opus_rate(rate: u32) -> Option<audiopus::SampleRate> {
+    audiopus::SampleRate::try_from(rate as i32).ok()
+}
+
+#[cfg(feature = "opus")]
+fn opus_channels(channels: u16) -> Result<audiopus::Channels> {
+    match channels {
+        1 => Ok(audiopus::Channels::Mono),
+        2 => Ok(audiopus::Channels::Stereo),
+        _ => anyhow::bail!("Opusのチャンネル数が不正です: {}", channels),
+    }
+}
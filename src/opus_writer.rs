@@ -0,0 +1,284 @@
+use anyhow::{Context, Result};
+use audiopus::coder::Encoder;
+use audiopus::{Application, Bitrate, Channels, SampleRate};
+use ogg::writing::{PacketWriteEndInfo, PacketWriter};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread::JoinHandle;
+
+use crate::audio::remap_channels;
+use crate::resample::{ResampleQuality, Resampler};
+
+/// Opusで符号化するレート（ほかのレートは48kHzへリサンプリングしてから符号化する）
+const OPUS_RATE: u32 = 48000;
+
+/// 1パケットの長さ（48kHzで20ms）
+const FRAME_FRAMES: usize = 960;
+
+/// 1パケットの最大バイト数（libopusの推奨値）
+const MAX_PACKET_BYTES: usize = 4000;
+
+/// このパケット数ごとにページを閉じてファイルへ書き出す（約1秒。異常終了しても読める範囲を残す）
+const PACKETS_PER_PAGE: u32 = 50;
+
+/// Ogg Opusファイルへの書き込み
+///
+/// 符号化は専用のスレッドで行い、`write` は音声をスレッドへ渡すだけなので
+/// 音声処理のループを待たせません。書き込みに失敗するとスレッドが止まり、
+/// 次の `write` か `finalize` でそのエラーを返します。
+pub struct OpusFileWriter {
+    path: PathBuf,
+    sender: Option<mpsc::Sender<Vec<f32>>>,
+    thread: Option<JoinHandle<Result<()>>>,
+}
+
+impl OpusFileWriter {
+    /// ファイルと符号化器を作り、符号化スレッドを起動する
+    pub fn create(path: &Path, sample_rate: u32, channels: u16, bitrate_kbps: u32) -> Result<Self> {
+        let mut encoding = Encoding::new(path, sample_rate, channels, bitrate_kbps)?;
+        let (sender, receiver) = mpsc::channel::<Vec<f32>>();
+        let thread = std::thread::Builder::new()
+            .name("opus-encoder".into())
+            .spawn(move || {
+                for samples in receiver {
+                    encoding.push(&samples)?;
+                }
+                encoding.finish()
+            })
+            .context("Opus符号化スレッドの起動エラー")?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            sender: Some(sender),
+            thread: Some(thread),
+        })
+    }
+
+    /// インターリーブ音声を書き込む（符号化は後でスレッドが行う）
+    pub fn write(&mut self, samples: &[f32]) -> Result<()> {
+        let sent = self
+            .sender
+            .as_ref()
+            .is_some_and(|sender| sender.send(samples.to_vec()).is_ok());
+        if sent {
+            return Ok(());
+        }
+        // スレッドが止まっている
+        self.sender = None;
+        Err(self.join().err().unwrap_or_else(|| {
+            anyhow::anyhow!(
+                "Opus符号化スレッドが止まっています: {}",
+                self.path.display()
+            )
+        }))
+    }
+
+    /// 残りを符号化してファイルを閉じる
+    pub fn finalize(mut self) -> Result<()> {
+        self.sender = None;
+        self.join()
+    }
+
+    fn join(&mut self) -> Result<()> {
+        match self.thread.take() {
+            Some(thread) => thread
+                .join()
+                .map_err(|_| anyhow::anyhow!("Opus符号化スレッドが異常終了しました"))?
+                .with_context(|| format!("録音ファイルの書き込みエラー: {}", self.path.display())),
+            None => Ok(()),
+        }
+    }
+}
+
+/// 符号化スレッドが持つ状態
+struct Encoding {
+    packets: PacketWriter<BufWriter<File>>,
+    encoder: Encoder,
+    /// 入力を48kHzにする（入力が48kHzならNone）
+    resampler: Option<Resampler>,
+    in_channels: u16,
+    channels: u16,
+    /// 符号化待ちの48kHzの音声（インターリーブ）
+    pending: Vec<f32>,
+    /// 符号化器の先読み（デコーダーが先頭で捨てるフレーム数）
+    pre_skip: u64,
+    /// 受け取った音声の長さ（48kHzのフレーム数）
+    frames: u64,
+    /// 符号化したフレーム数
+    encoded: u64,
+    /// 直前のパケット（最後のパケットにストリームの終わりを付けるため1つ遅らせて書く）
+    held: Option<(Vec<u8>, u64)>,
+    packets_in_page: u32,
+    serial: u32,
+}
+
+impl Encoding {
+    fn new(path: &Path, sample_rate: u32, channels: u16, bitrate_kbps: u32) -> Result<Self> {
+        // マッピングファミリー0（モノラルかステレオ）で書く
+        let out_channels = channels.clamp(1, 2);
+        let mut encoder = Encoder::new(
+            SampleRate::Hz48000,
+            if out_channels == 1 {
+                Channels::Mono
+            } else {
+                Channels::Stereo
+            },
+            Application::Voip,
+        )
+        .context("Opus符号化器の作成エラー")?;
+        encoder
+            .set_bitrate(Bitrate::BitsPerSecond(bitrate_kbps as i32 * 1000))
+            .with_context(|| format!("Opusのビットレートが不正です: {}kbps", bitrate_kbps))?;
+        let pre_skip = encoder
+            .lookahead()
+            .context("Opus符号化器の先読みの取得エラー")? as u64;
+
+        let resampler = if sample_rate == OPUS_RATE {
+            None
+        } else {
+            Some(Resampler::new(
+                sample_rate,
+                OPUS_RATE,
+                out_channels,
+                ResampleQuality::Balanced,
+            )?)
+        };
+
+        let file = File::create(path)
+            .with_context(|| format!("録音ファイルの作成エラー: {}", path.display()))?;
+        let mut encoding = Self {
+            packets: PacketWriter::new(BufWriter::new(file)),
+            encoder,
+            resampler,
+            in_channels: channels,
+            channels: out_channels,
+            pending: Vec::new(),
+            pre_skip,
+            frames: 0,
+            encoded: 0,
+            held: None,
+            packets_in_page: 0,
+            serial: rand_serial(),
+        };
+        encoding.write_headers(sample_rate)?;
+        Ok(encoding)
+    }
+
+    /// OpusHead と OpusTags（それぞれ単独のページに置く）
+    fn write_headers(&mut self, sample_rate: u32) -> Result<()> {
+        let mut head = b"OpusHead".to_vec();
+        head.push(1); // バージョン
+        head.push(self.channels as u8);
+        head.extend_from_slice(&(self.pre_skip as u16).to_le_bytes());
+        head.extend_from_slice(&sample_rate.to_le_bytes()); // 元のレート（再生時の参考）
+        head.extend_from_slice(&0i16.to_le_bytes()); // 出力ゲイン
+        head.push(0); // マッピングファミリー
+        self.packets.write_packet(
+            head.into_boxed_slice(),
+            self.serial,
+            PacketWriteEndInfo::EndPage,
+            0,
+        )?;
+
+        let vendor = format!("makebeliv {}", env!("CARGO_PKG_VERSION"));
+        let mut tags = b"OpusTags".to_vec();
+        tags.extend_from_slice(&(vendor.len() as u32).to_le_bytes());
+        tags.extend_from_slice(vendor.as_bytes());
+        tags.extend_from_slice(&0u32.to_le_bytes()); // コメントの数
+        self.packets.write_packet(
+            tags.into_boxed_slice(),
+            self.serial,
+            PacketWriteEndInfo::EndPage,
+            0,
+        )?;
+        Ok(())
+    }
+
+    fn push(&mut self, samples: &[f32]) -> Result<()> {
+        let samples = remap_channels(samples, self.in_channels, self.channels);
+        let samples = match self.resampler.as_mut() {
+            Some(resampler) => resampler.process(&samples)?,
+            None => samples,
+        };
+        self.frames += (samples.len() / self.channels as usize) as u64;
+        self.pending.extend_from_slice(&samples);
+        self.encode_pending()
+    }
+
+    /// 溜まった音声を20msずつ符号化する
+    fn encode_pending(&mut self) -> Result<()> {
+        let frame_len = FRAME_FRAMES * self.channels as usize;
+        let mut packet = vec![0u8; MAX_PACKET_BYTES];
+        while self.pending.len() >= frame_len {
+            let frame: Vec<f32> = self.pending.drain(..frame_len).collect();
+            let len = self
+                .encoder
+                .encode_float(&frame, &mut packet)
+                .context("Opusの符号化エラー")?;
+            self.encoded += FRAME_FRAMES as u64;
+            self.emit(packet[..len].to_vec(), self.encoded)?;
+        }
+        Ok(())
+    }
+
+    /// 1つ前のパケットを書き、今のパケットを持っておく
+    fn emit(&mut self, packet: Vec<u8>, granule: u64) -> Result<()> {
+        if let Some((previous, previous_granule)) = self.held.replace((packet, granule)) {
+            self.packets_in_page += 1;
+            let end = if self.packets_in_page >= PACKETS_PER_PAGE {
+                self.packets_in_page = 0;
+                PacketWriteEndInfo::EndPage
+            } else {
+                PacketWriteEndInfo::NormalPacket
+            };
+            self.packets.write_packet(
+                previous.into_boxed_slice(),
+                self.serial,
+                end,
+                previous_granule,
+            )?;
+            if end == PacketWriteEndInfo::EndPage {
+                self.packets.inner_mut().flush()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// リサンプラーと符号化器の遅延分まで出し切り、ストリームを閉じる
+    fn finish(mut self) -> Result<()> {
+        if let Some(resampler) = self.resampler.as_mut() {
+            let tail = resampler.flush()?;
+            self.frames += (tail.len() / self.channels as usize) as u64;
+            self.pending.extend_from_slice(&tail);
+        }
+
+        // 先読みの分を無音で押し出し、最後のパケットを20msにそろえる
+        let total = self.pre_skip + self.frames;
+        let needed = total.saturating_sub(self.encoded) as usize;
+        let padded = needed.div_ceil(FRAME_FRAMES) * FRAME_FRAMES;
+        let missing = padded * self.channels as usize - self.pending.len();
+        self.pending.extend(std::iter::repeat_n(0.0, missing));
+        self.encode_pending()?;
+
+        // 最後のパケットのグラニュール位置で、埋めた無音を再生しないようにする
+        if let Some((packet, _)) = self.held.take() {
+            self.packets.write_packet(
+                packet.into_boxed_slice(),
+                self.serial,
+                PacketWriteEndInfo::EndStream,
+                total,
+            )?;
+        }
+        self.packets.inner_mut().flush()?;
+        Ok(())
+    }
+}
+
+/// Oggの論理ストリームの番号（ファイルごとに変える）
+fn rand_serial() -> u32 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(1, |d| d.subsec_nanos() ^ d.as_secs() as u32)
+}
@@ -0,0 +1,2964 @@
+use anyhow::{Context, Result};
+use std::future::Future;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::{debug, info, warn};
+
+use crate::adaptive::{self, AdaptiveChunk};
+use crate::audio::{remap_channels, AudioBuffer, ChannelSelect, ClipCounter};
+use crate::autoinput;
+use crate::backend::{self, AudioBackend, AudioStream, InputDevice, OutputDevice, WavBackend};
+use crate::bleep::{BleepMode, Bleeper};
+use crate::bluetooth;
+use crate::chaos::ChaosOptions;
+use crate::client::{Codec, ResourceStats, Transport, VoiceConversionClient};
+use crate::config::{Config, Preset};
+use crate::control::{
+    self, ControlMessage, ControlRequest, ControlResponse, ControlServer, LiveParams,
+};
+use crate::converter::{ChunkConverter, ConvertedChunk, Engine, PipelineConfig};
+use crate::dsp::analysis::{self, Level, LevelMeter};
+use crate::dsp::crossfade::ChunkCrossfade;
+use crate::dsp::noise::NoiseMixer;
+use crate::dsp::stereo::StereoImage;
+use crate::dsp::vad::{self, VoiceGate};
+use crate::dsp::{pitch, stretch, DspChain, DspStage};
+
+use crate::fifo::{self, PcmFormat};
+use crate::graph::{GraphFormat, PipelineGraph, Stage};
+use crate::hooks::{HookEvent, Hooks};
+use crate::hotkeys::{self, HotkeyAction, HotkeyListener, Hotkeys};
+use crate::hotplug::{self, DeviceDirection, DeviceEvent};
+use crate::jitter::JitterBuffer;
+use crate::notify::{Alert, Notifier};
+use crate::recorder::{RecordFormat, RecordingOptions, SessionRecorder};
+use crate::resample::{ResampleQuality, Resampler};
+use crate::script::{ChunkEvent, ParamScript};
+use crate::secondary::{SecondaryFeeder, SecondaryOptions, SecondaryOutput};
+use crate::summary::{LatencySummary, SessionSummary};
+#[cfg(feature = "tui")]
+use crate::tui::{self, Dashboard, DashboardFrame};
+use crate::virtual_audio;
+
+/// 入出力バッファに保持する最大時間（秒）
+const BUFFER_SECONDS: usize = 2;
+
+/// サイドトーンのバッファ長（ミリ秒）。遅延を抑えるため短くする
+const SIDETONE_BUFFER_MS: u32 = 100;
+
+/// デバイス設定の変化を確認する間隔
+const DEVICE_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// 低電力モードでのデバイス設定の確認とデバイス一覧のポーリングの間隔
+const LOW_POWER_DEVICE_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// 低電力モードでの最短のチャンク長（ミリ秒、`--low-power`）
+pub const LOW_POWER_CHUNK_MS: u32 = 400;
+
+/// 出力の予備再生に使う無音の長さ（ミリ秒）
+const OUTPUT_PREROLL_MS: u32 = 50;
+
+/// 出力ストリームの起動を待つ最大時間
+const OUTPUT_WARMUP_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// 入出力のレベルを表示し、クリップ・無音を確かめる既定の間隔（秒、`--level-interval`）
+pub const DEFAULT_LEVEL_INTERVAL_SECS: u64 = 10;
+
+/// 入力のピークがこれ未満なら、ほぼ無音とみなす（dBFS）
+const NEAR_SILENT_PEAK_DB: f32 = -50.0;
+
+/// 入力がほぼ無音のまま続いたら警告する長さ（話していない間と区別するため長めにとる）
+const NEAR_SILENT_WARN_AFTER: Duration = Duration::from_secs(30);
+
+/// ダッシュボードに表示する往復遅延の数（直近のチャンク）
+#[cfg(feature = "tui")]
+const DASHBOARD_LATENCIES: usize = 200;
+
+/// 停止時に、変換済みの音声をデバイスで再生し切るまで待つ上限
+const STOP_DRAIN_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// チャンク境界からゼロクロスを探す範囲（ミリ秒）
+const ZERO_CROSSING_SEARCH_MS: u32 = 10;
+
+/// 早回しで追いつくときの再生速度（チャンクの端は等速のまま残すため、実際はやや遅くなる）
+const CATCHUP_SPEED: f32 = 1.1;
+
+/// 代わりの音声を出している間に、サーバーが戻ったか問い合わせる間隔
+const FALLBACK_PROBE_INTERVAL: Duration = Duration::from_secs(2);
+
+/// サーバーのGPUメモリ・使用率・キューを問い合わせる間隔
+const RESOURCE_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// FIFO入力の既定フォーマット
+pub const DEFAULT_FIFO_FORMAT: PcmFormat = PcmFormat {
+    sample_format: fifo::SampleFormat::S16Le,
+    sample_rate: 48000,
+    channels: 1,
+};
+
+/// 入力ソースの指定（`--input`）
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum InputSpec {
+    /// デフォルトの入力デバイス
+    #[default]
+    Default,
+    /// 入力デバイスを採点して自動選択（`auto`）
+    Auto,
+    /// FIFO（名前付きパイプ）からの生PCM（`fifo:/tmp/in.pcm`）
+    Fifo(PathBuf),
+    /// WAVファイルを実時間で流す仮想入力（`wav:in.wav`）。終わりに達すると停止する
+    Wav(PathBuf),
+    /// Termux（Android）のマイク（`termux`）
+    #[cfg(feature = "termux")]
+    Termux,
+}
+
+impl FromStr for InputSpec {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        #[cfg(feature = "termux")]
+        if s == crate::termux::TERMUX_DEVICE {
+            return Ok(InputSpec::Termux);
+        }
+        if s == "default" {
+            Ok(InputSpec::Default)
+        } else if s == "auto" {
+            Ok(InputSpec::Auto)
+        } else if let Some(path) = s.strip_prefix("fifo:") {
+            Ok(InputSpec::Fifo(PathBuf::from(path)))
+        } else if let Some(path) = virtual_audio::parse_virtual(s) {
+            Ok(InputSpec::Wav(path))
+        } else {
+            anyhow::bail!(
+                "不正な入力指定: {}（default, auto, fifo:<パス> または wav:<パス>）",
+                s
+            )
+        }
+    }
+}
+
+impl InputSpec {
+    /// 入力デバイスを開く（FIFOはデバイスではないためNone）
+    pub fn open(&self) -> Result<Option<Box<dyn InputDevice>>> {
+        Ok(match self {
+            InputSpec::Default => Some(backend::CpalBackend.open_input(None)?),
+            InputSpec::Auto => {
+                Some(backend::CpalBackend.open_input(Some(&autoinput::select_input()?))?)
+            }
+            InputSpec::Wav(path) => Some(WavBackend.open_input(Some(&path.to_string_lossy()))?),
+            InputSpec::Fifo(_) => None,
+            #[cfg(feature = "termux")]
+            InputSpec::Termux => Some(crate::termux::TermuxBackend.open_input(None)?),
+        })
+    }
+}
+
+/// 出力の遅延が上限を超えたときの追いつき方（`--catchup`）
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CatchUp {
+    /// 溜まった古い音声を捨てる（すぐ戻るが言葉が欠ける）
+    #[default]
+    Drop,
+    /// 少し速く再生して徐々に戻す（WSOLAでピッチは変えない）
+    Stretch,
+}
+
+impl FromStr for CatchUp {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "drop" => Ok(Self::Drop),
+            "stretch" => Ok(Self::Stretch),
+            _ => anyhow::bail!("不明な追いつき方: {}（drop, stretch）", s),
+        }
+    }
+}
+
+impl std::fmt::Display for CatchUp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Drop => "drop",
+            Self::Stretch => "stretch",
+        })
+    }
+}
+
+/// サーバーに届かない間に出力するもの（`--fallback`）
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Fallback {
+    /// 何も出さない（変換していない地声を出さない）
+    #[default]
+    Silence,
+    /// マイクの入力をそのまま出す
+    Passthrough,
+    /// マイクの入力にローカルのピッチシフトをかけて出す
+    Pitch,
+}
+
+impl FromStr for Fallback {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "silence" => Ok(Self::Silence),
+            "passthrough" => Ok(Self::Passthrough),
+            "pitch" => {
+                pitch::ensure_available()?;
+                Ok(Self::Pitch)
+            }
+            _ => anyhow::bail!("不明なフォールバック: {}（silence, passthrough, pitch）", s),
+        }
+    }
+}
+
+impl std::fmt::Display for Fallback {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Silence => "silence",
+            Self::Passthrough => "passthrough",
+            Self::Pitch => "pitch",
+        })
+    }
+}
+
+/// 機材に合わせた既定値のまとまり（`--profile`）
+///
+/// 明示したオプションはプロファイルより優先されます。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Profile {
+    /// 通常のPC
+    #[default]
+    Default,
+    /// Raspberry Piなどの小型ARM機。長いチャンクと深いジッターバッファで処理の揺れを吸収し、
+    /// 軽いリサンプラーとOpusでCPUと回線の負荷を抑える（変換は別マシンのGPUサーバーに任せる）
+    Pi,
+}
+
+/// `--profile pi` での最短のチャンク長（ミリ秒）
+pub const PI_CHUNK_MS: u32 = 300;
+
+/// `--profile pi` での既定のジッターバッファの深さ（ミリ秒）
+pub const PI_JITTER_BUFFER_MS: u32 = 150;
+
+impl Profile {
+    /// チャンク長の既定値（設定ファイルの値をもとにする）
+    pub fn chunk_ms(self, configured: u32) -> u32 {
+        match self {
+            Self::Default => configured,
+            Self::Pi => configured.max(PI_CHUNK_MS),
+        }
+    }
+
+    /// ジッターバッファの深さの既定値（ミリ秒）
+    pub fn jitter_buffer_ms(self) -> u32 {
+        match self {
+            Self::Default => 0,
+            Self::Pi => PI_JITTER_BUFFER_MS,
+        }
+    }
+
+    /// チャンクのコーデックの既定値
+    pub fn codec(self) -> Codec {
+        match self {
+            Self::Default => Codec::Pcm,
+            Self::Pi => Codec::Opus,
+        }
+    }
+}
+
+impl FromStr for Profile {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "default" => Ok(Self::Default),
+            "pi" => Ok(Self::Pi),
+            _ => anyhow::bail!("不明なプロファイル: {}（default, pi）", s),
+        }
+    }
+}
+
+impl std::fmt::Display for Profile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Default => "default",
+            Self::Pi => "pi",
+        })
+    }
+}
+
+/// 出力に溜まる遅延の上限
+#[derive(Debug, Clone, Copy)]
+pub struct LatencyGuard {
+    /// これを超えたら追いつく（ミリ秒）。上限の半分まで戻す
+    pub max_latency_ms: u32,
+    pub catchup: CatchUp,
+}
+
+/// チャンク長（サンプル数）とゼロクロス探索範囲（フレーム数）
+fn chunk_layout(chunk_ms: u32, sample_rate: u32, channels: u16) -> (usize, usize) {
+    let chunk_len = (sample_rate as u64 * chunk_ms as u64 / 1000) as usize * channels as usize;
+    let search_frames = (sample_rate * ZERO_CROSSING_SEARCH_MS / 1000) as usize;
+    (chunk_len, search_frames)
+}
+
+fn start_input(
+    input: &dyn InputDevice,
+    buffer: &AudioBuffer,
+    sidetone: Option<&SecondaryOutput>,
+) -> Result<AudioStream> {
+    let buffer = buffer.clone();
+    let mut sidetone = sidetone.map(|s| s.feeder(input.sample_rate(), input.channels()));
+    input.start_stream(Box::new(move |data| {
+        buffer.push(data);
+        if let Some(sidetone) = sidetone.as_mut() {
+            sidetone.push(data);
+        }
+    }))
+}
+
+/// 出力コールバックと共有する、実行中に変えられる設定（ホットキー・制御ソケット）
+#[derive(Clone)]
+struct OutputControls {
+    /// 出力を無音にしているか
+    muted: Arc<AtomicBool>,
+    /// 背景ノイズの音量（f32のビット列）
+    noise_level: Arc<AtomicU32>,
+}
+
+impl OutputControls {
+    fn new(noise_level: f32) -> Self {
+        Self {
+            muted: Arc::new(AtomicBool::new(false)),
+            noise_level: Arc::new(AtomicU32::new(noise_level.to_bits())),
+        }
+    }
+
+    fn noise_level(&self) -> f32 {
+        f32::from_bits(self.noise_level.load(Ordering::Relaxed))
+    }
+
+    fn set_noise_level(&self, level: f32) {
+        self.noise_level.store(level.to_bits(), Ordering::Relaxed);
+    }
+}
+
+fn start_output(
+    output: &dyn OutputDevice,
+    buffer: &AudioBuffer,
+    jitter: &JitterBuffer,
+    clips: &ClipCounter,
+    noise: Option<&NoiseMixer>,
+    controls: &OutputControls,
+) -> Result<AudioStream> {
+    let buffer = buffer.clone();
+    let controls = controls.clone();
+    let mut jitter = jitter.reader(output.sample_rate(), output.channels());
+    let clips = clips.clone();
+    // 変換結果が途切れてもノイズは鳴り続けるよう、出力コールバックで混ぜる
+    let mut noise = noise.cloned();
+    if let Some(noise) = noise.as_mut() {
+        noise.prepare(output.sample_rate(), output.channels());
+    }
+    output.start_stream(Box::new(move |data| {
+        // 途切れた回数はバッファが数える
+        jitter.fill(&buffer, data);
+        if let Some(noise) = noise.as_mut() {
+            let level = controls.noise_level();
+            if level != noise.level() {
+                noise.set_level(level);
+            }
+            noise.process(data);
+        }
+        // ミュート中も再生は進め、遅延が溜まらないようにする
+        if controls.muted.load(Ordering::Relaxed) {
+            data.fill(0.0);
+        }
+        clips.observe(data);
+    }))
+}
+
+/// 制御ソケットの次の要求を待つ（受け付けていなければ戻らない）
+async fn next_control(server: &mut Option<ControlServer>) -> Option<ControlMessage> {
+    match server {
+        Some(server) => server.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// 次の時刻を待つ（Noneなら戻らない）
+async fn next_tick(interval: &mut Option<tokio::time::Interval>) {
+    match interval {
+        Some(interval) => {
+            interval.tick().await;
+        }
+        None => std::future::pending().await,
+    }
+}
+
+/// 裏で問い合わせているリソース使用状況を待つ（問い合わせていなければ戻らない）
+async fn next_resources(
+    probe: &mut Option<tokio::task::JoinHandle<Option<ResourceStats>>>,
+) -> Option<ResourceStats> {
+    match probe {
+        Some(handle) => {
+            let resources = handle.await.ok().flatten();
+            *probe = None;
+            resources
+        }
+        None => std::future::pending().await,
+    }
+}
+
+/// 次のホットキーの操作を待つ（受け付けていなければ戻らない）
+async fn next_hotkey(listener: &mut Option<HotkeyListener>) -> Option<HotkeyAction> {
+    match listener {
+        Some(listener) => listener.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// 新しく接続された出力デバイスを知らせ、切り替え方法を案内する
+///
+/// ALSAでは使用中のデバイスが一覧から消えることがあるため、切断は通知しない。
+fn announce_device(event: &DeviceEvent, output: &dyn OutputDevice) {
+    if let DeviceEvent::Added {
+        direction: DeviceDirection::Output,
+        name,
+    } = event
+    {
+        if output.name() != *name {
+            info!(
+                "🎧 新しい出力デバイスが接続されました: {}（切り替えるには --output-device \"{}\" で再起動）",
+                name, name
+            );
+        }
+    }
+}
+
+/// 変換結果をエコー出力へ流す（形式が変わったら送り手を作り直す）
+fn push_echo(state: &mut StreamState, converted: &ConvertedChunk) {
+    let Some(echo) = state.echo.as_ref() else {
+        return;
+    };
+    let format = (converted.sample_rate, converted.channels);
+    if state
+        .echo_feeder
+        .as_ref()
+        .is_none_or(|(rate, channels, _)| (*rate, *channels) != format)
+    {
+        state.echo_feeder = Some((format.0, format.1, echo.feeder(format.0, format.1)));
+    }
+    if let Some((_, _, feeder)) = state.echo_feeder.as_mut() {
+        feeder.push(&converted.samples);
+    }
+}
+
+/// 前回の報告以降に出力でクリップがあればログに出す
+fn report_output_clips(state: &mut StreamState) {
+    let total = state.output_clips.events();
+    if total > state.reported_output_clips {
+        warn!(
+            "⚠ 出力がクリップしました: {}回（累計{}回）",
+            total - state.reported_output_clips,
+            total
+        );
+        state.reported_output_clips = total;
+    }
+}
+
+/// 出力に溜まった遅延が上限を超えていれば、捨てるか早回しして追いつく
+fn guard_latency(guard: &LatencyGuard, state: &mut StreamState, samples: Vec<f32>) -> Vec<f32> {
+    let samples_per_ms = state.out_rate as f64 * state.out_channels as f64 / 1000.0;
+    let queued_ms = state.output_buffer.len() as f64 / samples_per_ms;
+    let max_ms = guard.max_latency_ms as f64;
+    let target_ms = max_ms / 2.0;
+
+    // 早回しでも追いつけないほど溜まったら捨てる
+    let drop = match guard.catchup {
+        CatchUp::Drop => queued_ms > max_ms,
+        CatchUp::Stretch => queued_ms > max_ms * 2.0,
+    };
+    if drop {
+        let excess = (queued_ms - target_ms) * samples_per_ms;
+        let excess = excess as usize / state.out_channels as usize * state.out_channels as usize;
+        state.output_buffer.take(excess);
+        warn!(
+            "⚠ 出力の遅延が{:.0}msに達したため、古い音声を{:.0}ms捨てました",
+            queued_ms,
+            queued_ms - target_ms
+        );
+        state.catching_up = false;
+        return samples;
+    }
+
+    if guard.catchup == CatchUp::Stretch {
+        if !state.catching_up && queued_ms > max_ms {
+            warn!(
+                "⚠ 出力の遅延が{:.0}msに達したため、早回しで追いつきます",
+                queued_ms
+            );
+            state.catching_up = true;
+        } else if state.catching_up && queued_ms <= target_ms {
+            info!("✓ 出力の遅延が{:.0}msに戻りました", queued_ms);
+            state.catching_up = false;
+        }
+    }
+
+    if state.catching_up {
+        stretch::time_stretch(&samples, state.out_channels, state.out_rate, CATCHUP_SPEED)
+    } else {
+        samples
+    }
+}
+
+/// 出力バッファへ書く（ジッターバッファを使わなければ、最初と出力の再構成後は無音を挟む）
+fn push_output(state: &mut StreamState, samples: &[f32]) {
+    if state.needs_preroll {
+        // 次のチャンクが届くまでの揺らぎを吸収する余裕を持たせる
+        state
+            .output_buffer
+            .push(&preroll_silence(state.out_rate, state.out_channels));
+        state.needs_preroll = false;
+    }
+    state.output_level = Level::measure(samples);
+    state.output_meter.observe(samples);
+    state.output_buffer.push(samples);
+}
+
+/// 文字起こしが終わって伏せた音声を出力へ流す（`finish` なら残りをすべて待つ）
+async fn release_bleeped(state: &mut StreamState, finish: bool) {
+    let Some(bleeper) = state.bleeper.as_mut() else {
+        return;
+    };
+    let released = if finish {
+        bleeper.finish().await
+    } else {
+        bleeper.poll().await
+    };
+    if !released.is_empty() {
+        push_output(state, &released);
+    }
+}
+
+fn preroll_silence(sample_rate: u32, channels: u16) -> Vec<f32> {
+    vec![0.0; (sample_rate * OUTPUT_PREROLL_MS / 1000) as usize * channels as usize]
+}
+
+/// 出力ストリームに無音を流し、コールバックが回り始めるまで待つ
+///
+/// 最初の変換チャンクが届く前にデバイスを起動しておき、再生開始時のノイズを防ぎます。
+async fn warm_up_output(buffer: &AudioBuffer, sample_rate: u32, channels: u16) {
+    buffer.push(&preroll_silence(sample_rate, channels));
+
+    let deadline = Instant::now() + OUTPUT_WARMUP_TIMEOUT;
+    while !buffer.is_empty() && Instant::now() < deadline {
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+
+    if buffer.is_empty() {
+        debug!("出力ストリームの予備再生完了");
+    } else {
+        warn!("出力ストリームの起動を確認できませんでした");
+        buffer.clear();
+    }
+}
+
+/// 入力ストリームが止まっているか設定が変わっていれば作り直す
+fn refresh_input(
+    input: &mut dyn InputDevice,
+    stream: &mut Option<AudioStream>,
+    buffer: &AudioBuffer,
+    state: &mut StreamState,
+) {
+    if stream.is_some() && !input.needs_rebuild() {
+        return;
+    }
+
+    *stream = None;
+    buffer.clear();
+    match input
+        .refresh()
+        .and_then(|_| start_input(input, buffer, state.sidetone.as_ref()))
+    {
+        Ok(new_stream) => {
+            state.in_rate = input.sample_rate();
+            state.in_channels = input.channels();
+            state.overlap.clear();
+            state.wire_resampler = None;
+            info!(
+                "入力を再構成しました: {}Hz / {}ch",
+                state.in_rate, state.in_channels
+            );
+            *stream = Some(new_stream);
+        }
+        Err(e) => warn!("入力の再構成エラー（再試行します）: {}", e),
+    }
+}
+
+/// 出力ストリームが止まっているか設定が変わっていれば作り直す
+fn refresh_output(
+    output: &mut dyn OutputDevice,
+    stream: &mut Option<AudioStream>,
+    buffer: &AudioBuffer,
+    state: &mut StreamState,
+) {
+    if stream.is_some() && !output.needs_rebuild() {
+        return;
+    }
+
+    *stream = None;
+    buffer.clear();
+    match output.refresh().and_then(|_| {
+        start_output(
+            output,
+            buffer,
+            &state.jitter,
+            &state.output_clips,
+            state.noise.as_ref(),
+            &state.controls,
+        )
+    }) {
+        Ok(new_stream) => {
+            state.out_rate = output.sample_rate();
+            state.out_channels = output.channels();
+            state.resampler = None;
+            state.crossfade.reset();
+            state.needs_preroll = state.jitter.depth_ms() == 0;
+            info!(
+                "出力を再構成しました: {}Hz / {}ch",
+                state.out_rate, state.out_channels
+            );
+            *stream = Some(new_stream);
+        }
+        Err(e) => warn!("出力の再構成エラー（再試行します）: {}", e),
+    }
+}
+
+/// チャンク境界の扱い
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ChunkOptions {
+    /// 停止時に残った端数チャンクを無音で埋めて変換する
+    pub pad_final: bool,
+    /// チャンク境界を近くのゼロクロスに合わせる（サーバー側の境界ノイズ対策）
+    pub align_zero_crossings: bool,
+    /// 前のチャンクの末尾をこの長さ（ミリ秒）だけ重ねて送り、変換結果をクロスフェードでつなぐ（0なら重ねない）
+    pub crossfade_ms: u32,
+}
+
+/// リアルタイム変換の設定（`monitor`）
+///
+/// 設定ファイルの値から作り、引数で上書きしてから [`crate::monitor::Monitor::connect`] に渡します
+/// （組み合わせは [`PipelineOptions::validate`] で確かめます）。
+/// チャンク長などプロファイルで既定値が変わるものは、指定がなければNoneのままにしておきます。
+#[derive(Debug, Clone)]
+pub struct PipelineOptions {
+    pub api_url: String,
+    pub model: String,
+    /// `model` に期待するバージョン（Noneなら確かめない）
+    pub model_version: Option<String>,
+    /// 背景ノイズの種類（cafe, street, room かWAVファイル）
+    pub noise: String,
+    /// 背景ノイズの音量（0で無効）
+    pub noise_level: f32,
+    /// 背景ノイズの広がり（0.0〜2.0）
+    pub noise_width: f32,
+    /// 句の切れ目に入れる息の音量（0で無効）
+    pub breath_level: f32,
+    pub breath_dir: Option<PathBuf>,
+    /// ピッチシフト（半音）
+    pub pitch: i32,
+    /// 句ごとのピッチの揺らぎ（±セント、0で無効）
+    pub pitch_contour: f32,
+    pub pan: f32,
+    pub stereo_width: f32,
+    pub profile: Profile,
+    pub low_power: bool,
+    /// チャンク長（ミリ秒。Noneならプロファイルと設定ファイルの値から決める）
+    pub chunk_ms: Option<u32>,
+    /// ジッターバッファの深さ（ミリ秒。Noneならプロファイルの既定値）
+    pub jitter_buffer_ms: Option<u32>,
+    /// チャンクのコーデック（Noneならプロファイルの既定値）
+    pub codec: Option<Codec>,
+    /// チャンクを重ねる長さ（ミリ秒。Noneなら設定ファイルの [conversion] overlap_ms）
+    pub crossfade_ms: Option<u32>,
+    pub pad_final: bool,
+    pub align_zero_crossings: bool,
+    /// 出力に溜まる遅延の上限（ミリ秒。Noneなら上限なし）
+    pub max_latency_ms: Option<u32>,
+    pub catchup: CatchUp,
+    /// 処理が追いつかないときにチャンク長を延ばす
+    pub adaptive_chunk: bool,
+    /// 延ばすチャンク長の上限（ミリ秒。Noneならチャンク長の数倍）
+    pub max_chunk_ms: Option<u32>,
+    pub overload_passthrough: bool,
+    pub fallback: Fallback,
+    /// 無音のチャンクを送らない
+    pub vad: bool,
+    pub vad_threshold: f32,
+    pub input: InputSpec,
+    pub channel: ChannelSelect,
+    pub output_device: Option<String>,
+    /// サーバーへ送るサンプルレート（Noneならサーバーの申告か入力のレート）
+    pub sample_rate: Option<u32>,
+    pub resample_quality: ResampleQuality,
+    /// FIFO入力の形式
+    pub input_format: PcmFormat,
+    /// コールバックあたりのフレーム数（Noneならチャンク長に合わせる）
+    pub device_buffer: Option<u32>,
+    /// Bluetoothヘッドセットを再生用プロファイル（A2DP）で使い、マイクは別のものにする
+    pub bt_a2dp: bool,
+    pub engine: Engine,
+    pub transport: Transport,
+    /// サーバーのモデルのバージョンが `model_version` と違えば止める（falseなら警告して続ける）
+    pub strict_versions: bool,
+    /// 伏せる語（空なら伏せない）
+    pub bleep_words: Vec<String>,
+    pub bleep_mode: BleepMode,
+    /// 文字起こしに送る区間の長さ（ミリ秒）
+    pub bleep_window_ms: u32,
+    /// チャンク変換に注入する障害
+    pub chaos: ChaosOptions,
+    /// パラメータスクリプト
+    pub script: Option<PathBuf>,
+    pub sidetone: Option<SecondaryOptions>,
+    pub echo: Option<SecondaryOptions>,
+    pub recording: Option<RecordingOptions>,
+    /// 録音をセッションの終了後にアップロードする（`[recording.upload]` があるとき）
+    pub upload: bool,
+    /// デスクトップ通知を出す
+    pub notify: bool,
+    /// ホットキーを受け付ける（`[hotkeys] enabled`）
+    pub hotkeys: bool,
+    /// 起動時のプリセット（ホットキーで切り替えるときの起点）
+    pub preset: Option<String>,
+    /// 制御ソケット（Noneなら開かない）
+    pub control_socket: Option<PathBuf>,
+    /// 入出力のレベルを表示する間隔（秒、0なら表示しない）
+    pub level_interval: u64,
+    /// 端末のダッシュボードを表示する（`tui` 機能）
+    pub dashboard: bool,
+    /// リアルタイム変換と並行して変換するファイルかフォルダー
+    pub batch: Option<PathBuf>,
+    /// バッチ変換の書き出し先（Noneなら [`crate::batch::DEFAULT_OUTPUT_DIR`]）
+    pub batch_output_dir: Option<PathBuf>,
+    /// 最初のチャンクの後に処理グラフを書き出して止める（書き出し先がNoneなら標準出力）
+    pub dump_pipeline: Option<(GraphFormat, Option<PathBuf>)>,
+    /// 設定ファイルのチャンク長と重ねる長さ（引数で指定しなかったときに使う）
+    configured_chunk_ms: u32,
+    configured_overlap_ms: u32,
+}
+
+impl PipelineOptions {
+    /// 設定ファイルの値から作る
+    pub fn from_config(config: &Config) -> Result<Self> {
+        let conversion = &config.conversion;
+        let audio = &config.audio;
+        Ok(Self {
+            api_url: config.server.api_url.clone(),
+            model: conversion.model.clone(),
+            model_version: conversion.model_version.clone(),
+            noise: conversion.noise.clone(),
+            noise_level: conversion.noise_level,
+            noise_width: audio.noise_width,
+            breath_level: conversion.breath_level,
+            breath_dir: conversion.breath_dir.clone(),
+            pitch: conversion.pitch,
+            pitch_contour: conversion.pitch_contour,
+            pan: audio.pan,
+            stereo_width: audio.stereo_width,
+            profile: audio
+                .profile
+                .as_deref()
+                .map(str::parse)
+                .transpose()
+                .context("[audio] profile の値が不正です")?
+                .unwrap_or_default(),
+            low_power: audio.low_power,
+            chunk_ms: None,
+            jitter_buffer_ms: None,
+            codec: None,
+            crossfade_ms: None,
+            pad_final: false,
+            align_zero_crossings: false,
+            max_latency_ms: None,
+            catchup: CatchUp::default(),
+            adaptive_chunk: false,
+            max_chunk_ms: None,
+            overload_passthrough: false,
+            fallback: Fallback::default(),
+            vad: audio.vad,
+            vad_threshold: audio.vad_threshold,
+            input: audio
+                .input
+                .as_deref()
+                .map(str::parse)
+                .transpose()
+                .context("[audio] input の値が不正です")?
+                .unwrap_or_default(),
+            channel: audio
+                .channel
+                .as_deref()
+                .map(str::parse)
+                .transpose()
+                .context("[audio] channel の値が不正です")?
+                .unwrap_or_default(),
+            output_device: audio.output_device.clone(),
+            sample_rate: None,
+            resample_quality: ResampleQuality::Fast,
+            input_format: DEFAULT_FIFO_FORMAT,
+            device_buffer: None,
+            bt_a2dp: false,
+            engine: Engine::Server,
+            transport: Transport::default(),
+            strict_versions: false,
+            bleep_words: config.bleep.words.clone(),
+            bleep_mode: config
+                .bleep
+                .mode
+                .parse()
+                .context("[bleep] mode の値が不正です")?,
+            bleep_window_ms: config.bleep.window_ms,
+            chaos: ChaosOptions::default(),
+            script: None,
+            sidetone: None,
+            echo: None,
+            recording: None,
+            upload: true,
+            notify: true,
+            hotkeys: config.hotkeys.enabled,
+            preset: None,
+            control_socket: Some(control::default_path()),
+            level_interval: DEFAULT_LEVEL_INTERVAL_SECS,
+            dashboard: false,
+            batch: None,
+            batch_output_dir: None,
+            dump_pipeline: None,
+            configured_chunk_ms: conversion.chunk_ms,
+            configured_overlap_ms: conversion.overlap_ms,
+        })
+    }
+
+    /// 組み合わせを確かめる（エラーには値をどこで指定したかを含める）
+    pub fn validate(&self) -> Result<()> {
+        let chunk_ms = self.chunk_ms();
+        anyhow::ensure!(chunk_ms > 0, "チャンク長は1ms以上を指定してください");
+        if let Some(max_latency_ms) = self.max_latency_ms {
+            anyhow::ensure!(
+                max_latency_ms >= chunk_ms,
+                "--max-latency-ms はチャンク長（{}ms）以上を指定してください",
+                chunk_ms
+            );
+            // 上限の半分まで戻したときに、ジッターバッファが空にならないようにする
+            anyhow::ensure!(
+                self.jitter_buffer_ms() <= max_latency_ms / 2,
+                "--jitter-buffer-ms は --max-latency-ms の半分（{}ms）以下を指定してください",
+                max_latency_ms / 2
+            );
+        }
+        if let (true, Some(max_chunk_ms)) = (self.adaptive_chunk, self.max_chunk_ms) {
+            anyhow::ensure!(
+                max_chunk_ms >= chunk_ms,
+                "--max-chunk-ms はチャンク長（{}ms）以上を指定してください",
+                chunk_ms
+            );
+            if let Some(max_latency_ms) = self.max_latency_ms {
+                anyhow::ensure!(
+                    max_chunk_ms <= max_latency_ms,
+                    "--max-chunk-ms は --max-latency-ms（{}ms）以下を指定してください",
+                    max_latency_ms
+                );
+            }
+        }
+        let (crossfade_ms, crossfade_source) = match self.crossfade_ms {
+            Some(ms) => (ms, "--crossfade-ms / --overlap-ms"),
+            None => (
+                self.configured_overlap_ms,
+                "設定ファイルの [conversion] overlap_ms",
+            ),
+        };
+        anyhow::ensure!(
+            crossfade_ms < chunk_ms,
+            "{}: {}ms はチャンク長（{}ms）より短く指定してください",
+            crossfade_source,
+            crossfade_ms,
+            chunk_ms
+        );
+        if let Some(rate) = self.sample_rate {
+            anyhow::ensure!(
+                (8000..=192_000).contains(&rate),
+                "--sample-rate は8000〜192000Hzで指定してください"
+            );
+        }
+        anyhow::ensure!(
+            (0.0..=2.0).contains(&self.noise_width),
+            "--noise-width は0.0〜2.0で指定してください: {}",
+            self.noise_width
+        );
+        anyhow::ensure!(
+            (-1.0..=1.0).contains(&self.pan),
+            "--pan は-1.0〜1.0で指定してください: {}",
+            self.pan
+        );
+        anyhow::ensure!(
+            (0.0..=2.0).contains(&self.stereo_width),
+            "--stereo-width は0.0〜2.0で指定してください: {}",
+            self.stereo_width
+        );
+        anyhow::ensure!(
+            (0.0..=1.0).contains(&self.chaos.error_rate),
+            "--inject-error-rate は0.0〜1.0で指定してください"
+        );
+        if !self.bleep_words.is_empty() {
+            anyhow::ensure!(
+                (200..=10_000).contains(&self.bleep_window_ms),
+                "--bleep-window は200〜10000ミリ秒で指定してください: {}",
+                self.bleep_window_ms
+            );
+        }
+        anyhow::ensure!(
+            self.batch.is_none() || self.engine == Engine::Server,
+            "--batch はサーバーで変換するときだけ使えます（--engine server）"
+        );
+        Ok(())
+    }
+
+    /// チャンク長（ミリ秒）
+    pub fn chunk_ms(&self) -> u32 {
+        match self.chunk_ms {
+            Some(chunk_ms) => chunk_ms,
+            // チャンクを長くして、送信とデバイスのコールバックの回数を減らす
+            None if self.low_power => self
+                .profile
+                .chunk_ms(self.configured_chunk_ms)
+                .max(LOW_POWER_CHUNK_MS),
+            None => self.profile.chunk_ms(self.configured_chunk_ms),
+        }
+    }
+
+    /// ジッターバッファの深さ（ミリ秒）
+    pub fn jitter_buffer_ms(&self) -> u32 {
+        self.jitter_buffer_ms
+            .unwrap_or(self.profile.jitter_buffer_ms())
+    }
+
+    pub fn codec(&self) -> Codec {
+        self.codec.unwrap_or(self.profile.codec())
+    }
+
+    /// 低電力モードでは軽いリサンプラーにする
+    pub fn resample_quality(&self) -> ResampleQuality {
+        if self.low_power {
+            ResampleQuality::Fast
+        } else {
+            self.resample_quality
+        }
+    }
+
+    pub fn chunk_options(&self) -> ChunkOptions {
+        ChunkOptions {
+            pad_final: self.pad_final,
+            align_zero_crossings: self.align_zero_crossings,
+            crossfade_ms: self.crossfade_ms.unwrap_or(self.configured_overlap_ms),
+        }
+    }
+
+    pub fn latency_guard(&self) -> Option<LatencyGuard> {
+        self.max_latency_ms.map(|max_latency_ms| LatencyGuard {
+            max_latency_ms,
+            catchup: self.catchup,
+        })
+    }
+
+    /// チャンク長の自動調整（上限を指定しなければチャンク長の数倍、遅延の上限まで）
+    pub fn adaptive_chunk(&self) -> Option<AdaptiveChunk> {
+        if !self.adaptive_chunk {
+            return None;
+        }
+        let chunk_ms = self.chunk_ms();
+        let max_chunk_ms = self.max_chunk_ms.unwrap_or_else(|| {
+            (chunk_ms * adaptive::DEFAULT_MAX_CHUNK_FACTOR)
+                .min(self.max_latency_ms.unwrap_or(u32::MAX))
+                .max(chunk_ms)
+        });
+        Some(AdaptiveChunk::new(chunk_ms, max_chunk_ms).with_passthrough(self.overload_passthrough))
+    }
+
+    pub fn voice_gate(&self) -> Option<VoiceGate> {
+        self.vad.then(|| VoiceGate::new(self.vad_threshold))
+    }
+}
+
+/// 実行中のストリームの状態
+struct StreamState {
+    in_rate: u32,
+    in_channels: u16,
+    out_rate: u32,
+    out_channels: u16,
+    output_buffer: AudioBuffer,
+    resampler: Option<(u32, u16, Resampler)>,
+    /// 入力をサーバーへ送るレートに合わせる（入力のレートと形式ごと）
+    wire_resampler: Option<(u32, u16, Resampler)>,
+    /// 次の変換結果の前に無音を挟む（最初のチャンクと出力再構成後）
+    needs_preroll: bool,
+    jitter: JitterBuffer,
+    /// 次のチャンクの先頭に重ねる入力の末尾
+    overlap: Vec<f32>,
+    crossfade: ChunkCrossfade,
+    input_clips: ClipCounter,
+    output_clips: ClipCounter,
+    /// 直近の入力チャンクと、出力へ送った音声のレベル
+    input_level: Level,
+    output_level: Level,
+    /// 前回のレベル表示からの入力と出力のレベル
+    input_meter: LevelMeter,
+    output_meter: LevelMeter,
+    /// 前回のレベル表示までの入力クリップ回数
+    reported_input_clips: u64,
+    /// 入力がほぼ無音のまま続いている長さと、それを警告したか
+    near_silent: Duration,
+    near_silent_warned: bool,
+    /// 最後にログへ出した出力クリップ回数
+    reported_output_clips: u64,
+    /// 直前のチャンク変換が成功したか（ServerLostを障害ごとに1回だけ発火する）
+    server_ok: bool,
+    /// サーバーに届かず代わりの音声を出している間の、復帰の確認（Noneなら変換している）
+    fallback: Option<FallbackProbe>,
+    chunk_index: u64,
+    chunks_converted: u64,
+    chunks_failed: u64,
+    /// 無音のため送らなかったチャンク数
+    chunks_skipped: u64,
+    /// チャンクごとの往復遅延（ミリ秒）
+    latencies_ms: Vec<f64>,
+    bytes_sent: u64,
+    bytes_received: u64,
+    recorder: Option<SessionRecorder>,
+    /// マイク直のモニター出力
+    sidetone: Option<SecondaryOutput>,
+    /// 変換結果の確認用出力と、直近の形式に合わせた送り手
+    echo: Option<SecondaryOutput>,
+    echo_feeder: Option<(u32, u16, SecondaryFeeder)>,
+    /// 出力に混ぜる背景ノイズ（出力を作り直すたびに複製して使う）
+    noise: Option<NoiseMixer>,
+    /// 早回しで遅延を取り戻している途中か
+    catching_up: bool,
+    /// 変換せずに入力をそのまま出力しているか（ホットキー）
+    bypass: bool,
+    /// ミュートと背景ノイズの音量（出力コールバックと共有する）
+    controls: OutputControls,
+    /// 指定した語を伏せるため、出力を溜めて文字起こしを待つ
+    bleeper: Option<Bleeper>,
+    /// 直近の変換結果の形式（`--dump-pipeline` で表示する）
+    server_format: Option<(u32, u16)>,
+    /// 直近に問い合わせたサーバーのリソース使用状況（まだ分からなければNone）
+    resources: Option<ResourceStats>,
+    /// 裏で問い合わせている途中のリソース使用状況
+    resource_probe: Option<tokio::task::JoinHandle<Option<ResourceStats>>>,
+}
+
+/// 代わりの音声を出し始めた時刻と、サーバーが戻ったかの問い合わせ
+///
+/// チャンクごとにサーバーの応答を待つとタイムアウトのたびに出力が途切れるため、
+/// 変換は止めたまま、裏で一定間隔に `/status` を問い合わせます。
+struct FallbackProbe {
+    since: Instant,
+    probed_at: Option<Instant>,
+    probe: Option<tokio::task::JoinHandle<bool>>,
+}
+
+/// マイク入力 → チャンク変換 → ローカルDSP → 出力 のリアルタイムパイプライン
+pub struct RealtimePipeline {
+    converter: ChunkConverter,
+    hooks: Hooks,
+    notifier: Notifier,
+    recording: Option<RecordingOptions>,
+    sidetone: Option<SecondaryOptions>,
+    echo: Option<SecondaryOptions>,
+    script: Option<ParamScript>,
+    hotkeys: Option<Hotkeys>,
+    /// 実行中のパラメータを変える制御ソケット（Noneなら受け付けない）
+    control_socket: Option<PathBuf>,
+    /// 端末のダッシュボード（`--tui`）
+    #[cfg(feature = "tui")]
+    dashboard: Option<Dashboard>,
+    /// 入出力のレベルを確かめる間隔と、そのたびにレベルを表示するか
+    level_interval: Duration,
+    print_levels: bool,
+    noise: Option<NoiseMixer>,
+    bleeper: Option<Bleeper>,
+    /// 出力チャンネルでの声の定位と広がり
+    stereo: Option<StereoImage>,
+    /// 無音のチャンクを送らないゲート（Noneなら全チャンクを送る）
+    vad: Option<VoiceGate>,
+    latency_guard: Option<LatencyGuard>,
+    /// 処理が追いつかないときにチャンク長を変える（Noneなら固定）
+    adaptive: Option<AdaptiveChunk>,
+    /// サーバーに届かない間に出力するもの
+    fallback: Fallback,
+    input: InputSpec,
+    input_format: PcmFormat,
+    /// 入力の指定と出力デバイスの代わりに使うバックエンド（テスト用）
+    backend: Option<Arc<dyn AudioBackend + Send>>,
+    /// 変換に送る入力チャンネル（既定はモノラルへの平均）
+    channel_select: ChannelSelect,
+    output_device: Option<String>,
+    resample_quality: ResampleQuality,
+    /// サーバーへ送る音声のサンプルレート（Noneなら入力のレートのまま）
+    wire_rate: Option<u32>,
+    chunk_options: ChunkOptions,
+    device_buffer: Option<u32>,
+    jitter: JitterBuffer,
+    /// 入力の確認やデバイスのポーリングを減らす（`--low-power`）
+    low_power: bool,
+    /// Bluetoothヘッドセットを再生用プロファイル（A2DP）で使い、マイクは別のものにする
+    bt_a2dp: bool,
+    /// 最初のチャンクの後に処理グラフを書き出して止める（書き出し先がNoneなら標準出力）
+    dump: Option<(GraphFormat, Option<PathBuf>)>,
+}
+
+impl RealtimePipeline {
+    pub fn new(client: VoiceConversionClient, config: PipelineConfig, chain: DspChain) -> Self {
+        Self {
+            converter: ChunkConverter::new(client, config, chain),
+            hooks: Hooks::default(),
+            notifier: Notifier::default(),
+            recording: None,
+            sidetone: None,
+            echo: None,
+            script: None,
+            hotkeys: None,
+            control_socket: None,
+            #[cfg(feature = "tui")]
+            dashboard: None,
+            level_interval: Duration::from_secs(DEFAULT_LEVEL_INTERVAL_SECS),
+            print_levels: true,
+            noise: None,
+            bleeper: None,
+            stereo: None,
+            vad: None,
+            latency_guard: None,
+            adaptive: None,
+            fallback: Fallback::Silence,
+            input: InputSpec::Default,
+            input_format: DEFAULT_FIFO_FORMAT,
+            backend: None,
+            channel_select: ChannelSelect::default(),
+            output_device: None,
+            resample_quality: ResampleQuality::Fast,
+            wire_rate: None,
+            chunk_options: ChunkOptions::default(),
+            device_buffer: None,
+            jitter: JitterBuffer::default(),
+            low_power: false,
+            bt_a2dp: false,
+            dump: None,
+        }
+    }
+
+    /// 入力ソースを設定（FIFOの場合はフォーマットも指定）
+    pub fn with_input(mut self, input: InputSpec, format: PcmFormat) -> Self {
+        self.input = input;
+        self.input_format = format;
+        self
+    }
+
+    /// 入力と出力を同じバックエンドで開く（入力の指定と出力デバイスは使わない。テスト用）
+    pub fn with_backend(mut self, backend: Arc<dyn AudioBackend + Send>) -> Self {
+        self.backend = Some(backend);
+        self
+    }
+
+    /// 無音のチャンクをサーバーへ送らず、代わりに無音（背景ノイズがなければコンフォートノイズ）を流す
+    pub fn with_vad(mut self, gate: Option<VoiceGate>) -> Self {
+        self.vad = gate;
+        self
+    }
+
+    /// 変換に送る入力チャンネルの選び方（出力ではデバイスのチャンネル数へ複製する）
+    pub fn with_channel_select(mut self, select: ChannelSelect) -> Self {
+        self.channel_select = select;
+        self
+    }
+
+    /// 出力デバイスを名前で指定（Noneならデフォルト）
+    pub fn with_output_device(mut self, name: Option<String>) -> Self {
+        self.output_device = name;
+        self
+    }
+
+    /// 出力デバイスのレートへ合わせるリサンプラーの品質
+    pub fn with_resample_quality(mut self, quality: ResampleQuality) -> Self {
+        self.resample_quality = quality;
+        self
+    }
+
+    /// サーバーへ送る音声のサンプルレート（入力をこのレートに変換して送る。Noneなら入力のまま）
+    pub fn with_wire_rate(mut self, sample_rate: Option<u32>) -> Self {
+        self.wire_rate = sample_rate;
+        self
+    }
+
+    /// チャンク境界の扱いを設定
+    pub fn with_chunk_options(mut self, options: ChunkOptions) -> Self {
+        self.chunk_options = options;
+        self
+    }
+
+    /// デバイスに要求するコールバックあたりのフレーム数（Noneならチャンク長に合わせる）
+    pub fn with_device_buffer(mut self, frames: Option<u32>) -> Self {
+        self.device_buffer = frames;
+        self
+    }
+
+    /// 入力を確認する間隔を延ばし、デバイスのポーリングを減らしてCPUの起床を抑える
+    pub fn with_low_power(mut self, enabled: bool) -> Self {
+        self.low_power = enabled;
+        self
+    }
+
+    /// Bluetoothヘッドセットの出力を再生用プロファイル（A2DP）にし、マイクはBluetooth以外を使う
+    pub fn with_bluetooth_a2dp(mut self, enabled: bool) -> Self {
+        self.bt_a2dp = enabled;
+        self
+    }
+
+    /// 出力のジッターバッファの深さ（ミリ秒、0なら溜めずに再生する）
+    pub fn with_jitter_buffer(mut self, depth_ms: u32) -> Self {
+        self.jitter = JitterBuffer::new(depth_ms);
+        self
+    }
+
+    /// チャンクごとにパラメータを調整するスクリプトを設定
+    pub fn with_script(mut self, script: ParamScript) -> Self {
+        self.script = Some(script);
+        self
+    }
+
+    /// 実行中のキー操作（バイパス・ミュート・ピッチ・プリセットの切り替え）を受け付ける
+    pub fn with_hotkeys(mut self, hotkeys: Option<Hotkeys>) -> Self {
+        self.hotkeys = hotkeys;
+        self
+    }
+
+    /// 制御ソケット（Unixソケットか名前付きパイプ）でモデル・ピッチ・ノイズの音量の変更を受け付ける
+    pub fn with_control_socket(mut self, path: Option<PathBuf>) -> Self {
+        self.control_socket = path;
+        self
+    }
+
+    /// モニターのTUIダッシュボードに状態を送る
+    #[cfg(feature = "tui")]
+    pub fn with_dashboard(mut self, dashboard: Option<Dashboard>) -> Self {
+        self.dashboard = dashboard;
+        self
+    }
+
+    /// 入出力のレベルを `seconds` 秒ごとに表示する（0なら表示せず、クリップと無音の警告だけ行う）
+    pub fn with_level_interval(mut self, seconds: u64) -> Self {
+        self.print_levels = seconds > 0;
+        if seconds > 0 {
+            self.level_interval = Duration::from_secs(seconds);
+        }
+        self
+    }
+
+    /// 出力に背景ノイズを混ぜる（サーバーの応答が途切れても鳴り続ける）
+    pub fn with_noise(mut self, noise: Option<NoiseMixer>) -> Self {
+        self.noise = noise;
+        self
+    }
+
+    /// 指定した語を伏せる（出力を文字起こしの分だけ遅らせ、見つかった区間をピー音か無音にする）
+    pub fn with_bleeper(mut self, bleeper: Option<Bleeper>) -> Self {
+        self.bleeper = bleeper;
+        self
+    }
+
+    pub fn with_stereo_image(mut self, stereo: Option<StereoImage>) -> Self {
+        self.stereo = stereo;
+        self
+    }
+
+    /// 出力に溜まる遅延に上限を設ける
+    pub fn with_latency_guard(mut self, guard: Option<LatencyGuard>) -> Self {
+        self.latency_guard = guard;
+        self
+    }
+
+    pub fn with_adaptive_chunk(mut self, adaptive: Option<AdaptiveChunk>) -> Self {
+        self.adaptive = adaptive;
+        self
+    }
+
+    pub fn with_fallback(mut self, fallback: Fallback) -> Self {
+        self.fallback = fallback;
+        self
+    }
+
+    /// ライフサイクルフックを設定
+    pub fn with_hooks(mut self, hooks: Hooks) -> Self {
+        self.hooks = hooks;
+        self
+    }
+
+    /// 重大イベントをデスクトップ通知する
+    pub fn with_notifier(mut self, notifier: Notifier) -> Self {
+        self.notifier = notifier;
+        self
+    }
+
+    /// 入力と変換結果を録音する（WAVかOpus）
+    pub fn with_recording(mut self, recording: Option<RecordingOptions>) -> Self {
+        self.recording = recording;
+        self
+    }
+
+    /// マイクの音をそのまま別の出力（ヘッドホンなど）へ流す
+    pub fn with_sidetone(mut self, sidetone: Option<SecondaryOptions>) -> Self {
+        self.sidetone = sidetone;
+        self
+    }
+
+    /// 変換結果を別の出力デバイスにも小音量で流す（エンジニアの確認用）
+    pub fn with_echo(mut self, echo: Option<SecondaryOptions>) -> Self {
+        self.echo = echo;
+        self
+    }
+
+    /// チャンク変換に遅延・失敗を注入する（耐障害性の確認用）
+    pub fn with_chaos(mut self, options: ChaosOptions) -> Self {
+        self.converter = self.converter.with_chaos(options);
+        self
+    }
+
+    /// 最初のチャンクを変換した時点の処理グラフを書き出して止める（`--dump-pipeline`）
+    ///
+    /// サーバーが返す形式は変換するまで分からないため、1チャンクだけ変換します。
+    pub fn with_dump_pipeline(mut self, format: GraphFormat, path: Option<PathBuf>) -> Self {
+        self.dump = Some((format, path));
+        self
+    }
+
+    /// `shutdown` が完了するまで（WAV入力ならファイルの終わりまで）変換を続け、セッションの概要を返す
+    pub async fn run<F>(mut self, shutdown: F) -> Result<SessionSummary>
+    where
+        F: Future<Output = ()>,
+    {
+        let custom = self.backend.clone();
+        let (mut input, (output_backend, output_name)) = match &custom {
+            Some(backend) => (
+                Some(backend.open_input(None)?),
+                (backend.as_ref() as &dyn AudioBackend, None),
+            ),
+            None => (
+                self.input.open()?,
+                backend::resolve(self.output_device.as_deref()),
+            ),
+        };
+        debug!("出力バックエンド: {}", output_backend.name());
+        let mut output = output_backend.open_output(output_name)?;
+        if self.check_bluetooth(&mut input, output.as_ref())? {
+            // プロファイルを切り替えたので、出力の形式を読み直す
+            output = output_backend.open_output(output_name)?;
+        }
+
+        let (in_rate, in_channels) = match &input {
+            Some(input) => (input.sample_rate(), input.channels()),
+            None => (self.input_format.sample_rate, self.input_format.channels),
+        };
+        let (out_rate, out_channels) = (output.sample_rate(), output.channels());
+
+        // コールバックの粒度をチャンク長に近づける
+        let chunk_ms = self.converter.config().chunk_ms;
+        let chunk_frames = |rate: u32| (rate as u64 * chunk_ms as u64 / 1000) as u32;
+        let in_buffer_frames = self.device_buffer.unwrap_or(chunk_frames(in_rate));
+        let out_buffer_frames = self.device_buffer.unwrap_or(chunk_frames(out_rate));
+        if let Some(input) = input.as_mut() {
+            input.request_buffer_frames(in_buffer_frames);
+        }
+        output.request_buffer_frames(out_buffer_frames);
+
+        info!("入力: {}Hz / {}ch", in_rate, in_channels);
+        info!("出力: {}Hz / {}ch", out_rate, out_channels);
+        if let ChannelSelect::Index(index) = self.channel_select {
+            if index >= in_channels {
+                warn!(
+                    "⚠ 入力は{}chのため、チャンネル{}の代わりに最後のチャンネルを使います",
+                    in_channels,
+                    index + 1
+                );
+            }
+        }
+        let send_channels = self.channel_select.channels(in_channels);
+        if send_channels != in_channels {
+            info!(
+                "チャンネル: {}ch → {}ch（{}）",
+                in_channels, send_channels, self.channel_select
+            );
+        }
+        let wire_rate = self.wire_rate.unwrap_or(in_rate);
+        if wire_rate != in_rate {
+            info!(
+                "リサンプリング（送信）: {}Hz → {}Hz（{}）",
+                in_rate, wire_rate, self.resample_quality
+            );
+        }
+        if wire_rate != out_rate {
+            info!(
+                "リサンプリング: {}Hz → {}Hz（{}）",
+                wire_rate, out_rate, self.resample_quality
+            );
+        }
+        let chain = self.converter.chain();
+        if !chain.is_empty() {
+            info!("ローカルDSP: {}", chain.stage_names().join(" → "));
+        }
+        if self.jitter.depth_ms() > 0 {
+            info!(
+                "ジッターバッファ: {}ms（溜まってから再生します）",
+                self.jitter.depth_ms()
+            );
+        }
+        if self.chunk_options.crossfade_ms > 0 {
+            info!(
+                "チャンクのクロスフェード: {}ms（出力はその分遅れます）",
+                self.chunk_options.crossfade_ms
+            );
+        }
+        if let Some(noise) = &self.noise {
+            info!("背景ノイズ: {}（{}）", noise.source_name(), noise.level());
+            if noise.width() != 1.0 {
+                info!("背景ノイズの広がり: {}", noise.width());
+            }
+        }
+        if let Some(stereo) = &self.stereo {
+            if out_channels < 2 {
+                warn!("⚠ 出力がモノラルのため、定位と広がりは効きません");
+            } else {
+                info!("定位: {:+.2} / 広がり: {}", stereo.pan(), stereo.width());
+            }
+        }
+        if let Some(bleeper) = &self.bleeper {
+            info!(
+                "伏せる語: {}（{}、出力は約{}ms遅れます）",
+                bleeper.keywords().join(", "),
+                bleeper.mode(),
+                bleeper.window().as_millis()
+            );
+        }
+
+        let input_buffer =
+            AudioBuffer::new(in_rate as usize * in_channels as usize * BUFFER_SECONDS);
+        let output_buffer =
+            AudioBuffer::new(out_rate as usize * out_channels as usize * BUFFER_SECONDS);
+
+        let sidetone = match (&self.sidetone, &input) {
+            (Some(options), Some(_)) => {
+                SecondaryOutput::open("サイドトーン", options, SIDETONE_BUFFER_MS)
+                    .map_err(|e| warn!("⚠ サイドトーンなしで続行します: {:#}", e))
+                    .ok()
+            }
+            (Some(_), None) => {
+                warn!("⚠ FIFO入力ではサイドトーンは使えません");
+                None
+            }
+            (None, _) => None,
+        };
+
+        let mut input_stream = match (&input, &self.input) {
+            (Some(input), _) => Some(start_input(
+                input.as_ref(),
+                &input_buffer,
+                sidetone.as_ref(),
+            )?),
+            (None, InputSpec::Fifo(path)) => {
+                fifo::spawn_reader(path, self.input_format, input_buffer.clone())?;
+                None
+            }
+            (None, _) => unreachable!("FIFO以外の入力はデバイスを開いている"),
+        };
+        let output_clips = ClipCounter::new();
+        let controls = OutputControls::new(self.noise.as_ref().map_or(0.0, NoiseMixer::level));
+        let mut output_stream = Some(start_output(
+            output.as_ref(),
+            &output_buffer,
+            &self.jitter,
+            &output_clips,
+            self.noise.as_ref(),
+            &controls,
+        )?);
+        warm_up_output(&output_buffer, out_rate, out_channels).await;
+
+        let poll_interval = self.poll_interval(chunk_ms);
+        let mut ticker = tokio::time::interval(poll_interval);
+        let (check_interval, hotplug_interval) = if self.low_power {
+            (
+                LOW_POWER_DEVICE_CHECK_INTERVAL,
+                LOW_POWER_DEVICE_CHECK_INTERVAL,
+            )
+        } else {
+            (DEVICE_CHECK_INTERVAL, hotplug::DEFAULT_POLL_INTERVAL)
+        };
+        let mut device_check = tokio::time::interval(check_interval);
+        let mut device_events = hotplug::watch(hotplug_interval);
+        let mut hotkey_listener = self.listen_hotkeys();
+        let mut control_server = self.control_socket.as_deref().and_then(|path| {
+            control::serve(path)
+                .map_err(|e| warn!("⚠ 制御ソケットなしで続行します: {:#}", e))
+                .ok()
+        });
+
+        #[cfg(feature = "tui")]
+        let mut redraw = self
+            .dashboard
+            .as_ref()
+            .map(|_| tokio::time::interval(tui::REFRESH_INTERVAL));
+        #[cfg(not(feature = "tui"))]
+        let mut redraw: Option<tokio::time::Interval> = None;
+        let mut level_check = tokio::time::interval(self.level_interval);
+        // 最初の tick はすぐに来るので読み捨てる
+        level_check.tick().await;
+        // ローカルエンジンではサーバーを使わない
+        let mut resource_check = (self.converter.config().engine == Engine::Server)
+            .then(|| tokio::time::interval(RESOURCE_POLL_INTERVAL));
+
+        tokio::pin!(shutdown);
+
+        let started = Instant::now();
+        let started_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        let mut state = StreamState {
+            in_rate,
+            in_channels,
+            out_rate,
+            out_channels,
+            output_buffer: output_buffer.clone(),
+            resampler: None,
+            wire_resampler: None,
+            needs_preroll: self.jitter.depth_ms() == 0,
+            jitter: self.jitter.clone(),
+            overlap: Vec::new(),
+            crossfade: ChunkCrossfade::new(),
+            input_clips: ClipCounter::new(),
+            output_clips,
+            input_level: Level::default(),
+            output_level: Level::default(),
+            input_meter: LevelMeter::default(),
+            output_meter: LevelMeter::default(),
+            reported_input_clips: 0,
+            near_silent: Duration::ZERO,
+            near_silent_warned: false,
+            reported_output_clips: 0,
+            server_ok: true,
+            fallback: None,
+            chunk_index: 0,
+            chunks_converted: 0,
+            chunks_failed: 0,
+            chunks_skipped: 0,
+            latencies_ms: Vec::new(),
+            bytes_sent: 0,
+            bytes_received: 0,
+            recorder: None,
+            sidetone,
+            echo: self.echo.as_ref().and_then(|options| {
+                SecondaryOutput::open("エコー", options, (BUFFER_SECONDS * 1000) as u32)
+                    .map_err(|e| warn!("⚠ エコー出力なしで続行します: {:#}", e))
+                    .ok()
+            }),
+            echo_feeder: None,
+            noise: self.noise.take(),
+            catching_up: false,
+            bypass: false,
+            controls,
+            bleeper: self.bleeper.take(),
+            server_format: None,
+            resources: None,
+            resource_probe: None,
+        };
+        if let Some(options) = self.recording.clone() {
+            // 録音できなくても変換は始める
+            match SessionRecorder::start(options, self.notifier.clone()) {
+                Ok(recorder) => state.recorder = Some(recorder),
+                Err(e) => warn!("⚠ 録音なしで続行します: {:#}", e),
+            }
+        }
+        self.hooks.fire(HookEvent::SessionStart, &[]);
+
+        loop {
+            tokio::select! {
+                _ = &mut shutdown => break,
+                _ = device_check.tick() => {
+                    if let Some(recorder) = state.recorder.as_mut() {
+                        recorder.check_disk_space();
+                    }
+                    // OSによるレート変更（Windowsで起きる）やデバイスエラーに追従する
+                    if let Some(input) = input.as_mut() {
+                        refresh_input(input.as_mut(), &mut input_stream, &input_buffer, &mut state);
+                        if input_stream.is_none() {
+                            continue;
+                        }
+                    }
+                    refresh_output(output.as_mut(), &mut output_stream, &output_buffer, &mut state);
+                    report_output_clips(&mut state);
+                }
+                Some(event) = device_events.recv() => {
+                    announce_device(&event, output.as_ref());
+                    continue;
+                }
+                Some(action) = next_hotkey(&mut hotkey_listener) => {
+                    self.apply_hotkey(action, &mut state);
+                    continue;
+                }
+                Some((request, reply)) = next_control(&mut control_server) => {
+                    let _ = reply.send(self.apply_control(request, &mut state));
+                    continue;
+                }
+                _ = level_check.tick() => {
+                    self.check_levels(&mut state);
+                    continue;
+                }
+                _ = next_tick(&mut resource_check) => {
+                    self.poll_resources(&mut state);
+                    continue;
+                }
+                Some(resources) = next_resources(&mut state.resource_probe) => {
+                    self.update_resources(&mut state, resources);
+                    continue;
+                }
+                _ = next_tick(&mut redraw) => {
+                    self.draw_dashboard(&state, &input_buffer, started);
+                    continue;
+                }
+                _ = ticker.tick() => {}
+            }
+
+            let chunk_ms = self.current_chunk_ms(chunk_ms);
+            let (chunk_len, search_frames) =
+                chunk_layout(chunk_ms, state.in_rate, state.in_channels);
+
+            // ゼロクロス探索のため、境界の先まで溜まってから切り出す
+            let needed = if self.chunk_options.align_zero_crossings {
+                chunk_len + search_frames * state.in_channels as usize
+            } else {
+                chunk_len
+            };
+
+            while input_buffer.len() >= needed {
+                let len = if self.chunk_options.align_zero_crossings {
+                    let window = input_buffer.peek(needed);
+                    let frames = chunk_len / state.in_channels as usize;
+                    analysis::nearest_zero_crossing(
+                        &window,
+                        state.in_channels,
+                        frames,
+                        search_frames,
+                    )
+                    .map_or(chunk_len, |frame| frame * state.in_channels as usize)
+                } else {
+                    chunk_len
+                };
+
+                let chunk = input_buffer.take(len);
+                self.handle_chunk(&chunk, &mut state).await?;
+            }
+            release_bleeped(&mut state, false).await;
+
+            if let Some((format, path)) = &self.dump {
+                if state.chunk_index > 0 {
+                    let graph = self.describe(
+                        &state,
+                        input.as_deref(),
+                        output.as_ref(),
+                        (in_buffer_frames, out_buffer_frames),
+                    );
+                    graph.write(*format, path.as_deref())?;
+                    if let Some(path) = path {
+                        info!("処理グラフを書き出しました: {}", path.display());
+                    }
+                    break;
+                }
+            }
+
+            if input.as_ref().is_some_and(|input| input.is_finished()) {
+                info!("入力ファイルの終わりに達しました");
+                break;
+            }
+        }
+
+        // 停止中のログは元の画面に出す
+        #[cfg(feature = "tui")]
+        drop(self.dashboard.take());
+
+        // 新しい入力を止めてから、変換済みの音声を出力へ書き出す
+        drop(input_stream.take());
+        // これ以上は溜まらないので、ジッターバッファの深さを待たずに再生する
+        self.jitter.drain();
+
+        if self.chunk_options.pad_final && !input_buffer.is_empty() {
+            // 端数を無音で埋めて変換し、再生し終えるまで待つ
+            let chunk_ms = self.current_chunk_ms(chunk_ms);
+            let (chunk_len, _) = chunk_layout(chunk_ms, state.in_rate, state.in_channels);
+            let mut chunk = input_buffer.take(input_buffer.len());
+            chunk.resize(chunk_len.max(chunk.len()), 0.0);
+            self.handle_chunk(&chunk, &mut state).await?;
+
+            let deadline = Instant::now() + Duration::from_secs(BUFFER_SECONDS as u64);
+            while !output_buffer.is_empty() && Instant::now() < deadline {
+                tokio::time::sleep(poll_interval).await;
+            }
+        }
+
+        // 重ねるために持っていた末尾と伏せ待ちの音声も出してから止める
+        let held = state.crossfade.flush();
+        if !held.is_empty() {
+            self.emit(&mut state, held);
+        }
+        release_bleeped(&mut state, true).await;
+
+        // 変換済みの音声を再生し切ってから止める（デバイスでは長く待たない）
+        let drain_timeout = if output.drain_on_stop() {
+            Duration::from_secs(BUFFER_SECONDS as u64)
+        } else {
+            STOP_DRAIN_TIMEOUT
+        };
+        let deadline = Instant::now() + drain_timeout;
+        while !output_buffer.is_empty() && Instant::now() < deadline {
+            tokio::time::sleep(poll_interval).await;
+        }
+        drop(output_stream.take());
+        output.finish()?;
+
+        info!("リアルタイム変換を停止");
+        report_output_clips(&mut state);
+
+        let config = self.converter.config();
+        let summary = SessionSummary {
+            session_id: config.session_id.clone(),
+            model: config.model.clone(),
+            started_at,
+            duration_seconds: started.elapsed().as_secs_f64(),
+            chunks_converted: state.chunks_converted,
+            chunks_failed: state.chunks_failed,
+            chunks_skipped: state.chunks_skipped,
+            latency_ms: LatencySummary::from_samples(&state.latencies_ms),
+            input_dropped_samples: input_buffer.dropped(),
+            input_overruns: input_buffer.overruns(),
+            output_dropped_samples: output_buffer.dropped(),
+            output_underruns: output_buffer.underruns(),
+            input_clips: state.input_clips.events(),
+            output_clips: state.output_clips.events(),
+            bytes_sent: state.bytes_sent,
+            bytes_received: state.bytes_received,
+            recordings: state
+                .recorder
+                .take()
+                .map(SessionRecorder::finish)
+                .unwrap_or_default(),
+        };
+
+        self.hooks.fire(
+            HookEvent::SessionStop,
+            &[
+                (
+                    "MAKEBELIV_DURATION_SECONDS",
+                    format!("{:.0}", summary.duration_seconds),
+                ),
+                ("MAKEBELIV_INPUT_CLIPS", summary.input_clips.to_string()),
+                ("MAKEBELIV_OUTPUT_CLIPS", summary.output_clips.to_string()),
+            ],
+        );
+
+        Ok(summary)
+    }
+
+    /// 1チャンクをスクリプト → 変換 → 出力バッファへ流す
+    async fn handle_chunk(&mut self, chunk: &[f32], state: &mut StreamState) -> Result<()> {
+        let before = state.input_clips.events();
+        state.input_clips.observe(chunk);
+        state.input_level = Level::measure(chunk);
+        state.input_meter.observe(chunk);
+        let clipped = state.input_clips.events() - before;
+        if clipped > 0 {
+            warn!(
+                "⚠ 入力がクリップしました: チャンク{}で{}回（累計{}回）",
+                state.chunk_index,
+                clipped,
+                state.input_clips.events()
+            );
+        }
+
+        self.run_script(state.chunk_index, chunk, state.in_rate, state.in_channels);
+        state.chunk_index += 1;
+        if let Some(recorder) = state.recorder.as_mut() {
+            recorder.write_input(chunk, state.in_rate, state.in_channels);
+        }
+
+        let selected = self.channel_select.apply(chunk, state.in_channels);
+        let chunk = selected.as_deref().unwrap_or(chunk);
+        let send_channels = self.channel_select.channels(state.in_channels);
+        let resampled = self.resample_input(state, chunk, send_channels)?;
+        let chunk = resampled.as_deref().unwrap_or(chunk);
+        if chunk.is_empty() {
+            // リサンプラーが1ブロック分溜まるのを待っている
+            return Ok(());
+        }
+        let wire_rate = self.wire_rate.unwrap_or(state.in_rate);
+
+        let overloaded = self
+            .adaptive
+            .as_mut()
+            .is_some_and(|adaptive| adaptive.is_passing_through(Instant::now()));
+        if state.bypass || overloaded {
+            return self.pass_through(state, chunk, wire_rate, send_channels);
+        }
+
+        if let Some(gate) = self.vad.as_mut() {
+            if !gate.is_open(chunk, wire_rate, send_channels) {
+                self.skip_silent_chunk(
+                    state,
+                    chunk.len() / send_channels.max(1) as usize,
+                    wire_rate,
+                );
+                return Ok(());
+            }
+        }
+
+        if state.fallback.is_some() {
+            if !self.server_responded(state).await {
+                return self.emit_fallback(state, chunk, wire_rate, send_channels);
+            }
+            if let Some(fallback) = state.fallback.take() {
+                info!(
+                    "✓ サーバーが応答したため変換を再開します（{:.1}秒間 {} で代替）",
+                    fallback.since.elapsed().as_secs_f64(),
+                    self.fallback
+                );
+            }
+        }
+
+        // 前のチャンクの末尾を先頭に重ねて送る
+        let in_channels = send_channels.max(1) as usize;
+        let overlap_frames = state.overlap.len() / in_channels;
+        let sent = if self.chunk_options.crossfade_ms > 0 {
+            let mut sent = std::mem::take(&mut state.overlap);
+            sent.extend_from_slice(chunk);
+            let keep = (wire_rate * self.chunk_options.crossfade_ms / 1000) as usize * in_channels;
+            state.overlap = chunk[chunk.len().saturating_sub(keep)..].to_vec();
+            sent
+        } else {
+            chunk.to_vec()
+        };
+
+        let sent_at = Instant::now();
+        match self
+            .converter
+            .convert(&sent, wire_rate, send_channels)
+            .await
+        {
+            Ok(converted) => {
+                state.chunks_converted += 1;
+                let latency_ms = sent_at.elapsed().as_secs_f64() * 1000.0;
+                state.latencies_ms.push(latency_ms);
+                if let Some(adaptive) = self.adaptive.as_mut() {
+                    adaptive.observe(latency_ms, Instant::now());
+                }
+                state.bytes_sent += converted.bytes_sent as u64;
+                state.bytes_received += converted.bytes_received as u64;
+
+                if !state.server_ok {
+                    info!("✓ サーバー接続が回復しました");
+                    state.server_ok = true;
+                    self.notifier.send(Alert::ServerRecovered);
+                }
+                state.server_format = Some((converted.sample_rate, converted.channels));
+                if let Some(recorder) = state.recorder.as_mut() {
+                    recorder.write_output(
+                        &converted.samples,
+                        converted.sample_rate,
+                        converted.channels,
+                    );
+                }
+                let converted = self.resample_output(state, converted)?;
+                push_echo(state, &converted);
+                let mut samples =
+                    remap_channels(&converted.samples, converted.channels, state.out_channels);
+                if let Some(stereo) = self.stereo.as_mut() {
+                    stereo.prepare(state.out_rate, state.out_channels);
+                    stereo.process(&mut samples);
+                }
+                if self.chunk_options.crossfade_ms > 0 {
+                    // 重ねた部分の長さを、変換結果の長さの比で出力のフレーム数に直す
+                    let frames = samples.len() / state.out_channels.max(1) as usize;
+                    let sent_frames = (sent.len() / in_channels).max(1);
+                    let overlap = frames * overlap_frames / sent_frames;
+                    let hold = (state.out_rate * self.chunk_options.crossfade_ms / 1000) as usize;
+                    samples = state
+                        .crossfade
+                        .process(&samples, state.out_channels, overlap, hold);
+                }
+                self.emit(state, samples);
+            }
+            Err(e) => {
+                state.chunks_failed += 1;
+                // 次のチャンクとは重ならないので、持っていた末尾はフェードアウトして出す
+                let held = state.crossfade.flush();
+                if !held.is_empty() {
+                    self.emit(state, held);
+                }
+                warn!("チャンク変換エラー: {}", e);
+                if self.fallback != Fallback::Silence
+                    && self.converter.config().engine == Engine::Server
+                {
+                    if state.fallback.is_none() {
+                        warn!(
+                            "⚠ サーバーに届かないため、応答が戻るまで {} で代替します",
+                            self.fallback
+                        );
+                        state.fallback = Some(FallbackProbe {
+                            since: Instant::now(),
+                            probed_at: Some(Instant::now()),
+                            probe: None,
+                        });
+                    }
+                    self.emit_fallback(state, chunk, wire_rate, send_channels)?;
+                }
+                if state.server_ok {
+                    state.server_ok = false;
+                    self.hooks.fire(
+                        HookEvent::ServerLost,
+                        &[("MAKEBELIV_ERROR", format!("{:#}", e))],
+                    );
+                    self.notifier.send(Alert::ServerLost {
+                        error: format!("{:#}", e),
+                    });
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// 送らなかった無音のチャンクの代わりに、同じ長さの無音を出力へ流す
+    ///
+    /// 再生を途切れさせないので、ジッターバッファが空になって溜め直すこともありません。
+    fn skip_silent_chunk(&mut self, state: &mut StreamState, frames: usize, rate: u32) {
+        state.chunks_skipped += 1;
+        // 次の発話のチャンクとは重ならないので、持っていた末尾はフェードアウトして出す
+        state.overlap.clear();
+        let held = state.crossfade.flush();
+        if !held.is_empty() {
+            self.emit(state, held);
+        }
+
+        let len = (frames as u64 * state.out_rate as u64 / rate.max(1) as u64) as usize
+            * state.out_channels as usize;
+        let samples = match (&state.noise, self.vad.as_mut()) {
+            (None, Some(gate)) => gate.comfort_noise(len),
+            _ => vec![0.0; len],
+        };
+        self.emit(state, samples);
+    }
+
+    /// バイパス中のチャンクを、変換もローカルDSPも通さずに出力へ流す
+    fn pass_through(
+        &mut self,
+        state: &mut StreamState,
+        chunk: &[f32],
+        rate: u32,
+        channels: u16,
+    ) -> Result<()> {
+        // 変換結果とは重ならないので、持っていた末尾はフェードアウトして出す
+        state.overlap.clear();
+        let held = state.crossfade.flush();
+        if !held.is_empty() {
+            self.emit(state, held);
+        }
+
+        let original = self.resample_output(
+            state,
+            ConvertedChunk {
+                samples: chunk.to_vec(),
+                sample_rate: rate,
+                channels,
+                bytes_sent: 0,
+                bytes_received: 0,
+            },
+        )?;
+        let mut samples = remap_channels(&original.samples, original.channels, state.out_channels);
+        if let Some(stereo) = self.stereo.as_mut() {
+            stereo.prepare(state.out_rate, state.out_channels);
+            stereo.process(&mut samples);
+        }
+        self.emit(state, samples);
+        Ok(())
+    }
+
+    /// 変換できないチャンクの代わりに、入力そのもの（またはローカルでピッチを変えたもの）を出す
+    fn emit_fallback(
+        &mut self,
+        state: &mut StreamState,
+        chunk: &[f32],
+        rate: u32,
+        channels: u16,
+    ) -> Result<()> {
+        match self.fallback {
+            Fallback::Silence => Ok(()),
+            Fallback::Passthrough => self.pass_through(state, chunk, rate, channels),
+            Fallback::Pitch => {
+                let shifted = self.converter.convert_local(chunk, rate, channels);
+                self.pass_through(
+                    state,
+                    &shifted.samples,
+                    shifted.sample_rate,
+                    shifted.channels,
+                )
+            }
+        }
+    }
+
+    /// 裏の問い合わせでサーバーの応答が確かめられたか（まだなら、間隔を空けて次を問い合わせる）
+    async fn server_responded(&mut self, state: &mut StreamState) -> bool {
+        let Some(fallback) = state.fallback.as_mut() else {
+            return true;
+        };
+        if let Some(probe) = fallback.probe.take_if(|probe| probe.is_finished()) {
+            if probe.await.unwrap_or(false) {
+                return true;
+            }
+        }
+        let due = fallback
+            .probed_at
+            .is_none_or(|at| at.elapsed() >= FALLBACK_PROBE_INTERVAL);
+        if fallback.probe.is_none() && due {
+            let client = self.converter.client().clone();
+            fallback.probed_at = Some(Instant::now());
+            fallback.probe = Some(tokio::spawn(
+                async move { client.check_status().await.is_ok() },
+            ));
+        }
+        false
+    }
+
+    /// サーバーのリソース使用状況の問い合わせを裏で始める（応答を待つとチャンクの処理が止まるため）
+    fn poll_resources(&self, state: &mut StreamState) {
+        // サーバーに届かない間は、復帰の確認に任せる
+        if state.resource_probe.is_some() || state.fallback.is_some() {
+            return;
+        }
+        let client = self.converter.client().clone();
+        state.resource_probe = Some(tokio::spawn(async move {
+            client
+                .resource_stats()
+                .await
+                .map_err(|e| debug!("リソース使用状況の取得エラー: {}", e))
+                .ok()
+        }));
+    }
+
+    /// 問い合わせたリソース使用状況を覚え、GPUが逼迫し始めたときと戻ったときにログへ出す
+    fn update_resources(&self, state: &mut StreamState, resources: ResourceStats) {
+        let was_pressured = state
+            .resources
+            .as_ref()
+            .is_some_and(ResourceStats::is_under_pressure);
+        match (was_pressured, resources.is_under_pressure()) {
+            (false, true) => warn!(
+                "⚠ サーバーのGPUが逼迫しています。変換が遅れる可能性があります: {}",
+                resources.summary()
+            ),
+            (true, false) => info!("✓ サーバーの負荷が下がりました: {}", resources.summary()),
+            _ => {}
+        }
+        state.resources = Some(resources);
+    }
+
+    /// 今のチャンク長（処理の追いつき具合で変える場合は、その時点の長さ）
+    fn current_chunk_ms(&self, configured: u32) -> u32 {
+        self.adaptive
+            .as_ref()
+            .map_or(configured, AdaptiveChunk::chunk_ms)
+    }
+
+    /// キー入力の受け付けを始める（端末でなければ受け付けない）
+    fn listen_hotkeys(&mut self) -> Option<HotkeyListener> {
+        let hotkeys = self.hotkeys.as_mut()?;
+        let config = self.converter.config();
+        hotkeys.base = (config.model.clone(), config.pitch_shift);
+        match hotkeys::listen(hotkeys.keymap.clone()) {
+            Ok(Some(listener)) => {
+                info!("⌨ ホットキー: {}", hotkeys.keymap.describe());
+                Some(listener)
+            }
+            Ok(None) => {
+                debug!("標準入力が端末ではないため、ホットキーは使えません");
+                None
+            }
+            Err(e) => {
+                warn!("⚠ ホットキーなしで続行します: {:#}", e);
+                None
+            }
+        }
+    }
+
+    /// 前回からの入出力のレベルを表示し、入力のクリップと無音を大きく警告する
+    ///
+    /// マイクのゲインの設定ミスは、変換がうまくいかない一番よくある原因です。
+    fn check_levels(&self, state: &mut StreamState) {
+        let input = state.input_meter.take();
+        let output = state.output_meter.take();
+        #[cfg(feature = "tui")]
+        let print = self.print_levels && self.dashboard.is_none();
+        #[cfg(not(feature = "tui"))]
+        let print = self.print_levels;
+        if print {
+            let show =
+                |level: Option<Level>| level.map_or_else(|| "-".to_string(), |l| l.to_string());
+            match &state.resources {
+                Some(resources) => info!(
+                    "🎚 入力 {} ｜ 出力 {} ｜ サーバー {}",
+                    show(input),
+                    show(output),
+                    resources.summary()
+                ),
+                None => info!("🎚 入力 {} ｜ 出力 {}", show(input), show(output)),
+            }
+        }
+        let Some(input) = input else {
+            return;
+        };
+
+        let clips = state.input_clips.events();
+        if clips > state.reported_input_clips {
+            warn!(
+                "⚠⚠ 入力がクリップしています: この{}秒で{}回（ピーク {:.1} dB）。マイクの入力ゲインを下げてください（音割れした声はきれいに変換できません）",
+                self.level_interval.as_secs(),
+                clips - state.reported_input_clips,
+                input.peak_db
+            );
+            state.reported_input_clips = clips;
+        }
+
+        if input.peak_db >= NEAR_SILENT_PEAK_DB {
+            if state.near_silent_warned {
+                info!("✓ 入力の音が戻りました（ピーク {:.1} dB）", input.peak_db);
+            }
+            state.near_silent = Duration::ZERO;
+            state.near_silent_warned = false;
+            return;
+        }
+        state.near_silent += self.level_interval;
+        if state.near_silent >= NEAR_SILENT_WARN_AFTER && !state.near_silent_warned {
+            state.near_silent_warned = true;
+            warn!(
+                "⚠⚠ 入力がほぼ無音です: {}秒間ピーク {:.1} dB 未満。マイクのミュート、入力ゲイン、入力デバイス（--input）を確認してください",
+                state.near_silent.as_secs(),
+                NEAR_SILENT_PEAK_DB
+            );
+        }
+    }
+
+    /// ダッシュボードを今の状態で描き直す（描けなければ閉じて続ける）
+    #[cfg(feature = "tui")]
+    fn draw_dashboard(
+        &mut self,
+        state: &StreamState,
+        input_buffer: &AudioBuffer,
+        started: Instant,
+    ) {
+        let chunk_ms = self.current_chunk_ms(self.converter.config().chunk_ms);
+        let Some(dashboard) = self.dashboard.as_mut() else {
+            return;
+        };
+        let buffered_ms = |len: usize, rate: u32, channels: u16| {
+            len as u64 * 1000 / (rate as u64 * channels.max(1) as u64).max(1)
+        };
+        let config = self.converter.config();
+        let latencies =
+            &state.latencies_ms[state.latencies_ms.len().saturating_sub(DASHBOARD_LATENCIES)..];
+        let frame = DashboardFrame {
+            elapsed: started.elapsed(),
+            model: config.model.clone(),
+            pitch: config.pitch_shift,
+            noise_level: state.noise.is_some().then(|| state.controls.noise_level()),
+            bypass: state.bypass,
+            muted: state.controls.muted.load(Ordering::Relaxed),
+            input_level: state.input_level,
+            output_level: state.output_level,
+            chunk_ms,
+            latencies_ms: latencies.iter().map(|&ms| ms.round() as u64).collect(),
+            chunks_converted: state.chunks_converted,
+            chunks_failed: state.chunks_failed,
+            chunks_skipped: state.chunks_skipped,
+            input_dropped: input_buffer.dropped(),
+            output_underruns: state.output_buffer.underruns(),
+            input_buffered_ms: buffered_ms(input_buffer.len(), state.in_rate, state.in_channels),
+            output_buffered_ms: buffered_ms(
+                state.output_buffer.len(),
+                state.out_rate,
+                state.out_channels,
+            ),
+            jitter_ms: state.jitter.depth_ms(),
+            server: state.resources.clone(),
+        };
+        if let Err(e) = dashboard.draw(&frame) {
+            self.dashboard = None;
+            warn!("⚠ ダッシュボードを閉じて続行します: {:#}", e);
+        }
+    }
+
+    #[cfg(not(feature = "tui"))]
+    fn draw_dashboard(
+        &mut self,
+        _state: &StreamState,
+        _input_buffer: &AudioBuffer,
+        _started: Instant,
+    ) {
+    }
+
+    /// ホットキーの操作を適用する
+    fn apply_hotkey(&mut self, action: HotkeyAction, state: &mut StreamState) {
+        match action {
+            HotkeyAction::Bypass => {
+                state.bypass = !state.bypass;
+                if state.bypass {
+                    info!("⌨ バイパス: オン（変換せずに元の声を流します）");
+                } else {
+                    info!("⌨ バイパス: オフ");
+                }
+            }
+            HotkeyAction::Mute => {
+                let muted = !state.controls.muted.fetch_xor(true, Ordering::Relaxed);
+                info!("⌨ ミュート: {}", if muted { "オン" } else { "オフ" });
+            }
+            HotkeyAction::PitchUp | HotkeyAction::PitchDown => {
+                let config = self.converter.config_mut();
+                let step = if action == HotkeyAction::PitchUp {
+                    1
+                } else {
+                    -1
+                };
+                config.pitch_shift += step;
+                info!("⌨ ピッチ: {:+}", config.pitch_shift);
+            }
+            HotkeyAction::NextPreset => self.next_preset(),
+        }
+    }
+
+    /// 制御ソケットの要求を処理する
+    fn apply_control(
+        &mut self,
+        request: ControlRequest,
+        state: &mut StreamState,
+    ) -> ControlResponse {
+        if let ControlRequest::Set { param, value } = request {
+            if let Err(e) = self.set_param(&param, value.trim(), state) {
+                return ControlResponse::error(format!("{:#}", e));
+            }
+        }
+        let config = self.converter.config();
+        ControlResponse::ok(LiveParams {
+            model: config.model.clone(),
+            pitch: config.pitch_shift,
+            noise_level: state.noise.is_some().then(|| state.controls.noise_level()),
+        })
+    }
+
+    /// パラメータを1つ変える（`ctl set`）
+    fn set_param(&mut self, param: &str, value: &str, state: &mut StreamState) -> Result<()> {
+        let config = self.converter.config_mut();
+        match param {
+            "model" => {
+                anyhow::ensure!(!value.is_empty(), "モデル名を指定してください");
+                info!("🎛 制御: モデル {} → {}", config.model, value);
+                config.model = value.to_string();
+            }
+            "pitch" => {
+                let pitch: i32 = value
+                    .parse()
+                    .with_context(|| format!("ピッチは半音の整数で指定してください: {}", value))?;
+                info!("🎛 制御: ピッチ {:+} → {:+}", config.pitch_shift, pitch);
+                config.pitch_shift = pitch;
+            }
+            "noise_level" => {
+                let level: f32 = value
+                    .parse()
+                    .ok()
+                    .filter(|level| (0.0..=1.0).contains(level))
+                    .with_context(|| {
+                        format!("ノイズの音量は0.0〜1.0で指定してください: {}", value)
+                    })?;
+                let noise = state.noise.as_mut().context(
+                    "背景ノイズなしで起動しているため変えられません（--noise を指定して起動してください）",
+                )?;
+                info!(
+                    "🎛 制御: ノイズの音量 {} → {}",
+                    state.controls.noise_level(),
+                    level
+                );
+                noise.set_level(level);
+                state.controls.set_noise_level(level);
+            }
+            _ => anyhow::bail!(
+                "不明なパラメータ: {}（{}）",
+                param,
+                control::PARAMS.join(", ")
+            ),
+        }
+        Ok(())
+    }
+
+    /// 次のプリセットのモデルとピッチに切り替える
+    ///
+    /// プリセットにない項目は起動時の値に戻します。ノイズ・息・デバイスは実行中には変えません。
+    fn next_preset(&mut self) {
+        let Some(hotkeys) = self.hotkeys.as_mut() else {
+            return;
+        };
+        if hotkeys.presets.is_empty() {
+            warn!("⚠ 切り替えるプリセットがありません（makebeliv preset save で作成できます）");
+            return;
+        }
+        let (base_model, base_pitch) = &hotkeys.base;
+        let next = match &hotkeys.current_preset {
+            Some(current) => hotkeys
+                .presets
+                .iter()
+                .position(|(name, _)| name == current)
+                .map_or(0, |index| (index + 1) % hotkeys.presets.len()),
+            None => 0,
+        };
+        let (name, preset) = &hotkeys.presets[next];
+        let config = self.converter.config_mut();
+        config.model = preset.model.clone().unwrap_or_else(|| base_model.clone());
+        config.pitch_shift = preset.pitch.unwrap_or(*base_pitch);
+        info!(
+            "⌨ プリセット: {}（モデル {} / ピッチ {:+}）",
+            name, config.model, config.pitch_shift
+        );
+        let others = Preset {
+            model: None,
+            pitch: None,
+            ..preset.clone()
+        };
+        if !others.is_empty() {
+            info!("  {} は次に起動したときに反映されます", others.summary());
+        }
+        hotkeys.current_preset = Some(name.clone());
+    }
+
+    /// 変換結果を遅延の上限 → 伏せる語 → 出力バッファへ流す
+    fn emit(&self, state: &mut StreamState, samples: Vec<f32>) {
+        let samples = match &self.latency_guard {
+            Some(guard) => guard_latency(guard, state, samples),
+            None => samples,
+        };
+        match state.bleeper.as_mut() {
+            Some(bleeper) => bleeper.push(&samples, state.out_rate, state.out_channels),
+            None => push_output(state, &samples),
+        }
+    }
+
+    /// 実際に組み上がった処理グラフ（段ごとの形式・バッファ・遅延）
+    fn describe(
+        &self,
+        state: &StreamState,
+        input: Option<&dyn InputDevice>,
+        output: &dyn OutputDevice,
+        (in_buffer_frames, out_buffer_frames): (u32, u32),
+    ) -> PipelineGraph {
+        let config = self.converter.config();
+        let frames_ms = |frames: u32, rate: u32| frames as f64 * 1000.0 / rate.max(1) as f64;
+        let mut graph = PipelineGraph::new();
+
+        let source = match (input, &self.input) {
+            (Some(input), _) => Stage::new(format!("入力: {}", input.name()))
+                .buffer(format!("{}フレーム", in_buffer_frames))
+                .latency_ms(frames_ms(in_buffer_frames, state.in_rate)),
+            (None, InputSpec::Fifo(path)) => Stage::new(format!("入力: FIFO {}", path.display()))
+                .detail(self.input_format.to_string()),
+            (None, _) => Stage::new("入力"),
+        };
+        let source = graph.then(source.format(state.in_rate, state.in_channels));
+        if let Some(sidetone) = &state.sidetone {
+            let mut stage = Stage::new(format!("サイドトーン: {}", sidetone.name()))
+                .format(sidetone.sample_rate(), sidetone.channels())
+                .buffer(format!("最大{}ms", SIDETONE_BUFFER_MS));
+            if sidetone.sample_rate() != state.in_rate {
+                stage = stage.detail(format!(
+                    "リサンプル {}Hz → {}Hz（{}）",
+                    state.in_rate,
+                    sidetone.sample_rate(),
+                    ResampleQuality::Fast
+                ));
+            }
+            graph.branch(source, stage);
+        }
+
+        let (chunk_len, search_frames) =
+            chunk_layout(config.chunk_ms, state.in_rate, state.in_channels);
+        let mut chunking = Stage::new(format!("チャンク分割: {}ms", config.chunk_ms))
+            .format(state.in_rate, state.in_channels)
+            .buffer(format!("{}サンプル", chunk_len))
+            .latency_ms(config.chunk_ms as f64);
+        if self.chunk_options.align_zero_crossings {
+            chunking = chunking
+                .detail(format!(
+                    "ゼロクロスに合わせる（±{}ms）",
+                    ZERO_CROSSING_SEARCH_MS
+                ))
+                .latency_ms(
+                    config.chunk_ms as f64 + frames_ms(search_frames as u32, state.in_rate),
+                );
+        }
+        if let Some(adaptive) = &self.adaptive {
+            chunking =
+                chunking.detail(format!("追いつかなければ{}msまで延ばす", adaptive.max_ms()));
+        }
+        if self.chunk_options.crossfade_ms > 0 {
+            chunking = chunking.detail(format!(
+                "前のチャンクの末尾{}msを重ねて送る",
+                self.chunk_options.crossfade_ms
+            ));
+        }
+        if self.script.is_some() {
+            chunking = chunking.detail("パラメータスクリプト");
+        }
+        let chunking = graph.then(chunking);
+        let send_channels = self.channel_select.channels(state.in_channels);
+        if send_channels != state.in_channels {
+            graph.then(
+                Stage::new(format!(
+                    "チャンネル選択: {}ch → {}ch（{}）",
+                    state.in_channels, send_channels, self.channel_select
+                ))
+                .format(state.in_rate, send_channels),
+            );
+        }
+        let wire_rate = self.wire_rate.unwrap_or(state.in_rate);
+        if wire_rate != state.in_rate {
+            let mut stage = Stage::new(format!(
+                "リサンプル（送信）: {}Hz → {}Hz（{}）",
+                state.in_rate, wire_rate, self.resample_quality
+            ))
+            .format(wire_rate, send_channels);
+            if let Some((_, _, resampler)) = &state.wire_resampler {
+                stage = stage.latency_ms(resampler.latency_ms(state.in_rate, wire_rate));
+            }
+            graph.then(stage);
+        }
+
+        if let Some(gate) = &self.vad {
+            graph.then(
+                Stage::new(format!("無音の判定: {}dBFS未満", gate.threshold_db()))
+                    .detail(format!(
+                        "発話の後{}msまでは送る。無音のチャンクは送らずに無音を出力する",
+                        vad::HANGOVER_MS
+                    ))
+                    .format(wire_rate, send_channels),
+            );
+        }
+        let mut conversion = match config.engine {
+            Engine::Server => Stage::new(format!(
+                "変換: サーバー（{}）",
+                match config.transport {
+                    Transport::Http => "HTTP",
+                    Transport::Ws => "WebSocket",
+                }
+            )),
+            Engine::Local => Stage::new("変換: ローカルのピッチシフト"),
+        }
+        .detail(format!(
+            "モデル {} / ピッチ {:+}",
+            config.model, config.pitch_shift
+        ));
+        if config.engine == Engine::Server && config.codec == Codec::Opus {
+            conversion = conversion.detail("Opusで送受信");
+        }
+        if config.engine == Engine::Server && self.fallback != Fallback::Silence {
+            conversion =
+                conversion.detail(format!("サーバーに届かなければ {} で代替", self.fallback));
+        }
+        if let Some((rate, channels)) = state.server_format {
+            conversion = conversion.format(rate, channels);
+        }
+        if let Some(latency) = state.latencies_ms.first() {
+            conversion = conversion
+                .detail("遅延は最初のチャンクの往復（接続を含む）")
+                .latency_ms(*latency);
+        }
+        let conversion = graph.then(conversion);
+        if let Some(options) = &self.recording {
+            let dir = options.dir.display();
+            let format = match options.format {
+                RecordFormat::Wav => "WAV 16bit".to_string(),
+                RecordFormat::Opus => {
+                    format!("Opus {}kbps（別スレッドで符号化）", options.bitrate_kbps)
+                }
+            };
+            graph.branch(
+                chunking,
+                Stage::new(format!("録音（入力）: {}", dir)).detail(format.clone()),
+            );
+            graph.branch(
+                conversion,
+                Stage::new(format!("録音（変換結果）: {}", dir)).detail(format),
+            );
+        }
+
+        let (server_rate, server_channels) =
+            state.server_format.unwrap_or((wire_rate, send_channels));
+        let mut last = conversion;
+        let chain = self.converter.chain();
+        if !chain.is_empty() {
+            last = graph.then(
+                Stage::new("ローカルDSP")
+                    .detail(chain.stage_names().join(" → "))
+                    .format(server_rate, server_channels),
+            );
+        }
+        if server_rate != state.out_rate {
+            let mut stage = Stage::new(format!(
+                "リサンプル: {}Hz → {}Hz（{}）",
+                server_rate, state.out_rate, self.resample_quality
+            ))
+            .format(state.out_rate, server_channels);
+            if let Some((_, _, resampler)) = &state.resampler {
+                stage = stage.latency_ms(resampler.latency_ms(server_rate, state.out_rate));
+            }
+            last = graph.then(stage);
+        }
+        if let Some(echo) = &state.echo {
+            let mut stage = Stage::new(format!("エコー: {}", echo.name()))
+                .format(echo.sample_rate(), echo.channels());
+            if echo.sample_rate() != state.out_rate {
+                stage = stage.detail(format!(
+                    "リサンプル {}Hz → {}Hz（{}）",
+                    state.out_rate,
+                    echo.sample_rate(),
+                    ResampleQuality::Fast
+                ));
+            }
+            graph.branch(last, stage);
+        }
+        if server_channels != state.out_channels {
+            graph.then(
+                Stage::new(format!(
+                    "チャンネル変換: {}ch → {}ch",
+                    server_channels, state.out_channels
+                ))
+                .format(state.out_rate, state.out_channels),
+            );
+        }
+        if self.chunk_options.crossfade_ms > 0 {
+            graph.then(
+                Stage::new(format!(
+                    "クロスフェード: {}ms",
+                    self.chunk_options.crossfade_ms
+                ))
+                .detail("末尾を次のチャンクの先頭と重ねる")
+                .format(state.out_rate, state.out_channels)
+                .latency_ms(self.chunk_options.crossfade_ms as f64),
+            );
+        }
+        if let Some(stereo) = &self.stereo {
+            graph.then(
+                Stage::new("定位と広がり")
+                    .detail(format!(
+                        "定位 {:+.2} / 広がり {}",
+                        stereo.pan(),
+                        stereo.width()
+                    ))
+                    .format(state.out_rate, state.out_channels),
+            );
+        }
+        if let Some(guard) = &self.latency_guard {
+            graph.then(
+                Stage::new("遅延の上限")
+                    .detail(format!(
+                        "{}msを超えたら {}",
+                        guard.max_latency_ms, guard.catchup
+                    ))
+                    .format(state.out_rate, state.out_channels),
+            );
+        }
+        if let Some(bleeper) = &state.bleeper {
+            graph.then(
+                Stage::new("伏せる語")
+                    .detail(format!(
+                        "{}（{}）",
+                        bleeper.keywords().join(", "),
+                        bleeper.mode()
+                    ))
+                    .format(state.out_rate, state.out_channels)
+                    .latency_ms(bleeper.window().as_secs_f64() * 1000.0),
+            );
+        }
+        let depth_ms = self.jitter.depth_ms();
+        let buffering = if depth_ms > 0 {
+            Stage::new("出力バッファ")
+                .detail(format!("ジッターバッファ: {}ms溜まってから再生", depth_ms))
+                .latency_ms(depth_ms as f64)
+        } else {
+            Stage::new("出力バッファ")
+                .detail(format!("最初に{}msの無音を挟む", OUTPUT_PREROLL_MS))
+                .latency_ms(OUTPUT_PREROLL_MS as f64)
+        };
+        graph.then(
+            buffering
+                .format(state.out_rate, state.out_channels)
+                .buffer(format!("最大{}秒", BUFFER_SECONDS)),
+        );
+        if let Some(noise) = &state.noise {
+            graph.then(
+                Stage::new("背景ノイズ")
+                    .detail(format!("{}（{}）", noise.source_name(), noise.level()))
+                    .format(state.out_rate, state.out_channels),
+            );
+        }
+        graph.then(
+            Stage::new(format!("出力: {}", output.name()))
+                .format(state.out_rate, state.out_channels)
+                .buffer(format!("{}フレーム", out_buffer_frames))
+                .latency_ms(frames_ms(out_buffer_frames, state.out_rate)),
+        );
+
+        // 構成の問題
+        match state.server_format {
+            Some((rate, _)) if rate != state.in_rate && rate != state.out_rate => graph.note(format!(
+                "二重のリサンプリング: 入力 {}Hz → サーバー {}Hz → 出力 {}Hz（デバイスのどちらかを{}Hzにそろえると1回で済みます）",
+                state.in_rate, rate, state.out_rate, rate
+            )),
+            Some(_) => {}
+            None => graph.note("最初のチャンクの変換に失敗したため、サーバーが返す形式は分かりません"),
+        }
+        let chunk_frames = (state.in_rate as u64 * config.chunk_ms as u64 / 1000) as u32;
+        if input.is_some() && in_buffer_frames > chunk_frames {
+            graph.note(format!(
+                "入力デバイスのバッファ（{}フレーム）がチャンク（{}フレーム）より長く、遅延が増えます",
+                in_buffer_frames, chunk_frames
+            ));
+        }
+        if self.stereo.is_some() && state.out_channels < 2 {
+            graph.note("出力がモノラルのため、定位と広がりは効きません");
+        }
+        if self.low_power {
+            graph.note(format!(
+                "低電力モード: 入力を{}msごとに確認するため、チャンクが溜まってから最大その分遅れます",
+                self.poll_interval(config.chunk_ms).as_millis()
+            ));
+        }
+        graph
+    }
+
+    /// 入力にチャンクが溜まったかを確かめる間隔（低電力モードでは半分の頻度）
+    fn poll_interval(&self, chunk_ms: u32) -> Duration {
+        let divisor = if self.low_power { 2 } else { 4 };
+        Duration::from_millis((chunk_ms as u64 / divisor).max(1))
+    }
+
+    /// Bluetoothヘッドセットの通話用プロファイル（HFP）を警告し、`--bt-a2dp` なら避ける
+    ///
+    /// Bluetoothのマイクを使うとヘッドセットが通話用プロファイルに切り替わり、入出力とも
+    /// 8/16kHzになって変換の質が大きく落ちます。`--bt-a2dp` では入力を別のマイクに替え、
+    /// Linuxではカードを再生用プロファイル（A2DP）に切り替えます。
+    /// 出力を開き直す必要があればtrueを返します。
+    fn check_bluetooth(
+        &self,
+        input: &mut Option<Box<dyn InputDevice>>,
+        output: &dyn OutputDevice,
+    ) -> Result<bool> {
+        let bt_input = input.as_ref().and_then(|input| {
+            bluetooth::inspect(&input.name(), input.sample_rate(), DeviceDirection::Input)
+        });
+        let bt_output = bluetooth::inspect(
+            &output.name(),
+            output.sample_rate(),
+            DeviceDirection::Output,
+        );
+        let hands_free = bt_input.iter().chain(&bt_output).any(|bt| bt.hands_free);
+        if !self.bt_a2dp {
+            if hands_free {
+                warn!(
+                    "⚠ Bluetoothヘッドセットが通話用プロファイル（HFP、{}Hz以下）で動いています。音質が大きく落ち、変換も不自然になります",
+                    bluetooth::HANDS_FREE_MAX_RATE
+                );
+                warn!("  別のマイクを使って高音質の再生（A2DP）にするには --bt-a2dp を指定してください");
+            } else if bt_input.is_some() {
+                warn!("⚠ Bluetoothのマイクを使うと、ヘッドセットが通話用プロファイル（HFP）に切り替わることがあります");
+            }
+            return Ok(false);
+        }
+
+        if let Some(bt) = &bt_input {
+            info!(
+                "Bluetoothのマイク（{}）の代わりに別のマイクを使います",
+                bt.name
+            );
+            let name = autoinput::select_non_bluetooth_input()
+                .context("Bluetooth以外の入力デバイスがありません（--bt-a2dp）")?;
+            *input = Some(backend::CpalBackend.open_input(Some(&name))?);
+        }
+        if bt_input.is_none() && bt_output.as_ref().is_none_or(|bt| !bt.hands_free) {
+            return Ok(false);
+        }
+        if !cfg!(target_os = "linux") {
+            info!("Bluetoothのマイクを使わなければ、OSがヘッドセットを再生用プロファイル（A2DP）に戻します");
+            return Ok(false);
+        }
+        let switched = bluetooth::force_a2dp()?;
+        if switched.is_empty() {
+            warn!("⚠ A2DPに切り替えられるBluetoothのカードが見つかりませんでした");
+        }
+        Ok(!switched.is_empty())
+    }
+
+    /// 入力のチャンクをサーバーへ送るレートに合わせる（同じレートならNone）
+    ///
+    /// チャンクをまたいで続けて処理するので、つなぎ目に継ぎ目はできませんが、
+    /// 1ブロックに満たない分は次のチャンクに回ります。
+    fn resample_input(
+        &self,
+        state: &mut StreamState,
+        chunk: &[f32],
+        channels: u16,
+    ) -> Result<Option<Vec<f32>>> {
+        let wire_rate = match self.wire_rate {
+            Some(rate) if rate != state.in_rate => rate,
+            _ => return Ok(None),
+        };
+        let matches = state
+            .wire_resampler
+            .as_ref()
+            .is_some_and(|(rate, resampler_channels, _)| {
+                *rate == state.in_rate && *resampler_channels == channels
+            });
+        if !matches {
+            state.wire_resampler = Some((
+                state.in_rate,
+                channels,
+                Resampler::new(state.in_rate, wire_rate, channels, self.resample_quality)?,
+            ));
+        }
+        let (_, _, resampler) = state
+            .wire_resampler
+            .as_mut()
+            .expect("リサンプラーは作成済み");
+        resampler.process(chunk).map(Some)
+    }
+
+    /// 変換結果を出力デバイスのレートに合わせる（レートや形式が変わったらリサンプラーを作り直す）
+    fn resample_output(
+        &self,
+        state: &mut StreamState,
+        converted: ConvertedChunk,
+    ) -> Result<ConvertedChunk> {
+        if converted.sample_rate == state.out_rate {
+            return Ok(converted);
+        }
+
+        let matches = state.resampler.as_ref().is_some_and(|(rate, channels, _)| {
+            *rate == converted.sample_rate && *channels == converted.channels
+        });
+        if !matches {
+            state.resampler = Some((
+                converted.sample_rate,
+                converted.channels,
+                Resampler::new(
+                    converted.sample_rate,
+                    state.out_rate,
+                    converted.channels,
+                    self.resample_quality,
+                )?,
+            ));
+        }
+
+        let (_, _, resampler) = state.resampler.as_mut().expect("リサンプラーは作成済み");
+        let samples = resampler.process(&converted.samples)?;
+        Ok(ConvertedChunk {
+            samples,
+            sample_rate: state.out_rate,
+            ..converted
+        })
+    }
+
+    /// スクリプトを呼び出し、要求されたパラメータ変更を適用
+    fn run_script(&mut self, index: u64, chunk: &[f32], sample_rate: u32, channels: u16) {
+        let Some(script) = &self.script else {
+            return;
+        };
+
+        let event = ChunkEvent::analyze(index, chunk, sample_rate, channels);
+        let config = self.converter.config_mut();
+        match script.on_chunk(&event, config.pitch_shift, &config.model) {
+            Ok(changes) => {
+                if let Some(pitch) = changes.pitch_shift {
+                    if pitch != config.pitch_shift {
+                        info!("スクリプト: ピッチ {:+} → {:+}", config.pitch_shift, pitch);
+                        config.pitch_shift = pitch;
+                    }
+                }
+                if let Some(model) = changes.model {
+                    if model != config.model {
+                        info!("スクリプト: モデル {} → {}", config.model, model);
+                        config.model = model;
+                    }
+                }
+            }
+            Err(e) => warn!("{:#}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options() -> PipelineOptions {
+        let mut config = Config::default();
+        config.conversion.chunk_ms = 200;
+        PipelineOptions::from_config(&config).unwrap()
+    }
+
+    fn error(options: &PipelineOptions) -> String {
+        options.validate().unwrap_err().to_string()
+    }
+
+    #[test]
+    fn chunk_length_follows_the_profile_and_low_power() {
+        let mut options = options();
+        assert_eq!(options.chunk_ms(), 200);
+        options.profile = Profile::Pi;
+        assert_eq!(options.chunk_ms(), PI_CHUNK_MS);
+        assert_eq!(options.jitter_buffer_ms(), PI_JITTER_BUFFER_MS);
+        assert_eq!(options.codec(), Codec::Opus);
+        options.low_power = true;
+        assert_eq!(options.chunk_ms(), LOW_POWER_CHUNK_MS);
+        // 明示した値はプロファイルより優先する
+        options.chunk_ms = Some(100);
+        options.jitter_buffer_ms = Some(20);
+        options.codec = Some(Codec::Pcm);
+        assert_eq!(options.chunk_ms(), 100);
+        assert_eq!(options.jitter_buffer_ms(), 20);
+        assert_eq!(options.codec(), Codec::Pcm);
+        options.validate().unwrap();
+    }
+
+    #[test]
+    fn reads_the_audio_section() {
+        let mut config = Config::default();
+        config.audio.profile = Some("pi".to_string());
+        config.audio.input = Some("wav:in.wav".to_string());
+        config.audio.vad = true;
+        config.audio.vad_threshold = -45.0;
+        let options = PipelineOptions::from_config(&config).unwrap();
+        assert_eq!(options.profile, Profile::Pi);
+        assert_eq!(options.input, InputSpec::Wav(PathBuf::from("in.wav")));
+        assert_eq!(options.voice_gate().unwrap().threshold_db(), -45.0);
+
+        config.audio.profile = Some("laptop".to_string());
+        let error = PipelineOptions::from_config(&config).unwrap_err();
+        assert!(format!("{:#}", error).contains("[audio] profile"));
+    }
+
+    #[test]
+    fn names_where_the_crossfade_came_from() {
+        let mut config = Config::default();
+        config.conversion.chunk_ms = 200;
+        config.conversion.overlap_ms = 200;
+        let mut options = PipelineOptions::from_config(&config).unwrap();
+        assert!(error(&options).starts_with("設定ファイルの [conversion] overlap_ms: 200ms"));
+        options.crossfade_ms = Some(250);
+        assert!(error(&options).starts_with("--crossfade-ms / --overlap-ms: 250ms"));
+        options.crossfade_ms = Some(20);
+        options.validate().unwrap();
+        assert_eq!(options.chunk_options().crossfade_ms, 20);
+    }
+
+    #[test]
+    fn rejects_a_latency_limit_the_buffers_cannot_meet() {
+        let mut options = options();
+        options.max_latency_ms = Some(100);
+        assert!(error(&options).contains("--max-latency-ms"));
+        options.max_latency_ms = Some(400);
+        options.jitter_buffer_ms = Some(250);
+        assert!(error(&options).contains("--jitter-buffer-ms"));
+        options.jitter_buffer_ms = Some(200);
+        options.validate().unwrap();
+        let guard = options.latency_guard().unwrap();
+        assert_eq!(guard.max_latency_ms, 400);
+    }
+
+    #[test]
+    fn caps_the_adaptive_chunk_length() {
+        let mut options = options();
+        assert!(options.adaptive_chunk().is_none());
+        options.adaptive_chunk = true;
+        assert_eq!(
+            options.adaptive_chunk().unwrap().max_ms(),
+            200 * adaptive::DEFAULT_MAX_CHUNK_FACTOR
+        );
+        // 遅延の上限を超えて延ばさない
+        options.max_latency_ms = Some(500);
+        assert_eq!(options.adaptive_chunk().unwrap().max_ms(), 500);
+        options.max_chunk_ms = Some(600);
+        assert!(error(&options).contains("--max-chunk-ms"));
+        options.max_chunk_ms = Some(100);
+        assert!(error(&options).contains("--max-chunk-ms"));
+        options.max_chunk_ms = Some(300);
+        options.validate().unwrap();
+        assert_eq!(options.adaptive_chunk().unwrap().max_ms(), 300);
+    }
+
+    #[test]
+    fn rejects_out_of_range_values() {
+        let mut options = options();
+        options.chunk_ms = Some(0);
+        assert!(error(&options).contains("チャンク長"));
+        options.chunk_ms = None;
+        options.sample_rate = Some(4000);
+        assert!(error(&options).contains("--sample-rate"));
+        options.sample_rate = Some(16000);
+        options.noise_width = 2.5;
+        assert!(error(&options).contains("--noise-width"));
+        options.noise_width = 1.0;
+        options.pan = -1.5;
+        assert!(error(&options).contains("--pan"));
+        options.pan = 0.0;
+        options.chaos.error_rate = 1.5;
+        assert!(error(&options).contains("--inject-error-rate"));
+    }
+
+    #[test]
+    fn checks_the_bleep_window_only_when_bleeping() {
+        let mut options = options();
+        options.bleep_window_ms = 50;
+        options.validate().unwrap();
+        options.bleep_words = vec!["秘密".to_string()];
+        assert!(error(&options).contains("--bleep-window"));
+    }
+
+    #[test]
+    fn batch_needs_the_server_engine() {
+        let mut options = options();
+        options.batch = Some(PathBuf::from("in"));
+        options.validate().unwrap();
+        options.engine = Engine::Local;
+        assert!(error(&options).contains("--batch"));
+    }
+
+    #[test]
+    fn low_power_uses_the_fast_resampler() {
+        let mut options = options();
+        options.resample_quality = ResampleQuality::Best;
+        assert_eq!(options.resample_quality(), ResampleQuality::Best);
+        options.low_power = true;
+        assert_eq!(options.resample_quality(), ResampleQuality::Fast);
+    }
+}
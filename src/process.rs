@@ -0,0 +1,1336 @@
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use tracing::{info, warn};
+
+use crate::arbiter::Arbiter;
+use crate::batch;
+use crate::bwf;
+use crate::chaos;
+use crate::client::{Capability, SpeakerTurn, VoiceConversionClient};
+use crate::config::Config;
+use crate::converter::Engine;
+use crate::cue::{format_time, CueSheet, TimeRange};
+use crate::diarize::{self, DiarizationSource};
+use crate::dsp::analysis::DEFAULT_VAD_THRESHOLD_DB;
+use crate::dsp::breath::BreathInserter;
+use crate::dsp::contour::PitchContour;
+use crate::dsp::gaps::GapKeeper;
+use crate::dsp::noise::NoiseMixer;
+use crate::dsp::pitch::{self, PitchShifter};
+use crate::dsp::rate::RateFluctuation;
+use crate::manifest::{Manifest, Timings};
+use crate::progress::TransferProgress;
+use crate::python;
+use crate::resample::ResampleQuality;
+use crate::retry::RetryPolicy;
+use crate::shutdown::{self, Interrupted};
+use crate::timecode::{self, MarkerLog, SyncOptions};
+use crate::video;
+use crate::watermark;
+use crate::wav::{self, BitDepth};
+
+/// 出力先を指定しなかったときの出力ファイル
+pub const DEFAULT_OUTPUT: &str = "audio/output/processed.wav";
+
+/// 区間に分けて変換するときに、つなぎ目で重ねる長さ（ミリ秒）
+const SEGMENT_OVERLAP_MS: usize = 30;
+
+/// 変換した範囲を元の音声に戻すときの、境界のクロスフェードの長さ（ミリ秒、`--splice`）
+const SPLICE_CROSSFADE_MS: usize = 10;
+
+/// ファイル変換の設定（`makebeliv process`）
+///
+/// `from_config` で設定ファイルの値から作り、必要な項目だけ書き換えて使います。
+#[derive(Debug, Clone)]
+pub struct ProcessOptions {
+    pub engine: Engine,
+    /// APIサーバー経由で変換する（falseならPythonを直接実行する。`Engine::Server` のときだけ）
+    pub use_api: bool,
+    pub api_url: String,
+    pub model: String,
+    /// `model` に期待するバージョン（Noneなら確かめない。`manifest::check_model_version`）
+    pub model_version: Option<String>,
+    /// 背景ノイズの種類（cafe, street, room かWAVファイル）
+    pub noise: String,
+    /// 背景ノイズの音量（0で無効）
+    pub noise_level: f32,
+    /// 句の切れ目に入れる息の音量（0で無効）
+    pub breath_level: f32,
+    pub breath_dir: Option<PathBuf>,
+    /// ピッチシフト（半音）
+    pub pitch: i32,
+    /// 句ごとのピッチの揺らぎ（±セント、0で無効）
+    pub pitch_contour: f32,
+    /// 話速の揺らぎ（±パーセント、0で無効）
+    pub rate_fluctuation: f32,
+    /// 出力のビット深度（Noneなら入力と同じ）
+    pub bit_depth: Option<BitDepth>,
+    pub resample_quality: ResampleQuality,
+    /// 失敗した変換リクエストの再試行（API経由のとき）
+    pub retry: RetryPolicy,
+    /// 送受信の進み具合の表示（API経由のとき。Noneなら表示しない）
+    pub progress: Option<TransferProgress>,
+    /// この話者の区間だけを変換し、ほかの話者はそのまま残す（Noneなら全体を変換する）
+    pub only_speaker: Option<String>,
+    pub diarization: DiarizationSource,
+    /// 話者の数の見込み
+    pub speakers: usize,
+    /// 区間ごとにモデル・ピッチ・ノイズを変えるキュー（Noneなら全体を同じ設定で変換する）
+    pub cues: Option<CueSheet>,
+    /// この時間範囲だけを変換して書き出す（Noneなら全体）
+    pub range: Option<TimeRange>,
+    /// 変換した範囲を元の音声の複製に戻して書き出す（`range` があるときだけ）
+    pub splice: bool,
+    /// 変換結果の句の間の長さを入力と同じに揃える
+    pub preserve_gaps: bool,
+    /// 出力に同期マーカー（BWFの開始時刻・タイムコードのサイドカー）を付ける
+    pub sync: Option<SyncOptions>,
+    /// 出力WAVに変換の設定を書いた bext チャンクを付ける
+    pub bwf: bool,
+    /// 揺らぎ・息・ノイズの乱数の種（Noneなら時刻から作る）
+    pub seed: Option<u64>,
+    /// 出力の横に再現用のマニフェスト（`<file>.makebeliv.json`）を書く
+    pub manifest: bool,
+    /// 変換したことを後で示せるよう、出力WAVに電子透かしを埋め込む
+    pub watermark: bool,
+    pub watermark_key: String,
+    /// 切り出す前の入力の形式（`range` の部分を一時ファイルにしたとき。ログには元の形式を出す）
+    source_spec: Option<hound::WavSpec>,
+}
+
+impl ProcessOptions {
+    /// 設定ファイルの値から作る（エンジンはサーバー、Pythonを直接実行する）
+    pub fn from_config(config: &Config) -> Self {
+        let conversion = &config.conversion;
+        Self {
+            engine: Engine::Server,
+            use_api: false,
+            api_url: config.server.api_url.clone(),
+            model: conversion.model.clone(),
+            model_version: conversion.model_version.clone(),
+            noise: conversion.noise.clone(),
+            noise_level: conversion.noise_level,
+            breath_level: conversion.breath_level,
+            breath_dir: conversion.breath_dir.clone(),
+            pitch: conversion.pitch,
+            pitch_contour: conversion.pitch_contour,
+            rate_fluctuation: conversion.rate_fluctuation,
+            bit_depth: None,
+            resample_quality: ResampleQuality::Best,
+            retry: config.server.retry.policy(),
+            progress: None,
+            only_speaker: None,
+            diarization: DiarizationSource::Auto,
+            speakers: diarize::DEFAULT_SPEAKERS,
+            cues: None,
+            range: None,
+            splice: false,
+            preserve_gaps: false,
+            sync: None,
+            bwf: false,
+            seed: None,
+            manifest: false,
+            watermark: conversion.watermark,
+            watermark_key: conversion
+                .watermark_key
+                .clone()
+                .unwrap_or_else(|| watermark::DEFAULT_KEY.to_string()),
+            source_spec: None,
+        }
+    }
+}
+
+/// 背景ノイズ（レベル0なら混ぜない）
+pub fn noise_mixer(noise: &str, level: f32) -> Result<Option<NoiseMixer>> {
+    if level <= 0.0 {
+        return Ok(None);
+    }
+    NoiseMixer::new(noise, level).map(Some)
+}
+
+/// 息の挿入（レベル0なら入れない）
+pub fn breath_inserter(level: f32, dir: Option<&Path>) -> Result<Option<BreathInserter>> {
+    if level <= 0.0 {
+        return Ok(None);
+    }
+    BreathInserter::new(level, dir).map(Some)
+}
+
+/// 句ごとのピッチの揺らぎ（0なら揺らさない）
+pub fn pitch_contour_stage(cents: f32) -> Result<Option<PitchContour>> {
+    anyhow::ensure!(
+        (0.0..=1200.0).contains(&cents),
+        "--pitch-contour は0〜1200セントで指定してください: {}",
+        cents
+    );
+    if cents > 0.0 {
+        pitch::ensure_available()?;
+    }
+    Ok((cents > 0.0).then(|| PitchContour::new(cents)))
+}
+
+/// 話速の揺らぎ（0なら揺らさない）
+pub fn rate_fluctuation_stage(percent: f32) -> Result<Option<RateFluctuation>> {
+    anyhow::ensure!(
+        (0.0..=20.0).contains(&percent),
+        "--rate-fluctuation は0〜20パーセントで指定してください: {}",
+        percent
+    );
+    Ok((percent > 0.0).then(|| RateFluctuation::new(percent)))
+}
+
+/// 変換後の音声に加える処理（話者の選択 → 話速 → 間の長さ → ピッチの揺らぎ → 息 → ノイズの順）
+///
+/// キューがあるときのノイズは、区間ごとに変換した時点で混ぜます。
+struct PostProcess {
+    speaker: Option<SpeakerSelection>,
+    rate: Option<RateFluctuation>,
+    gaps: Option<GapKeeper>,
+    contour: Option<PitchContour>,
+    breath: Option<BreathInserter>,
+    noise: Option<NoiseMixer>,
+}
+
+impl PostProcess {
+    fn new(options: &ProcessOptions) -> Result<Self> {
+        let mut post = Self {
+            speaker: None,
+            gaps: None,
+            rate: rate_fluctuation_stage(options.rate_fluctuation)?,
+            contour: pitch_contour_stage(options.pitch_contour)?,
+            breath: breath_inserter(options.breath_level, options.breath_dir.as_deref())?,
+            noise: match options.cues {
+                Some(_) => None,
+                None => noise_mixer(&options.noise, options.noise_level)?,
+            },
+        };
+        // 処理ごとに種をずらし、同じ乱数の列を使い回さない
+        if let Some(seed) = options.seed {
+            post.rate = post.rate.map(|rate| rate.with_seed(seed));
+            post.contour = post
+                .contour
+                .map(|contour| contour.with_seed(seed.wrapping_add(1)));
+            post.breath = post
+                .breath
+                .map(|breath| breath.with_seed(seed.wrapping_add(2)));
+            post.noise = post
+                .noise
+                .map(|noise| noise.with_seed(seed.wrapping_add(3)));
+        }
+        Ok(post)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.speaker.is_none()
+            && self.rate.is_none()
+            && self.gaps.is_none()
+            && self.contour.is_none()
+            && self.breath.is_none()
+            && self.noise.is_none()
+    }
+
+    fn apply(&mut self, mut samples: Vec<f32>, sample_rate: u32, channels: u16) -> Vec<f32> {
+        if let Some(selection) = &self.speaker {
+            samples = diarize::splice(
+                &selection.original,
+                &samples,
+                sample_rate,
+                channels,
+                &selection.spans,
+            );
+        }
+        if let Some(rate) = self.rate.as_mut() {
+            samples = rate.apply_all(&samples, sample_rate, channels);
+        }
+        if let Some(gaps) = &self.gaps {
+            samples = gaps.apply_all(&samples, sample_rate, channels);
+        }
+        if let Some(contour) = self.contour.as_mut() {
+            samples = contour.apply_all(&samples, sample_rate, channels);
+        }
+        if let Some(breath) = self.breath.as_mut() {
+            breath.insert_all(&mut samples, sample_rate, channels);
+        }
+        if let Some(mixer) = self.noise.as_mut() {
+            mixer.mix_all(&mut samples, sample_rate, channels);
+        }
+        samples
+    }
+}
+
+/// 選んだ話者の区間（`--only-speaker`）
+struct SpeakerSelection {
+    /// 区間の外に使う元の音声
+    original: Vec<f32>,
+    /// 話者の区間（秒）
+    spans: Vec<(f64, f64)>,
+}
+
+/// `--only-speaker` の話者を探し、その区間だけに変換結果を使うよう `post` に設定する
+///
+/// サーバーで区別するときは `client` を使います（Noneならローカルで推定する）。
+async fn select_speaker(
+    post: &mut PostProcess,
+    input: &Path,
+    client: Option<&VoiceConversionClient>,
+    options: &ProcessOptions,
+) -> Result<()> {
+    let Some(speaker) = &options.only_speaker else {
+        return Ok(());
+    };
+    let server = match (options.diarization, client) {
+        (DiarizationSource::Local, _) => None,
+        (DiarizationSource::Server, Some(client)) => Some(client),
+        (DiarizationSource::Server, None) => {
+            anyhow::bail!("サーバーで話者を区別するには --use-api を指定してください")
+        }
+        (DiarizationSource::Auto, client) => {
+            client.filter(|client| client.supports(Capability::Diarize))
+        }
+    };
+
+    let (samples, spec) = wav::read_file(input)?;
+    let turns = match server {
+        Some(client) => {
+            info!("  話者の区別: サーバー");
+            let audio = tokio::fs::read(input)
+                .await
+                .with_context(|| format!("WAVファイルの読み込みエラー: {}", input.display()))?;
+            client.diarize(audio, options.speakers).await?
+        }
+        None => {
+            info!("  話者の区別: ローカル（音量とピッチによる推定）");
+            let (samples, speakers) = (samples.clone(), options.speakers);
+            tokio::task::spawn_blocking(move || {
+                diarize::local_turns(&samples, spec.sample_rate, spec.channels, speakers)
+            })
+            .await
+            .context("話者の推定の実行エラー")??
+        }
+    };
+
+    post.speaker = Some(SpeakerSelection {
+        spans: speaker_spans(&turns, speaker)?,
+        original: samples,
+    });
+    Ok(())
+}
+
+/// `--preserve-gaps` なら入力の句の間を測り、変換結果の間を揃えるよう `post` に設定する
+fn keep_gaps(post: &mut PostProcess, samples: &[f32], spec: &hound::WavSpec) {
+    let keeper = GapKeeper::new(
+        samples,
+        spec.sample_rate,
+        spec.channels,
+        DEFAULT_VAD_THRESHOLD_DB,
+    );
+    info!("  間の保持: 入力の句の間 {}か所", keeper.len());
+    post.gaps = Some(keeper);
+}
+
+/// 検出した話者を表示し、`speaker` の区間を返す（見つからなければエラー）
+fn speaker_spans(turns: &[SpeakerTurn], speaker: &str) -> Result<Vec<(f64, f64)>> {
+    let totals = diarize::speaker_totals(turns);
+    for (name, seconds, count) in &totals {
+        info!("    {}: {:.1}秒（{}区間）", name, seconds, count);
+    }
+    let spans: Vec<(f64, f64)> = turns
+        .iter()
+        .filter(|turn| turn.speaker.eq_ignore_ascii_case(speaker))
+        .map(|turn| (turn.start, turn.end))
+        .collect();
+    if spans.is_empty() {
+        anyhow::bail!(
+            "話者 {} が見つかりません（検出した話者: {}）",
+            speaker,
+            totals
+                .iter()
+                .map(|(name, _, _)| name.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    }
+    info!(
+        "  変換する話者: {}（ほかの話者はそのまま残します）",
+        speaker
+    );
+    Ok(spans)
+}
+
+/// 1つのWAVファイルを変換して書き出す
+///
+/// Ctrl+Cで中断したときは `Interrupted` を返します（Pythonの子プロセスも止まります）。
+pub async fn process_file(input: &Path, output: &Path, options: &ProcessOptions) -> Result<()> {
+    if !input.exists() {
+        anyhow::bail!("入力ファイルが見つかりません: {}", input.display());
+    }
+
+    // 再現できるよう、乱数の種を決めてから変換する
+    let seeded;
+    let options = match options.seed {
+        None if options.manifest => {
+            seeded = ProcessOptions {
+                seed: Some(chaos::time_seed()),
+                ..options.clone()
+            };
+            &seeded
+        }
+        _ => options,
+    };
+    let started = chrono::Local::now();
+    let timer = Instant::now();
+
+    if video::is_video(input) {
+        process_video(input, output, options).await?;
+    } else {
+        process_audio(input, output, options).await?;
+    }
+    // bext チャンクはファイルを書き直すと消えるため、透かしを先に入れる
+    if options.watermark {
+        if batch::is_wav(output) {
+            // 範囲だけを変換して元の音声に戻したときは、変換した範囲にだけ入れる
+            let range = options.range.filter(|_| options.splice);
+            watermark::embed_file(output, &options.watermark_key, range)?;
+        } else {
+            warn!("⚠ 動画の出力には電子透かしを埋め込みません");
+        }
+    }
+    if options.bwf {
+        write_bext(input, output, options)?;
+    }
+    if let Some(sync) = &options.sync {
+        mark_sync(input, output, options, sync)?;
+    }
+    if options.manifest {
+        let timings = Timings::new(started, timer.elapsed().as_secs_f64());
+        Manifest::collect(input, output, options, timings)
+            .await?
+            .write(output)?;
+    }
+    Ok(())
+}
+
+/// 出力の先頭の時刻（その日の0時からの秒。入力がBWFでなければNone）
+fn start_seconds(input: &Path, options: &ProcessOptions) -> Result<Option<f64>> {
+    let reference = match batch::is_wav(input) {
+        true => bwf::read(input)?,
+        false => None,
+    };
+    let Some(bext) = reference else {
+        return Ok(None);
+    };
+    let start = bext.time_reference as f64 / wav::read_spec(input)?.sample_rate.max(1) as f64;
+    // 範囲だけを書き出したときは、範囲の始まりの時刻から
+    Ok(Some(match options.range {
+        Some(range) if !options.splice => start + range.start,
+        _ => start,
+    }))
+}
+
+/// 出力WAVに bext チャンク（作成日時・説明・コーディングヒストリー）を書く
+///
+/// 入力がBWFなら、その開始時刻とコーディングヒストリーを引き継ぎ、変換の行を足します。
+fn write_bext(input: &Path, output: &Path, options: &ProcessOptions) -> Result<()> {
+    if !batch::is_wav(output) {
+        warn!("⚠ 動画の出力には bext チャンクを付けません");
+        return Ok(());
+    }
+    let source = match batch::is_wav(input) {
+        true => bwf::read(input)?,
+        false => None,
+    };
+    let spec = wav::read_spec(output)?;
+    let settings = conversion_settings(options);
+    let mut coding_history = source.map(|bext| bext.coding_history).unwrap_or_default();
+    coding_history.push_str(&bwf::coding_history_line(
+        &spec,
+        &format!("makebeliv {}; {}", env!("CARGO_PKG_VERSION"), settings),
+    ));
+    let now = chrono::Local::now();
+    let bext = bwf::Bext {
+        description: format!("Converted by makebeliv ({})", settings),
+        originator: "makebeliv".to_string(),
+        originator_reference: String::new(),
+        origination_date: now.format("%Y-%m-%d").to_string(),
+        origination_time: now.format("%H:%M:%S").to_string(),
+        time_reference: start_seconds(input, options)?.map_or(0, |seconds| {
+            (seconds * spec.sample_rate as f64).round() as u64
+        }),
+        coding_history,
+    };
+    bwf::write(output, &bext)?;
+    info!("  🏷 bext チャンクに変換の設定を書きました: {}", settings);
+    Ok(())
+}
+
+/// 変換に使った設定の要約（bext の説明・コーディングヒストリー用）
+fn conversion_settings(options: &ProcessOptions) -> String {
+    let mut settings = vec![format!("engine={}", options.engine)];
+    if options.engine == Engine::Server {
+        match options.cues {
+            Some(_) => settings.push("model=cues".to_string()),
+            None => settings.push(format!("model={}", options.model)),
+        }
+    }
+    settings.push(format!("pitch={:+}", options.pitch));
+    if options.noise_level > 0.0 {
+        settings.push(format!("noise={}@{}", options.noise, options.noise_level));
+    }
+    if options.breath_level > 0.0 {
+        settings.push(format!("breath={}", options.breath_level));
+    }
+    if options.pitch_contour > 0.0 {
+        settings.push(format!("pitch_contour={}", options.pitch_contour));
+    }
+    if options.rate_fluctuation > 0.0 {
+        settings.push(format!("rate_fluctuation={}", options.rate_fluctuation));
+    }
+    if let Some(speaker) = &options.only_speaker {
+        settings.push(format!("speaker={}", speaker));
+    }
+    if let Some(range) = options.range {
+        settings.push(format!(
+            "range={}-{}",
+            format_time(range.start),
+            format_time(range.end)
+        ));
+    }
+    if options.watermark {
+        settings.push("watermark".to_string());
+    }
+    settings.join("; ")
+}
+
+/// 出力に同期マーカーを付ける（入力がBWFなら、その開始時刻を引き継ぐ）
+fn mark_sync(
+    input: &Path,
+    output: &Path,
+    options: &ProcessOptions,
+    sync: &SyncOptions,
+) -> Result<()> {
+    if !batch::is_wav(output) {
+        warn!("⚠ 動画の出力には同期マーカーを付けません（映像のタイムコードを使ってください）");
+        return Ok(());
+    }
+    let start = start_seconds(input, options)?.unwrap_or_else(|| {
+        info!("  入力にBWFの開始時刻がないため、タイムコードは00:00:00:00から数えます");
+        0.0
+    });
+    let log = MarkerLog::from_start(
+        start,
+        wav::read_frames(output)?,
+        wav::read_spec(output)?.sample_rate,
+        sync.interval_secs,
+    );
+    timecode::apply(output, &log, sync)
+}
+
+/// 出力先を指定しなかったときの出力ファイル（動画なら同じ形式の動画）
+pub fn default_output(input: &Path) -> PathBuf {
+    let output = PathBuf::from(DEFAULT_OUTPUT);
+    match input.extension() {
+        Some(ext) if video::is_video(input) => output.with_extension(ext),
+        _ => output,
+    }
+}
+
+async fn process_audio(input: &Path, output: &Path, options: &ProcessOptions) -> Result<()> {
+    match options.range {
+        Some(range) => process_range(input, output, range, options).await,
+        None => process_whole(input, output, options).await,
+    }
+}
+
+/// 動画の音声を取り出して変換し、映像はそのままに音声だけ差し替えた動画を書き出す
+///
+/// 出力がWAVなら、変換した音声だけを書き出します。
+async fn process_video(input: &Path, output: &Path, options: &ProcessOptions) -> Result<()> {
+    video::ensure_ffmpeg()?;
+    let remux = !batch::is_wav(output);
+    anyhow::ensure!(
+        !remux || video::is_video(output),
+        "動画を入力にしたときの出力は動画（{}）かWAVにしてください: {}",
+        video::VIDEO_EXTENSIONS.join(", "),
+        output.display()
+    );
+    anyhow::ensure!(
+        !remux || options.range.is_none() || options.splice,
+        "--range で動画に戻すには --splice も指定してください（範囲の音声だけなら -o に .wav を指定）"
+    );
+
+    let extracted = sibling(output, ".audio.wav");
+    let result = async {
+        video::extract_audio(input, &extracted).await?;
+        if !remux {
+            return process_audio(&extracted, output, options).await;
+        }
+        let converted = sibling(output, ".audio.out.wav");
+        let result = async {
+            process_audio(&extracted, &converted, options).await?;
+            video::remux(input, &converted, output).await
+        }
+        .await;
+        std::fs::remove_file(&converted).ok();
+        result
+    }
+    .await;
+    std::fs::remove_file(&extracted).ok();
+    result?;
+    if remux {
+        info!("✅ 動画を書き出しました: {}", output.display());
+    }
+    Ok(())
+}
+
+/// 入力の `range` の部分を一時ファイルに切り出して変換する
+///
+/// 範囲はサンプル単位で切り出し、出力のビット深度は（指定がなければ）元の入力に合わせます。
+/// `splice` なら、変換した範囲を元の音声に戻したファイル全体を書き出します。
+async fn process_range(
+    input: &Path,
+    output: &Path,
+    range: TimeRange,
+    options: &ProcessOptions,
+) -> Result<()> {
+    anyhow::ensure!(
+        options.cues.is_none(),
+        "--range と --cues は同時に使えません"
+    );
+    let (samples, spec) = wav::read_file(input)?;
+    let ch = spec.channels.max(1) as usize;
+    let (start, end) = range.frames(samples.len() / ch, spec.sample_rate)?;
+    info!("  範囲: {}（{}〜{}フレーム）", range, start, end);
+
+    let excerpt = sibling(output, ".range.wav");
+    wav::write_file(
+        &excerpt,
+        &samples[start * ch..end * ch],
+        spec.sample_rate,
+        spec.channels,
+        BitDepth::Float32,
+    )?;
+    let mut options = options.clone();
+    options.range = None;
+    options.source_spec = Some(spec);
+    options.bit_depth = Some(
+        options
+            .bit_depth
+            .unwrap_or_else(|| BitDepth::from_spec(&spec)),
+    );
+    if !options.splice {
+        let result = process_whole(&excerpt, output, &options).await;
+        std::fs::remove_file(&excerpt).ok();
+        return result;
+    }
+
+    let converted = sibling(output, ".range.out.wav");
+    let result = process_whole(&excerpt, &converted, &options).await;
+    std::fs::remove_file(&excerpt).ok();
+    let result = result.and_then(|()| {
+        let (range_samples, range_spec) = wav::read_file(&converted)?;
+        let range_samples = wav::conform(
+            range_samples,
+            &range_spec,
+            spec.sample_rate,
+            spec.channels,
+            options.resample_quality,
+        )?;
+        let spliced = splice(samples, &range_samples, start, end, spec.sample_rate, ch);
+        info!(
+            "  変換した範囲を元の音声に戻しました（境界のクロスフェード {}ms）",
+            SPLICE_CROSSFADE_MS
+        );
+        wav::write_file(
+            output,
+            &spliced,
+            spec.sample_rate,
+            spec.channels,
+            options
+                .bit_depth
+                .unwrap_or_else(|| BitDepth::from_spec(&spec)),
+        )
+    });
+    std::fs::remove_file(&converted).ok();
+    result?;
+    info!("✅ 差し替え完了: {}", output.display());
+    Ok(())
+}
+
+/// 元の音声の `start`〜`end` フレームを変換結果に差し替え、境界を短くクロスフェードする
+///
+/// クロスフェードは範囲の内側で行うため、範囲の外の音声は元のままです。
+fn splice(
+    mut original: Vec<f32>,
+    converted: &[f32],
+    start: usize,
+    end: usize,
+    sample_rate: u32,
+    ch: usize,
+) -> Vec<f32> {
+    let frames = end - start;
+    let fade = (sample_rate as usize * SPLICE_CROSSFADE_MS / 1000).min(frames / 2);
+    for i in 0..frames {
+        // 変換結果が短ければ、足りない分は元の音声のまま
+        let Some(frame) = converted.get(i * ch..(i + 1) * ch) else {
+            break;
+        };
+        let t = if i < fade {
+            (i + 1) as f32 / (fade + 1) as f32
+        } else if frames - i <= fade {
+            (frames - i) as f32 / (fade + 1) as f32
+        } else {
+            1.0
+        };
+        for (c, &sample) in frame.iter().enumerate() {
+            let index = (start + i) * ch + c;
+            original[index] = original[index] * (1.0 - t) + sample * t;
+        }
+    }
+    original
+}
+
+/// 入力全体を、エンジンに合わせた方法で変換する
+async fn process_whole(input: &Path, output: &Path, options: &ProcessOptions) -> Result<()> {
+    match options.engine {
+        // CPUで処理するため、並列に変換するときにランタイムを止めないよう別スレッドで動かす
+        Engine::Local => {
+            let (input, output, options) =
+                (input.to_path_buf(), output.to_path_buf(), options.clone());
+            tokio::task::spawn_blocking(move || process_local(&input, &output, &options))
+                .await
+                .context("ローカル変換の実行エラー")?
+        }
+        Engine::Server if options.use_api => process_via_api(input, output, options).await,
+        Engine::Server => process_direct(input, output, options).await,
+    }
+}
+
+/// 入力の形式を出す（範囲を切り出した一時ファイルなら元のファイルの形式）
+fn log_input_format(spec: &hound::WavSpec, options: &ProcessOptions) {
+    let spec = options.source_spec.as_ref().unwrap_or(spec);
+    info!(
+        "  入力形式: {}Hz, {}ch, {}bit {:?}",
+        spec.sample_rate, spec.channels, spec.bits_per_sample, spec.sample_format
+    );
+}
+
+fn log_settings(input: &Path, output: &Path, options: &ProcessOptions) {
+    info!("設定:");
+    info!("  入力: {}", input.display());
+    info!("  出力: {}", output.display());
+    if options.engine == Engine::Server {
+        info!("  モデル: {}", options.model);
+    }
+    info!("  ノイズ: {} ({})", options.noise, options.noise_level);
+    if options.breath_level > 0.0 {
+        info!("  息: {}", options.breath_level);
+    }
+    info!("  ピッチ: {:+} semitones", options.pitch);
+    if options.pitch_contour > 0.0 {
+        info!("  ピッチの揺らぎ: ±{} cents", options.pitch_contour);
+    }
+    if options.rate_fluctuation > 0.0 {
+        info!("  話速の揺らぎ: ±{}%", options.rate_fluctuation);
+    }
+    if let Some(bit_depth) = options.bit_depth {
+        info!("  ビット深度: {}", bit_depth);
+    }
+    if let Some(cues) = &options.cues {
+        info!(
+            "  キュー: {}個（キューのない部分は上の設定）",
+            cues.cues().len()
+        );
+    }
+}
+
+/// キューで分けた1区間（`--cues`）
+struct CueSection {
+    /// 区間の始まり（フレーム）
+    start: usize,
+    /// 次の区間と重ねる分を含めて送る範囲の終わり（フレーム）
+    sent_end: usize,
+    /// キューの上書きを反映した設定
+    options: ProcessOptions,
+}
+
+impl CueSection {
+    /// 送る音声
+    fn samples<'a>(&self, samples: &'a [f32], channels: u16) -> &'a [f32] {
+        let ch = channels.max(1) as usize;
+        &samples[self.start * ch..self.sent_end * ch]
+    }
+}
+
+/// 音声をキューの区間とその間に分け、区間ごとの設定を作る
+fn cue_sections(
+    cues: &CueSheet,
+    frames: usize,
+    sample_rate: u32,
+    options: &ProcessOptions,
+) -> Vec<CueSection> {
+    let overlap = sample_rate as usize * SEGMENT_OVERLAP_MS / 1000;
+    let seconds = |frame: usize| format_time(frame as f64 / sample_rate as f64);
+    cues.spans(frames, sample_rate)
+        .into_iter()
+        .map(|span| {
+            let mut section = options.clone();
+            section.cues = None;
+            if let Some(cue) = span.cue {
+                if let Some(model) = &cue.model {
+                    section.model = model.clone();
+                }
+                if let Some(pitch) = cue.pitch {
+                    section.pitch = pitch;
+                }
+                if let Some(noise) = &cue.noise {
+                    section.noise = noise.clone();
+                }
+                if let Some(level) = cue.noise_level {
+                    section.noise_level = level;
+                }
+            }
+            let label = match span.cue {
+                Some(cue) => cue
+                    .label
+                    .as_ref()
+                    .map(|label| format!(" {}", label))
+                    .unwrap_or_default(),
+                None => " （キューなし）".to_string(),
+            };
+            info!(
+                "  {}〜{}{}: モデル {} / ピッチ {:+} / ノイズ {} ({})",
+                seconds(span.start),
+                seconds(span.end),
+                label,
+                section.model,
+                section.pitch,
+                section.noise,
+                section.noise_level
+            );
+            CueSection {
+                start: span.start,
+                sent_end: (span.end + overlap).min(frames),
+                options: section,
+            }
+        })
+        .collect()
+}
+
+/// 区間の変換結果に区間の背景ノイズを混ぜ、前の区間とつなぐ
+fn join_section(
+    output: &mut Vec<f32>,
+    mut converted: Vec<f32>,
+    section: &CueSection,
+    sample_rate: u32,
+    channels: u16,
+) -> Result<()> {
+    let ch = channels.max(1) as usize;
+    converted.resize((section.sent_end - section.start) * ch, 0.0);
+    if let Some(mixer) = noise_mixer(&section.options.noise, section.options.noise_level)? {
+        let mut mixer = match section.options.seed {
+            Some(seed) => mixer.with_seed(seed.wrapping_add(3).wrapping_add(section.start as u64)),
+            None => mixer,
+        };
+        mixer.mix_all(&mut converted, sample_rate, channels);
+    }
+    join_overlapped(output, &converted, section.start, ch);
+    Ok(())
+}
+
+/// `start` フレームから始まる変換結果を、前の区間の末尾と重なる部分をクロスフェードしてつなぐ
+fn join_overlapped(output: &mut Vec<f32>, converted: &[f32], start: usize, ch: usize) {
+    let shared = (output.len() / ch).saturating_sub(start);
+    for i in 0..shared {
+        let t = (i + 1) as f32 / (shared + 1) as f32;
+        for c in 0..ch {
+            let index = (start + i) * ch + c;
+            output[index] = output[index] * (1.0 - t) + converted[i * ch + c] * t;
+        }
+    }
+    output.extend_from_slice(&converted[shared * ch..]);
+}
+
+async fn process_direct(input: &Path, output: &Path, options: &ProcessOptions) -> Result<()> {
+    info!("🎙️ 音声ファイル処理モード（直接実行）");
+    log_settings(input, output, options);
+    let mut post = PostProcess::new(options)?;
+
+    // 壊れた入力はPythonを起動する前に弾く
+    let (input_samples, input_spec) = wav::read_file(input)?;
+    log_input_format(&input_spec, options);
+    if options.preserve_gaps {
+        keep_gaps(&mut post, &input_samples, &input_spec);
+    }
+    drop(input_samples);
+    select_speaker(&mut post, input, None, options).await?;
+
+    if let Some(cues) = &options.cues {
+        return process_direct_cued(input, output, options, cues, &mut post).await;
+    }
+
+    run_file_processor(input, output, options).await?;
+
+    // Python側の出力を検証し、入力と形式が違えば揃え、話速・ピッチの揺らぎと息・ノイズを加えて書き直す
+    let (converted, converted_spec) = wav::read_file(output)?;
+    if (converted_spec.sample_rate, converted_spec.channels)
+        != (input_spec.sample_rate, input_spec.channels)
+        || !post.is_empty()
+    {
+        let converted = wav::conform(
+            converted,
+            &converted_spec,
+            input_spec.sample_rate,
+            input_spec.channels,
+            options.resample_quality,
+        )?;
+        let converted = post.apply(converted, input_spec.sample_rate, input_spec.channels);
+        wav::write_file(
+            output,
+            &converted,
+            input_spec.sample_rate,
+            input_spec.channels,
+            options
+                .bit_depth
+                .unwrap_or_else(|| BitDepth::from_spec(&converted_spec)),
+        )?;
+    }
+
+    info!("✅ 処理完了: {}", output.display());
+
+    Ok(())
+}
+
+/// キューの区間ごとに一時ファイルへ書き出してPythonで変換し、つないで書き出す
+///
+/// 区間ごとにPythonを起動し直すため、区間が多いと時間がかかります。
+async fn process_direct_cued(
+    input: &Path,
+    output: &Path,
+    options: &ProcessOptions,
+    cues: &CueSheet,
+    post: &mut PostProcess,
+) -> Result<()> {
+    let (samples, spec) = wav::read_file(input)?;
+    let frames = samples.len() / spec.channels.max(1) as usize;
+    let mut converted = Vec::with_capacity(samples.len());
+    for (i, section) in cue_sections(cues, frames, spec.sample_rate, options)
+        .iter()
+        .enumerate()
+    {
+        let section_input = sibling(output, &format!(".cue{}.in.wav", i));
+        let section_output = sibling(output, &format!(".cue{}.out.wav", i));
+        let result = async {
+            wav::write_file(
+                &section_input,
+                section.samples(&samples, spec.channels),
+                spec.sample_rate,
+                spec.channels,
+                BitDepth::Float32,
+            )?;
+            run_file_processor(&section_input, &section_output, &section.options).await?;
+            let (section_converted, section_spec) = wav::read_file(&section_output)?;
+            wav::conform(
+                section_converted,
+                &section_spec,
+                spec.sample_rate,
+                spec.channels,
+                options.resample_quality,
+            )
+        }
+        .await;
+        std::fs::remove_file(&section_input).ok();
+        std::fs::remove_file(&section_output).ok();
+        join_section(
+            &mut converted,
+            result?,
+            section,
+            spec.sample_rate,
+            spec.channels,
+        )?;
+    }
+
+    let converted = post.apply(converted, spec.sample_rate, spec.channels);
+    wav::write_file(
+        output,
+        &converted,
+        spec.sample_rate,
+        spec.channels,
+        options
+            .bit_depth
+            .unwrap_or_else(|| BitDepth::from_spec(&spec)),
+    )?;
+
+    info!("✅ 処理完了: {}", output.display());
+
+    Ok(())
+}
+
+/// Pythonのファイル変換スクリプトを実行する（ノイズはこちらで混ぜるため付けさせない）
+async fn run_file_processor(input: &Path, output: &Path, options: &ProcessOptions) -> Result<()> {
+    // 作業ディレクトリが変わるためパスは絶対パスで渡す
+    let root = python::project_root()?;
+    let mut command = python::uv(&root);
+    command
+        .args(["run", "python"])
+        .arg(python::script(&root, "file_processor.py"))
+        .arg(python::absolute(input)?)
+        .arg("--output")
+        .arg(python::absolute(output)?)
+        .args(["--model", &options.model, "--noise", &options.noise])
+        // ノイズはこちらで混ぜる
+        .arg("--noise-level=0")
+        .arg(format!("--pitch={}", options.pitch));
+    if let Some(bit_depth) = options.bit_depth {
+        command.args(["--bit-depth", &bit_depth.to_string()]);
+    }
+    let status = shutdown::run_child(command)
+        .await
+        .context("Pythonスクリプトの実行に失敗")?;
+
+    if !status.success() {
+        anyhow::bail!("音声処理に失敗しました");
+    }
+    if !output.exists() {
+        anyhow::bail!("出力ファイルが作成されませんでした: {}", output.display());
+    }
+    Ok(())
+}
+
+fn process_local(input: &Path, output: &Path, options: &ProcessOptions) -> Result<()> {
+    pitch::ensure_available()?;
+    info!("🎙️ 音声ファイル処理モード（ローカルDSP）");
+    log_settings(input, output, options);
+    warn!("ローカルエンジンはピッチシフトのみです（モデルによる声の変換は行いません）");
+    let mut post = PostProcess::new(options)?;
+
+    let (samples, spec) = wav::read_file(input)?;
+    let bit_depth = options
+        .bit_depth
+        .unwrap_or_else(|| BitDepth::from_spec(&spec));
+    log_input_format(&spec, options);
+    if let Some(speaker) = &options.only_speaker {
+        anyhow::ensure!(
+            options.diarization != DiarizationSource::Server,
+            "サーバーで話者を区別するには --engine server --use-api を指定してください"
+        );
+        info!("  話者の区別: ローカル（音量とピッチによる推定）");
+        let turns =
+            diarize::local_turns(&samples, spec.sample_rate, spec.channels, options.speakers)?;
+        post.speaker = Some(SpeakerSelection {
+            spans: speaker_spans(&turns, speaker)?,
+            original: samples.clone(),
+        });
+    }
+    if options.preserve_gaps {
+        keep_gaps(&mut post, &samples, &spec);
+    }
+
+    let converted = match &options.cues {
+        Some(cues) => {
+            if cues.cues().iter().any(|cue| cue.model.is_some()) {
+                warn!("ローカルエンジンではキューの model は使われません");
+            }
+            let frames = samples.len() / spec.channels.max(1) as usize;
+            let mut converted = Vec::with_capacity(samples.len());
+            for section in cue_sections(cues, frames, spec.sample_rate, options) {
+                let shifted = PitchShifter::new(section.options.pitch as f32).shift_all(
+                    section.samples(&samples, spec.channels),
+                    spec.sample_rate,
+                    spec.channels,
+                );
+                join_section(
+                    &mut converted,
+                    shifted,
+                    &section,
+                    spec.sample_rate,
+                    spec.channels,
+                )?;
+            }
+            converted
+        }
+        None => PitchShifter::new(options.pitch as f32).shift_all(
+            &samples,
+            spec.sample_rate,
+            spec.channels,
+        ),
+    };
+    let converted = post.apply(converted, spec.sample_rate, spec.channels);
+    wav::write_file(
+        output,
+        &converted,
+        spec.sample_rate,
+        spec.channels,
+        bit_depth,
+    )?;
+
+    info!("✅ 処理完了: {}", output.display());
+
+    Ok(())
+}
+
+async fn process_via_api(input: &Path, output: &Path, options: &ProcessOptions) -> Result<()> {
+    info!("🎙️ 音声ファイル処理モード（API経由）");
+    log_settings(input, output, options);
+    info!("  APIサーバー: {}", options.api_url);
+
+    // APIクライアント作成
+    let client = VoiceConversionClient::new(options.api_url.clone())?
+        .with_retry(options.retry)
+        .with_progress(options.progress.clone());
+
+    // サーバー状態確認
+    match client.check_status().await {
+        Ok(status) => {
+            info!("✓ サーバー接続成功: {} ({})", status.status, status.device);
+            status.negotiate()?;
+            client.probe_capabilities(&status).await;
+        }
+        Err(e) => {
+            warn!("⚠ サーバー接続エラー: {}", e);
+            println!("\nAPIサーバーが起動していない可能性があります。");
+            println!("以下のコマンドでサーバーを起動してください:");
+            println!("  makebeliv server");
+            println!("サーバーなしでピッチだけ変えるには --engine local を指定してください。");
+            return Err(e);
+        }
+    }
+
+    process_with_client(&client, input, output, options).await
+}
+
+/// 接続済みのクライアントで1つのWAVファイルを変換して書き出す
+///
+/// クライアントに調停（[`Arbiter`]）が付いていれば、同じクライアントで行っている
+/// リアルタイム変換の邪魔にならないよう、音声を短い区間に分けて送ります。
+pub async fn process_with_client(
+    client: &VoiceConversionClient,
+    input: &Path,
+    output: &Path,
+    options: &ProcessOptions,
+) -> Result<()> {
+    let mut post = PostProcess::new(options)?;
+
+    let spec = wav::read_spec(input)?;
+    let bit_depth = options
+        .bit_depth
+        .unwrap_or_else(|| BitDepth::from_spec(&spec));
+    log_input_format(&spec, options);
+    info!("  出力ビット深度: {}", bit_depth);
+    if options.preserve_gaps {
+        let (samples, spec) = wav::read_file(input)?;
+        keep_gaps(&mut post, &samples, &spec);
+    }
+    select_speaker(&mut post, input, Some(client), options).await?;
+
+    // 音声変換（ノイズはこちらで混ぜるため、サーバーには付けさせない）
+    match (client.arbiter(), &options.cues) {
+        (arbiter, Some(cues)) => {
+            let (samples, spec) = wav::read_file(input)?;
+            let converted = tokio::select! {
+                converted = convert_cued(client, arbiter.map(|a| a.as_ref()), cues, &samples, spec.sample_rate, spec.channels, options) => converted?,
+                _ = shutdown::ctrl_c() => return Err(Interrupted.into()),
+            };
+            let converted = post.apply(converted, spec.sample_rate, spec.channels);
+            wav::write_file(
+                output,
+                &converted,
+                spec.sample_rate,
+                spec.channels,
+                bit_depth,
+            )?;
+        }
+        (Some(arbiter), None) => {
+            // 区間に分けて送るため、入力を読み込む（24bit・32bit floatも含め、送信は32bit floatに統一）
+            let (samples, spec) = wav::read_file(input)?;
+            let converted = tokio::select! {
+                converted = convert_segments(client, arbiter, &samples, spec.sample_rate, spec.channels, options) => converted?,
+                _ = shutdown::ctrl_c() => return Err(Interrupted.into()),
+            };
+            let converted = post.apply(converted, spec.sample_rate, spec.channels);
+            wav::write_file(
+                output,
+                &converted,
+                spec.sample_rate,
+                spec.channels,
+                bit_depth,
+            )?;
+        }
+        (None, None) => {
+            let received = sibling(output, ".received");
+            tokio::select! {
+                result = convert_streaming(client, input, output, &received, &spec, bit_depth, &mut post, options) => {
+                    std::fs::remove_file(&received).ok();
+                    result?
+                }
+                _ = shutdown::ctrl_c() => {
+                    // 受信途中のファイルを残さない
+                    std::fs::remove_file(&received).ok();
+                    std::fs::remove_file(sibling(&received, ".part")).ok();
+                    return Err(Interrupted.into());
+                }
+            }
+        }
+    }
+
+    info!("✅ 処理完了: {}", output.display());
+
+    Ok(())
+}
+
+/// ファイルのまま送受信して変換する（音声全体をメモリに載せない）
+///
+/// 変換結果を `received` に受け取り、出力と同じ形式で後処理もなければそのまま出力にします。
+/// 形式が違うときだけ読み込んで、入力と同じレート・チャンネル数・ビット深度に揃えます。
+#[allow(clippy::too_many_arguments)]
+async fn convert_streaming(
+    client: &VoiceConversionClient,
+    input: &Path,
+    output: &Path,
+    received: &Path,
+    spec: &hound::WavSpec,
+    bit_depth: BitDepth,
+    post: &mut PostProcess,
+    options: &ProcessOptions,
+) -> Result<()> {
+    if let Some(parent) = output.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent).context("出力ディレクトリの作成エラー")?;
+    }
+    client
+        .convert_file(
+            input,
+            received,
+            &options.model,
+            options.pitch,
+            &options.noise,
+            0.0,
+        )
+        .await?;
+
+    let received_spec = wav::read_spec(received).context("サーバーの応答を読み込めません")?;
+    if post.is_empty() && received_spec == bit_depth.spec(spec.sample_rate, spec.channels) {
+        return std::fs::rename(received, output)
+            .with_context(|| format!("WAVファイルの書き込みエラー: {}", output.display()));
+    }
+
+    info!(
+        "  変換結果を出力の形式に揃えます: {}Hz, {}ch, {}bit",
+        received_spec.sample_rate, received_spec.channels, received_spec.bits_per_sample
+    );
+    let (converted, converted_spec) =
+        wav::read_file(received).context("サーバーの応答を読み込めません")?;
+    let converted = wav::conform(
+        converted,
+        &converted_spec,
+        spec.sample_rate,
+        spec.channels,
+        options.resample_quality,
+    )?;
+    let converted = post.apply(converted, spec.sample_rate, spec.channels);
+    wav::write_file(
+        output,
+        &converted,
+        spec.sample_rate,
+        spec.channels,
+        bit_depth,
+    )
+}
+
+/// `path` の末尾に `suffix` を付けたパス（一時ファイル用）
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// サーバーで変換し、入力と同じサンプリングレート・チャンネル数に戻す
+async fn convert_whole(
+    client: &VoiceConversionClient,
+    samples: &[f32],
+    sample_rate: u32,
+    channels: u16,
+    options: &ProcessOptions,
+) -> Result<Vec<f32>> {
+    let request = wav::encode(samples, sample_rate, channels)?;
+    let response = client
+        .convert_wav(request, &options.model, options.pitch, &options.noise, 0.0)
+        .await?;
+    let (converted, converted_spec) =
+        wav::decode(&response.audio).context("サーバーの応答を読み込めません")?;
+    wav::conform(
+        converted,
+        &converted_spec,
+        sample_rate,
+        channels,
+        options.resample_quality,
+    )
+}
+
+/// キューの区間ごとに設定を変えてサーバーで変換し、つなぎ目を重ねてつなぐ
+async fn convert_cued(
+    client: &VoiceConversionClient,
+    arbiter: Option<&Arbiter>,
+    cues: &CueSheet,
+    samples: &[f32],
+    sample_rate: u32,
+    channels: u16,
+    options: &ProcessOptions,
+) -> Result<Vec<f32>> {
+    let frames = samples.len() / channels.max(1) as usize;
+    let mut output = Vec::with_capacity(samples.len());
+    for section in cue_sections(cues, frames, sample_rate, options) {
+        let segment = section.samples(samples, channels);
+        let converted = match arbiter {
+            Some(arbiter) => {
+                convert_segments(
+                    client,
+                    arbiter,
+                    segment,
+                    sample_rate,
+                    channels,
+                    &section.options,
+                )
+                .await?
+            }
+            None => convert_whole(client, segment, sample_rate, channels, &section.options).await?,
+        };
+        join_section(&mut output, converted, &section, sample_rate, channels)?;
+    }
+    Ok(output)
+}
+
+/// 区間に分けて1つずつ変換し、つなぎ目を重ねてつなぐ
+async fn convert_segments(
+    client: &VoiceConversionClient,
+    arbiter: &Arbiter,
+    samples: &[f32],
+    sample_rate: u32,
+    channels: u16,
+    options: &ProcessOptions,
+) -> Result<Vec<f32>> {
+    let ch = channels.max(1) as usize;
+    let frames = samples.len() / ch;
+    let overlap = (sample_rate as usize * SEGMENT_OVERLAP_MS / 1000).max(1);
+    let mut output: Vec<f32> = Vec::with_capacity(samples.len());
+
+    let mut start = 0;
+    while start < frames {
+        let _permit = arbiter.batch_permit().await;
+        let length = ((arbiter.segment_seconds() * sample_rate as f64) as usize).max(overlap * 2);
+        let end = (start + length).min(frames);
+        // 次の区間と重ねる分も送る
+        let sent_end = (end + overlap).min(frames);
+        let segment = &samples[start * ch..sent_end * ch];
+
+        let started = Instant::now();
+        let mut converted = convert_whole(client, segment, sample_rate, channels, options).await?;
+        arbiter.record_batch(
+            (sent_end - start) as f64 / sample_rate as f64,
+            started.elapsed(),
+        );
+        converted.resize((sent_end - start) * ch, 0.0);
+        join_overlapped(&mut output, &converted, start, ch);
+        start = end;
+    }
+    Ok(output)
+}
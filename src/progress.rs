@@ -0,0 +1,110 @@
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
+use std::time::Duration;
+
+/// 表示を描き直す間隔（変換待ちの経過時間を進めるため）
+const TICK: Duration = Duration::from_millis(120);
+
+const TRANSFER_TEMPLATE: &str =
+    "{spinner:.green} {prefix:.bold} {msg} [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} ({binary_bytes_per_sec}, 残り {eta})";
+
+/// 大きさの分からない受信（Content-Lengthなし）
+const STREAM_TEMPLATE: &str =
+    "{spinner:.green} {prefix:.bold} {msg} {bytes} ({binary_bytes_per_sec})";
+
+const WAITING_TEMPLATE: &str = "{spinner:.green} {prefix:.bold} {msg} {elapsed}";
+
+/// ファイル変換の進み具合の表示（`process`）
+///
+/// ファイルごとに1行を出し、送信 → サーバーでの変換 → 受信と切り替えます。
+/// 並列に変換するとき（`--jobs`）は、変換中のファイルの行を並べます。
+/// 標準エラー出力が端末でなければ何も表示しません。
+#[derive(Debug, Clone)]
+pub struct TransferProgress {
+    multi: MultiProgress,
+}
+
+impl Default for TransferProgress {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TransferProgress {
+    pub fn new() -> Self {
+        Self {
+            multi: MultiProgress::with_draw_target(ProgressDrawTarget::stderr()),
+        }
+    }
+
+    /// 1ファイル分の行を足す
+    pub fn start(&self, name: &str) -> FileProgress {
+        let bar = self.multi.add(ProgressBar::new(0));
+        bar.set_style(style(WAITING_TEMPLATE));
+        bar.set_prefix(name.to_string());
+        bar.set_message("接続中");
+        bar.enable_steady_tick(TICK);
+        FileProgress { bar }
+    }
+}
+
+/// 1ファイルの進み具合（落とすと行を消す）
+pub struct FileProgress {
+    bar: ProgressBar,
+}
+
+impl FileProgress {
+    /// 送信を始める（再試行のときは最初から数え直す）
+    pub fn uploading(&self, total: u64) {
+        self.bar.set_length(total);
+        self.bar.set_position(0);
+        self.bar.set_message("送信");
+        self.bar.set_style(style(TRANSFER_TEMPLATE));
+    }
+
+    /// 送ったバイト数を数える関数（送り終えたらサーバーの変換待ちの表示にする）
+    ///
+    /// 送信する本文のストリームに持たせるためのものです。
+    pub fn upload_counter(&self) -> impl Fn(usize) + Send + Sync + 'static {
+        let bar = self.bar.clone();
+        move |bytes| {
+            bar.inc(bytes as u64);
+            if bar.length().is_some_and(|length| bar.position() >= length) {
+                bar.set_message("サーバーで変換中");
+                bar.reset_elapsed();
+                bar.set_style(style(WAITING_TEMPLATE));
+            }
+        }
+    }
+
+    /// 受信を始める（`processing_time_ms` はサーバーが返した処理時間）
+    pub fn downloading(&self, total: Option<u64>, processing_time_ms: Option<f64>) {
+        self.bar.set_position(0);
+        self.bar.set_message(match processing_time_ms {
+            Some(ms) => format!("受信（変換 {:.1}秒）", ms / 1000.0),
+            None => "受信".to_string(),
+        });
+        match total {
+            Some(total) => {
+                self.bar.set_length(total);
+                self.bar.set_style(style(TRANSFER_TEMPLATE));
+            }
+            None => self.bar.set_style(style(STREAM_TEMPLATE)),
+        }
+    }
+
+    pub fn downloaded(&self, bytes: usize) {
+        self.bar.inc(bytes as u64);
+    }
+}
+
+impl Drop for FileProgress {
+    fn drop(&mut self) {
+        self.bar.finish_and_clear();
+    }
+}
+
+fn style(template: &str) -> ProgressStyle {
+    ProgressStyle::with_template(template)
+        .expect("進捗表示のテンプレート")
+        .progress_chars("=> ")
+}
@@ -0,0 +1,85 @@
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Python側の場所を明示する環境変数（python/ と pyproject.toml を含むディレクトリ）
+pub const HOME_ENV: &str = "MAKEBELIV_HOME";
+
+/// Python側のディレクトリ名
+const PYTHON_DIR: &str = "python";
+
+/// この場所にPython側が揃っているか
+fn is_project_root(dir: &Path) -> bool {
+    dir.join(PYTHON_DIR).join("file_processor.py").is_file()
+}
+
+/// Python側を探す候補（優先順）
+fn candidates() -> Vec<PathBuf> {
+    let mut candidates = Vec::new();
+
+    // 開発時: リポジトリのルートで実行
+    if let Ok(cwd) = std::env::current_dir() {
+        candidates.push(cwd);
+    }
+
+    // インストール時: 実行ファイルの隣、<prefix>/share/makebeliv、
+    // 開発ビルド: target/<profile>/ から遡ったリポジトリのルート
+    if let Some(exe_dir) = std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(Path::to_path_buf))
+    {
+        if let Some(prefix) = exe_dir.parent() {
+            candidates.push(prefix.join("share").join("makebeliv"));
+        }
+        candidates.extend(exe_dir.ancestors().take(3).map(Path::to_path_buf));
+    }
+
+    if let Some(data_dir) = dirs::data_dir() {
+        candidates.push(data_dir.join("makebeliv"));
+    }
+
+    candidates
+}
+
+/// Python側（pyproject.toml と python/ を含むディレクトリ）を探す
+pub fn project_root() -> Result<PathBuf> {
+    if let Some(home) = std::env::var_os(HOME_ENV) {
+        let home = PathBuf::from(home);
+        anyhow::ensure!(
+            is_project_root(&home),
+            "{} にPython側が見つかりません: {}",
+            HOME_ENV,
+            home.display()
+        );
+        return Ok(home);
+    }
+
+    candidates()
+        .into_iter()
+        .find(|dir| is_project_root(dir))
+        .with_context(|| {
+            format!(
+                "Python側（{}/file_processor.py）が見つかりません。リポジトリのルートで実行するか、{} を設定してください",
+                PYTHON_DIR, HOME_ENV
+            )
+        })
+}
+
+/// Python側のスクリプトのパス
+pub fn script(root: &Path, name: &str) -> PathBuf {
+    root.join(PYTHON_DIR).join(name)
+}
+
+/// Python側のルートで実行する `uv` コマンド
+///
+/// 作業ディレクトリが変わるため、ファイルパスは [`absolute`] で渡してください。
+pub fn uv(root: &Path) -> Command {
+    let mut command = Command::new("uv");
+    command.current_dir(root);
+    command
+}
+
+/// 呼び出し元の作業ディレクトリ基準の絶対パス（存在しなくてもよい）
+pub fn absolute(path: &Path) -> Result<PathBuf> {
+    std::path::absolute(path).with_context(|| format!("パスを解決できません: {}", path.display()))
+}
@@ -0,0 +1,389 @@
+use anyhow::{Context, Result};
+use hound::WavWriter;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+use crate::dsp::simd;
+use crate::notify::{Alert, Notifier};
+#[cfg(feature = "opus")]
+use crate::opus_writer::OpusFileWriter;
+use crate::timecode::{self, MarkerLog, SyncOptions};
+use crate::wav::BitDepth;
+
+/// 空き容量がこれを下回ったら警告する（MB、`--record-min-free` のデフォルト）
+pub const DEFAULT_WARN_FREE_MB: u64 = 2048;
+
+/// 空き容量がこれを下回ったら録音を止める（MB）
+///
+/// 書き込み途中で容量が尽きてWAVが壊れる前に、余裕を持って閉じる。
+pub const STOP_FREE_MB: u64 = 256;
+
+/// 録音中に空き容量を確認する間隔
+const DISK_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Opus録音の既定のビットレート（kbps、`--record-bitrate` のデフォルト）
+pub const DEFAULT_OPUS_BITRATE_KBPS: u32 = 64;
+
+const MB: u64 = 1024 * 1024;
+
+#[cfg(not(feature = "opus"))]
+const OPUS_UNAVAILABLE: &str =
+    "Opus形式で録音するには opus フィーチャーを有効にしてビルドしてください（cargo build --features opus）";
+
+/// 録音ファイルの形式（`--record-format`）
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RecordFormat {
+    /// 16bit WAV（無圧縮）
+    #[default]
+    Wav,
+    /// Ogg Opus（`opus` フィーチャーが必要）
+    Opus,
+}
+
+impl RecordFormat {
+    /// 録音ファイルの拡張子
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Wav => "wav",
+            Self::Opus => "opus",
+        }
+    }
+}
+
+impl FromStr for RecordFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "wav" => Ok(Self::Wav),
+            "opus" | "ogg" => Ok(Self::Opus),
+            _ => anyhow::bail!("不明な録音形式: {}（wav, opus）", s),
+        }
+    }
+}
+
+impl std::fmt::Display for RecordFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.extension())
+    }
+}
+
+/// セッション録音の設定
+#[derive(Debug, Clone)]
+pub struct RecordingOptions {
+    /// 録音ファイルの保存先ディレクトリ
+    pub dir: PathBuf,
+    /// 空き容量の警告しきい値（MB）
+    pub warn_free_mb: u64,
+    /// 録音ファイルの形式
+    pub format: RecordFormat,
+    /// Opus録音のビットレート（kbps）
+    pub bitrate_kbps: u32,
+    /// 録音に同期マーカー（BWFの開始時刻・タイムコードのサイドカー）を付ける
+    pub sync: Option<SyncOptions>,
+}
+
+/// 保存先ボリュームの空き容量の状態
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DiskStatus {
+    Ok,
+    /// 警告しきい値を下回った
+    Low(u64),
+    /// 録音を続けられない
+    Critical(u64),
+}
+
+fn disk_status(dir: &Path, warn_free_mb: u64) -> Result<DiskStatus> {
+    let free = fs2::available_space(dir)
+        .with_context(|| format!("空き容量の取得エラー: {}", dir.display()))?;
+    let free_mb = free / MB;
+    Ok(if free_mb < STOP_FREE_MB {
+        DiskStatus::Critical(free_mb)
+    } else if free_mb < warn_free_mb {
+        DiskStatus::Low(free_mb)
+    } else {
+        DiskStatus::Ok
+    })
+}
+
+/// 録音するトラック
+#[derive(Debug, Clone, Copy)]
+enum TrackKind {
+    Input = 0,
+    Output = 1,
+}
+
+impl TrackKind {
+    fn label(self) -> &'static str {
+        match self {
+            TrackKind::Input => "input",
+            TrackKind::Output => "output",
+        }
+    }
+}
+
+/// 録音ファイルへの書き込み
+enum TrackWriter {
+    Wav(WavWriter<BufWriter<File>>),
+    /// 符号化は別スレッドで行う
+    #[cfg(feature = "opus")]
+    Opus(OpusFileWriter),
+}
+
+/// 1本の録音ファイル
+struct Track {
+    path: PathBuf,
+    writer: TrackWriter,
+    sample_rate: u32,
+    channels: u16,
+    /// 書いたフレーム数
+    frames: u64,
+    /// 同期マーカー（`sync` があるときだけ）
+    markers: Option<(MarkerLog, SyncOptions)>,
+}
+
+impl Track {
+    fn create(
+        path: PathBuf,
+        options: &RecordingOptions,
+        sample_rate: u32,
+        channels: u16,
+    ) -> Result<Self> {
+        let writer = match options.format {
+            RecordFormat::Wav => {
+                let spec = BitDepth::Int16.spec(sample_rate, channels);
+                TrackWriter::Wav(
+                    WavWriter::create(&path, spec)
+                        .with_context(|| format!("録音ファイルの作成エラー: {}", path.display()))?,
+                )
+            }
+            #[cfg(feature = "opus")]
+            RecordFormat::Opus => TrackWriter::Opus(OpusFileWriter::create(
+                &path,
+                sample_rate,
+                channels,
+                options.bitrate_kbps,
+            )?),
+            #[cfg(not(feature = "opus"))]
+            RecordFormat::Opus => anyhow::bail!(OPUS_UNAVAILABLE),
+        };
+        info!("🔴 録音開始: {}", path.display());
+        Ok(Self {
+            path,
+            writer,
+            sample_rate,
+            channels,
+            frames: 0,
+            markers: options
+                .sync
+                .map(|sync| (MarkerLog::new(sample_rate, sync.interval_secs), sync)),
+        })
+    }
+
+    fn write(&mut self, samples: &[f32]) -> Result<()> {
+        match &mut self.writer {
+            TrackWriter::Wav(writer) => {
+                let mut converted = Vec::new();
+                simd::to_i16(samples, &mut converted);
+                for sample in converted {
+                    writer.write_sample(sample)?;
+                }
+                // ヘッダーを更新しておき、異常終了しても読めるファイルを残す
+                writer.flush()?;
+            }
+            #[cfg(feature = "opus")]
+            TrackWriter::Opus(writer) => writer.write(samples)?,
+        }
+        self.frames += (samples.len() / self.channels.max(1) as usize) as u64;
+        if let Some((markers, _)) = &mut self.markers {
+            markers.observe(self.frames, chrono::Local::now());
+        }
+        Ok(())
+    }
+
+    fn finalize(self) -> Result<PathBuf> {
+        match self.writer {
+            TrackWriter::Wav(writer) => writer.finalize().with_context(|| {
+                format!("録音ファイルの書き込みエラー: {}", self.path.display())
+            })?,
+            #[cfg(feature = "opus")]
+            TrackWriter::Opus(writer) => writer.finalize()?,
+        }
+        if let Some((markers, sync)) = &self.markers {
+            timecode::apply(&self.path, markers, sync)?;
+        }
+        Ok(self.path)
+    }
+}
+
+/// リアルタイム変換セッションの録音（入力と変換結果を別々のWAVに保存）
+///
+/// 空き容量が足りなくなったり書き込みに失敗したりした場合は録音だけを止め、
+/// 変換自体は続けます。
+pub struct SessionRecorder {
+    options: RecordingOptions,
+    prefix: String,
+    notifier: Notifier,
+    tracks: [Option<Track>; 2],
+    /// トラックごとのファイル数（形式が変わるたびに増える）
+    parts: [u32; 2],
+    /// 録音を止めた（以降の書き込みは捨てる）
+    stopped: bool,
+    warned_low: bool,
+    last_check: Instant,
+    /// 閉じ終えた録音ファイル
+    finished: Vec<PathBuf>,
+}
+
+impl SessionRecorder {
+    /// 空き容量を確認して録音を準備する（ファイルは最初の書き込み時に作る）
+    pub fn start(options: RecordingOptions, notifier: Notifier) -> Result<Self> {
+        if options.format == RecordFormat::Opus {
+            #[cfg(not(feature = "opus"))]
+            anyhow::bail!(OPUS_UNAVAILABLE);
+            #[cfg(feature = "opus")]
+            anyhow::ensure!(
+                (6..=510).contains(&options.bitrate_kbps),
+                "Opusのビットレートは6〜510kbpsで指定してください: {}",
+                options.bitrate_kbps
+            );
+        }
+
+        std::fs::create_dir_all(&options.dir)
+            .with_context(|| format!("録音ディレクトリの作成エラー: {}", options.dir.display()))?;
+
+        match disk_status(&options.dir, options.warn_free_mb)? {
+            DiskStatus::Critical(free_mb) => anyhow::bail!(
+                "録音先の空き容量が足りません: {}MB（{}MB以上必要）",
+                free_mb,
+                STOP_FREE_MB
+            ),
+            DiskStatus::Low(free_mb) => {
+                warn!("⚠ 録音先の空き容量が少なくなっています: {}MB", free_mb)
+            }
+            DiskStatus::Ok => {}
+        }
+
+        Ok(Self {
+            prefix: format!("makebeliv-{}", chrono::Local::now().format("%Y%m%d-%H%M%S")),
+            options,
+            notifier,
+            tracks: [None, None],
+            parts: [0, 0],
+            stopped: false,
+            warned_low: false,
+            last_check: Instant::now(),
+            finished: Vec::new(),
+        })
+    }
+
+    /// マイク入力を書き込む
+    pub fn write_input(&mut self, samples: &[f32], sample_rate: u32, channels: u16) {
+        self.write(TrackKind::Input, samples, sample_rate, channels);
+    }
+
+    /// 変換結果を書き込む
+    pub fn write_output(&mut self, samples: &[f32], sample_rate: u32, channels: u16) {
+        self.write(TrackKind::Output, samples, sample_rate, channels);
+    }
+
+    fn write(&mut self, kind: TrackKind, samples: &[f32], sample_rate: u32, channels: u16) {
+        if self.stopped {
+            return;
+        }
+
+        let index = kind as usize;
+
+        // 形式が変わったら（デバイスの切り替えなど）別ファイルに分ける
+        if self.tracks[index]
+            .as_ref()
+            .is_some_and(|t| t.sample_rate != sample_rate || t.channels != channels)
+        {
+            let track = self.tracks[index].take().expect("録音トラックあり");
+            self.finalize_track(track);
+        }
+
+        let result = match &mut self.tracks[index] {
+            Some(track) => track.write(samples),
+            None => {
+                self.parts[index] += 1;
+                let extension = self.options.format.extension();
+                let name = match self.parts[index] {
+                    1 => format!("{}-{}.{}", self.prefix, kind.label(), extension),
+                    part => format!("{}-{}-{}.{}", self.prefix, kind.label(), part, extension),
+                };
+                let path = self.options.dir.join(name);
+                Track::create(path, &self.options, sample_rate, channels).and_then(|mut track| {
+                    track.write(samples)?;
+                    self.tracks[index] = Some(track);
+                    Ok(())
+                })
+            }
+        };
+
+        if let Err(e) = result {
+            self.stop(&format!("書き込みエラー: {:#}", e));
+        }
+    }
+
+    /// 空き容量を確認する（一定間隔ごと。足りなければ録音を止める）
+    pub fn check_disk_space(&mut self) {
+        if self.stopped || self.last_check.elapsed() < DISK_CHECK_INTERVAL {
+            return;
+        }
+        self.last_check = Instant::now();
+
+        match disk_status(&self.options.dir, self.options.warn_free_mb) {
+            Ok(DiskStatus::Ok) => self.warned_low = false,
+            Ok(DiskStatus::Low(free_mb)) => {
+                if !self.warned_low {
+                    self.warned_low = true;
+                    warn!("⚠ 録音先の空き容量が少なくなっています: {}MB", free_mb);
+                    self.notifier.send(Alert::DiskLow { free_mb });
+                }
+            }
+            Ok(DiskStatus::Critical(free_mb)) => {
+                self.stop(&format!("録音先の空き容量が {}MB になりました", free_mb));
+            }
+            Err(e) => warn!("{:#}", e),
+        }
+    }
+
+    /// 録音を止めてファイルを閉じる（変換は続ける）
+    fn stop(&mut self, reason: &str) {
+        warn!("⚠ 録音を停止しました（変換は継続します）: {}", reason);
+        self.stopped = true;
+        self.close_tracks();
+        self.notifier.send(Alert::RecordingStopped {
+            reason: reason.to_string(),
+        });
+    }
+
+    fn finalize_track(&mut self, track: Track) {
+        match track.finalize() {
+            Ok(path) => self.finished.push(path),
+            Err(e) => warn!("{:#}", e),
+        }
+    }
+
+    fn close_tracks(&mut self) {
+        for index in 0..self.tracks.len() {
+            if let Some(track) = self.tracks[index].take() {
+                self.finalize_track(track);
+            }
+        }
+    }
+
+    /// 録音を終了し、書き出したファイルを返す
+    pub fn finish(mut self) -> Vec<PathBuf> {
+        self.close_tracks();
+        for path in &self.finished {
+            info!("💾 録音を保存: {}", path.display());
+        }
+        self.finished
+    }
+}
@@ -0,0 +1,383 @@
+#[cfg(feature = "local-dsp")]
+use anyhow::Context;
+use anyhow::Result;
+#[cfg(feature = "local-dsp")]
+use rubato::{
+    calculate_cutoff, FastFixedIn, PolynomialDegree, SincFixedIn, SincInterpolationParameters,
+    SincInterpolationType, VecResampler, WindowFunction,
+};
+use std::str::FromStr;
+
+/// 1回の処理で渡すフレーム数
+#[cfg(feature = "local-dsp")]
+const BLOCK_FRAMES: usize = 1024;
+
+/// リサンプラーの品質プロファイル（`--resample-quality`）
+///
+/// `local-dsp` なしのビルドでは、どれを選んでも線形補間です。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResampleQuality {
+    /// 多項式補間。CPU負荷が最も低い（リアルタイム向け）
+    Fast,
+    /// 短いsincフィルタ
+    Balanced,
+    /// 長いsincフィルタ。CPU負荷は高いが折り返し歪みが最も少ない（ファイル処理向け）
+    Best,
+}
+
+#[cfg(feature = "local-dsp")]
+impl ResampleQuality {
+    fn build(
+        self,
+        from: u32,
+        to: u32,
+        channels: usize,
+    ) -> Result<Box<dyn VecResampler<f32> + Send>, rubato::ResamplerConstructionError> {
+        let ratio = to as f64 / from as f64;
+
+        let sinc =
+            |sinc_len, oversampling_factor, interpolation, window| SincInterpolationParameters {
+                sinc_len,
+                f_cutoff: calculate_cutoff(sinc_len, window),
+                oversampling_factor,
+                interpolation,
+                window,
+            };
+
+        Ok(match self {
+            Self::Fast => Box::new(FastFixedIn::<f32>::new(
+                ratio,
+                1.0,
+                PolynomialDegree::Cubic,
+                BLOCK_FRAMES,
+                channels,
+            )?),
+            Self::Balanced => Box::new(SincFixedIn::<f32>::new(
+                ratio,
+                1.0,
+                sinc(
+                    64,
+                    128,
+                    SincInterpolationType::Linear,
+                    WindowFunction::Blackman2,
+                ),
+                BLOCK_FRAMES,
+                channels,
+            )?),
+            Self::Best => Box::new(SincFixedIn::<f32>::new(
+                ratio,
+                1.0,
+                sinc(
+                    256,
+                    256,
+                    SincInterpolationType::Cubic,
+                    WindowFunction::BlackmanHarris2,
+                ),
+                BLOCK_FRAMES,
+                channels,
+            )?),
+        })
+    }
+}
+
+impl FromStr for ResampleQuality {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "fast" => Ok(Self::Fast),
+            "balanced" => Ok(Self::Balanced),
+            "best" => Ok(Self::Best),
+            _ => anyhow::bail!(
+                "不明なリサンプル品質: {}（fast, balanced, best のいずれか）",
+                s
+            ),
+        }
+    }
+}
+
+impl std::fmt::Display for ResampleQuality {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Fast => "fast",
+            Self::Balanced => "balanced",
+            Self::Best => "best",
+        })
+    }
+}
+
+/// インターリーブ音声のストリーミング・リサンプラー
+///
+/// 任意長の入力を受け取り、内部でブロック単位に処理します。
+/// 端数のフレームは次回の入力と合わせて処理されます。
+#[cfg(feature = "local-dsp")]
+pub struct Resampler {
+    inner: Box<dyn VecResampler<f32> + Send>,
+    channels: usize,
+    /// 未処理の入力（チャンネルごと）
+    pending: Vec<Vec<f32>>,
+}
+
+#[cfg(feature = "local-dsp")]
+impl Resampler {
+    pub fn new(from: u32, to: u32, channels: u16, quality: ResampleQuality) -> Result<Self> {
+        let channels = channels.max(1) as usize;
+        let inner = quality
+            .build(from, to, channels)
+            .with_context(|| format!("リサンプラーの作成エラー（{}Hz → {}Hz）", from, to))?;
+
+        Ok(Self {
+            inner,
+            channels,
+            pending: vec![Vec::new(); channels],
+        })
+    }
+
+    /// 入力を追加し、処理できた分の出力を返す
+    pub fn process(&mut self, samples: &[f32]) -> Result<Vec<f32>> {
+        for frame in samples.chunks_exact(self.channels) {
+            for (pending, &sample) in self.pending.iter_mut().zip(frame) {
+                pending.push(sample);
+            }
+        }
+
+        let mut output = Vec::new();
+        while self.pending[0].len() >= self.inner.input_frames_next() {
+            let needed = self.inner.input_frames_next();
+            let block: Vec<Vec<f32>> = self
+                .pending
+                .iter_mut()
+                .map(|p| p.drain(..needed).collect())
+                .collect();
+            let resampled = self
+                .inner
+                .process(&block, None)
+                .context("リサンプリングエラー")?;
+            interleave_into(&resampled, &mut output);
+        }
+
+        Ok(output)
+    }
+
+    /// 遅延の見積もり（ミリ秒）: ブロックが溜まるまでの待ちとフィルタの遅延
+    pub fn latency_ms(&self, from: u32, to: u32) -> f64 {
+        self.inner.input_frames_next() as f64 * 1000.0 / from.max(1) as f64
+            + self.inner.output_delay() as f64 * 1000.0 / to.max(1) as f64
+    }
+
+    /// 残りの入力とフィルタ内の遅延分を出力し切る
+    pub fn flush(&mut self) -> Result<Vec<f32>> {
+        let mut output = Vec::new();
+
+        if !self.pending[0].is_empty() {
+            let block = std::mem::replace(&mut self.pending, vec![Vec::new(); self.channels]);
+            let resampled = self
+                .inner
+                .process_partial(Some(&block), None)
+                .context("リサンプリングエラー")?;
+            interleave_into(&resampled, &mut output);
+        }
+
+        let resampled = self
+            .inner
+            .process_partial(None, None)
+            .context("リサンプリングエラー")?;
+        interleave_into(&resampled, &mut output);
+
+        Ok(output)
+    }
+}
+
+/// `local-dsp` なしのビルドのストリーミング・リサンプラー（品質によらず線形補間）
+///
+/// 前回の入力の最後のフレームを持っておき、次の入力との間も補間します。
+#[cfg(not(feature = "local-dsp"))]
+pub struct Resampler {
+    /// 出力1フレームあたりに進む入力のフレーム数
+    step: f64,
+    channels: usize,
+    /// 前回の入力の最後のフレーム
+    previous: Vec<f32>,
+    /// 次に出力する位置（`previous` を0とした入力のフレーム位置）
+    position: f64,
+}
+
+#[cfg(not(feature = "local-dsp"))]
+impl Resampler {
+    pub fn new(from: u32, to: u32, channels: u16, _quality: ResampleQuality) -> Result<Self> {
+        anyhow::ensure!(
+            from > 0 && to > 0,
+            "リサンプラーの作成エラー（{}Hz → {}Hz）",
+            from,
+            to
+        );
+        Ok(Self {
+            step: from as f64 / to as f64,
+            channels: channels.max(1) as usize,
+            previous: Vec::new(),
+            position: 0.0,
+        })
+    }
+
+    /// 入力を追加し、処理できた分の出力を返す
+    pub fn process(&mut self, samples: &[f32]) -> Result<Vec<f32>> {
+        let ch = self.channels;
+        let frames = samples.len() / ch;
+        if frames == 0 {
+            return Ok(Vec::new());
+        }
+        let previous = std::mem::take(&mut self.previous);
+        let offset = usize::from(!previous.is_empty());
+        let total = frames + offset;
+        let frame = |i: usize| {
+            if i < offset {
+                &previous[..]
+            } else {
+                &samples[(i - offset) * ch..(i - offset + 1) * ch]
+            }
+        };
+
+        let mut output = Vec::new();
+        let mut position = self.position;
+        while position < (total - 1) as f64 {
+            let i = position as usize;
+            let t = (position - i as f64) as f32;
+            output.extend(
+                frame(i)
+                    .iter()
+                    .zip(frame(i + 1))
+                    .map(|(a, b)| a + (b - a) * t),
+            );
+            position += self.step;
+        }
+        self.position = position - (total - 1) as f64;
+        self.previous = frame(total - 1).to_vec();
+        Ok(output)
+    }
+
+    /// 遅延の見積もり（ミリ秒）: 次の入力を待つ1フレーム
+    pub fn latency_ms(&self, from: u32, _to: u32) -> f64 {
+        1000.0 / from.max(1) as f64
+    }
+
+    /// 最後のフレームの分を出力し切る
+    pub fn flush(&mut self) -> Result<Vec<f32>> {
+        let mut output = Vec::new();
+        if !self.previous.is_empty() {
+            while self.position < 1.0 {
+                output.extend_from_slice(&self.previous);
+                self.position += self.step;
+            }
+        }
+        self.previous.clear();
+        self.position = 0.0;
+        Ok(output)
+    }
+}
+
+/// 音声全体を一括でリサンプリング（出力長は入力の時間長に揃える）
+pub fn resample(
+    samples: &[f32],
+    from: u32,
+    to: u32,
+    channels: u16,
+    quality: ResampleQuality,
+) -> Result<Vec<f32>> {
+    if from == to {
+        return Ok(samples.to_vec());
+    }
+
+    let mut resampler = Resampler::new(from, to, channels, quality)?;
+    let mut output = resampler.process(samples)?;
+    output.extend(resampler.flush()?);
+
+    let channels = channels.max(1) as usize;
+    let frames = samples.len() / channels;
+    let expected = (frames as u64 * to as u64 / from as u64) as usize * channels;
+    output.resize(expected, 0.0);
+
+    Ok(output)
+}
+
+#[cfg(feature = "local-dsp")]
+fn interleave_into(channels: &[Vec<f32>], output: &mut Vec<f32>) {
+    let frames = channels.first().map_or(0, Vec::len);
+    output.reserve(frames * channels.len());
+    for i in 0..frames {
+        output.extend(channels.iter().map(|c| c[i]));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const QUALITIES: [ResampleQuality; 3] = [
+        ResampleQuality::Fast,
+        ResampleQuality::Balanced,
+        ResampleQuality::Best,
+    ];
+
+    #[test]
+    fn keeps_the_duration() {
+        for quality in QUALITIES {
+            for (from, to, channels) in [
+                (16_000, 48_000, 1),
+                (48_000, 16_000, 2),
+                (44_100, 48_000, 2),
+            ] {
+                let input = vec![0.0; from as usize / 2 * channels as usize];
+                let output = resample(&input, from, to, channels, quality).unwrap();
+                assert_eq!(
+                    output.len(),
+                    to as usize / 2 * channels as usize,
+                    "{} {}→{}",
+                    quality,
+                    from,
+                    to
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn keeps_a_constant_level_on_each_channel() {
+        let input: Vec<f32> = (0..24_000).flat_map(|_| [0.5, -0.25]).collect();
+        for quality in QUALITIES {
+            let output = resample(&input, 24_000, 48_000, 2, quality).unwrap();
+            // フィルタの立ち上がりと立ち下がりを除いた中ほど
+            let middle = &output[output.len() / 4..output.len() * 3 / 4];
+            for frame in middle.chunks_exact(2) {
+                assert!((frame[0] - 0.5).abs() < 0.01, "{}: {:?}", quality, frame);
+                assert!((frame[1] + 0.25).abs() < 0.01, "{}: {:?}", quality, frame);
+            }
+        }
+    }
+
+    #[test]
+    fn streaming_in_pieces_matches_the_whole() {
+        let input: Vec<f32> = (0..9_000).map(|i| (i as f32 * 0.01).sin()).collect();
+        let whole = resample(&input, 16_000, 22_050, 1, ResampleQuality::Fast).unwrap();
+
+        let mut resampler = Resampler::new(16_000, 22_050, 1, ResampleQuality::Fast).unwrap();
+        let mut pieces = Vec::new();
+        for piece in input.chunks(777) {
+            pieces.extend(resampler.process(piece).unwrap());
+        }
+        pieces.extend(resampler.flush().unwrap());
+        pieces.resize(whole.len(), 0.0);
+        // 位置の計算の丸めの違いだけ
+        for (i, (a, b)) in pieces.iter().zip(&whole).enumerate() {
+            assert!((a - b).abs() < 1e-4, "{}: {} != {}", i, a, b);
+        }
+    }
+
+    #[test]
+    fn same_rate_is_a_copy() {
+        let input = vec![0.1, 0.2, 0.3, 0.4];
+        assert_eq!(
+            resample(&input, 48_000, 48_000, 2, ResampleQuality::Best).unwrap(),
+            input
+        );
+    }
+}
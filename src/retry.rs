@@ -0,0 +1,267 @@
+use anyhow::Result;
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::Duration;
+use tracing::warn;
+
+use crate::chaos::XorShift;
+
+/// ファイル変換（`/convert`）の既定の試行回数（最初の1回を含む）
+pub const DEFAULT_ATTEMPTS: u32 = 3;
+
+/// ファイル変換の最初の再試行までの既定の待ち時間（ミリ秒）
+pub const DEFAULT_BACKOFF_MS: u64 = 200;
+
+/// 再試行までの待ち時間の既定の上限（ミリ秒）
+pub const DEFAULT_MAX_BACKOFF_MS: u64 = 5000;
+
+/// 待ち時間の既定の揺れ（±割合）
+pub const DEFAULT_JITTER: f64 = 0.2;
+
+/// チャンク変換（`/convert-chunk`）の既定の試行回数
+///
+/// リアルタイム変換では待つほど遅れるため、1回だけすぐに送り直します。
+pub const DEFAULT_CHUNK_ATTEMPTS: u32 = 2;
+
+/// チャンク変換の再試行までの既定の待ち時間（ミリ秒）
+pub const DEFAULT_CHUNK_BACKOFF_MS: u64 = 20;
+
+/// 失敗したリクエストの再試行の方針（指数バックオフ）
+///
+/// n回目の再試行の前に `backoff × 2^(n-1)`（`max_backoff` まで）を、`jitter` の割合だけ
+/// 揺らして待ちます。同じサーバーを使う複数のクライアントが一斉に送り直さないためです。
+/// 接続できない・タイムアウト・5xx・429 など、送り直せば通りうる失敗だけを再試行します。
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    /// 試行回数（最初の1回を含む。1なら再試行しない）
+    pub attempts: u32,
+    pub backoff: Duration,
+    pub max_backoff: Duration,
+    /// 待ち時間の揺れ（0.0〜1.0、±割合）
+    pub jitter: f64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            attempts: DEFAULT_ATTEMPTS,
+            backoff: Duration::from_millis(DEFAULT_BACKOFF_MS),
+            max_backoff: Duration::from_millis(DEFAULT_MAX_BACKOFF_MS),
+            jitter: DEFAULT_JITTER,
+        }
+    }
+}
+
+/// 待ち時間を揺らす乱数（クライアントの複製で共有する）
+static RNG: Mutex<Option<XorShift>> = Mutex::new(None);
+
+impl RetryPolicy {
+    /// 再試行しない
+    pub fn none() -> Self {
+        Self {
+            attempts: 1,
+            ..Self::default()
+        }
+    }
+
+    /// チャンク変換の既定
+    pub fn chunk() -> Self {
+        Self {
+            attempts: DEFAULT_CHUNK_ATTEMPTS,
+            backoff: Duration::from_millis(DEFAULT_CHUNK_BACKOFF_MS),
+            ..Self::default()
+        }
+    }
+
+    /// `retry` 回目（1始まり）の再試行の前に待つ時間
+    pub fn delay(&self, retry: u32) -> Duration {
+        let exponent = retry.saturating_sub(1).min(31);
+        let base = self
+            .backoff
+            .saturating_mul(1 << exponent)
+            .min(self.max_backoff);
+        let jitter = self.jitter.clamp(0.0, 1.0);
+        if jitter == 0.0 {
+            return base;
+        }
+        let random = RNG
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get_or_insert_with(XorShift::from_time)
+            .next_f64();
+        base.mul_f64(1.0 + jitter * (random * 2.0 - 1.0))
+    }
+
+    /// 送り直せば通りうる失敗なら再試行しながら `request` を実行する
+    ///
+    /// `what` はログに出すリクエストの名前です。
+    pub async fn run<T, F, Fut>(&self, what: &str, mut request: F) -> Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let attempts = self.attempts.max(1);
+        let mut attempt = 1;
+        loop {
+            match request().await {
+                Ok(value) => return Ok(value),
+                Err(e) if attempt < attempts && is_transient(&e) => {
+                    let delay = self.delay(attempt);
+                    warn!(
+                        "⚠ {}に失敗しました（{}/{}回目）: {:#}。{}ms後に再試行します",
+                        what,
+                        attempt,
+                        attempts,
+                        e,
+                        delay.as_millis()
+                    );
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+/// 送り直せば通りうる失敗か（接続できない・タイムアウト・5xx・408・429）
+///
+/// APIキーの誤り（401）や入力の誤り（400, 422）などは、送り直しても同じ結果になります。
+pub fn is_transient(error: &anyhow::Error) -> bool {
+    let Some(error) = error
+        .chain()
+        .find_map(|cause| cause.downcast_ref::<reqwest::Error>())
+    else {
+        return false;
+    };
+    if let Some(status) = error.status() {
+        return is_transient_status(status);
+    }
+    error.is_connect() || error.is_timeout() || error.is_request() || error.is_body()
+}
+
+/// 送り直せば通りうるHTTPステータスか（5xx・408・429）
+pub fn is_transient_status(status: reqwest::StatusCode) -> bool {
+    status.is_server_error()
+        || status == reqwest::StatusCode::REQUEST_TIMEOUT
+        || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::StatusCode;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    fn policy(jitter: f64) -> RetryPolicy {
+        RetryPolicy {
+            attempts: 4,
+            backoff: Duration::from_millis(200),
+            max_backoff: Duration::from_millis(1000),
+            jitter,
+        }
+    }
+
+    /// そのステータスで失敗した応答のエラー
+    fn status_error(status: u16) -> anyhow::Error {
+        let response = http::Response::builder()
+            .status(status)
+            .body(Vec::<u8>::new())
+            .unwrap();
+        let error = reqwest::Response::from(response)
+            .error_for_status()
+            .unwrap_err();
+        anyhow::Error::new(error).context("チャンク変換エラー")
+    }
+
+    #[test]
+    fn delay_doubles_up_to_the_cap() {
+        let policy = policy(0.0);
+        let delays: Vec<u128> = (1..=6).map(|n| policy.delay(n).as_millis()).collect();
+        assert_eq!(delays, [200, 400, 800, 1000, 1000, 1000]);
+        // 指数が大きくてもあふれない
+        assert_eq!(policy.delay(u32::MAX), Duration::from_millis(1000));
+        assert_eq!(policy.delay(0), Duration::from_millis(200));
+    }
+
+    #[test]
+    fn jitter_stays_within_bounds() {
+        let policy = policy(0.2);
+        for retry in 1..=5 {
+            let base = policy.with_jitter(0.0).delay(retry).as_secs_f64();
+            for _ in 0..200 {
+                let delay = policy.delay(retry).as_secs_f64();
+                assert!(
+                    (base * 0.8..=base * 1.2).contains(&delay),
+                    "{}回目: {} は {} の±20%を超えています",
+                    retry,
+                    delay,
+                    base
+                );
+            }
+        }
+        // 1を超える揺れは1に抑える（待ち時間が負にならない）
+        let wild = policy.with_jitter(5.0);
+        assert!((0..200).all(|_| wild.delay(1) <= Duration::from_millis(400)));
+    }
+
+    #[test]
+    fn retries_only_transient_statuses() {
+        for status in [408, 429, 500, 502, 503, 504] {
+            assert!(is_transient(&status_error(status)), "{}", status);
+        }
+        for status in [400, 401, 403, 404, 409, 413, 422] {
+            assert!(!is_transient(&status_error(status)), "{}", status);
+        }
+        assert!(is_transient_status(StatusCode::SERVICE_UNAVAILABLE));
+        assert!(!is_transient_status(StatusCode::UNAUTHORIZED));
+        // reqwest以外の失敗（応答の形式違いなど）は送り直さない
+        assert!(!is_transient(&anyhow::anyhow!("WAVデータではありません")));
+    }
+
+    #[tokio::test]
+    async fn run_stops_after_the_configured_attempts() {
+        let policy = RetryPolicy {
+            backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(2),
+            ..policy(0.0)
+        };
+        let calls = AtomicU32::new(0);
+        let result: Result<()> = policy
+            .run("テスト", || async {
+                calls.fetch_add(1, Ordering::Relaxed);
+                Err(status_error(503))
+            })
+            .await;
+        assert!(result.is_err());
+        assert_eq!(calls.load(Ordering::Relaxed), 4);
+
+        calls.store(0, Ordering::Relaxed);
+        let result: Result<()> = policy
+            .run("テスト", || async {
+                calls.fetch_add(1, Ordering::Relaxed);
+                Err(status_error(422))
+            })
+            .await;
+        assert!(result.is_err());
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+
+        calls.store(0, Ordering::Relaxed);
+        let value = policy
+            .run("テスト", || async {
+                match calls.fetch_add(1, Ordering::Relaxed) {
+                    0 => Err(status_error(429)),
+                    _ => Ok(7),
+                }
+            })
+            .await
+            .unwrap();
+        assert_eq!((value, calls.load(Ordering::Relaxed)), (7, 2));
+    }
+
+    impl RetryPolicy {
+        fn with_jitter(self, jitter: f64) -> Self {
+            Self { jitter, ..self }
+        }
+    }
+}
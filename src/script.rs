@@ -0,0 +1,170 @@
+use anyhow::Result;
+#[cfg(feature = "scripting")]
+use anyhow::{anyhow, Context};
+#[cfg(feature = "scripting")]
+use rhai::{Dynamic, Engine, Map, Scope, AST};
+use std::path::Path;
+
+use crate::dsp::analysis;
+
+/// スクリプト1回の呼び出しで許可する最大演算数（無限ループ対策）
+#[cfg(feature = "scripting")]
+const MAX_OPERATIONS: u64 = 100_000;
+
+/// スクリプトのエントリ関数名
+#[cfg(feature = "scripting")]
+const ENTRY_FN: &str = "on_chunk";
+
+#[cfg(not(feature = "scripting"))]
+const UNAVAILABLE: &str =
+    "--script を使うには scripting フィーチャーを有効にしてビルドしてください（cargo build --features scripting）";
+
+/// チャンクごとにスクリプトへ渡すイベント
+#[derive(Debug, Clone)]
+pub struct ChunkEvent {
+    /// セッション開始からのチャンク番号
+    pub index: u64,
+    pub rms_db: f32,
+    pub peak_db: f32,
+    /// 推定ピッチ（無声時はNone）
+    pub pitch_hz: Option<f32>,
+    /// 発話中か（VAD）
+    pub voiced: bool,
+}
+
+impl ChunkEvent {
+    /// 入力チャンクを解析してイベントを作成
+    pub fn analyze(index: u64, chunk: &[f32], sample_rate: u32, channels: u16) -> Self {
+        let mono = analysis::downmix(chunk, channels);
+        let rms_db = analysis::rms_db(&mono);
+        let voiced = rms_db >= analysis::DEFAULT_VAD_THRESHOLD_DB;
+
+        Self {
+            index,
+            rms_db,
+            peak_db: analysis::peak_db(chunk),
+            pitch_hz: voiced
+                .then(|| analysis::estimate_pitch(&mono, sample_rate))
+                .flatten(),
+            voiced,
+        }
+    }
+}
+
+/// スクリプトが要求したパラメータ変更
+#[derive(Debug, Default, PartialEq)]
+pub struct ParamChanges {
+    pub pitch_shift: Option<i32>,
+    pub model: Option<String>,
+}
+
+impl ParamChanges {
+    pub fn is_empty(&self) -> bool {
+        self.pitch_shift.is_none() && self.model.is_none()
+    }
+}
+
+/// ライブパラメータ制御スクリプト（rhai）
+///
+/// スクリプトは `on_chunk(event)` を定義し、変更したいパラメータを
+/// マップで返します（例: `#{ pitch_shift: 2 }`）。変更がなければ `()` か空マップを返します。
+#[cfg(feature = "scripting")]
+pub struct ParamScript {
+    engine: Engine,
+    ast: AST,
+}
+
+/// `scripting` なしのビルドのスクリプト（読み込めないため作られない）
+#[cfg(not(feature = "scripting"))]
+pub struct ParamScript {
+    _unavailable: (),
+}
+
+#[cfg(not(feature = "scripting"))]
+impl ParamScript {
+    pub fn load(_path: &Path) -> Result<Self> {
+        anyhow::bail!(UNAVAILABLE)
+    }
+
+    pub fn on_chunk(
+        &self,
+        _event: &ChunkEvent,
+        _pitch_shift: i32,
+        _model: &str,
+    ) -> Result<ParamChanges> {
+        Ok(ParamChanges::default())
+    }
+}
+
+#[cfg(feature = "scripting")]
+impl ParamScript {
+    pub fn load(path: &Path) -> Result<Self> {
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+
+        let ast = engine
+            .compile_file(path.to_path_buf())
+            .map_err(|e| anyhow!("{}", e))
+            .with_context(|| format!("スクリプトのコンパイルエラー: {}", path.display()))?;
+
+        if !ast.iter_functions().any(|f| f.name == ENTRY_FN) {
+            anyhow::bail!(
+                "スクリプトに {}(event) が定義されていません: {}",
+                ENTRY_FN,
+                path.display()
+            );
+        }
+
+        Ok(Self { engine, ast })
+    }
+
+    /// チャンクイベントでスクリプトを呼び出す
+    pub fn on_chunk(
+        &self,
+        event: &ChunkEvent,
+        pitch_shift: i32,
+        model: &str,
+    ) -> Result<ParamChanges> {
+        let mut map = Map::new();
+        map.insert("index".into(), (event.index as i64).into());
+        map.insert("rms_db".into(), (event.rms_db as f64).into());
+        map.insert("peak_db".into(), (event.peak_db as f64).into());
+        map.insert(
+            "pitch_hz".into(),
+            event.pitch_hz.map_or(Dynamic::UNIT, |p| (p as f64).into()),
+        );
+        map.insert("voiced".into(), event.voiced.into());
+        map.insert("pitch_shift".into(), (pitch_shift as i64).into());
+        map.insert("model".into(), model.into());
+
+        let result: Dynamic = self
+            .engine
+            .call_fn(&mut Scope::new(), &self.ast, ENTRY_FN, (map,))
+            .map_err(|e| anyhow!("スクリプト実行エラー: {}", e))?;
+
+        if result.is_unit() {
+            return Ok(ParamChanges::default());
+        }
+
+        let result = result
+            .try_cast::<Map>()
+            .context("on_chunk はマップか () を返す必要があります")?;
+
+        let mut changes = ParamChanges::default();
+        if let Some(value) = result.get("pitch_shift") {
+            let pitch = value
+                .as_int()
+                .map_err(|t| anyhow!("pitch_shift は整数で指定してください（{}）", t))?;
+            changes.pitch_shift = Some(pitch as i32);
+        }
+        if let Some(value) = result.get("model") {
+            let model = value
+                .clone()
+                .into_string()
+                .map_err(|t| anyhow!("model は文字列で指定してください（{}）", t))?;
+            changes.model = Some(model);
+        }
+
+        Ok(changes)
+    }
+}
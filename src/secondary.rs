@@ -0,0 +1,124 @@
+use anyhow::Result;
+use cpal::Stream;
+use tracing::{info, warn};
+
+use crate::audio::{remap_channels, AudioBuffer, AudioOutput};
+use crate::dsp::{analysis, simd};
+use crate::resample::{ResampleQuality, Resampler};
+
+/// 補助出力に要求するコールバックあたりのフレーム数（低遅延優先）
+const BUFFER_FRAMES: u32 = 256;
+
+/// 補助出力の設定
+#[derive(Debug, Clone)]
+pub struct SecondaryOptions {
+    /// 出力デバイス名（Noneならデフォルト）
+    pub device: Option<String>,
+    /// 音量（dB）
+    pub level_db: f32,
+}
+
+/// メイン出力とは別のデバイスへ小音量で流す補助出力
+///
+/// サイドトーン（マイク直のモニター）やエコー（変換結果の確認用）に使います。
+pub struct SecondaryOutput {
+    label: &'static str,
+    output: AudioOutput,
+    buffer: AudioBuffer,
+    gain: f32,
+    _stream: Stream,
+}
+
+impl SecondaryOutput {
+    /// `buffer_ms` を超えて溜まった分は古い順に捨てる（小さいほど遅延が少ない）
+    pub fn open(label: &'static str, options: &SecondaryOptions, buffer_ms: u32) -> Result<Self> {
+        let mut output = AudioOutput::open(options.device.as_deref())?;
+        output.request_buffer_frames(BUFFER_FRAMES);
+
+        let buffer = AudioBuffer::new(
+            output.sample_rate() as usize * output.channels() as usize * buffer_ms as usize / 1000,
+        );
+        let stream = {
+            let buffer = buffer.clone();
+            output.start_stream(move |data| {
+                buffer.fill(data);
+            })?
+        };
+
+        info!(
+            "{}: {}（{:+.1} dB）",
+            label,
+            output.name(),
+            options.level_db
+        );
+
+        Ok(Self {
+            label,
+            output,
+            buffer,
+            gain: analysis::from_db(options.level_db),
+            _stream: stream,
+        })
+    }
+
+    pub fn name(&self) -> String {
+        self.output.name()
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.output.sample_rate()
+    }
+
+    pub fn channels(&self) -> u16 {
+        self.output.channels()
+    }
+
+    /// 指定した形式の音声を流し込む送り手を作る
+    ///
+    /// 送り手は音声コールバック内でも使えます（レートが違えばリサンプリングする）。
+    pub fn feeder(&self, sample_rate: u32, channels: u16) -> SecondaryFeeder {
+        let out_rate = self.output.sample_rate();
+        let resampler = if sample_rate == out_rate {
+            None
+        } else {
+            Resampler::new(sample_rate, out_rate, channels, ResampleQuality::Fast)
+                .map_err(|e| warn!("{}のリサンプラー作成エラー: {:#}", self.label, e))
+                .ok()
+        };
+
+        SecondaryFeeder {
+            buffer: self.buffer.clone(),
+            gain: self.gain,
+            resampler,
+            in_channels: channels,
+            out_channels: self.output.channels(),
+        }
+    }
+}
+
+/// 補助出力へ音声を送る
+pub struct SecondaryFeeder {
+    buffer: AudioBuffer,
+    gain: f32,
+    resampler: Option<Resampler>,
+    in_channels: u16,
+    out_channels: u16,
+}
+
+impl SecondaryFeeder {
+    pub fn push(&mut self, samples: &[f32]) {
+        let mut samples = match self.resampler.as_mut() {
+            Some(resampler) => match resampler.process(samples) {
+                Ok(resampled) => resampled,
+                Err(_) => return,
+            },
+            None => samples.to_vec(),
+        };
+        simd::scale(&mut samples, self.gain);
+        self.buffer.push(&remap_channels(
+            &samples,
+            self.in_channels,
+            self.out_channels,
+        ));
+    }
+}
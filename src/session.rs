@@ -0,0 +1,79 @@
+use anyhow::Result;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::client::{SessionInfo, VoiceConversionClient};
+
+/// リアルタイム変換のセッション管理
+///
+/// セッションIDをUUIDで発行します。サーバーの揺らぎの状態はセッションごとに分かれるため、
+/// 開始時と終了時（Ctrl+Cを含む）にリセットして、前の状態を引き継いだり
+/// サーバーに残したりしないようにします。
+pub struct SessionManager {
+    client: VoiceConversionClient,
+    /// サーバーを使うか（ローカルエンジンではIDの発行だけ行う）
+    remote: bool,
+    /// 開始して、まだ終了していないセッション
+    active: Vec<String>,
+}
+
+impl SessionManager {
+    pub fn new(api_url: String) -> Result<Self> {
+        Ok(Self {
+            client: VoiceConversionClient::new(api_url)?,
+            remote: true,
+            active: Vec::new(),
+        })
+    }
+
+    /// サーバーを使うか（falseならリセットを送らない）
+    pub fn with_remote(mut self, remote: bool) -> Self {
+        self.remote = remote;
+        self
+    }
+
+    /// 新しいセッションを始めてIDを返す
+    pub async fn start(&mut self) -> String {
+        let session_id = Uuid::new_v4().to_string();
+        if self.remote {
+            if let Err(e) = self.client.reset_session(&session_id).await {
+                warn!("セッション開始時のリセットに失敗: {:#}", e);
+            }
+        }
+        info!("セッション開始: {}", session_id);
+        self.active.push(session_id.clone());
+        session_id
+    }
+
+    /// 開始したセッションをすべて終了する（失敗しても止めない）
+    pub async fn stop_all(&mut self) {
+        for session_id in std::mem::take(&mut self.active) {
+            if self.remote {
+                if let Err(e) = self.client.reset_session(&session_id).await {
+                    warn!("セッション終了時のリセットに失敗: {:#}", e);
+                }
+            }
+            info!("セッション終了: {}", session_id);
+        }
+    }
+
+    /// サーバーが状態を持っているセッションの一覧
+    pub async fn list(&self) -> Result<Vec<SessionInfo>> {
+        self.client.handshake().await?;
+        self.client.list_sessions().await
+    }
+
+    /// 指定したセッションをリセット
+    pub async fn reset(&self, session_id: &str) -> Result<()> {
+        self.client.reset_session(session_id).await
+    }
+
+    /// サーバーのすべてのセッションをリセットし、その数を返す
+    pub async fn reset_all(&self) -> Result<usize> {
+        let sessions = self.list().await?;
+        for session in &sessions {
+            self.reset(&session.session_id).await?;
+        }
+        Ok(sessions.len())
+    }
+}
@@ -0,0 +1,57 @@
+use anyhow::{Context, Result};
+use std::process::ExitStatus;
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// Ctrl+Cで中断したときの終了コード（128 + SIGINT）
+pub const INTERRUPTED_EXIT_CODE: u8 = 130;
+
+/// Ctrl+Cの後、子プロセスが自分で終了するのを待つ時間
+const CHILD_GRACE_PERIOD: Duration = Duration::from_secs(10);
+
+/// Ctrl+Cで中断されたことを表すエラー（`main` で終了コードに変える）
+#[derive(Debug)]
+pub struct Interrupted;
+
+impl std::fmt::Display for Interrupted {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Ctrl+Cで中断しました")
+    }
+}
+
+impl std::error::Error for Interrupted {}
+
+/// Ctrl+Cを待つ
+///
+/// 一度呼ぶとプロセスの既定のSIGINTの処理（即時終了）が置き換わるため、
+/// 呼んだ側が後片付けをしてから終了できます。シグナルを受け取れない環境では戻りません。
+pub async fn ctrl_c() {
+    if let Err(e) = tokio::signal::ctrl_c().await {
+        warn!("Ctrl+Cを受け取れません: {}", e);
+        std::future::pending::<()>().await;
+    }
+}
+
+/// 子プロセスを実行して終了を待つ
+///
+/// Ctrl+Cは同じプロセスグループの子プロセスにも届くため、中断されたら子プロセスが
+/// 自分で後片付けをして終わるのを待ち、`Interrupted` を返します。
+/// 猶予を過ぎても終わらなければ強制終了します。
+pub async fn run_child(command: std::process::Command) -> Result<ExitStatus> {
+    let mut child = tokio::process::Command::from(command)
+        .kill_on_drop(true)
+        .spawn()
+        .context("子プロセスの起動エラー")?;
+
+    tokio::select! {
+        status = child.wait() => status.context("子プロセスの待機エラー"),
+        _ = ctrl_c() => {
+            info!("停止中...（子プロセスの終了を待っています）");
+            if tokio::time::timeout(CHILD_GRACE_PERIOD, child.wait()).await.is_err() {
+                warn!("子プロセスが終了しないため強制終了します");
+                child.kill().await.ok();
+            }
+            Err(Interrupted.into())
+        }
+    }
+}
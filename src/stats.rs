@@ -0,0 +1,169 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local, NaiveDate};
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+use crate::summary::SessionSummary;
+
+/// セッション履歴のファイル名（1行1セッションのJSON Lines）
+const STORE_FILE_NAME: &str = "sessions.jsonl";
+
+/// 履歴の保存先（例: ~/.local/share/makebeliv/sessions.jsonl）
+pub fn store_path() -> Result<PathBuf> {
+    let dir = dirs::data_dir().context("データディレクトリが見つかりません")?;
+    Ok(dir.join("makebeliv").join(STORE_FILE_NAME))
+}
+
+/// セッション概要を履歴に追記
+pub fn record(summary: &SessionSummary) -> Result<()> {
+    record_to(&store_path()?, summary)
+}
+
+/// 指定パスの履歴に追記
+pub fn record_to(path: &Path, summary: &SessionSummary) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("ディレクトリの作成エラー: {}", parent.display()))?;
+    }
+
+    let mut line = serde_json::to_string(summary)?;
+    line.push('\n');
+
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut file| file.write_all(line.as_bytes()))
+        .with_context(|| format!("セッション履歴の書き込みエラー: {}", path.display()))
+}
+
+/// 履歴を読み込む（壊れた行は警告して読み飛ばす）
+pub fn load(path: &Path) -> Result<Vec<SessionSummary>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("セッション履歴の読み込みエラー: {}", path.display()))?;
+
+    let mut sessions = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str(line) {
+            Ok(summary) => sessions.push(summary),
+            Err(e) => warn!("セッション履歴 {}行目を読み飛ばします: {}", i + 1, e),
+        }
+    }
+
+    Ok(sessions)
+}
+
+/// 複数セッションの集計
+#[derive(Debug, Default)]
+struct Totals {
+    sessions: u64,
+    duration_seconds: f64,
+    chunks_converted: u64,
+    chunks_failed: u64,
+    /// チャンク数で重み付けした平均遅延の合計
+    weighted_latency_ms: f64,
+    weighted_p95_ms: f64,
+    bytes_sent: u64,
+    bytes_received: u64,
+}
+
+impl Totals {
+    fn add(&mut self, s: &SessionSummary) {
+        self.sessions += 1;
+        self.duration_seconds += s.duration_seconds;
+        self.chunks_converted += s.chunks_converted;
+        self.chunks_failed += s.chunks_failed;
+        if let Some(latency) = &s.latency_ms {
+            self.weighted_latency_ms += latency.mean * s.chunks_converted as f64;
+            self.weighted_p95_ms += latency.p95 * s.chunks_converted as f64;
+        }
+        self.bytes_sent += s.bytes_sent;
+        self.bytes_received += s.bytes_received;
+    }
+
+    fn mean_latency_ms(&self) -> Option<f64> {
+        (self.chunks_converted > 0).then(|| self.weighted_latency_ms / self.chunks_converted as f64)
+    }
+
+    fn mean_p95_ms(&self) -> Option<f64> {
+        (self.chunks_converted > 0).then(|| self.weighted_p95_ms / self.chunks_converted as f64)
+    }
+
+    fn failure_rate(&self) -> f64 {
+        let total = self.chunks_converted + self.chunks_failed;
+        if total == 0 {
+            0.0
+        } else {
+            self.chunks_failed as f64 / total as f64
+        }
+    }
+}
+
+/// セッション開始日（ローカル時刻）
+fn session_date(summary: &SessionSummary) -> Option<NaiveDate> {
+    DateTime::from_timestamp(summary.started_at as i64, 0)
+        .map(|t| t.with_timezone(&Local).date_naive())
+}
+
+/// 累計と直近の日別推移を表示
+pub fn print_report(sessions: &[SessionSummary], days: u32) {
+    if sessions.is_empty() {
+        println!("セッション履歴はまだありません（monitor 終了時に記録されます）");
+        return;
+    }
+
+    let mut total = Totals::default();
+    let mut daily: BTreeMap<NaiveDate, Totals> = BTreeMap::new();
+    for s in sessions {
+        total.add(s);
+        if let Some(date) = session_date(s) {
+            daily.entry(date).or_default().add(s);
+        }
+    }
+
+    let fmt = |v: Option<f64>| v.map_or_else(|| "-".to_string(), |v| format!("{:.1}", v));
+
+    println!("\n📈 累計");
+    println!("  セッション: {}回", total.sessions);
+    println!("  時間: {:.1}時間", total.duration_seconds / 3600.0);
+    println!(
+        "  チャンク: 変換 {} / 失敗 {}（{:.1}%）",
+        total.chunks_converted,
+        total.chunks_failed,
+        total.failure_rate() * 100.0
+    );
+    println!("  平均遅延: {}ms", fmt(total.mean_latency_ms()));
+    println!(
+        "  転送量: 送信 {:.1} MB / 受信 {:.1} MB",
+        total.bytes_sent as f64 / 1_048_576.0,
+        total.bytes_received as f64 / 1_048_576.0
+    );
+
+    println!("\n直近{}日", days);
+    println!(
+        "{:<12} {:>6} {:>8} {:>8} {:>10} {:>10} {:>7}",
+        "日付", "回数", "時間(分)", "チャンク", "平均(ms)", "p95(ms)", "失敗率"
+    );
+    let since = Local::now().date_naive() - chrono::Days::new(days.saturating_sub(1) as u64);
+    for (date, t) in daily.range(since..) {
+        println!(
+            "{:<12} {:>6} {:>8.0} {:>8} {:>10} {:>10} {:>6.1}%",
+            date.to_string(),
+            t.sessions,
+            t.duration_seconds / 60.0,
+            t.chunks_converted,
+            fmt(t.mean_latency_ms()),
+            fmt(t.mean_p95_ms()),
+            t.failure_rate() * 100.0
+        );
+    }
+}
@@ -0,0 +1,139 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::bench::{mean, percentile};
+
+/// 往復遅延の統計（ミリ秒）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LatencySummary {
+    pub mean: f64,
+    pub p50: f64,
+    pub p95: f64,
+    pub p99: f64,
+    pub max: f64,
+}
+
+impl LatencySummary {
+    /// 計測値から集計（計測値がなければNone）
+    pub fn from_samples(latencies_ms: &[f64]) -> Option<Self> {
+        Some(Self {
+            mean: mean(latencies_ms)?,
+            p50: percentile(latencies_ms, 50.0)?,
+            p95: percentile(latencies_ms, 95.0)?,
+            p99: percentile(latencies_ms, 99.0)?,
+            max: percentile(latencies_ms, 100.0)?,
+        })
+    }
+}
+
+/// リアルタイム変換セッションの概要
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionSummary {
+    pub session_id: String,
+    pub model: String,
+    /// 開始時刻（UNIX秒）
+    pub started_at: u64,
+    pub duration_seconds: f64,
+    pub chunks_converted: u64,
+    /// 変換に失敗して捨てたチャンク数
+    pub chunks_failed: u64,
+    /// 無音のため送らなかったチャンク数（`--vad`）
+    #[serde(default)]
+    pub chunks_skipped: u64,
+    pub latency_ms: Option<LatencySummary>,
+    /// 入力バッファのあふれで捨てたサンプル数
+    pub input_dropped_samples: u64,
+    /// 入力バッファがあふれた回数（変換が入力に追いつかなかった）
+    #[serde(default)]
+    pub input_overruns: u64,
+    /// 出力バッファのあふれで捨てたサンプル数
+    #[serde(default)]
+    pub output_dropped_samples: u64,
+    /// 再生中に出力バッファが空になった回数
+    pub output_underruns: u64,
+    pub input_clips: u64,
+    pub output_clips: u64,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    /// 書き出した録音ファイル
+    #[serde(default)]
+    pub recordings: Vec<PathBuf>,
+}
+
+impl SessionSummary {
+    /// 変換に失敗したチャンクの割合
+    pub fn failure_rate(&self) -> f64 {
+        let total = self.chunks_converted + self.chunks_failed;
+        if total == 0 {
+            0.0
+        } else {
+            self.chunks_failed as f64 / total as f64
+        }
+    }
+
+    /// 概要を表示
+    pub fn print(&self) {
+        println!("\n📊 セッション概要");
+        println!("  時間: {:.0}秒", self.duration_seconds);
+        println!("  モデル: {}", self.model);
+        println!(
+            "  チャンク: 変換 {} / 失敗 {}（{:.1}%）",
+            self.chunks_converted,
+            self.chunks_failed,
+            self.failure_rate() * 100.0
+        );
+        if self.chunks_skipped > 0 {
+            println!("  無音で送らなかったチャンク: {}", self.chunks_skipped);
+        }
+        match &self.latency_ms {
+            Some(l) => println!(
+                "  遅延: 平均 {:.1}ms / p50 {:.1}ms / p95 {:.1}ms / p99 {:.1}ms / 最大 {:.1}ms",
+                l.mean, l.p50, l.p95, l.p99, l.max
+            ),
+            None => println!("  遅延: -"),
+        }
+        println!(
+            "  ドロップ: 入力 {}サンプル（{}回） / 出力 {}サンプル / アンダーラン {}回",
+            self.input_dropped_samples,
+            self.input_overruns,
+            self.output_dropped_samples,
+            self.output_underruns
+        );
+        println!(
+            "  クリップ: 入力 {}回 / 出力 {}回",
+            self.input_clips, self.output_clips
+        );
+        println!(
+            "  転送量: 送信 {} / 受信 {}",
+            format_bytes(self.bytes_sent),
+            format_bytes(self.bytes_received)
+        );
+        for path in &self.recordings {
+            println!("  録音: {}", path.display());
+        }
+    }
+
+    /// JSONで書き出す
+    pub fn write_json(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, json)
+            .with_context(|| format!("セッション概要の書き込みエラー: {}", path.display()))
+    }
+}
+
+/// バイト数を読みやすい単位で表示
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
@@ -0,0 +1,286 @@
+use anyhow::{Context, Result};
+use std::io::{Read, Write};
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use tracing::{debug, info, warn};
+
+use crate::backend::{
+    AudioBackend, AudioStream, InputCallback, InputDevice, OutputCallback, OutputDevice,
+};
+
+/// Termuxのデバイスを表す名前（`--input termux`、`--output-device termux`）
+pub const TERMUX_DEVICE: &str = "termux";
+
+/// 入出力のサンプリングレート（Androidの多くの機種のネイティブレート）
+const SAMPLE_RATE: u32 = 48000;
+
+/// 入力のチャンネル数（スマートフォンのマイクはモノラル）
+const INPUT_CHANNELS: u16 = 1;
+
+/// 出力のチャンネル数
+const OUTPUT_CHANNELS: u16 = 2;
+
+/// 要求がない場合のコールバックあたりの長さ（ミリ秒）
+const DEFAULT_BLOCK_MS: u32 = 20;
+
+/// マイク入力に使うPulseAudioのモジュール（OpenSL ES）
+const SOURCE_MODULE: &str = "module-sles-source";
+
+/// Termux（Android）の音声入出力
+///
+/// Termuxのpulseaudioパッケージを経由して、マイクはOpenSL ES（`module-sles-source`）、
+/// 再生はAAudio（`module-aaudio-sink`、Termuxの既定の設定）で入出力します。
+/// 音声は `parec` / `pacat` との間で生PCM（f32le）のパイプで受け渡します。
+/// PulseAudioが起動していなければ起動し、マイクのモジュールを読み込みます。
+pub struct TermuxBackend;
+
+impl AudioBackend for TermuxBackend {
+    fn name(&self) -> &'static str {
+        "termux"
+    }
+
+    fn open_input(&self, _device: Option<&str>) -> Result<Box<dyn InputDevice>> {
+        ensure_pulseaudio(true)?;
+        Ok(Box::new(TermuxInput {
+            buffer_frames: None,
+        }))
+    }
+
+    fn open_output(&self, _device: Option<&str>) -> Result<Box<dyn OutputDevice>> {
+        ensure_pulseaudio(false)?;
+        Ok(Box::new(TermuxOutput {
+            buffer_frames: None,
+        }))
+    }
+}
+
+/// PulseAudioを起動し、入力にはマイクのモジュールを読み込む
+fn ensure_pulseaudio(microphone: bool) -> Result<()> {
+    let running = Command::new("pactl")
+        .arg("info")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .context("pactl が見つかりません。Termuxで pkg install pulseaudio を実行してください")?
+        .success();
+    if !running {
+        info!("PulseAudioを起動します");
+        let status = Command::new("pulseaudio")
+            .args(["--start", "--exit-idle-time=-1"])
+            .status()
+            .context("pulseaudio を起動できません")?;
+        anyhow::ensure!(status.success(), "pulseaudio の起動に失敗しました");
+    }
+
+    if microphone {
+        let modules = Command::new("pactl")
+            .args(["list", "short", "modules"])
+            .output()
+            .context("PulseAudioのモジュール一覧を取得できません")?;
+        if !String::from_utf8_lossy(&modules.stdout).contains(SOURCE_MODULE) {
+            info!("マイク入力のモジュールを読み込みます: {}", SOURCE_MODULE);
+            let status = Command::new("pactl")
+                .args(["load-module", SOURCE_MODULE])
+                .status()
+                .context("pactl load-module に失敗しました")?;
+            anyhow::ensure!(
+                status.success(),
+                "{} を読み込めません。Termux:APIをインストールし、Termuxにマイクの権限を与えてください",
+                SOURCE_MODULE
+            );
+        }
+    }
+    Ok(())
+}
+
+/// `parec` / `pacat` に渡す生PCMの形式
+fn pcm_args(channels: u16, block_frames: u32) -> Vec<String> {
+    vec![
+        "--raw".into(),
+        "--format=float32le".into(),
+        format!("--rate={}", SAMPLE_RATE),
+        format!("--channels={}", channels),
+        format!(
+            "--latency-msec={}",
+            (block_frames as u64 * 1000 / SAMPLE_RATE as u64).max(1)
+        ),
+    ]
+}
+
+fn block_frames(requested: Option<u32>) -> u32 {
+    requested
+        .unwrap_or(SAMPLE_RATE * DEFAULT_BLOCK_MS / 1000)
+        .max(1)
+}
+
+/// パイプの相手のプロセスと受け渡しのスレッド（ドロップで停止）
+struct PipeStream {
+    child: Child,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Drop for PipeStream {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        // プロセスを止めると、パイプの読み書きで待っているスレッドも抜ける
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// マイク入力（`parec` の標準出力を読む）
+pub struct TermuxInput {
+    buffer_frames: Option<u32>,
+}
+
+impl InputDevice for TermuxInput {
+    fn start_stream(&self, mut callback: InputCallback) -> Result<AudioStream> {
+        let frames = block_frames(self.buffer_frames);
+        let mut child = Command::new("parec")
+            .args(pcm_args(INPUT_CHANNELS, frames))
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .context("parec を起動できません")?;
+        let mut stdout = child.stdout.take().context("parec の出力を開けません")?;
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let thread = {
+            let stop = stop.clone();
+            std::thread::Builder::new()
+                .name("termux-input".into())
+                .spawn(move || {
+                    let mut raw = vec![0u8; frames as usize * INPUT_CHANNELS as usize * 4];
+                    let mut samples = Vec::with_capacity(raw.len() / 4);
+                    while !stop.load(Ordering::Relaxed) {
+                        if let Err(e) = stdout.read_exact(&mut raw) {
+                            if !stop.load(Ordering::Relaxed) {
+                                warn!("⚠ Termuxの入力が止まりました: {}", e);
+                            }
+                            return;
+                        }
+                        samples.clear();
+                        samples.extend(
+                            raw.chunks_exact(4)
+                                .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]])),
+                        );
+                        callback(&samples);
+                    }
+                })
+                .context("Termux入力のスレッド起動に失敗")?
+        };
+        debug!("parec: {}フレームごと", frames);
+        info!("Termux入力ストリーム開始");
+
+        Ok(AudioStream::new(PipeStream {
+            child,
+            stop,
+            thread: Some(thread),
+        }))
+    }
+
+    /// 形式は固定で、PulseAudioがデバイスとの違いを吸収する
+    fn needs_rebuild(&self) -> bool {
+        false
+    }
+
+    fn refresh(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn request_buffer_frames(&mut self, frames: u32) {
+        self.buffer_frames = Some(frames);
+    }
+
+    fn name(&self) -> String {
+        format!("{}（OpenSL ES）", TERMUX_DEVICE)
+    }
+
+    fn sample_rate(&self) -> u32 {
+        SAMPLE_RATE
+    }
+
+    fn channels(&self) -> u16 {
+        INPUT_CHANNELS
+    }
+}
+
+/// 再生（`pacat` の標準入力へ書く。書き込みが詰まる速さで再生のペースに合う）
+pub struct TermuxOutput {
+    buffer_frames: Option<u32>,
+}
+
+impl OutputDevice for TermuxOutput {
+    fn start_stream(&self, mut callback: OutputCallback) -> Result<AudioStream> {
+        let frames = block_frames(self.buffer_frames);
+        let mut child = Command::new("pacat")
+            .arg("--playback")
+            .args(pcm_args(OUTPUT_CHANNELS, frames))
+            .stdin(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .context("pacat を起動できません")?;
+        let mut stdin = child.stdin.take().context("pacat の入力を開けません")?;
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let thread = {
+            let stop = stop.clone();
+            std::thread::Builder::new()
+                .name("termux-output".into())
+                .spawn(move || {
+                    let mut block = vec![0.0f32; frames as usize * OUTPUT_CHANNELS as usize];
+                    let mut raw = Vec::with_capacity(block.len() * 4);
+                    while !stop.load(Ordering::Relaxed) {
+                        callback(&mut block);
+                        raw.clear();
+                        raw.extend(block.iter().flat_map(|s| s.to_le_bytes()));
+                        if let Err(e) = stdin.write_all(&raw) {
+                            if !stop.load(Ordering::Relaxed) {
+                                warn!("⚠ Termuxの出力が止まりました: {}", e);
+                            }
+                            return;
+                        }
+                    }
+                })
+                .context("Termux出力のスレッド起動に失敗")?
+        };
+        debug!("pacat: {}フレームごと", frames);
+        info!("Termux出力ストリーム開始");
+
+        Ok(AudioStream::new(PipeStream {
+            child,
+            stop,
+            thread: Some(thread),
+        }))
+    }
+
+    fn needs_rebuild(&self) -> bool {
+        false
+    }
+
+    fn refresh(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn request_buffer_frames(&mut self, frames: u32) {
+        self.buffer_frames = Some(frames);
+    }
+
+    fn name(&self) -> String {
+        format!("{}（AAudio）", TERMUX_DEVICE)
+    }
+
+    fn sample_rate(&self) -> u32 {
+        SAMPLE_RATE
+    }
+
+    fn channels(&self) -> u16 {
+        OUTPUT_CHANNELS
+    }
+}
@@ -0,0 +1,229 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local, Timelike};
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use tracing::info;
+
+use crate::bwf::{self, Bext};
+
+/// 同期マーカーを打つ間隔の既定値（秒、`--sync-interval`）
+pub const DEFAULT_SYNC_INTERVAL_SECS: u32 = 10;
+
+/// タイムコードのフレームレートの既定値（`--timecode-fps`）
+pub const DEFAULT_TIMECODE_FPS: u32 = 30;
+
+/// サイドカーのファイル名に付ける拡張子
+const SIDECAR_SUFFIX: &str = ".timecode.csv";
+
+const SECONDS_PER_DAY: f64 = 24.0 * 60.0 * 60.0;
+
+/// 同期マーカーの書き込み先（`--sync-markers`）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncMarkers {
+    /// WAVの bext チャンクに先頭の時刻（TimeReference）を書く
+    Bwf,
+    /// 一定間隔のタイムコードをサイドカーのCSVに書く
+    Sidecar,
+    /// 両方
+    Both,
+}
+
+impl FromStr for SyncMarkers {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "bwf" => Ok(Self::Bwf),
+            "sidecar" => Ok(Self::Sidecar),
+            "both" => Ok(Self::Both),
+            _ => anyhow::bail!("不明な同期マーカー: {}（bwf, sidecar, both）", s),
+        }
+    }
+}
+
+impl std::fmt::Display for SyncMarkers {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Bwf => "bwf",
+            Self::Sidecar => "sidecar",
+            Self::Both => "both",
+        })
+    }
+}
+
+/// 同期マーカーの設定
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SyncOptions {
+    pub markers: SyncMarkers,
+    /// サイドカーにマーカーを打つ間隔（秒）
+    pub interval_secs: u32,
+    /// タイムコードのフレームレート（ノンドロップ）
+    pub fps: u32,
+}
+
+impl SyncOptions {
+    pub fn new(markers: SyncMarkers, interval_secs: u32, fps: u32) -> Result<Self> {
+        anyhow::ensure!(
+            interval_secs > 0,
+            "--sync-interval は1秒以上を指定してください"
+        );
+        anyhow::ensure!(
+            (1..=120).contains(&fps),
+            "--timecode-fps は1〜120で指定してください"
+        );
+        Ok(Self {
+            markers,
+            interval_secs,
+            fps,
+        })
+    }
+
+    fn bwf(&self) -> bool {
+        matches!(self.markers, SyncMarkers::Bwf | SyncMarkers::Both)
+    }
+
+    fn sidecar(&self) -> bool {
+        matches!(self.markers, SyncMarkers::Sidecar | SyncMarkers::Both)
+    }
+}
+
+/// 音声のフレームと、その時刻（先頭のマーカーの日の0時からの秒）の対応
+#[derive(Debug, Clone)]
+pub struct MarkerLog {
+    sample_rate: u32,
+    interval_frames: u64,
+    next: u64,
+    markers: Vec<(u64, f64)>,
+    /// 先頭のマーカーを打った時刻（録音中の時計）
+    started: Option<(DateTime<Local>, f64)>,
+}
+
+impl MarkerLog {
+    pub fn new(sample_rate: u32, interval_secs: u32) -> Self {
+        Self {
+            sample_rate,
+            interval_frames: sample_rate as u64 * interval_secs as u64,
+            next: 0,
+            markers: Vec::new(),
+            started: None,
+        }
+    }
+
+    /// 録音中に、ここまでに書いたフレーム数と今の時刻を記録する（間隔ごとに1つ）
+    ///
+    /// 時計で打つため、オーディオデバイスのクロックのずれもマーカーに現れます。
+    pub fn observe(&mut self, frames: u64, now: DateTime<Local>) {
+        if frames < self.next {
+            return;
+        }
+        let seconds = match self.started {
+            Some((start, start_seconds)) => {
+                start_seconds + (now - start).num_microseconds().unwrap_or(0) as f64 / 1e6
+            }
+            None => {
+                let seconds = seconds_of_day(&now);
+                self.started = Some((now, seconds));
+                seconds
+            }
+        };
+        self.markers.push((frames, seconds));
+        self.next = frames - frames % self.interval_frames.max(1) + self.interval_frames;
+    }
+
+    /// 先頭が `start_seconds` に始まる `frames` フレームの音声のマーカー（ファイルの変換用）
+    pub fn from_start(
+        start_seconds: f64,
+        frames: u64,
+        sample_rate: u32,
+        interval_secs: u32,
+    ) -> Self {
+        let mut log = Self::new(sample_rate, interval_secs);
+        let step = log.interval_frames.max(1) as usize;
+        log.markers = (0..=frames)
+            .step_by(step)
+            .map(|frame| (frame, start_seconds + frame as f64 / sample_rate as f64))
+            .collect();
+        log
+    }
+
+    /// 先頭のサンプルの時刻（その日の0時からのサンプル数、BWFの TimeReference）
+    pub fn time_reference(&self) -> u64 {
+        self.markers.first().map_or(0, |&(frame, seconds)| {
+            ((seconds * self.sample_rate as f64).round() as u64).saturating_sub(frame)
+        })
+    }
+
+    /// 先頭のサンプルのタイムコード
+    pub fn start_timecode(&self, fps: u32) -> String {
+        format_timecode(self.time_reference() as f64 / self.sample_rate as f64, fps)
+    }
+
+    /// `audio` の横にサイドカーのCSV（フレーム, 秒, タイムコード）を書く
+    pub fn write_sidecar(&self, audio: &Path, fps: u32) -> Result<PathBuf> {
+        let path = sidecar_path(audio);
+        let mut csv = String::from("sample,seconds,timecode\n");
+        for &(frame, seconds) in &self.markers {
+            writeln!(
+                csv,
+                "{},{:.3},{}",
+                frame,
+                frame as f64 / self.sample_rate as f64,
+                format_timecode(seconds, fps)
+            )?;
+        }
+        std::fs::write(&path, csv)
+            .with_context(|| format!("タイムコードの書き込みエラー: {}", path.display()))?;
+        Ok(path)
+    }
+}
+
+/// 書き出した音声に同期マーカーを付ける（bext はWAVのときだけ）
+pub fn apply(audio: &Path, log: &MarkerLog, options: &SyncOptions) -> Result<()> {
+    let wav = audio
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("wav"));
+    if options.bwf() && wav {
+        // 既に書いた説明・コーディングヒストリーは残す
+        let bext = Bext {
+            time_reference: log.time_reference(),
+            ..bwf::read(audio)?.unwrap_or_default()
+        };
+        bwf::write(audio, &bext)?;
+    }
+    if options.sidecar() {
+        log.write_sidecar(audio, options.fps)?;
+    }
+    info!(
+        "  🕒 同期マーカー: {}（開始 {}）",
+        audio.display(),
+        log.start_timecode(options.fps)
+    );
+    Ok(())
+}
+
+/// サイドカーのパス（`take.wav` → `take.wav.timecode.csv`）
+pub fn sidecar_path(audio: &Path) -> PathBuf {
+    let mut path = audio.as_os_str().to_owned();
+    path.push(SIDECAR_SUFFIX);
+    PathBuf::from(path)
+}
+
+/// その日の0時からの秒
+pub fn seconds_of_day(time: &DateTime<Local>) -> f64 {
+    time.num_seconds_from_midnight() as f64 + time.nanosecond().min(999_999_999) as f64 / 1e9
+}
+
+/// `HH:MM:SS:FF`（24時間で一周する）
+pub fn format_timecode(seconds: f64, fps: u32) -> String {
+    let total_frames = (seconds.rem_euclid(SECONDS_PER_DAY) * fps as f64).floor() as u64;
+    let fps = fps.max(1) as u64;
+    let (frames, total_seconds) = (total_frames % fps, total_frames / fps);
+    format!(
+        "{:02}:{:02}:{:02}:{:02}",
+        total_seconds / 3600,
+        total_seconds / 60 % 60,
+        total_seconds % 60,
+        frames
+    )
+}
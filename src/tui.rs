@@ -0,0 +1,449 @@
+use anyhow::{Context, Result};
+use crossterm::{cursor, execute, terminal};
+use ratatui::backend::CrosstermBackend;
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, Gauge, Paragraph, Sparkline};
+use ratatui::{Frame, Terminal};
+use std::collections::VecDeque;
+use std::io::{IsTerminal, Stdout, Write};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+use tracing_subscriber::fmt::MakeWriter;
+
+use crate::client::ResourceStats;
+use crate::dsp::analysis::Level;
+
+/// ダッシュボードを描き直す間隔
+pub const REFRESH_INTERVAL: Duration = Duration::from_millis(200);
+
+/// ログ欄のために残しておく行数
+const LOG_LINES: usize = 200;
+
+/// レベルメーターの左端（dBFS）
+const METER_FLOOR_DB: f32 = -60.0;
+
+/// バッファの残量のゲージの右端（ミリ秒）
+const BUFFER_SCALE_MS: u64 = 1000;
+
+/// `--tui` のときにログを溜める先（[`capture_logs`] で作る）
+static LOGS: OnceLock<LogBuffer> = OnceLock::new();
+
+/// ログの出力先を差し替え、ダッシュボードの表示中はログ欄に溜めるようにする
+///
+/// ダッシュボードを表示していない間（起動中・終了後）は、そのまま標準出力へ書きます。
+pub fn capture_logs() -> LogBuffer {
+    LOGS.get_or_init(LogBuffer::default).clone()
+}
+
+/// ダッシュボードの表示中に出たログの行
+#[derive(Clone, Default)]
+pub struct LogBuffer {
+    inner: Arc<Mutex<LogState>>,
+}
+
+#[derive(Default)]
+struct LogState {
+    lines: VecDeque<String>,
+    /// ダッシュボードを表示しているか（falseなら標準出力へも書く）
+    attached: bool,
+}
+
+impl LogBuffer {
+    fn lock(&self) -> std::sync::MutexGuard<'_, LogState> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn set_attached(&self, attached: bool) {
+        self.lock().attached = attached;
+    }
+
+    /// 直近の `count` 行（古い順）
+    fn tail(&self, count: usize) -> Vec<String> {
+        let state = self.lock();
+        let skip = state.lines.len().saturating_sub(count);
+        state.lines.iter().skip(skip).cloned().collect()
+    }
+
+    fn push(&self, text: &str) {
+        let mut state = self.lock();
+        if !state.attached {
+            let _ = std::io::stdout().write_all(text.as_bytes());
+        }
+        for line in text.lines().filter(|line| !line.is_empty()) {
+            if state.lines.len() == LOG_LINES {
+                state.lines.pop_front();
+            }
+            state.lines.push_back(line.to_string());
+        }
+    }
+}
+
+impl<'a> MakeWriter<'a> for LogBuffer {
+    type Writer = LogWriter;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        LogWriter {
+            buffer: self.clone(),
+            text: Vec::new(),
+        }
+    }
+}
+
+/// 1件のログを受け取り、ドロップでまとめて [`LogBuffer`] へ渡す
+pub struct LogWriter {
+    buffer: LogBuffer,
+    text: Vec<u8>,
+}
+
+impl Write for LogWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.text.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for LogWriter {
+    fn drop(&mut self) {
+        self.buffer.push(&String::from_utf8_lossy(&self.text));
+    }
+}
+
+/// ダッシュボードに表示する、ある時点の状態
+#[derive(Debug, Clone, Default)]
+pub struct DashboardFrame {
+    pub elapsed: Duration,
+    pub model: String,
+    pub pitch: i32,
+    /// 背景ノイズの音量（ノイズなしならNone）
+    pub noise_level: Option<f32>,
+    pub bypass: bool,
+    pub muted: bool,
+    /// 直近の入力チャンクのレベル
+    pub input_level: Level,
+    /// 直近に出力へ送った音声のレベル
+    pub output_level: Level,
+    pub chunk_ms: u32,
+    /// 直近のチャンクの往復遅延（ミリ秒、古い順）
+    pub latencies_ms: Vec<u64>,
+    pub chunks_converted: u64,
+    pub chunks_failed: u64,
+    pub chunks_skipped: u64,
+    /// 入力バッファがあふれて捨てたサンプル数
+    pub input_dropped: u64,
+    /// 出力が途切れた回数
+    pub output_underruns: u64,
+    /// 入力バッファに溜まっている長さ（ミリ秒）
+    pub input_buffered_ms: u64,
+    /// 出力バッファに溜まっている長さ（ミリ秒）
+    pub output_buffered_ms: u64,
+    /// ジッターバッファの深さ（ミリ秒、0なら使っていない）
+    pub jitter_ms: u32,
+    /// サーバーのGPUメモリ・使用率・キュー（まだ分からないか、ローカルエンジンならNone）
+    pub server: Option<ResourceStats>,
+}
+
+/// `monitor --tui` の端末ダッシュボード（ドロップで端末を元に戻す）
+///
+/// 代替画面に描くため、終了後は元の画面に戻ります。表示中のログはログ欄に出し、
+/// 終了時に溜まっていた分を元の画面にも書き出します。キー入力はホットキーがそのまま受け取ります。
+pub struct Dashboard {
+    terminal: Terminal<CrosstermBackend<Stdout>>,
+    logs: LogBuffer,
+}
+
+impl Dashboard {
+    pub fn start() -> Result<Self> {
+        let logs = LOGS
+            .get()
+            .cloned()
+            .context("ログの出力先が設定されていません")?;
+        anyhow::ensure!(
+            std::io::stdout().is_terminal(),
+            "標準出力が端末ではないため、ダッシュボードを表示できません"
+        );
+        let mut stdout = std::io::stdout();
+        execute!(stdout, terminal::EnterAlternateScreen, cursor::Hide)
+            .context("端末を切り替えられません")?;
+        let terminal = Terminal::new(CrosstermBackend::new(stdout))
+            .context("ダッシュボードを開始できません")?;
+        logs.set_attached(true);
+        Ok(Self { terminal, logs })
+    }
+
+    pub fn draw(&mut self, frame: &DashboardFrame) -> Result<()> {
+        let logs = &self.logs;
+        self.terminal
+            .draw(|f| render(f, frame, logs))
+            .context("ダッシュボードの描画エラー")?;
+        Ok(())
+    }
+}
+
+impl Drop for Dashboard {
+    fn drop(&mut self) {
+        let _ = execute!(
+            self.terminal.backend_mut(),
+            terminal::LeaveAlternateScreen,
+            cursor::Show
+        );
+        self.logs.set_attached(false);
+        let mut stdout = std::io::stdout();
+        for line in self.logs.tail(LOG_LINES) {
+            let _ = writeln!(stdout, "{}", line);
+        }
+    }
+}
+
+fn render(f: &mut Frame, frame: &DashboardFrame, logs: &LogBuffer) {
+    let [status, input, output, latency, chunks, server, buffers, log] = Layout::vertical([
+        Constraint::Length(1),
+        Constraint::Length(3),
+        Constraint::Length(3),
+        Constraint::Length(6),
+        Constraint::Length(1),
+        Constraint::Length(1),
+        Constraint::Length(3),
+        Constraint::Min(3),
+    ])
+    .areas(f.area());
+
+    f.render_widget(Paragraph::new(status_line(frame)), status);
+    render_meter(f, input, "入力", frame.input_level);
+    render_meter(f, output, "出力", frame.output_level);
+    render_latency(f, latency, frame);
+    f.render_widget(Paragraph::new(chunk_line(frame)), chunks);
+    f.render_widget(Paragraph::new(server_line(frame.server.as_ref())), server);
+
+    let [input_buffer, output_buffer] =
+        Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)]).areas(buffers);
+    render_buffer(
+        f,
+        input_buffer,
+        "入力バッファ",
+        frame.input_buffered_ms,
+        None,
+    );
+    render_buffer(
+        f,
+        output_buffer,
+        "出力バッファ",
+        frame.output_buffered_ms,
+        (frame.jitter_ms > 0).then_some(frame.jitter_ms),
+    );
+
+    let height = log.height.saturating_sub(2) as usize;
+    let lines: Vec<Line> = logs.tail(height).into_iter().map(Line::from).collect();
+    f.render_widget(
+        Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title("ログ")),
+        log,
+    );
+}
+
+fn status_line(frame: &DashboardFrame) -> Line<'static> {
+    let seconds = frame.elapsed.as_secs();
+    let mut spans = vec![
+        Span::styled(
+            format!(
+                " makebeliv monitor {:02}:{:02}:{:02} ",
+                seconds / 3600,
+                seconds / 60 % 60,
+                seconds % 60
+            ),
+            Style::default().add_modifier(Modifier::REVERSED),
+        ),
+        Span::raw(format!(
+            "  モデル {}  ピッチ {:+}  ",
+            frame.model, frame.pitch
+        )),
+        Span::raw(match frame.noise_level {
+            Some(level) => format!("ノイズ {}", level),
+            None => "ノイズなし".to_string(),
+        }),
+    ];
+    if frame.bypass {
+        spans.push(Span::styled(
+            "  [バイパス]",
+            Style::default().fg(Color::Yellow),
+        ));
+    }
+    if frame.muted {
+        spans.push(Span::styled(
+            "  [ミュート]",
+            Style::default().fg(Color::Red),
+        ));
+    }
+    Line::from(spans)
+}
+
+/// RMSをゲージで、ピークを数値で表示する（クリップ寸前は赤）
+fn render_meter(f: &mut Frame, area: Rect, title: &str, level: Level) {
+    let ratio = ((level.rms_db - METER_FLOOR_DB) / -METER_FLOOR_DB).clamp(0.0, 1.0);
+    let color = if level.peak_db >= -1.0 {
+        Color::Red
+    } else if level.peak_db >= -6.0 {
+        Color::Yellow
+    } else {
+        Color::Green
+    };
+    f.render_widget(
+        Gauge::default()
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(title.to_string()),
+            )
+            .gauge_style(Style::default().fg(color))
+            .ratio(ratio as f64)
+            .label(level.to_string()),
+        area,
+    );
+}
+
+/// チャンクごとの往復遅延（チャンク長を超えていれば変換が追いついていない）
+fn render_latency(f: &mut Frame, area: Rect, frame: &DashboardFrame) {
+    let last = frame.latencies_ms.last().copied();
+    let behind = last.is_some_and(|ms| ms > frame.chunk_ms as u64);
+    let title = match last {
+        Some(ms) => format!(
+            "往復遅延 {}ms（チャンク長 {}ms{}）",
+            ms,
+            frame.chunk_ms,
+            if behind {
+                "、追いついていません"
+            } else {
+                ""
+            }
+        ),
+        None => format!("往復遅延 -（チャンク長 {}ms）", frame.chunk_ms),
+    };
+    let max = frame
+        .latencies_ms
+        .iter()
+        .copied()
+        .max()
+        .unwrap_or(0)
+        .max(frame.chunk_ms as u64);
+    // 右端を最新にするため、幅に収まる分だけ渡す
+    let width = area.width.saturating_sub(2) as usize;
+    let data = &frame.latencies_ms[frame.latencies_ms.len().saturating_sub(width)..];
+    f.render_widget(
+        Sparkline::default()
+            .block(Block::default().borders(Borders::ALL).title(title))
+            .style(Style::default().fg(if behind { Color::Red } else { Color::Cyan }))
+            .max(max)
+            .data(data),
+        area,
+    );
+}
+
+fn chunk_line(frame: &DashboardFrame) -> Line<'static> {
+    let warn = |count: u64| {
+        if count > 0 {
+            Style::default().fg(Color::Red)
+        } else {
+            Style::default()
+        }
+    };
+    Line::from(vec![
+        Span::raw(format!(
+            " チャンク: 変換 {} / 無音 {} / ",
+            frame.chunks_converted, frame.chunks_skipped
+        )),
+        Span::styled(
+            format!("失敗 {}", frame.chunks_failed),
+            warn(frame.chunks_failed),
+        ),
+        Span::raw(" / "),
+        Span::styled(
+            format!("入力の取りこぼし {}サンプル", frame.input_dropped),
+            warn(frame.input_dropped),
+        ),
+        Span::raw(" / "),
+        Span::styled(
+            format!("出力の途切れ {}回", frame.output_underruns),
+            warn(frame.output_underruns),
+        ),
+    ])
+}
+
+/// サーバーのリソース使用状況（逼迫していれば赤）
+fn server_line(server: Option<&ResourceStats>) -> Line<'static> {
+    match server {
+        Some(stats) => Line::from(Span::styled(
+            format!(" サーバー: {}", stats.summary()),
+            if stats.is_under_pressure() {
+                Style::default().fg(Color::Red)
+            } else {
+                Style::default()
+            },
+        )),
+        None => Line::from(" サーバー: -"),
+    }
+}
+
+fn render_buffer(f: &mut Frame, area: Rect, title: &str, ms: u64, jitter_ms: Option<u32>) {
+    let label = match jitter_ms {
+        Some(depth) => format!("{}ms（ジッターバッファ {}ms）", ms, depth),
+        None => format!("{}ms", ms),
+    };
+    f.render_widget(
+        Gauge::default()
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(title.to_string()),
+            )
+            .gauge_style(Style::default().fg(Color::Blue))
+            .ratio((ms.min(BUFFER_SCALE_MS) as f64) / BUFFER_SCALE_MS as f64)
+            .label(label),
+        area,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(line: &Line) -> String {
+        line.spans
+            .iter()
+            .map(|span| span.content.as_ref())
+            .collect()
+    }
+
+    #[test]
+    fn server_line_shows_gpu_and_queue() {
+        let stats = ResourceStats {
+            queue_depth: 0,
+            gpu_memory_used_mb: Some(2048.0),
+            gpu_memory_total_mb: Some(8192.0),
+            gpu_utilization_percent: Some(35.0),
+            cpu_load_percent: None,
+        };
+        let line = server_line(Some(&stats));
+        assert_eq!(
+            text(&line),
+            " サーバー: GPUメモリ 2048/8192MB | GPU使用率 35% | キュー 0"
+        );
+        assert_eq!(line.spans[0].style.fg, None);
+        assert_eq!(text(&server_line(None)), " サーバー: -");
+    }
+
+    #[test]
+    fn server_line_is_red_when_the_gpu_falls_behind() {
+        let stats = ResourceStats {
+            queue_depth: 3,
+            ..ResourceStats::default()
+        };
+        assert_eq!(
+            server_line(Some(&stats)).spans[0].style.fg,
+            Some(Color::Red)
+        );
+    }
+}
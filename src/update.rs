@@ -0,0 +1,300 @@
+use anyhow::{Context, Result};
+use semver::Version;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::io::{Read, Write};
+use tracing::info;
+
+/// リリースを取得するGitHubリポジトリ
+const REPOSITORY: &str = "kako-jun/makebeliv";
+
+/// チェックサム一覧のアセット名
+const CHECKSUMS_ASSET: &str = "SHA256SUMS";
+
+/// チェックサム一覧の署名（minisign）のアセット名
+const SIGNATURE_ASSET: &str = "SHA256SUMS.minisig";
+
+/// リリースの署名を確かめる公開鍵（minisign。ビルド時に `MAKEBELIV_UPDATE_PUBLIC_KEY` で埋め込む）
+///
+/// 埋め込まずにビルドした実行ファイルはセルフアップデートできません。
+const PUBLIC_KEY: Option<&str> = option_env!("MAKEBELIV_UPDATE_PUBLIC_KEY");
+
+/// 現在のバージョン
+pub const CURRENT_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+#[derive(Debug, Deserialize)]
+struct Release {
+    tag_name: String,
+    assets: Vec<Asset>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Asset {
+    name: String,
+    browser_download_url: String,
+}
+
+/// 利用可能なアップデート
+pub struct AvailableUpdate {
+    pub version: Version,
+    archive_url: String,
+    checksums_url: String,
+    signature_url: String,
+    archive_name: String,
+}
+
+/// このプラットフォーム向けのアーカイブ名（例: makebeliv-x86_64-linux.tar.gz）
+pub fn archive_name() -> String {
+    format!(
+        "makebeliv-{}-{}.tar.gz",
+        std::env::consts::ARCH,
+        std::env::consts::OS
+    )
+}
+
+/// アーカイブ内の実行ファイル名
+fn binary_name() -> String {
+    format!("makebeliv{}", std::env::consts::EXE_SUFFIX)
+}
+
+fn http_client() -> Result<reqwest::Client> {
+    reqwest::Client::builder()
+        .user_agent(format!("makebeliv/{}", CURRENT_VERSION))
+        .build()
+        .context("HTTPクライアントの作成に失敗")
+}
+
+/// 最新リリースを確認し、新しいバージョンがあれば返す
+pub async fn check() -> Result<Option<AvailableUpdate>> {
+    let client = http_client()?;
+    let url = format!(
+        "https://api.github.com/repos/{}/releases/latest",
+        REPOSITORY
+    );
+
+    let release: Release = client
+        .get(&url)
+        .send()
+        .await
+        .context("リリース情報の取得エラー")?
+        .error_for_status()
+        .context("リリース情報の取得エラー")?
+        .json()
+        .await
+        .context("リリース情報の解析エラー")?;
+
+    let latest = Version::parse(release.tag_name.trim_start_matches('v'))
+        .with_context(|| format!("不正なバージョンタグ: {}", release.tag_name))?;
+    let current = Version::parse(CURRENT_VERSION)?;
+
+    if latest <= current {
+        return Ok(None);
+    }
+
+    let archive_name = archive_name();
+    let find_asset = |name: &str| {
+        release
+            .assets
+            .iter()
+            .find(|a| a.name == name)
+            .map(|a| a.browser_download_url.clone())
+            .with_context(|| format!("リリース {} にアセット {} がありません", latest, name))
+    };
+
+    Ok(Some(AvailableUpdate {
+        archive_url: find_asset(&archive_name)?,
+        checksums_url: find_asset(CHECKSUMS_ASSET)?,
+        signature_url: find_asset(SIGNATURE_ASSET)?,
+        archive_name,
+        version: latest,
+    }))
+}
+
+/// アップデートをダウンロード・検証して実行ファイルを置き換える
+///
+/// チェックサム一覧の署名を埋め込みの公開鍵で確かめ、一致しなければ何も置き換えません。
+pub async fn install(update: &AvailableUpdate) -> Result<()> {
+    let public_key = PUBLIC_KEY.context(
+        "このビルドには署名を確かめる公開鍵が埋め込まれていないため、セルフアップデートできません（リリース版を使うか、手動で更新してください）",
+    )?;
+    let client = http_client()?;
+
+    info!("ダウンロード中: {}", update.archive_url);
+    let archive = download(&client, &update.archive_url).await?;
+    let checksums = download(&client, &update.checksums_url).await?;
+    let signature = download(&client, &update.signature_url).await?;
+
+    let signature = String::from_utf8(signature).context("署名の形式が不正です")?;
+    verify_signature(public_key, &checksums, &signature)?;
+    info!("  ✓ 署名検証OK");
+
+    let checksums = String::from_utf8(checksums).context("チェックサム一覧の形式が不正です")?;
+    let expected = expected_checksum(&checksums, &update.archive_name)
+        .with_context(|| format!("{} のチェックサムが見つかりません", update.archive_name))?;
+    let actual = format!("{:x}", Sha256::digest(&archive));
+
+    if !actual.eq_ignore_ascii_case(expected) {
+        anyhow::bail!(
+            "チェックサムが一致しません（期待値: {}, 実際: {}）",
+            expected,
+            actual
+        );
+    }
+    info!("  ✓ チェックサム検証OK");
+
+    let binary = extract_binary(&archive)?;
+    replace_executable(&binary)?;
+
+    info!("  ✓ makebeliv {} に更新しました", update.version);
+
+    Ok(())
+}
+
+/// チェックサム一覧の minisign の署名を `public_key`（base64）で確かめる
+fn verify_signature(public_key: &str, checksums: &[u8], signature: &str) -> Result<()> {
+    let public_key = minisign_verify::PublicKey::from_base64(public_key)
+        .context("埋め込みの公開鍵が不正です")?;
+    let signature =
+        minisign_verify::Signature::decode(signature).context("署名の形式が不正です")?;
+    public_key.verify(checksums, &signature, false).context(
+        "チェックサム一覧の署名を確かめられません（改ざんされたか、別の鍵で署名されています）",
+    )
+}
+
+/// 新しい実行ファイルを同じディレクトリの一時ファイルに書き、今の実行ファイルと置き換える
+///
+/// 同じファイルシステム内の名前の変更なので、途中で止まっても壊れた実行ファイルは残りません。
+fn replace_executable(binary: &[u8]) -> Result<()> {
+    let exe = std::env::current_exe().context("実行ファイルの場所が分かりません")?;
+    // シンボリックリンクなら、リンク先を置き換える
+    let exe = exe.canonicalize().unwrap_or(exe);
+    let dir = exe
+        .parent()
+        .context("実行ファイルのディレクトリが分かりません")?;
+
+    let mut temp = tempfile::NamedTempFile::new_in(dir)
+        .with_context(|| format!("一時ファイルを作成できません: {}", dir.display()))?;
+    temp.write_all(binary)
+        .and_then(|()| temp.as_file().sync_all())
+        .context("一時ファイルの書き込みエラー")?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(temp.path(), std::fs::Permissions::from_mode(0o755))?;
+        temp.persist(&exe)
+            .map_err(|e| e.error)
+            .with_context(|| format!("実行ファイルの置き換えに失敗: {}", exe.display()))?;
+    }
+    // 実行中のファイルは名前を変えて上書きできないため、self-replace に任せる
+    #[cfg(not(unix))]
+    self_replace::self_replace(temp.path()).context("実行ファイルの置き換えに失敗")?;
+
+    Ok(())
+}
+
+async fn download(client: &reqwest::Client, url: &str) -> Result<Vec<u8>> {
+    let bytes = client
+        .get(url)
+        .send()
+        .await
+        .with_context(|| format!("ダウンロードエラー: {}", url))?
+        .error_for_status()
+        .with_context(|| format!("ダウンロードエラー: {}", url))?
+        .bytes()
+        .await
+        .with_context(|| format!("ダウンロードエラー: {}", url))?;
+
+    Ok(bytes.to_vec())
+}
+
+/// `sha256sum` 形式（"<hex>  <ファイル名>"）から該当ファイルのハッシュを探す
+fn expected_checksum<'a>(checksums: &'a str, file_name: &str) -> Option<&'a str> {
+    checksums.lines().find_map(|line| {
+        let mut parts = line.split_whitespace();
+        let hash = parts.next()?;
+        let name = parts.next()?.trim_start_matches('*');
+        (name == file_name).then_some(hash)
+    })
+}
+
+/// tar.gzアーカイブから実行ファイルを取り出す
+fn extract_binary(archive: &[u8]) -> Result<Vec<u8>> {
+    let binary_name = binary_name();
+    let mut tar = tar::Archive::new(flate2::read::GzDecoder::new(archive));
+
+    for entry in tar.entries().context("アーカイブの読み込みエラー")? {
+        let mut entry = entry?;
+        let is_binary = entry
+            .path()?
+            .file_name()
+            .is_some_and(|name| name == binary_name.as_str());
+
+        if is_binary {
+            let mut data = Vec::new();
+            entry.read_to_end(&mut data)?;
+            return Ok(data);
+        }
+    }
+
+    anyhow::bail!("アーカイブに {} が含まれていません", binary_name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// テスト用の鍵で署名したチェックサム一覧（minisign の形式）
+    const TEST_PUBLIC_KEY: &str = "RWTPijE6s9iTJUllWYF/YfFAw/9xlNumvZ4oi4YscpNghfweTW2O5NV1";
+    const OTHER_PUBLIC_KEY: &str = "RWRmMz5NfKaPMu799NDMrd+ya+DOJnDr4xlT9Y1qkBqiYygeeG4YReSG";
+    const CHECKSUMS: &str = "0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef  makebeliv-x86_64-linux.tar.gz\n";
+    const SIGNATURE: &str = "untrusted comment: signature from minisign secret key
+RUTPijE6s9iTJWyfpgALw8DuLxZyQcc0VUIJGG5TBF37d8IVx0rOGHcJyGHVJgSYnXpMuW2/hcOcn9pBaDn+/1gJqhn3w2LCIwk=
+trusted comment: timestamp:1760659200\tfile:SHA256SUMS
+TQrfQqB9NuqUwwqQt1+Dk6kEqcNDsVwvJ6qqGNSELTH9B9pCdkefwbaBgISUK9DA4/pncfyFsSlbIsWfja6UAg==
+";
+
+    #[test]
+    fn accepts_a_valid_signature() {
+        verify_signature(TEST_PUBLIC_KEY, CHECKSUMS.as_bytes(), SIGNATURE).unwrap();
+    }
+
+    #[test]
+    fn rejects_tampered_checksums() {
+        let tampered = CHECKSUMS.replacen('0', "f", 1);
+        assert!(verify_signature(TEST_PUBLIC_KEY, tampered.as_bytes(), SIGNATURE).is_err());
+    }
+
+    #[test]
+    fn rejects_another_key() {
+        assert!(verify_signature(OTHER_PUBLIC_KEY, CHECKSUMS.as_bytes(), SIGNATURE).is_err());
+    }
+
+    #[test]
+    fn rejects_a_tampered_trusted_comment() {
+        let signature = SIGNATURE.replace("file:SHA256SUMS", "file:OTHER");
+        assert!(verify_signature(TEST_PUBLIC_KEY, CHECKSUMS.as_bytes(), &signature).is_err());
+    }
+
+    #[test]
+    fn rejects_malformed_signatures_and_keys() {
+        for signature in ["", "untrusted comment: x\nnot base64\n", "<html>404</html>"] {
+            assert!(verify_signature(TEST_PUBLIC_KEY, CHECKSUMS.as_bytes(), signature).is_err());
+        }
+        assert!(verify_signature("RWTshort", CHECKSUMS.as_bytes(), SIGNATURE).is_err());
+    }
+
+    #[test]
+    fn finds_the_archive_checksum() {
+        let checksums =
+            "aaaa  makebeliv-aarch64-linux.tar.gz\nbbbb *makebeliv-x86_64-linux.tar.gz\n";
+        assert_eq!(
+            expected_checksum(checksums, "makebeliv-x86_64-linux.tar.gz"),
+            Some("bbbb")
+        );
+        assert_eq!(
+            expected_checksum(checksums, "makebeliv-x86_64-macos.tar.gz"),
+            None
+        );
+    }
+}
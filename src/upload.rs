@@ -0,0 +1,386 @@
+use anyhow::{Context, Result};
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio_util::io::ReaderStream;
+use tracing::{info, warn};
+
+use crate::client;
+use crate::config::UploadConfig;
+use crate::retry::RetryPolicy;
+
+/// 録音のアップロード先
+#[derive(Debug, Clone)]
+enum Destination {
+    /// `url` の末尾にファイル名を付けてPUTする
+    Put {
+        url: String,
+        headers: Vec<(String, String)>,
+    },
+    /// S3互換ストレージ（署名V4、パス形式のURL）
+    S3 {
+        endpoint: String,
+        region: String,
+        bucket: String,
+        prefix: String,
+        credentials: Credentials,
+    },
+}
+
+/// S3の認証情報（環境変数から読む）
+#[derive(Clone)]
+struct Credentials {
+    access_key_id: String,
+    secret_access_key: String,
+    session_token: Option<String>,
+}
+
+impl std::fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // 秘密鍵はログに出さない
+        f.debug_struct("Credentials")
+            .field("access_key_id", &self.access_key_id)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Credentials {
+    fn from_env() -> Result<Self> {
+        let var = |name: &str| std::env::var(name).ok().filter(|value| !value.is_empty());
+        Ok(Self {
+            access_key_id: var("AWS_ACCESS_KEY_ID")
+                .context("S3へのアップロードには環境変数 AWS_ACCESS_KEY_ID が必要です")?,
+            secret_access_key: var("AWS_SECRET_ACCESS_KEY")
+                .context("S3へのアップロードには環境変数 AWS_SECRET_ACCESS_KEY が必要です")?,
+            session_token: var("AWS_SESSION_TOKEN"),
+        })
+    }
+}
+
+/// アップロードの結果
+#[derive(Debug, Default)]
+pub struct UploadReport {
+    pub uploaded: Vec<PathBuf>,
+    pub failed: Vec<(PathBuf, String)>,
+}
+
+/// セッション録音のアップロード（`[recording.upload]`）
+///
+/// ファイルを1つずつ送り、送り直せば通りうる失敗は指数バックオフで再試行します。
+/// 失敗したファイルは手元に残して次へ進みます。
+#[derive(Debug)]
+pub struct Uploader {
+    http: reqwest::Client,
+    destination: Destination,
+    retry: RetryPolicy,
+    delete_after_upload: bool,
+}
+
+impl Uploader {
+    /// 設定からアップロード先を作る（アップロード先がなければNone）
+    ///
+    /// 認証情報の不足や書式の誤りは、録音を始める前に分かるようここでエラーにします。
+    pub fn from_config(config: &UploadConfig) -> Result<Option<Self>> {
+        let Some(target) = config.destination.as_deref().filter(|d| !d.is_empty()) else {
+            return Ok(None);
+        };
+
+        let destination = if let Some(path) = target.strip_prefix("s3://") {
+            let (bucket, prefix) = path.split_once('/').unwrap_or((path, ""));
+            anyhow::ensure!(!bucket.is_empty(), "S3のバケット名がありません: {}", target);
+            let endpoint = config
+                .endpoint
+                .clone()
+                .unwrap_or_else(|| format!("https://s3.{}.amazonaws.com", config.region));
+            Destination::S3 {
+                endpoint: endpoint.trim_end_matches('/').to_string(),
+                region: config.region.clone(),
+                bucket: bucket.to_string(),
+                prefix: with_trailing_slash(prefix),
+                credentials: Credentials::from_env()?,
+            }
+        } else if target.starts_with("http://") || target.starts_with("https://") {
+            let headers = config
+                .headers
+                .iter()
+                .map(|(name, value)| Ok((name.clone(), expand_env(value)?)))
+                .collect::<Result<Vec<_>>>()?;
+            Destination::Put {
+                url: with_trailing_slash(target),
+                headers,
+            }
+        } else {
+            anyhow::bail!(
+                "不明なアップロード先: {}（s3://<バケット>/... か https://...）",
+                target
+            );
+        };
+
+        // 長い録音も送れるよう、リクエスト全体のタイムアウトは付けない
+        let http = reqwest::Client::builder()
+            .connect_timeout(client::timeouts().connect)
+            .build()
+            .context("HTTPクライアントの作成エラー")?;
+
+        Ok(Some(Self {
+            http,
+            destination,
+            retry: RetryPolicy {
+                attempts: config.attempts,
+                backoff: Duration::from_secs(1),
+                max_backoff: Duration::from_secs(60),
+                ..RetryPolicy::default()
+            },
+            delete_after_upload: config.delete_after_upload,
+        }))
+    }
+
+    /// アップロード先（ログ用）
+    pub fn describe(&self) -> String {
+        match &self.destination {
+            Destination::Put { url, .. } => url.clone(),
+            Destination::S3 {
+                endpoint,
+                bucket,
+                prefix,
+                ..
+            } => format!("s3://{}/{}（{}）", bucket, prefix, endpoint),
+        }
+    }
+
+    /// 録音ファイルを順に送る
+    pub async fn upload_all(&self, paths: &[PathBuf]) -> UploadReport {
+        let mut report = UploadReport::default();
+        for path in paths {
+            let name = file_name(path);
+            let result = self
+                .retry
+                .run(
+                    &format!("録音のアップロード（{}）", name),
+                    || self.upload(path),
+                )
+                .await;
+            match result {
+                Ok(()) => {
+                    info!("☁ 録音をアップロードしました: {}", name);
+                    if self.delete_after_upload {
+                        if let Err(e) = std::fs::remove_file(path) {
+                            warn!(
+                                "⚠ アップロードした録音を削除できません: {}: {}",
+                                path.display(),
+                                e
+                            );
+                        }
+                    }
+                    report.uploaded.push(path.clone());
+                }
+                Err(e) => {
+                    warn!("⚠ 録音のアップロードに失敗しました: {}: {:#}", name, e);
+                    report.failed.push((path.clone(), format!("{:#}", e)));
+                }
+            }
+        }
+        report
+    }
+
+    async fn upload(&self, path: &Path) -> Result<()> {
+        let name = file_name(path);
+        let length = tokio::fs::metadata(path)
+            .await
+            .with_context(|| format!("録音ファイルの読み込みエラー: {}", path.display()))?
+            .len();
+
+        let request = match &self.destination {
+            Destination::Put { url, headers } => {
+                let mut request = self.http.put(format!("{}{}", url, uri_encode(&name)));
+                for (header, value) in headers {
+                    request = request.header(header.as_str(), value.as_str());
+                }
+                request
+            }
+            Destination::S3 {
+                endpoint,
+                region,
+                bucket,
+                prefix,
+                credentials,
+            } => {
+                let key = format!("{}{}", prefix, name);
+                let url = reqwest::Url::parse(&format!(
+                    "{}/{}/{}",
+                    endpoint,
+                    uri_encode(bucket),
+                    key.split('/').map(uri_encode).collect::<Vec<_>>().join("/")
+                ))
+                .with_context(|| format!("S3のエンドポイントが不正です: {}", endpoint))?;
+                let payload_hash = file_sha256(path).await?;
+                let signed = sign_v4(&url, region, credentials, &payload_hash, chrono::Utc::now())?;
+                let mut request = self.http.put(url);
+                for (header, value) in signed {
+                    request = request.header(header, value);
+                }
+                request
+            }
+        };
+
+        // 送り直すたびにファイルを先頭から読み直す
+        let file = tokio::fs::File::open(path)
+            .await
+            .with_context(|| format!("録音ファイルの読み込みエラー: {}", path.display()))?;
+        request
+            .header(reqwest::header::CONTENT_LENGTH, length)
+            .header(reqwest::header::CONTENT_TYPE, content_type(path))
+            .body(reqwest::Body::wrap_stream(ReaderStream::new(file)))
+            .send()
+            .await
+            .context("アップロードのリクエストエラー")?
+            .error_for_status()
+            .context("アップロードのリクエストエラー")?;
+        Ok(())
+    }
+}
+
+/// S3の署名V4のヘッダー（Authorization ほか）
+fn sign_v4(
+    url: &reqwest::Url,
+    region: &str,
+    credentials: &Credentials,
+    payload_hash: &str,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Result<Vec<(&'static str, String)>> {
+    let host = match (url.host_str(), url.port()) {
+        (Some(host), Some(port)) => format!("{}:{}", host, port),
+        (Some(host), None) => host.to_string(),
+        (None, _) => anyhow::bail!("S3のエンドポイントにホストがありません: {}", url),
+    };
+    let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+    let date = now.format("%Y%m%d").to_string();
+    let scope = format!("{}/{}/s3/aws4_request", date, region);
+
+    // 署名するヘッダー（名前の順に並べる）
+    let mut headers = vec![
+        ("host", host),
+        ("x-amz-content-sha256", payload_hash.to_string()),
+        ("x-amz-date", amz_date.clone()),
+    ];
+    if let Some(token) = &credentials.session_token {
+        headers.push(("x-amz-security-token", token.clone()));
+    }
+    let canonical_headers: String = headers
+        .iter()
+        .map(|(name, value)| format!("{}:{}\n", name, value.trim()))
+        .collect();
+    let signed_headers = headers
+        .iter()
+        .map(|(name, _)| *name)
+        .collect::<Vec<_>>()
+        .join(";");
+
+    let canonical_request = format!(
+        "PUT\n{}\n\n{}\n{}\n{}",
+        url.path(),
+        canonical_headers,
+        signed_headers,
+        payload_hash
+    );
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{:x}",
+        amz_date,
+        scope,
+        Sha256::digest(canonical_request.as_bytes())
+    );
+
+    let key = [date.as_str(), region, "s3", "aws4_request"].iter().fold(
+        format!("AWS4{}", credentials.secret_access_key).into_bytes(),
+        |key, part| hmac_sha256(&key, part.as_bytes()),
+    );
+    let signature: String = hmac_sha256(&key, string_to_sign.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+
+    headers.retain(|(name, _)| *name != "host");
+    headers.push((
+        "authorization",
+        format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+            credentials.access_key_id, scope, signed_headers, signature
+        ),
+    ));
+    Ok(headers)
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMACは任意の長さの鍵を受け付ける");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// ファイルのSHA-256（16進）。大きな録音でもメモリに載せないよう少しずつ読む
+async fn file_sha256(path: &Path) -> Result<String> {
+    let path = path.to_path_buf();
+    tokio::task::spawn_blocking(move || -> Result<String> {
+        let mut file = std::fs::File::open(&path)
+            .with_context(|| format!("録音ファイルの読み込みエラー: {}", path.display()))?;
+        let mut hasher = Sha256::new();
+        let mut buffer = vec![0u8; 1 << 16];
+        loop {
+            let read = file
+                .read(&mut buffer)
+                .with_context(|| format!("録音ファイルの読み込みエラー: {}", path.display()))?;
+            if read == 0 {
+                break;
+            }
+            hasher.update(&buffer[..read]);
+        }
+        Ok(format!("{:x}", hasher.finalize()))
+    })
+    .await
+    .context("ハッシュ計算の実行エラー")?
+}
+
+/// URLのパスの1要素として使えるようにする（S3の署名と同じ規則）
+fn uri_encode(segment: &str) -> String {
+    segment
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+/// `$NAME` と書かれた値を環境変数から読む
+fn expand_env(value: &str) -> Result<String> {
+    match value.strip_prefix('$') {
+        Some(name) => std::env::var(name)
+            .with_context(|| format!("アップロードのヘッダーに使う環境変数 {} がありません", name)),
+        None => Ok(value.to_string()),
+    }
+}
+
+fn with_trailing_slash(path: &str) -> String {
+    if path.is_empty() || path.ends_with('/') {
+        path.to_string()
+    } else {
+        format!("{}/", path)
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| path.display().to_string())
+}
+
+fn content_type(path: &Path) -> &'static str {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("wav") => "audio/wav",
+        Some("opus") => "audio/ogg",
+        _ => "application/octet-stream",
+    }
+}
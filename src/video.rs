@@ -0,0 +1,106 @@
+use anyhow::{Context, Result};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use tracing::info;
+
+use crate::shutdown;
+
+/// 動画として扱う拡張子（小文字）
+pub const VIDEO_EXTENSIONS: &[&str] = &["mp4", "m4v", "mov", "mkv", "webm"];
+
+/// 戻す音声のビットレート（AAC・Opusで書き直すとき）
+const AUDIO_BITRATE: &str = "192k";
+
+/// 拡張子が動画か（`process` の入力・出力）
+pub fn is_video(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| VIDEO_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+}
+
+/// ffmpeg が使えるか確かめる
+pub fn ensure_ffmpeg() -> Result<()> {
+    let found = Command::new("ffmpeg")
+        .arg("-version")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success());
+    anyhow::ensure!(
+        found,
+        "ffmpeg が見つかりません。動画の音声を取り出して戻すには ffmpeg をインストールしてください"
+    );
+    Ok(())
+}
+
+/// 動画の最初の音声トラックを、元のサンプリングレート・チャンネル数のまま32bit floatのWAVに取り出す
+pub async fn extract_audio(video: &Path, wav: &Path) -> Result<()> {
+    info!("🎬 動画から音声を取り出します: {}", video.display());
+    create_parent(wav)?;
+    let mut command = ffmpeg();
+    command
+        .arg("-i")
+        .arg(video)
+        .args(["-map", "0:a:0", "-vn", "-c:a", "pcm_f32le"])
+        .arg(wav);
+    run(command)
+        .await
+        .with_context(|| format!("動画から音声を取り出せません: {}", video.display()))
+}
+
+/// 変換した音声を元の動画に戻して `output` に書き出す
+///
+/// 映像・字幕・チャプター・メタデータは再エンコードせずにそのまま写し、
+/// 最初の音声トラックだけを `audio` に置き換えます（ほかの音声トラックは残しません）。
+/// 音声はコンテナに合わせて、WebMならOpus、それ以外はAACで書き込みます。
+pub async fn remux(video: &Path, audio: &Path, output: &Path) -> Result<()> {
+    create_parent(output)?;
+    let codec = match output.extension().and_then(|ext| ext.to_str()) {
+        Some(ext) if ext.eq_ignore_ascii_case("webm") => "libopus",
+        _ => "aac",
+    };
+    let mut command = ffmpeg();
+    command
+        .arg("-i")
+        .arg(video)
+        .arg("-i")
+        .arg(audio)
+        .args(["-map", "0:v?", "-map", "1:a:0", "-map", "0:s?"])
+        .args(["-map_metadata", "0", "-map_chapters", "0"])
+        .args([
+            "-c:v",
+            "copy",
+            "-c:s",
+            "copy",
+            "-c:a",
+            codec,
+            "-b:a",
+            AUDIO_BITRATE,
+        ])
+        .arg(output);
+    run(command)
+        .await
+        .with_context(|| format!("変換した音声を動画に戻せません: {}", output.display()))?;
+    info!("🎬 映像はそのまま、音声を差し替えました（{}）", codec);
+    Ok(())
+}
+
+fn create_parent(path: &Path) -> Result<()> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent).context("出力ディレクトリの作成エラー")?;
+    }
+    Ok(())
+}
+
+/// 確認なしで上書きし、エラーだけを表示する ffmpeg
+fn ffmpeg() -> Command {
+    let mut command = Command::new("ffmpeg");
+    command.args(["-hide_banner", "-loglevel", "error", "-nostdin", "-y"]);
+    command
+}
+
+async fn run(command: Command) -> Result<()> {
+    let status = shutdown::run_child(command).await?;
+    anyhow::ensure!(status.success(), "ffmpeg が失敗しました（{}）", status);
+    Ok(())
+}
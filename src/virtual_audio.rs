@@ -0,0 +1,319 @@
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+use crate::backend::{AudioStream, InputCallback, InputDevice, OutputCallback, OutputDevice};
+use crate::wav;
+
+/// 仮想デバイスを表す名前の接頭辞（`wav:<パス>`）
+pub const VIRTUAL_PREFIX: &str = "wav:";
+
+/// 仮想出力のサンプリングレート
+pub const VIRTUAL_OUTPUT_SAMPLE_RATE: u32 = 48000;
+
+/// 仮想出力のチャンネル数（一般的な出力デバイスに合わせてステレオ）
+pub const VIRTUAL_OUTPUT_CHANNELS: u16 = 2;
+
+/// 要求がない場合のコールバックあたりの長さ（ミリ秒）
+const DEFAULT_BLOCK_MS: u32 = 10;
+
+/// `wav:<パス>` 形式ならパスを返す
+pub fn parse_virtual(name: &str) -> Option<PathBuf> {
+    name.strip_prefix(VIRTUAL_PREFIX).map(PathBuf::from)
+}
+
+/// 仮想デバイスのストリーム（ドロップで停止）
+pub struct VirtualStream {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl VirtualStream {
+    /// 実デバイスと同じ間隔で `tick` を呼び続けるスレッドを起動する（falseを返したら終了）
+    fn spawn<F>(name: &str, period: Duration, mut tick: F) -> Result<Self>
+    where
+        F: FnMut() -> bool + Send + 'static,
+    {
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = {
+            let stop = stop.clone();
+            std::thread::Builder::new()
+                .name(name.into())
+                .spawn(move || {
+                    // 処理時間で周期がずれないよう、開始時刻からの予定時刻に合わせて眠る
+                    let mut deadline = Instant::now();
+                    while !stop.load(Ordering::Relaxed) && tick() {
+                        deadline += period;
+                        if let Some(wait) = deadline.checked_duration_since(Instant::now()) {
+                            std::thread::sleep(wait);
+                        }
+                    }
+                })
+                .context("仮想デバイスのスレッド起動に失敗")?
+        };
+
+        Ok(Self {
+            stop,
+            thread: Some(thread),
+        })
+    }
+}
+
+impl Drop for VirtualStream {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// コールバックあたりのフレーム数（要求がなければ既定の長さ）
+fn block_frames(requested: Option<u32>, sample_rate: u32) -> usize {
+    requested
+        .unwrap_or(sample_rate * DEFAULT_BLOCK_MS / 1000)
+        .max(1) as usize
+}
+
+fn block_period(frames: usize, sample_rate: u32) -> Duration {
+    Duration::from_secs_f64(frames as f64 / sample_rate as f64)
+}
+
+/// WAVファイルを実時間で流す仮想入力デバイス
+///
+/// サウンドハードウェアのない環境（CIなど）でリアルタイムパイプラインを動かすためのものです。
+/// ファイルの終わりに達すると止まり、`is_finished` がtrueになります。
+pub struct VirtualInput {
+    path: PathBuf,
+    samples: Arc<Vec<f32>>,
+    sample_rate: u32,
+    channels: u16,
+    buffer_frames: Option<u32>,
+    /// 次に流すサンプル位置（ストリームを作り直しても続きから流す）
+    position: Arc<AtomicUsize>,
+    finished: Arc<AtomicBool>,
+}
+
+impl VirtualInput {
+    pub fn open(path: &Path) -> Result<Self> {
+        let data = std::fs::read(path)
+            .with_context(|| format!("入力ファイルを読み込めません: {}", path.display()))?;
+        let (samples, spec) = wav::decode(&data)?;
+        info!(
+            "仮想入力: {}（{}Hz / {}ch、{:.1}秒）",
+            path.display(),
+            spec.sample_rate,
+            spec.channels,
+            samples.len() as f64 / spec.channels.max(1) as f64 / spec.sample_rate as f64
+        );
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            samples: Arc::new(samples),
+            sample_rate: spec.sample_rate,
+            channels: spec.channels,
+            buffer_frames: None,
+            position: Arc::new(AtomicUsize::new(0)),
+            finished: Arc::new(AtomicBool::new(false)),
+        })
+    }
+}
+
+impl InputDevice for VirtualInput {
+    fn start_stream(&self, mut callback: InputCallback) -> Result<AudioStream> {
+        let frames = block_frames(self.buffer_frames, self.sample_rate);
+        let block = frames * self.channels as usize;
+        let samples = self.samples.clone();
+        let position = self.position.clone();
+        let finished = self.finished.clone();
+
+        let stream = VirtualStream::spawn(
+            "virtual-input",
+            block_period(frames, self.sample_rate),
+            move || {
+                let start = position.load(Ordering::Relaxed);
+                let end = (start + block).min(samples.len());
+                if start >= end {
+                    finished.store(true, Ordering::Relaxed);
+                    return false;
+                }
+                callback(&samples[start..end]);
+                position.store(end, Ordering::Relaxed);
+                true
+            },
+        )?;
+        info!("仮想入力ストリーム開始");
+
+        Ok(AudioStream::new(stream))
+    }
+
+    /// 仮想デバイスの設定は変わらない
+    fn needs_rebuild(&self) -> bool {
+        false
+    }
+
+    fn refresh(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn request_buffer_frames(&mut self, frames: u32) {
+        self.buffer_frames = Some(frames);
+    }
+
+    /// デバイス名（`wav:<パス>`）
+    fn name(&self) -> String {
+        format!("{}{}", VIRTUAL_PREFIX, self.path.display())
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    /// ファイルを最後まで流し終えたか
+    fn is_finished(&self) -> bool {
+        self.finished.load(Ordering::Relaxed)
+    }
+}
+
+/// 再生した音声をメモリに溜める仮想出力デバイス
+///
+/// 溜めた音声は `captured` で取り出すか、`save` でWAVファイルに書き出します。
+pub struct VirtualOutput {
+    /// 書き出し先（Noneなら溜めるだけ）
+    path: Option<PathBuf>,
+    sample_rate: u32,
+    channels: u16,
+    buffer_frames: Option<u32>,
+    captured: Arc<Mutex<Vec<f32>>>,
+}
+
+impl VirtualOutput {
+    /// `path` は `save` の書き出し先
+    pub fn new(path: &Path) -> Self {
+        info!(
+            "仮想出力: {}（{}Hz / {}ch）",
+            path.display(),
+            VIRTUAL_OUTPUT_SAMPLE_RATE,
+            VIRTUAL_OUTPUT_CHANNELS
+        );
+        Self {
+            path: Some(path.to_path_buf()),
+            sample_rate: VIRTUAL_OUTPUT_SAMPLE_RATE,
+            channels: VIRTUAL_OUTPUT_CHANNELS,
+            buffer_frames: None,
+            captured: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    /// 再生した音声を `captured` に足していく（ファイルには書き出さない）
+    pub fn shared(captured: Arc<Mutex<Vec<f32>>>) -> Self {
+        Self {
+            path: None,
+            sample_rate: VIRTUAL_OUTPUT_SAMPLE_RATE,
+            channels: VIRTUAL_OUTPUT_CHANNELS,
+            buffer_frames: None,
+            captured,
+        }
+    }
+
+    /// これまでに再生した音声（インターリーブ）
+    pub fn captured(&self) -> Vec<f32> {
+        self.captured
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// 再生した音声をWAV（32bit float）に書き出す
+    pub fn save(&self) -> Result<PathBuf> {
+        let path = self
+            .path
+            .as_ref()
+            .context("仮想出力の書き出し先がありません")?;
+        let samples = self.captured();
+        let wav = wav::encode(&samples, self.sample_rate, self.channels)?;
+        std::fs::write(path, wav)
+            .with_context(|| format!("出力ファイル書き込みエラー: {}", path.display()))?;
+
+        let seconds = samples.len() as f64 / self.channels as f64 / self.sample_rate as f64;
+        if samples.iter().all(|&s| s == 0.0) {
+            warn!("仮想出力は無音でした");
+        }
+        info!("仮想出力を保存: {}（{:.1}秒）", path.display(), seconds);
+
+        Ok(path.clone())
+    }
+}
+
+impl OutputDevice for VirtualOutput {
+    fn start_stream(&self, mut callback: OutputCallback) -> Result<AudioStream> {
+        let frames = block_frames(self.buffer_frames, self.sample_rate);
+        let mut block = vec![0.0; frames * self.channels as usize];
+        let captured = self.captured.clone();
+
+        let stream = VirtualStream::spawn(
+            "virtual-output",
+            block_period(frames, self.sample_rate),
+            move || {
+                callback(&mut block);
+                captured
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .extend_from_slice(&block);
+                true
+            },
+        )?;
+        info!("仮想出力ストリーム開始");
+
+        Ok(AudioStream::new(stream))
+    }
+
+    /// 仮想デバイスの設定は変わらない
+    fn needs_rebuild(&self) -> bool {
+        false
+    }
+
+    fn refresh(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn request_buffer_frames(&mut self, frames: u32) {
+        self.buffer_frames = Some(frames);
+    }
+
+    /// デバイス名（`wav:<パス>`。書き出さないものは `memory`）
+    fn name(&self) -> String {
+        match &self.path {
+            Some(path) => format!("{}{}", VIRTUAL_PREFIX, path.display()),
+            None => "memory".to_string(),
+        }
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    /// 変換済みの音声を欠かさず書き出すため
+    fn drain_on_stop(&self) -> bool {
+        true
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        match self.path {
+            Some(_) => self.save().map(|_| ()),
+            None => Ok(()),
+        }
+    }
+}
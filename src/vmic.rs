@@ -0,0 +1,374 @@
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use std::f32::consts::PI;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+use tracing::{info, warn};
+
+use crate::audio::{AudioBuffer, AudioInput, AudioOutput};
+use crate::config::{self, Config, CONFIG_FILE_NAME};
+use crate::dsp::analysis;
+
+/// 変換音声を書き込むPCM名（monitorの出力先）
+pub const OUTPUT_PCM: &str = "makebeliv_out";
+
+/// 他のアプリからマイクとして選択するPCM名
+pub const MIC_PCM: &str = "makebeliv_mic";
+
+/// `.asoundrc` 内でmakebelivが管理するブロックの区切り
+const BLOCK_BEGIN: &str = "# >>> makebeliv vmic >>>";
+const BLOCK_END: &str = "# <<< makebeliv vmic <<<";
+
+/// 疎通テストで信号が届いたと判定するレベル
+const SIGNAL_THRESHOLD_DB: f32 = -60.0;
+
+/// 疎通テストのトーン長
+const SIGNAL_TEST_DURATION: Duration = Duration::from_millis(1500);
+
+/// 仮想オーディオドライバの情報
+struct VirtualDriver {
+    name: &'static str,
+    /// 変換音声を書き込む再生デバイス名（部分一致）
+    playback: &'static str,
+    /// アプリがマイクとして選ぶ録音デバイス名（部分一致）
+    capture: &'static str,
+    /// 未インストール時の案内
+    install: &'static [&'static str],
+    /// サンプルレート・チャンネル数の変更方法
+    settings: &'static str,
+}
+
+/// このプラットフォームで想定する仮想オーディオドライバ
+fn platform_driver() -> VirtualDriver {
+    if cfg!(target_os = "windows") {
+        VirtualDriver {
+            name: "VB-Cable",
+            playback: "CABLE Input",
+            capture: "CABLE Output",
+            install: &[
+                "https://vb-audio.com/Cable/ からダウンロードし、",
+                "VBCABLE_Setup_x64.exe を管理者として実行してください（インストール後に再起動）",
+            ],
+            settings: "サウンド設定 → サウンド コントロール パネル → 録音/再生タブの CABLE → プロパティ → 詳細",
+        }
+    } else if cfg!(target_os = "macos") {
+        VirtualDriver {
+            name: "BlackHole",
+            playback: "BlackHole",
+            capture: "BlackHole",
+            install: &[
+                "brew install blackhole-2ch",
+                "または https://existential.audio/blackhole/ からインストーラーを入手してください",
+            ],
+            settings: "「Audio MIDI設定」アプリ → BlackHole → フォーマット",
+        }
+    } else {
+        VirtualDriver {
+            name: "ALSAループバック",
+            playback: OUTPUT_PCM,
+            capture: MIC_PCM,
+            install: &["makebeliv vmic create --backend alsa"],
+            settings: "~/.asoundrc の makebeliv ブロック",
+        }
+    }
+}
+
+/// 仮想マイクを提供するバックエンド
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum VmicBackend {
+    /// ALSA loopback (snd-aloop)
+    Alsa,
+}
+
+/// 仮想マイクを作成し、monitorの出力先をそこへ向ける
+pub fn create(backend: VmicBackend, skip_confirm: bool) -> Result<()> {
+    match backend {
+        VmicBackend::Alsa => create_alsa(skip_confirm),
+    }
+}
+
+fn create_alsa(skip_confirm: bool) -> Result<()> {
+    if !cfg!(target_os = "linux") {
+        anyhow::bail!("ALSAバックエンドはLinuxでのみ使用できます");
+    }
+
+    info!("🎛️ ALSAループバック仮想マイクのセットアップ");
+
+    // 1. snd-aloopの確認・ロード
+    info!("snd-aloopの確認中...");
+    if aloop_loaded() {
+        info!("  ✓ snd-aloop はロード済み");
+    } else {
+        warn!("  ✗ snd-aloop がロードされていません");
+        if !confirm(
+            "\nsudo modprobe snd-aloop を実行しますか？ (y/N)",
+            skip_confirm,
+        )? {
+            println!("セットアップをキャンセルしました。");
+            return Ok(());
+        }
+
+        let status = Command::new("sudo")
+            .args(["modprobe", "snd-aloop"])
+            .status()
+            .context("modprobeの実行に失敗")?;
+        if !status.success() || !aloop_loaded() {
+            anyhow::bail!("snd-aloop のロードに失敗しました");
+        }
+        info!("  ✓ snd-aloop をロードしました");
+        println!("\n💡 再起動後も有効にするには:");
+        println!("  echo snd-aloop | sudo tee /etc/modules-load.d/makebeliv.conf");
+    }
+
+    // 2. .asoundrcにルーティングを書き込む
+    let asoundrc = asoundrc_path()?;
+    let existing = if asoundrc.exists() {
+        std::fs::read_to_string(&asoundrc)
+            .with_context(|| format!("読み込みエラー: {}", asoundrc.display()))?
+    } else {
+        String::new()
+    };
+    std::fs::write(&asoundrc, replace_block(&existing, &asoundrc_block()))
+        .with_context(|| format!("書き込みエラー: {}", asoundrc.display()))?;
+    info!("  ✓ {} にルーティングを書き込みました", asoundrc.display());
+
+    // 3. monitorの出力先を設定
+    config::set_value(
+        Path::new(CONFIG_FILE_NAME),
+        "audio",
+        "output_device",
+        OUTPUT_PCM,
+    )?;
+    info!(
+        "  ✓ {} の [audio] output_device を {} に設定しました",
+        CONFIG_FILE_NAME, OUTPUT_PCM
+    );
+
+    println!("\n✅ 仮想マイクを作成しました！");
+    println!("  変換音声の出力先: {}", OUTPUT_PCM);
+    println!("  アプリで選択するマイク: {}", MIC_PCM);
+    println!("\n次のステップ:");
+    println!("  makebeliv monitor");
+
+    Ok(())
+}
+
+/// snd-aloopがロードされているか
+fn aloop_loaded() -> bool {
+    Path::new("/sys/module/snd_aloop").exists()
+        || std::fs::read_to_string("/proc/asound/cards")
+            .is_ok_and(|cards| cards.contains("Loopback"))
+}
+
+fn confirm(prompt: &str, skip_confirm: bool) -> Result<bool> {
+    if skip_confirm {
+        return Ok(true);
+    }
+
+    println!("{}", prompt);
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    Ok(input.trim().eq_ignore_ascii_case("y"))
+}
+
+fn asoundrc_path() -> Result<PathBuf> {
+    let home = std::env::var_os("HOME").context("HOME が設定されていません")?;
+    Ok(PathBuf::from(home).join(".asoundrc"))
+}
+
+/// ループバックの再生側に書き込み、キャプチャ側をマイクとして見せる設定
+fn asoundrc_block() -> String {
+    format!(
+        r#"{begin}
+pcm.{output} {{
+    type plug
+    slave.pcm "hw:Loopback,0,0"
+    hint {{
+        show on
+        description "makebeliv 変換音声出力"
+    }}
+}}
+
+pcm.{mic} {{
+    type plug
+    slave.pcm "hw:Loopback,1,0"
+    hint {{
+        show on
+        description "makebeliv 仮想マイク"
+    }}
+}}
+{end}
+"#,
+        begin = BLOCK_BEGIN,
+        end = BLOCK_END,
+        output = OUTPUT_PCM,
+        mic = MIC_PCM,
+    )
+}
+
+/// 既存の管理ブロックを置き換える（なければ末尾に追記）
+fn replace_block(existing: &str, block: &str) -> String {
+    if let (Some(start), Some(end)) = (existing.find(BLOCK_BEGIN), existing.find(BLOCK_END)) {
+        if start < end {
+            let after = existing[end + BLOCK_END.len()..].trim_start_matches('\n');
+            return format!("{}{}{}", &existing[..start], block, after);
+        }
+    }
+
+    if existing.is_empty() {
+        block.to_string()
+    } else if existing.ends_with('\n') {
+        format!("{}\n{}", existing, block)
+    } else {
+        format!("{}\n\n{}", existing, block)
+    }
+}
+
+/// 仮想オーディオドライバの導入状況と経路を診断し、修正方法を案内する
+pub fn doctor(skip_confirm: bool) -> Result<()> {
+    let driver = platform_driver();
+    info!("🩺 仮想マイクの診断（{}）", driver.name);
+
+    // 1. ドライバの検出
+    info!("{}の確認中...", driver.name);
+    let (output, input) = match (
+        AudioOutput::open(Some(driver.playback)),
+        AudioInput::open(Some(driver.capture)),
+    ) {
+        (Ok(output), Ok(input)) => (output, input),
+        _ => {
+            warn!("  ✗ {} が見つかりません", driver.name);
+            println!("\n📦 {}をインストールしてください:", driver.name);
+            for line in driver.install {
+                println!("  {}", line);
+            }
+            println!("\nインストール後、再度 makebeliv vmic doctor を実行してください。");
+            return Ok(());
+        }
+    };
+    info!("  ✓ 再生側: {}", output.name());
+    info!("  ✓ 録音側: {}", input.name());
+
+    // 2. サンプルレート・チャンネル数
+    let mut problems = 0;
+    if output.sample_rate() == input.sample_rate() {
+        info!("  ✓ サンプルレート: {}Hz", output.sample_rate());
+    } else {
+        problems += 1;
+        warn!(
+            "  ✗ サンプルレートが一致しません（再生側 {}Hz / 録音側 {}Hz）",
+            output.sample_rate(),
+            input.sample_rate()
+        );
+        println!("\n🔧 両方を同じサンプルレート（48000Hz推奨）に揃えてください:");
+        println!("  {}", driver.settings);
+    }
+
+    if input.channels() <= 2 {
+        info!(
+            "  ✓ チャンネル数: 再生側 {}ch / 録音側 {}ch",
+            output.channels(),
+            input.channels()
+        );
+    } else {
+        problems += 1;
+        warn!(
+            "  ✗ 録音側が {}ch です。多くの通話アプリはモノラル/ステレオのみ扱えます",
+            input.channels()
+        );
+        println!("\n🔧 録音側を2ch（ステレオ）に設定してください:");
+        println!("  {}", driver.settings);
+    }
+
+    // 3. monitorの出力先
+    let configured = Config::load()?.audio.output_device;
+    let routed = configured
+        .as_deref()
+        .is_some_and(|name| output.name().contains(name));
+    if routed {
+        info!("  ✓ monitorの出力先: {}", output.name());
+    } else {
+        warn!(
+            "  ✗ monitorの出力先が {} になっていません（現在: {}）",
+            driver.name,
+            configured.as_deref().unwrap_or("デフォルトデバイス")
+        );
+        let prompt = format!(
+            "\n{} の [audio] output_device を {} に設定しますか？ (y/N)",
+            CONFIG_FILE_NAME,
+            output.name()
+        );
+        if confirm(&prompt, skip_confirm)? {
+            config::set_value(
+                Path::new(CONFIG_FILE_NAME),
+                "audio",
+                "output_device",
+                output.name(),
+            )?;
+            info!("  ✓ 出力先を設定しました");
+        } else {
+            problems += 1;
+        }
+    }
+
+    // 4. 疎通テスト（再生側にトーンを流し、録音側に届くか確認）
+    info!("疎通テスト中...");
+    match signal_test(&output, &input) {
+        Ok(level_db) if level_db > SIGNAL_THRESHOLD_DB => {
+            info!("  ✓ 信号が録音側に届いています（{:.1} dBFS）", level_db);
+        }
+        Ok(level_db) => {
+            problems += 1;
+            warn!("  ✗ 録音側に信号が届いていません（{:.1} dBFS）", level_db);
+            println!("\n🔧 経路を確認してください:");
+            println!("  - {} がミュートされていないか", driver.name);
+            println!("  - {}", driver.settings);
+        }
+        Err(e) => {
+            problems += 1;
+            warn!("  ✗ 疎通テストに失敗: {}", e);
+        }
+    }
+
+    if problems == 0 {
+        println!("\n✅ 仮想マイクは正常です！");
+        println!(
+            "  通話・配信アプリのマイクに「{}」を選択してください。",
+            input.name()
+        );
+    } else {
+        println!(
+            "\n⚠ {}件の問題があります。上記の手順で修正してください。",
+            problems
+        );
+        println!(
+            "  アプリ側ではマイクに「{}」を選択してください。",
+            input.name()
+        );
+    }
+
+    Ok(())
+}
+
+/// 再生側に440Hzのトーンを流し、録音側のレベル（dBFS）を返す
+fn signal_test(output: &AudioOutput, input: &AudioInput) -> Result<f32> {
+    let captured = AudioBuffer::new(input.sample_rate() as usize * input.channels() as usize * 2);
+    let capture_buffer = captured.clone();
+    let _input_stream = input.start_stream(move |data| capture_buffer.push(data))?;
+
+    let rate = output.sample_rate() as f32;
+    let channels = output.channels() as usize;
+    let mut phase = 0.0f32;
+    let _output_stream = output.start_stream(move |data| {
+        for frame in data.chunks_mut(channels) {
+            frame.fill(0.3 * (2.0 * PI * phase).sin());
+            phase = (phase + 440.0 / rate).fract();
+        }
+    })?;
+
+    std::thread::sleep(SIGNAL_TEST_DURATION);
+
+    let samples = captured.take(captured.len());
+    Ok(analysis::rms_db(&samples))
+}
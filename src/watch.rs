@@ -0,0 +1,160 @@
+use anyhow::{Context, Result};
+use notify::{EventKind, RecursiveMode, Watcher};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use tracing::{debug, warn};
+
+use crate::batch;
+use crate::wav;
+
+/// 書き込み中のファイルを変換しないよう、サイズが変わらなくなってから待つ既定の時間（ミリ秒）
+pub const DEFAULT_SETTLE_MS: u64 = 1000;
+
+/// WAVとして読めないまま、書き込みの完了を待ち続ける時間
+const GIVE_UP_AFTER: Duration = Duration::from_secs(60);
+
+/// 待っているファイルの様子を見る間隔
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// 書き込みの完了を待っているファイル
+struct Pending {
+    size: Option<u64>,
+    /// 最後にサイズが変わった（イベントを受けた）時刻
+    changed: Instant,
+    first_seen: Instant,
+}
+
+/// フォルダに置かれたWAVファイルを見つける
+///
+/// ファイルシステムの通知を受けてから、サイズがしばらく変わらず、WAVとして
+/// 読み込めるようになったファイルを返します。コピー中や録音中のファイルを
+/// 途中で変換しないためです。起動前からあったファイルは対象にしません。
+pub struct FolderWatcher {
+    dir: PathBuf,
+    /// 止めると通知も止まるため持っておく
+    _watcher: notify::RecommendedWatcher,
+    events: mpsc::UnboundedReceiver<PathBuf>,
+    settle: Duration,
+    pending: BTreeMap<PathBuf, Pending>,
+    /// 自分で書き出したファイル（入力と出力が同じフォルダのとき）
+    ignored: BTreeSet<PathBuf>,
+}
+
+impl FolderWatcher {
+    pub fn new(dir: &Path, settle: Duration) -> Result<Self> {
+        let dir = dir
+            .canonicalize()
+            .with_context(|| format!("監視するフォルダが見つかりません: {}", dir.display()))?;
+        anyhow::ensure!(dir.is_dir(), "フォルダではありません: {}", dir.display());
+
+        let (sender, events) = mpsc::unbounded_channel();
+        let mut watcher =
+            notify::recommended_watcher(move |event: notify::Result<notify::Event>| match event {
+                Ok(event) if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) => {
+                    for path in event.paths {
+                        sender.send(path).ok();
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => warn!("フォルダ監視エラー: {}", e),
+            })
+            .context("フォルダ監視の開始エラー")?;
+        watcher
+            .watch(&dir, RecursiveMode::NonRecursive)
+            .with_context(|| format!("フォルダを監視できません: {}", dir.display()))?;
+
+        Ok(Self {
+            dir,
+            _watcher: watcher,
+            events,
+            settle,
+            pending: BTreeMap::new(),
+            ignored: BTreeSet::new(),
+        })
+    }
+
+    /// 監視しているフォルダ（絶対パス）
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// 通知が来ても変換しないファイル（書き出した出力など）
+    pub fn ignore(&mut self, path: &Path) {
+        let path = path
+            .parent()
+            .and_then(|parent| parent.canonicalize().ok())
+            .zip(path.file_name())
+            .map_or_else(|| path.to_path_buf(), |(parent, name)| parent.join(name));
+        self.ignored.insert(path);
+    }
+
+    /// 次に変換できるファイルを待つ（監視が止まったらNone）
+    pub async fn next(&mut self) -> Option<PathBuf> {
+        let mut ticker = tokio::time::interval(POLL_INTERVAL);
+        loop {
+            tokio::select! {
+                path = self.events.recv() => self.touch(path?),
+                _ = ticker.tick() => {
+                    if let Some(path) = self.take_ready() {
+                        return Some(path);
+                    }
+                }
+            }
+        }
+    }
+
+    fn touch(&mut self, path: PathBuf) {
+        if !batch::is_wav(&path) || self.ignored.contains(&path) {
+            return;
+        }
+        let now = Instant::now();
+        let pending = self.pending.entry(path).or_insert_with(|| Pending {
+            size: None,
+            changed: now,
+            first_seen: now,
+        });
+        pending.changed = now;
+    }
+
+    /// 書き込みが終わったファイルを1つ取り出す
+    fn take_ready(&mut self) -> Option<PathBuf> {
+        let mut ready = None;
+        self.pending.retain(|path, pending| {
+            if ready.is_some() {
+                return true;
+            }
+            let Ok(metadata) = std::fs::metadata(path) else {
+                // 一時ファイルの名前変更などで消えた
+                return false;
+            };
+            let size = Some(metadata.len());
+            if pending.size != size {
+                pending.size = size;
+                pending.changed = Instant::now();
+                return true;
+            }
+            if pending.changed.elapsed() < self.settle {
+                return true;
+            }
+
+            match wav::read_file(path) {
+                Ok(_) => {
+                    ready = Some(path.clone());
+                    false
+                }
+                Err(e) if pending.first_seen.elapsed() > GIVE_UP_AFTER => {
+                    warn!("⚠ WAVとして読み込めないため変換しません: {:#}", e);
+                    false
+                }
+                Err(e) => {
+                    debug!("書き込みの完了を待っています: {:#}", e);
+                    pending.changed = Instant::now();
+                    true
+                }
+            }
+        });
+        ready
+    }
+}
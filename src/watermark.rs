@@ -0,0 +1,299 @@
+use anyhow::Result;
+use sha2::{Digest, Sha256};
+use std::path::Path;
+use tracing::info;
+
+use crate::chaos::XorShift;
+use crate::cue::TimeRange;
+use crate::wav::{self, BitDepth};
+
+/// 鍵を指定しないときの鍵（`--watermark-key`）
+pub const DEFAULT_KEY: &str = "makebeliv";
+
+/// 拡散系列の周期（サンプル）。同じ系列を繰り返し埋め込む
+const PERIOD: usize = 4096;
+
+/// 音量に合わせて透かしの大きさを変える区間（サンプル）
+const BLOCK: usize = 1024;
+
+/// 透かしの大きさ（その区間の音声のRMSに対するdB）
+const STRENGTH_DB: f32 = -38.0;
+
+/// 検出したとみなす相関の強さ（すべてのずれの相関の標準偏差の何倍か）
+///
+/// 透かしのない音声で、最大の相関がこれを超える確率は約 `PERIOD` × 1e-9 です。
+pub const DETECTION_THRESHOLD: f64 = 6.0;
+
+/// 検出に必要な最短の長さ（周期の数）
+const MIN_PERIODS: usize = 4;
+
+/// 拡散スペクトラム方式の電子透かし（`--watermark`）
+///
+/// 鍵から作った±1の系列を、音声のRMSより十分小さい大きさで足し込みます。声が大きいところほど
+/// 強く、無音のところには入れないため、聞き取れません。検出では音声を周期で折り重ねて系列と
+/// 相関を取り、ずれを総当たりして最も強い相関が偶然の範囲を超えるかを見ます。
+/// 先頭が切り取られていても見つかりますが、リサンプリングすると周期がずれて見つかりません。
+#[derive(Debug, Clone)]
+pub struct Watermark {
+    sequence: Vec<f32>,
+}
+
+/// 透かしの検出結果
+#[derive(Debug, Clone, Copy)]
+pub struct Detection {
+    /// 最も強い相関の、偶然の範囲に対する倍率
+    pub score: f64,
+    /// 系列の始まりの位置（周期内のサンプル）
+    pub offset: usize,
+}
+
+impl Detection {
+    pub fn is_present(&self) -> bool {
+        self.score >= DETECTION_THRESHOLD
+    }
+}
+
+impl Watermark {
+    pub fn new(key: &str) -> Self {
+        let digest = Sha256::digest(key.as_bytes());
+        let seed = u64::from_le_bytes(digest[..8].try_into().unwrap_or_default());
+        let mut rng = XorShift::new(seed);
+        let sequence = (0..PERIOD)
+            .map(|_| if rng.next_f64() < 0.5 { -1.0 } else { 1.0 })
+            .collect();
+        Self { sequence }
+    }
+
+    /// インターリーブされた音声の `frames` の範囲に透かしを足す（全チャンネルに同じ値）
+    pub fn embed(&self, samples: &mut [f32], channels: u16, frames: std::ops::Range<usize>) {
+        let channels = channels.max(1) as usize;
+        let total = samples.len() / channels;
+        let gain = 10f32.powf(STRENGTH_DB / 20.0);
+        let mut start = frames.start.min(total);
+        let end = frames.end.min(total);
+        while start < end {
+            let stop = (start + BLOCK).min(end);
+            let block = &mut samples[start * channels..stop * channels];
+            let rms = (block.iter().map(|s| s * s).sum::<f32>() / block.len().max(1) as f32).sqrt();
+            let amplitude = rms * gain;
+            for (i, frame) in block.chunks_mut(channels).enumerate() {
+                let chip = self.sequence[(start + i) % PERIOD] * amplitude;
+                for sample in frame {
+                    *sample = (*sample + chip).clamp(-1.0, 1.0);
+                }
+            }
+            start = stop;
+        }
+    }
+
+    /// 透かしを探す（短すぎて判定できなければNone）
+    pub fn detect(&self, samples: &[f32], channels: u16) -> Option<Detection> {
+        let channels = channels.max(1) as usize;
+        let frames = samples.len() / channels;
+        if frames < PERIOD * MIN_PERIODS {
+            return None;
+        }
+
+        // モノラルにして差分を取り（声の多い低い周波数を弱める）、周期で折り重ねる
+        let mut folded = vec![0.0f64; PERIOD];
+        let mut previous = 0.0f64;
+        for (i, frame) in samples.chunks_exact(channels).enumerate() {
+            let mono = frame.iter().map(|&s| s as f64).sum::<f64>() / channels as f64;
+            if i > 0 {
+                folded[i % PERIOD] += mono - previous;
+            }
+            previous = mono;
+        }
+        let reference: Vec<f64> = (0..PERIOD)
+            .map(|i| (self.sequence[i] - self.sequence[(i + PERIOD - 1) % PERIOD]) as f64)
+            .collect();
+
+        let correlations: Vec<f64> = (0..PERIOD)
+            .map(|offset| {
+                reference
+                    .iter()
+                    .enumerate()
+                    .map(|(i, r)| folded[(i + offset) % PERIOD] * r)
+                    .sum()
+            })
+            .collect();
+        let mean = correlations.iter().sum::<f64>() / PERIOD as f64;
+        let deviation =
+            (correlations.iter().map(|c| (c - mean).powi(2)).sum::<f64>() / PERIOD as f64).sqrt();
+        let (offset, peak) = correlations
+            .iter()
+            .copied()
+            .enumerate()
+            .max_by(|a, b| a.1.total_cmp(&b.1))?;
+        Some(Detection {
+            score: if deviation > 0.0 {
+                (peak - mean) / deviation
+            } else {
+                0.0
+            },
+            offset,
+        })
+    }
+}
+
+/// WAVファイルの `range` の範囲（Noneなら全体）に透かしを埋め込んで書き直す
+pub fn embed_file(path: &Path, key: &str, range: Option<TimeRange>) -> Result<()> {
+    let (mut samples, spec) = wav::read_file(path)?;
+    let frames = samples.len() / spec.channels.max(1) as usize;
+    let (start, end) = match range {
+        Some(range) => range.frames(frames, spec.sample_rate)?,
+        None => (0, frames),
+    };
+    Watermark::new(key).embed(&mut samples, spec.channels, start..end);
+    wav::write_file(
+        path,
+        &samples,
+        spec.sample_rate,
+        spec.channels,
+        BitDepth::from_spec(&spec),
+    )?;
+    info!("  🔏 電子透かしを埋め込みました: {}", path.display());
+    Ok(())
+}
+
+/// WAVファイルの透かしを探す（短すぎて判定できなければNone）
+pub fn detect_file(path: &Path, key: &str) -> Result<Option<Detection>> {
+    let (samples, spec) = wav::read_file(path)?;
+    Ok(Watermark::new(key).detect(&samples, spec.channels))
+}
+
+/// 判定に必要な最短の長さ（秒）
+pub fn min_seconds(sample_rate: u32) -> f64 {
+    (PERIOD * MIN_PERIODS) as f64 / sample_rate.max(1) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: u32 = 16_000;
+
+    /// 低い周波数の多い雑音（白色雑音を漏れ積分したブラウンノイズ）
+    fn noise(frames: usize, seed: u64) -> Vec<f32> {
+        let mut rng = XorShift::new(seed);
+        let mut level = 0.0f32;
+        (0..frames)
+            .map(|_| {
+                level = level * 0.98 + (rng.next_f64() as f32 * 2.0 - 1.0) * 0.05;
+                level
+            })
+            .collect()
+    }
+
+    /// 声に似た信号（倍音を持つ有声音を音節ごとに区切り、間に無音を挟む）
+    fn speech_like(frames: usize, seed: u64) -> Vec<f32> {
+        let mut rng = XorShift::new(seed);
+        let syllable = RATE as usize / 4;
+        (0..frames)
+            .map(|i| {
+                let t = i as f32 / RATE as f32;
+                let position = i % syllable;
+                // 4音節に1つは無音
+                if (i / syllable) % 4 == 3 {
+                    return 0.0;
+                }
+                let envelope = (std::f32::consts::PI * position as f32 / syllable as f32).sin();
+                let pitch = 140.0 + 30.0 * (t * 1.3).sin();
+                let voiced: f32 = (1..=8)
+                    .map(|h| (std::f32::consts::TAU * pitch * h as f32 * t).sin() / h as f32)
+                    .sum();
+                let breath = (rng.next_f64() as f32 * 2.0 - 1.0) * 0.02;
+                envelope * (0.3 * voiced + breath)
+            })
+            .collect()
+    }
+
+    fn marked(mut samples: Vec<f32>, channels: u16) -> Vec<f32> {
+        let frames = samples.len() / channels as usize;
+        Watermark::new(DEFAULT_KEY).embed(&mut samples, channels, 0..frames);
+        samples
+    }
+
+    fn score(samples: &[f32], channels: u16, key: &str) -> f64 {
+        Watermark::new(key)
+            .detect(samples, channels)
+            .expect("判定できる長さのはず")
+            .score
+    }
+
+    #[test]
+    fn detects_the_mark_in_noise() {
+        let samples = marked(noise(RATE as usize * 3, 1), 1);
+        let detection = Watermark::new(DEFAULT_KEY).detect(&samples, 1).unwrap();
+        assert!(detection.is_present(), "score {}", detection.score);
+        assert_eq!(detection.offset, 0);
+        // 別の鍵では見つからない
+        assert!(score(&samples, 1, "other") < DETECTION_THRESHOLD);
+    }
+
+    #[test]
+    fn detects_the_mark_in_speech_like_stereo() {
+        let mono = speech_like(RATE as usize * 4, 2);
+        let stereo: Vec<f32> = mono.iter().flat_map(|&s| [s, s * 0.8]).collect();
+        let samples = marked(stereo, 2);
+        let score = score(&samples, 2, DEFAULT_KEY);
+        assert!(score >= DETECTION_THRESHOLD, "score {}", score);
+    }
+
+    #[test]
+    fn does_not_detect_unmarked_audio() {
+        for seed in 1..=4 {
+            for samples in [
+                noise(RATE as usize * 3, seed),
+                speech_like(RATE as usize * 3, seed),
+            ] {
+                let score = score(&samples, 1, DEFAULT_KEY);
+                assert!(
+                    score < DETECTION_THRESHOLD,
+                    "seed {}: score {}",
+                    seed,
+                    score
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn survives_16_bit_requantisation() {
+        let samples = marked(speech_like(RATE as usize * 4, 3), 1);
+        let encoded = wav::encode_with_depth(&samples, RATE, 1, BitDepth::Int16).unwrap();
+        let (decoded, spec) = wav::decode(&encoded).unwrap();
+        assert_eq!(spec.bits_per_sample, 16);
+        let score = score(&decoded, 1, DEFAULT_KEY);
+        assert!(score >= DETECTION_THRESHOLD, "score {}", score);
+    }
+
+    #[test]
+    fn survives_a_trimmed_start() {
+        let samples = marked(noise(RATE as usize * 3, 4), 1);
+        let trim = 1234;
+        let detection = Watermark::new(DEFAULT_KEY)
+            .detect(&samples[trim..], 1)
+            .unwrap();
+        assert!(detection.is_present(), "score {}", detection.score);
+        assert_eq!(detection.offset, PERIOD - trim);
+    }
+
+    #[test]
+    fn leaves_silence_and_other_ranges_untouched() {
+        let mut samples = noise(RATE as usize, 5);
+        samples[..BLOCK].fill(0.0);
+        let original = samples.clone();
+        let end = BLOCK * 8;
+        Watermark::new(DEFAULT_KEY).embed(&mut samples, 1, 0..end);
+        assert!(samples[..BLOCK].iter().all(|&s| s == 0.0));
+        assert_ne!(samples[BLOCK..end], original[BLOCK..end]);
+        assert_eq!(samples[end..], original[end..]);
+    }
+
+    #[test]
+    fn refuses_to_judge_short_audio() {
+        let samples = marked(noise(PERIOD * MIN_PERIODS - 1, 6), 1);
+        assert!(Watermark::new(DEFAULT_KEY).detect(&samples, 1).is_none());
+    }
+}
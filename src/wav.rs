@@ -0,0 +1,352 @@
+use anyhow::{Context, Result};
+use std::io::Cursor;
+use std::path::Path;
+use tracing::info;
+
+use crate::audio::remap_channels;
+use crate::dsp::simd;
+use crate::resample::{self, ResampleQuality};
+
+/// 受け付けるサンプリングレートの範囲
+pub const SAMPLE_RATE_RANGE: std::ops::RangeInclusive<u32> = 8000..=192000;
+
+/// 受け付ける最大チャンネル数
+pub const MAX_CHANNELS: u16 = 8;
+
+/// WAVのビット深度
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BitDepth {
+    /// 16bit整数PCM
+    Int16,
+    /// 24bit整数PCM
+    Int24,
+    /// 32bit浮動小数点
+    Float32,
+}
+
+impl BitDepth {
+    /// 入力WAVのビット深度（24bitを超える整数PCMは32bit floatとして扱う）
+    pub fn from_spec(spec: &hound::WavSpec) -> Self {
+        match (spec.sample_format, spec.bits_per_sample) {
+            (hound::SampleFormat::Float, _) => Self::Float32,
+            (hound::SampleFormat::Int, 0..=16) => Self::Int16,
+            (hound::SampleFormat::Int, 17..=24) => Self::Int24,
+            (hound::SampleFormat::Int, _) => Self::Float32,
+        }
+    }
+
+    pub(crate) fn spec(self, sample_rate: u32, channels: u16) -> hound::WavSpec {
+        let (bits_per_sample, sample_format) = match self {
+            Self::Int16 => (16, hound::SampleFormat::Int),
+            Self::Int24 => (24, hound::SampleFormat::Int),
+            Self::Float32 => (32, hound::SampleFormat::Float),
+        };
+        hound::WavSpec {
+            channels,
+            sample_rate,
+            bits_per_sample,
+            sample_format,
+        }
+    }
+}
+
+impl std::str::FromStr for BitDepth {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "16" => Ok(Self::Int16),
+            "24" => Ok(Self::Int24),
+            "32f" => Ok(Self::Float32),
+            _ => anyhow::bail!("不明なビット深度: {}（16, 24, 32f のいずれか）", s),
+        }
+    }
+}
+
+impl std::fmt::Display for BitDepth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Int16 => "16",
+            Self::Int24 => "24",
+            Self::Float32 => "32f",
+        })
+    }
+}
+
+/// WAVの形式が扱える範囲か確認する
+pub fn validate(spec: &hound::WavSpec) -> Result<()> {
+    anyhow::ensure!(
+        (1..=MAX_CHANNELS).contains(&spec.channels),
+        "未対応のチャンネル数: {}（1〜{}）",
+        spec.channels,
+        MAX_CHANNELS
+    );
+    anyhow::ensure!(
+        SAMPLE_RATE_RANGE.contains(&spec.sample_rate),
+        "未対応のサンプリングレート: {}Hz（{}〜{}Hz）",
+        spec.sample_rate,
+        SAMPLE_RATE_RANGE.start(),
+        SAMPLE_RATE_RANGE.end()
+    );
+    match (spec.sample_format, spec.bits_per_sample) {
+        (hound::SampleFormat::Int, 8..=32) | (hound::SampleFormat::Float, 32) => Ok(()),
+        (format, bits) => anyhow::bail!("未対応のサンプル形式: {}bit {:?}", bits, format),
+    }
+}
+
+/// WAVバイト列をf32サンプルにデコード（インターリーブ）
+pub fn decode(data: &[u8]) -> Result<(Vec<f32>, hound::WavSpec)> {
+    // HTMLやJSONのエラー応答をそのまま渡されたときに分かりやすくする
+    if data.len() < 12 || &data[..4] != b"RIFF" || &data[8..12] != b"WAVE" {
+        let head = String::from_utf8_lossy(&data[..data.len().min(40)]).into_owned();
+        anyhow::bail!(
+            "WAVデータではありません（{}バイト、先頭: {:?}）",
+            data.len(),
+            head
+        );
+    }
+
+    let mut reader = hound::WavReader::new(Cursor::new(data)).context("WAVデコードエラー")?;
+    let spec = reader.spec();
+    validate(&spec)?;
+
+    let samples = match spec.sample_format {
+        hound::SampleFormat::Float => reader
+            .samples::<f32>()
+            .collect::<Result<Vec<_>, _>>()
+            .context("WAVデコードエラー")?,
+        hound::SampleFormat::Int => {
+            let scale = 1.0 / (1i64 << (spec.bits_per_sample - 1)) as f32;
+            reader
+                .samples::<i32>()
+                .map(|s| s.map(|s| s as f32 * scale))
+                .collect::<Result<Vec<_>, _>>()
+                .context("WAVデコードエラー")?
+        }
+    };
+
+    Ok((samples, spec))
+}
+
+/// f32サンプルをWAVバイト列にエンコード（チャンク送信用）
+pub fn encode(samples: &[f32], sample_rate: u32, channels: u16) -> Result<Vec<u8>> {
+    encode_with_depth(samples, sample_rate, channels, BitDepth::Float32)
+}
+
+/// f32サンプルを指定ビット深度のWAVバイト列にエンコード（整数PCMはクリップする）
+pub fn encode_with_depth(
+    samples: &[f32],
+    sample_rate: u32,
+    channels: u16,
+    bit_depth: BitDepth,
+) -> Result<Vec<u8>> {
+    let spec = bit_depth.spec(sample_rate, channels);
+
+    let mut cursor = Cursor::new(Vec::new());
+    {
+        let mut writer = hound::WavWriter::new(&mut cursor, spec).context("WAVエンコードエラー")?;
+        match bit_depth {
+            BitDepth::Float32 => {
+                for &sample in samples {
+                    writer.write_sample(sample)?;
+                }
+            }
+            BitDepth::Int16 => {
+                let mut converted = Vec::new();
+                simd::to_i16(samples, &mut converted);
+                for sample in converted {
+                    writer.write_sample(sample)?;
+                }
+            }
+            BitDepth::Int24 => {
+                let scale = ((1i32 << (spec.bits_per_sample - 1)) - 1) as f32;
+                for &sample in samples {
+                    writer.write_sample((sample.clamp(-1.0, 1.0) * scale).round() as i32)?;
+                }
+            }
+        }
+        writer.finalize().context("WAVエンコードエラー")?;
+    }
+
+    Ok(cursor.into_inner())
+}
+
+/// WAVファイルを読み込む
+pub fn read_file(path: &Path) -> Result<(Vec<f32>, hound::WavSpec)> {
+    let data = std::fs::read(path)
+        .with_context(|| format!("WAVファイルの読み込みエラー: {}", path.display()))?;
+    decode(&data).with_context(|| format!("WAVファイルを読み込めません: {}", path.display()))
+}
+
+/// WAVファイルの形式だけを読む（音声は読み込まない）
+pub fn read_spec(path: &Path) -> Result<hound::WavSpec> {
+    let reader = hound::WavReader::open(path)
+        .with_context(|| format!("WAVファイルを読み込めません: {}", path.display()))?;
+    let spec = reader.spec();
+    validate(&spec).with_context(|| format!("WAVファイルを読み込めません: {}", path.display()))?;
+    Ok(spec)
+}
+
+/// WAVファイルの長さ（フレーム数）を読む（音声は読み込まない）
+pub fn read_frames(path: &Path) -> Result<u64> {
+    let reader = hound::WavReader::open(path)
+        .with_context(|| format!("WAVファイルを読み込めません: {}", path.display()))?;
+    Ok(reader.duration() as u64)
+}
+
+/// WAVファイルを書き出す（親ディレクトリがなければ作成）
+///
+/// 一時ファイルに書いてから置き換えるため、途中で中断されても書きかけのファイルは残りません。
+pub fn write_file(
+    path: &Path,
+    samples: &[f32],
+    sample_rate: u32,
+    channels: u16,
+    bit_depth: BitDepth,
+) -> Result<()> {
+    let data = encode_with_depth(samples, sample_rate, channels, bit_depth)?;
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent).context("出力ディレクトリの作成エラー")?;
+    }
+
+    let mut partial = path.as_os_str().to_owned();
+    partial.push(".part");
+    let partial = std::path::PathBuf::from(partial);
+    std::fs::write(&partial, data)
+        .and_then(|()| std::fs::rename(&partial, path))
+        .inspect_err(|_| {
+            std::fs::remove_file(&partial).ok();
+        })
+        .with_context(|| format!("WAVファイルの書き込みエラー: {}", path.display()))
+}
+
+/// 音声を指定のレート・チャンネル数に揃える（変換結果を入力と同じ形式に戻す）
+pub fn conform(
+    samples: Vec<f32>,
+    spec: &hound::WavSpec,
+    sample_rate: u32,
+    channels: u16,
+    quality: ResampleQuality,
+) -> Result<Vec<f32>> {
+    let samples = if spec.channels != channels {
+        info!("チャンネル数の変換: {}ch → {}ch", spec.channels, channels);
+        remap_channels(&samples, spec.channels, channels)
+    } else {
+        samples
+    };
+
+    if spec.sample_rate == sample_rate {
+        return Ok(samples);
+    }
+    info!(
+        "リサンプリング: {}Hz → {}Hz（{}）",
+        spec.sample_rate, sample_rate, quality
+    );
+    resample::resample(&samples, spec.sample_rate, sample_rate, channels, quality)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec(
+        channels: u16,
+        sample_rate: u32,
+        bits: u16,
+        format: hound::SampleFormat,
+    ) -> hound::WavSpec {
+        hound::WavSpec {
+            channels,
+            sample_rate,
+            bits_per_sample: bits,
+            sample_format: format,
+        }
+    }
+
+    fn ramp() -> Vec<f32> {
+        (0..200).map(|i| (i as f32 / 100.0) - 1.0).collect()
+    }
+
+    #[test]
+    fn round_trips_each_bit_depth() {
+        // 書くときは 2^(n-1)-1 倍、読むときは 1/2^(n-1) 倍なので、丸めと合わせて1.5LSBまでずれる
+        for (depth, tolerance) in [
+            (BitDepth::Int16, 1.5 / 32768.0),
+            (BitDepth::Int24, 1.5 / 8_388_608.0),
+            (BitDepth::Float32, 0.0),
+        ] {
+            let samples = ramp();
+            let data = encode_with_depth(&samples, 48000, 2, depth).unwrap();
+            let (decoded, spec) = decode(&data).unwrap();
+            assert_eq!(BitDepth::from_spec(&spec), depth);
+            assert_eq!((spec.sample_rate, spec.channels), (48000, 2));
+            assert_eq!(decoded.len(), samples.len());
+            for (a, b) in samples.iter().zip(&decoded) {
+                assert!((a - b).abs() <= tolerance, "{}: {} → {}", depth, a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn integer_depths_clamp_full_scale() {
+        let samples = [1.0, -1.0, 1.5, -1.5];
+        for (depth, max) in [(BitDepth::Int16, 32767), (BitDepth::Int24, 8_388_607)] {
+            let data = encode_with_depth(&samples, 16000, 1, depth).unwrap();
+            let mut reader = hound::WavReader::new(Cursor::new(data)).unwrap();
+            let ints: Vec<i32> = reader.samples::<i32>().map(Result::unwrap).collect();
+            // 正は 2^(n-1)-1、負は対称に -(2^(n-1)-1) で止まる
+            assert_eq!(ints, vec![max, -max, max, -max], "{}", depth);
+        }
+    }
+
+    #[test]
+    fn decodes_negative_full_scale_to_minus_one() {
+        let mut cursor = Cursor::new(Vec::new());
+        {
+            let mut writer =
+                hound::WavWriter::new(&mut cursor, spec(1, 16000, 24, hound::SampleFormat::Int))
+                    .unwrap();
+            writer.write_sample(-8_388_608i32).unwrap();
+            writer.write_sample(8_388_607i32).unwrap();
+            writer.finalize().unwrap();
+        }
+        let (decoded, _) = decode(&cursor.into_inner()).unwrap();
+        assert_eq!(decoded[0], -1.0);
+        assert!(decoded[1] < 1.0 && decoded[1] > 0.9999);
+    }
+
+    #[test]
+    fn rejects_unsupported_formats() {
+        use hound::SampleFormat::{Float, Int};
+        assert!(validate(&spec(1, 48000, 16, Int)).is_ok());
+        assert!(validate(&spec(8, 192000, 24, Int)).is_ok());
+        assert!(validate(&spec(2, 8000, 32, Float)).is_ok());
+        for bad in [
+            spec(0, 48000, 16, Int),
+            spec(MAX_CHANNELS + 1, 48000, 16, Int),
+            spec(1, 4000, 16, Int),
+            spec(1, 384000, 16, Int),
+            spec(1, 48000, 4, Int),
+            spec(1, 48000, 64, Float),
+        ] {
+            assert!(validate(&bad).is_err(), "{:?}", bad);
+        }
+    }
+
+    #[test]
+    fn rejects_data_that_is_not_wav() {
+        let error = decode(b"{\"detail\": \"Internal Server Error\"}").unwrap_err();
+        assert!(error.to_string().contains("WAVデータではありません"));
+        assert!(decode(b"RIFF").is_err());
+    }
+
+    #[test]
+    fn parses_bit_depth() {
+        for depth in [BitDepth::Int16, BitDepth::Int24, BitDepth::Float32] {
+            assert_eq!(depth.to_string().parse::<BitDepth>().unwrap(), depth);
+        }
+        for bad in ["8", "32", "24f", ""] {
+            assert!(bad.parse::<BitDepth>().is_err(), "{}", bad);
+        }
+    }
+}
@@ -0,0 +1,141 @@
+//! マイクとスピーカーの代わりにWAVファイルと共有のバッファを使い、
+//! モックサーバーを相手にリアルタイムパイプラインを入力の終わりまで動かす
+
+use std::net::{SocketAddr, TcpListener};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use makebeliv::backend::CaptureBackend;
+use makebeliv::client::{Codec, Transport, VoiceConversionClient};
+use makebeliv::converter::{Engine, PipelineConfig};
+use makebeliv::dsp::DspChain;
+use makebeliv::mock_server::{self, MockOptions};
+use makebeliv::notify::Notifier;
+use makebeliv::summary::SessionSummary;
+use makebeliv::virtual_audio::{VIRTUAL_OUTPUT_CHANNELS, VIRTUAL_OUTPUT_SAMPLE_RATE};
+use makebeliv::wav::{self, BitDepth};
+use makebeliv::RealtimePipeline;
+
+const RATE: u32 = 16_000;
+const CHUNK_MS: u32 = 200;
+/// 入力の長さ（チャンク長のちょうど5倍）
+const INPUT_MS: u32 = 1000;
+const AMPLITUDE: f32 = 0.3;
+
+/// 220Hzの正弦波のWAVを書く
+fn write_fixture(path: &Path) {
+    let frames = (RATE * INPUT_MS / 1000) as usize;
+    let samples: Vec<f32> = (0..frames)
+        .map(|i| AMPLITUDE * (std::f32::consts::TAU * 220.0 * i as f32 / RATE as f32).sin())
+        .collect();
+    wav::write_file(path, &samples, RATE, 1, BitDepth::Float32).unwrap();
+}
+
+/// 空いているポートでモックサーバーを起動し、応答するまで待つ
+async fn start_mock_server(options: MockOptions) -> String {
+    let addr: SocketAddr = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap();
+    tokio::spawn(mock_server::serve(addr, options));
+    let url = format!("http://{}", addr);
+    let client = VoiceConversionClient::new(url.clone()).unwrap();
+    for _ in 0..100 {
+        if client.check_status().await.is_ok() {
+            return url;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    panic!("モックサーバーが起動しません: {}", url);
+}
+
+/// フィクスチャを入力にして変換し、セッションの概要と再生した音声を返す
+async fn run_pipeline(options: MockOptions) -> (SessionSummary, Vec<f32>) {
+    let url = start_mock_server(options).await;
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("input.wav");
+    write_fixture(&input);
+
+    let backend = CaptureBackend::new(&input);
+    let played = backend.played();
+    let config = PipelineConfig {
+        model: "default".to_string(),
+        pitch_shift: 0,
+        chunk_ms: CHUNK_MS,
+        session_id: "monitor-pipeline-test".to_string(),
+        transport: Transport::Http,
+        codec: Codec::Pcm,
+        engine: Engine::Server,
+    };
+    let pipeline = RealtimePipeline::new(
+        VoiceConversionClient::new(url).unwrap(),
+        config,
+        DspChain::new(),
+    )
+    .with_backend(Arc::new(backend))
+    .with_notifier(Notifier::new(false));
+
+    // 入力の終わりで止まる
+    let summary = tokio::time::timeout(
+        Duration::from_secs(30),
+        pipeline.run(std::future::pending()),
+    )
+    .await
+    .expect("入力の終わりで止まるはず")
+    .unwrap();
+    let played = played.lock().unwrap().clone();
+    (summary, played)
+}
+
+fn peak(samples: &[f32]) -> f32 {
+    samples.iter().fold(0.0f32, |peak, s| peak.max(s.abs()))
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn plays_the_converted_input_through_the_mock_server() {
+    let (summary, played) = run_pipeline(MockOptions::default()).await;
+
+    assert_eq!(summary.chunks_converted, (INPUT_MS / CHUNK_MS) as u64);
+    assert_eq!(summary.chunks_failed, 0);
+    assert_eq!(summary.chunks_skipped, 0);
+    assert_eq!(summary.bytes_sent, summary.bytes_received);
+
+    // 出力の形式（48kHzステレオ）で、入力の長さ以上を再生している
+    let channels = VIRTUAL_OUTPUT_CHANNELS as usize;
+    assert_eq!(played.len() % channels, 0);
+    let input_frames = (VIRTUAL_OUTPUT_SAMPLE_RATE * INPUT_MS / 1000) as usize;
+    assert!(
+        played.len() >= input_frames * channels,
+        "{}サンプルしか再生していない",
+        played.len()
+    );
+    // モックサーバーは受け取った音声をそのまま返す
+    let peak = peak(&played);
+    assert!(
+        (AMPLITUDE * 0.8..=AMPLITUDE * 1.2).contains(&peak),
+        "peak {}",
+        peak
+    );
+    let audible = played.iter().filter(|s| s.abs() > 0.01).count();
+    assert!(
+        audible > input_frames,
+        "{}サンプルしか音が出ていない",
+        audible
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn failed_chunks_are_counted_and_not_played() {
+    let (summary, played) = run_pipeline(MockOptions {
+        error_rate: 1.0,
+        ..MockOptions::default()
+    })
+    .await;
+
+    assert_eq!(summary.chunks_converted, 0);
+    assert!(summary.chunks_failed > 0);
+    // 既定のフォールバックは無音（変換していない地声を出さない）
+    assert!(!played.is_empty());
+    assert_eq!(peak(&played), 0.0);
+}